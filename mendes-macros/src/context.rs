@@ -0,0 +1,62 @@
+use proc_macro2::{Span, TokenStream};
+use quote::quote;
+use syn::parse_quote;
+
+pub fn from_context(ast: syn::DeriveInput) -> TokenStream {
+    let fields = match &ast.data {
+        syn::Data::Struct(data) => &data.fields,
+        _ => panic!("FromContext can only be derived for structs"),
+    };
+
+    let mut lifetimes = ast.generics.lifetimes();
+    let lifetime = match (lifetimes.next(), lifetimes.next()) {
+        (Some(def), None) => def.lifetime.clone(),
+        (None, _) => syn::Lifetime::new("'a", Span::call_site()),
+        (Some(_), Some(_)) => panic!("FromContext derive supports at most one lifetime parameter"),
+    };
+
+    let mut generics = ast.generics.clone();
+    if generics.lifetimes().next().is_none() {
+        generics.params.insert(0, parse_quote!(#lifetime));
+    }
+    generics.params.push(parse_quote!(__A: mendes::Application));
+
+    let where_clause = generics.make_where_clause();
+    for field in fields {
+        let ty = &field.ty;
+        where_clause
+            .predicates
+            .push(parse_quote!(#ty: mendes::FromContext<#lifetime, __A>));
+    }
+
+    let extract = fields.iter().map(|field| {
+        let ty = &field.ty;
+        quote!(<#ty as mendes::FromContext<#lifetime, __A>>::from_context(app, req, state, body)?)
+    });
+
+    let construct = match fields {
+        syn::Fields::Named(_) => {
+            let names = fields.iter().map(|field| &field.ident);
+            quote!(Self { #(#names: #extract,)* })
+        }
+        syn::Fields::Unnamed(_) => quote!(Self(#(#extract,)*)),
+        syn::Fields::Unit => quote!(Self),
+    };
+
+    let (impl_generics, _, where_clause) = generics.split_for_impl();
+    let (_, ty_generics, _) = ast.generics.split_for_impl();
+    let ident = &ast.ident;
+    quote!(
+        impl #impl_generics mendes::FromContext<#lifetime, __A> for #ident #ty_generics #where_clause {
+            #[allow(unused_variables)]
+            fn from_context(
+                app: &#lifetime std::sync::Arc<__A>,
+                req: &#lifetime mendes::http::request::Parts,
+                state: &mut mendes::application::PathState,
+                body: &mut Option<__A::RequestBody>,
+            ) -> Result<Self, __A::Error> {
+                Ok(#construct)
+            }
+        }
+    )
+}
@@ -0,0 +1,63 @@
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use proc_macro2::TokenStream;
+use quote::quote;
+
+pub fn embed_dir(lit: syn::LitStr) -> TokenStream {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").unwrap_or_default();
+    let root = Path::new(&manifest_dir).join(lit.value());
+
+    let mut files = Vec::new();
+    if let Err(e) = visit(&root, &root, &mut files) {
+        let msg = format!("unable to read {}: {e}", root.display());
+        return syn::Error::new(lit.span(), msg).to_compile_error();
+    }
+    files.sort();
+
+    let files = files.into_iter().map(|(name, path, etag)| {
+        let path = path.to_string_lossy();
+        quote!(mendes::embedded::EmbeddedFile {
+            path: #name,
+            contents: include_bytes!(#path),
+            etag: #etag,
+        })
+    });
+
+    quote!(mendes::embedded::Embedded::new(&[#(#files,)*]))
+}
+
+fn visit(
+    root: &Path,
+    dir: &Path,
+    files: &mut Vec<(String, PathBuf, String)>,
+) -> std::io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            visit(root, &path, files)?;
+            continue;
+        }
+
+        let name = match path.strip_prefix(root).ok().and_then(|p| p.to_str()) {
+            Some(name) => name.replace(std::path::MAIN_SEPARATOR, "/"),
+            None => continue,
+        };
+
+        let etag = format!("\"{:016x}\"", fnv1a(&fs::read(&path)?));
+        files.push((name, path, etag));
+    }
+
+    Ok(())
+}
+
+/// 64-bit FNV-1a, which is stable across builds (unlike the standard library's hashers)
+fn fnv1a(data: &[u8]) -> u64 {
+    let mut hash = 0xcbf29ce484222325u64;
+    for &byte in data {
+        hash ^= u64::from(byte);
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
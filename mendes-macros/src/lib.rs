@@ -4,7 +4,9 @@ use proc_macro::TokenStream;
 use quote::{quote, ToTokens};
 use syn::parse_macro_input;
 
+mod context;
 mod cookies;
+mod embed;
 mod forms;
 mod route;
 
@@ -44,6 +46,60 @@ pub fn form(meta: TokenStream, item: TokenStream) -> TokenStream {
 /// }
 /// ```
 ///
+/// Handlers that consume a request body can restrict the accepted content types with one or
+/// more `accepts` arguments. Requests with any other `Content-Type` are rejected with a
+/// `415 Unsupported Media Type` error before any arguments are extracted. A subtype of `*`
+/// accepts any subtype of the given type.
+///
+/// ```no_run
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::{Response, StatusCode};
+/// # use mendes::{handler, Application, Body, Error};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(POST, accepts = "application/json")]
+/// async fn create(_: &App, _: &Parts, body: Body) -> Result<Response<Body>, Error> {
+///     Ok(Response::builder()
+///         .status(StatusCode::OK)
+///         .body(body)
+///         .unwrap())
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Fixed response metadata can be declared with a `status` argument and any number of
+/// `header` arguments. These are applied to the `Response` returned by a successful call, so
+/// the function must return `Result<Response<_>, _>`; any status or header with the same name
+/// set by the function body is overwritten.
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Application, Body, Error};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(POST, status = 201, header("Cache-Control", "no-store"))]
+/// async fn create(_: &App, body: Body) -> Result<Response<Body>, Error> {
+///     Ok(Response::new(body))
+/// }
+/// # fn main() {}
+/// ```
+///
 /// The first argument of the function must be a reference to an implementer of
 /// the `Application` trait (the implementor may also be wrapped in an `Arc`).
 /// All unannotated arguments must be of types that implement the `FromContext`
@@ -59,12 +115,26 @@ pub fn form(meta: TokenStream, item: TokenStream) -> TokenStream {
 /// * If the `hyper` feature is enabled, `hyper::body::Body`
 ///   (only if `Application::RequestBody` is also `Body`)
 ///
-/// Each of these types can be wrapped in `Option` for optional path components.
+/// Any argument type can be wrapped in `Option` to get `None` instead of an error response
+/// if extraction fails (for example, for optional path components), or in
+/// `Result<_, App::Error>` to handle the error in the handler. A path component that is
+/// present but can't be parsed is still rejected when wrapped in `Option`, so that later
+/// arguments don't silently receive the wrong components.
 /// Additionally, there are two attributes that may be used on handler arguments:
 ///
 /// * `#[rest]`: a `&str` representing the part of the request path not yet consumed by routing
 /// * `#[query]`: a type that implements `Deserialize`, and will be used to deserialize the URI query
 ///
+/// Arguments with these attributes can also be wrapped in `Option` or `Result`. Arguments of
+/// types that implement `FromContextAsync` rather than `FromContext` must be annotated with
+/// `#[async_extract]`.
+///
+/// Arguments are extracted in the order they are declared, which is also the order in which
+/// they consume path components. Declare arguments that read the body last, so that requests
+/// rejected by other extractors (for example, for authentication) are answered before the
+/// body is read; with the `hyper` feature, clients sending `Expect: 100-continue` then receive
+/// the rejection without sending the body.
+///
 /// This macro will generate a module that contains a `call()` function mirroring
 /// the original function, and you may rely on this behavior (for example, for testing).
 ///
@@ -83,8 +153,8 @@ pub fn form(meta: TokenStream, item: TokenStream) -> TokenStream {
 #[proc_macro_attribute]
 pub fn handler(meta: TokenStream, item: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(item as syn::ItemFn);
-    let methods = parse_macro_input!(meta as route::HandlerMethods).methods;
-    route::handler(&methods, ast)
+    let meta = parse_macro_input!(meta as route::HandlerMeta);
+    route::handler(&meta, ast)
 }
 
 #[proc_macro_attribute]
@@ -93,6 +163,25 @@ pub fn scope(_: TokenStream, item: TokenStream) -> TokenStream {
     route::scope(ast)
 }
 
+/// Embed all files below a directory into the binary
+///
+/// The path is relative to the crate's manifest directory. This expands to a
+/// `mendes::embedded::Embedded` which can serve the files by their path relative to the
+/// directory:
+///
+/// ```no_run
+/// # use mendes::embedded::{embed_dir, Embedded};
+/// static SOURCES: Embedded = embed_dir!("src");
+/// ```
+///
+/// Changes to embedded files cause a rebuild, but files added to the directory are only
+/// picked up when the crate is rebuilt for another reason.
+#[proc_macro]
+pub fn embed_dir(item: TokenStream) -> TokenStream {
+    let lit = parse_macro_input!(item as syn::LitStr);
+    TokenStream::from(embed::embed_dir(lit))
+}
+
 #[proc_macro]
 pub fn route(item: TokenStream) -> TokenStream {
     let mut ast = parse_macro_input!(item as syn::ExprMatch);
@@ -100,6 +189,49 @@ pub fn route(item: TokenStream) -> TokenStream {
     quote!(#ast).into()
 }
 
+/// Implement `FromContext` for a struct whose fields all implement `FromContext`
+///
+/// This makes it easy to bundle extractors that are commonly used together into a single
+/// handler argument. Fields are extracted in the order in which they are declared, which
+/// matters for fields that consume path components.
+///
+/// ```no_run
+/// # use mendes::application::Extension;
+/// # use mendes::i18n::{AppWithCatalogs, Catalogs, Locale};
+/// # use mendes::{Application, Body, Error, FromContext};
+/// # #[derive(Clone)]
+/// # struct RequestId(u64);
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithCatalogs for App {
+/// #     fn catalogs(&self) -> &Catalogs {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[derive(FromContext)]
+/// struct RequestEnv<'a> {
+///     app: &'a App,
+///     locale: Locale<'a>,
+///     id: Extension<RequestId>,
+/// }
+/// ```
+///
+/// The struct may have at most one lifetime parameter, which is used as the lifetime of the
+/// request data borrowed by the fields.
+#[proc_macro_derive(FromContext)]
+pub fn derive_from_context(item: TokenStream) -> TokenStream {
+    let ast = parse_macro_input!(item as syn::DeriveInput);
+    TokenStream::from(context::from_context(ast))
+}
+
 #[proc_macro_derive(ToField, attributes(option))]
 pub fn derive_to_field(item: TokenStream) -> TokenStream {
     let ast = parse_macro_input!(item as syn::DeriveInput);
@@ -1,5 +1,3 @@
-use std::fmt::Display;
-
 use proc_macro::TokenStream;
 use proc_macro2::{Ident, Span};
 use quote::quote;
@@ -8,10 +6,7 @@ use syn::parse_quote;
 use syn::punctuated::Punctuated;
 use syn::token::Comma;
 
-pub fn handler<T>(methods: &[T], mut ast: syn::ItemFn) -> TokenStream
-where
-    T: Display,
-{
+pub fn handler(meta: &HandlerMeta, mut ast: syn::ItemFn) -> TokenStream {
     let app_type = match ast.sig.inputs.first() {
         Some(syn::FnArg::Typed(syn::PatType { ty, .. })) => match **ty {
             syn::Type::Reference(ref reffed) => (*reffed.elem).clone(),
@@ -40,7 +35,7 @@ where
     .unwrap_or(app_type);
 
     let mut method_patterns = proc_macro2::TokenStream::new();
-    for (i, method) in methods.iter().enumerate() {
+    for (i, method) in meta.methods.iter().enumerate() {
         let method = Ident::new(&method.to_string().to_ascii_uppercase(), Span::call_site());
         method_patterns.extend(if i > 0 {
             quote!( | &mendes::http::Method::#method)
@@ -61,31 +56,38 @@ where
         let mut special = false;
         let (pat, ty) = (&*typed.pat, &typed.ty);
         let name = match pat {
-            syn::Pat::Wild(_) => Ident::new(&format!("_{i}"), Span::call_site()),
             syn::Pat::Ident(pat) => pat.ident.clone(),
-            _ => panic!("only identifiers and wildcards allowed in handler argument list"),
+            syn::Pat::Wild(_) | syn::Pat::TupleStruct(_) | syn::Pat::Struct(_) => {
+                Ident::new(&format!("_{i}"), Span::call_site())
+            }
+            _ => panic!(
+                "only identifiers, wildcards and struct patterns allowed in handler argument list"
+            ),
         };
 
         typed.attrs.retain(|attr| {
             if attr.path().is_ident("rest") {
-                prefix.extend(quote!(
-                    let #pat = <mendes::application::Rest<#ty> as mendes::FromContext<#app_type>>::from_context(
-                        &cx.app, &cx.req, &mut cx.path, &mut cx.body,
-                    )?.0;
-                ));
+                let extract = wrapped(quote!(mendes::application::Rest), ty, &app_type);
+                prefix.extend(quote!(let #name = #extract;));
                 args.extend(quote!(#name,));
                 done = true;
                 special = true;
                 false
-            } else if attr.path().is_ident("query") {
+            } else if attr.path().is_ident("async_extract") {
                 prefix.extend(quote!(
-                    let #pat = <mendes::application::Query<#ty> as mendes::FromContext<#app_type>>::from_context(
+                    let #name = <#ty as mendes::FromContextAsync<#app_type>>::from_context(
                         &cx.app, &cx.req, &mut cx.path, &mut cx.body,
-                    )?.0;
+                    ).await?;
                 ));
                 args.extend(quote!(#name,));
                 special = true;
                 false
+            } else if attr.path().is_ident("query") {
+                let extract = wrapped(quote!(mendes::application::Query), ty, &app_type);
+                prefix.extend(quote!(let #name = #extract;));
+                args.extend(quote!(#name,));
+                special = true;
+                false
             } else {
                 true
             }
@@ -105,6 +107,44 @@ where
         args.extend(quote!(#name,));
     }
 
+    let accepts = match meta.accepts.is_empty() {
+        true => quote!(),
+        false => {
+            let accepts = &meta.accepts;
+            quote!(
+                mendes::application::check_content_type(&cx.req, &[#(#accepts),*])
+                    .map_err(|e| <#app_type as mendes::Application>::rejection(e, &cx.req))?;
+            )
+        }
+    };
+
+    let mut meta_apply = proc_macro2::TokenStream::new();
+    if let Some(status) = &meta.status {
+        meta_apply.extend(quote!(
+            *rsp.status_mut() = mendes::http::StatusCode::from_u16(#status).unwrap();
+        ));
+    }
+    for (name, value) in &meta.headers {
+        let name = name.value().to_ascii_lowercase();
+        meta_apply.extend(quote!(
+            rsp.headers_mut().insert(
+                mendes::http::header::HeaderName::from_static(#name),
+                mendes::http::HeaderValue::from_static(#value),
+            );
+        ));
+    }
+
+    let invoke = match meta_apply.is_empty() {
+        true => quote!(call(#args).await),
+        false => quote!(
+            let mut result = call(#args).await;
+            if let Ok(rsp) = &mut result {
+                #meta_apply
+            }
+            result
+        ),
+    };
+
     let name = ast.sig.ident.clone();
     let orig_vis = ast.vis.clone();
     ast.vis = nested_visibility(ast.vis);
@@ -118,12 +158,17 @@ where
             #nested_vis async fn handler #generics(
                 cx: &mut mendes::application::Context<#app_type>
             ) #rtype #where_clause {
+                cx.routed_to(module_path!());
                 match &cx.req.method {
                     #method_patterns => {}
-                    _ => return Err(mendes::Error::MethodNotAllowed.into()),
+                    _ => {
+                        let e = mendes::Error::from(mendes::application::ErrorKind::MethodNotAllowed);
+                        return Err(<#app_type as mendes::Application>::rejection(e, &cx.req).into());
+                    }
                 }
+                #accepts
                 #prefix
-                call(#args).await
+                #invoke
             }
         )
     };
@@ -254,27 +299,149 @@ pub fn route(ast: &mut syn::ExprMatch) {
 
         ast.arms.push(parse_quote!(
             _ => {
-                let e = ::mendes::Error::#variant;
-                ::mendes::application::IntoResponse::into_response(e, &*#cx.app, &cx.req)
+                let e = ::mendes::Error::from(::mendes::application::ErrorKind::#variant);
+                ::mendes::application::error_response(&*#cx.app, &cx.req, e)
             }
         ));
     }
 }
 
+/// Extract an argument through one of the wrapper types for annotated arguments
+///
+/// If the argument type is an `Option` or `Result`, the wrapper is applied to the inner type
+/// so that a failed extraction can be handled by the handler.
+fn wrapped(
+    wrapper: proc_macro2::TokenStream,
+    ty: &syn::Type,
+    app_type: &syn::Type,
+) -> proc_macro2::TokenStream {
+    let mut outer = ty.clone();
+    if let Some(inner) = fallible_inner(&mut outer) {
+        *inner = parse_quote!(#wrapper<#inner>);
+        return quote!(
+            <#outer as mendes::FromContext<#app_type>>::from_context(
+                &cx.app, &cx.req, &mut cx.path, &mut cx.body,
+            )?.map(|v| v.0)
+        );
+    }
+
+    quote!(
+        <#wrapper<#ty> as mendes::FromContext<#app_type>>::from_context(
+            &cx.app, &cx.req, &mut cx.path, &mut cx.body,
+        )?.0
+    )
+}
+
+fn fallible_inner(ty: &mut syn::Type) -> Option<&mut syn::Type> {
+    let segment = match ty {
+        syn::Type::Path(syn::TypePath { qself: None, path }) => path.segments.last_mut()?,
+        _ => return None,
+    };
+
+    if segment.ident != "Option" && segment.ident != "Result" {
+        return None;
+    }
+
+    match &mut segment.arguments {
+        syn::PathArguments::AngleBracketed(args) => match args.args.first_mut() {
+            Some(syn::GenericArgument::Type(inner)) => Some(inner),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
 enum RouteType {
     Path,
     Method,
 }
 
-pub struct HandlerMethods {
+pub struct HandlerMeta {
     pub methods: Vec<syn::Ident>,
+    pub accepts: Vec<syn::LitStr>,
+    pub status: Option<syn::LitInt>,
+    pub headers: Vec<(syn::LitStr, syn::LitStr)>,
 }
 
-impl Parse for HandlerMethods {
+impl Parse for HandlerMeta {
     fn parse(input: ParseStream) -> syn::Result<Self> {
-        let methods = Punctuated::<syn::Ident, Comma>::parse_terminated(input)?;
-        Ok(Self {
-            methods: methods.into_iter().collect(),
-        })
+        let mut meta = Self {
+            methods: Vec::new(),
+            accepts: Vec::new(),
+            status: None,
+            headers: Vec::new(),
+        };
+
+        for item in Punctuated::<HandlerMetaItem, Comma>::parse_terminated(input)? {
+            match item {
+                HandlerMetaItem::Method(method) => meta.methods.push(method),
+                HandlerMetaItem::Accepts(accepts) => meta.accepts.push(accepts),
+                HandlerMetaItem::Status(status) => match meta.status {
+                    Some(_) => return Err(syn::Error::new(status.span(), "duplicate status")),
+                    None => meta.status = Some(status),
+                },
+                HandlerMetaItem::Header(name, value) => meta.headers.push((name, value)),
+            }
+        }
+
+        Ok(meta)
+    }
+}
+
+enum HandlerMetaItem {
+    Method(syn::Ident),
+    Accepts(syn::LitStr),
+    Status(syn::LitInt),
+    Header(syn::LitStr, syn::LitStr),
+}
+
+impl Parse for HandlerMetaItem {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let ident = input.parse::<syn::Ident>()?;
+        if input.peek(syn::token::Paren) {
+            if ident != "header" {
+                return Err(syn::Error::new(ident.span(), "unknown handler argument"));
+            }
+
+            let content;
+            syn::parenthesized!(content in input);
+            let name = content.parse::<syn::LitStr>()?;
+            content.parse::<Comma>()?;
+            let value = content.parse::<syn::LitStr>()?;
+            content.parse::<Option<Comma>>()?;
+
+            let valid_name = name
+                .value()
+                .bytes()
+                .all(|b| b.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&b));
+            if name.value().is_empty() || !valid_name {
+                return Err(syn::Error::new(name.span(), "invalid header name"));
+            }
+            if !value
+                .value()
+                .bytes()
+                .all(|b| b == b'\t' || (b' '..=b'~').contains(&b))
+            {
+                return Err(syn::Error::new(value.span(), "invalid header value"));
+            }
+            return Ok(Self::Header(name, value));
+        }
+
+        if !input.peek(syn::Token![=]) {
+            return Ok(Self::Method(ident));
+        }
+
+        input.parse::<syn::Token![=]>()?;
+        match ident.to_string().as_str() {
+            "accepts" => Ok(Self::Accepts(input.parse()?)),
+            "status" => {
+                let status = input.parse::<syn::LitInt>()?;
+                match status.base10_parse::<u16>() {
+                    Ok(100..=999) => Ok(Self::Status(status)),
+                    _ => Err(syn::Error::new(status.span(), "invalid status code")),
+                }
+            }
+            _ => Err(syn::Error::new(ident.span(), "unknown handler argument")),
+        }
     }
 }
@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use data_encoding::HEXLOWER;
+use http::header::{HeaderName, AUTHORIZATION};
+use http::request::Parts;
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::application::{Application, Error, ErrorKind, FromContextAsync, PathState};
+use crate::utils::constant_time_eq;
+
+/// Give the `ApiKey` extractor access to the application's API keys
+pub trait AppWithApiKeys: Application {
+    type Store: ApiKeyStore;
+
+    fn api_keys(&self) -> &ApiKeys<Self::Store>;
+}
+
+/// Mints, verifies and revokes API keys
+///
+/// Keys look like `{prefix}_{id}_{secret}`. Only a SHA-256 hash of the secret is stored, so
+/// the full key is only available when it is minted. The prefix makes keys easy to recognize
+/// (for example by secret scanners) and the id is used to look up the stored key.
+pub struct ApiKeys<S> {
+    store: S,
+    prefix: Cow<'static, str>,
+}
+
+impl<S: ApiKeyStore> ApiKeys<S> {
+    pub fn new(store: S, prefix: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            store,
+            prefix: prefix.into(),
+        }
+    }
+
+    /// Mint a new key with the given `name` and `scopes`
+    ///
+    /// Returns the full key, to be shown to the user once, and the stored key.
+    pub async fn mint(&self, name: impl Into<String>, scopes: Vec<String>) -> (String, StoredKey) {
+        let mut id = [0; ID_LEN];
+        let mut secret = [0; SECRET_LEN];
+        let rng = SystemRandom::new();
+        rng.fill(&mut id)
+            .and_then(|()| rng.fill(&mut secret))
+            .expect("failed to generate random API key");
+
+        let (id, secret) = (HEXLOWER.encode(&id), HEXLOWER.encode(&secret));
+        let token = format!("{}_{id}_{secret}", self.prefix);
+        let key = StoredKey {
+            id,
+            name: name.into(),
+            hash: hash(&secret),
+            scopes,
+            created: SystemTime::now(),
+            revoked: false,
+        };
+
+        self.store.insert(key.clone()).await;
+        (token, key)
+    }
+
+    /// Revoke the key with the given `id`, returning whether it was found
+    pub async fn revoke(&self, id: &str) -> bool {
+        self.store.revoke(id).await
+    }
+
+    /// Look up the stored key for the full `key`, if it is valid and not revoked
+    pub async fn verify(&self, key: &str) -> Option<StoredKey> {
+        let (rest, secret) = key.rsplit_once('_')?;
+        let (prefix, id) = rest.rsplit_once('_')?;
+        if prefix != self.prefix || id.len() != ID_LEN * 2 {
+            return None;
+        }
+
+        let stored = self.store.get(id).await?;
+        match !stored.revoked && constant_time_eq(&stored.hash, &hash(secret)) {
+            true => Some(stored),
+            false => None,
+        }
+    }
+}
+
+/// Extracts and verifies the API key for a request
+///
+/// Looks for the key in the `X-Api-Key` header, or in an `Authorization` header using the
+/// `Bearer` scheme. Requests without a key are rejected with an `ApiKeyMissing` error, requests
+/// with an unknown or revoked key with an `ApiKeyInvalid` error. This extractor is
+/// asynchronous, so handler arguments must be annotated with `#[async_extract]`:
+///
+/// ```no_run
+/// # use mendes::apikeys::{ApiKey, ApiKeys, AppWithApiKeys, MemoryStore};
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # struct App {
+/// #     keys: ApiKeys<MemoryStore>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithApiKeys for App {
+/// #     type Store = MemoryStore;
+/// #     fn api_keys(&self) -> &ApiKeys<MemoryStore> {
+/// #         &self.keys
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn reports(app: &App, #[async_extract] key: ApiKey) -> Result<Response<Body>, Error> {
+///     key.require("reports:read")?;
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug)]
+pub struct ApiKey(pub StoredKey);
+
+impl ApiKey {
+    /// Whether the key was granted `scope`
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.0.scopes.iter().any(|s| s == scope)
+    }
+
+    /// Fail with a `403 Forbidden` error if the key wasn't granted `scope`
+    pub fn require(&self, scope: &str) -> Result<(), Error> {
+        match self.has_scope(scope) {
+            true => Ok(()),
+            false => Err(Error::forbidden(format!("API key lacks scope {scope:?}"))),
+        }
+    }
+}
+
+#[async_trait]
+impl<'a, A> FromContextAsync<'a, A> for ApiKey
+where
+    A: AppWithApiKeys + Sync,
+{
+    async fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let key = match request_key(req) {
+            Some(key) => key,
+            None => return Err(state.reject::<A>(ErrorKind::ApiKeyMissing.into(), req)),
+        };
+
+        match app.api_keys().verify(key).await {
+            Some(stored) => Ok(ApiKey(stored)),
+            None => Err(state.reject::<A>(ErrorKind::ApiKeyInvalid.into(), req)),
+        }
+    }
+}
+
+fn request_key(req: &Parts) -> Option<&str> {
+    if let Some(value) = req.headers.get(X_API_KEY) {
+        return value.to_str().ok().map(str::trim);
+    }
+
+    let value = req.headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, key) = value.split_once(' ')?;
+    match scheme.eq_ignore_ascii_case("bearer") {
+        true => Some(key.trim()),
+        false => None,
+    }
+}
+
+/// An API key as kept in an `ApiKeyStore`
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct StoredKey {
+    pub id: String,
+    pub name: String,
+    /// SHA-256 hash of the secret part of the key
+    pub hash: [u8; 32],
+    pub scopes: Vec<String>,
+    pub created: SystemTime,
+    pub revoked: bool,
+}
+
+/// Storage for API keys, like a database table
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync {
+    async fn get(&self, id: &str) -> Option<StoredKey>;
+
+    async fn insert(&self, key: StoredKey);
+
+    /// Mark the key with the given `id` as revoked, returning whether it was found
+    async fn revoke(&self, id: &str) -> bool;
+}
+
+/// An `ApiKeyStore` keeping keys in memory
+///
+/// Keys are lost when the process exits, so this is mostly useful for testing.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    keys: Mutex<HashMap<String, StoredKey>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for MemoryStore {
+    async fn get(&self, id: &str) -> Option<StoredKey> {
+        self.keys.lock().unwrap().get(id).cloned()
+    }
+
+    async fn insert(&self, key: StoredKey) {
+        self.keys.lock().unwrap().insert(key.id.clone(), key);
+    }
+
+    async fn revoke(&self, id: &str) -> bool {
+        match self.keys.lock().unwrap().get_mut(id) {
+            Some(key) => {
+                key.revoked = true;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+fn hash(secret: &str) -> [u8; 32] {
+    let mut hash = [0; 32];
+    hash.copy_from_slice(digest(&SHA256, secret.as_bytes()).as_ref());
+    hash
+}
+
+const ID_LEN: usize = 8;
+const SECRET_LEN: usize = 24;
+const X_API_KEY: HeaderName = HeaderName::from_static("x-api-key");
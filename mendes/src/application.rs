@@ -1,21 +1,29 @@
+use std::backtrace::{Backtrace, BacktraceStatus};
 use std::borrow::Cow;
-#[cfg(feature = "body-util")]
 use std::error::Error as StdError;
+use std::fmt;
+use std::mem;
 use std::str::FromStr;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 #[cfg(feature = "body-util")]
 use bytes::Bytes;
-use http::header::LOCATION;
+use http::header::{CONTENT_TYPE, LOCATION};
 use http::request::Parts;
-use http::Request;
+use http::{Method, Request};
 use http::{Response, StatusCode};
 use http_body::Body as HttpBody;
 use percent_encoding::percent_decode_str;
-use thiserror::Error;
 
-pub use mendes_macros::{handler, route, scope};
+#[cfg(feature = "timing")]
+use crate::timing::ServerTiming;
+#[cfg(feature = "transfer")]
+use crate::transfer::{Progress, Transfer};
+#[cfg(feature = "transfer")]
+use crate::Body;
+
+pub use mendes_macros::{handler, route, scope, FromContext};
 
 /// Main interface for an application or service
 ///
@@ -34,18 +42,42 @@ pub trait Application: Send + Sized {
 
     async fn handle(cx: Context<Self>) -> Response<Self::ResponseBody>;
 
+    /// Convert an error from one of the built-in extractors into `Self::Error`
+    ///
+    /// All errors mendes produces while routing a request or extracting handler arguments
+    /// pass through here. Override this to customize the response for specific errors, for
+    /// example to describe where the request body failed to deserialize; the error's `kind()`
+    /// and `source()` provide the details.
+    fn rejection(error: Error, _: &Parts) -> Self::Error {
+        Self::Error::from(error)
+    }
+
+    /// Whether to render server errors as a debug page
+    ///
+    /// With the `debug` feature, `Error` renders 5xx responses in debug mode as an HTML page
+    /// with the error chain, the handler the request was routed to, its headers and a
+    /// backtrace (if enabled through `RUST_BACKTRACE`), instead of the public message. Use
+    /// `debug::error_page()` to do the same for other error types. This exposes internal
+    /// details, so only enable it during development.
+    fn debug(&self) -> bool {
+        false
+    }
+
     fn from_query<'a, T: serde::Deserialize<'a>>(req: &'a Parts) -> Result<T, Self::Error> {
-        let query = req.uri.query().ok_or(Error::QueryMissing)?;
-        let data =
-            serde_urlencoded::from_bytes::<T>(query.as_bytes()).map_err(Error::QueryDecode)?;
-        Ok(data)
+        let query = match req.uri.query() {
+            Some(query) => query,
+            None => return Err(Self::rejection(ErrorKind::QueryMissing.into(), req)),
+        };
+
+        serde_urlencoded::from_bytes::<T>(query.as_bytes())
+            .map_err(|e| Self::rejection(Error::caused_by(ErrorKind::QueryDecode, e), req))
     }
 
     fn from_body_bytes<'de, T: serde::de::Deserialize<'de>>(
         req: &Parts,
         bytes: &'de [u8],
-    ) -> Result<T, Error> {
-        from_bytes::<T>(req, bytes)
+    ) -> Result<T, Self::Error> {
+        from_bytes::<T>(req, bytes).map_err(|e| Self::rejection(e, req))
     }
 
     #[cfg(feature = "body-util")]
@@ -54,45 +86,43 @@ pub trait Application: Send + Sized {
         req: &Parts,
         body: Self::RequestBody,
         max_len: usize,
-    ) -> Result<T, Error>
+    ) -> Result<T, Self::Error>
     where
         Self::RequestBody: HttpBody + Send,
         <Self::RequestBody as HttpBody>::Data: Send,
         <Self::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Sync + Send>>,
     {
         // Check if the Content-Length header suggests the body is larger than our max len
-        // to avoid allocation if we drop the request in any case.
+        // to avoid allocation if we drop the request in any case. This happens before the
+        // body is polled, so clients sending `Expect: 100-continue` don't send the body.
         let expected_len = match body.size_hint().upper() {
             Some(length) => length,
             None => body.size_hint().lower(),
         };
 
         if expected_len > max_len as u64 {
-            return Err(Error::BodyTooLarge);
+            return Err(Self::rejection(ErrorKind::BodyTooLarge.into(), req));
         }
 
-        from_body::<Self::RequestBody, T>(req, body, max_len).await
+        from_body::<Self::RequestBody, T>(req, body, max_len)
+            .await
+            .map_err(|e| Self::rejection(e, req))
     }
 
     #[cfg(feature = "body-util")]
     #[cfg_attr(docsrs, doc(cfg(feature = "body-util")))]
-    async fn body_bytes<B: HttpBody + Send>(body: B, max_len: usize) -> Result<Bytes, Error>
+    async fn body_bytes<B: HttpBody + Send>(
+        req: &Parts,
+        body: B,
+        max_len: usize,
+    ) -> Result<Bytes, Self::Error>
     where
         B::Data: Send,
         B::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
     {
-        // Check if the Content-Length header suggests the body is larger than our max len
-        // to avoid allocation if we drop the request in any case.
-        let expected_len = match body.size_hint().upper() {
-            Some(length) => length,
-            None => body.size_hint().lower(),
-        };
-
-        if expected_len > max_len as u64 {
-            return Err(Error::BodyTooLarge);
-        }
-
-        Ok(to_bytes(body, max_len).await?)
+        read_body(body, max_len)
+            .await
+            .map_err(|e| Self::rejection(e, req))
     }
 
     fn redirect(status: StatusCode, path: impl AsRef<str>) -> Response<Self::ResponseBody>
@@ -130,12 +160,64 @@ impl<A: Application, T: IntoResponse<A>, E: IntoResponse<A>> IntoResponse<A> for
     }
 }
 
-impl<A: Application> IntoResponse<A> for Error {
-    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
-        A::Error::from(self).into_response(app, req)
+impl<A: Application> IntoResponse<A> for Error
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(mut self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        // Let the application's error type render the error, unless that is `Error` itself
+        // (or a type that hands the error back to us)
+        if !self.converted {
+            self.converted = true;
+            return A::Error::from(self).into_response(app, req);
+        }
+
+        #[cfg(feature = "debug")]
+        if self.status.is_server_error() && app.debug() {
+            return crate::debug::error_page(self.status, &self, self.backtrace(), req);
+        }
+
+        Response::builder()
+            .status(self.status)
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(String::from(self.message).into())
+            .unwrap()
+    }
+}
+
+// This should only be used by procedural routing macros.
+#[doc(hidden)]
+pub fn check_content_type(req: &Parts, accepts: &[&str]) -> Result<(), Error> {
+    let media_type = match req.headers.get(CONTENT_TYPE).map(|v| v.to_str()) {
+        Some(Ok(value)) => value.split(';').next().unwrap_or_default().trim(),
+        _ => return Err(ErrorKind::BodyUnknownType.into()),
+    };
+
+    let accepted = accepts
+        .iter()
+        .any(|accept| match accept.strip_suffix("/*") {
+            Some(prefix) => media_type
+                .split_once('/')
+                .is_some_and(|(ty, _)| ty.eq_ignore_ascii_case(prefix)),
+            None => accept.eq_ignore_ascii_case(media_type),
+        });
+
+    match accepted {
+        true => Ok(()),
+        false => Err(Error::with_detail(ErrorKind::BodyUnknownType, media_type)),
     }
 }
 
+// This should only be used by procedural routing macros.
+#[doc(hidden)]
+pub fn error_response<A: Application>(
+    app: &A,
+    req: &Parts,
+    error: Error,
+) -> Response<A::ResponseBody> {
+    A::rejection(error, req).into_response(app, req)
+}
+
 /// Maintains state during the routing of requests to the selected handler
 ///
 /// The `Context` is created by the `Server` (or similar code) from a `Request` and
@@ -177,6 +259,17 @@ where
         }
     }
 
+    // This should only be used by procedural routing macros.
+    #[doc(hidden)]
+    pub fn routed_to(&mut self, #[allow(unused_variables)] handler: &'static str) {
+        #[cfg(feature = "debug")]
+        if self.app.debug() {
+            let path = self.path.matched(self.req.uri.path()).to_owned();
+            let route = crate::debug::Route { handler, path };
+            self.req.extensions.insert(route);
+        }
+    }
+
     // This should only be used by procedural routing macros.
     #[doc(hidden)]
     pub fn path(&mut self) -> Option<Cow<'_, str>> {
@@ -218,6 +311,143 @@ where
     pub fn headers(&self) -> &http::HeaderMap {
         &self.req.headers
     }
+
+    /// Apply a method override to a `POST` request
+    ///
+    /// HTML forms can only submit `GET` and `POST` requests. Calling this before routing lets a
+    /// `POST` request reach a `PUT`, `PATCH` or `DELETE` handler instead, as selected by the
+    /// `X-HTTP-Method-Override` header or a `_method` query parameter (as in
+    /// `<form method="post" action="/posts/1?_method=DELETE">`). Other methods can't be
+    /// selected this way.
+    pub fn override_method(&mut self) {
+        if self.req.method != Method::POST {
+            return;
+        }
+
+        let header = self
+            .req
+            .headers
+            .get("x-http-method-override")
+            .and_then(|v| v.to_str().ok());
+        let value = header.or_else(|| {
+            let query = self.req.uri.query()?;
+            form_value(query.as_bytes(), b"_method").and_then(|v| std::str::from_utf8(v).ok())
+        });
+
+        if let Some(method) = value.and_then(override_target) {
+            self.req.method = method;
+        }
+    }
+
+    /// Apply a method override to a `POST` request, including from a `_method` form field
+    ///
+    /// Like `override_method()`, but also looks for a `_method` field in URL-encoded form
+    /// bodies. The body is buffered (up to `max_len` bytes) to do so, and put back for the
+    /// handler to consume.
+    #[cfg(feature = "body-util")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "body-util")))]
+    pub async fn override_method_from_form(&mut self, max_len: usize) -> Result<(), Error>
+    where
+        A::RequestBody: HttpBody + From<Bytes>,
+        <A::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Send + Sync + 'static>>,
+    {
+        self.override_method();
+        if self.req.method != Method::POST {
+            return Ok(());
+        }
+
+        let is_form = match self.req.headers.get(CONTENT_TYPE) {
+            Some(value) => value
+                .as_bytes()
+                .starts_with(b"application/x-www-form-urlencoded"),
+            None => false,
+        };
+
+        let body = match (is_form, self.body.take()) {
+            (true, Some(body)) => body,
+            (_, body) => {
+                self.body = body;
+                return Ok(());
+            }
+        };
+
+        let bytes = to_bytes(body, max_len).await?;
+        let method = form_value(&bytes, b"_method")
+            .and_then(|v| std::str::from_utf8(v).ok())
+            .and_then(override_target);
+        if let Some(method) = method {
+            self.req.method = method;
+        }
+
+        self.body = Some(A::RequestBody::from(bytes));
+        Ok(())
+    }
+
+    /// Enable `Server-Timing` collection for this request
+    ///
+    /// Returns a handle to the request's `ServerTiming`, which handlers can also extract to
+    /// record their own metrics. Calling this again returns a handle to the same collection.
+    #[cfg(feature = "timing")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "timing")))]
+    pub fn server_timing(&mut self) -> ServerTiming {
+        if let Some(timing) = self.req.extensions.get::<ServerTiming>() {
+            return timing.clone();
+        }
+
+        let timing = ServerTiming::new();
+        self.req.extensions.insert(timing.clone());
+        timing
+    }
+}
+
+#[cfg(feature = "transfer")]
+#[cfg_attr(docsrs, doc(cfg(feature = "transfer")))]
+impl<A: Application<RequestBody = Body>> Context<A> {
+    /// Enable byte accounting for this request
+    ///
+    /// Wraps the request body to count the bytes read from it and returns a handle to the
+    /// request's `Transfer`, which handlers can also extract. Calling this again returns a
+    /// handle to the same `Transfer` without wrapping the body again.
+    pub fn transfer(&mut self) -> Transfer {
+        if let Some(transfer) = self.req.extensions.get::<Transfer>() {
+            return transfer.clone();
+        }
+
+        let transfer = Transfer::new();
+        self.body = self
+            .body
+            .take()
+            .map(|body| Body::stream(transfer.count_read(body)));
+        self.req.extensions.insert(transfer.clone());
+        transfer
+    }
+
+    /// Report progress while the request body is read
+    ///
+    /// Wraps the request body so that `on_progress` is called with the number of bytes received
+    /// so far and the expected total, if known (see `transfer::Progress` for details).
+    pub fn upload_progress(&mut self, on_progress: impl FnMut(u64, Option<u64>) + Send + 'static) {
+        self.body = self
+            .body
+            .take()
+            .map(|body| Body::stream(Progress::new(body, on_progress)));
+    }
+}
+
+/// Find the value for `key` in URL-encoded form data
+///
+/// The value is not decoded, which is fine for the method override values we look for.
+fn form_value<'a>(data: &'a [u8], key: &[u8]) -> Option<&'a [u8]> {
+    data.split(|&b| b == b'&').find_map(|pair| {
+        let (name, value) = pair.split_at(pair.iter().position(|&b| b == b'=')?);
+        (name == key).then_some(&value[1..])
+    })
+}
+
+fn override_target(method: &str) -> Option<Method> {
+    [Method::PUT, Method::PATCH, Method::DELETE]
+        .into_iter()
+        .find(|m| m.as_str().eq_ignore_ascii_case(method))
 }
 
 impl<A: Application> AsMut<Context<A>> for Context<A> {
@@ -238,6 +468,105 @@ where
     ) -> Result<Self, A::Error>;
 }
 
+/// Asynchronous version of `FromContext`
+///
+/// Implement this for extractors that need to do I/O, like loading a session from a store.
+/// Handler arguments using an asynchronous extractor must be annotated with `#[async_extract]`:
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use async_trait::async_trait;
+/// # use mendes::application::PathState;
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::{Response, StatusCode};
+/// # use mendes::{handler, Application, Body, Error, FromContextAsync};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct User {
+/// #     name: String,
+/// # }
+/// # #[async_trait]
+/// # impl<'a> FromContextAsync<'a, App> for User {
+/// #     async fn from_context(
+/// #         _: &'a Arc<App>,
+/// #         _: &'a Parts,
+/// #         _: &mut PathState,
+/// #         _: &mut Option<Body>,
+/// #     ) -> Result<Self, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn profile(_: &App, #[async_extract] user: User) -> Result<Response<Body>, Error> {
+///     Ok(Response::builder()
+///         .status(StatusCode::OK)
+///         .body(user.name.into())
+///         .unwrap())
+/// }
+/// # fn main() {}
+/// ```
+///
+/// `Option<T>` and `Result<T, A::Error>` can be used to handle extraction failures in the
+/// handler, like for `FromContext`.
+#[async_trait]
+pub trait FromContextAsync<'a, A>: Sized
+where
+    A: Application,
+{
+    async fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error>;
+}
+
+#[async_trait]
+impl<'a, A, T> FromContextAsync<'a, A> for Option<T>
+where
+    A: Application + Sync,
+    T: FromContextAsync<'a, A>,
+{
+    async fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let (next, optional) = (state.next, mem::replace(&mut state.optional, true));
+        let result = T::from_context(app, req, state, body).await;
+        state.optional = optional;
+        state.optional_result::<A, _>(result, next, req)
+    }
+}
+
+#[async_trait]
+impl<'a, A, T> FromContextAsync<'a, A> for Result<T, A::Error>
+where
+    A: Application + Sync,
+    T: FromContextAsync<'a, A>,
+{
+    async fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let optional = mem::replace(&mut state.optional, false);
+        let result = T::from_context(app, req, state, body).await;
+        state.optional = optional;
+        Ok(result)
+    }
+}
+
 macro_rules! from_context_from_str {
     ($self:ty) => {
         impl<'a, A: Application> FromContext<'a, A> for $self {
@@ -247,27 +576,11 @@ macro_rules! from_context_from_str {
                 state: &mut PathState,
                 _: &mut Option<A::RequestBody>,
             ) -> Result<Self, A::Error> {
-                let s = state
-                    .next(req.uri.path())
-                    .ok_or(Error::PathComponentMissing.into())?;
-                <$self>::from_str(s).map_err(|_| Error::PathParse.into())
-            }
-        }
-
-        impl<'a, A: Application> FromContext<'a, A> for Option<$self> {
-            fn from_context(
-                _: &'a Arc<A>,
-                req: &'a Parts,
-                state: &mut PathState,
-                _: &mut Option<A::RequestBody>,
-            ) -> Result<Self, A::Error> {
-                match state.next(req.uri.path()) {
-                    Some(s) => match <$self>::from_str(s) {
-                        Ok(v) => Ok(Some(v)),
-                        Err(_) => Err(Error::PathParse.into()),
-                    },
-                    None => Ok(None),
-                }
+                let s = state.next(req.uri.path()).ok_or_else(|| {
+                    state.reject::<A>(ErrorKind::PathComponentMissing.into(), req)
+                })?;
+                <$self>::from_str(s)
+                    .map_err(|_| state.reject::<A>(ErrorKind::PathParse.into(), req))
             }
         }
     };
@@ -306,39 +619,53 @@ impl<'a, A: Application> FromContext<'a, A> for &'a http::request::Parts {
     }
 }
 
-impl<'a, A: Application> FromContext<'a, A> for Option<&'a [u8]> {
+/// Yields `None` if extracting `T` fails
+///
+/// For path components, this makes the component optional. A component that is present but
+/// cannot be parsed is still an error, so that it isn't skipped (which would shift the
+/// components for any later arguments); use `Result<T, A::Error>` to handle that case.
+/// Errors that yield `None` don't pass through `Application::rejection()`.
+impl<'a, A: Application, T: FromContext<'a, A>> FromContext<'a, A> for Option<T> {
     fn from_context(
-        _: &'a Arc<A>,
+        app: &'a Arc<A>,
         req: &'a Parts,
         state: &mut PathState,
-        _: &mut Option<A::RequestBody>,
+        body: &mut Option<A::RequestBody>,
     ) -> Result<Self, A::Error> {
-        Ok(state.next(req.uri.path()).map(|s| s.as_bytes()))
+        let (next, optional) = (state.next, mem::replace(&mut state.optional, true));
+        let result = T::from_context(app, req, state, body);
+        state.optional = optional;
+        state.optional_result::<A, _>(result, next, req)
     }
 }
 
-impl<'a, A: Application> FromContext<'a, A> for &'a [u8] {
+/// Yields the result of extracting `T`, so the handler can deal with the error
+impl<'a, A: Application, T: FromContext<'a, A>> FromContext<'a, A> for Result<T, A::Error> {
     fn from_context(
-        _: &'a Arc<A>,
+        app: &'a Arc<A>,
         req: &'a Parts,
         state: &mut PathState,
-        _: &mut Option<A::RequestBody>,
+        body: &mut Option<A::RequestBody>,
     ) -> Result<Self, A::Error> {
-        state
-            .next(req.uri.path())
-            .ok_or_else(|| Error::PathComponentMissing.into())
-            .map(|s| s.as_bytes())
+        // The handler gets the error, so it is not deferred by an enclosing `Option`
+        let optional = mem::replace(&mut state.optional, false);
+        let result = T::from_context(app, req, state, body);
+        state.optional = optional;
+        Ok(result)
     }
 }
 
-impl<'a, A: Application> FromContext<'a, A> for Option<Cow<'a, str>> {
+impl<'a, A: Application> FromContext<'a, A> for &'a [u8] {
     fn from_context(
         _: &'a Arc<A>,
         req: &'a Parts,
         state: &mut PathState,
         _: &mut Option<A::RequestBody>,
     ) -> Result<Self, A::Error> {
-        Ok(path_str(req, state)?)
+        state
+            .next(req.uri.path())
+            .ok_or_else(|| state.reject::<A>(ErrorKind::PathComponentMissing.into(), req))
+            .map(|s| s.as_bytes())
     }
 }
 
@@ -349,24 +676,13 @@ impl<'a, A: Application> FromContext<'a, A> for Cow<'a, str> {
         state: &mut PathState,
         _: &mut Option<A::RequestBody>,
     ) -> Result<Self, A::Error> {
-        match path_str(req, state)? {
+        match path_str(req, state).map_err(|e| state.reject::<A>(e, req))? {
             Some(s) => Ok(s),
-            None => Err(Error::PathComponentMissing.into()),
+            None => Err(state.reject::<A>(ErrorKind::PathComponentMissing.into(), req)),
         }
     }
 }
 
-impl<'a, A: Application> FromContext<'a, A> for Option<String> {
-    fn from_context(
-        _: &'a Arc<A>,
-        req: &'a Parts,
-        state: &mut PathState,
-        _: &mut Option<A::RequestBody>,
-    ) -> Result<Self, A::Error> {
-        Ok(path_str(req, state)?.map(|s| s.into_owned()))
-    }
-}
-
 impl<'a, A: Application> FromContext<'a, A> for String {
     fn from_context(
         _: &'a Arc<A>,
@@ -374,9 +690,9 @@ impl<'a, A: Application> FromContext<'a, A> for String {
         state: &mut PathState,
         _: &mut Option<A::RequestBody>,
     ) -> Result<Self, A::Error> {
-        match path_str(req, state)? {
+        match path_str(req, state).map_err(|e| state.reject::<A>(e, req))? {
             Some(s) => Ok(s.into_owned()),
-            None => Err(Error::PathComponentMissing.into()),
+            None => Err(state.reject::<A>(ErrorKind::PathComponentMissing.into(), req)),
         }
     }
 }
@@ -390,7 +706,7 @@ fn path_str<'a>(req: &'a Parts, state: &mut PathState) -> Result<Option<Cow<'a,
     percent_decode_str(s)
         .decode_utf8()
         .map(Some)
-        .map_err(|_| Error::PathDecode)
+        .map_err(|_| Error::from(ErrorKind::PathDecode))
 }
 
 from_context_from_str!(bool);
@@ -412,33 +728,54 @@ from_context_from_str!(usize);
 
 macro_rules! deserialize_body {
     ($req:ident, $bytes:ident) => {{
-        let content_type = $req.headers.get("content-type").ok_or(Error::BodyNoType)?;
+        let content_type = $req
+            .headers
+            .get("content-type")
+            .ok_or(Error::from(ErrorKind::BodyNoType))?;
         let ct_str = content_type.to_str().map_err(|_| {
-            Error::BodyUnknownType(String::from_utf8_lossy(content_type.as_bytes()).into_owned())
+            Error::with_detail(
+                ErrorKind::BodyUnknownType,
+                String::from_utf8_lossy(content_type.as_bytes()),
+            )
         })?;
 
         let mut parts = ct_str.splitn(2, ';');
         match parts.next().map(|s| s.trim()) {
-            Some("application/x-www-form-urlencoded") => {
-                serde_urlencoded::from_bytes::<T>(&$bytes).map_err(Error::BodyDecodeForm)
-            }
+            Some("application/x-www-form-urlencoded") => serde_urlencoded::from_bytes::<T>(&$bytes)
+                .map_err(|e| Error::caused_by(ErrorKind::BodyDecodeForm, e)),
             #[cfg(feature = "json")]
-            Some("application/json") => {
-                serde_json::from_slice::<T>(&$bytes).map_err(Error::BodyDecodeJson)
-            }
+            Some("application/json") => serde_json::from_slice::<T>(&$bytes)
+                .map_err(|e| Error::caused_by(ErrorKind::BodyDecodeJson, e)),
             #[cfg(feature = "uploads")]
             Some("multipart/form-data") => {
-                crate::forms::from_form_data::<T>(&$req.headers, &$bytes)
-                    .map_err(Error::BodyDecodeMultipart)
+                crate::multipart::from_content_type::<T>(content_type.as_bytes(), &$bytes)
+                    .map_err(|e| Error::caused_by(ErrorKind::BodyDecodeMultipart, e))
             }
-            Some(_) | None => Err(Error::BodyUnknownType(ct_str.to_owned())),
+            Some(_) | None => Err(Error::with_detail(ErrorKind::BodyUnknownType, ct_str)),
         }
     }};
 }
 
-#[doc(hidden)]
+/// Extracts the part of the request path that has not yet been consumed by routing
+///
+/// This is useful for catch-all handlers mounted at a prefix, like a static file server.
+/// Extract a `Rest<&str>` or `Rest<&[u8]>` for the raw path, or a `Rest<Cow<'_, str>>` for
+/// the percent-decoded path. The `#[rest]` attribute on a handler argument does the same,
+/// but yields the inner value directly.
+#[derive(Clone, Copy, Debug)]
 pub struct Rest<T>(pub T);
 
+impl<'a, A: Application> FromContext<'a, A> for Rest<&'a str> {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(Rest(state.rest(req.uri.path())))
+    }
+}
+
 impl<'a, A: Application> FromContext<'a, A> for Rest<&'a [u8]> {
     fn from_context(
         _: &'a Arc<A>,
@@ -460,7 +797,7 @@ impl<'a, A: Application> FromContext<'a, A> for Rest<Cow<'a, str>> {
         Ok(Rest(
             percent_decode_str(state.rest(req.uri.path()))
                 .decode_utf8()
-                .map_err(|_| Error::PathDecode)?,
+                .map_err(|_| state.reject::<A>(ErrorKind::PathDecode.into(), req))?,
         ))
     }
 }
@@ -482,6 +819,65 @@ where
     }
 }
 
+/// Extracts a value of type `T` from the request extensions
+///
+/// Extensions can be added to the request before routing, for example to pass data computed
+/// by the `Application` (like the authenticated user or a request id) to handlers:
+///
+/// ```no_run
+/// # use mendes::application::Extension;
+/// # use mendes::http::{Response, StatusCode};
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # #[derive(Clone)]
+/// # struct RequestId(u64);
+/// # impl RequestId {
+/// #     fn new() -> Self {
+/// #         Self(1)
+/// #     }
+/// # }
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     let id = RequestId::new();
+///     cx.req.extensions.insert(id);
+///     route!(match cx.path() {
+///         Some("hello") => hello,
+///     })
+/// }
+/// # }
+///
+/// #[handler(GET)]
+/// async fn hello(_: &App, Extension(id): Extension<RequestId>) -> Result<Response<Body>, Error> {
+///     Ok(Response::builder()
+///         .status(StatusCode::OK)
+///         .body(format!("request {}", id.0).into())
+///         .unwrap())
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Extraction fails with an internal server error if no value of type `T` is present.
+#[derive(Clone, Copy, Debug)]
+pub struct Extension<T>(pub T);
+
+impl<'a, A: Application, T: Clone + Send + Sync + 'static> FromContext<'a, A> for Extension<T> {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        match req.extensions.get::<T>() {
+            Some(value) => Ok(Extension(value.clone())),
+            None => Err(state.reject::<A>(ErrorKind::ExtensionMissing.into(), req)),
+        }
+    }
+}
+
 #[cfg(feature = "body-util")]
 #[cfg_attr(docsrs, doc(cfg(feature = "body-util")))]
 async fn from_body<B, T: serde::de::DeserializeOwned>(
@@ -504,6 +900,26 @@ fn from_bytes<'de, T: serde::de::Deserialize<'de>>(
     deserialize_body!(req, bytes)
 }
 
+#[cfg(feature = "body-util")]
+pub(crate) async fn read_body<B: HttpBody>(body: B, max_len: usize) -> Result<Bytes, Error>
+where
+    B::Error: Into<Box<dyn StdError + Send + Sync + 'static>>,
+{
+    // Check if the Content-Length header suggests the body is larger than our max len
+    // to avoid allocation if we drop the request in any case. This happens before the
+    // body is polled, so clients sending `Expect: 100-continue` don't send the body.
+    let expected_len = match body.size_hint().upper() {
+        Some(length) => length,
+        None => body.size_hint().lower(),
+    };
+
+    if expected_len > max_len as u64 {
+        return Err(ErrorKind::BodyTooLarge.into());
+    }
+
+    to_bytes(body, max_len).await
+}
+
 #[cfg(feature = "body-util")]
 #[cfg_attr(docsrs, doc(cfg(feature = "body-util")))]
 #[cfg_attr(feature = "tracing", tracing::instrument(skip(body)))]
@@ -517,7 +933,10 @@ where
     let limited = http_body_util::Limited::new(body, max_len);
     match limited.collect().await {
         Ok(collected) => Ok(collected.to_bytes()),
-        Err(err) => Err(Error::BodyReceive(err)),
+        Err(err) if err.is::<http_body_util::LengthLimitError>() => {
+            Err(Error::caused_by(ErrorKind::BodyTooLarge, err))
+        }
+        Err(err) => Err(Error::caused_by(ErrorKind::BodyReceive, err)),
     }
 }
 
@@ -526,6 +945,10 @@ where
 pub struct PathState {
     prev: Option<usize>,
     next: Option<usize>,
+    /// Whether errors from built-in extractors may still be discarded by an `Option<T>`
+    optional: bool,
+    /// The error from a built-in extractor while `optional` is set
+    rejected: Option<Error>,
 }
 
 impl PathState {
@@ -537,7 +960,63 @@ impl PathState {
         } else {
             Some(0)
         };
-        Self { prev: None, next }
+        Self {
+            prev: None,
+            next,
+            optional: false,
+            rejected: None,
+        }
+    }
+
+    /// Convert an error from a built-in extractor into `A::Error` through `A::rejection()`
+    ///
+    /// Within an `Option<T>` argument, the error is kept until it's clear whether it will be
+    /// returned, so that the hook doesn't see errors that are discarded.
+    pub(crate) fn reject<A: Application>(&mut self, error: Error, req: &Parts) -> A::Error {
+        if !self.optional {
+            return A::rejection(error, req);
+        }
+
+        let stand_in = Error {
+            kind: error.kind,
+            status: error.status,
+            message: error.message.clone(),
+            source: None,
+            extensions: http::Extensions::new(),
+            backtrace: None,
+            converted: false,
+        };
+        self.rejected = Some(error);
+        A::Error::from(stand_in)
+    }
+
+    /// Turn the `result` of extracting `T` into the result for `Option<T>`
+    fn optional_result<A: Application, T>(
+        &mut self,
+        result: Result<T, A::Error>,
+        next: Option<usize>,
+        req: &Parts,
+    ) -> Result<Option<T>, A::Error> {
+        let rejected = self.rejected.take();
+        match result {
+            Ok(value) => Ok(Some(value)),
+            // A path component that is present but fails to parse is still an error
+            Err(e) if self.next != next => match rejected {
+                Some(error) => Err(self.reject::<A>(error, req)),
+                None => Err(e),
+            },
+            Err(_) => Ok(None),
+        }
+    }
+
+    // This should only be used by procedural routing macros.
+    #[doc(hidden)]
+    pub fn matched<'r>(&self, path: &'r str) -> &'r str {
+        match self.next {
+            Some(next) if next > 1 => path[..next].trim_end_matches('/'),
+            Some(_) => "/",
+            None => path,
+        }
     }
 
     // This should only be used by procedural routing macros.
@@ -585,64 +1064,503 @@ impl PathState {
     }
 }
 
-#[derive(Debug, Error)]
-pub enum Error {
-    #[error("method not allowed")]
+/// An error produced while handling a request
+///
+/// An `Error` carries the status code for the response, a message that is safe to show to
+/// the client, an optional source error for internal use (logging, for example) and extension
+/// data that the application can use when rendering the error. Errors produced by mendes itself
+/// can be told apart by their `kind()`.
+///
+/// Applications can use `Error` as their `Application::Error` type directly, in which case it
+/// is rendered as a plain text response containing the public message (or as a debug page, see
+/// `Application::debug()`). Otherwise, an `Error` returned from a handler is converted into the
+/// application's error type, which renders it.
+#[derive(Debug)]
+pub struct Error {
+    kind: ErrorKind,
+    status: StatusCode,
+    message: Cow<'static, str>,
+    source: Option<Box<dyn StdError + Send + Sync + 'static>>,
+    extensions: http::Extensions,
+    backtrace: Option<Backtrace>,
+    /// Whether this error has been converted into `Application::Error` for rendering
+    converted: bool,
+}
+
+impl Error {
+    /// Create an error with the given status code and public message
+    pub fn new(status: StatusCode, message: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            kind: ErrorKind::Other,
+            status,
+            message: message.into(),
+            source: None,
+            extensions: http::Extensions::new(),
+            backtrace: capture_backtrace(status),
+            converted: false,
+        }
+    }
+
+    /// Create a `400 Bad Request` error
+    pub fn bad_request(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, message)
+    }
+
+    /// Create a `401 Unauthorized` error
+    pub fn unauthorized(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::UNAUTHORIZED, message)
+    }
+
+    /// Create a `403 Forbidden` error
+    pub fn forbidden(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::FORBIDDEN, message)
+    }
+
+    /// Create a `404 Not Found` error
+    pub fn not_found(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::NOT_FOUND, message)
+    }
+
+    /// Create a `409 Conflict` error
+    pub fn conflict(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::CONFLICT, message)
+    }
+
+    /// Create a `422 Unprocessable Entity` error
+    pub fn unprocessable(message: impl Into<Cow<'static, str>>) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, message)
+    }
+
+    /// Create a `500 Internal Server Error` error caused by `source`
+    ///
+    /// The public message is generic, so details from the source are not exposed to clients.
+    pub fn internal(source: impl Into<Box<dyn StdError + Send + Sync + 'static>>) -> Self {
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal server error").with_source(source)
+    }
+
+    /// Set the source of this error
+    pub fn with_source(
+        mut self,
+        source: impl Into<Box<dyn StdError + Send + Sync + 'static>>,
+    ) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Attach extension data to this error
+    pub fn with_extension<T: Clone + Send + Sync + 'static>(mut self, value: T) -> Self {
+        self.extensions.insert(value);
+        self
+    }
+
+    /// The kind of error, for errors produced by mendes
+    pub fn kind(&self) -> ErrorKind {
+        self.kind
+    }
+
+    /// The status code for the response
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    /// The message that is safe to show to clients
+    pub fn message(&self) -> &str {
+        &self.message
+    }
+
+    /// Extension data attached to this error
+    pub fn extensions(&self) -> &http::Extensions {
+        &self.extensions
+    }
+
+    /// Mutable access to the extension data attached to this error
+    pub fn extensions_mut(&mut self) -> &mut http::Extensions {
+        &mut self.extensions
+    }
+
+    /// Where this error was created, for server errors with backtraces enabled
+    ///
+    /// Backtraces are captured as configured by the `RUST_BACKTRACE` and `RUST_LIB_BACKTRACE`
+    /// environment variables.
+    pub fn backtrace(&self) -> Option<&Backtrace> {
+        self.backtrace.as_ref()
+    }
+
+    fn caused_by(
+        kind: ErrorKind,
+        source: impl Into<Box<dyn StdError + Send + Sync + 'static>>,
+    ) -> Self {
+        let mut error = Self::from(kind);
+        error.source = Some(source.into());
+        error
+    }
+
+    fn with_detail(kind: ErrorKind, detail: impl fmt::Display) -> Self {
+        let mut error = Self::from(kind);
+        error.message = Cow::Owned(format!("{}: {detail}", kind.description()));
+        error
+    }
+}
+
+/// Capture a backtrace for errors with a server error `status`, if backtraces are enabled
+fn capture_backtrace(status: StatusCode) -> Option<Backtrace> {
+    if !status.is_server_error() {
+        return None;
+    }
+
+    let backtrace = Backtrace::capture();
+    match backtrace.status() {
+        BacktraceStatus::Captured => Some(backtrace),
+        _ => None,
+    }
+}
+
+impl From<ErrorKind> for Error {
+    fn from(kind: ErrorKind) -> Self {
+        Self {
+            kind,
+            status: kind.status(),
+            message: Cow::Borrowed(kind.description()),
+            source: None,
+            extensions: http::Extensions::new(),
+            backtrace: capture_backtrace(kind.status()),
+            converted: false,
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+impl From<serde_json::Error> for Error {
+    fn from(e: serde_json::Error) -> Self {
+        Self::caused_by(ErrorKind::BodyDecodeJson, e)
+    }
+}
+
+#[cfg(feature = "cookies")]
+impl From<crate::cookies::Error> for Error {
+    fn from(e: crate::cookies::Error) -> Self {
+        Self::internal(e)
+    }
+}
+
+#[cfg(feature = "auth")]
+impl From<crate::auth::Error> for Error {
+    fn from(e: crate::auth::Error) -> Self {
+        match e {
+            // Return paths usually come from the request
+            crate::auth::Error::InvalidReturnPath => Self::bad_request(e.to_string()),
+            e => Self::internal(e),
+        }
+    }
+}
+
+#[cfg(feature = "storage")]
+impl From<crate::storage::Error> for Error {
+    fn from(e: crate::storage::Error) -> Self {
+        Self::internal(e)
+    }
+}
+
+#[cfg(feature = "uploads")]
+impl From<crate::multipart::Error> for Error {
+    fn from(e: crate::multipart::Error) -> Self {
+        Self::caused_by(ErrorKind::BodyDecodeMultipart, e)
+    }
+}
+
+#[cfg(feature = "xml")]
+impl From<crate::xml::Error> for Error {
+    fn from(e: crate::xml::Error) -> Self {
+        Self::caused_by(ErrorKind::BodyDecodeXml, e)
+    }
+}
+
+#[cfg(feature = "msgpack")]
+impl From<crate::msgpack::Error> for Error {
+    fn from(e: crate::msgpack::Error) -> Self {
+        Self::caused_by(ErrorKind::BodyDecodeMsgPack, e)
+    }
+}
+
+#[cfg(feature = "cbor")]
+impl From<crate::cbor::Error> for Error {
+    fn from(e: crate::cbor::Error) -> Self {
+        Self::caused_by(ErrorKind::BodyDecodeCbor, e)
+    }
+}
+
+#[cfg(feature = "scan")]
+impl From<crate::scan::Error> for Error {
+    fn from(e: crate::scan::Error) -> Self {
+        let kind = match &e {
+            crate::scan::Error::Rejected { .. } => ErrorKind::UploadRejected,
+            crate::scan::Error::Failed(_) => ErrorKind::UploadScanFailed,
+            crate::scan::Error::Multipart(_) => ErrorKind::BodyDecodeMultipart,
+        };
+        Self::caused_by(kind, e)
+    }
+}
+
+#[cfg(feature = "oauth")]
+impl From<crate::oauth::Error> for Error {
+    fn from(e: crate::oauth::Error) -> Self {
+        use crate::oauth::Error as OAuthError;
+        match e {
+            // The provider failed us, not the client
+            OAuthError::Transport(_) | OAuthError::Status(_) | OAuthError::InvalidResponse => {
+                Self::caused_by(ErrorKind::ProviderFailed, e)
+            }
+            OAuthError::InvalidLocation(_) | OAuthError::Cookie(_) => Self::internal(e),
+            _ => Self::caused_by(ErrorKind::SignInFailed, e),
+        }
+    }
+}
+
+#[cfg(feature = "webauthn")]
+impl From<crate::webauthn::Error> for Error {
+    fn from(e: crate::webauthn::Error) -> Self {
+        Self::caused_by(ErrorKind::PasskeyInvalid, e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+impl StdError for Error {
+    fn source(&self) -> Option<&(dyn StdError + 'static)> {
+        match &self.source {
+            Some(source) => Some(&**source),
+            None => None,
+        }
+    }
+}
+
+impl From<&Error> for StatusCode {
+    fn from(e: &Error) -> StatusCode {
+        e.status
+    }
+}
+
+/// The kinds of errors produced by mendes
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ErrorKind {
     MethodNotAllowed,
-    #[error("no matching routes")]
     PathNotFound,
-    #[error("missing path component")]
     PathComponentMissing,
-    #[error("unable to parse path component")]
     PathParse,
-    #[error("unable to decode UTF-8 from path component")]
     PathDecode,
-    #[error("no query in request URL")]
     QueryMissing,
-    #[error("unable to decode request URI query: {0}")]
-    QueryDecode(serde_urlencoded::de::Error),
+    QueryDecode,
     #[cfg(feature = "body-util")]
-    #[error("unable to receive request body: {0}")]
-    BodyReceive(Box<dyn StdError + Send + Sync + 'static>),
+    BodyReceive,
+    /// The request body exceeds the size limit
+    ///
+    /// Responds with 413 Payload Too Large; earlier releases used 400 Bad Request.
     #[cfg(feature = "body-util")]
-    #[error("request body too large")]
     BodyTooLarge,
     #[cfg(feature = "json")]
-    #[error("unable to decode body as JSON: {0}")]
-    BodyDecodeJson(#[from] serde_json::Error),
-    #[error("unable to decode body as form data: {0}")]
-    BodyDecodeForm(serde_urlencoded::de::Error),
+    BodyDecodeJson,
+    BodyDecodeForm,
     #[cfg(feature = "uploads")]
-    #[error("unable to decode body as multipart form data: {0}")]
-    BodyDecodeMultipart(#[from] crate::multipart::Error),
-    #[error("content type on request body unknown: {0}")]
-    BodyUnknownType(String),
-    #[error("no content type on request body")]
+    BodyDecodeMultipart,
+    #[cfg(feature = "xml")]
+    BodyDecodeXml,
+    #[cfg(feature = "msgpack")]
+    BodyDecodeMsgPack,
+    #[cfg(feature = "cbor")]
+    BodyDecodeCbor,
+    BodyUnknownType,
     BodyNoType,
-    #[cfg(feature = "static")]
-    #[error("file not found")]
+    #[cfg(any(feature = "cbor", feature = "msgpack", feature = "xml"))]
+    NotAcceptable,
+    #[cfg(any(feature = "static", feature = "embed"))]
     FileNotFound,
+    ExtensionMissing,
+    #[cfg(feature = "services")]
+    ServiceMissing,
+    #[cfg(feature = "authz")]
+    PermissionDenied,
+    #[cfg(feature = "patch")]
+    PatchFailed,
+    #[cfg(feature = "precondition")]
+    PreconditionFailed,
+    #[cfg(feature = "precondition")]
+    PreconditionRequired,
+    #[cfg(feature = "scan")]
+    UploadRejected,
+    #[cfg(feature = "scan")]
+    UploadScanFailed,
+    #[cfg(feature = "signed")]
+    SignatureInvalid,
+    #[cfg(feature = "signed")]
+    SignatureExpired,
+    #[cfg(feature = "apikeys")]
+    ApiKeyMissing,
+    #[cfg(feature = "apikeys")]
+    ApiKeyInvalid,
+    #[cfg(feature = "oauth")]
+    SignInFailed,
+    #[cfg(feature = "oauth")]
+    SignInRequired,
+    #[cfg(feature = "oauth")]
+    ProviderFailed,
+    #[cfg(feature = "webauthn")]
+    PasskeyInvalid,
+    #[cfg(feature = "webhooks")]
+    WebhookInvalid,
+    #[cfg(feature = "webhooks")]
+    WebhookExpired,
+    #[cfg(feature = "webhooks")]
+    WebhookReplayed,
+    #[cfg(feature = "websocket")]
+    WebSocketUpgrade,
+    /// An error created by the application
+    Other,
 }
 
-impl From<&Error> for StatusCode {
-    fn from(e: &Error) -> StatusCode {
-        use Error::*;
-        match e {
+impl ErrorKind {
+    fn status(self) -> StatusCode {
+        use ErrorKind::*;
+        match self {
             MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
-            QueryMissing | QueryDecode(_) | BodyNoType => StatusCode::BAD_REQUEST,
-            BodyUnknownType(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            QueryMissing | QueryDecode | BodyNoType => StatusCode::BAD_REQUEST,
+            BodyUnknownType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
             PathNotFound | PathComponentMissing | PathParse | PathDecode => StatusCode::NOT_FOUND,
             #[cfg(feature = "body-util")]
-            BodyReceive(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            BodyReceive => StatusCode::INTERNAL_SERVER_ERROR,
             #[cfg(feature = "body-util")]
-            BodyTooLarge => StatusCode::BAD_REQUEST,
-            BodyDecodeForm(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            BodyTooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyDecodeForm => StatusCode::UNPROCESSABLE_ENTITY,
             #[cfg(feature = "json")]
-            BodyDecodeJson(_) => StatusCode::UNPROCESSABLE_ENTITY,
+            BodyDecodeJson => StatusCode::UNPROCESSABLE_ENTITY,
             #[cfg(feature = "uploads")]
-            BodyDecodeMultipart(_) => StatusCode::UNPROCESSABLE_ENTITY,
-            #[cfg(feature = "static")]
+            BodyDecodeMultipart => StatusCode::UNPROCESSABLE_ENTITY,
+            #[cfg(feature = "xml")]
+            BodyDecodeXml => StatusCode::UNPROCESSABLE_ENTITY,
+            #[cfg(feature = "msgpack")]
+            BodyDecodeMsgPack => StatusCode::UNPROCESSABLE_ENTITY,
+            #[cfg(feature = "cbor")]
+            BodyDecodeCbor => StatusCode::UNPROCESSABLE_ENTITY,
+            #[cfg(any(feature = "cbor", feature = "msgpack", feature = "xml"))]
+            NotAcceptable => StatusCode::NOT_ACCEPTABLE,
+            #[cfg(any(feature = "static", feature = "embed"))]
             FileNotFound => StatusCode::NOT_FOUND,
+            ExtensionMissing => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "services")]
+            ServiceMissing => StatusCode::INTERNAL_SERVER_ERROR,
+            #[cfg(feature = "authz")]
+            PermissionDenied => StatusCode::FORBIDDEN,
+            #[cfg(feature = "patch")]
+            PatchFailed => StatusCode::CONFLICT,
+            #[cfg(feature = "precondition")]
+            PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            #[cfg(feature = "precondition")]
+            PreconditionRequired => StatusCode::PRECONDITION_REQUIRED,
+            #[cfg(feature = "scan")]
+            UploadRejected => StatusCode::UNPROCESSABLE_ENTITY,
+            #[cfg(feature = "scan")]
+            UploadScanFailed => StatusCode::SERVICE_UNAVAILABLE,
+            #[cfg(feature = "signed")]
+            SignatureInvalid | SignatureExpired => StatusCode::FORBIDDEN,
+            #[cfg(feature = "apikeys")]
+            ApiKeyMissing | ApiKeyInvalid => StatusCode::UNAUTHORIZED,
+            #[cfg(feature = "oauth")]
+            SignInFailed => StatusCode::BAD_REQUEST,
+            #[cfg(feature = "oauth")]
+            SignInRequired => StatusCode::UNAUTHORIZED,
+            #[cfg(feature = "oauth")]
+            ProviderFailed => StatusCode::BAD_GATEWAY,
+            #[cfg(feature = "webauthn")]
+            PasskeyInvalid => StatusCode::BAD_REQUEST,
+            #[cfg(feature = "webhooks")]
+            WebhookInvalid | WebhookExpired | WebhookReplayed => StatusCode::BAD_REQUEST,
+            #[cfg(feature = "websocket")]
+            WebSocketUpgrade => StatusCode::BAD_REQUEST,
+            Other => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    fn description(self) -> &'static str {
+        use ErrorKind::*;
+        match self {
+            MethodNotAllowed => "method not allowed",
+            PathNotFound => "no matching routes",
+            PathComponentMissing => "missing path component",
+            PathParse => "unable to parse path component",
+            PathDecode => "unable to decode UTF-8 from path component",
+            QueryMissing => "no query in request URL",
+            QueryDecode => "unable to decode request URI query",
+            #[cfg(feature = "body-util")]
+            BodyReceive => "unable to receive request body",
+            #[cfg(feature = "body-util")]
+            BodyTooLarge => "request body too large",
+            #[cfg(feature = "json")]
+            BodyDecodeJson => "unable to decode body as JSON",
+            BodyDecodeForm => "unable to decode body as form data",
+            #[cfg(feature = "uploads")]
+            BodyDecodeMultipart => "unable to decode body as multipart form data",
+            #[cfg(feature = "xml")]
+            BodyDecodeXml => "unable to decode body as XML",
+            #[cfg(feature = "msgpack")]
+            BodyDecodeMsgPack => "unable to decode body as MessagePack",
+            #[cfg(feature = "cbor")]
+            BodyDecodeCbor => "unable to decode body as CBOR",
+            BodyUnknownType => "content type on request body unknown",
+            BodyNoType => "no content type on request body",
+            #[cfg(any(feature = "cbor", feature = "msgpack", feature = "xml"))]
+            NotAcceptable => "no acceptable response format",
+            #[cfg(any(feature = "static", feature = "embed"))]
+            FileNotFound => "file not found",
+            ExtensionMissing => "request extension missing",
+            #[cfg(feature = "services")]
+            ServiceMissing => "request-scoped service missing",
+            #[cfg(feature = "authz")]
+            PermissionDenied => "permission denied",
+            #[cfg(feature = "patch")]
+            PatchFailed => "unable to apply patch",
+            #[cfg(feature = "precondition")]
+            PreconditionFailed => "resource was modified",
+            #[cfg(feature = "precondition")]
+            PreconditionRequired => "request must be conditional",
+            #[cfg(feature = "scan")]
+            UploadRejected => "uploaded file rejected",
+            #[cfg(feature = "scan")]
+            UploadScanFailed => "unable to scan uploaded file",
+            #[cfg(feature = "signed")]
+            SignatureInvalid => "invalid URL signature",
+            #[cfg(feature = "signed")]
+            SignatureExpired => "URL expired",
+            #[cfg(feature = "apikeys")]
+            ApiKeyMissing => "no API key in request",
+            #[cfg(feature = "apikeys")]
+            ApiKeyInvalid => "invalid API key",
+            #[cfg(feature = "oauth")]
+            SignInFailed => "sign-in failed",
+            #[cfg(feature = "oauth")]
+            SignInRequired => "sign-in required",
+            #[cfg(feature = "oauth")]
+            ProviderFailed => "sign-in provider unavailable",
+            #[cfg(feature = "webauthn")]
+            PasskeyInvalid => "passkey verification failed",
+            #[cfg(feature = "webhooks")]
+            WebhookInvalid => "invalid webhook signature",
+            #[cfg(feature = "webhooks")]
+            WebhookExpired => "webhook timestamp outside tolerance",
+            #[cfg(feature = "webhooks")]
+            WebhookReplayed => "webhook already received",
+            #[cfg(feature = "websocket")]
+            WebSocketUpgrade => "invalid WebSocket upgrade request",
+            Other => "internal server error",
         }
     }
 }
+
+impl fmt::Display for ErrorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.description())
+    }
+}
@@ -0,0 +1,189 @@
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use std::{fs, io};
+
+use data_encoding::HEXLOWER;
+use http::Response;
+use ring::digest::{digest, SHA256};
+
+use crate::application::{Error, ErrorKind};
+use crate::cache_control::CacheControl;
+use crate::utils::file;
+
+/// A manifest of static assets with content-hashed file names
+///
+/// Hashed file names change whenever the contents of a file change, so responses for them
+/// can be cached by clients indefinitely. Load the manifest when starting the application,
+/// use `url()` to link to assets from templates and `serve()` to respond to asset requests:
+///
+/// ```no_run
+/// # use std::borrow::Cow;
+/// # use mendes::assets::Assets;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # struct App {
+/// #     assets: Assets,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # fn main() -> std::io::Result<()> {
+/// let assets = Assets::load("static", "/static")?;
+/// assert_eq!(assets.url("app.css"), "/static/app.8f3a1b2c.css");
+/// # Ok(())
+/// # }
+///
+/// #[handler(GET)]
+/// async fn asset(app: &App, #[rest] path: Cow<'_, str>) -> Result<Response<Body>, Error> {
+///     Ok(app.assets.serve(&path).await?)
+/// }
+/// ```
+///
+/// Assets are also served under their original names, but without far-future caching headers.
+#[derive(Clone, Debug)]
+pub struct Assets {
+    prefix: String,
+    urls: HashMap<String, String>,
+    files: HashMap<String, (PathBuf, bool)>,
+}
+
+impl Assets {
+    /// Build a manifest from all files below `root`
+    ///
+    /// Generated URLs start with `prefix`, which should match the path the assets handler is
+    /// routed at.
+    pub fn load(root: impl AsRef<Path>, prefix: &str) -> io::Result<Self> {
+        let mut assets = Self {
+            prefix: prefix.trim_end_matches('/').to_owned(),
+            urls: HashMap::new(),
+            files: HashMap::new(),
+        };
+
+        let root = root.as_ref();
+        let mut dirs = vec![root.to_path_buf()];
+        while let Some(dir) = dirs.pop() {
+            for entry in fs::read_dir(&dir)? {
+                let path = entry?.path();
+                if path.is_dir() {
+                    dirs.push(path);
+                    continue;
+                }
+
+                let name = match path.strip_prefix(root).ok().and_then(|p| p.to_str()) {
+                    Some(name) => name.replace(std::path::MAIN_SEPARATOR, "/"),
+                    None => continue,
+                };
+
+                let hash = digest(&SHA256, &fs::read(&path)?);
+                let hashed = fingerprint(&name, &HEXLOWER.encode(&hash.as_ref()[..4]));
+                assets.files.insert(hashed.clone(), (path.clone(), true));
+                assets.files.insert(name.clone(), (path, false));
+                assets.urls.insert(name, hashed);
+            }
+        }
+
+        Ok(assets)
+    }
+
+    /// The URL for the asset with the given (unhashed) path relative to the root
+    ///
+    /// Returns the unhashed URL if the asset isn't in the manifest.
+    pub fn url(&self, path: &str) -> String {
+        let path = path.trim_start_matches('/');
+        let name = self.urls.get(path).map(|s| s.as_str()).unwrap_or(path);
+        format!("{}/{}", self.prefix, name)
+    }
+
+    /// The hashed path for the given (unhashed) path relative to the root
+    pub fn hashed(&self, path: &str) -> Option<&str> {
+        self.urls
+            .get(path.trim_start_matches('/'))
+            .map(|s| s.as_str())
+    }
+
+    /// Respond with the asset at the given path, relative to the prefix
+    ///
+    /// Hashed paths are served with a `Cache-Control` header allowing clients to cache them
+    /// for a year; any other path in the manifest requires clients to revalidate. Paths not in
+    /// the manifest result in a `404 Not Found` error.
+    pub async fn serve<B: From<Vec<u8>>>(&self, path: &str) -> Result<Response<B>, Error> {
+        let (file_path, immutable) = self
+            .files
+            .get(path.trim_start_matches('/'))
+            .ok_or_else(|| Error::from(ErrorKind::FileNotFound))?;
+
+        let mut rsp = file(file_path.clone()).await?;
+        let cache_control = match immutable {
+            true => CacheControl::public()
+                .max_age(Duration::from_secs(31_536_000))
+                .immutable(),
+            false => CacheControl::no_cache(),
+        };
+        cache_control.apply(&mut rsp);
+        Ok(rsp)
+    }
+}
+
+/// Insert the `hash` into the file name of `path`, before its extension
+fn fingerprint(path: &str, hash: &str) -> String {
+    let (dir, name) = match path.rfind('/') {
+        Some(i) => path.split_at(i + 1),
+        None => ("", path),
+    };
+
+    match name.find('.') {
+        Some(i) if i > 0 => format!("{dir}{}.{hash}{}", &name[..i], &name[i..]),
+        _ => format!("{dir}{name}.{hash}"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::CACHE_CONTROL;
+
+    use super::*;
+
+    #[test]
+    fn fingerprinting() {
+        assert_eq!(fingerprint("app.css", "8f3a"), "app.8f3a.css");
+        assert_eq!(fingerprint("js/app.min.js", "8f3a"), "js/app.8f3a.min.js");
+        assert_eq!(fingerprint("LICENSE", "8f3a"), "LICENSE.8f3a");
+        assert_eq!(fingerprint("img/.hidden", "8f3a"), "img/.hidden.8f3a");
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let root = std::env::temp_dir().join(format!("mendes-assets-{}", std::process::id()));
+        fs::create_dir_all(root.join("css")).unwrap();
+        fs::write(root.join("css/app.css"), "body { color: red }").unwrap();
+
+        let assets = Assets::load(&root, "/static/").unwrap();
+        let url = assets.url("css/app.css");
+        assert_eq!(url, "/static/css/app.925e8741.css");
+        assert_eq!(assets.url("missing.js"), "/static/missing.js");
+
+        let rsp = assets
+            .serve::<Vec<u8>>("css/app.925e8741.css")
+            .await
+            .unwrap();
+        assert_eq!(
+            rsp.headers()[CACHE_CONTROL],
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(rsp.headers()["content-type"], "text/css");
+        assert_eq!(rsp.into_body(), b"body { color: red }");
+
+        let rsp = assets.serve::<Vec<u8>>("/css/app.css").await.unwrap();
+        assert_eq!(rsp.headers()[CACHE_CONTROL], "no-cache");
+        assert!(assets.serve::<Vec<u8>>("../etc/passwd").await.is_err());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
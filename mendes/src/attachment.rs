@@ -0,0 +1,143 @@
+use std::borrow::Cow;
+
+use http::header::{CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE, X_CONTENT_TYPE_OPTIONS};
+use http::request::Parts;
+use http::{HeaderValue, Response, StatusCode};
+use http_body::Body as _;
+
+use crate::application::{Application, IntoResponse};
+use crate::utils::content_disposition;
+use crate::Body;
+
+/// A file download response
+///
+/// Sets the `Content-Disposition` header so that browsers save the response as `filename`,
+/// encoding non-ASCII file names as described in RFC 5987:
+///
+/// ```no_run
+/// # use mendes::attachment::Attachment;
+/// # use mendes::{handler, Error};
+/// # struct Invoices;
+/// # impl Invoices {
+/// #     async fn render(&self, id: u64) -> Result<Vec<u8>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     invoices: Invoices,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn invoice(app: &App, id: u64) -> Result<Attachment, Error> {
+///     let pdf = app.invoices.render(id).await?;
+///     Ok(Attachment::new(format!("factuur-{id}.pdf"), pdf).content_type("application/pdf"))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The content type defaults to `application/octet-stream`, and browsers are told not to sniff
+/// another type from the contents.
+pub struct Attachment {
+    filename: Cow<'static, str>,
+    body: Body,
+    content_type: Cow<'static, str>,
+    content_length: Option<u64>,
+    inline: bool,
+}
+
+impl Attachment {
+    /// Create a download for `body`, which may also be a streaming `Body`
+    ///
+    /// The `Content-Length` is taken from the body's size hint if it has an exact size.
+    pub fn new(filename: impl Into<Cow<'static, str>>, body: impl Into<Body>) -> Self {
+        let body = body.into();
+        Self {
+            filename: filename.into(),
+            content_length: body.size_hint().exact(),
+            body,
+            content_type: Cow::Borrowed("application/octet-stream"),
+            inline: false,
+        }
+    }
+
+    pub fn content_type(mut self, content_type: impl Into<Cow<'static, str>>) -> Self {
+        self.content_type = content_type.into();
+        self
+    }
+
+    /// Set the `Content-Length`, for streaming bodies of known size
+    pub fn content_length(mut self, len: u64) -> Self {
+        self.content_length = Some(len);
+        self
+    }
+
+    /// Let the browser display the file if it can, using `filename` only when saving it
+    pub fn inline(mut self) -> Self {
+        self.inline = true;
+        self
+    }
+
+    pub fn into_response(self) -> Response<Body> {
+        let disposition = match self.inline {
+            true => "inline",
+            false => "attachment",
+        };
+
+        let mut builder = Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, &*self.content_type)
+            .header(
+                CONTENT_DISPOSITION,
+                content_disposition(disposition, &self.filename),
+            )
+            .header(X_CONTENT_TYPE_OPTIONS, HeaderValue::from_static("nosniff"));
+        if let Some(len) = self.content_length {
+            builder = builder.header(CONTENT_LENGTH, len);
+        }
+
+        builder.body(self.body).unwrap()
+    }
+}
+
+impl<A: Application<ResponseBody = Body>> IntoResponse<A> for Attachment {
+    fn into_response(self, _: &A, _: &Parts) -> Response<Body> {
+        Attachment::into_response(self)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn headers() {
+        let rsp = Attachment::new("Überblick 2024.pdf", "%PDF-1.7")
+            .content_type("application/pdf")
+            .into_response();
+        let headers = rsp.headers();
+        assert_eq!(headers[CONTENT_TYPE], "application/pdf");
+        assert_eq!(headers[CONTENT_LENGTH], "8");
+        assert_eq!(
+            headers[CONTENT_DISPOSITION],
+            "attachment; filename*=UTF-8''%C3%9Cberblick%202024.pdf"
+        );
+        assert_eq!(headers[X_CONTENT_TYPE_OPTIONS], "nosniff");
+
+        let rsp = Attachment::new("notes.txt", String::from("hello"))
+            .inline()
+            .into_response();
+        assert_eq!(rsp.headers()[CONTENT_TYPE], "application/octet-stream");
+        assert_eq!(
+            rsp.headers()[CONTENT_DISPOSITION],
+            "inline; filename=\"notes.txt\""
+        );
+    }
+}
@@ -0,0 +1,270 @@
+use bytes::Bytes;
+use http::header::{InvalidHeaderValue, LOCATION, SET_COOKIE};
+use http::{HeaderMap, HeaderValue, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+use crate::cookies::{AppWithAeadKey, AppWithCookies, CookieData, CookieMeta, SameSite};
+use crate::utils::is_local_path;
+
+pub mod password;
+pub mod totp;
+
+/// Start a session by storing `session` in its cookie, then redirect to `return_to`
+///
+/// Call this once the user's credentials have been checked, usually from the handler for a
+/// login form. The redirect uses `303 See Other`, so reloading the next page doesn't submit
+/// the form again:
+///
+/// ```no_run
+/// # #[cfg(feature = "body-util")]
+/// # mod example {
+/// # use mendes::auth::{self, password::{Hasher, Verification}};
+/// # use mendes::cookies::{AppWithAeadKey, CookieData, Key};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Application, Body, Error};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Deserialize)]
+/// # struct LoginForm {
+/// #     username: String,
+/// #     password: String,
+/// # }
+/// # #[derive(Deserialize, Serialize)]
+/// # struct Session {
+/// #     user: u64,
+/// # }
+/// # impl CookieData for Session {
+/// #     const NAME: &'static str = "Session";
+/// # }
+/// # struct User {
+/// #     id: u64,
+/// #     password_hash: String,
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn user_by_name(&self, name: &str) -> Result<User, Error> {
+/// #         todo!()
+/// #     }
+/// #     async fn update_password_hash(&self, id: u64, hash: &str) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// #     hasher: Hasher,
+/// #     key: Key,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithAeadKey for App {
+/// #     fn key(&self) -> &Key {
+/// #         &self.key
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn login(app: &App, req: &Parts, body: Body) -> Result<Response<Body>, Error> {
+///     let form = App::from_body::<LoginForm>(req, body, 1024).await?;
+///     let user = app.db.user_by_name(&form.username).await?;
+///     match app.hasher.verify(&form.password, &user.password_hash) {
+///         Verification::Invalid => return Err(Error::unauthorized("invalid credentials")),
+///         Verification::Valid => {}
+///         Verification::Rehash(hash) => app.db.update_password_hash(user.id, &hash).await?,
+///     }
+///
+///     Ok(auth::login(app, Session { user: user.id }, "/")?)
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// Handlers then get the session through `AppWithCookies::cookie()`. Like the other
+/// redirecting functions in this module, `login()` fails if `return_to` is not a local path,
+/// so a return path taken from the request can't send users to another site.
+pub fn login<A, S, B>(app: &A, session: S, return_to: &str) -> Result<Response<B>, Error>
+where
+    A: AppWithAeadKey,
+    S: CookieData + Serialize,
+    B: From<Bytes>,
+{
+    redirect([app.set_cookie_header(Some(session))?], return_to)
+}
+
+/// End the session stored in the `S` cookie, then redirect to `return_to`
+pub fn logout<A, S, B>(app: &A, return_to: &str) -> Result<Response<B>, Error>
+where
+    A: AppWithAeadKey,
+    S: CookieData + Serialize,
+    B: From<Bytes>,
+{
+    redirect([app.set_cookie_header::<S>(None)?], return_to)
+}
+
+/// Hold on to `session` until a second factor is verified, redirecting to `challenge_path`
+///
+/// Use this instead of `login()` for users with two-factor authentication enabled. The
+/// session is kept in a short-lived `PendingLogin` cookie; the handler for the challenge
+/// form gets it back with `pending()`, checks the code and calls `complete()`:
+///
+/// ```no_run
+/// # #[cfg(feature = "body-util")]
+/// # mod example {
+/// # use mendes::auth::{self, totp::Totp};
+/// # use mendes::cookies::{AppWithAeadKey, CookieData, Key};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Application, Body, Error};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Deserialize)]
+/// # struct CodeForm {
+/// #     code: String,
+/// # }
+/// # #[derive(Deserialize, Serialize)]
+/// # struct Session {
+/// #     user: u64,
+/// # }
+/// # impl CookieData for Session {
+/// #     const NAME: &'static str = "Session";
+/// # }
+/// # struct User {
+/// #     id: u64,
+/// #     totp_secret: String,
+/// #     totp_step: Option<u64>,
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn user(&self, id: u64) -> Result<User, Error> {
+/// #         todo!()
+/// #     }
+/// #     async fn set_totp_step(&self, id: u64, step: u64) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// #     key: Key,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithAeadKey for App {
+/// #     fn key(&self) -> &Key {
+/// #         &self.key
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn verify(app: &App, req: &Parts, body: Body) -> Result<Response<Body>, Error> {
+///     let form = App::from_body::<CodeForm>(req, body, 1024).await?;
+///     let session = auth::pending::<_, Session>(app, &req.headers)
+///         .ok_or_else(|| Error::unauthorized("login expired"))?;
+///     let user = app.db.user(session.user).await?;
+///     let totp = Totp::from_base32(&user.totp_secret).unwrap();
+///     match totp.verify(&form.code, user.totp_step) {
+///         Some(step) => app.db.set_totp_step(user.id, step).await?,
+///         None => return Err(Error::unauthorized("invalid code")),
+///     }
+///
+///     Ok(auth::complete(app, session, "/")?)
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// The same steps can be used for step-up authentication before sensitive actions, by
+/// storing the time of the last verification in the session.
+pub fn challenge<A, S, B>(app: &A, session: S, challenge_path: &str) -> Result<Response<B>, Error>
+where
+    A: AppWithAeadKey,
+    S: Serialize,
+    B: From<Bytes>,
+{
+    redirect(
+        [app.set_cookie_header(Some(Pending(session)))?],
+        challenge_path,
+    )
+}
+
+/// The session held by `challenge()`, if it hasn't expired
+pub fn pending<A, S>(app: &A, headers: &HeaderMap) -> Option<S>
+where
+    A: AppWithAeadKey,
+    S: DeserializeOwned,
+{
+    app.cookie::<Pending<S>>(headers).map(|pending| pending.0)
+}
+
+/// Finish a login started with `challenge()`, storing `session` and redirecting to `return_to`
+pub fn complete<A, S, B>(app: &A, session: S, return_to: &str) -> Result<Response<B>, Error>
+where
+    A: AppWithAeadKey,
+    S: CookieData + Serialize,
+    B: From<Bytes>,
+{
+    redirect(
+        [
+            app.set_cookie_header::<Pending<()>>(None)?,
+            app.set_cookie_header(Some(session))?,
+        ],
+        return_to,
+    )
+}
+
+fn redirect<B: From<Bytes>>(
+    cookies: impl IntoIterator<Item = HeaderValue>,
+    location: &str,
+) -> Result<Response<B>, Error> {
+    if !is_local_path(location) {
+        return Err(Error::InvalidReturnPath);
+    }
+
+    let mut rsp = Response::new(B::from(Bytes::new()));
+    *rsp.status_mut() = StatusCode::SEE_OTHER;
+    let headers = rsp.headers_mut();
+    headers.insert(LOCATION, HeaderValue::try_from(location)?);
+    for cookie in cookies {
+        headers.append(SET_COOKIE, cookie);
+    }
+    Ok(rsp)
+}
+
+/// A session waiting for a second factor
+#[derive(Deserialize, Serialize)]
+#[serde(transparent)]
+struct Pending<S>(S);
+
+impl<S> CookieData for Pending<S> {
+    fn meta() -> CookieMeta<'static> {
+        CookieMeta {
+            http_only: true,
+            max_age: 5 * 60,
+            same_site: Some(SameSite::Lax),
+            ..CookieMeta::default()
+        }
+    }
+
+    const NAME: &'static str = "PendingLogin";
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("return path must be a local path")]
+    InvalidReturnPath,
+    #[error("invalid redirect location")]
+    InvalidLocation(#[from] InvalidHeaderValue),
+    #[error("cookie error: {0}")]
+    Cookie(#[from] crate::cookies::Error),
+}
@@ -0,0 +1,224 @@
+use std::num::NonZeroU32;
+
+use data_encoding::BASE64_NOPAD;
+use ring::pbkdf2::{self, PBKDF2_HMAC_SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+
+/// Hashes and verifies passwords
+///
+/// Passwords are hashed with PBKDF2-HMAC-SHA256 and a random salt, and encoded in the PHC
+/// string format (`$pbkdf2-sha256$i=600000$<salt>$<hash>`), so the parameters are stored
+/// with each hash. When the policy changes, `verify()` asks for older hashes to be replaced
+/// the next time the user logs in.
+///
+/// PBKDF2 is not memory-hard. Because the algorithm is named in every encoded hash, a later
+/// move to a memory-hard function like argon2id stays compatible: `verify()` will keep
+/// checking `$pbkdf2-sha256$` hashes and return `Verification::Rehash` for them, so stored
+/// hashes migrate as users log in.
+///
+/// ```no_run
+/// # use mendes::auth::password::{Hasher, Verification};
+/// # use mendes::Error;
+/// # struct App {
+/// #     hasher: Hasher,
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn update_password_hash(&self, id: u64, hash: &str) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct Form {
+/// #     password: String,
+/// # }
+/// # struct User {
+/// #     id: u64,
+/// #     password_hash: String,
+/// # }
+/// # async fn login(app: &App, db: &Db, form: Form, user: User) -> Result<(), Error> {
+/// match app.hasher.verify(&form.password, &user.password_hash) {
+///     Verification::Invalid => return Err(Error::unauthorized("invalid credentials")),
+///     Verification::Valid => {}
+///     Verification::Rehash(hash) => db.update_password_hash(user.id, &hash).await?,
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Hasher {
+    iterations: NonZeroU32,
+}
+
+impl Hasher {
+    /// Hash with the given number of PBKDF2 iterations
+    pub fn new(iterations: NonZeroU32) -> Self {
+        Self { iterations }
+    }
+
+    /// Hash `password` with a new random salt
+    pub fn hash(&self, password: &str) -> String {
+        let mut salt = [0; SALT_LEN];
+        SystemRandom::new()
+            .fill(&mut salt)
+            .expect("failed to generate random salt");
+
+        let mut hash = [0; HASH_LEN];
+        pbkdf2::derive(
+            PBKDF2_HMAC_SHA256,
+            self.iterations,
+            &salt,
+            password.as_bytes(),
+            &mut hash,
+        );
+
+        format!(
+            "${}$i={}${}${}",
+            ALGORITHM.id(),
+            self.iterations,
+            BASE64_NOPAD.encode(&salt),
+            BASE64_NOPAD.encode(&hash)
+        )
+    }
+
+    /// Check `password` against a hash produced by `hash()`
+    ///
+    /// The comparison takes constant time. Returns `Verification::Rehash` with a new hash if
+    /// the password is correct but the hash was made with weaker parameters than the current
+    /// policy.
+    pub fn verify(&self, password: &str, hash: &str) -> Verification {
+        let parsed = match Parsed::new(hash) {
+            Some(parsed) => parsed,
+            None => return Verification::Invalid,
+        };
+
+        let valid = match parsed.algorithm {
+            Algorithm::Pbkdf2Sha256 => pbkdf2::verify(
+                PBKDF2_HMAC_SHA256,
+                parsed.iterations,
+                &parsed.salt,
+                password.as_bytes(),
+                &parsed.hash,
+            ),
+        };
+
+        match valid {
+            Err(_) => Verification::Invalid,
+            Ok(()) if parsed.outdated(self) => Verification::Rehash(self.hash(password)),
+            Ok(()) => Verification::Valid,
+        }
+    }
+}
+
+impl Default for Hasher {
+    /// Uses 600,000 iterations, as recommended by OWASP
+    fn default() -> Self {
+        Self::new(NonZeroU32::new(600_000).unwrap())
+    }
+}
+
+/// The outcome of verifying a password
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verification {
+    /// The password does not match (or the hash could not be parsed)
+    Invalid,
+    /// The password matches
+    Valid,
+    /// The password matches, but the stored hash should be replaced with this one
+    ///
+    /// Returned when the stored hash used an older algorithm or weaker parameters.
+    Rehash(String),
+}
+
+impl Verification {
+    pub fn is_valid(&self) -> bool {
+        !matches!(self, Verification::Invalid)
+    }
+}
+
+struct Parsed {
+    algorithm: Algorithm,
+    iterations: NonZeroU32,
+    salt: Vec<u8>,
+    hash: Vec<u8>,
+}
+
+impl Parsed {
+    fn new(s: &str) -> Option<Self> {
+        let mut parts = s.strip_prefix('$')?.split('$');
+        let algorithm = Algorithm::from_id(parts.next()?)?;
+
+        let iterations = parts.next()?.strip_prefix("i=")?.parse().ok()?;
+        let salt = BASE64_NOPAD.decode(parts.next()?.as_bytes()).ok()?;
+        let hash = BASE64_NOPAD.decode(parts.next()?.as_bytes()).ok()?;
+        match parts.next() {
+            Some(_) => None,
+            None => Some(Self {
+                algorithm,
+                iterations,
+                salt,
+                hash,
+            }),
+        }
+    }
+
+    /// Whether this hash should be replaced with one made by `hasher`
+    fn outdated(&self, hasher: &Hasher) -> bool {
+        self.algorithm != ALGORITHM
+            || self.iterations < hasher.iterations
+            || self.hash.len() != HASH_LEN
+    }
+}
+
+/// The algorithms `Hasher` can verify, by their PHC identifier
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Algorithm {
+    Pbkdf2Sha256,
+}
+
+impl Algorithm {
+    fn from_id(id: &str) -> Option<Self> {
+        match id {
+            "pbkdf2-sha256" => Some(Self::Pbkdf2Sha256),
+            _ => None,
+        }
+    }
+
+    fn id(self) -> &'static str {
+        match self {
+            Self::Pbkdf2Sha256 => "pbkdf2-sha256",
+        }
+    }
+}
+
+/// The algorithm new hashes are made with
+const ALGORITHM: Algorithm = Algorithm::Pbkdf2Sha256;
+const SALT_LEN: usize = 16;
+const HASH_LEN: usize = 32;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn verify() {
+        let weak = Hasher::new(NonZeroU32::new(1_000).unwrap());
+        let hash = weak.hash("hunter2");
+        assert!(hash.starts_with("$pbkdf2-sha256$i=1000$"));
+        assert_ne!(hash, weak.hash("hunter2"));
+        assert_eq!(weak.verify("hunter2", &hash), Verification::Valid);
+        assert_eq!(weak.verify("hunter3", &hash), Verification::Invalid);
+        assert_eq!(
+            weak.verify("hunter2", "$argon2id$..."),
+            Verification::Invalid
+        );
+
+        let strong = Hasher::new(NonZeroU32::new(2_000).unwrap());
+        let rehashed = match strong.verify("hunter2", &hash) {
+            Verification::Rehash(rehashed) => rehashed,
+            verification => panic!("expected rehash, got {verification:?}"),
+        };
+        assert!(rehashed.starts_with("$pbkdf2-sha256$i=2000$"));
+        assert_eq!(strong.verify("hunter2", &rehashed), Verification::Valid);
+        assert_eq!(strong.verify("hunter3", &hash), Verification::Invalid);
+    }
+}
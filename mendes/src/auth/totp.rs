@@ -0,0 +1,200 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE32_NOPAD;
+use percent_encoding::{utf8_percent_encode, NON_ALPHANUMERIC};
+use ring::hmac::{self, HMAC_SHA1_FOR_LEGACY_USE_ONLY};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::utils::constant_time_eq;
+
+/// Time-based one-time passwords (RFC 6238), as used by authenticator apps
+///
+/// Generate a secret when the user enables two-factor authentication, show the provisioning
+/// URI as a QR code so they can add it to their app, then confirm with a first code before
+/// storing the secret:
+///
+/// ```no_run
+/// # use mendes::auth::totp::Totp;
+/// # use mendes::Error;
+/// # mod qrcode {
+/// #     pub fn render(uri: &str) -> Vec<u8> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn enable_totp(&self, id: u64, secret: &str, step: u64) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct Form {
+/// #     code: String,
+/// # }
+/// # struct User {
+/// #     id: u64,
+/// #     email: String,
+/// # }
+/// # async fn enable(db: &Db, user: User, form: Form) -> Result<(), Error> {
+/// let totp = Totp::generate();
+/// let qr = qrcode::render(&totp.uri("Example", &user.email));
+/// // ...
+/// if let Some(step) = totp.verify(&form.code, None) {
+///     db.enable_totp(user.id, &totp.secret(), step).await?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Codes use HMAC-SHA1 with 6 digits and 30 second steps, the defaults supported by all
+/// common authenticator apps.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Totp {
+    secret: Vec<u8>,
+    skew: u64,
+}
+
+impl Totp {
+    /// Generate a new random secret
+    pub fn generate() -> Self {
+        let mut secret = vec![0; SECRET_LEN];
+        SystemRandom::new()
+            .fill(&mut secret)
+            .expect("failed to generate random TOTP secret");
+        Self::new(secret)
+    }
+
+    /// Create from a raw secret
+    pub fn new(secret: Vec<u8>) -> Self {
+        Self { secret, skew: 1 }
+    }
+
+    /// Create from a base32-encoded secret, as returned by `secret()`
+    pub fn from_base32(secret: &str) -> Option<Self> {
+        let secret = secret.trim_end_matches('=').to_ascii_uppercase();
+        BASE32_NOPAD.decode(secret.as_bytes()).ok().map(Self::new)
+    }
+
+    /// Accept codes up to `skew` steps before or after the current one
+    ///
+    /// Defaults to 1, allowing for 30 seconds of clock drift (or of typing).
+    pub fn skew(mut self, skew: u64) -> Self {
+        self.skew = skew;
+        self
+    }
+
+    /// The base32-encoded secret, for storage or manual entry in an authenticator app
+    pub fn secret(&self) -> String {
+        BASE32_NOPAD.encode(&self.secret)
+    }
+
+    /// The `otpauth://` provisioning URI, to be shown as a QR code
+    pub fn uri(&self, issuer: &str, account: &str) -> String {
+        let issuer = utf8_percent_encode(issuer, NON_ALPHANUMERIC);
+        let account = utf8_percent_encode(account, NON_ALPHANUMERIC);
+        format!(
+            "otpauth://totp/{issuer}:{account}?secret={}&issuer={issuer}&algorithm=SHA1&digits={DIGITS}&period={PERIOD}",
+            self.secret()
+        )
+    }
+
+    /// Check `code` against the current time
+    ///
+    /// Returns the time step that matched. Store it and pass it as `last_step` for the next
+    /// verification, so that a code can't be used more than once.
+    pub fn verify(&self, code: &str, last_step: Option<u64>) -> Option<u64> {
+        self.verify_at(code, SystemTime::now(), last_step)
+    }
+
+    /// Check `code` against the given `time`
+    pub fn verify_at(&self, code: &str, time: SystemTime, last_step: Option<u64>) -> Option<u64> {
+        let code = code.trim();
+        if code.len() != DIGITS as usize || !code.bytes().all(|b| b.is_ascii_digit()) {
+            return None;
+        }
+
+        let current = step(time);
+        let mut matched = None;
+        // Check every step in the window, so the time taken doesn't reveal which one matched
+        for step in current.saturating_sub(self.skew)..=current.saturating_add(self.skew) {
+            let expected = format!("{:0width$}", self.code(step), width = DIGITS as usize);
+            if constant_time_eq(expected.as_bytes(), code.as_bytes()) {
+                matched = Some(step);
+            }
+        }
+
+        match (matched, last_step) {
+            (Some(step), Some(last)) if step <= last => None,
+            (matched, _) => matched,
+        }
+    }
+
+    /// The code for the given `time`
+    pub fn code_at(&self, time: SystemTime) -> String {
+        format!("{:0width$}", self.code(step(time)), width = DIGITS as usize)
+    }
+
+    fn code(&self, step: u64) -> u32 {
+        let key = hmac::Key::new(HMAC_SHA1_FOR_LEGACY_USE_ONLY, &self.secret);
+        let tag = hmac::sign(&key, &step.to_be_bytes());
+        let tag = tag.as_ref();
+
+        // Dynamic truncation, from RFC 4226 section 5.3
+        let offset = (tag[tag.len() - 1] & 0xf) as usize;
+        let bytes = [
+            tag[offset],
+            tag[offset + 1],
+            tag[offset + 2],
+            tag[offset + 3],
+        ];
+        (u32::from_be_bytes(bytes) & 0x7fff_ffff) % 10u32.pow(DIGITS)
+    }
+}
+
+fn step(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / PERIOD
+}
+
+const DIGITS: u32 = 6;
+const PERIOD: u64 = 30;
+const SECRET_LEN: usize = 20;
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::*;
+
+    #[test]
+    fn rfc6238() {
+        let totp = Totp::new(b"12345678901234567890".to_vec());
+        let at = |secs| UNIX_EPOCH + Duration::from_secs(secs);
+        assert_eq!(totp.code_at(at(59)), "287082");
+        assert_eq!(totp.code_at(at(1_111_111_109)), "081804");
+        assert_eq!(totp.code_at(at(2_000_000_000)), "279037");
+
+        let time = at(1_111_111_109);
+        let step = totp.verify_at("081804", time, None).unwrap();
+        assert_eq!(step, 1_111_111_109 / 30);
+        // Codes from adjacent steps are accepted, others aren't
+        assert!(totp
+            .verify_at("081804", time + Duration::from_secs(30), None)
+            .is_some());
+        assert!(totp
+            .verify_at("081804", time + Duration::from_secs(60), None)
+            .is_none());
+        assert!(totp.verify_at("081804", time, Some(step)).is_none());
+        assert!(totp.verify_at("81804", time, None).is_none());
+    }
+
+    #[test]
+    fn provisioning() {
+        let totp = Totp::generate();
+        assert_eq!(Totp::from_base32(&totp.secret()), Some(totp.clone()));
+        assert!(totp
+            .uri("Example Co", "user@example.com")
+            .starts_with("otpauth://totp/Example%20Co:user%40example%2Ecom?secret="));
+    }
+}
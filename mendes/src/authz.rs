@@ -0,0 +1,242 @@
+use std::borrow::Cow;
+use std::collections::{HashMap, HashSet};
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use http::request::Parts;
+use http::Response;
+
+use crate::application::{error_response, Application, Context, ErrorKind, FromContext, PathState};
+
+/// A permission that handlers can require
+///
+/// Permissions are usually unit structs:
+///
+/// ```no_run
+/// # use mendes::authz::Permission;
+/// struct EditPosts;
+///
+/// impl Permission for EditPosts {
+///     const NAME: &'static str = "posts:edit";
+/// }
+/// ```
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+/// Decides whether a request has a permission
+///
+/// Implement this for the application, mapping the request's session or token claims to
+/// permissions, for example through `Roles`:
+///
+/// ```no_run
+/// # #[cfg(feature = "cookies")]
+/// # mod example {
+/// # use mendes::authz::{Policy, Roles};
+/// # use mendes::cookies::{AppWithAeadKey, AppWithCookies, CookieData, Key};
+/// # use mendes::http::request::Parts;
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # struct Session {
+/// #     roles: Vec<String>,
+/// # }
+/// # impl CookieData for Session {
+/// #     const NAME: &'static str = "Session";
+/// # }
+/// # struct App {
+/// #     key: Key,
+/// #     roles: Roles,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithAeadKey for App {
+/// #     fn key(&self) -> &Key {
+/// #         &self.key
+/// #     }
+/// # }
+/// impl Policy for App {
+///     fn permits(&self, req: &Parts, permission: &str) -> bool {
+///         match self.cookie::<Session>(&req.headers) {
+///             Some(session) => self.roles.permits(&session.roles, permission),
+///             None => false,
+///         }
+///     }
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+pub trait Policy: Application {
+    fn permits(&self, req: &Parts, permission: &str) -> bool;
+}
+
+/// Requires the permission `P` for a handler
+///
+/// Requests without the permission are rejected with a `PermissionDenied` error, which
+/// results in a `403 Forbidden` response:
+///
+/// ```no_run
+/// # use mendes::authz::{Permission, Policy, Requires};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct EditPosts;
+/// # impl Permission for EditPosts {
+/// #     const NAME: &'static str = "posts:edit";
+/// # }
+/// # impl Policy for App {
+/// #     fn permits(&self, req: &Parts, permission: &str) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn publish(app: &App, _: Requires<EditPosts>, id: u64) -> Result<Response<Body>, Error> {
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+pub struct Requires<P>(PhantomData<P>);
+
+impl<'a, A: Policy, P: Permission> FromContext<'a, A> for Requires<P> {
+    fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        match app.permits(req, P::NAME) {
+            true => Ok(Requires(PhantomData)),
+            false => Err(state.reject::<A>(ErrorKind::PermissionDenied.into(), req)),
+        }
+    }
+}
+
+/// Require the permission `P` for all routes in a scope
+///
+/// Returns the error response if the request doesn't have the permission:
+///
+/// ```no_run
+/// # use mendes::authz::{self, Permission, Policy};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, route, scope, Body, Context, Error};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct ManageUsers;
+/// # impl Permission for ManageUsers {
+/// #     const NAME: &'static str = "users:manage";
+/// # }
+/// # impl Policy for App {
+/// #     fn permits(&self, req: &Parts, permission: &str) -> bool {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[scope]
+/// async fn admin(cx: &mut Context<App>) -> Response<Body> {
+///     if let Err(rsp) = authz::guard::<ManageUsers, _>(cx) {
+///         return rsp;
+///     }
+///
+///     route!(match cx.path() {
+///         Some("users") => users,
+///     })
+/// }
+/// # #[handler(GET)]
+/// # async fn users(_: &App) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # fn main() {}
+/// ```
+pub fn guard<P: Permission, A: Policy>(cx: &Context<A>) -> Result<(), Response<A::ResponseBody>> {
+    match cx.app.permits(&cx.req, P::NAME) {
+        true => Ok(()),
+        false => Err(error_response(
+            &*cx.app,
+            &cx.req,
+            ErrorKind::PermissionDenied.into(),
+        )),
+    }
+}
+
+/// A mapping from roles to the permissions they grant
+#[derive(Clone, Debug, Default)]
+pub struct Roles {
+    roles: HashMap<Cow<'static, str>, HashSet<Cow<'static, str>>>,
+}
+
+impl Roles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Grant `permissions` to `role`, in addition to any permissions granted earlier
+    pub fn role<I, S>(mut self, role: impl Into<Cow<'static, str>>, permissions: I) -> Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<Cow<'static, str>>,
+    {
+        self.roles
+            .entry(role.into())
+            .or_default()
+            .extend(permissions.into_iter().map(Into::into));
+        self
+    }
+
+    /// Whether any of `roles` grants `permission`
+    pub fn permits<I, S>(&self, roles: I, permission: &str) -> bool
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<str>,
+    {
+        roles.into_iter().any(|role| {
+            self.roles
+                .get(role.as_ref())
+                .is_some_and(|permissions| permissions.contains(permission))
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn roles() {
+        let roles = Roles::new()
+            .role("editor", ["posts:edit"])
+            .role("admin", ["users:manage"])
+            .role("admin", ["posts:edit"]);
+
+        assert!(roles.permits(["editor"], "posts:edit"));
+        assert!(!roles.permits(["editor"], "users:manage"));
+        assert!(roles.permits(
+            vec!["viewer".to_owned(), "admin".to_owned()],
+            "users:manage"
+        ));
+        assert!(!roles.permits(Vec::<String>::new(), "posts:edit"));
+    }
+}
@@ -0,0 +1,383 @@
+use std::marker::PhantomData;
+
+use serde::ser::{self, Serialize};
+
+/// How a self-describing binary format like MessagePack or CBOR encodes each kind of value
+pub(crate) trait Format {
+    type Error: ser::Error;
+
+    fn null(out: &mut Vec<u8>);
+    fn bool(out: &mut Vec<u8>, v: bool);
+    fn int(out: &mut Vec<u8>, v: i128) -> Result<(), Self::Error>;
+    fn f32(out: &mut Vec<u8>, v: f32);
+    fn f64(out: &mut Vec<u8>, v: f64);
+    fn str(out: &mut Vec<u8>, v: &str) -> Result<(), Self::Error>;
+    fn bytes(out: &mut Vec<u8>, v: &[u8]) -> Result<(), Self::Error>;
+    /// The header of a sequence of `len` values
+    fn seq(out: &mut Vec<u8>, len: usize) -> Result<(), Self::Error>;
+    /// The header of a map of `len` key-value pairs
+    fn map(out: &mut Vec<u8>, len: usize) -> Result<(), Self::Error>;
+}
+
+/// Serialize `value` in format `F`
+///
+/// Structs are maps keyed by field name, and enums are externally tagged: unit variants are
+/// just their name, other variants a single-entry map from their name to their contents.
+pub(crate) fn to_vec<F: Format, T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, F::Error> {
+    let mut out = Vec::new();
+    value.serialize(Serializer::<F>::new(&mut out))?;
+    Ok(out)
+}
+
+struct Serializer<'a, F> {
+    out: &'a mut Vec<u8>,
+    format: PhantomData<F>,
+}
+
+impl<'a, F: Format> Serializer<'a, F> {
+    fn new(out: &'a mut Vec<u8>) -> Self {
+        Self {
+            out,
+            format: PhantomData,
+        }
+    }
+
+    /// Start the single-entry map holding a non-unit enum variant
+    fn variant(&mut self, variant: &str) -> Result<(), F::Error> {
+        F::map(self.out, 1)?;
+        F::str(self.out, variant)
+    }
+}
+
+impl<'a, F: Format> ser::Serializer for Serializer<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+    type SerializeSeq = Compound<'a, F>;
+    type SerializeTuple = Compound<'a, F>;
+    type SerializeTupleStruct = Compound<'a, F>;
+    type SerializeTupleVariant = Compound<'a, F>;
+    type SerializeMap = Compound<'a, F>;
+    type SerializeStruct = Compound<'a, F>;
+    type SerializeStructVariant = Compound<'a, F>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), F::Error> {
+        F::bool(self.out, v);
+        Ok(())
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_i128(self, v: i128) -> Result<(), F::Error> {
+        F::int(self.out, v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), F::Error> {
+        F::int(self.out, v.into())
+    }
+
+    fn serialize_u128(self, v: u128) -> Result<(), F::Error> {
+        match i128::try_from(v) {
+            Ok(v) => F::int(self.out, v),
+            Err(_) => Err(ser::Error::custom("integer out of range")),
+        }
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), F::Error> {
+        F::f32(self.out, v);
+        Ok(())
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), F::Error> {
+        F::f64(self.out, v);
+        Ok(())
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), F::Error> {
+        F::str(self.out, v.encode_utf8(&mut [0; 4]))
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), F::Error> {
+        F::str(self.out, v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), F::Error> {
+        F::bytes(self.out, v)
+    }
+
+    fn serialize_none(self) -> Result<(), F::Error> {
+        F::null(self.out);
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), F::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), F::Error> {
+        F::null(self.out);
+        Ok(())
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), F::Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), F::Error> {
+        F::str(self.out, variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), F::Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        mut self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), F::Error> {
+        self.variant(variant)?;
+        value.serialize(self)
+    }
+
+    fn serialize_seq(self, len: Option<usize>) -> Result<Compound<'a, F>, F::Error> {
+        Compound::new(self.out, len, false)
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<Compound<'a, F>, F::Error> {
+        Compound::new(self.out, Some(len), false)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, F>, F::Error> {
+        Compound::new(self.out, Some(len), false)
+    }
+
+    fn serialize_tuple_variant(
+        mut self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, F>, F::Error> {
+        self.variant(variant)?;
+        Compound::new(self.out, Some(len), false)
+    }
+
+    fn serialize_map(self, len: Option<usize>) -> Result<Compound<'a, F>, F::Error> {
+        Compound::new(self.out, len, true)
+    }
+
+    fn serialize_struct(self, _: &'static str, len: usize) -> Result<Compound<'a, F>, F::Error> {
+        Compound::new(self.out, Some(len), true)
+    }
+
+    fn serialize_struct_variant(
+        mut self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        len: usize,
+    ) -> Result<Compound<'a, F>, F::Error> {
+        self.variant(variant)?;
+        Compound::new(self.out, Some(len), true)
+    }
+}
+
+struct Compound<'a, F> {
+    out: &'a mut Vec<u8>,
+    /// The items so far and their number, if the header had to wait for the length
+    pending: Option<(Vec<u8>, usize)>,
+    map: bool,
+    format: PhantomData<F>,
+}
+
+impl<'a, F: Format> Compound<'a, F> {
+    fn new(out: &'a mut Vec<u8>, len: Option<usize>, map: bool) -> Result<Self, F::Error> {
+        let pending = match len {
+            Some(len) => {
+                Self::header(out, len, map)?;
+                None
+            }
+            None => Some((Vec::new(), 0)),
+        };
+
+        Ok(Self {
+            out,
+            pending,
+            map,
+            format: PhantomData,
+        })
+    }
+
+    /// Serialize the next item, where a map entry counts as a single item
+    fn item<T: Serialize + ?Sized>(&mut self, value: &T, first: bool) -> Result<(), F::Error> {
+        let out = match &mut self.pending {
+            Some((items, len)) => {
+                *len += usize::from(first);
+                items
+            }
+            None => &mut *self.out,
+        };
+        value.serialize(Serializer::<F>::new(out))
+    }
+
+    fn finish(self) -> Result<(), F::Error> {
+        if let Some((items, len)) = self.pending {
+            Self::header(self.out, len, self.map)?;
+            self.out.extend_from_slice(&items);
+        }
+        Ok(())
+    }
+
+    fn header(out: &mut Vec<u8>, len: usize, map: bool) -> Result<(), F::Error> {
+        match map {
+            true => F::map(out, len),
+            false => F::seq(out, len),
+        }
+    }
+}
+
+impl<'a, F: Format> ser::SerializeSeq for Compound<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), F::Error> {
+        self.item(value, true)
+    }
+
+    fn end(self) -> Result<(), F::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, F: Format> ser::SerializeTuple for Compound<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), F::Error> {
+        self.item(value, true)
+    }
+
+    fn end(self) -> Result<(), F::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, F: Format> ser::SerializeTupleStruct for Compound<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), F::Error> {
+        self.item(value, true)
+    }
+
+    fn end(self) -> Result<(), F::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, F: Format> ser::SerializeTupleVariant for Compound<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), F::Error> {
+        self.item(value, true)
+    }
+
+    fn end(self) -> Result<(), F::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, F: Format> ser::SerializeMap for Compound<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), F::Error> {
+        self.item(key, true)
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), F::Error> {
+        self.item(value, false)
+    }
+
+    fn end(self) -> Result<(), F::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, F: Format> ser::SerializeStruct for Compound<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), F::Error> {
+        self.item(key, true)?;
+        self.item(value, false)
+    }
+
+    fn end(self) -> Result<(), F::Error> {
+        self.finish()
+    }
+}
+
+impl<'a, F: Format> ser::SerializeStructVariant for Compound<'a, F> {
+    type Ok = ();
+    type Error = F::Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), F::Error> {
+        self.item(key, true)?;
+        self.item(value, false)
+    }
+
+    fn end(self) -> Result<(), F::Error> {
+        self.finish()
+    }
+}
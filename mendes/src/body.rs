@@ -16,7 +16,6 @@ use bytes::{Buf, Bytes, BytesMut};
 #[cfg(any(feature = "brotli", feature = "deflate", feature = "gzip"))]
 use http::header::{ACCEPT_ENCODING, CONTENT_ENCODING};
 use http::request::Parts;
-#[cfg(any(feature = "brotli", feature = "deflate", feature = "gzip"))]
 use http::HeaderMap;
 #[cfg(any(feature = "brotli", feature = "deflate", feature = "gzip"))]
 use http::{request, HeaderValue, Response};
@@ -66,6 +65,27 @@ impl Body {
             done: false,
         }
     }
+
+    /// Stream the body from `reader`
+    ///
+    /// See `ReaderBody` to configure the chunk size or length.
+    #[cfg(feature = "reader")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "reader")))]
+    pub fn reader(reader: impl tokio::io::AsyncRead + Send + 'static) -> Self {
+        Self::stream(ReaderBody::new(reader))
+    }
+
+    /// Send the given trailers after the body's data
+    ///
+    /// Trailers are only transmitted over HTTP/2, or over HTTP/1.1 if the client indicated
+    /// support for them with a `TE: trailers` request header. Note that this turns the body
+    /// into a streaming body, so it will no longer be compressed by `EncodeResponse`.
+    pub fn with_trailers(self, trailers: HeaderMap) -> Self {
+        Self::stream(WithTrailers {
+            inner: self,
+            trailers: Some(trailers),
+        })
+    }
 }
 
 impl<'a, A: Application<RequestBody = Body>> FromContext<'a, A> for Body {
@@ -98,6 +118,7 @@ impl http_body::Body for Body {
 
         #[allow(unused_mut)] // Depends on features
         let mut buf = BytesMut::new();
+        #[allow(clippy::let_unit_value)] // Depends on features
         let result = match this.inner.project() {
             #[cfg(feature = "brotli")]
             PinnedBody::Brotli(encoder) => poll_read_buf(encoder, cx, &mut buf),
@@ -189,7 +210,8 @@ impl http_body::Body for Body {
             (false, InnerBody::Bytes(body)) => SizeHint::with_exact(body.len() as u64),
             #[cfg(feature = "hyper")]
             (false, InnerBody::Hyper(inner)) => inner.size_hint(),
-            (false, InnerBody::Lazy { .. } | InnerBody::Streaming(_)) => SizeHint::default(),
+            (false, InnerBody::Streaming(inner)) => inner.size_hint(),
+            (false, InnerBody::Lazy { .. }) => SizeHint::default(),
             #[cfg(any(feature = "brotli", feature = "deflate", feature = "gzip"))]
             (false, InnerBody::Brotli(_) | InnerBody::Deflate(_) | InnerBody::Gzip(_)) => {
                 let mut hint = SizeHint::default();
@@ -201,6 +223,180 @@ impl http_body::Body for Body {
     }
 }
 
+#[pin_project]
+struct WithTrailers {
+    #[pin]
+    inner: Body,
+    trailers: Option<HeaderMap>,
+}
+
+impl http_body::Body for WithTrailers {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        match ready!(this.inner.poll_frame(cx)) {
+            Some(result) => Poll::Ready(Some(result)),
+            None => Poll::Ready(this.trailers.take().map(|t| Ok(Frame::trailers(t)))),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream() && self.trailers.is_none()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// A body streaming data from an `AsyncRead`
+///
+/// This serves data from pipes, archives and object storage clients without buffering all of
+/// it first. Combine it with `Attachment` to set download headers:
+///
+/// ```no_run
+/// # use std::pin::Pin;
+/// # use mendes::attachment::Attachment;
+/// # use mendes::body::ReaderBody;
+/// # use mendes::{handler, Body, Error};
+/// # use tokio::io::AsyncRead;
+/// # struct Object {
+/// #     name: String,
+/// #     reader: Pin<Box<dyn AsyncRead + Send>>,
+/// #     size: u64,
+/// # }
+/// # struct Storage;
+/// # impl Storage {
+/// #     async fn get(&self, key: &str) -> Result<Object, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     storage: Storage,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn download(app: &App, key: String) -> Result<Attachment, Error> {
+///     let object = app.storage.get(&key).await?;
+///     let body = ReaderBody::new(object.reader).len(object.size);
+///     Ok(Attachment::new(object.name, Body::stream(body)))
+/// }
+/// # fn main() {}
+/// ```
+#[cfg(feature = "reader")]
+#[cfg_attr(docsrs, doc(cfg(feature = "reader")))]
+#[pin_project]
+pub struct ReaderBody<R> {
+    #[pin]
+    reader: R,
+    chunk_size: usize,
+    remaining: Option<u64>,
+    done: bool,
+}
+
+#[cfg(feature = "reader")]
+impl<R: tokio::io::AsyncRead> ReaderBody<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            chunk_size: 64 * 1024,
+            remaining: None,
+            done: false,
+        }
+    }
+
+    /// Read at most `size` bytes per chunk, 64 KiB by default
+    pub fn chunk_size(mut self, size: usize) -> Self {
+        self.chunk_size = size.max(1);
+        self
+    }
+
+    /// Set the number of bytes the reader will yield
+    ///
+    /// This lets the response carry a `Content-Length` instead of using chunked encoding.
+    /// Reading fails if the reader yields fewer bytes; only `len` bytes are read from it.
+    pub fn len(mut self, len: u64) -> Self {
+        self.remaining = Some(len);
+        self
+    }
+}
+
+#[cfg(feature = "reader")]
+impl<R: tokio::io::AsyncRead> http_body::Body for ReaderBody<R> {
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut std::task::Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        if *this.done || *this.remaining == Some(0) {
+            *this.done = true;
+            return Poll::Ready(None);
+        }
+
+        let size = match *this.remaining {
+            Some(remaining) => remaining.min(*this.chunk_size as u64) as usize,
+            None => *this.chunk_size,
+        };
+
+        let mut buf = BytesMut::zeroed(size);
+        let mut read_buf = tokio::io::ReadBuf::new(&mut buf);
+        if let Err(error) = ready!(this.reader.poll_read(cx, &mut read_buf)) {
+            *this.done = true;
+            return Poll::Ready(Some(Err(error)));
+        }
+
+        let len = read_buf.filled().len();
+        match (len, this.remaining.as_mut()) {
+            (0, Some(_)) => {
+                *this.done = true;
+                Poll::Ready(Some(Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "reader ended before the expected length",
+                ))))
+            }
+            (0, None) => {
+                *this.done = true;
+                Poll::Ready(None)
+            }
+            (_, remaining) => {
+                if let Some(remaining) = remaining {
+                    *remaining -= len as u64;
+                }
+                buf.truncate(len);
+                Poll::Ready(Some(Ok(Frame::data(buf.freeze()))))
+            }
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done || self.remaining == Some(0)
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        match (self.done, self.remaining) {
+            (true, _) => SizeHint::with_exact(0),
+            (false, Some(remaining)) => SizeHint::with_exact(remaining),
+            (false, None) => SizeHint::default(),
+        }
+    }
+}
+
 #[cfg(feature = "hyper")]
 impl From<hyper::body::Incoming> for Body {
     fn from(inner: hyper::body::Incoming) -> Self {
@@ -299,6 +495,7 @@ pub trait EncodeResponse {
     fn encoded(self, req: &request::Parts) -> Self;
 }
 
+#[allow(clippy::large_enum_variant)] // Compression encoders are large
 #[pin_project(project = PinnedBody)]
 enum InnerBody {
     #[cfg(feature = "brotli")]
@@ -446,3 +643,30 @@ impl FromStr for Encoding {
         })
     }
 }
+
+#[cfg(all(test, feature = "reader"))]
+mod tests {
+    use http_body::Body as _;
+
+    use super::*;
+    use crate::utils::collect;
+
+    #[tokio::test]
+    async fn reader() {
+        let body = ReaderBody::new(&b"hello world"[..]).chunk_size(4);
+        assert_eq!(body.size_hint().exact(), None);
+        let mut body = std::pin::pin!(body);
+        let mut chunks = Vec::new();
+        while let Some(frame) = std::future::poll_fn(|cx| body.as_mut().poll_frame(cx)).await {
+            chunks.push(frame.unwrap().into_data().unwrap());
+        }
+        assert_eq!(chunks, ["hell", "o wo", "rld"]);
+
+        let body = ReaderBody::new(&b"hello world"[..]).len(5);
+        assert_eq!(body.size_hint().exact(), Some(5));
+        assert_eq!(collect(Body::stream(body)).await.unwrap(), "hello");
+
+        let body = ReaderBody::new(&b"hello"[..]).len(10);
+        assert!(collect(body).await.is_err());
+    }
+}
@@ -0,0 +1,410 @@
+use std::collections::HashMap;
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+#[cfg(feature = "quota")]
+use std::time::SystemTime;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use http::request::Parts;
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+
+use crate::application::{Application, FromContext, PathState};
+use crate::idempotency::{IdempotencyStore, Lookup, StoredResponse};
+#[cfg(feature = "quota")]
+use crate::quota::QuotaStore;
+
+#[cfg(feature = "redis")]
+#[cfg_attr(docsrs, doc(cfg(feature = "redis")))]
+pub mod redis;
+
+/// Give the `Cache` extractor access to the application's cache
+pub trait AppWithCache: Application {
+    type Cache: CacheStore;
+
+    fn cache(&self) -> &Self::Cache;
+}
+
+/// The application's cache, as a handler argument
+///
+/// ```no_run
+/// # #[cfg(feature = "redis")]
+/// # mod example {
+/// # use std::time::Duration;
+/// # use bytes::Bytes;
+/// # use mendes::cache::redis::Redis;
+/// # use mendes::cache::{AppWithCache, Cache, CacheStore};
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # struct Db;
+/// # impl Db {
+/// #     async fn render_report(&self, id: u64) -> Result<Vec<u8>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     cache: Redis,
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithCache for App {
+/// #     type Cache = Redis;
+/// #     fn cache(&self) -> &Redis {
+/// #         &self.cache
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn report(app: &App, cache: Cache<'_, Redis>, id: u64) -> Result<Response<Body>, Error> {
+///     let key = format!("report:{id}");
+///     let report = match cache.get(&key).await {
+///         Some(report) => report,
+///         None => {
+///             let report = Bytes::from(app.db.render_report(id).await?);
+///             cache.set(&key, report.clone(), Duration::from_secs(300)).await;
+///             report
+///         }
+///     };
+///     todo!()
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+pub struct Cache<'a, C>(pub &'a C);
+
+impl<'a, A: AppWithCache> FromContext<'a, A> for Cache<'a, A::Cache> {
+    fn from_context(
+        app: &'a Arc<A>,
+        _: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(Cache(app.cache()))
+    }
+}
+
+impl<C> Deref for Cache<'_, C> {
+    type Target = C;
+
+    fn deref(&self) -> &C {
+        self.0
+    }
+}
+
+/// A key-value cache with expiring entries, like Redis or Memcached
+///
+/// Every `CacheStore` is also a `QuotaStore` and an `IdempotencyStore`, so quotas and
+/// idempotency keys can share the application's cache:
+///
+/// ```no_run
+/// # #[cfg(all(feature = "quota", feature = "redis"))]
+/// # fn main() {
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// # use mendes::cache::redis::Redis;
+/// # use mendes::idempotency::Idempotency;
+/// # use mendes::quota::{Period, Quotas};
+/// let cache = Arc::new(Redis::new("localhost:6379"));
+/// let quotas = Quotas::new(cache.clone(), 1_000, Period::Day);
+/// let idempotency = Idempotency::new(cache.clone(), Duration::from_secs(24 * 3600));
+/// # }
+/// # #[cfg(not(all(feature = "quota", feature = "redis")))]
+/// # fn main() {}
+/// ```
+///
+/// Keys used by those are prefixed with `quota:` and `idempotency:` respectively.
+#[async_trait]
+pub trait CacheStore: Send + Sync {
+    async fn get(&self, key: &str) -> Option<Bytes>;
+
+    /// Store `value` for `key`, replacing any earlier value
+    async fn set(&self, key: &str, value: Bytes, ttl: Duration);
+
+    /// Store `value` for `key` only if there is no value for it yet
+    ///
+    /// Returns whether the value was stored. Implementations must make this atomic.
+    async fn add(&self, key: &str, value: Bytes, ttl: Duration) -> bool;
+
+    async fn delete(&self, key: &str);
+
+    /// Add `delta` to the counter stored for `key` and return the new value
+    ///
+    /// Counters are stored as decimal strings. Missing counters start at 0 and expire after
+    /// `ttl`; the expiry of existing counters is left alone. Implementations must make this
+    /// atomic.
+    async fn increment(&self, key: &str, delta: u64, ttl: Duration) -> u64;
+}
+
+#[async_trait]
+impl<C: CacheStore + ?Sized> CacheStore for Arc<C> {
+    async fn get(&self, key: &str) -> Option<Bytes> {
+        (**self).get(key).await
+    }
+
+    async fn set(&self, key: &str, value: Bytes, ttl: Duration) {
+        (**self).set(key, value, ttl).await
+    }
+
+    async fn add(&self, key: &str, value: Bytes, ttl: Duration) -> bool {
+        (**self).add(key, value, ttl).await
+    }
+
+    async fn delete(&self, key: &str) {
+        (**self).delete(key).await
+    }
+
+    async fn increment(&self, key: &str, delta: u64, ttl: Duration) -> u64 {
+        (**self).increment(key, delta, ttl).await
+    }
+}
+
+/// A `CacheStore` keeping entries in memory, for a single instance of the application
+///
+/// Expired entries are removed when they are accessed. Entries whose TTL reaches past the
+/// range of `Instant` never expire.
+#[derive(Debug, Default)]
+pub struct MemoryCache {
+    entries: Mutex<HashMap<String, (Bytes, Option<Instant>)>>,
+}
+
+impl MemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CacheStore for MemoryCache {
+    async fn get(&self, key: &str) -> Option<Bytes> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((value, expires)) if live(expires, Instant::now()) => Some(value.clone()),
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    async fn set(&self, key: &str, value: Bytes, ttl: Duration) {
+        let mut entries = self.entries.lock().unwrap();
+        entries.insert(key.to_owned(), (value, Instant::now().checked_add(ttl)));
+    }
+
+    async fn add(&self, key: &str, value: Bytes, ttl: Duration) -> bool {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        match entries.get(key) {
+            Some((_, expires)) if live(expires, now) => false,
+            _ => {
+                entries.insert(key.to_owned(), (value, now.checked_add(ttl)));
+                true
+            }
+        }
+    }
+
+    async fn delete(&self, key: &str) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    async fn increment(&self, key: &str, delta: u64, ttl: Duration) -> u64 {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        let (current, expires): (u64, _) = match entries.get(key) {
+            Some((value, expires)) if live(expires, now) => {
+                let value = std::str::from_utf8(value).ok();
+                (value.and_then(|v| v.parse().ok()).unwrap_or(0), *expires)
+            }
+            _ => (0, now.checked_add(ttl)),
+        };
+
+        let new = current.saturating_add(delta);
+        entries.insert(key.to_owned(), (Bytes::from(new.to_string()), expires));
+        new
+    }
+}
+
+/// Whether an entry expiring at `expires` (`None` for never) is still valid at `now`
+fn live(expires: &Option<Instant>, now: Instant) -> bool {
+    expires.map_or(true, |expires| expires > now)
+}
+
+#[cfg(feature = "quota")]
+#[async_trait]
+impl<C: CacheStore> QuotaStore for C {
+    async fn add(&self, key: &str, window: u64, amount: u64, expires: SystemTime) -> u64 {
+        let ttl = expires
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        self.increment(&format!("quota:{key}:{window}"), amount, ttl)
+            .await
+    }
+}
+
+#[async_trait]
+impl<C: CacheStore> IdempotencyStore for C {
+    async fn begin(&self, key: &str, fingerprint: &str, ttl: Duration) -> Lookup {
+        let key = format!("idempotency:{key}");
+        let entry = encode_entry(fingerprint, None);
+        if CacheStore::add(self, &key, entry, ttl).await {
+            return Lookup::New;
+        }
+
+        // If the entry expired in the meantime, let the client retry later
+        let (stored, response) = match self.get(&key).await.and_then(decode_entry) {
+            Some(entry) => entry,
+            None => return Lookup::InProgress,
+        };
+
+        match response {
+            _ if stored != fingerprint => Lookup::Mismatch,
+            Some(response) => Lookup::Completed(response),
+            None => Lookup::InProgress,
+        }
+    }
+
+    async fn complete(&self, key: &str, response: StoredResponse, ttl: Duration) {
+        let key = format!("idempotency:{key}");
+        let fingerprint = match self.get(&key).await.and_then(decode_entry) {
+            Some((fingerprint, _)) => fingerprint,
+            None => return,
+        };
+        self.set(&key, encode_entry(&fingerprint, Some(&response)), ttl)
+            .await;
+    }
+
+    async fn release(&self, key: &str) {
+        self.delete(&format!("idempotency:{key}")).await;
+    }
+}
+
+/// Encode an idempotency entry as the fingerprint, optionally followed by the response
+fn encode_entry(fingerprint: &str, response: Option<&StoredResponse>) -> Bytes {
+    let mut buf = BytesMut::new();
+    put_bytes(&mut buf, fingerprint.as_bytes());
+    if let Some(response) = response {
+        buf.put_u16(response.status.as_u16());
+        buf.put_u32(response.headers.len() as u32);
+        for (name, value) in &response.headers {
+            put_bytes(&mut buf, name.as_str().as_bytes());
+            put_bytes(&mut buf, value.as_bytes());
+        }
+        buf.put_slice(&response.body);
+    }
+    buf.freeze()
+}
+
+fn decode_entry(mut buf: Bytes) -> Option<(String, Option<StoredResponse>)> {
+    let fingerprint = String::from_utf8(get_bytes(&mut buf)?.to_vec()).ok()?;
+    if !buf.has_remaining() {
+        return Some((fingerprint, None));
+    }
+
+    let status = StatusCode::from_u16(buf.try_get_u16().ok()?).ok()?;
+    let mut headers = HeaderMap::new();
+    for _ in 0..buf.try_get_u32().ok()? {
+        let name = HeaderName::from_bytes(&get_bytes(&mut buf)?).ok()?;
+        let value = HeaderValue::from_maybe_shared(get_bytes(&mut buf)?).ok()?;
+        headers.append(name, value);
+    }
+
+    let response = StoredResponse {
+        status,
+        headers,
+        body: buf,
+    };
+    Some((fingerprint, Some(response)))
+}
+
+fn put_bytes(buf: &mut BytesMut, bytes: &[u8]) {
+    buf.put_u32(bytes.len() as u32);
+    buf.put_slice(bytes);
+}
+
+fn get_bytes(buf: &mut Bytes) -> Option<Bytes> {
+    let len = buf.try_get_u32().ok()? as usize;
+    match buf.remaining() >= len {
+        true => Some(buf.split_to(len)),
+        false => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn memory() {
+        let cache = MemoryCache::new();
+        let ttl = Duration::from_secs(60);
+        assert_eq!(cache.get("a").await, None);
+        cache.set("a", Bytes::from("1"), ttl).await;
+        assert_eq!(cache.get("a").await, Some(Bytes::from("1")));
+        assert!(!CacheStore::add(&cache, "a", Bytes::from("2"), ttl).await);
+        assert_eq!(cache.increment("a", 2, ttl).await, 3);
+        cache.delete("a").await;
+        assert!(CacheStore::add(&cache, "a", Bytes::from("2"), ttl).await);
+
+        cache.set("b", Bytes::from("1"), Duration::ZERO).await;
+        assert_eq!(cache.get("b").await, None);
+
+        cache.set("c", Bytes::from("1"), Duration::MAX).await;
+        assert_eq!(cache.get("c").await, Some(Bytes::from("1")));
+        assert!(!CacheStore::add(&cache, "c", Bytes::from("2"), Duration::MAX).await);
+        assert_eq!(
+            cache.increment("c", u64::MAX, Duration::MAX).await,
+            u64::MAX
+        );
+    }
+
+    #[tokio::test]
+    async fn idempotency() {
+        let cache = MemoryCache::new();
+        let ttl = Duration::from_secs(60);
+        assert!(matches!(cache.begin("k", "POST /", ttl).await, Lookup::New));
+        assert!(matches!(
+            cache.begin("k", "POST /", ttl).await,
+            Lookup::InProgress
+        ));
+
+        let mut headers = HeaderMap::new();
+        headers.insert("content-type", HeaderValue::from_static("text/plain"));
+        let response = StoredResponse {
+            status: StatusCode::CREATED,
+            headers,
+            body: Bytes::from("created"),
+        };
+        cache.complete("k", response, ttl).await;
+        match cache.begin("k", "POST /", ttl).await {
+            Lookup::Completed(rsp) => {
+                assert_eq!(rsp.status, StatusCode::CREATED);
+                assert_eq!(rsp.headers["content-type"], "text/plain");
+                assert_eq!(rsp.body, "created");
+            }
+            lookup => panic!("unexpected lookup: {lookup:?}"),
+        }
+        assert!(matches!(
+            cache.begin("k", "PATCH /", ttl).await,
+            Lookup::Mismatch
+        ));
+    }
+
+    #[cfg(feature = "quota")]
+    #[tokio::test]
+    async fn quota() {
+        let cache = MemoryCache::new();
+        let expires = SystemTime::now() + Duration::from_secs(60);
+        assert_eq!(QuotaStore::add(&cache, "client", 7, 2, expires).await, 2);
+        assert_eq!(QuotaStore::add(&cache, "client", 7, 1, expires).await, 3);
+        assert_eq!(QuotaStore::add(&cache, "client", 8, 1, expires).await, 1);
+    }
+}
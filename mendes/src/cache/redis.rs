@@ -0,0 +1,235 @@
+use std::future::Future;
+use std::io;
+use std::pin::Pin;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use thiserror::Error;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::sync::Mutex;
+
+use super::CacheStore;
+
+/// A `CacheStore` backed by a Redis server
+///
+/// Commands are sent over a single connection, which is (re)established on demand. Failed
+/// commands (for example because the server is unreachable) are treated as cache misses:
+/// `get()` returns `None`, `add()` returns `false` and `increment()` returns `delta`, so that
+/// an outage doesn't take the application down with it.
+///
+/// This speaks plain RESP without TLS, for servers on the local host or network.
+pub struct Redis {
+    addr: String,
+    password: Option<String>,
+    conn: Mutex<Option<BufReader<TcpStream>>>,
+}
+
+impl Redis {
+    /// Create a client for the server at `addr`, like `"localhost:6379"`
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            password: None,
+            conn: Mutex::new(None),
+        }
+    }
+
+    /// Authenticate with `password` after connecting
+    pub fn password(mut self, password: impl Into<String>) -> Self {
+        self.password = Some(password.into());
+        self
+    }
+
+    /// Send a command and return its reply
+    ///
+    /// Server errors are returned as `Error::Server`.
+    pub async fn query(&self, args: &[&[u8]]) -> Result<Value, Error> {
+        let mut conn = self.conn.lock().await;
+        let stream = match &mut *conn {
+            Some(stream) => stream,
+            None => conn.insert(self.connect().await?),
+        };
+
+        let result = command(stream, args).await;
+        if matches!(result, Err(Error::Io(_) | Error::Protocol(_))) {
+            // The connection is in an unknown state, so start over for the next command
+            *conn = None;
+        }
+        result
+    }
+
+    async fn connect(&self) -> Result<BufReader<TcpStream>, Error> {
+        let mut stream = BufReader::new(TcpStream::connect(&self.addr).await?);
+        if let Some(password) = &self.password {
+            command(&mut stream, &[b"AUTH", password.as_bytes()]).await?;
+        }
+        Ok(stream)
+    }
+}
+
+#[async_trait]
+impl CacheStore for Redis {
+    async fn get(&self, key: &str) -> Option<Bytes> {
+        match self.query(&[b"GET", key.as_bytes()]).await {
+            Ok(Value::Bulk(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    async fn set(&self, key: &str, value: Bytes, ttl: Duration) {
+        let ttl = millis(ttl);
+        let args: [&[u8]; 5] = [b"SET", key.as_bytes(), &value, b"PX", ttl.as_bytes()];
+        let _ = self.query(&args).await;
+    }
+
+    async fn add(&self, key: &str, value: Bytes, ttl: Duration) -> bool {
+        let ttl = millis(ttl);
+        let args: [&[u8]; 6] = [b"SET", key.as_bytes(), &value, b"NX", b"PX", ttl.as_bytes()];
+        matches!(self.query(&args).await, Ok(Value::Simple(_)))
+    }
+
+    async fn delete(&self, key: &str) {
+        let _ = self.query(&[b"DEL", key.as_bytes()]).await;
+    }
+
+    async fn increment(&self, key: &str, delta: u64, ttl: Duration) -> u64 {
+        let (delta_str, ttl) = (delta.to_string(), millis(ttl));
+        let args: [&[u8]; 6] = [
+            b"EVAL",
+            INCREMENT.as_bytes(),
+            b"1",
+            key.as_bytes(),
+            delta_str.as_bytes(),
+            ttl.as_bytes(),
+        ];
+        match self.query(&args).await {
+            Ok(Value::Int(value)) => value as u64,
+            _ => delta,
+        }
+    }
+}
+
+/// A reply from the Redis server
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Value {
+    Nil,
+    Simple(String),
+    Int(i64),
+    Bulk(Bytes),
+    Array(Vec<Value>),
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("Redis protocol error: {0}")]
+    Protocol(String),
+    #[error("Redis error: {0}")]
+    Server(String),
+}
+
+async fn command(stream: &mut BufReader<TcpStream>, args: &[&[u8]]) -> Result<Value, Error> {
+    let mut buf = format!("*{}\r\n", args.len()).into_bytes();
+    for arg in args {
+        buf.extend_from_slice(format!("${}\r\n", arg.len()).as_bytes());
+        buf.extend_from_slice(arg);
+        buf.extend_from_slice(b"\r\n");
+    }
+    stream.get_mut().write_all(&buf).await?;
+    read_value(stream).await
+}
+
+/// Read a RESP2 value
+fn read_value<R: AsyncBufRead + Unpin + Send>(
+    reader: &mut R,
+) -> Pin<Box<dyn Future<Output = Result<Value, Error>> + Send + '_>> {
+    Box::pin(async move {
+        let mut line = String::new();
+        if reader.read_line(&mut line).await? == 0 {
+            return Err(Error::Protocol("connection closed".into()));
+        }
+
+        let line = line.trim_end_matches("\r\n");
+        let (kind, rest) = match line.as_bytes().first() {
+            Some(kind) => (*kind, &line[1..]),
+            None => return Err(Error::Protocol("empty reply".into())),
+        };
+
+        let len = || match rest.parse::<i64>() {
+            Ok(len) => Ok(len),
+            Err(_) => Err(Error::Protocol(format!("invalid length {rest:?}"))),
+        };
+
+        match kind {
+            b'+' => Ok(Value::Simple(rest.to_owned())),
+            b'-' => Err(Error::Server(rest.to_owned())),
+            b':' => Ok(Value::Int(len()?)),
+            b'$' => match usize::try_from(len()?) {
+                Ok(len) => {
+                    let mut buf = vec![0; len + 2];
+                    reader.read_exact(&mut buf).await?;
+                    buf.truncate(len);
+                    Ok(Value::Bulk(Bytes::from(buf)))
+                }
+                Err(_) => Ok(Value::Nil),
+            },
+            b'*' => match usize::try_from(len()?) {
+                Ok(len) => {
+                    let mut values = Vec::with_capacity(len.min(1024));
+                    for _ in 0..len {
+                        values.push(read_value(reader).await?);
+                    }
+                    Ok(Value::Array(values))
+                }
+                Err(_) => Ok(Value::Nil),
+            },
+            _ => Err(Error::Protocol(format!("unknown reply type {line:?}"))),
+        }
+    })
+}
+
+fn millis(ttl: Duration) -> String {
+    // Redis rejects an expiry of 0
+    ttl.as_millis().max(1).to_string()
+}
+
+/// Increment a counter, setting the expiry only when it's created
+const INCREMENT: &str = "local v = redis.call('INCRBY', KEYS[1], ARGV[1]) \
+if v == tonumber(ARGV[1]) then redis.call('PEXPIRE', KEYS[1], ARGV[2]) end \
+return v";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn parse() {
+        let mut input =
+            &b"+OK\r\n:42\r\n$5\r\nhello\r\n$-1\r\n*2\r\n:1\r\n$0\r\n\r\n-ERR wrong\r\n"[..];
+        assert_eq!(
+            read_value(&mut input).await.unwrap(),
+            Value::Simple("OK".into())
+        );
+        assert_eq!(read_value(&mut input).await.unwrap(), Value::Int(42));
+        assert_eq!(
+            read_value(&mut input).await.unwrap(),
+            Value::Bulk(Bytes::from("hello"))
+        );
+        assert_eq!(read_value(&mut input).await.unwrap(), Value::Nil);
+        assert_eq!(
+            read_value(&mut input).await.unwrap(),
+            Value::Array(vec![Value::Int(1), Value::Bulk(Bytes::new())])
+        );
+        assert!(matches!(
+            read_value(&mut input).await,
+            Err(Error::Server(msg)) if msg == "ERR wrong"
+        ));
+        assert!(matches!(
+            read_value(&mut input).await,
+            Err(Error::Protocol(_))
+        ));
+    }
+}
@@ -0,0 +1,276 @@
+use std::fmt;
+use std::time::Duration;
+
+use http::header::{HeaderName, CACHE_CONTROL, VARY};
+use http::{HeaderValue, Response};
+
+/// A typed `Cache-Control` header value
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use mendes::cache_control::{vary, CacheControl};
+/// # use mendes::http::header::ACCEPT_LANGUAGE;
+/// # use mendes::http::Response;
+/// # struct Page;
+/// # fn render(page: &Page) -> Result<Response<String>, std::fmt::Error> {
+/// #     todo!()
+/// # }
+/// # fn example(page: Page) -> Result<(), std::fmt::Error> {
+/// let mut rsp = render(&page)?;
+/// CacheControl::public()
+///     .max_age(Duration::from_secs(60))
+///     .s_maxage(Duration::from_secs(3600))
+///     .stale_while_revalidate(Duration::from_secs(30))
+///     .apply(&mut rsp);
+/// vary(&mut rsp, ACCEPT_LANGUAGE);
+/// # Ok(())
+/// # }
+/// ```
+///
+/// `max-age` applies to all caches, while `s-maxage` overrides it for shared caches like
+/// CDNs and reverse proxies. Responses that must be revalidated on every use (usually
+/// together with an `ETag`) can use `no_cache()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CacheControl {
+    public: bool,
+    private: bool,
+    no_cache: bool,
+    no_store: bool,
+    max_age: Option<Duration>,
+    s_maxage: Option<Duration>,
+    stale_while_revalidate: Option<Duration>,
+    stale_if_error: Option<Duration>,
+    must_revalidate: bool,
+    immutable: bool,
+}
+
+impl CacheControl {
+    /// Allow shared caches to store the response
+    pub fn public() -> Self {
+        Self {
+            public: true,
+            ..Self::default()
+        }
+    }
+
+    /// Only allow the client's own cache to store the response
+    pub fn private() -> Self {
+        Self {
+            private: true,
+            ..Self::default()
+        }
+    }
+
+    /// Allow caches to store the response, but only use it after revalidating
+    pub fn no_cache() -> Self {
+        Self {
+            no_cache: true,
+            ..Self::default()
+        }
+    }
+
+    /// Prevent all caches from storing the response
+    pub fn no_store() -> Self {
+        Self {
+            no_store: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Set the maximum age for shared caches
+    pub fn s_maxage(mut self, s_maxage: Duration) -> Self {
+        self.s_maxage = Some(s_maxage);
+        self
+    }
+
+    /// Allow caches to serve a stale response while they revalidate it in the background
+    pub fn stale_while_revalidate(mut self, duration: Duration) -> Self {
+        self.stale_while_revalidate = Some(duration);
+        self
+    }
+
+    /// Allow caches to serve a stale response if revalidation fails with a server error
+    pub fn stale_if_error(mut self, duration: Duration) -> Self {
+        self.stale_if_error = Some(duration);
+        self
+    }
+
+    /// Prevent caches from serving the response once it's stale
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+        self
+    }
+
+    /// Mark the response as never changing, like fingerprinted assets
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    /// Set the `Cache-Control` header on `rsp`, replacing any earlier value
+    pub fn apply<B>(&self, rsp: &mut Response<B>) {
+        rsp.headers_mut().insert(CACHE_CONTROL, self.into());
+    }
+}
+
+impl fmt::Display for CacheControl {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut first = true;
+        let mut directive = |f: &mut fmt::Formatter<'_>, name: &str, value: Option<Duration>| {
+            if !first {
+                f.write_str(", ")?;
+            }
+            first = false;
+            f.write_str(name)?;
+            match value {
+                Some(value) => write!(f, "={}", value.as_secs()),
+                None => Ok(()),
+            }
+        };
+
+        let flags = [
+            ("public", self.public),
+            ("private", self.private),
+            ("no-cache", self.no_cache),
+            ("no-store", self.no_store),
+        ];
+        for (name, set) in flags {
+            if set {
+                directive(f, name, None)?;
+            }
+        }
+
+        let durations = [
+            ("max-age", self.max_age),
+            ("s-maxage", self.s_maxage),
+            ("stale-while-revalidate", self.stale_while_revalidate),
+            ("stale-if-error", self.stale_if_error),
+        ];
+        for (name, value) in durations {
+            if value.is_some() {
+                directive(f, name, value)?;
+            }
+        }
+
+        let flags = [
+            ("must-revalidate", self.must_revalidate),
+            ("immutable", self.immutable),
+        ];
+        for (name, set) in flags {
+            if set {
+                directive(f, name, None)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl From<&CacheControl> for HeaderValue {
+    fn from(value: &CacheControl) -> Self {
+        // Directives and numbers are always valid header characters
+        HeaderValue::try_from(value.to_string()).unwrap()
+    }
+}
+
+impl From<CacheControl> for HeaderValue {
+    fn from(value: CacheControl) -> Self {
+        Self::from(&value)
+    }
+}
+
+/// Add `name` to the `Vary` header of `rsp`, unless it's already listed
+///
+/// Caches store a separate response for every combination of values of the request
+/// headers listed in `Vary`, so list every header that the response depends on.
+pub fn vary<B>(rsp: &mut Response<B>, name: HeaderName) {
+    let mut names = String::new();
+    for value in rsp.headers().get_all(VARY) {
+        let value = match value.to_str() {
+            Ok(value) => value,
+            Err(_) => continue,
+        };
+
+        for existing in value.split(',').map(str::trim) {
+            if existing == "*" || existing.eq_ignore_ascii_case(name.as_str()) {
+                return;
+            }
+            if !existing.is_empty() {
+                if !names.is_empty() {
+                    names.push_str(", ");
+                }
+                names.push_str(existing);
+            }
+        }
+    }
+
+    if !names.is_empty() {
+        names.push_str(", ");
+    }
+    names.push_str(name.as_str());
+    // Was made from valid header values and a header name
+    rsp.headers_mut()
+        .insert(VARY, HeaderValue::try_from(names).unwrap());
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::{ACCEPT_ENCODING, ACCEPT_LANGUAGE, COOKIE};
+
+    use super::*;
+
+    #[test]
+    fn cache_control() {
+        let value = CacheControl::public()
+            .max_age(Duration::from_secs(60))
+            .s_maxage(Duration::from_secs(3600))
+            .stale_while_revalidate(Duration::from_secs(30));
+        assert_eq!(
+            value.to_string(),
+            "public, max-age=60, s-maxage=3600, stale-while-revalidate=30"
+        );
+        assert_eq!(CacheControl::no_store().to_string(), "no-store");
+        assert_eq!(
+            CacheControl::private()
+                .max_age(Duration::ZERO)
+                .must_revalidate()
+                .to_string(),
+            "private, max-age=0, must-revalidate"
+        );
+
+        let mut rsp = Response::new(());
+        value.apply(&mut rsp);
+        assert_eq!(
+            rsp.headers()[CACHE_CONTROL],
+            "public, max-age=60, s-maxage=3600, stale-while-revalidate=30"
+        );
+    }
+
+    #[test]
+    fn vary_merge() {
+        let mut rsp = Response::new(());
+        vary(&mut rsp, ACCEPT_ENCODING);
+        assert_eq!(rsp.headers()[VARY], "accept-encoding");
+
+        rsp.headers_mut()
+            .append(VARY, HeaderValue::from_static("Cookie"));
+        vary(&mut rsp, ACCEPT_LANGUAGE);
+        vary(&mut rsp, COOKIE);
+        assert_eq!(
+            rsp.headers()[VARY],
+            "accept-encoding, Cookie, accept-language"
+        );
+        assert_eq!(rsp.headers().get_all(VARY).iter().count(), 1);
+
+        let mut rsp = Response::new(());
+        rsp.headers_mut()
+            .insert(VARY, HeaderValue::from_static("*"));
+        vary(&mut rsp, COOKIE);
+        assert_eq!(rsp.headers()[VARY], "*");
+    }
+}
@@ -0,0 +1,868 @@
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{Response, StatusCode};
+use http_body::Body as HttpBody;
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Serialize};
+use thiserror::Error;
+
+use crate::application::{self, check_content_type, Application, ErrorKind, FromContextAsync};
+use crate::application::{read_body, IntoResponse, PathState};
+use crate::binary::{self, Format};
+use crate::utils::negotiate;
+use crate::value::{Deserializer, Value};
+
+/// A CBOR request body or response
+///
+/// As an extractor, `Cbor<T>` accepts bodies of up to `MAX_LEN` bytes with an
+/// `application/cbor` content type. As a response, it is sent if the request's `Accept`
+/// header allows that, and rejected with a `NotAcceptable` error otherwise:
+///
+/// ```no_run
+/// # use async_trait::async_trait;
+/// # use mendes::http::Response;
+/// # use mendes::cbor::Cbor;
+/// # use mendes::{handler, Application, Body, Context, Error};
+/// # use serde::{Deserialize, Serialize};
+/// # struct App {}
+/// # #[async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: Context<Self>) -> Response<Body> { todo!() }
+/// # }
+/// #[derive(Deserialize, Serialize)]
+/// struct Order {
+///     id: u64,
+///     items: Vec<String>,
+/// }
+///
+/// #[handler(POST)]
+/// async fn submit(_: &App, #[async_extract] order: Cbor<Order>) -> Result<Cbor<Order>, Error> {
+///     let Cbor(mut order) = order;
+///     order.items.push("receipt".to_owned());
+///     Ok(Cbor(order))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Structs are maps keyed by field name, like in JSON. Tags are accepted but ignored, so a
+/// tagged date is read as its untagged string or number.
+pub struct Cbor<T>(pub T);
+
+#[async_trait]
+impl<'a, A, T> FromContextAsync<'a, A> for Cbor<T>
+where
+    A: Application + Sync,
+    A::RequestBody: HttpBody + Send,
+    <A::RequestBody as HttpBody>::Data: Send,
+    <A::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
+    T: DeserializeOwned,
+{
+    async fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let body = match body.take() {
+            Some(body) => body,
+            None => panic!("attempted to retrieve body twice"),
+        };
+
+        check_content_type(req, MEDIA_TYPES).map_err(|e| state.reject::<A>(e, req))?;
+        let bytes = read_body(body, MAX_LEN)
+            .await
+            .map_err(|e| state.reject::<A>(e, req))?;
+        match from_slice(&bytes) {
+            Ok(value) => Ok(Self(value)),
+            Err(e) => Err(state.reject::<A>(e.into(), req)),
+        }
+    }
+}
+
+impl<A: Application, T: Serialize> IntoResponse<A> for Cbor<T>
+where
+    A::ResponseBody: From<String> + From<Vec<u8>>,
+{
+    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        let media_type = match negotiate(req, MEDIA_TYPES) {
+            Some(media_type) => media_type,
+            None => {
+                return application::Error::from(ErrorKind::NotAcceptable).into_response(app, req)
+            }
+        };
+
+        match to_vec(&self.0) {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, media_type)
+                .body(body.into())
+                .unwrap(),
+            Err(e) => application::Error::internal(e).into_response(app, req),
+        }
+    }
+}
+
+/// Deserialize an instance of `T` from CBOR
+pub fn from_slice<T: DeserializeOwned>(input: &[u8]) -> Result<T, Error> {
+    let mut parser = Parser { input, pos: 0 };
+    let value = parser.value(0)?;
+    if parser.pos < input.len() {
+        return Err(parser.error("trailing bytes"));
+    }
+    T::deserialize(Deserializer::new(value))
+}
+
+/// Serialize `value` as CBOR
+///
+/// Integers and lengths use the smallest encoding that fits, and lengths are always definite.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    binary::to_vec::<CborFormat, _>(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn value(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+
+        let initial = self.take(1)?[0];
+        let (major, info) = (initial >> 5, initial & 0x1f);
+        if major == 7 {
+            return self.simple(info);
+        } else if info == 31 {
+            return match major {
+                2 => Ok(Value::Bytes(self.chunks(major)?)),
+                3 => {
+                    let bytes = self.chunks(major)?;
+                    self.str(bytes)
+                }
+                4 => self.seq(None, depth),
+                5 => self.map(None, depth),
+                _ => Err(self.error("invalid indefinite length")),
+            };
+        }
+
+        let arg = self.argument(info)?;
+        match major {
+            0 => Ok(Value::Int(arg.into())),
+            1 => Ok(Value::Int(-1 - i128::from(arg))),
+            2 => {
+                let len = self.len(arg)?;
+                Ok(Value::Bytes(self.take(len)?.to_vec()))
+            }
+            3 => {
+                let len = self.len(arg)?;
+                let bytes = self.take(len)?.to_vec();
+                self.str(bytes)
+            }
+            4 => {
+                let len = self.len(arg)?;
+                self.seq(Some(len), depth)
+            }
+            5 => {
+                let len = self.len(arg)?;
+                self.map(Some(len), depth)
+            }
+            // The tag number is `arg`, which we ignore
+            _ => self.value(depth + 1),
+        }
+    }
+
+    fn simple(&mut self, info: u8) -> Result<Value, Error> {
+        Ok(match info {
+            20 => Value::Bool(false),
+            21 => Value::Bool(true),
+            22 | 23 => Value::Null,
+            25 => Value::Float(f16(self.uint(2)? as u16)),
+            26 => Value::Float(f32::from_bits(self.uint(4)? as u32).into()),
+            27 => Value::Float(f64::from_bits(self.uint(8)?)),
+            31 => return Err(self.error("unexpected break")),
+            _ => return Err(self.error("unsupported simple value")),
+        })
+    }
+
+    /// Read items of a sequence up to its length, or up to a break if it has none
+    fn seq(&mut self, len: Option<usize>, depth: usize) -> Result<Value, Error> {
+        // Every value takes at least a byte, which bounds the allocation
+        let mut items = Vec::with_capacity(len.unwrap_or(0).min(self.input.len() - self.pos));
+        while self.more(items.len(), len) {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Seq(items))
+    }
+
+    fn map(&mut self, len: Option<usize>, depth: usize) -> Result<Value, Error> {
+        let mut entries = Vec::with_capacity(len.unwrap_or(0).min(self.input.len() - self.pos));
+        while self.more(entries.len(), len) {
+            entries.push((self.value(depth + 1)?, self.value(depth + 1)?));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    /// Whether a sequence or map with `read` items so far has more items
+    fn more(&mut self, read: usize, len: Option<usize>) -> bool {
+        match len {
+            Some(len) => read < len,
+            None => match self.input.get(self.pos) {
+                Some(0xff) => {
+                    self.pos += 1;
+                    false
+                }
+                // Let `value()` report the end of input
+                _ => true,
+            },
+        }
+    }
+
+    /// Concatenate the definite-length chunks of an indefinite-length byte or text string
+    fn chunks(&mut self, major: u8) -> Result<Vec<u8>, Error> {
+        let mut bytes = Vec::new();
+        loop {
+            let initial = self.take(1)?[0];
+            if initial == 0xff {
+                return Ok(bytes);
+            } else if initial >> 5 != major || initial & 0x1f == 31 {
+                return Err(self.error("invalid string chunk"));
+            }
+
+            let len = self.argument(initial & 0x1f)?;
+            let len = self.len(len)?;
+            bytes.extend_from_slice(self.take(len)?);
+        }
+    }
+
+    fn str(&self, bytes: Vec<u8>) -> Result<Value, Error> {
+        match String::from_utf8(bytes) {
+            Ok(s) => Ok(Value::Str(s)),
+            Err(_) => Err(self.error("invalid UTF-8")),
+        }
+    }
+
+    /// Read the argument following an initial byte with additional information `info`
+    fn argument(&mut self, info: u8) -> Result<u64, Error> {
+        match info {
+            0..=23 => Ok(info.into()),
+            24..=27 => self.uint(1 << (info - 24)),
+            _ => Err(self.error("invalid additional information")),
+        }
+    }
+
+    fn len(&self, len: u64) -> Result<usize, Error> {
+        usize::try_from(len).map_err(|_| self.error("length out of range"))
+    }
+
+    /// Read a big-endian unsigned integer of `size` bytes
+    fn uint(&mut self, size: usize) -> Result<u64, Error> {
+        let bytes = self.take(size)?;
+        Ok(bytes.iter().fold(0, |acc, &b| acc << 8 | u64::from(b)))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        match self.input.get(self.pos..).and_then(|rest| rest.get(..len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn error(&self, msg: &'static str) -> Error {
+        Error::Syntax(self.pos, msg)
+    }
+}
+
+/// Convert a half-precision float
+fn f16(bits: u16) -> f64 {
+    let exponent = i32::from(bits >> 10 & 0x1f);
+    let mantissa = f64::from(bits & 0x3ff);
+    let value = match exponent {
+        0 => mantissa * 2f64.powi(-24),
+        31 if mantissa == 0.0 => f64::INFINITY,
+        31 => f64::NAN,
+        _ => (mantissa + 1024.0) * 2f64.powi(exponent - 25),
+    };
+
+    match bits & 0x8000 {
+        0 => value,
+        _ => -value,
+    }
+}
+
+struct CborFormat;
+
+impl CborFormat {
+    /// Write an initial byte for `major` type with argument `arg`
+    fn head(out: &mut Vec<u8>, major: u8, arg: u64) {
+        let major = major << 5;
+        match arg {
+            0..=23 => out.push(major | arg as u8),
+            24..=0xff => out.extend([major | 24, arg as u8]),
+            0x100..=0xffff => {
+                out.push(major | 25);
+                out.extend((arg as u16).to_be_bytes());
+            }
+            0x1_0000..=0xffff_ffff => {
+                out.push(major | 26);
+                out.extend((arg as u32).to_be_bytes());
+            }
+            _ => {
+                out.push(major | 27);
+                out.extend(arg.to_be_bytes());
+            }
+        }
+    }
+}
+
+impl Format for CborFormat {
+    type Error = Error;
+
+    fn null(out: &mut Vec<u8>) {
+        out.push(0xf6);
+    }
+
+    fn bool(out: &mut Vec<u8>, v: bool) {
+        out.push(if v { 0xf5 } else { 0xf4 });
+    }
+
+    fn int(out: &mut Vec<u8>, v: i128) -> Result<(), Error> {
+        let (major, arg) = match v < 0 {
+            true => (1, u64::try_from(-1 - v)),
+            false => (0, u64::try_from(v)),
+        };
+
+        match arg {
+            Ok(arg) => Self::head(out, major, arg),
+            Err(_) => return Err(ser::Error::custom("integer out of range")),
+        }
+        Ok(())
+    }
+
+    fn f32(out: &mut Vec<u8>, v: f32) {
+        out.push(0xfa);
+        out.extend(v.to_be_bytes());
+    }
+
+    fn f64(out: &mut Vec<u8>, v: f64) {
+        out.push(0xfb);
+        out.extend(v.to_be_bytes());
+    }
+
+    fn str(out: &mut Vec<u8>, v: &str) -> Result<(), Error> {
+        Self::head(out, 3, v.len() as u64);
+        out.extend_from_slice(v.as_bytes());
+        Ok(())
+    }
+
+    fn bytes(out: &mut Vec<u8>, v: &[u8]) -> Result<(), Error> {
+        Self::head(out, 2, v.len() as u64);
+        out.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn seq(out: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+        Self::head(out, 4, len as u64);
+        Ok(())
+    }
+
+    fn map(out: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+        Self::head(out, 5, len as u64);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid CBOR at byte {0}: {1}")]
+    Syntax(usize, &'static str),
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// The maximum size of CBOR request bodies, in bytes
+pub const MAX_LEN: usize = 1024 * 1024;
+
+const MEDIA_TYPES: &[&str] = &["application/cbor"];
+const MAX_DEPTH: usize = 64;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Order {
+        id: u64,
+        note: Option<String>,
+        items: Vec<(String, f64)>,
+        status: Status,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Status {
+        Open,
+        Shipped { carrier: String },
+    }
+
+    #[test]
+    fn round_trip() {
+        let order = Order {
+            id: 300,
+            note: None,
+            items: vec![("tea".to_owned(), 2.5)],
+            status: Status::Shipped {
+                carrier: "post".to_owned(),
+            },
+        };
+
+        let bytes = to_vec(&order).unwrap();
+        assert_eq!(
+            bytes,
+            [
+                &[0xa4, 0x62][..],
+                b"id",
+                &[0x19, 0x01, 0x2c, 0x64],
+                b"note",
+                &[0xf6, 0x65],
+                b"items",
+                &[0x81, 0x82, 0x63],
+                b"tea",
+                &[0xfb, 0x40, 0x04, 0, 0, 0, 0, 0, 0, 0x66],
+                b"status",
+                &[0xa1, 0x67],
+                b"Shipped",
+                &[0xa1, 0x67],
+                b"carrier",
+                &[0x64],
+                b"post",
+            ]
+            .concat()
+        );
+        assert_eq!(from_slice::<Order>(&bytes).unwrap(), order);
+        assert_eq!(
+            from_slice::<Status>(&to_vec(&Status::Open).unwrap()).unwrap(),
+            Status::Open
+        );
+    }
+
+    #[test]
+    fn integers() {
+        // From the examples in RFC 8949, appendix A
+        for (v, encoded) in [
+            (0, &[0x00][..]),
+            (23, &[0x17]),
+            (24, &[0x18, 0x18]),
+            (1000, &[0x19, 0x03, 0xe8]),
+            (1_000_000, &[0x1a, 0x00, 0x0f, 0x42, 0x40]),
+            (-1, &[0x20]),
+            (-1000, &[0x39, 0x03, 0xe7]),
+        ] {
+            assert_eq!(to_vec(&v).unwrap(), encoded, "{v}");
+            assert_eq!(from_slice::<i64>(encoded).unwrap(), v);
+        }
+
+        let max = [0x1b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff];
+        assert_eq!(from_slice::<u64>(&max).unwrap(), u64::MAX);
+        assert_eq!(to_vec(&u64::MAX).unwrap(), max);
+        assert!(
+            from_slice::<i64>(&[0x3b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff]).is_err()
+        );
+        assert!(to_vec(&i128::MIN).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        // Indefinite-length map, text and array, a half float and a tagged date string
+        let input = [
+            &[
+                0xbf, 0x7f, 0x61, b'a', 0x61, b'b', 0xff, 0x9f, 0xf9, 0x3e, 0x00,
+            ][..],
+            &[0xc0, 0x64],
+            b"2013",
+            &[0xff, 0x61, b'c', 0x80, 0xff],
+        ]
+        .concat();
+        #[derive(Deserialize)]
+        struct Document {
+            ab: (f32, String),
+            c: Vec<u8>,
+        }
+
+        let document = from_slice::<Document>(&input).unwrap();
+        assert_eq!(document.ab, (1.5, "2013".to_owned()));
+        assert!(document.c.is_empty());
+
+        assert_eq!(f16(0x7bff), 65504.0);
+        assert_eq!(f16(0x0001), 5.960464477539063e-8);
+        assert_eq!(f16(0xfc00), f64::NEG_INFINITY);
+        assert_eq!(from_slice::<String>(&[0x42, b'h', b'i']).unwrap(), "hi");
+        assert_eq!(from_slice::<Option<bool>>(&[0xf7]).unwrap(), None);
+
+        for invalid in [
+            &[][..],
+            &[0xff],
+            &[0x1c],
+            &[0x62, b'a'],
+            &[0x61, 0xff],
+            &[0x5f, 0x61, b'a', 0xff],
+            &[0xf6, 0xf6],
+            &[0x9f, 0xf6],
+            &[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff],
+        ] {
+            assert!(
+                matches!(from_slice::<()>(invalid), Err(Error::Syntax(..))),
+                "{invalid:?}"
+            );
+        }
+
+        let deep = [0x81; 100];
+        assert!(from_slice::<Vec<()>>(&deep).is_err());
+    }
+
+    #[test]
+    fn data_model() {
+        let value = DataModel::new();
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice::<DataModel>(&bytes).unwrap(), value);
+
+        for (value, encoded) in [
+            (Shape::Point, &[0x65, b'P', b'o', b'i', b'n', b't'][..]),
+            (
+                Shape::Circle(1),
+                &[0xa1, 0x66, b'C', b'i', b'r', b'c', b'l', b'e', 0x01],
+            ),
+            (
+                Shape::Line(1, 2),
+                &[0xa1, 0x64, b'L', b'i', b'n', b'e', 0x82, 0x01, 0x02],
+            ),
+            (
+                Shape::Rect { w: 3 },
+                &[0xa1, 0x64, b'R', b'e', b'c', b't', 0xa1, 0x61, b'w', 0x03],
+            ),
+        ] {
+            assert_eq!(to_vec(&value).unwrap(), encoded, "{value:?}");
+            assert_eq!(from_slice::<Shape>(encoded).unwrap(), value);
+        }
+        assert!(from_slice::<Shape>(&[0xa1, 0x63, b'S', b'u', b'n', 0xf6]).is_err());
+
+        assert_eq!(to_vec(&Bytes(vec![1, 2])).unwrap(), [0x42, 0x01, 0x02]);
+        assert_eq!(to_vec(&'\u{e9}').unwrap(), [0x62, 0xc3, 0xa9]);
+        assert_eq!(to_vec(&()).unwrap(), [0xf6]);
+        assert_eq!(to_vec(&1.5f32).unwrap(), [0xfa, 0x3f, 0xc0, 0, 0]);
+
+        // Sequences and maps of unknown length are buffered to write a definite length
+        let unsized_seq = Unsized(vec![1, 2, 3]);
+        assert_eq!(to_vec(&unsized_seq).unwrap(), [0x83, 0x01, 0x02, 0x03]);
+    }
+
+    #[test]
+    fn lengths() {
+        for truncated in [
+            // Missing length bytes, for each size of length
+            &[0x78][..],
+            &[0x59, 0x00],
+            &[0x9a, 0x00, 0x00, 0x00],
+            &[0xbb, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+            // Fewer bytes or items than the length says
+            &[0x63, b'a', b'b'],
+            &[0x58, 0x02, 0x00],
+            &[0x83, 0x01, 0x02],
+            &[0xa2, 0x01, 0x02, 0x03],
+            &[0xc0],
+            &[0xf9, 0x3e],
+            &[0xfb, 0, 0, 0, 0],
+        ] {
+            assert!(
+                matches!(
+                    from_slice::<Ignored>(truncated),
+                    Err(Error::Syntax(_, "unexpected end of input"))
+                ),
+                "{truncated:?}"
+            );
+        }
+
+        // Lengths far beyond the input must fail without allocating for them
+        for oversized in [
+            &[0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00][..],
+            &[0x7a, 0xff, 0xff, 0xff, 0xff, b'a'],
+            &[0x9b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00],
+            &[
+                0xbb, 0x7f, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00,
+            ],
+            &[
+                0x5f, 0x5b, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff,
+            ],
+        ] {
+            assert!(
+                matches!(
+                    from_slice::<Ignored>(oversized),
+                    Err(Error::Syntax(_, "unexpected end of input"))
+                ),
+                "{oversized:?}"
+            );
+        }
+
+        for reserved in [&[0x1c][..], &[0x5d], &[0x7e], &[0x9c], &[0xbd], &[0xde]] {
+            assert!(
+                matches!(
+                    from_slice::<Ignored>(reserved),
+                    Err(Error::Syntax(1, "invalid additional information"))
+                ),
+                "{reserved:?}"
+            );
+        }
+
+        assert!(matches!(
+            from_slice::<String>(&[0x62, 0xc3, 0x28]),
+            Err(Error::Syntax(_, "invalid UTF-8"))
+        ));
+        assert!(matches!(
+            from_slice::<u8>(&[0x01, 0x02]),
+            Err(Error::Syntax(1, "trailing bytes"))
+        ));
+    }
+
+    #[test]
+    fn indefinite() {
+        // Byte and text strings in chunks, including empty ones
+        let bytes = [0x5f, 0x42, 0x01, 0x02, 0x40, 0x41, 0x03, 0xff];
+        assert_eq!(from_slice::<Bytes>(&bytes).unwrap(), Bytes(vec![1, 2, 3]));
+        let text = [0x7f, 0x62, b'a', b'b', 0x60, 0x61, b'c', 0xff];
+        assert_eq!(from_slice::<String>(&text).unwrap(), "abc");
+        assert_eq!(from_slice::<String>(&[0x7f, 0xff]).unwrap(), "");
+
+        // Nested arrays and maps, mixing definite and indefinite lengths
+        let input = [
+            0xbf, 0x61, b'a', 0x9f, 0x01, 0x82, 0x02, 0x03, 0x9f, 0xff, 0xff, 0x61, b'b', 0xa1,
+            0x61, b'c', 0x9f, 0xff, 0xff,
+        ];
+        #[derive(Debug, Deserialize, PartialEq)]
+        enum Item {
+            #[serde(untagged)]
+            Int(u8),
+            #[serde(untagged)]
+            List(Vec<u8>),
+        }
+        #[derive(Debug, Deserialize, PartialEq)]
+        struct Nested {
+            a: Vec<Item>,
+            b: BTreeMap<String, Vec<u8>>,
+        }
+
+        let nested = from_slice::<Nested>(&input).unwrap();
+        assert_eq!(
+            nested.a,
+            [Item::Int(1), Item::List(vec![2, 3]), Item::List(vec![])]
+        );
+        assert_eq!(nested.b["c"], Vec::<u8>::new());
+
+        for (invalid, msg) in [
+            (&[0x9f, 0x01][..], "unexpected end of input"),
+            (&[0xbf, 0x61, b'a', 0xff], "unexpected break"),
+            (&[0x81, 0xff], "unexpected break"),
+            (&[0x5f, 0x61, b'a', 0xff], "invalid string chunk"),
+            (&[0x7f, 0x7f, 0xff, 0xff], "invalid string chunk"),
+            (&[0x5f, 0x01, 0xff], "invalid string chunk"),
+            (&[0x5f, 0x42, 0x01], "unexpected end of input"),
+            (&[0x1f], "invalid indefinite length"),
+            (&[0x3f], "invalid indefinite length"),
+            (&[0xdf, 0x00], "invalid indefinite length"),
+        ] {
+            match from_slice::<Ignored>(invalid) {
+                Err(Error::Syntax(_, m)) => assert_eq!(m, msg, "{invalid:?}"),
+                result => panic!("{invalid:?}: {result:?}"),
+            }
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let mut ints = BTreeMap::new();
+        ints.insert(-10i64, "a".to_owned());
+        ints.insert(500, "b".to_owned());
+        let bytes = to_vec(&ints).unwrap();
+        assert_eq!(
+            bytes,
+            [0xa2, 0x29, 0x61, b'a', 0x19, 0x01, 0xf4, 0x61, b'b']
+        );
+        assert_eq!(from_slice::<BTreeMap<i64, String>>(&bytes).unwrap(), ints);
+
+        let mut composite = BTreeMap::new();
+        composite.insert((true, 'x'), 1u8);
+        composite.insert((false, 'y'), 2);
+        let bytes = to_vec(&composite).unwrap();
+        assert_eq!(
+            from_slice::<BTreeMap<(bool, char), u8>>(&bytes).unwrap(),
+            composite
+        );
+
+        let mut units = BTreeMap::new();
+        units.insert(Shape::Point, 1u8);
+        units.insert(Shape::Circle(2), 2);
+        let bytes = to_vec(&units).unwrap();
+        assert_eq!(from_slice::<BTreeMap<Shape, u8>>(&bytes).unwrap(), units);
+
+        // Keys of another type than the map's
+        assert!(from_slice::<BTreeMap<String, u8>>(&[0xa1, 0x01, 0x02]).is_err());
+        assert!(from_slice::<BTreeMap<u8, u8>>(&[0xa1, 0x61, b'a', 0x02]).is_err());
+        assert!(from_slice::<BTreeMap<u8, u8>>(&[0xa1, 0xf6, 0x02]).is_err());
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct DataModel {
+        bool: bool,
+        i8: i8,
+        i16: i16,
+        i32: i32,
+        i64: i64,
+        i128: i128,
+        u8: u8,
+        u16: u16,
+        u32: u32,
+        u64: u64,
+        u128: u128,
+        f32: f32,
+        f64: f64,
+        char: char,
+        string: String,
+        bytes: Bytes,
+        none: Option<u8>,
+        some: Option<String>,
+        unit: (),
+        unit_struct: UnitStruct,
+        newtype: Newtype,
+        tuple: (u8, String, bool),
+        tuple_struct: TupleStruct,
+        seq: Vec<Vec<u16>>,
+        map: BTreeMap<String, Option<f64>>,
+        shapes: Vec<Shape>,
+    }
+
+    impl DataModel {
+        fn new() -> Self {
+            let mut map = BTreeMap::new();
+            map.insert("pi".to_owned(), Some(std::f64::consts::PI));
+            map.insert("none".to_owned(), None);
+
+            Self {
+                bool: true,
+                i8: i8::MIN,
+                i16: i16::MIN,
+                i32: i32::MIN,
+                i64: i64::MIN,
+                i128: -1 - i128::from(u64::MAX),
+                u8: u8::MAX,
+                u16: u16::MAX,
+                u32: u32::MAX,
+                u64: u64::MAX,
+                u128: u64::MAX.into(),
+                f32: -0.1,
+                f64: f64::MAX,
+                char: '\u{1F600}',
+                string: "h\u{e9}llo\0".to_owned(),
+                bytes: Bytes((0..=255).collect()),
+                none: None,
+                some: Some(String::new()),
+                unit: (),
+                unit_struct: UnitStruct,
+                newtype: Newtype(-7),
+                tuple: (1, "two".to_owned(), false),
+                tuple_struct: TupleStruct(1.5, 'q'),
+                seq: vec![vec![], vec![1, 300, 70]],
+                map,
+                shapes: vec![
+                    Shape::Point,
+                    Shape::Circle(-1),
+                    Shape::Line(0, 1 << 40),
+                    Shape::Rect { w: 9 },
+                ],
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct UnitStruct;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Newtype(i32);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct TupleStruct(f32, char);
+
+    #[derive(Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+    enum Shape {
+        Point,
+        Circle(i8),
+        Line(u8, u64),
+        Rect { w: u16 },
+    }
+
+    /// A byte string, which `Vec<u8>` would serialize as an array
+    #[derive(Debug, PartialEq)]
+    struct Bytes(Vec<u8>);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BytesVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                type Value = Bytes;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("a byte string")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                    Ok(Bytes(v))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    /// A sequence that doesn't know its length up front
+    struct Unsized(Vec<u8>);
+
+    impl Serialize for Unsized {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter().filter(|_| true))
+        }
+    }
+
+    type Ignored = serde::de::IgnoredAny;
+}
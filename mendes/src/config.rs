@@ -0,0 +1,407 @@
+use std::error::Error as StdError;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{env, fs, io};
+
+use http::request::Parts;
+use serde::de::{self, DeserializeOwned, Deserializer, IntoDeserializer, Unexpected, Visitor};
+use serde::forward_to_deserialize_any;
+use serde_json::{Map, Value};
+use thiserror::Error;
+
+use crate::application::{Application, FromContext, PathState};
+
+/// Loads typed configuration from layers of files and environment variables
+///
+/// Layers are merged in the order they are added, with later layers overriding values from
+/// earlier ones (nested tables are merged key by key). The merged configuration is then
+/// deserialized into the configuration type and validated:
+///
+/// ```no_run
+/// # use mendes::config::{Loader, Validate};
+/// # use serde::Deserialize;
+/// # mod toml {
+/// #     pub fn from_str(s: &str) -> Result<serde_json::Value, std::io::Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # #[derive(Deserialize)]
+/// # struct Settings {}
+/// # impl Validate for Settings {}
+/// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+/// let config = Loader::new()
+///     .json_file("config.json")?
+///     .file("config.toml", |s| toml::from_str(s))?
+///     .env("APP")
+///     .load::<Settings>()?;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Only JSON files are supported out of the box. Other formats can be used by passing a
+/// function that parses the file contents into a `serde_json::Value` to `file()`, using
+/// `toml::from_str()` or `serde_yaml::from_str()`.
+#[derive(Debug)]
+pub struct Loader {
+    value: Value,
+}
+
+impl Loader {
+    pub fn new() -> Self {
+        Self {
+            value: Value::Object(Map::new()),
+        }
+    }
+
+    /// Add a layer from the JSON file at `path`
+    pub fn json_file(self, path: impl AsRef<Path>) -> Result<Self, ConfigError> {
+        self.file(path, |s| serde_json::from_str(s))
+    }
+
+    /// Add a layer from the file at `path`, using `parse` to parse its contents
+    pub fn file<E>(
+        mut self,
+        path: impl AsRef<Path>,
+        parse: impl FnOnce(&str) -> Result<Value, E>,
+    ) -> Result<Self, ConfigError>
+    where
+        E: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|source| ConfigError::Read {
+            path: path.to_owned(),
+            source,
+        })?;
+
+        let layer = parse(&contents).map_err(|source| ConfigError::Parse {
+            path: path.to_owned(),
+            source: source.into(),
+        })?;
+
+        merge(&mut self.value, layer);
+        Ok(self)
+    }
+
+    /// Add a layer from environment variables starting with `prefix` and an underscore
+    ///
+    /// The rest of the variable name is lowercased, with double underscores separating
+    /// nested keys: `APP_DATABASE__MAX_CONNECTIONS` sets `database.max_connections` for the
+    /// `APP` prefix. Values are strings, but are parsed when deserialized into booleans or
+    /// numbers. Variables that aren't valid Unicode are ignored.
+    pub fn env(self, prefix: &str) -> Self {
+        self.vars(
+            prefix,
+            env::vars_os()
+                .filter_map(|(k, v)| Some((k.into_string().ok()?, v.into_string().ok()?))),
+        )
+    }
+
+    fn vars(mut self, prefix: &str, vars: impl Iterator<Item = (String, String)>) -> Self {
+        for (name, value) in vars {
+            let key = match name
+                .strip_prefix(prefix)
+                .and_then(|name| name.strip_prefix('_'))
+            {
+                Some(key) if !key.is_empty() => key.to_lowercase(),
+                _ => continue,
+            };
+
+            let layer = key.rsplit("__").fold(Value::String(value), |value, key| {
+                Value::Object(Map::from_iter([(key.to_owned(), value)]))
+            });
+            merge(&mut self.value, layer);
+        }
+
+        self
+    }
+
+    /// Deserialize the merged layers into a `T` and validate it
+    pub fn load<T: DeserializeOwned + Validate>(self) -> Result<T, ConfigError> {
+        let config = T::deserialize(Lenient(self.value)).map_err(ConfigError::Deserialize)?;
+        config.validate().map_err(ConfigError::Invalid)?;
+        Ok(config)
+    }
+}
+
+impl Default for Loader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Validation for configuration values, run by `Loader::load()`
+///
+/// The default implementation accepts any value.
+pub trait Validate {
+    /// Check the configuration, returning a description of the problem if it's invalid
+    fn validate(&self) -> Result<(), String> {
+        Ok(())
+    }
+}
+
+/// Extracts the application's configuration
+///
+/// The application exposes its configuration by implementing `AsRef<T>`:
+///
+/// ```no_run
+/// # use mendes::config::Config;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # struct Settings {}
+/// # struct App {
+/// #     settings: Settings,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// impl AsRef<Settings> for App {
+///     fn as_ref(&self) -> &Settings {
+///         &self.settings
+///     }
+/// }
+///
+/// #[handler(GET)]
+/// async fn hello(_: &App, Config(settings): Config<'_, Settings>) -> Result<Response<Body>, Error> {
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct Config<'a, T>(pub &'a T);
+
+impl<'a, A, T> FromContext<'a, A> for Config<'a, T>
+where
+    A: Application + AsRef<T>,
+{
+    fn from_context(
+        app: &'a Arc<A>,
+        _: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(Config(<A as AsRef<T>>::as_ref(app)))
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum ConfigError {
+    #[error("unable to read {path:?}: {source}")]
+    Read { path: PathBuf, source: io::Error },
+    #[error("unable to parse {path:?}: {source}")]
+    Parse {
+        path: PathBuf,
+        source: Box<dyn StdError + Send + Sync>,
+    },
+    #[error("invalid configuration: {0}")]
+    Deserialize(serde_json::Error),
+    #[error("invalid configuration: {0}")]
+    Invalid(String),
+}
+
+fn merge(base: &mut Value, layer: Value) {
+    match (base, layer) {
+        (Value::Object(base), Value::Object(layer)) => {
+            for (key, value) in layer {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, layer) => *base = layer,
+    }
+}
+
+/// Deserializes a `Value`, parsing strings where booleans or numbers are expected
+struct Lenient(Value);
+
+macro_rules! parse_str {
+    ($($method:ident => $visit:ident,)*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+            match self.0 {
+                Value::String(s) => match s.trim().parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(de::Error::invalid_value(Unexpected::Str(&s), &visitor)),
+                },
+                value => value.$method(visitor),
+            }
+        })*
+    };
+}
+
+impl<'de> Deserializer<'de> for Lenient {
+    type Error = serde_json::Error;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Object(map) => {
+                let mut map = de::value::MapDeserializer::new(
+                    map.into_iter().map(|(key, value)| (key, Lenient(value))),
+                );
+                let value = visitor.visit_map(&mut map)?;
+                map.end()?;
+                Ok(value)
+            }
+            Value::Array(seq) => {
+                let mut seq = de::value::SeqDeserializer::new(seq.into_iter().map(Lenient));
+                let value = visitor.visit_seq(&mut seq)?;
+                seq.end()?;
+                Ok(value)
+            }
+            value => value.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, Self::Error> {
+        match self.0 {
+            Value::Null => visitor.visit_none(),
+            value => visitor.visit_some(Lenient(value)),
+        }
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        name: &'static str,
+        variants: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, Self::Error> {
+        self.0.deserialize_enum(name, variants, visitor)
+    }
+
+    parse_str! {
+        deserialize_bool => visit_bool,
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    forward_to_deserialize_any! {
+        char str string bytes byte_buf unit unit_struct seq tuple tuple_struct map struct
+        identifier ignored_any
+    }
+}
+
+impl<'de> IntoDeserializer<'de, serde_json::Error> for Lenient {
+    type Deserializer = Self;
+
+    fn into_deserializer(self) -> Self {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde::Deserialize;
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Settings {
+        name: String,
+        debug: bool,
+        database: Database,
+        tags: Vec<String>,
+    }
+
+    impl Validate for Settings {
+        fn validate(&self) -> Result<(), String> {
+            match self.database.max_connections {
+                0 => Err("database.max_connections must be positive".into()),
+                _ => Ok(()),
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq)]
+    struct Database {
+        url: String,
+        max_connections: u32,
+        timeout: Option<f64>,
+    }
+
+    #[test]
+    fn layers() {
+        let path = env::temp_dir().join(format!("mendes-config-{}.json", std::process::id()));
+        let json = r#"{
+            "name": "app",
+            "debug": false,
+            "database": {"url": "postgres://localhost/app", "max_connections": 4},
+            "tags": ["a", "b"]
+        }"#;
+        fs::write(&path, json).unwrap();
+
+        let vars = [
+            ("APP_DEBUG", "true"),
+            ("APP_DATABASE__MAX_CONNECTIONS", "16"),
+            ("APP_DATABASE__TIMEOUT", "2.5"),
+            ("APPLICATION_NAME", "other"),
+            ("OTHER_NAME", "other"),
+        ];
+        let config = Loader::new()
+            .json_file(&path)
+            .unwrap()
+            .vars(
+                "APP",
+                vars.iter().map(|(k, v)| (k.to_string(), v.to_string())),
+            )
+            .load::<Settings>()
+            .unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(
+            config,
+            Settings {
+                name: "app".into(),
+                debug: true,
+                database: Database {
+                    url: "postgres://localhost/app".into(),
+                    max_connections: 16,
+                    timeout: Some(2.5),
+                },
+                tags: vec!["a".into(), "b".into()],
+            }
+        );
+
+        let error = Loader::new()
+            .json_file(path.with_extension("missing"))
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::Read { .. }));
+
+        let loader = Loader {
+            value: serde_json::from_str(json).unwrap(),
+        };
+        let vars = [("APP_DATABASE__MAX_CONNECTIONS".into(), "0".into())];
+        let error = loader
+            .vars("APP", vars.into_iter())
+            .load::<Settings>()
+            .unwrap_err();
+        assert!(matches!(error, ConfigError::Invalid(_)));
+
+        let loader = Loader::new().vars("APP", [("APP_DEBUG".into(), "maybe".into())].into_iter());
+        let error = loader.load::<Settings>().unwrap_err();
+        assert!(matches!(error, ConfigError::Deserialize(_)));
+    }
+}
@@ -184,16 +184,11 @@ fn extract<T: CookieData + DeserializeOwned>(key: &Key, headers: &HeaderMap) ->
         };
         // A single cookie header can contain multiple cookies (delimited by ;)
         // even if there are multiple cookie headers.
-        for cookie in value.split(';') {
-            let cookie = cookie.trim_start();
-            if cookie.len() < (name.len() + 1 + NONCE_LEN + TAG_LEN)
-                || !cookie.starts_with(name)
-                || cookie.as_bytes()[name.len()] != b'='
-            {
+        for (cookie, encoded) in crate::utils::cookie_pairs(value) {
+            if cookie != name || encoded.len() < NONCE_LEN + TAG_LEN {
                 continue;
             }
 
-            let encoded = &cookie[name.len() + 1..];
             match T::decode(encoded, key) {
                 Some(data) => return Some(data),
                 None => continue,
@@ -0,0 +1,684 @@
+use std::borrow::Cow;
+use std::convert::Infallible;
+use std::error::Error as StdError;
+use std::fmt::{self, Display};
+use std::io;
+use std::iter::Map;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{self, Iter, Stream};
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::request::Parts;
+use http::{Response, StatusCode};
+use http_body::{Frame, SizeHint};
+use pin_project::pin_project;
+use serde::ser::{self, Impossible, Serialize, SerializeSeq, SerializeStruct, SerializeTuple};
+
+use crate::application::{Application, IntoResponse};
+use crate::utils::attachment;
+use crate::Body;
+
+/// A `text/csv` response streamed from a sequence of rows
+///
+/// Each row is serialized using its `Serialize` implementation. Rows can be structs (in which case
+/// the field names of the first row are emitted as a header row), tuples or sequences, and may
+/// only contain primitive values (strings, numbers, booleans and options of those).
+///
+/// ```no_run
+/// # use futures_util::stream::{self, Stream};
+/// # use mendes::csv::Csv;
+/// # use mendes::{handler, Error};
+/// # use serde::Serialize;
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[derive(Serialize)]
+/// struct Order {
+///     id: u64,
+///     total: f64,
+/// }
+///
+/// #[handler(GET)]
+/// async fn export(_: &App) -> Result<Csv<impl Stream<Item = Result<Order, Error>> + Send>, Error> {
+///     let orders = stream::iter([Ok(Order { id: 1, total: 9.5 })]);
+///     Ok(Csv::new(orders).filename("orders.csv"))
+/// }
+/// # fn main() {}
+/// ```
+pub struct Csv<S> {
+    rows: S,
+    filename: Option<Cow<'static, str>>,
+}
+
+impl<I: Iterator> Csv<Iter<Map<I, fn(I::Item) -> Result<I::Item, Infallible>>>> {
+    /// Create a CSV response from an iterator of rows
+    pub fn from_rows(rows: impl IntoIterator<IntoIter = I>) -> Self {
+        Csv::new(stream::iter(rows.into_iter().map(Ok as fn(_) -> _)))
+    }
+}
+
+impl<S> Csv<S> {
+    /// Create a CSV response from a stream of rows
+    ///
+    /// If the stream yields an error, the response body is aborted.
+    pub fn new(rows: S) -> Self {
+        Self {
+            rows,
+            filename: None,
+        }
+    }
+
+    /// Set the file name to suggest to the client for downloading the response
+    ///
+    /// This sets a `Content-Disposition: attachment` header on the response.
+    pub fn filename(mut self, filename: impl Into<Cow<'static, str>>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+}
+
+impl<S, T, E> Csv<S>
+where
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Serialize,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Convert into a `Response` with a streaming `Body`
+    pub fn into_response(self) -> Response<Body> {
+        let mut builder = Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/csv; charset=utf-8");
+        if let Some(filename) = &self.filename {
+            builder = builder.header(CONTENT_DISPOSITION, attachment(filename));
+        }
+
+        builder
+            .body(Body::stream(CsvBody {
+                rows: self.rows,
+                header: true,
+                done: false,
+            }))
+            .unwrap()
+    }
+}
+
+impl<A, S, T, E> IntoResponse<A> for Csv<S>
+where
+    A: Application<ResponseBody = Body>,
+    S: Stream<Item = Result<T, E>> + Send + 'static,
+    T: Serialize,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<Body> {
+        Csv::into_response(self)
+    }
+}
+
+#[pin_project]
+struct CsvBody<S> {
+    #[pin]
+    rows: S,
+    header: bool,
+    done: bool,
+}
+
+impl<S, T, E> http_body::Body for CsvBody<S>
+where
+    S: Stream<Item = Result<T, E>>,
+    T: Serialize,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let mut this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+
+        let mut buf = BytesMut::new();
+        while buf.len() < CHUNK_SIZE {
+            let row = match this.rows.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(row))) => row,
+                Poll::Ready(Some(Err(error))) => {
+                    *this.done = true;
+                    return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::Other, error))));
+                }
+                Poll::Ready(None) => {
+                    *this.done = true;
+                    break;
+                }
+                Poll::Pending if buf.is_empty() => return Poll::Pending,
+                Poll::Pending => break,
+            };
+
+            if let Err(error) = write_row(&mut buf, &row, this.header) {
+                *this.done = true;
+                return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::InvalidData, error))));
+            }
+        }
+
+        match buf.is_empty() {
+            true => Poll::Ready(None),
+            false => Poll::Ready(Some(Ok(Frame::data(buf.freeze())))),
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+const CHUNK_SIZE: usize = 8 * 1024;
+
+/// Serialize a single row into `buf`, preceded by a header row if `header` is `true`
+///
+/// `header` is reset to `false` after the first row has been written.
+fn write_row<T: Serialize>(buf: &mut BytesMut, row: &T, header: &mut bool) -> Result<(), Error> {
+    let mut names = Vec::new();
+    let mut fields = Vec::new();
+    row.serialize(RowSerializer {
+        names: match header {
+            true => Some(&mut names),
+            false => None,
+        },
+        fields: &mut fields,
+    })?;
+
+    if *header && !names.is_empty() {
+        write_record(buf, names.iter().map(|s| s.as_bytes()));
+    }
+    *header = false;
+
+    write_record(buf, fields.iter().map(|s| s.as_slice()));
+    Ok(())
+}
+
+fn write_record<'a>(buf: &mut BytesMut, fields: impl Iterator<Item = &'a [u8]>) {
+    for (i, field) in fields.enumerate() {
+        if i > 0 {
+            buf.extend_from_slice(b",");
+        }
+
+        let quote = field
+            .iter()
+            .any(|b| matches!(b, b',' | b'"' | b'\r' | b'\n'));
+        if !quote {
+            buf.extend_from_slice(field);
+            continue;
+        }
+
+        buf.extend_from_slice(b"\"");
+        for chunk in field.split_inclusive(|b| *b == b'"') {
+            buf.extend_from_slice(chunk);
+            if chunk.ends_with(b"\"") {
+                buf.extend_from_slice(b"\"");
+            }
+        }
+        buf.extend_from_slice(b"\"");
+    }
+    buf.extend_from_slice(b"\r\n");
+}
+
+struct RowSerializer<'a> {
+    names: Option<&'a mut Vec<&'static str>>,
+    fields: &'a mut Vec<Vec<u8>>,
+}
+
+impl<'a> ser::Serializer for RowSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Self;
+    type SerializeTuple = Self;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Self;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_bool(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_i8(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_i16(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_i32(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_i64(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_u8(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_u16(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_u32(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_u64(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_f32(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_f64(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_char(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_str(v)
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_bytes(v)
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_none()
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        FieldSerializer(self.fields).serialize_unit()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), Error> {
+        Err(Error::Unsupported("enum variant with data"))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::Unsupported("tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Unsupported("enum variant with data"))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::Unsupported("map"))
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self, Error> {
+        Ok(self)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Unsupported("enum variant with data"))
+    }
+}
+
+impl<'a> SerializeSeq for RowSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(FieldSerializer(self.fields))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> SerializeTuple for RowSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(FieldSerializer(self.fields))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> SerializeStruct for RowSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        if let Some(names) = &mut self.names {
+            names.push(key);
+        }
+        value.serialize(FieldSerializer(self.fields))
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Serializes a single primitive value as a CSV field
+struct FieldSerializer<'a>(&'a mut Vec<Vec<u8>>);
+
+impl FieldSerializer<'_> {
+    fn display(self, value: impl Display) -> Result<(), Error> {
+        self.0.push(value.to_string().into_bytes());
+        Ok(())
+    }
+}
+
+impl<'a> ser::Serializer for FieldSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = Impossible<(), Error>;
+    type SerializeTuple = Impossible<(), Error>;
+    type SerializeTupleStruct = Impossible<(), Error>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = Impossible<(), Error>;
+    type SerializeStruct = Impossible<(), Error>;
+    type SerializeStructVariant = Impossible<(), Error>;
+
+    fn serialize_bool(self, v: bool) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_i8(self, v: i8) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_i16(self, v: i16) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_i32(self, v: i32) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_i64(self, v: i64) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_u8(self, v: u8) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_u16(self, v: u16) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_u32(self, v: u32) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_u64(self, v: u64) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_f32(self, v: f32) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_f64(self, v: f64) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_char(self, v: char) -> Result<(), Error> {
+        self.display(v)
+    }
+
+    fn serialize_str(self, v: &str) -> Result<(), Error> {
+        self.0.push(v.as_bytes().to_vec());
+        Ok(())
+    }
+
+    fn serialize_bytes(self, v: &[u8]) -> Result<(), Error> {
+        self.0.push(v.to_vec());
+        Ok(())
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        self.0.push(Vec::new());
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<(), Error> {
+        self.serialize_none()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.serialize_str(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<(), Error> {
+        Err(Error::Unsupported("enum variant with data"))
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(Error::Unsupported("nested sequence"))
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(Error::Unsupported("nested tuple"))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(Error::Unsupported("nested tuple struct"))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Unsupported("enum variant with data"))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(Error::Unsupported("nested map"))
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(Error::Unsupported("nested struct"))
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(Error::Unsupported("enum variant with data"))
+    }
+}
+
+#[derive(Debug)]
+enum Error {
+    Custom(String),
+    Unsupported(&'static str),
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Error::Custom(msg.to_string())
+    }
+}
+
+impl Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::Custom(msg) => f.write_str(msg),
+            Error::Unsupported(kind) => write!(f, "unable to serialize {kind} as CSV field"),
+        }
+    }
+}
+
+impl StdError for Error {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rows() {
+        #[derive(serde::Serialize)]
+        struct Row<'a> {
+            id: u32,
+            name: &'a str,
+            score: Option<f32>,
+        }
+
+        let mut buf = BytesMut::new();
+        let mut header = true;
+        let rows = [
+            Row {
+                id: 1,
+                name: "plain",
+                score: Some(1.5),
+            },
+            Row {
+                id: 2,
+                name: "with, \"quotes\"",
+                score: None,
+            },
+        ];
+        for row in &rows {
+            write_row(&mut buf, row, &mut header).unwrap();
+        }
+
+        assert_eq!(
+            &buf[..],
+            b"id,name,score\r\n1,plain,1.5\r\n2,\"with, \"\"quotes\"\"\",\r\n"
+        );
+    }
+
+    #[test]
+    fn tuples() {
+        let mut buf = BytesMut::new();
+        let mut header = true;
+        write_row(&mut buf, &("a\nb", 3, true), &mut header).unwrap();
+        assert_eq!(&buf[..], b"\"a\nb\",3,true\r\n");
+
+        let nested = (1, (2, 3));
+        assert!(write_row(&mut buf, &nested, &mut header).is_err());
+    }
+}
@@ -0,0 +1,144 @@
+use std::backtrace::Backtrace;
+use std::error::Error as StdError;
+
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{Response, StatusCode};
+
+use crate::html::{Markup, PreEscaped};
+
+/// The handler a request was routed to
+///
+/// In debug mode (see `Application::debug()`), handlers add this to the request extensions
+/// before extracting their arguments, so the debug error page can show where the request went.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Route {
+    /// The module path of the handler
+    pub handler: &'static str,
+    /// The part of the request path consumed while routing to the handler
+    pub path: String,
+}
+
+/// An HTML page describing `error`, for the debug mode of an application
+///
+/// `Error` uses this for server errors in debug mode. Applications with their own error type
+/// can call it from their `IntoResponse` implementation:
+///
+/// ```no_run
+/// # use std::fmt;
+/// # use mendes::application::IntoResponse;
+/// # use mendes::debug;
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::{Response, StatusCode};
+/// # use mendes::{Application, Body, Context};
+/// # #[derive(Debug)]
+/// # struct AppError;
+/// # impl fmt::Display for AppError {
+/// #     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl std::error::Error for AppError {}
+/// # impl From<&AppError> for StatusCode {
+/// #     fn from(e: &AppError) -> Self {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl From<mendes::Error> for AppError {
+/// #     fn from(e: mendes::Error) -> Self {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = AppError;
+/// #     async fn handle(cx: Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// impl IntoResponse<App> for AppError {
+///     fn into_response(self, app: &App, req: &Parts) -> Response<Body> {
+///         let status = StatusCode::from(&self);
+///         if status.is_server_error() && app.debug() {
+///             return debug::error_page(status, &self, None, req);
+///         }
+///         todo!()
+///     }
+/// }
+/// ```
+///
+/// The page shows the error with its chain of sources, the matched `Route` (if any), the
+/// request headers and the `backtrace`.
+pub fn error_page<B: From<String>>(
+    status: StatusCode,
+    error: &(dyn StdError + 'static),
+    backtrace: Option<&Backtrace>,
+    req: &Parts,
+) -> Response<B> {
+    let mut html = Markup::new();
+    html.push(PreEscaped(
+        "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>",
+    ))
+    .push_display(status)
+    .push(PreEscaped(
+        "</title><style>body { font-family: sans-serif; } \
+         pre { background: #f4f4f4; padding: 0.5em; overflow: auto; } \
+         th { text-align: left; padding-right: 1em; }</style></head><body><h1>",
+    ))
+    .push_display(status)
+    .push(PreEscaped("</h1><p><code>"))
+    .push_display(&req.method)
+    .push(" ")
+    .push_display(&req.uri)
+    .push(PreEscaped("</code></p><h2>Error</h2><ol>"));
+
+    let mut next = Some(error);
+    while let Some(error) = next {
+        html.push(PreEscaped("<li>"))
+            .push_display(error)
+            .push(PreEscaped("</li>"));
+        next = error.source();
+    }
+    html.push(PreEscaped("</ol>"));
+
+    if let Some(route) = req.extensions.get::<Route>() {
+        html.push(PreEscaped(
+            "<h2>Route</h2><table><tr><th>Handler</th><td><code>",
+        ))
+        .push(route.handler)
+        .push(PreEscaped(
+            "</code></td></tr><tr><th>Matched path</th><td><code>",
+        ))
+        .push(route.path.as_str())
+        .push(PreEscaped("</code></td></tr></table>"));
+    }
+
+    html.push(PreEscaped("<h2>Request headers</h2><pre>"));
+    for (name, value) in &req.headers {
+        html.push_display(format_args!(
+            "{name}: {}\n",
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    html.push(PreEscaped("</pre><h2>Backtrace</h2>"));
+
+    match backtrace {
+        Some(backtrace) => html
+            .push(PreEscaped("<pre>"))
+            .push_display(backtrace)
+            .push(PreEscaped("</pre>")),
+        None => html.push(PreEscaped(
+            "<p>No backtrace captured; set <code>RUST_BACKTRACE=1</code> to enable them.</p>",
+        )),
+    };
+
+    html.push(PreEscaped("</body></html>"));
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(html.into_string().into())
+        .unwrap()
+}
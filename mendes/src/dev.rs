@@ -0,0 +1,244 @@
+use std::borrow::Cow;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Weak};
+use std::time::{Duration, SystemTime};
+use std::{fs, io};
+
+use bytes::{Bytes, BytesMut};
+use futures_util::stream::{Stream, StreamExt};
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
+use http::{Response, StatusCode};
+
+use crate::sse::{Broadcaster, Event, Lagged, Sse};
+use crate::utils::collect;
+use crate::Body;
+
+mod recorder;
+pub use recorder::{Exchange, RecordedBody, Recorder};
+
+/// Reloads browser tabs when files change during development
+///
+/// The `Reloader` polls the watched directories (like templates and static files) for
+/// changes. HTML responses passed through `inject()` get a small script that listens for
+/// changes on an SSE endpoint, which the application should route to `events()`:
+///
+/// ```no_run
+/// # use futures_util::stream::Stream;
+/// # use mendes::dev::Reloader;
+/// # use mendes::http::Response;
+/// # use mendes::sse::{Event, Lagged, Sse};
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # struct App {
+/// #     reloader: Reloader,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     let rsp = route!(match cx.path() {
+///         Some("__reload") => reload,
+///         Some("orders") => orders,
+///     });
+///     cx.app.reloader.inject(rsp).await
+/// }
+/// # }
+///
+/// #[handler(GET)]
+/// async fn reload(app: &App) -> Result<Sse<impl Stream<Item = Result<Event, Lagged>>>, Error> {
+///     Ok(app.reloader.events())
+/// }
+/// # #[handler(GET)]
+/// # async fn orders(_: &App) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// The script also reloads the page when it reconnects to the endpoint, so restarting the
+/// server after a code change (with a tool like `cargo watch`) refreshes open tabs as well.
+pub struct Reloader {
+    events: Arc<Broadcaster<()>>,
+    path: Cow<'static, str>,
+}
+
+impl Reloader {
+    /// Watch `dirs`, polling for changes every `interval`
+    ///
+    /// Must be called from within a Tokio runtime. Polling stops when the `Reloader` is dropped.
+    pub fn watch<P: Into<PathBuf>>(dirs: impl IntoIterator<Item = P>, interval: Duration) -> Self {
+        let events = Arc::new(Broadcaster::new(4));
+        let dirs = dirs.into_iter().map(Into::into).collect::<Vec<_>>();
+        tokio::spawn(poll(Arc::downgrade(&events), dirs, interval));
+        Self {
+            events,
+            path: Cow::Borrowed("/__reload"),
+        }
+    }
+
+    /// Set the path the script connects to for change events (defaults to `/__reload`)
+    pub fn path(mut self, path: impl Into<Cow<'static, str>>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// An SSE response sending an event whenever the watched files change
+    pub fn events(&self) -> Sse<impl Stream<Item = Result<Event, Lagged>> + Send + 'static> {
+        let events = self
+            .events
+            .subscribe()
+            .map(|msg| msg.map(|()| Event::new("").event("reload")));
+        Sse::new(events).keep_alive(Duration::from_secs(15))
+    }
+
+    /// Reload all connected pages
+    pub fn reload(&self) {
+        self.events.send(());
+    }
+
+    /// Add the reload script to `rsp` if it's an HTML response
+    ///
+    /// The script is inserted before the closing `</body>` tag, or appended if there is none.
+    pub async fn inject(&self, rsp: Response<Body>) -> Response<Body> {
+        let html = rsp
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .is_some_and(|value| value.starts_with("text/html"));
+        if !html {
+            return rsp;
+        }
+
+        let (mut parts, body) = rsp.into_parts();
+        let body = match collect(body).await {
+            Ok(body) => body,
+            Err(_) => {
+                let mut rsp = Response::new(Body::empty());
+                *rsp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return rsp;
+            }
+        };
+
+        let script = self.script();
+        let at = body
+            .windows(7)
+            .rposition(|window| window.eq_ignore_ascii_case(b"</body>"))
+            .unwrap_or(body.len());
+        let mut new = BytesMut::with_capacity(body.len() + script.len());
+        new.extend_from_slice(&body[..at]);
+        new.extend_from_slice(script.as_bytes());
+        new.extend_from_slice(&body[at..]);
+
+        parts.headers.remove(CONTENT_LENGTH);
+        Response::from_parts(parts, Body::from(Bytes::from(new)))
+    }
+
+    /// The script tag `inject()` adds to HTML responses
+    pub fn script(&self) -> String {
+        let path = self.path.replace(['"', '\\', '<'], "");
+        format!(
+            "<script>(() => {{ let open = false; const events = new EventSource(\"{path}\"); \
+             events.addEventListener(\"reload\", () => location.reload()); \
+             events.onopen = () => {{ if (open) location.reload(); open = true; }}; }})();\
+             </script>"
+        )
+    }
+}
+
+async fn poll(events: Weak<Broadcaster<()>>, dirs: Vec<PathBuf>, interval: Duration) {
+    let dirs = Arc::new(dirs);
+    let mut last = None;
+    loop {
+        let snapshot = {
+            let dirs = dirs.clone();
+            tokio::task::spawn_blocking(move || snapshot(&dirs)).await
+        };
+
+        let events = match events.upgrade() {
+            Some(events) => events,
+            None => return,
+        };
+
+        if let Ok(snapshot) = snapshot {
+            if last.as_ref().is_some_and(|last| *last != snapshot) {
+                events.send(());
+            }
+            last = Some(snapshot);
+        }
+
+        drop(events);
+        tokio::time::sleep(interval).await;
+    }
+}
+
+/// List all files below `dirs` with their modification time, sorted by path
+fn snapshot(dirs: &[PathBuf]) -> Vec<(PathBuf, Option<SystemTime>)> {
+    let mut files = Vec::new();
+    for dir in dirs {
+        // Directories that don't exist (yet) are treated as empty
+        let _ = visit(dir, &mut files);
+    }
+    files.sort();
+    files
+}
+
+fn visit(dir: &Path, files: &mut Vec<(PathBuf, Option<SystemTime>)>) -> io::Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let entry = entry?;
+        let metadata = entry.metadata()?;
+        if metadata.is_dir() {
+            visit(&entry.path(), files)?;
+        } else {
+            files.push((entry.path(), metadata.modified().ok()));
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn snapshot() {
+        let dir = std::env::temp_dir().join(format!("mendes-dev-{}", std::process::id()));
+        fs::create_dir_all(dir.join("nested")).unwrap();
+        let before = super::snapshot(std::slice::from_ref(&dir));
+        assert!(before.is_empty());
+
+        fs::write(dir.join("nested").join("index.html"), "<h1>Hello</h1>").unwrap();
+        let after = super::snapshot(&[dir.clone(), dir.join("missing")]);
+        assert_eq!(after.len(), 1);
+        assert_ne!(before, after);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn inject() {
+        let reloader = Reloader::watch(Vec::<PathBuf>::new(), Duration::from_secs(60));
+        let script = reloader.script();
+        assert!(script.contains("new EventSource(\"/__reload\")"));
+
+        let rsp = Response::builder()
+            .header(CONTENT_TYPE, "text/html; charset=utf-8")
+            .header(CONTENT_LENGTH, 30)
+            .body(Body::from("<html><BODY>Hello</BODY></html>"))
+            .unwrap();
+        let rsp = reloader.inject(rsp).await;
+        assert!(!rsp.headers().contains_key(CONTENT_LENGTH));
+        let body = collect(rsp.into_body()).await.unwrap();
+        let expected = format!("<html><BODY>Hello{script}</BODY></html>");
+        assert_eq!(body, expected.as_bytes());
+
+        let rsp = Response::builder()
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from("{}"))
+            .unwrap();
+        let body = collect(reloader.inject(rsp).await.into_body())
+            .await
+            .unwrap();
+        assert_eq!(body, "{}".as_bytes());
+    }
+}
@@ -0,0 +1,497 @@
+use std::borrow::Cow;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context as TaskContext, Poll};
+use std::time::{Duration, Instant, SystemTime};
+
+use bytes::{Bytes, BytesMut};
+use http::header::{
+    HeaderName, AUTHORIZATION, CONTENT_TYPE, COOKIE, PROXY_AUTHORIZATION, SET_COOKIE,
+};
+use http::{HeaderMap, HeaderValue, Method, Response, StatusCode};
+use http_body::{Frame, SizeHint};
+use pin_project::pin_project;
+
+use crate::application::{Application, Context};
+use crate::html::{Markup, PreEscaped};
+use crate::Body;
+
+/// Records requests and responses in memory for inspection during development
+///
+/// Wrap request handling with `handle()` and route the inspection path to `inspect()`, which
+/// renders the recorded exchanges as an HTML page (newest first):
+///
+/// ```no_run
+/// # use mendes::dev::Recorder;
+/// # use mendes::html::Markup;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # struct App {
+/// #     recorder: Recorder,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(cx: Context<Self>) -> Response<Body> {
+///     let app = cx.app.clone();
+///     app.recorder
+///         .handle(cx, |mut cx| async move {
+///             route!(match cx.path() {
+///                 Some("__requests") => requests,
+///                 Some("orders") => orders,
+///             })
+///         })
+///         .await
+/// }
+/// # }
+///
+/// #[handler(GET)]
+/// async fn requests(app: &App) -> Result<Markup, Error> {
+///     Ok(app.recorder.inspect())
+/// }
+/// # #[handler(GET)]
+/// # async fn orders(_: &App) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// Bodies are recorded while they stream through, up to a size limit, so handlers and
+/// clients see them unchanged. Requests to the inspection path are not recorded.
+///
+/// Values of sensitive headers are replaced before they are stored, as are values of sensitive
+/// fields in query strings. Form and JSON bodies are redacted when they are read from the
+/// `Exchange`, since they are only complete once streamed. By default, the `Authorization`,
+/// `Proxy-Authorization`, `Cookie` and `Set-Cookie` headers and `password` fields are redacted.
+pub struct Recorder {
+    exchanges: Mutex<VecDeque<Arc<Exchange>>>,
+    capacity: usize,
+    max_body_size: usize,
+    headers: Vec<HeaderName>,
+    fields: Arc<Vec<Cow<'static, str>>>,
+    path: Cow<'static, str>,
+    next_id: AtomicU64,
+}
+
+impl Recorder {
+    /// Keep the last `capacity` exchanges
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            exchanges: Mutex::new(VecDeque::with_capacity(capacity)),
+            capacity,
+            max_body_size: 64 * 1024,
+            headers: vec![AUTHORIZATION, PROXY_AUTHORIZATION, COOKIE, SET_COOKIE],
+            fields: Arc::new(vec![Cow::Borrowed("password")]),
+            path: Cow::Borrowed("/__requests"),
+            next_id: AtomicU64::new(1),
+        }
+    }
+
+    /// Redact the value of the header `name`
+    pub fn redact_header(mut self, name: HeaderName) -> Self {
+        self.headers.push(name);
+        self
+    }
+
+    /// Redact values of fields called `name` in query strings, forms and JSON objects
+    pub fn redact_field(mut self, name: impl Into<Cow<'static, str>>) -> Self {
+        Arc::make_mut(&mut self.fields).push(name.into());
+        self
+    }
+
+    /// Set how much of each body is kept (defaults to 64 KiB)
+    pub fn max_body_size(mut self, size: usize) -> Self {
+        self.max_body_size = size;
+        self
+    }
+
+    /// Set the path routed to `inspect()`, which is not recorded (defaults to `/__requests`)
+    pub fn path(mut self, path: impl Into<Cow<'static, str>>) -> Self {
+        self.path = path.into();
+        self
+    }
+
+    /// Handle the request in `cx` with `handler`, recording the request and its response
+    pub async fn handle<A, F, Fut>(&self, mut cx: Context<A>, handler: F) -> Response<Body>
+    where
+        A: Application<RequestBody = Body, ResponseBody = Body>,
+        F: FnOnce(Context<A>) -> Fut,
+        Fut: Future<Output = Response<Body>>,
+    {
+        if cx.req.uri.path() == self.path {
+            return handler(cx).await;
+        }
+
+        let (time, start) = (SystemTime::now(), Instant::now());
+        let request_body = Arc::new(Capture::default());
+        cx.body = match cx.body.take() {
+            Some(body) => Some(Body::stream(Tee::new(
+                body,
+                request_body.clone(),
+                self.max_body_size,
+            ))),
+            None => {
+                request_body.state.lock().unwrap().complete = true;
+                None
+            }
+        };
+
+        let method = cx.req.method.clone();
+        let uri = match cx.req.uri.query() {
+            Some(query) => format!(
+                "{}?{}",
+                cx.req.uri.path(),
+                redact_form(query.as_bytes(), &self.fields)
+            ),
+            None => cx.req.uri.path().to_owned(),
+        };
+        let request_headers = self.redact_headers(&cx.req.headers);
+
+        let (parts, body) = handler(cx).await.into_parts();
+        let response_body = Arc::new(Capture::default());
+        let body = Body::stream(Tee::new(body, response_body.clone(), self.max_body_size));
+
+        self.push(Exchange {
+            id: self.next_id.fetch_add(1, Ordering::Relaxed),
+            time,
+            duration: start.elapsed(),
+            method,
+            uri,
+            request_headers,
+            status: parts.status,
+            response_headers: self.redact_headers(&parts.headers),
+            request_body,
+            response_body,
+            fields: self.fields.clone(),
+        });
+
+        Response::from_parts(parts, body)
+    }
+
+    /// The recorded exchanges, newest first
+    pub fn exchanges(&self) -> Vec<Arc<Exchange>> {
+        self.exchanges.lock().unwrap().iter().cloned().collect()
+    }
+
+    /// Remove all recorded exchanges
+    pub fn clear(&self) {
+        self.exchanges.lock().unwrap().clear();
+    }
+
+    /// An HTML page listing the recorded exchanges
+    pub fn inspect(&self) -> Markup {
+        let mut html = Markup::new();
+        html.push(PreEscaped(
+            "<!DOCTYPE html><html><head><meta charset=\"utf-8\"><title>Recorded requests</title>\
+             <style>body { font-family: sans-serif; } summary { cursor: pointer; } \
+             pre { background: #f4f4f4; padding: 0.5em; overflow: auto; }</style>\
+             </head><body><h1>Recorded requests</h1>",
+        ));
+
+        let exchanges = self.exchanges();
+        if exchanges.is_empty() {
+            html.push(PreEscaped("<p>No requests recorded yet.</p>"));
+        }
+
+        for exchange in exchanges {
+            html.push(PreEscaped("<details><summary>"))
+                .push_display(format_args!(
+                    "#{} {} {} \u{2192} {} ({} ms)",
+                    exchange.id,
+                    exchange.method,
+                    exchange.uri,
+                    exchange.status,
+                    exchange.duration.as_millis(),
+                ))
+                .push(PreEscaped("</summary><h3>Request</h3>"));
+            render_message(
+                &mut html,
+                &exchange.request_headers,
+                exchange.request_body(),
+            );
+            html.push(PreEscaped("<h3>Response</h3>"));
+            render_message(
+                &mut html,
+                &exchange.response_headers,
+                exchange.response_body(),
+            );
+            html.push(PreEscaped("</details>"));
+        }
+
+        html.push(PreEscaped("</body></html>"));
+        html
+    }
+
+    fn push(&self, exchange: Exchange) {
+        let mut exchanges = self.exchanges.lock().unwrap();
+        if exchanges.len() >= self.capacity {
+            exchanges.pop_back();
+        }
+        if self.capacity > 0 {
+            exchanges.push_front(Arc::new(exchange));
+        }
+    }
+
+    fn redact_headers(&self, headers: &HeaderMap) -> HeaderMap {
+        let mut headers = headers.clone();
+        for name in &self.headers {
+            if let http::header::Entry::Occupied(mut entry) = headers.entry(name.clone()) {
+                for value in entry.iter_mut() {
+                    *value = HeaderValue::from_static(REDACTED);
+                }
+            }
+        }
+        headers
+    }
+}
+
+fn render_message(html: &mut Markup, headers: &HeaderMap, body: RecordedBody) {
+    html.push(PreEscaped("<pre>"));
+    for (name, value) in headers {
+        html.push_display(format_args!(
+            "{name}: {}\n",
+            String::from_utf8_lossy(value.as_bytes())
+        ));
+    }
+    html.push(PreEscaped("</pre>"));
+
+    if body.len == 0 && body.complete {
+        return;
+    }
+
+    html.push(PreEscaped("<pre>"));
+    match std::str::from_utf8(&body.data) {
+        Ok(text) => html.push(text),
+        Err(_) => html.push_display(format_args!("[{} bytes of binary data]", body.data.len())),
+    };
+    html.push(PreEscaped("</pre>"));
+
+    if body.is_truncated() {
+        html.push(PreEscaped("<p>"))
+            .push_display(format_args!(
+                "Showing {} of {} bytes.",
+                body.data.len(),
+                body.len
+            ))
+            .push(PreEscaped("</p>"));
+    }
+    if !body.complete {
+        html.push(PreEscaped("<p>Body not read completely.</p>"));
+    }
+}
+
+/// A request and its response, recorded by the `Recorder`
+#[derive(Debug)]
+pub struct Exchange {
+    pub id: u64,
+    /// When the request was received
+    pub time: SystemTime,
+    /// The time until the handler returned a response
+    pub duration: Duration,
+    pub method: Method,
+    /// The request's path and query, with sensitive query fields redacted
+    pub uri: String,
+    pub request_headers: HeaderMap,
+    pub status: StatusCode,
+    pub response_headers: HeaderMap,
+    request_body: Arc<Capture>,
+    response_body: Arc<Capture>,
+    fields: Arc<Vec<Cow<'static, str>>>,
+}
+
+impl Exchange {
+    /// The request body recorded so far
+    pub fn request_body(&self) -> RecordedBody {
+        self.request_body
+            .get(self.request_headers.get(CONTENT_TYPE), &self.fields)
+    }
+
+    /// The response body recorded so far
+    pub fn response_body(&self) -> RecordedBody {
+        self.response_body
+            .get(self.response_headers.get(CONTENT_TYPE), &self.fields)
+    }
+}
+
+/// A (partially) recorded body
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RecordedBody {
+    /// The recorded data, up to the `Recorder`'s maximum body size
+    pub data: Bytes,
+    /// The number of bytes received so far
+    pub len: u64,
+    /// Whether the body has been read to the end
+    pub complete: bool,
+}
+
+impl RecordedBody {
+    /// Whether the body was larger than what was recorded
+    pub fn is_truncated(&self) -> bool {
+        (self.data.len() as u64) < self.len
+    }
+}
+
+#[derive(Debug, Default)]
+struct Capture {
+    state: Mutex<CaptureState>,
+}
+
+impl Capture {
+    fn get(&self, content_type: Option<&HeaderValue>, fields: &[Cow<'_, str>]) -> RecordedBody {
+        let state = self.state.lock().unwrap();
+        let mut body = RecordedBody {
+            data: Bytes::copy_from_slice(&state.data),
+            len: state.len,
+            complete: state.complete,
+        };
+        drop(state);
+
+        let content_type = content_type
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or_default();
+        if content_type.starts_with("application/x-www-form-urlencoded") {
+            body.data = Bytes::from(redact_form(&body.data, fields));
+        } else if content_type.starts_with("application/json") || content_type.contains("+json") {
+            body.data = redact_json(&body.data, fields);
+        }
+        body
+    }
+}
+
+#[derive(Debug, Default)]
+struct CaptureState {
+    data: BytesMut,
+    len: u64,
+    complete: bool,
+}
+
+/// A body that copies its data into a `Capture` as it is read
+#[pin_project]
+struct Tee<B> {
+    #[pin]
+    inner: B,
+    capture: Arc<Capture>,
+    limit: usize,
+}
+
+impl<B: http_body::Body> Tee<B> {
+    fn new(inner: B, capture: Arc<Capture>, limit: usize) -> Self {
+        capture.state.lock().unwrap().complete = inner.is_end_stream();
+        Self {
+            inner,
+            capture,
+            limit,
+        }
+    }
+}
+
+impl<B: http_body::Body<Data = Bytes>> http_body::Body for Tee<B> {
+    type Data = Bytes;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut TaskContext<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        let mut state = this.capture.state.lock().unwrap();
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    state.len += data.len() as u64;
+                    let keep = this.limit.saturating_sub(state.data.len()).min(data.len());
+                    state.data.extend_from_slice(&data[..keep]);
+                }
+            }
+            Some(Err(_)) => {}
+            None => state.complete = true,
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+/// Replace the values of `fields` in URL-encoded form data
+fn redact_form(data: &[u8], fields: &[Cow<'_, str>]) -> String {
+    let data = String::from_utf8_lossy(data);
+    let pairs = data.split('&').map(|pair| {
+        let name = pair.split('=').next().unwrap_or_default();
+        match fields.iter().any(|field| field == name) {
+            true => Cow::Owned(format!("{name}={REDACTED}")),
+            false => Cow::Borrowed(pair),
+        }
+    });
+    pairs.collect::<Vec<_>>().join("&")
+}
+
+/// Replace the values of `fields` in a JSON document
+///
+/// Documents that can't be parsed (for example, because they were truncated) are replaced
+/// entirely, since the fields can't be found reliably.
+fn redact_json(data: &[u8], fields: &[Cow<'_, str>]) -> Bytes {
+    fn visit(value: &mut serde_json::Value, fields: &[Cow<'_, str>]) {
+        match value {
+            serde_json::Value::Object(map) => {
+                for (name, value) in map.iter_mut() {
+                    match fields.iter().any(|field| field == name) {
+                        true => *value = serde_json::Value::from(REDACTED),
+                        false => visit(value, fields),
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => {
+                values.iter_mut().for_each(|value| visit(value, fields))
+            }
+            _ => {}
+        }
+    }
+
+    if data.is_empty() || fields.is_empty() {
+        return Bytes::copy_from_slice(data);
+    }
+
+    match serde_json::from_slice::<serde_json::Value>(data) {
+        Ok(mut value) => {
+            visit(&mut value, fields);
+            Bytes::from(value.to_string())
+        }
+        Err(_) => Bytes::from_static(b"[JSON body redacted: unable to parse]"),
+    }
+}
+
+const REDACTED: &str = "[redacted]";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn redact() {
+        let fields = [Cow::Borrowed("password"), Cow::Borrowed("token")];
+        assert_eq!(
+            redact_form(b"user=jane&password=hunter2&token", &fields),
+            "user=jane&password=[redacted]&token=[redacted]"
+        );
+        assert_eq!(
+            redact_json(br#"{"user":{"name":"jane","password":"hunter2"}}"#, &fields),
+            r#"{"user":{"name":"jane","password":"[redacted]"}}"#
+        );
+        assert_eq!(
+            redact_json(br#"{"password":"hun"#, &fields),
+            "[JSON body redacted: unable to parse]"
+        );
+    }
+}
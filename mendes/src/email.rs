@@ -0,0 +1,554 @@
+use std::error::Error as StdError;
+use std::fmt::{Display, Write};
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use data_encoding::{BASE64, HEXLOWER};
+use thiserror::Error;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpStream, ToSocketAddrs};
+use tokio::sync::mpsc;
+
+/// An email message with a plain text and/or an HTML body
+///
+/// Bodies take anything that implements `Display`, which includes askama templates:
+///
+/// ```no_run
+/// # use std::fmt;
+/// # use mendes::email::{Message, Outbox};
+/// # struct User {
+/// #     email: String,
+/// # }
+/// # struct WelcomeText<'a> {
+/// #     user: &'a User,
+/// # }
+/// # impl fmt::Display for WelcomeText<'_> {
+/// #     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct WelcomeHtml<'a> {
+/// #     user: &'a User,
+/// # }
+/// # impl fmt::Display for WelcomeHtml<'_> {
+/// #     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     outbox: Outbox,
+/// # }
+/// # fn welcome(app: &App, user: &User) {
+/// let msg = Message::new("Example <noreply@example.com>", "Welcome to Example")
+///     .to(&user.email)
+///     .text(WelcomeText { user: &user })
+///     .html(WelcomeHtml { user: &user });
+/// app.outbox.send(msg);
+/// # }
+/// ```
+///
+/// Messages with both bodies are sent as `multipart/alternative`, so that mail clients
+/// pick the richest version they can display.
+#[derive(Clone, Debug)]
+pub struct Message {
+    from: String,
+    to: Vec<String>,
+    cc: Vec<String>,
+    bcc: Vec<String>,
+    reply_to: Option<String>,
+    subject: String,
+    headers: Vec<(String, String)>,
+    text: Option<String>,
+    html: Option<String>,
+}
+
+impl Message {
+    /// Create a message; addresses may be bare (`a@example.com`) or named (`A <a@example.com>`)
+    pub fn new(from: impl Into<String>, subject: impl Into<String>) -> Self {
+        Self {
+            from: from.into(),
+            to: Vec::new(),
+            cc: Vec::new(),
+            bcc: Vec::new(),
+            reply_to: None,
+            subject: subject.into(),
+            headers: Vec::new(),
+            text: None,
+            html: None,
+        }
+    }
+
+    pub fn to(mut self, address: impl Into<String>) -> Self {
+        self.to.push(address.into());
+        self
+    }
+
+    pub fn cc(mut self, address: impl Into<String>) -> Self {
+        self.cc.push(address.into());
+        self
+    }
+
+    /// Add a recipient that is left out of the message headers
+    pub fn bcc(mut self, address: impl Into<String>) -> Self {
+        self.bcc.push(address.into());
+        self
+    }
+
+    pub fn reply_to(mut self, address: impl Into<String>) -> Self {
+        self.reply_to = Some(address.into());
+        self
+    }
+
+    /// Add a custom header, like `List-Unsubscribe`
+    pub fn header(mut self, name: impl Into<String>, value: impl Into<String>) -> Self {
+        self.headers.push((name.into(), value.into()));
+        self
+    }
+
+    /// Set the plain text body
+    pub fn text(mut self, body: impl Display) -> Self {
+        self.text = Some(body.to_string());
+        self
+    }
+
+    /// Set the HTML body
+    pub fn html(mut self, body: impl Display) -> Self {
+        self.html = Some(body.to_string());
+        self
+    }
+
+    /// The envelope sender, without a display name
+    pub fn sender(&self) -> &str {
+        address(&self.from)
+    }
+
+    /// The envelope recipients (including Bcc), without display names
+    pub fn recipients(&self) -> impl Iterator<Item = &str> {
+        self.to
+            .iter()
+            .chain(&self.cc)
+            .chain(&self.bcc)
+            .map(|addr| address(addr))
+    }
+
+    pub fn subject(&self) -> &str {
+        &self.subject
+    }
+
+    pub fn text_body(&self) -> Option<&str> {
+        self.text.as_deref()
+    }
+
+    pub fn html_body(&self) -> Option<&str> {
+        self.html.as_deref()
+    }
+
+    /// Format the message as MIME (RFC 5322), with CRLF line endings
+    pub fn format(&self) -> String {
+        let mut out = String::new();
+        let date = chrono::Utc::now().to_rfc2822();
+        let domain = self
+            .sender()
+            .rsplit_once('@')
+            .map_or("localhost", |(_, d)| d);
+        header(&mut out, "Date", &date);
+        header(
+            &mut out,
+            "Message-ID",
+            &format!("<{}@{domain}>", random_hex()),
+        );
+        header(&mut out, "From", &self.from);
+        if !self.to.is_empty() {
+            header(&mut out, "To", &self.to.join(", "));
+        }
+        if !self.cc.is_empty() {
+            header(&mut out, "Cc", &self.cc.join(", "));
+        }
+        if let Some(reply_to) = &self.reply_to {
+            header(&mut out, "Reply-To", reply_to);
+        }
+        header(&mut out, "Subject", &encode_word(&self.subject));
+        for (name, value) in &self.headers {
+            header(&mut out, name, value);
+        }
+        header(&mut out, "MIME-Version", "1.0");
+
+        match (&self.text, &self.html) {
+            (Some(text), Some(html)) => {
+                let boundary = format!("=_{}", random_hex());
+                let content_type = format!("multipart/alternative; boundary=\"{boundary}\"");
+                header(&mut out, "Content-Type", &content_type);
+                out.push_str("\r\n");
+                for (subtype, body) in [("plain", text), ("html", html)] {
+                    write!(out, "--{boundary}\r\n").unwrap();
+                    part(&mut out, subtype, body);
+                }
+                write!(out, "--{boundary}--\r\n").unwrap();
+            }
+            (Some(text), None) => part(&mut out, "plain", text),
+            (None, Some(html)) => part(&mut out, "html", html),
+            (None, None) => part(&mut out, "plain", ""),
+        }
+
+        out
+    }
+}
+
+/// Delivers messages, through SMTP or an email provider's API
+///
+/// Implement this for providers with an HTTP API by posting the message's fields (or its
+/// `format()`ted MIME form) with your HTTP client of choice.
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, msg: &Message) -> Result<(), Error>;
+}
+
+#[async_trait]
+impl<M: Mailer + ?Sized> Mailer for Arc<M> {
+    async fn send(&self, msg: &Message) -> Result<(), Error> {
+        (**self).send(msg).await
+    }
+}
+
+/// Sends messages to an SMTP relay
+///
+/// This speaks plain SMTP without TLS or authentication, so it is meant for relays on the
+/// local host or network (a Postfix instance or a mail sidecar) that take care of onward
+/// delivery. Use a `Mailer` for a provider API to send mail across the internet directly.
+#[derive(Clone, Debug)]
+pub struct Smtp<A> {
+    addr: A,
+    hello: String,
+}
+
+impl<A: ToSocketAddrs + Send + Sync> Smtp<A> {
+    /// Create a sender for the relay at `addr`, like `"localhost:25"`
+    pub fn new(addr: A) -> Self {
+        Self {
+            addr,
+            hello: "localhost".to_owned(),
+        }
+    }
+
+    /// The host name to send in the `EHLO` command (defaults to `localhost`)
+    pub fn hello(mut self, name: impl Into<String>) -> Self {
+        self.hello = name.into();
+        self
+    }
+}
+
+#[async_trait]
+impl<A: ToSocketAddrs + Send + Sync> Mailer for Smtp<A> {
+    async fn send(&self, msg: &Message) -> Result<(), Error> {
+        let recipients = msg.recipients().collect::<Vec<_>>();
+        if recipients.is_empty() {
+            return Err(Error::NoRecipients);
+        }
+
+        let mut conn = Connection::new(TcpStream::connect(&self.addr).await?);
+        conn.expect(220).await?;
+        conn.command(&format!("EHLO {}", self.hello), 250).await?;
+        conn.command(&format!("MAIL FROM:<{}>", msg.sender()), 250)
+            .await?;
+        for rcpt in recipients {
+            conn.command(&format!("RCPT TO:<{rcpt}>"), 250).await?;
+        }
+        conn.command("DATA", 354).await?;
+
+        // Dot-stuffing, from RFC 5321 section 4.5.2
+        let mut data = String::new();
+        for line in msg.format().split_inclusive("\r\n") {
+            if line.starts_with('.') {
+                data.push('.');
+            }
+            data.push_str(line);
+        }
+        data.push('.');
+        conn.command(&data, 250).await?;
+        conn.command("QUIT", 221).await
+    }
+}
+
+struct Connection {
+    stream: BufReader<TcpStream>,
+    line: String,
+}
+
+impl Connection {
+    fn new(stream: TcpStream) -> Self {
+        Self {
+            stream: BufReader::new(stream),
+            line: String::new(),
+        }
+    }
+
+    async fn command(&mut self, cmd: &str, code: u16) -> Result<(), Error> {
+        let stream = self.stream.get_mut();
+        stream.write_all(cmd.as_bytes()).await?;
+        stream.write_all(b"\r\n").await?;
+        self.expect(code).await
+    }
+
+    async fn expect(&mut self, code: u16) -> Result<(), Error> {
+        let mut message = String::new();
+        loop {
+            self.line.clear();
+            if self.stream.read_line(&mut self.line).await? == 0 {
+                return Err(Error::Protocol("connection closed".into()));
+            }
+
+            let line = self.line.trim_end();
+            let (received, last) = match (line.get(..3).map(str::parse), line.as_bytes().get(3)) {
+                (Some(Ok(received)), None | Some(b' ')) => (received, true),
+                (Some(Ok(received)), Some(b'-')) => (received, false),
+                _ => return Err(Error::Protocol(format!("invalid reply: {line:?}"))),
+            };
+
+            if !message.is_empty() {
+                message.push('\n');
+            }
+            message.push_str(line.get(4..).unwrap_or(""));
+            if !last {
+                continue;
+            }
+
+            return match received == code {
+                true => Ok(()),
+                false => Err(Error::Rejected {
+                    code: received,
+                    message,
+                }),
+            };
+        }
+    }
+}
+
+/// Delivers messages in the background
+///
+/// Request handlers can queue messages without waiting for the mailer. Messages that fail
+/// to send are passed to the error callback, which can log them or store them for a retry.
+#[derive(Clone, Debug)]
+pub struct Outbox {
+    tx: mpsc::UnboundedSender<Message>,
+}
+
+impl Outbox {
+    /// Spawn a task on the current tokio runtime that sends queued messages through `mailer`
+    pub fn spawn<M, F>(mailer: M, on_error: F) -> Self
+    where
+        M: Mailer + 'static,
+        F: Fn(Message, Error) + Send + 'static,
+    {
+        let (tx, mut rx) = mpsc::unbounded_channel::<Message>();
+        tokio::spawn(async move {
+            while let Some(msg) = rx.recv().await {
+                if let Err(error) = mailer.send(&msg).await {
+                    on_error(msg, error);
+                }
+            }
+        });
+        Self { tx }
+    }
+
+    /// Queue a message for delivery
+    ///
+    /// Returns the message if the background task is no longer running.
+    pub fn send(&self, msg: Message) -> Result<(), Box<Message>> {
+        self.tx.send(msg).map_err(|err| Box::new(err.0))
+    }
+}
+
+/// Collects messages instead of sending them, for tests
+#[derive(Debug, Default)]
+pub struct MemoryMailer {
+    sent: Mutex<Vec<Message>>,
+}
+
+impl MemoryMailer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Take the messages sent so far
+    pub fn take(&self) -> Vec<Message> {
+        std::mem::take(&mut *self.sent.lock().unwrap())
+    }
+}
+
+#[async_trait]
+impl Mailer for MemoryMailer {
+    async fn send(&self, msg: &Message) -> Result<(), Error> {
+        self.sent.lock().unwrap().push(msg.clone());
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("message has no recipients")]
+    NoRecipients,
+    #[error("SMTP protocol error: {0}")]
+    Protocol(String),
+    #[error("rejected by server ({code}): {message}")]
+    Rejected { code: u16, message: String },
+    #[error("mailer error: {0}")]
+    Mailer(Box<dyn StdError + Send + Sync>),
+}
+
+fn header(out: &mut String, name: &str, value: &str) {
+    // Strip line breaks so values can't inject headers
+    let value = value.replace(['\r', '\n'], " ");
+    write!(out, "{name}: {value}\r\n").unwrap();
+}
+
+fn part(out: &mut String, subtype: &str, body: &str) {
+    header(
+        out,
+        "Content-Type",
+        &format!("text/{subtype}; charset=utf-8"),
+    );
+    header(out, "Content-Transfer-Encoding", "base64");
+    out.push_str("\r\n");
+    let encoded = BASE64.encode(body.as_bytes());
+    // Lines must not be longer than 76 characters (RFC 2045 section 6.8)
+    for chunk in encoded.as_bytes().chunks(76) {
+        out.push_str(std::str::from_utf8(chunk).unwrap());
+        out.push_str("\r\n");
+    }
+}
+
+/// Encode non-ASCII header values as an RFC 2047 encoded word
+fn encode_word(value: &str) -> String {
+    match value.is_ascii() {
+        true => value.to_owned(),
+        false => format!("=?utf-8?B?{}?=", BASE64.encode(value.as_bytes())),
+    }
+}
+
+fn address(addr: &str) -> &str {
+    match (addr.rfind('<'), addr.rfind('>')) {
+        (Some(start), Some(end)) if start < end => &addr[start + 1..end],
+        _ => addr.trim(),
+    }
+}
+
+fn random_hex() -> String {
+    let mut buf = [0; 16];
+    getrandom::getrandom(&mut buf).expect("failed to generate random message id");
+    HEXLOWER.encode(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[test]
+    fn format() {
+        let msg = Message::new("Example <noreply@example.com>", "Grüße")
+            .to("a@example.com")
+            .bcc("hidden@example.com")
+            .text("Hello")
+            .html("<p>Hello</p>");
+        assert_eq!(msg.sender(), "noreply@example.com");
+        assert_eq!(
+            msg.recipients().collect::<Vec<_>>(),
+            ["a@example.com", "hidden@example.com"]
+        );
+
+        let formatted = msg.format();
+        assert!(formatted.contains("\r\nFrom: Example <noreply@example.com>\r\n"));
+        assert!(formatted.contains("\r\nTo: a@example.com\r\n"));
+        assert!(!formatted.contains("hidden@"));
+        assert!(formatted.contains("\r\nSubject: =?utf-8?B?R3LDvMOfZQ==?=\r\n"));
+        assert!(formatted.contains("@example.com>\r\n"));
+        assert!(formatted.contains("Content-Type: multipart/alternative; boundary="));
+        assert!(formatted.contains(
+            "text/plain; charset=utf-8\r\nContent-Transfer-Encoding: base64\r\n\r\nSGVsbG8=\r\n"
+        ));
+        assert!(formatted.contains("text/html; charset=utf-8"));
+        assert!(formatted.ends_with("--\r\n"));
+
+        let injected = Message::new("a@example.com", "Hi\r\nBcc: c@example.com").format();
+        assert!(!injected.contains("\r\nBcc:"));
+    }
+
+    #[tokio::test]
+    async fn smtp() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        let server = tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            let mut log = Vec::new();
+            stream.get_mut().write_all(b"220 ready\r\n").await.unwrap();
+            let mut line = String::new();
+            let mut data = false;
+            loop {
+                line.clear();
+                stream.read_line(&mut line).await.unwrap();
+                let cmd = line.trim_end().to_owned();
+                if data {
+                    match cmd == "." {
+                        true => data = false,
+                        false => {
+                            log.push(cmd);
+                            continue;
+                        }
+                    }
+                } else {
+                    log.push(cmd.clone());
+                }
+
+                let reply: &[u8] = match cmd.as_str() {
+                    "." => b"250 queued\r\n",
+                    "DATA" => {
+                        data = true;
+                        b"354 go ahead\r\n"
+                    }
+                    "QUIT" => {
+                        stream.get_mut().write_all(b"221 bye\r\n").await.unwrap();
+                        return log;
+                    }
+                    cmd if cmd.starts_with("EHLO") => b"250-hello\r\n250 8BITMIME\r\n",
+                    _ => b"250 ok\r\n",
+                };
+                stream.get_mut().write_all(reply).await.unwrap();
+            }
+        });
+
+        let msg = Message::new("noreply@example.com", "Hi")
+            .to("A <a@example.com>")
+            .text("Hello");
+        Smtp::new(addr).send(&msg).await.unwrap();
+        let log = server.await.unwrap();
+        assert_eq!(log[0], "EHLO localhost");
+        assert_eq!(log[1], "MAIL FROM:<noreply@example.com>");
+        assert_eq!(log[2], "RCPT TO:<a@example.com>");
+        assert!(log.contains(&"Subject: Hi".to_owned()));
+
+        assert!(matches!(
+            Smtp::new(addr)
+                .send(&Message::new("a@example.com", "Hi"))
+                .await,
+            Err(Error::NoRecipients)
+        ));
+    }
+
+    #[tokio::test]
+    async fn outbox() {
+        let mailer = Arc::new(MemoryMailer::new());
+        let outbox = Outbox::spawn(mailer.clone(), |_, _| {});
+        outbox
+            .send(Message::new("a@example.com", "Hi").to("b@example.com"))
+            .unwrap();
+        tokio::task::yield_now().await;
+        let sent = mailer.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].subject(), "Hi");
+    }
+}
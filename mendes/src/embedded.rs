@@ -0,0 +1,124 @@
+use bytes::Bytes;
+use http::header::{CONTENT_LENGTH, CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use http::request::Parts;
+use http::{Response, StatusCode};
+
+pub use mendes_macros::embed_dir;
+
+use crate::application::{Error, ErrorKind};
+
+/// Static files embedded into the binary with `embed_dir!()`
+///
+/// This makes it possible to deploy an application as a single binary, without a directory
+/// of static files next to it:
+///
+/// ```no_run
+/// # use std::borrow::Cow;
+/// # use mendes::embedded::{embed_dir, Embedded};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// static STATIC: Embedded = embed_dir!("tests/static");
+///
+/// #[handler(GET)]
+/// async fn files(_: &App, req: &Parts, #[rest] path: Cow<'_, str>) -> Result<Response<Body>, Error> {
+///     Ok(STATIC.serve(req, &path)?)
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Embedded {
+    files: &'static [EmbeddedFile],
+}
+
+impl Embedded {
+    /// Create from a list of files sorted by path, as generated by `embed_dir!()`
+    pub const fn new(files: &'static [EmbeddedFile]) -> Self {
+        Self { files }
+    }
+
+    /// Get the file at `path`, relative to the embedded directory
+    ///
+    /// Like `utils::file()`, this looks for an `index.html` file for directory paths.
+    pub fn get(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        let path = path.trim_matches('/');
+        self.find(path).or_else(|| match path {
+            "" => self.find("index.html"),
+            _ => self.find(&format!("{path}/index.html")),
+        })
+    }
+
+    /// Respond with the file at `path`, relative to the embedded directory
+    ///
+    /// Responses carry an `ETag` derived from the file's contents. Requests that already
+    /// have the current version (according to their `If-None-Match` header) get an empty
+    /// `304 Not Modified` response.
+    pub fn serve<B: From<Bytes>>(&self, req: &Parts, path: &str) -> Result<Response<B>, Error> {
+        let file = self
+            .get(path)
+            .ok_or_else(|| Error::from(ErrorKind::FileNotFound))?;
+
+        let mut builder = Response::builder().header(ETAG, file.etag);
+        if matches(req, file.etag) {
+            return Ok(builder
+                .status(StatusCode::NOT_MODIFIED)
+                .body(B::from(Bytes::new()))
+                .unwrap());
+        }
+
+        builder = builder
+            .status(StatusCode::OK)
+            .header(CONTENT_LENGTH, file.contents.len());
+        if let Some(mime) = mime_guess::from_path(file.path).first() {
+            builder = builder.header(CONTENT_TYPE, mime.to_string());
+        }
+
+        Ok(builder
+            .body(B::from(Bytes::from_static(file.contents)))
+            .unwrap())
+    }
+
+    /// Iterate over all embedded files
+    pub fn iter(&self) -> impl Iterator<Item = &'static EmbeddedFile> {
+        self.files.iter()
+    }
+
+    fn find(&self, path: &str) -> Option<&'static EmbeddedFile> {
+        let files = self.files;
+        files
+            .binary_search_by(|file| file.path.cmp(path))
+            .ok()
+            .map(|idx| &files[idx])
+    }
+}
+
+/// A single file embedded with `embed_dir!()`
+#[derive(Debug)]
+pub struct EmbeddedFile {
+    /// The path relative to the embedded directory, using `/` as the separator
+    pub path: &'static str,
+    pub contents: &'static [u8],
+    /// The quoted entity tag for the contents
+    pub etag: &'static str,
+}
+
+/// Whether the `If-None-Match` header in `req` matches `etag`
+fn matches(req: &Parts, etag: &str) -> bool {
+    req.headers
+        .get_all(IF_NONE_MATCH)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|tag| tag.trim())
+        .any(|tag| tag == "*" || tag.strip_prefix("W/").unwrap_or(tag) == etag)
+}
@@ -0,0 +1,511 @@
+use std::borrow::Cow;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{Response, StatusCode};
+
+use crate::application::{Application, IntoResponse};
+use crate::html::escape_into;
+
+/// An Atom feed
+///
+/// ```no_run
+/// # use chrono::{DateTime, Utc};
+/// # use mendes::feeds::{Atom, AtomEntry};
+/// # use mendes::{handler, Error};
+/// # struct Db;
+/// # struct Post {
+/// #     slug: String,
+/// #     title: String,
+/// #     body: String,
+/// #     updated: DateTime<Utc>,
+/// # }
+/// # impl Post {
+/// #     fn url(&self) -> String {
+/// #         format!("https://example.com/blog/{}", self.slug)
+/// #     }
+/// # }
+/// # impl Db {
+/// #     async fn recent_posts(&self) -> Result<Vec<Post>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn feed(app: &App) -> Result<Atom, Error> {
+///     let posts = app.db.recent_posts().await?;
+///     let mut feed = Atom::new("https://example.com/", "Example blog", posts[0].updated)
+///         .link("https://example.com/feed.atom");
+///     for post in posts {
+///         feed = feed.entry(
+///             AtomEntry::new(post.url(), post.title, post.updated).content_html(post.body),
+///         );
+///     }
+///     Ok(feed)
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug)]
+pub struct Atom {
+    id: Cow<'static, str>,
+    title: Cow<'static, str>,
+    updated: DateTime<Utc>,
+    subtitle: Option<Cow<'static, str>>,
+    link: Option<Cow<'static, str>>,
+    author: Option<Person>,
+    entries: Vec<AtomEntry>,
+}
+
+impl Atom {
+    /// Create a feed without entries
+    ///
+    /// The `id` must be a permanent IRI identifying the feed, usually the site's URL.
+    pub fn new(
+        id: impl Into<Cow<'static, str>>,
+        title: impl Into<Cow<'static, str>>,
+        updated: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            updated,
+            subtitle: None,
+            link: None,
+            author: None,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Set the subtitle
+    pub fn subtitle(mut self, subtitle: impl Into<Cow<'static, str>>) -> Self {
+        self.subtitle = Some(subtitle.into());
+        self
+    }
+
+    /// Set the URL the feed itself is served from
+    pub fn link(mut self, href: impl Into<Cow<'static, str>>) -> Self {
+        self.link = Some(href.into());
+        self
+    }
+
+    /// Set the default author for all entries
+    pub fn author(mut self, author: Person) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Add an entry
+    pub fn entry(mut self, entry: AtomEntry) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    /// Render the feed as XML
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity(256 + self.entries.len() * 512);
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+        element(&mut out, "id", &self.id);
+        element(&mut out, "title", &self.title);
+        if let Some(subtitle) = &self.subtitle {
+            element(&mut out, "subtitle", subtitle);
+        }
+        element(&mut out, "updated", &rfc3339(&self.updated));
+        if let Some(href) = &self.link {
+            out.push_str("<link rel=\"self\" href=\"");
+            escape_into(&mut out, href);
+            out.push_str("\"/>\n");
+        }
+        if let Some(author) = &self.author {
+            author.render(&mut out);
+        }
+
+        for entry in &self.entries {
+            entry.render(&mut out);
+        }
+
+        out.push_str("</feed>\n");
+        out
+    }
+}
+
+impl<A: Application> IntoResponse<A> for Atom
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        xml_response(self.render(), "application/atom+xml; charset=utf-8")
+    }
+}
+
+/// A single entry in an `Atom` feed
+#[derive(Clone, Debug)]
+pub struct AtomEntry {
+    id: Cow<'static, str>,
+    title: Cow<'static, str>,
+    updated: DateTime<Utc>,
+    link: Option<Cow<'static, str>>,
+    author: Option<Person>,
+    summary: Option<Cow<'static, str>>,
+    content: Option<Cow<'static, str>>,
+}
+
+impl AtomEntry {
+    /// Create an entry
+    ///
+    /// The `id` must be a permanent IRI identifying the entry. It is also used as the entry's
+    /// link, unless one is set with `link()`.
+    pub fn new(
+        id: impl Into<Cow<'static, str>>,
+        title: impl Into<Cow<'static, str>>,
+        updated: DateTime<Utc>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            title: title.into(),
+            updated,
+            link: None,
+            author: None,
+            summary: None,
+            content: None,
+        }
+    }
+
+    /// Set the URL of the entry's page
+    pub fn link(mut self, href: impl Into<Cow<'static, str>>) -> Self {
+        self.link = Some(href.into());
+        self
+    }
+
+    /// Set the author
+    pub fn author(mut self, author: Person) -> Self {
+        self.author = Some(author);
+        self
+    }
+
+    /// Set a plain text summary
+    pub fn summary(mut self, summary: impl Into<Cow<'static, str>>) -> Self {
+        self.summary = Some(summary.into());
+        self
+    }
+
+    /// Set the full content, as HTML
+    ///
+    /// The HTML is escaped when rendering the feed, as the format requires.
+    pub fn content_html(mut self, html: impl Into<Cow<'static, str>>) -> Self {
+        self.content = Some(html.into());
+        self
+    }
+
+    fn render(&self, out: &mut String) {
+        out.push_str("<entry>\n");
+        element(out, "id", &self.id);
+        element(out, "title", &self.title);
+        element(out, "updated", &rfc3339(&self.updated));
+        out.push_str("<link rel=\"alternate\" href=\"");
+        escape_into(out, self.link.as_ref().unwrap_or(&self.id));
+        out.push_str("\"/>\n");
+        if let Some(author) = &self.author {
+            author.render(out);
+        }
+        if let Some(summary) = &self.summary {
+            element(out, "summary", summary);
+        }
+        if let Some(content) = &self.content {
+            out.push_str("<content type=\"html\">");
+            escape_into(out, content);
+            out.push_str("</content>\n");
+        }
+        out.push_str("</entry>\n");
+    }
+}
+
+/// The author of an `Atom` feed or entry
+#[derive(Clone, Debug)]
+pub struct Person {
+    name: Cow<'static, str>,
+    email: Option<Cow<'static, str>>,
+    uri: Option<Cow<'static, str>>,
+}
+
+impl Person {
+    /// Create a person with the given name
+    pub fn new(name: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            name: name.into(),
+            email: None,
+            uri: None,
+        }
+    }
+
+    /// Set the email address
+    pub fn email(mut self, email: impl Into<Cow<'static, str>>) -> Self {
+        self.email = Some(email.into());
+        self
+    }
+
+    /// Set the URL of the person's home page
+    pub fn uri(mut self, uri: impl Into<Cow<'static, str>>) -> Self {
+        self.uri = Some(uri.into());
+        self
+    }
+
+    fn render(&self, out: &mut String) {
+        out.push_str("<author>\n");
+        element(out, "name", &self.name);
+        if let Some(email) = &self.email {
+            element(out, "email", email);
+        }
+        if let Some(uri) = &self.uri {
+            element(out, "uri", uri);
+        }
+        out.push_str("</author>\n");
+    }
+}
+
+/// An RSS 2.0 feed
+///
+/// Prefer `Atom` for new feeds; RSS is provided for clients that don't support it.
+#[derive(Clone, Debug)]
+pub struct Rss {
+    title: Cow<'static, str>,
+    link: Cow<'static, str>,
+    description: Cow<'static, str>,
+    language: Option<Cow<'static, str>>,
+    last_build: Option<DateTime<Utc>>,
+    items: Vec<RssItem>,
+}
+
+impl Rss {
+    /// Create a feed (an RSS "channel") without items
+    pub fn new(
+        title: impl Into<Cow<'static, str>>,
+        link: impl Into<Cow<'static, str>>,
+        description: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            title: title.into(),
+            link: link.into(),
+            description: description.into(),
+            language: None,
+            last_build: None,
+            items: Vec::new(),
+        }
+    }
+
+    /// Set the language the feed is written in, like `en-us`
+    pub fn language(mut self, language: impl Into<Cow<'static, str>>) -> Self {
+        self.language = Some(language.into());
+        self
+    }
+
+    /// Set the last time the content of the feed changed
+    pub fn last_build(mut self, date: DateTime<Utc>) -> Self {
+        self.last_build = Some(date);
+        self
+    }
+
+    /// Add an item
+    pub fn item(mut self, item: RssItem) -> Self {
+        self.items.push(item);
+        self
+    }
+
+    /// Render the feed as XML
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity(256 + self.items.len() * 512);
+        out.push_str("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+        out.push_str("<rss version=\"2.0\">\n<channel>\n");
+        element(&mut out, "title", &self.title);
+        element(&mut out, "link", &self.link);
+        element(&mut out, "description", &self.description);
+        if let Some(language) = &self.language {
+            element(&mut out, "language", language);
+        }
+        if let Some(date) = &self.last_build {
+            element(&mut out, "lastBuildDate", &date.to_rfc2822());
+        }
+
+        for item in &self.items {
+            item.render(&mut out);
+        }
+
+        out.push_str("</channel>\n</rss>\n");
+        out
+    }
+}
+
+impl<A: Application> IntoResponse<A> for Rss
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        xml_response(self.render(), "application/rss+xml; charset=utf-8")
+    }
+}
+
+/// A single item in an `Rss` feed
+#[derive(Clone, Debug)]
+pub struct RssItem {
+    title: Cow<'static, str>,
+    link: Cow<'static, str>,
+    description: Option<Cow<'static, str>>,
+    guid: Option<Cow<'static, str>>,
+    pub_date: Option<DateTime<Utc>>,
+}
+
+impl RssItem {
+    /// Create an item
+    ///
+    /// The `link` is also used as the item's permanent identifier, unless one is set with
+    /// `guid()`.
+    pub fn new(title: impl Into<Cow<'static, str>>, link: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            title: title.into(),
+            link: link.into(),
+            description: None,
+            guid: None,
+            pub_date: None,
+        }
+    }
+
+    /// Set the description, as HTML
+    ///
+    /// The HTML is escaped when rendering the feed, as the format requires.
+    pub fn description(mut self, html: impl Into<Cow<'static, str>>) -> Self {
+        self.description = Some(html.into());
+        self
+    }
+
+    /// Set a permanent identifier that isn't a URL
+    pub fn guid(mut self, guid: impl Into<Cow<'static, str>>) -> Self {
+        self.guid = Some(guid.into());
+        self
+    }
+
+    /// Set the publication date
+    pub fn pub_date(mut self, date: DateTime<Utc>) -> Self {
+        self.pub_date = Some(date);
+        self
+    }
+
+    fn render(&self, out: &mut String) {
+        out.push_str("<item>\n");
+        element(out, "title", &self.title);
+        element(out, "link", &self.link);
+        if let Some(description) = &self.description {
+            element(out, "description", description);
+        }
+        match &self.guid {
+            Some(guid) => {
+                out.push_str("<guid isPermaLink=\"false\">");
+                escape_into(out, guid);
+                out.push_str("</guid>\n");
+            }
+            None => element(out, "guid", &self.link),
+        }
+        if let Some(date) = &self.pub_date {
+            element(out, "pubDate", &date.to_rfc2822());
+        }
+        out.push_str("</item>\n");
+    }
+}
+
+fn element(out: &mut String, name: &str, text: &str) {
+    out.push('<');
+    out.push_str(name);
+    out.push('>');
+    escape_into(out, text);
+    out.push_str("</");
+    out.push_str(name);
+    out.push_str(">\n");
+}
+
+fn rfc3339(date: &DateTime<Utc>) -> String {
+    date.to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+fn xml_response<B: From<String>>(body: String, content_type: &'static str) -> Response<B> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, content_type)
+        .body(body.into())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::TimeZone;
+
+    use super::*;
+
+    #[test]
+    fn atom() {
+        let updated = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let feed = Atom::new("https://example.com/", "Tom & Jerry", updated)
+            .link("https://example.com/feed.atom")
+            .author(Person::new("Tom"))
+            .entry(
+                AtomEntry::new("https://example.com/1", "First", updated)
+                    .content_html("<p>Hello</p>"),
+            );
+
+        assert_eq!(
+            feed.render(),
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<feed xmlns=\"http://www.w3.org/2005/Atom\">\n",
+                "<id>https://example.com/</id>\n",
+                "<title>Tom &amp; Jerry</title>\n",
+                "<updated>2024-05-01T12:00:00Z</updated>\n",
+                "<link rel=\"self\" href=\"https://example.com/feed.atom\"/>\n",
+                "<author>\n<name>Tom</name>\n</author>\n",
+                "<entry>\n",
+                "<id>https://example.com/1</id>\n",
+                "<title>First</title>\n",
+                "<updated>2024-05-01T12:00:00Z</updated>\n",
+                "<link rel=\"alternate\" href=\"https://example.com/1\"/>\n",
+                "<content type=\"html\">&lt;p&gt;Hello&lt;/p&gt;</content>\n",
+                "</entry>\n",
+                "</feed>\n",
+            )
+        );
+    }
+
+    #[test]
+    fn rss() {
+        let date = Utc.with_ymd_and_hms(2024, 5, 1, 12, 0, 0).unwrap();
+        let feed = Rss::new("Blog", "https://example.com/", "Posts")
+            .item(RssItem::new("First", "https://example.com/1").pub_date(date))
+            .item(RssItem::new("Second", "https://example.com/2").guid("post-2"));
+
+        assert_eq!(
+            feed.render(),
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<rss version=\"2.0\">\n<channel>\n",
+                "<title>Blog</title>\n",
+                "<link>https://example.com/</link>\n",
+                "<description>Posts</description>\n",
+                "<item>\n<title>First</title>\n<link>https://example.com/1</link>\n",
+                "<guid>https://example.com/1</guid>\n",
+                "<pubDate>Wed, 1 May 2024 12:00:00 +0000</pubDate>\n</item>\n",
+                "<item>\n<title>Second</title>\n<link>https://example.com/2</link>\n",
+                "<guid isPermaLink=\"false\">post-2</guid>\n</item>\n",
+                "</channel>\n</rss>\n",
+            )
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use http::request::Parts;
+
+use crate::application::{Application, ErrorKind, FromContext, PathState};
+
+/// The feature flags for a single request
+///
+/// Store `Flags` in the request extensions before routing, keyed by the user or session
+/// that percentage rollouts should be consistent for. Handlers can then extract it:
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use mendes::flags::{FlagProvider, Flags};
+/// # use mendes::http::Response;
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # struct User {
+/// #     id: u64,
+/// # }
+/// # struct App {
+/// #     flags: Arc<dyn FlagProvider>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     let mut flags = Flags::new(cx.app.flags.clone());
+///     if let Some(user) = cx.req.extensions.get::<User>() {
+///         flags = flags.key(user.id.to_string());
+///     }
+///     cx.req.extensions.insert(flags);
+///     route!(match cx.path() {
+///         Some("checkout") => checkout,
+///     })
+/// }
+/// # }
+///
+/// #[handler(GET)]
+/// async fn checkout(_: &App, flags: Flags) -> Result<Response<Body>, Error> {
+///     if flags.enabled("new-checkout").await {
+///         todo!()
+///     }
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Each flag is looked up from the provider at most once per request, so a request sees
+/// the same state for a flag even if it changes while the request is being handled.
+/// Extraction fails with an internal server error if no `Flags` were stored for the request.
+#[derive(Clone)]
+pub struct Flags {
+    provider: Arc<dyn FlagProvider>,
+    key: Option<Arc<str>>,
+    cache: Arc<Mutex<HashMap<String, bool>>>,
+}
+
+impl Flags {
+    /// Create flags for a request without a rollout key
+    ///
+    /// Flags in a percentage rollout are disabled until a key is set.
+    pub fn new(provider: Arc<dyn FlagProvider>) -> Self {
+        Self {
+            provider,
+            key: None,
+            cache: Arc::default(),
+        }
+    }
+
+    /// Set the key identifying the user or session for percentage rollouts
+    pub fn key(mut self, key: impl Into<Arc<str>>) -> Self {
+        self.key = Some(key.into());
+        self
+    }
+
+    /// Whether the flag called `name` is enabled for this request
+    ///
+    /// Unknown flags are disabled.
+    pub async fn enabled(&self, name: &str) -> bool {
+        if let Some(&enabled) = self.cache.lock().unwrap().get(name) {
+            return enabled;
+        }
+
+        let enabled = match self.provider.flag(name).await {
+            Some(flag) => flag.enabled_for(name, self.key.as_deref()),
+            None => false,
+        };
+
+        *self
+            .cache
+            .lock()
+            .unwrap()
+            .entry(name.to_owned())
+            .or_insert(enabled)
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for Flags {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        match req.extensions.get::<Flags>() {
+            Some(flags) => Ok(flags.clone()),
+            None => Err(state.reject::<A>(ErrorKind::ExtensionMissing.into(), req)),
+        }
+    }
+}
+
+/// The state of a feature flag
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Flag {
+    Enabled,
+    Disabled,
+    /// Enabled for the given percentage of rollout keys
+    Rollout(u8),
+}
+
+impl Flag {
+    /// Whether the flag called `name` is enabled for the given rollout key
+    ///
+    /// Rollouts hash the flag name together with the key, such that a key consistently
+    /// falls in or out of the rollout for a flag, and the same keys don't get enabled first
+    /// for every flag.
+    pub fn enabled_for(self, name: &str, key: Option<&str>) -> bool {
+        match (self, key) {
+            (Flag::Enabled, _) => true,
+            (Flag::Disabled, _) => false,
+            (Flag::Rollout(percentage), Some(key)) => bucket(name, key) < u32::from(percentage),
+            (Flag::Rollout(_), None) => false,
+        }
+    }
+}
+
+/// A source of feature flag states, like a database table or a remote flag service
+///
+/// Implementations for remote services should cache flag states: `Flags` only caches them
+/// for the duration of a request.
+#[async_trait]
+pub trait FlagProvider: Send + Sync {
+    /// Get the state of the flag called `name`, if it's known
+    async fn flag(&self, name: &str) -> Option<Flag>;
+}
+
+/// A `FlagProvider` with a fixed set of flags, for example from the application configuration
+#[derive(Clone, Debug, Default)]
+pub struct StaticFlags {
+    flags: HashMap<String, Flag>,
+}
+
+impl StaticFlags {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the state of the flag called `name`
+    pub fn set(mut self, name: impl Into<String>, flag: Flag) -> Self {
+        self.flags.insert(name.into(), flag);
+        self
+    }
+}
+
+impl<S: Into<String>> FromIterator<(S, Flag)> for StaticFlags {
+    fn from_iter<I: IntoIterator<Item = (S, Flag)>>(iter: I) -> Self {
+        Self {
+            flags: iter
+                .into_iter()
+                .map(|(name, flag)| (name.into(), flag))
+                .collect(),
+        }
+    }
+}
+
+#[async_trait]
+impl FlagProvider for StaticFlags {
+    async fn flag(&self, name: &str) -> Option<Flag> {
+        self.flags.get(name).copied()
+    }
+}
+
+/// Map the flag `name` and rollout `key` to a bucket in `0..100`
+///
+/// This uses 32-bit FNV-1a, which (unlike the standard library's hashers) is stable across
+/// Rust versions and platforms.
+fn bucket(name: &str, key: &str) -> u32 {
+    let mut hash = 0x811c9dc5u32;
+    for byte in name.bytes().chain([0]).chain(key.bytes()) {
+        hash ^= u32::from(byte);
+        hash = hash.wrapping_mul(0x01000193);
+    }
+    hash % 100
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+
+    #[test]
+    fn rollout() {
+        let flag = Flag::Rollout(25);
+        let enabled = (0..1000)
+            .filter(|i| flag.enabled_for("beta", Some(&i.to_string())))
+            .count();
+        assert!((200..300).contains(&enabled), "{enabled}");
+
+        for i in 0..100 {
+            let key = i.to_string();
+            assert!(Flag::Rollout(100).enabled_for("beta", Some(&key)));
+            assert!(!Flag::Rollout(0).enabled_for("beta", Some(&key)));
+        }
+
+        assert!(!flag.enabled_for("beta", None));
+        assert!(Flag::Enabled.enabled_for("beta", None));
+        assert!(!Flag::Disabled.enabled_for("beta", Some("1")));
+    }
+
+    #[tokio::test]
+    async fn cached() {
+        struct Counting(AtomicUsize);
+
+        #[async_trait]
+        impl FlagProvider for Counting {
+            async fn flag(&self, _: &str) -> Option<Flag> {
+                match self.0.fetch_add(1, Ordering::SeqCst) {
+                    0 => Some(Flag::Enabled),
+                    _ => Some(Flag::Disabled),
+                }
+            }
+        }
+
+        let provider = Arc::new(Counting(AtomicUsize::new(0)));
+        let flags = Flags::new(provider.clone());
+        assert!(flags.enabled("beta").await);
+        assert!(flags.clone().enabled("beta").await);
+        assert_eq!(provider.0.load(Ordering::SeqCst), 1);
+
+        let flags = Flags::new(Arc::new(StaticFlags::new().set("beta", Flag::Enabled)));
+        assert!(flags.enabled("beta").await);
+        assert!(!flags.enabled("gamma").await);
+    }
+}
@@ -4,6 +4,8 @@ use std::{fmt, str};
 pub use mendes_macros::{form, ToField};
 use thiserror::Error;
 
+use crate::html::{Escaped, Markup, PreEscaped, Render};
+
 #[cfg(feature = "uploads")]
 #[cfg_attr(docsrs, doc(cfg(feature = "uploads")))]
 pub use crate::multipart::{from_form_data, File};
@@ -57,20 +59,20 @@ impl fmt::Display for Form {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(fmt, "<form")?;
         if let Some(s) = &self.action {
-            write!(fmt, r#" action="{s}""#)?;
+            write!(fmt, r#" action="{}""#, Escaped(s))?;
         }
         if let Some(s) = &self.enctype {
-            write!(fmt, r#" enctype="{s}""#)?;
+            write!(fmt, r#" enctype="{}""#, Escaped(s))?;
         }
         if let Some(s) = &self.method {
-            write!(fmt, r#" method="{s}""#)?;
+            write!(fmt, r#" method="{}""#, Escaped(s))?;
         }
         if !self.classes.is_empty() {
             write!(fmt, r#" class=""#)?;
             for (i, s) in self.classes.iter().enumerate() {
                 match i {
-                    0 => write!(fmt, "{s}")?,
-                    _ => write!(fmt, " {s}")?,
+                    0 => write!(fmt, "{}", Escaped(s))?,
+                    _ => write!(fmt, " {}", Escaped(s))?,
                 }
             }
             write!(fmt, "\"")?;
@@ -83,6 +85,13 @@ impl fmt::Display for Form {
     }
 }
 
+/// Forms escape their own contents, so they are embedded in `Markup` as-is
+impl Render for &Form {
+    fn render(self, out: &mut Markup) {
+        out.push(PreEscaped(self));
+    }
+}
+
 pub struct FieldSet {
     pub legend: Option<&'static str>,
     pub items: Vec<Item>,
@@ -92,7 +101,7 @@ impl fmt::Display for FieldSet {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(fmt, "<fieldset>")?;
         if let Some(s) = self.legend {
-            write!(fmt, "<legend>{s}</legend>")?;
+            write!(fmt, "<legend>{}</legend>", Escaped(s))?;
         }
         for item in &self.items {
             write!(fmt, "{item}")?;
@@ -188,11 +197,11 @@ impl fmt::Display for Item {
             (ItemContents::Single(f), Some(l)) => write!(
                 fmt,
                 r#"<label for="{}">{}</label>{}"#,
-                f.name().unwrap(),
-                l,
+                Escaped(f.name().unwrap()),
+                Escaped(l),
                 self.contents
             ),
-            (_, Some(l)) => write!(fmt, r#"<label>{}</label>{}"#, l, self.contents),
+            (_, Some(l)) => write!(fmt, r#"<label>{}</label>{}"#, Escaped(l), self.contents),
             (_, None) => write!(fmt, "{}", self.contents),
         }
     }
@@ -277,7 +286,7 @@ impl fmt::Display for Checkbox {
         write!(
             fmt,
             r#"<input type="checkbox" name="{}" value="true""#,
-            self.name
+            Escaped(&self.name)
         )?;
         if self.checked {
             write!(fmt, " checked")?;
@@ -293,9 +302,9 @@ pub struct Date {
 
 impl fmt::Display for Date {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<input type="date" name="{}""#, self.name)?;
+        write!(fmt, r#"<input type="date" name="{}""#, Escaped(&self.name))?;
         if let Some(s) = &self.value {
-            write!(fmt, r#" value="{s}""#)?;
+            write!(fmt, r#" value="{}""#, Escaped(s))?;
         }
         write!(fmt, ">")
     }
@@ -308,9 +317,9 @@ pub struct Email {
 
 impl fmt::Display for Email {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<input type="email" name="{}""#, self.name)?;
+        write!(fmt, r#"<input type="email" name="{}""#, Escaped(&self.name))?;
         if let Some(s) = &self.value {
-            write!(fmt, r#" value="{s}""#)?;
+            write!(fmt, r#" value="{}""#, Escaped(s))?;
         }
         write!(fmt, ">")
     }
@@ -322,7 +331,7 @@ pub struct FileInput {
 
 impl fmt::Display for FileInput {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<input type="file" name="{}">"#, self.name)
+        write!(fmt, r#"<input type="file" name="{}">"#, Escaped(&self.name))
     }
 }
 
@@ -339,9 +348,13 @@ impl Hidden {
 
 impl fmt::Display for Hidden {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<input type="hidden" name="{}""#, self.name)?;
+        write!(
+            fmt,
+            r#"<input type="hidden" name="{}""#,
+            Escaped(&self.name)
+        )?;
         if let Some(s) = &self.value {
-            write!(fmt, r#" value="{s}""#)?;
+            write!(fmt, r#" value="{}""#, Escaped(s))?;
         }
         write!(fmt, ">")
     }
@@ -354,9 +367,13 @@ pub struct Number {
 
 impl fmt::Display for Number {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<input type="number" name="{}""#, self.name)?;
+        write!(
+            fmt,
+            r#"<input type="number" name="{}""#,
+            Escaped(&self.name)
+        )?;
         if let Some(s) = &self.value {
-            write!(fmt, r#" value="{s}""#)?;
+            write!(fmt, r#" value="{}""#, Escaped(s))?;
         }
         write!(fmt, ">")
     }
@@ -369,9 +386,13 @@ pub struct Password {
 
 impl fmt::Display for Password {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<input type="password" name="{}""#, self.name)?;
+        write!(
+            fmt,
+            r#"<input type="password" name="{}""#,
+            Escaped(&self.name)
+        )?;
         if let Some(s) = &self.value {
-            write!(fmt, r#" value="{s}""#)?;
+            write!(fmt, r#" value="{}""#, Escaped(s))?;
         }
         write!(fmt, ">")
     }
@@ -384,7 +405,7 @@ pub struct Select {
 
 impl fmt::Display for Select {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<select name="{}">"#, &self.name)?;
+        write!(fmt, r#"<select name="{}">"#, Escaped(&self.name))?;
         for opt in &self.options {
             write!(fmt, "{opt}")?;
         }
@@ -401,14 +422,14 @@ pub struct SelectOption {
 
 impl fmt::Display for SelectOption {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<option value="{}""#, self.value)?;
+        write!(fmt, r#"<option value="{}""#, Escaped(&self.value))?;
         if self.disabled {
             write!(fmt, " disabled")?;
         }
         if self.selected {
             write!(fmt, " selected")?;
         }
-        write!(fmt, ">{}</option>", self.label)
+        write!(fmt, ">{}</option>", Escaped(&self.label))
     }
 }
 
@@ -420,7 +441,7 @@ impl fmt::Display for Submit {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(fmt, r#"<input type="submit""#)?;
         if let Some(s) = &self.value {
-            write!(fmt, r#" value="{s}""#)?;
+            write!(fmt, r#" value="{}""#, Escaped(s))?;
         }
         write!(fmt, ">")
     }
@@ -433,9 +454,9 @@ pub struct Text {
 
 impl fmt::Display for Text {
     fn fmt(&self, fmt: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(fmt, r#"<input type="text" name="{}""#, self.name)?;
+        write!(fmt, r#"<input type="text" name="{}""#, Escaped(&self.name))?;
         if let Some(s) = &self.value {
-            write!(fmt, r#" value="{s}""#)?;
+            write!(fmt, r#" value="{}""#, Escaped(s))?;
         }
         write!(fmt, ">")
     }
@@ -0,0 +1,632 @@
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use std::{fs, io};
+
+use http::request::Parts;
+use thiserror::Error;
+
+use crate::application::{Application, FromContext, PathState};
+
+/// Give the `GeoIp` extractor access to the application's geolocation database
+pub trait AppWithGeoIp: Application {
+    fn geoip(&self) -> &Database;
+
+    /// The IP address of the client that sent `req`
+    ///
+    /// By default, this uses an `IpAddr` from the request extensions, or the peer address from
+    /// `ClientAddr` (with the `hyper` feature). Applications behind a reverse proxy should
+    /// resolve the client address from the proxy's headers, and insert it as an `IpAddr`
+    /// extension or override this method.
+    fn client_ip(&self, req: &Parts) -> Option<IpAddr> {
+        if let Some(ip) = req.extensions.get::<IpAddr>() {
+            return Some(*ip);
+        }
+
+        #[cfg(feature = "hyper")]
+        if let Some(addr) = req.extensions.get::<crate::hyper::ClientAddr>() {
+            return Some(addr.ip());
+        }
+
+        None
+    }
+}
+
+/// The location of the client, according to the application's `Database`
+///
+/// Both fields are `None` if the client address is unknown or not in the database, so handlers
+/// must decide on a default:
+///
+/// ```no_run
+/// # use std::collections::HashSet;
+/// # use mendes::geoip::{AppWithGeoIp, Database, GeoIp};
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # struct App {
+/// #     embargoed: HashSet<String>,
+/// #     geoip: Database,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithGeoIp for App {
+/// #     fn geoip(&self) -> &Database {
+/// #         &self.geoip
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn checkout(app: &App, geo: GeoIp) -> Result<Response<Body>, Error> {
+///     if geo.country.as_deref().is_some_and(|c| app.embargoed.contains(c)) {
+///         return Err(Error::forbidden("not available in your country"));
+///     }
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct GeoIp {
+    /// The ISO 3166-1 country code, like `"NL"`
+    pub country: Option<String>,
+    /// The ISO 3166-2 subdivision code within the country, like `"NH"`
+    pub region: Option<String>,
+}
+
+impl<'a, A: AppWithGeoIp> FromContext<'a, A> for GeoIp {
+    fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(app
+            .client_ip(req)
+            .and_then(|ip| app.geoip().locate(ip))
+            .unwrap_or_default())
+    }
+}
+
+/// A geolocation database in the MaxMind DB format
+///
+/// This reads the GeoIP2 and GeoLite2 Country and City databases (and others with the same
+/// record structure). The database is kept in memory, so load it once at startup.
+pub struct Database {
+    data: Vec<u8>,
+    node_count: usize,
+    record_size: usize,
+    ip_version: u16,
+}
+
+impl Database {
+    /// Load the database from the file at `path`
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_bytes(fs::read(path)?)
+    }
+
+    /// Load the database from its contents
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, Error> {
+        let start = data
+            .windows(METADATA_MARKER.len())
+            .rposition(|window| window == METADATA_MARKER)
+            .ok_or(Error::Invalid("metadata not found"))?
+            + METADATA_MARKER.len();
+
+        // Pointers in the metadata are relative to its start
+        let decoder = Decoder {
+            data: &data,
+            base: start,
+        };
+        let metadata = decoder.decode(start, 0)?.0;
+        let field = |name| match metadata.get(name) {
+            Some(Value::Uint(value)) => Ok(*value),
+            _ => Err(Error::Invalid("missing metadata field")),
+        };
+
+        let (node_count, record_size) = (field("node_count")?, field("record_size")?);
+        let ip_version = field("ip_version")?;
+        if !matches!(record_size, 24 | 28 | 32) || !matches!(ip_version, 4 | 6) {
+            return Err(Error::Invalid("unsupported search tree"));
+        }
+
+        // Each node takes at least 6 bytes, so this also keeps `data_start()` from overflowing
+        let node_count = match usize::try_from(node_count) {
+            Ok(node_count) if node_count <= data.len() => node_count,
+            _ => return Err(Error::Invalid("search tree exceeds file size")),
+        };
+
+        let db = Self {
+            node_count,
+            record_size: record_size as usize,
+            ip_version: ip_version as u16,
+            data,
+        };
+
+        match db.data_start() <= start {
+            true => Ok(db),
+            false => Err(Error::Invalid("search tree exceeds file size")),
+        }
+    }
+
+    /// Find the country and region for `ip`
+    pub fn locate(&self, ip: IpAddr) -> Option<GeoIp> {
+        let record = self.lookup(ip)?;
+        let country = record
+            .get("country")
+            .or_else(|| record.get("registered_country"))
+            .and_then(|country| country.get("iso_code"))
+            .and_then(Value::as_str)
+            .map(str::to_owned);
+        let region = match record.get("subdivisions") {
+            Some(Value::Array(subdivisions)) => subdivisions
+                .first()
+                .and_then(|subdivision| subdivision.get("iso_code"))
+                .and_then(Value::as_str)
+                .map(str::to_owned),
+            _ => None,
+        };
+
+        Some(GeoIp { country, region })
+    }
+
+    /// Get the full database record for `ip`
+    ///
+    /// IPv4-mapped IPv6 addresses (`::ffff:a.b.c.d`, as reported by dual-stack listeners) are
+    /// looked up as the IPv4 address they contain.
+    pub fn lookup(&self, ip: IpAddr) -> Option<Value> {
+        let ip = match ip {
+            IpAddr::V6(ip) => ip.to_ipv4_mapped().map_or(IpAddr::V6(ip), IpAddr::V4),
+            ip => ip,
+        };
+
+        let (bits, len) = match (ip, self.ip_version) {
+            (IpAddr::V4(ip), 4) => (u128::from(u32::from(ip)) << 96, 32),
+            (IpAddr::V4(ip), _) => (u128::from(u32::from(ip)), 128),
+            (IpAddr::V6(ip), 6) => (u128::from(ip), 128),
+            (IpAddr::V6(_), _) => return None,
+        };
+
+        let mut node = 0;
+        for i in 0..len {
+            if node >= self.node_count {
+                break;
+            }
+            let bit = (bits >> (127 - i)) & 1;
+            node = self.record(node, bit == 1)?;
+        }
+
+        // A record equal to the node count means there is no data for the address
+        let offset = node.checked_sub(self.node_count + 16)?;
+        let decoder = Decoder {
+            data: &self.data,
+            base: self.data_start(),
+        };
+        decoder
+            .decode(self.data_start() + offset, 0)
+            .ok()
+            .map(|(value, _)| value)
+    }
+
+    fn record(&self, node: usize, right: bool) -> Option<usize> {
+        let size = self.record_size * 2 / 8;
+        let bytes = self.data.get(node * size..(node + 1) * size)?;
+        let uint = |bytes: &[u8]| bytes.iter().fold(0, |acc, b| acc << 8 | *b as usize);
+        Some(match (self.record_size, right) {
+            (24, false) => uint(&bytes[..3]),
+            (24, true) => uint(&bytes[3..]),
+            (28, false) => (bytes[3] as usize >> 4) << 24 | uint(&bytes[..3]),
+            (28, true) => (bytes[3] as usize & 0x0f) << 24 | uint(&bytes[4..]),
+            (_, false) => uint(&bytes[..4]),
+            (_, true) => uint(&bytes[4..]),
+        })
+    }
+
+    fn data_start(&self) -> usize {
+        self.node_count * self.record_size * 2 / 8 + 16
+    }
+}
+
+/// A value from a MaxMind DB data section
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    String(String),
+    Double(f64),
+    Bytes(Vec<u8>),
+    Uint(u128),
+    Int(i32),
+    Map(Vec<(String, Value)>),
+    Array(Vec<Value>),
+    Bool(bool),
+    Float(f32),
+}
+
+impl Value {
+    /// Get the value for `key`, if this is a map
+    pub fn get(&self, key: &str) -> Option<&Value> {
+        match self {
+            Self::Map(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+struct Decoder<'a> {
+    data: &'a [u8],
+    /// The start of the section that pointers are relative to
+    base: usize,
+}
+
+impl Decoder<'_> {
+    /// Decode the value at `offset`, returning it and the offset following it
+    fn decode(&self, offset: usize, depth: usize) -> Result<(Value, usize), Error> {
+        if depth > MAX_DEPTH {
+            return Err(Error::Invalid("data nested too deeply"));
+        }
+
+        let ctrl = self.byte(offset)?;
+        let mut pos = offset + 1;
+        let mut kind = ctrl >> 5;
+        if kind == 0 {
+            kind = 7 + self.byte(pos)?;
+            pos += 1;
+        }
+
+        if kind == 1 {
+            let (len, vvv) = (((ctrl >> 3) & 0x03) as usize + 1, (ctrl & 0x07) as usize);
+            let bytes = self.bytes(pos, len)?;
+            let pointer = match len {
+                1 => vvv << 8 | uint(bytes) as usize,
+                2 => (vvv << 16 | uint(bytes) as usize) + 2048,
+                3 => (vvv << 24 | uint(bytes) as usize) + 526_336,
+                _ => uint(bytes) as usize,
+            };
+            let (value, _) = self.decode(self.base + pointer, depth + 1)?;
+            return Ok((value, pos + len));
+        }
+
+        let mut size = (ctrl & 0x1f) as usize;
+        if size >= 29 {
+            let extra = size - 28;
+            let bytes = uint(self.bytes(pos, extra)?) as usize;
+            size = [29, 285, 65_821][extra - 1] + bytes;
+            pos += extra;
+        }
+
+        let value = match kind {
+            2 => match std::str::from_utf8(self.bytes(pos, size)?) {
+                Ok(s) => Value::String(s.to_owned()),
+                Err(_) => return Err(Error::Invalid("invalid UTF-8 string")),
+            },
+            3 => Value::Double(f64::from_bits(uint(self.bytes(pos, 8)?) as u64)),
+            4 => Value::Bytes(self.bytes(pos, size)?.to_vec()),
+            5 | 6 | 9 | 10 => Value::Uint(uint(self.bytes(pos, size)?)),
+            7 => {
+                let mut entries = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let (key, next) = self.decode(pos, depth + 1)?;
+                    let key = match key {
+                        Value::String(key) => key,
+                        _ => return Err(Error::Invalid("map key is not a string")),
+                    };
+                    let (value, next) = self.decode(next, depth + 1)?;
+                    entries.push((key, value));
+                    pos = next;
+                }
+                return Ok((Value::Map(entries), pos));
+            }
+            8 => Value::Int(uint(self.bytes(pos, size)?) as u32 as i32),
+            11 => {
+                let mut values = Vec::with_capacity(size.min(64));
+                for _ in 0..size {
+                    let (value, next) = self.decode(pos, depth + 1)?;
+                    values.push(value);
+                    pos = next;
+                }
+                return Ok((Value::Array(values), pos));
+            }
+            14 => return Ok((Value::Bool(size != 0), pos)),
+            15 => Value::Float(f32::from_bits(uint(self.bytes(pos, 4)?) as u32)),
+            _ => return Err(Error::Invalid("unknown data type")),
+        };
+
+        let len = match kind {
+            3 => 8,
+            15 => 4,
+            _ => size,
+        };
+        Ok((value, pos + len))
+    }
+
+    fn byte(&self, offset: usize) -> Result<u8, Error> {
+        self.data
+            .get(offset)
+            .copied()
+            .ok_or(Error::Invalid("unexpected end of data"))
+    }
+
+    fn bytes(&self, offset: usize, len: usize) -> Result<&[u8], Error> {
+        offset
+            .checked_add(len)
+            .and_then(|end| self.data.get(offset..end))
+            .ok_or(Error::Invalid("unexpected end of data"))
+    }
+}
+
+/// Decode a big-endian unsigned integer of up to 16 bytes
+fn uint(bytes: &[u8]) -> u128 {
+    bytes
+        .iter()
+        .take(16)
+        .fold(0, |acc, b| acc << 8 | u128::from(*b))
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("invalid MaxMind database: {0}")]
+    Invalid(&'static str),
+}
+
+const METADATA_MARKER: &[u8] = b"\xab\xcd\xefMaxMind.com";
+const MAX_DEPTH: usize = 32;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        let db = Database::from_bytes(database()).unwrap();
+        assert_eq!(
+            db.locate("81.2.69.160".parse().unwrap()),
+            Some(GeoIp {
+                country: Some("GB".into()),
+                region: Some("ENG".into()),
+            })
+        );
+        assert_eq!(db.locate("81.2.70.1".parse().unwrap()), None);
+        assert_eq!(db.locate("8.8.8.8".parse().unwrap()), None);
+        assert_eq!(db.locate("2001:db8::1".parse().unwrap()), None);
+
+        assert!(Database::from_bytes(b"not a database".to_vec()).is_err());
+    }
+
+    #[test]
+    fn ipv4_in_ipv6() {
+        let gb = Some(GeoIp {
+            country: Some("GB".into()),
+            region: Some("ENG".into()),
+        });
+
+        // IPv4-mapped addresses in an IPv4 database
+        let db = Database::from_bytes(database()).unwrap();
+        assert_eq!(db.locate("::ffff:81.2.69.160".parse().unwrap()), gb);
+        assert_eq!(db.locate("::ffff:81.2.70.1".parse().unwrap()), None);
+
+        // IPv4 addresses live in `::/96` in an IPv6 database
+        let prefix = u128::from(u32::from_be_bytes([81, 2, 69, 0]));
+        let db = Database::from_bytes(tree(prefix, 120, 6)).unwrap();
+        assert_eq!(db.locate("81.2.69.160".parse().unwrap()), gb);
+        assert_eq!(db.locate("::81.2.69.160".parse().unwrap()), gb);
+        assert_eq!(db.locate("::ffff:81.2.69.160".parse().unwrap()), gb);
+        assert_eq!(db.locate("81.2.70.1".parse().unwrap()), None);
+        assert_eq!(db.locate("2001:db8::1".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn missing_records() {
+        let mut data = database();
+        let db = Database::from_bytes(data.clone()).unwrap();
+        assert_eq!(db.lookup("81.2.70.1".parse().unwrap()), None);
+
+        // A record without a country or subdivisions still locates the address
+        let start = db.data_start();
+        data[start] = 7 << 5;
+        let db = Database::from_bytes(data.clone()).unwrap();
+        assert_eq!(
+            db.locate("81.2.69.160".parse().unwrap()),
+            Some(GeoIp::default())
+        );
+
+        // A record that fails to decode is treated as missing
+        data[start..start + 2].copy_from_slice(&[0, 12 - 7]);
+        let db = Database::from_bytes(data.clone()).unwrap();
+        assert_eq!(db.lookup("81.2.69.160".parse().unwrap()), None);
+
+        // As is a record pointing past the end of the data section
+        let past_end = 24u32 + 16 + (1 << 16);
+        data[23 * 6 + 3..24 * 6].copy_from_slice(&past_end.to_be_bytes()[1..]);
+        let db = Database::from_bytes(data).unwrap();
+        assert_eq!(db.lookup("81.2.69.160".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn types() {
+        let decode = |data: &[u8]| {
+            let decoder = Decoder { data, base: 0 };
+            decoder.decode(0, 0).map(|(value, _)| value)
+        };
+
+        let mut double = vec![3 << 5 | 8];
+        double.extend_from_slice(&2.5f64.to_bits().to_be_bytes());
+        assert_eq!(decode(&double).unwrap(), Value::Double(2.5));
+        assert_eq!(
+            decode(&[4 << 5 | 2, 1, 2]).unwrap(),
+            Value::Bytes(vec![1, 2])
+        );
+        assert_eq!(decode(&[5 << 5 | 2, 1, 0]).unwrap(), Value::Uint(256));
+        assert_eq!(decode(&[6 << 5]).unwrap(), Value::Uint(0));
+
+        // Extended types
+        assert_eq!(
+            decode(&[4, 8 - 7, 0xff, 0xff, 0xff, 0xfe]).unwrap(),
+            Value::Int(-2)
+        );
+        assert_eq!(
+            decode(&[8, 9 - 7, 0, 0, 0, 1, 0, 0, 0, 0]).unwrap(),
+            Value::Uint(1 << 32)
+        );
+        let mut uint128 = vec![16, 10 - 7];
+        uint128.extend_from_slice(&(1u128 << 120).to_be_bytes());
+        assert_eq!(decode(&uint128).unwrap(), Value::Uint(1 << 120));
+        assert_eq!(
+            decode(&[2, 11 - 7, 5 << 5 | 1, 7, 2 << 5 | 1, b'x']).unwrap(),
+            Value::Array(vec![Value::Uint(7), Value::String("x".into())])
+        );
+        assert_eq!(decode(&[1, 14 - 7]).unwrap(), Value::Bool(true));
+        assert_eq!(decode(&[0, 14 - 7]).unwrap(), Value::Bool(false));
+        let mut float = vec![4, 15 - 7];
+        float.extend_from_slice(&1.5f32.to_bits().to_be_bytes());
+        assert_eq!(decode(&float).unwrap(), Value::Float(1.5));
+        assert!(decode(&[0, 12 - 7]).is_err());
+        assert!(decode(&[0, 13 - 7]).is_err());
+
+        // Sizes of 29 and up take 1 to 3 extra bytes
+        for (ctrl, extra, len) in [
+            (29, &[1][..], 30),
+            (30, &[0, 1][..], 286),
+            (31, &[0, 0, 1][..], 65_822),
+        ] {
+            let mut data = vec![2 << 5 | ctrl];
+            data.extend_from_slice(extra);
+            data.resize(data.len() + len, b'a');
+            assert_eq!(decode(&data).unwrap(), Value::String("a".repeat(len)));
+            data.pop();
+            assert!(decode(&data).is_err());
+        }
+
+        // Pointers of 1 to 4 bytes
+        for (pointer, target) in [
+            (&[1 << 5 | 1, 5][..], 0x105),
+            (&[1 << 5 | 1 << 3, 0, 5][..], 2048 + 5),
+            (&[1 << 5 | 2 << 3, 0, 0, 5][..], 526_336 + 5),
+            (&[1 << 5 | 3 << 3, 0, 0, 1, 5][..], 0x105),
+        ] {
+            let mut data = pointer.to_vec();
+            data.resize(target, 0);
+            data.extend_from_slice(&[2 << 5 | 1, b'x']);
+            assert_eq!(decode(&data).unwrap(), Value::String("x".into()));
+            data.truncate(target);
+            assert!(decode(&data).is_err());
+        }
+
+        // Pointer loops and deep nesting are rejected
+        assert!(decode(&[1 << 5, 0]).is_err());
+        assert!(decode(&[1, 11 - 7].repeat(MAX_DEPTH + 2)).is_err());
+
+        // Malformed values
+        assert!(decode(&[7 << 5 | 1, 5 << 5 | 1, 1, 5 << 5]).is_err());
+        assert!(decode(&[2 << 5 | 1, 0xff]).is_err());
+        assert!(decode(&[2 << 5 | 2, b'x']).is_err());
+        assert!(decode(&[]).is_err());
+    }
+
+    #[test]
+    fn corrupt_metadata() {
+        let data = database();
+        for len in 0..data.len() {
+            assert!(Database::from_bytes(data[..len].to_vec()).is_err());
+        }
+
+        let fields = [("node_count", 24), ("record_size", 24), ("ip_version", 4)];
+        let tree_len = 24 * 6 + 16;
+        for (i, invalid) in [(0, 200), (1, 20), (2, 5)] {
+            let mut fields = fields;
+            fields[i].1 = invalid;
+            let mut data = data[..tree_len].to_vec();
+            metadata(&mut data, &fields);
+            assert!(Database::from_bytes(data).is_err());
+        }
+
+        let mut data = data[..tree_len].to_vec();
+        metadata(&mut data, &fields[..2]);
+        assert!(Database::from_bytes(data).is_err());
+
+        // The metadata must be a map with integer fields
+        let mut data = database()[..tree_len].to_vec();
+        data.extend_from_slice(METADATA_MARKER);
+        data.extend_from_slice(&[11 << 5 | 1, 5 << 5 | 1, 24]);
+        assert!(Database::from_bytes(data).is_err());
+
+        let mut data = database()[..tree_len].to_vec();
+        data.extend_from_slice(METADATA_MARKER);
+        data.push(7 << 5 | 1);
+        string(&mut data, "node_count");
+        string(&mut data, "24");
+        assert!(Database::from_bytes(data).is_err());
+    }
+
+    /// Build an IPv4 database with a single record for 81.2.69.0/24
+    fn database() -> Vec<u8> {
+        tree(u128::from(u32::from_be_bytes([81, 2, 69, 0])) << 96, 24, 4)
+    }
+
+    /// Build a database with a single record for the `len`-bit `prefix`
+    fn tree(prefix: u128, len: u8, ip_version: u8) -> Vec<u8> {
+        let node_count = u32::from(len);
+        let mut data = Vec::new();
+        for i in 0..node_count {
+            let bit = (prefix >> (127 - i)) & 1;
+            let next = match i == node_count - 1 {
+                true => node_count + 16,
+                false => i + 1,
+            };
+            let (left, right) = match bit {
+                0 => (next, node_count),
+                _ => (node_count, next),
+            };
+            data.extend_from_slice(&left.to_be_bytes()[1..]);
+            data.extend_from_slice(&right.to_be_bytes()[1..]);
+        }
+        data.extend_from_slice(&[0; 16]);
+
+        // {"country": {"iso_code": "GB"}, "subdivisions": [{"iso_code": "ENG"}]}
+        data.push(7 << 5 | 2);
+        string(&mut data, "country");
+        data.push(7 << 5 | 1);
+        let iso_code = data.len() - (node_count as usize * 6 + 16);
+        string(&mut data, "iso_code");
+        string(&mut data, "GB");
+        string(&mut data, "subdivisions");
+        data.extend_from_slice(&[1, 11 - 7]);
+        data.push(7 << 5 | 1);
+        data.extend_from_slice(&[1 << 5, iso_code as u8]);
+        string(&mut data, "ENG");
+
+        metadata(
+            &mut data,
+            &[
+                ("node_count", len),
+                ("record_size", 24),
+                ("ip_version", ip_version),
+            ],
+        );
+        data
+    }
+
+    fn metadata(data: &mut Vec<u8>, fields: &[(&str, u8)]) {
+        data.extend_from_slice(METADATA_MARKER);
+        data.push(7 << 5 | fields.len() as u8);
+        for (name, value) in fields {
+            string(data, name);
+            data.extend_from_slice(&[6 << 5 | 1, *value]);
+        }
+    }
+
+    fn string(data: &mut Vec<u8>, s: &str) {
+        data.push(2 << 5 | s.len() as u8);
+        data.extend_from_slice(s.as_bytes());
+    }
+}
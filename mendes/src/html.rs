@@ -0,0 +1,304 @@
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
+#[cfg(feature = "application")]
+use http::header::CONTENT_TYPE;
+#[cfg(feature = "application")]
+use http::request::Parts;
+#[cfg(feature = "application")]
+use http::{Response, StatusCode};
+
+#[cfg(feature = "application")]
+use crate::application::{Application, IntoResponse};
+
+/// Escape text for use in HTML content and (quoted) attribute values
+///
+/// Returns the input unchanged if it contains no characters that need escaping.
+pub fn escape(s: &str) -> Cow<'_, str> {
+    match s.contains(['&', '<', '>', '"', '\'']) {
+        true => Cow::Owned(Escaped(s).to_string()),
+        false => Cow::Borrowed(s),
+    }
+}
+
+/// Formats the wrapped value with HTML escaping applied
+///
+/// Use this to interpolate untrusted values into HTML with `write!()` or `format!()`.
+#[derive(Clone, Copy, Debug)]
+pub struct Escaped<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for Escaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(EscapeWriter(f), "{}", self.0)
+    }
+}
+
+/// Append `s` to `out` with HTML escaping applied
+///
+/// The escaped output is also valid XML text or attribute content.
+#[cfg(any(feature = "feeds", feature = "sitemap"))]
+pub(crate) fn escape_into(out: &mut String, s: &str) {
+    EscapeWriter(out).write_str(s).unwrap();
+}
+
+struct EscapeWriter<W>(W);
+
+impl<W: Write> Write for EscapeWriter<W> {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        let mut last = 0;
+        for (i, byte) in s.bytes().enumerate() {
+            let entity = match byte {
+                b'&' => "&amp;",
+                b'<' => "&lt;",
+                b'>' => "&gt;",
+                b'"' => "&quot;",
+                b'\'' => "&#39;",
+                _ => continue,
+            };
+            self.0.write_str(&s[last..i])?;
+            self.0.write_str(entity)?;
+            last = i + 1;
+        }
+        self.0.write_str(&s[last..])
+    }
+}
+
+/// Marks its contents as HTML that is safe to embed without escaping
+///
+/// Only wrap content that was generated by trusted code, like the output of a template engine,
+/// `Form` rendering or `sanitize()`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PreEscaped<T>(pub T);
+
+impl<T: fmt::Display> fmt::Display for PreEscaped<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt(f)
+    }
+}
+
+#[cfg(feature = "application")]
+impl<A: Application, T: Into<String>> IntoResponse<A> for PreEscaped<T>
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        html_response(self.0.into())
+    }
+}
+
+/// A buffer of HTML that escapes all text unless it's explicitly marked as `PreEscaped`
+///
+/// ```
+/// use mendes::html::{Markup, PreEscaped};
+///
+/// let mut html = Markup::new();
+/// html.push(PreEscaped("<h1>"));
+/// html.push("Tom & Jerry");
+/// html.push(PreEscaped("</h1>"));
+/// assert_eq!(html.as_str(), "<h1>Tom &amp; Jerry</h1>");
+/// ```
+///
+/// When returned from a handler, a `Markup` is sent as `text/html`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Markup(String);
+
+impl Markup {
+    /// Create an empty buffer
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Append content, escaping it unless it is `PreEscaped`
+    pub fn push(&mut self, content: impl Render) -> &mut Self {
+        content.render(self);
+        self
+    }
+
+    /// Append escaped text formatted with `Display`
+    pub fn push_display(&mut self, value: impl fmt::Display) -> &mut Self {
+        write!(EscapeWriter(&mut self.0), "{value}").unwrap();
+        self
+    }
+
+    /// The HTML in this buffer
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// Take the HTML from this buffer
+    pub fn into_string(self) -> String {
+        self.0
+    }
+}
+
+impl fmt::Display for Markup {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl From<Markup> for String {
+    fn from(markup: Markup) -> Self {
+        markup.0
+    }
+}
+
+#[cfg(feature = "application")]
+impl<A: Application> IntoResponse<A> for Markup
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        html_response(self.0)
+    }
+}
+
+/// Content that can be appended to a `Markup` buffer
+///
+/// Text types are escaped, while `PreEscaped` and `Markup` contents are appended as-is.
+pub trait Render {
+    fn render(self, out: &mut Markup);
+}
+
+impl Render for &str {
+    fn render(self, out: &mut Markup) {
+        out.push_display(self);
+    }
+}
+
+impl Render for &String {
+    fn render(self, out: &mut Markup) {
+        out.push_display(self);
+    }
+}
+
+impl Render for String {
+    fn render(self, out: &mut Markup) {
+        out.push_display(self);
+    }
+}
+
+impl Render for Cow<'_, str> {
+    fn render(self, out: &mut Markup) {
+        out.push_display(self);
+    }
+}
+
+impl<T: fmt::Display> Render for PreEscaped<T> {
+    fn render(self, out: &mut Markup) {
+        write!(out.0, "{}", self.0).unwrap();
+    }
+}
+
+impl Render for &Markup {
+    fn render(self, out: &mut Markup) {
+        out.0.push_str(&self.0);
+    }
+}
+
+impl Render for Markup {
+    fn render(self, out: &mut Markup) {
+        out.0.push_str(&self.0);
+    }
+}
+
+/// Sanitize untrusted HTML, keeping only the given tags
+///
+/// Allowed tags are kept without any of their attributes (so `<a>` tags won't retain their
+/// `href`). Everything else, including other tags, comments and entities, is escaped as text,
+/// so the output can't contain active content.
+///
+/// ```
+/// use mendes::html::sanitize;
+///
+/// let html = sanitize(r#"<b onclick="steal()">hi</b><script>x()</script>"#, &["b"]);
+/// assert_eq!(html.as_str(), "<b>hi</b>&lt;script&gt;x()&lt;/script&gt;");
+/// ```
+pub fn sanitize(input: &str, allowed: &[&str]) -> Markup {
+    let mut out = Markup(String::with_capacity(input.len()));
+    let mut rest = input;
+    while let Some(start) = rest.find('<') {
+        out.push(&rest[..start]);
+        rest = &rest[start..];
+
+        let tag = rest.find('>').and_then(|end| {
+            let inner = &rest[1..end];
+            let (closing, inner) = match inner.strip_prefix('/') {
+                Some(inner) => (true, inner),
+                None => (false, inner),
+            };
+
+            let name_end = inner
+                .find(|c: char| c.is_ascii_whitespace() || c == '/')
+                .unwrap_or(inner.len());
+            let name = &inner[..name_end];
+            let allowed = !name.is_empty()
+                && name.bytes().all(|b| b.is_ascii_alphanumeric())
+                && allowed.iter().any(|tag| tag.eq_ignore_ascii_case(name));
+            match allowed {
+                true => Some((end, closing, name.to_ascii_lowercase())),
+                false => None,
+            }
+        });
+
+        match tag {
+            Some((end, closing, name)) => {
+                out.0.push('<');
+                if closing {
+                    out.0.push('/');
+                }
+                out.0.push_str(&name);
+                out.0.push('>');
+                rest = &rest[end + 1..];
+            }
+            None => {
+                out.0.push_str("&lt;");
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push(rest);
+    out
+}
+
+#[cfg(feature = "application")]
+fn html_response<B: From<String>>(body: String) -> Response<B> {
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "text/html; charset=utf-8")
+        .body(body.into())
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert!(matches!(escape("plain"), Cow::Borrowed("plain")));
+        assert_eq!(
+            escape(r#"<a href="x">O'Neil & co</a>"#),
+            "&lt;a href=&quot;x&quot;&gt;O&#39;Neil &amp; co&lt;/a&gt;"
+        );
+        assert_eq!(format!("{}", Escaped(1 < 2)), "true");
+        assert_eq!(format!("<p>{}</p>", Escaped("<br>")), "<p>&lt;br&gt;</p>");
+    }
+
+    #[test]
+    fn sanitizing() {
+        assert_eq!(
+            sanitize("<p>a <EM>b</em><br/> &amp; c</p>", &["p", "em", "br"]).as_str(),
+            "<p>a <em>b</em><br> &amp;amp; c</p>"
+        );
+        assert_eq!(
+            sanitize("<img src=x onerror=alert(1)> <<b>>", &["b"]).as_str(),
+            "&lt;img src=x onerror=alert(1)&gt; &lt;<b>&gt;"
+        );
+        assert_eq!(
+            sanitize("unterminated <b", &["b"]).as_str(),
+            "unterminated &lt;b"
+        );
+    }
+}
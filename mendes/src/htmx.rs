@@ -0,0 +1,300 @@
+use std::sync::Arc;
+
+use http::header::HeaderName;
+use http::request::Parts;
+use http::{HeaderValue, Response};
+
+use crate::application::{Application, FromContext, IntoResponse, PathState};
+use crate::cache_control::vary;
+
+/// Request details sent by htmx
+///
+/// Use this to render a fragment for htmx requests and the full page otherwise:
+///
+/// ```no_run
+/// # use mendes::htmx::{Htmx, HxResponse};
+/// # use mendes::http::Response;
+/// # use mendes::{handler, Body, Error};
+/// # type Markup = Response<Body>;
+/// # struct Contact;
+/// # struct Db;
+/// # impl Db {
+/// #     async fn contacts(&self) -> Result<Vec<Contact>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # fn render_contacts(contacts: &[Contact]) -> Markup {
+/// #     todo!()
+/// # }
+/// # fn layout(title: &str, content: Markup) -> Markup {
+/// #     todo!()
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn contacts(app: &App, htmx: Htmx) -> Result<HxResponse<Markup>, Error> {
+///     let list = render_contacts(&app.db.contacts().await?);
+///     Ok(HxResponse::new(htmx.render(list, |list| layout("Contacts", list))))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Responses that depend on the request type must list `HX-Request` in their `Vary` header,
+/// which `HxResponse` takes care of.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Htmx {
+    /// Whether the request was made by htmx
+    pub request: bool,
+    /// Whether the request was made by an element using `hx-boost`
+    pub boosted: bool,
+    /// Whether the request restores history after a cache miss
+    pub history_restore: bool,
+    /// The current URL of the browser
+    pub current_url: Option<String>,
+    /// The `id` of the target element
+    pub target: Option<String>,
+    /// The `id` of the triggering element
+    pub trigger: Option<String>,
+    /// The `name` of the triggering element
+    pub trigger_name: Option<String>,
+    /// The user's response to an `hx-prompt`
+    pub prompt: Option<String>,
+}
+
+impl Htmx {
+    /// Get the htmx request headers from `req`
+    pub fn from_request(req: &Parts) -> Self {
+        let header = |name: &str| {
+            req.headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_owned)
+        };
+        let flag = |name: &str| req.headers.get(name).is_some_and(|v| v == "true");
+
+        Self {
+            request: flag("hx-request"),
+            boosted: flag("hx-boosted"),
+            history_restore: flag("hx-history-restore-request"),
+            current_url: header("hx-current-url"),
+            target: header("hx-target"),
+            trigger: header("hx-trigger"),
+            trigger_name: header("hx-trigger-name"),
+            prompt: header("hx-prompt"),
+        }
+    }
+
+    /// Whether to respond with a fragment rather than a full page
+    ///
+    /// Boosted requests and history restoration replace the whole page, so they need the full
+    /// page even though they are made by htmx.
+    pub fn is_partial(&self) -> bool {
+        self.request && !self.boosted && !self.history_restore
+    }
+
+    /// Return `fragment` for partial requests, or the full page built from it by `page`
+    pub fn render<T>(&self, fragment: T, page: impl FnOnce(T) -> T) -> T {
+        match self.is_partial() {
+            true => fragment,
+            false => page(fragment),
+        }
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for Htmx {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(Self::from_request(req))
+    }
+}
+
+/// A response with htmx response headers
+///
+/// The response also gets `HX-Request` added to its `Vary` header.
+pub struct HxResponse<T> {
+    inner: T,
+    headers: Vec<(HeaderName, String)>,
+    triggers: Vec<(String, Option<String>)>,
+}
+
+impl<T> HxResponse<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            headers: Vec::new(),
+            triggers: Vec::new(),
+        }
+    }
+
+    /// Make the browser navigate to `url` with a full page load
+    pub fn redirect(self, url: impl Into<String>) -> Self {
+        self.header("hx-redirect", url.into())
+    }
+
+    /// Navigate to `url` without a full page load, like following an `hx-boost` link
+    pub fn location(self, url: impl Into<String>) -> Self {
+        self.header("hx-location", url.into())
+    }
+
+    /// Make the browser reload the page
+    pub fn refresh(self) -> Self {
+        self.header("hx-refresh", "true".to_owned())
+    }
+
+    /// Push `url` onto the browser history
+    pub fn push_url(self, url: impl Into<String>) -> Self {
+        self.header("hx-push-url", url.into())
+    }
+
+    /// Replace the current URL in the browser history
+    pub fn replace_url(self, url: impl Into<String>) -> Self {
+        self.header("hx-replace-url", url.into())
+    }
+
+    /// Swap the response into the element matching `selector` instead of the request's target
+    pub fn retarget(self, selector: impl Into<String>) -> Self {
+        self.header("hx-retarget", selector.into())
+    }
+
+    /// Override the swap strategy, like `"outerHTML"`
+    pub fn reswap(self, swap: impl Into<String>) -> Self {
+        self.header("hx-reswap", swap.into())
+    }
+
+    /// Trigger the client-side `event` once the response is received
+    pub fn trigger(mut self, event: impl Into<String>) -> Self {
+        self.triggers.push((event.into(), None));
+        self
+    }
+
+    /// Trigger the client-side `event` with `detail` once the response is received
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn trigger_detail(
+        mut self,
+        event: impl Into<String>,
+        detail: impl serde::Serialize,
+    ) -> Result<Self, serde_json::Error> {
+        let detail = serde_json::to_string(&detail)?;
+        self.triggers.push((event.into(), Some(detail)));
+        Ok(self)
+    }
+
+    /// Trigger the client-side `event` after the response has been swapped in
+    pub fn trigger_after_swap(self, event: impl Into<String>) -> Self {
+        self.header("hx-trigger-after-swap", event.into())
+    }
+
+    /// Trigger the client-side `event` after the response has settled
+    pub fn trigger_after_settle(self, event: impl Into<String>) -> Self {
+        self.header("hx-trigger-after-settle", event.into())
+    }
+
+    fn header(mut self, name: &'static str, value: String) -> Self {
+        self.headers.push((HeaderName::from_static(name), value));
+        self
+    }
+
+    /// The `HX-Trigger` header value: a list of names, or a JSON object if there are details
+    fn trigger_header(&self) -> Option<String> {
+        if self.triggers.is_empty() {
+            return None;
+        }
+
+        if self.triggers.iter().all(|(_, detail)| detail.is_none()) {
+            let names = self.triggers.iter().map(|(name, _)| name.as_str());
+            return Some(names.collect::<Vec<_>>().join(", "));
+        }
+
+        #[cfg(feature = "json")]
+        {
+            let events = self.triggers.iter().map(|(name, detail)| {
+                let name = serde_json::Value::from(name.as_str()).to_string();
+                format!("{name}:{}", detail.as_deref().unwrap_or("null"))
+            });
+            Some(format!("{{{}}}", events.collect::<Vec<_>>().join(",")))
+        }
+        #[cfg(not(feature = "json"))]
+        unreachable!()
+    }
+}
+
+impl<A: Application, T: IntoResponse<A>> IntoResponse<A> for HxResponse<T> {
+    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        let trigger = self.trigger_header();
+        let mut rsp = self.inner.into_response(app, req);
+        let triggers = trigger.map(|trigger| (HeaderName::from_static("hx-trigger"), trigger));
+        for (name, value) in self.headers.into_iter().chain(triggers) {
+            // Values that are not valid in a header can't have come from a valid URL or event
+            if let Ok(value) = HeaderValue::try_from(value) {
+                rsp.headers_mut().append(name, value);
+            }
+        }
+
+        vary(&mut rsp, HeaderName::from_static("hx-request"));
+        rsp
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::*;
+
+    #[test]
+    fn request() {
+        let (req, _) = Request::get("/contacts")
+            .header("HX-Request", "true")
+            .header("HX-Target", "list")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let htmx = Htmx::from_request(&req);
+        assert!(htmx.is_partial());
+        assert_eq!(htmx.target.as_deref(), Some("list"));
+        assert_eq!(htmx.render("list", |_| "page"), "list");
+
+        let (req, _) = Request::get("/contacts")
+            .header("HX-Request", "true")
+            .header("HX-Boosted", "true")
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert_eq!(Htmx::from_request(&req).render("list", |_| "page"), "page");
+
+        let rsp = HxResponse::new(())
+            .push_url("/contacts?page=2")
+            .trigger("saved")
+            .trigger("closed");
+        assert_eq!(rsp.trigger_header().as_deref(), Some("saved, closed"));
+        assert_eq!(rsp.headers[0].0, "hx-push-url");
+    }
+
+    #[cfg(feature = "json")]
+    #[test]
+    fn trigger_detail() {
+        let rsp = HxResponse::new(())
+            .trigger("saved")
+            .trigger_detail("notify", serde_json::json!({"level": "info"}))
+            .unwrap();
+        assert_eq!(
+            rsp.trigger_header().as_deref(),
+            Some(r#"{"saved":null,"notify":{"level":"info"}}"#)
+        );
+    }
+}
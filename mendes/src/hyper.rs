@@ -6,11 +6,20 @@ use std::net::SocketAddr;
 use std::panic::AssertUnwindSafe;
 use std::pin::{pin, Pin};
 use std::sync::Arc;
+use std::task::{ready, Poll};
 use std::time::Duration;
 
-use futures_util::future::{CatchUnwind, FutureExt, Map};
+#[cfg(feature = "grpc")]
+use std::future::poll_fn;
+
+use futures_util::future::{self, CatchUnwind, FutureExt, Ready};
+#[cfg(feature = "grpc")]
+use http::header::CONTENT_TYPE;
+use http::header::EXPECT;
 use http::request::Parts;
-use http::{Request, Response, StatusCode};
+use http::{Method, Request, Response, StatusCode, Uri};
+#[cfg(feature = "grpc")]
+use http_body_util::Either;
 use hyper::body::{Body, Incoming};
 use hyper::service::Service;
 use hyper_util::rt::{TokioExecutor, TokioIo};
@@ -18,17 +27,22 @@ use hyper_util::server::conn::auto::Builder;
 use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::watch;
 use tokio::time::sleep;
+#[cfg(feature = "trace-context")]
+use tracing::Instrument;
 use tracing::{debug, error, info};
 
 use super::Application;
 use crate::application::{Context, FromContext, PathState};
+#[cfg(feature = "trace-context")]
+use crate::trace_context::TraceContext;
 
 pub use hyper::body;
 
-pub struct Server<A, F> {
+pub struct Server<A, F, G = ()> {
     listener: TcpListener,
     app: Arc<A>,
     signal: Option<F>,
+    grpc: G,
 }
 
 impl<A: Application> Server<A, Pending<()>> {
@@ -41,28 +55,62 @@ impl<A: Application> Server<A, Pending<()>> {
             listener,
             app: Arc::new(app),
             signal: None,
+            grpc: (),
         }
     }
 }
 
-impl<A: Application> Server<A, Pending<()>> {
-    pub fn with_graceful_shutdown<F: Future<Output = ()>>(self, signal: F) -> Server<A, F> {
-        let Server { listener, app, .. } = self;
+impl<A: Application, G> Server<A, Pending<()>, G> {
+    pub fn with_graceful_shutdown<F: Future<Output = ()>>(self, signal: F) -> Server<A, F, G> {
+        let Server {
+            listener,
+            app,
+            grpc,
+            ..
+        } = self;
         Server {
             listener,
             app,
             signal: Some(signal),
+            grpc,
         }
     }
 }
 
-impl<A, F> Server<A, F>
+#[cfg(feature = "grpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "grpc")))]
+impl<A: Application, F> Server<A, F> {
+    /// Serve the given gRPC service on the same listener as the `Application`
+    ///
+    /// Requests with a `content-type` of `application/grpc` (or one of its subtypes, like
+    /// `application/grpc+proto`) are dispatched to `service` instead of the `Application`.
+    /// Any `tower::Service` will do, including a tonic `Routes` or generated server type.
+    pub fn with_grpc<S>(self, service: S) -> Server<A, F, Grpc<S>> {
+        let Server {
+            listener,
+            app,
+            signal,
+            ..
+        } = self;
+        Server {
+            listener,
+            app,
+            signal,
+            grpc: Grpc(service),
+        }
+    }
+}
+
+impl<A, F, G, B> Server<A, F, G>
 where
     A: Application + Sync + 'static,
-    A::RequestBody: From<Incoming>,
-    <<A as Application>::ResponseBody as Body>::Data: Send,
-    <<A as Application>::ResponseBody as Body>::Error: StdError + Send + Sync,
-    <A as Application>::ResponseBody: From<&'static str> + Send,
+    G: Clone + Send + 'static,
+    ConnectionService<A, G>:
+        Service<Request<Incoming>, Response = Response<B>, Error = Infallible> + Send + 'static,
+    <ConnectionService<A, G> as Service<Request<Incoming>>>::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
     F: Future<Output = ()> + Send + 'static,
 {
     pub async fn serve(self) -> Result<(), io::Error> {
@@ -70,6 +118,7 @@ where
             listener,
             app,
             signal,
+            grpc,
         } = self;
 
         let (listener_state, conn_state) = states(signal);
@@ -108,7 +157,11 @@ where
                     stream,
                     addr,
                     state: conn_state.clone(),
-                    app: app.clone(),
+                    service: ConnectionService {
+                        addr,
+                        app: app.clone(),
+                        grpc: grpc.clone(),
+                    },
                 }
                 .run(),
             );
@@ -168,30 +221,29 @@ struct ListenerState {
     task_monitor: Option<watch::Sender<()>>,
 }
 
-struct Connection<A> {
+struct Connection<S> {
     stream: TcpStream,
     addr: SocketAddr,
     state: ConnectionState,
-    app: Arc<A>,
+    service: S,
 }
 
-impl<A: Application + 'static> Connection<A>
+impl<S, B> Connection<S>
 where
-    A::RequestBody: From<Incoming>,
-    A::ResponseBody: From<&'static str> + Send,
-    <A::ResponseBody as Body>::Data: Send,
-    <A::ResponseBody as Body>::Error: StdError + Send + Sync,
+    S: Service<Request<Incoming>, Response = Response<B>, Error = Infallible> + 'static,
+    S::Future: Send + 'static,
+    B: Body + Send + 'static,
+    B::Data: Send,
+    B::Error: Into<Box<dyn StdError + Send + Sync>>,
 {
     async fn run(self) {
         let Connection {
             stream,
             addr,
             state,
-            app,
+            service,
         } = self;
 
-        let service = ConnectionService { addr, app };
-
         let builder = Builder::new(TokioExecutor::new());
         let stream = TokioIo::new(stream);
         let mut conn = pin!(builder.serve_connection_with_upgrades(stream, service));
@@ -230,9 +282,11 @@ struct ConnectionState {
     _task_done: Option<watch::Receiver<()>>,
 }
 
-pub struct ConnectionService<A> {
+pub struct ConnectionService<A, G = ()> {
     addr: SocketAddr,
     app: Arc<A>,
+    #[cfg_attr(not(feature = "grpc"), allow(dead_code))]
+    grpc: G,
 }
 
 impl<A: Application + 'static> Service<Request<Incoming>> for ConnectionService<A>
@@ -242,48 +296,226 @@ where
 {
     type Response = Response<A::ResponseBody>;
     type Error = Infallible;
-    type Future = UnwindSafeHandlerFuture<Self::Response, Self::Error>;
+    type Future = HandlerFuture<A::ResponseBody>;
 
     fn call(&self, mut req: Request<Incoming>) -> Self::Future {
+        if !expectation_supported(&req) {
+            return HandlerFuture::ready(expectation_failed());
+        }
+
         req.extensions_mut().insert(ClientAddr(self.addr));
-        let cx = Context::new(self.app.clone(), req.map(|body| body.into()));
-        AssertUnwindSafe(A::handle(cx))
-            .catch_unwind()
-            .map(panic_response)
+        handle(self.app.clone(), req.map(|body| body.into()))
     }
 }
 
-type UnwindSafeHandlerFuture<T, E> = Map<
-    CatchUnwind<AssertUnwindSafe<Pin<Box<dyn Future<Output = T> + Send>>>>,
-    fn(Result<T, Box<(dyn std::any::Any + std::marker::Send + 'static)>>) -> Result<T, E>,
->;
-
-fn panic_response<B: From<&'static str>>(
-    result: Result<Response<B>, Box<dyn std::any::Any + std::marker::Send + 'static>>,
-) -> Result<Response<B>, Infallible> {
-    #[allow(unused_variables)] // Depends on features
-    let error = match result {
-        Ok(rsp) => return Ok(rsp),
-        Err(e) => e,
+/// Handle the request, turning a panic in the `Application` into a 500 response
+fn handle<A: Application + 'static>(
+    app: Arc<A>,
+    #[allow(unused_mut)] // Depends on features
+    mut req: Request<A::RequestBody>,
+) -> HandlerFuture<A::ResponseBody> {
+    let (method, uri) = (req.method().clone(), req.uri().clone());
+
+    #[cfg(feature = "trace-context")]
+    let future: BoxFuture<_> = {
+        let trace = TraceContext::from_headers(req.headers());
+        let span = trace.span(req.method(), req.uri());
+        req.extensions_mut().insert(trace);
+        let future = A::handle(Context::new(app, req));
+        Box::pin(
+            async move {
+                let rsp = future.await;
+                tracing::Span::current().record("http.response.status_code", rsp.status().as_u16());
+                rsp
+            }
+            .instrument(span),
+        )
     };
 
-    #[cfg(feature = "tracing")]
-    {
-        let panic_str = if let Some(s) = error.downcast_ref::<String>() {
-            Some(s.as_str())
-        } else if let Some(s) = error.downcast_ref::<&'static str>() {
-            Some(*s)
-        } else {
-            Some("no error")
-        };
-
-        tracing::error!("caught panic from request handler: {:?}", panic_str);
+    #[cfg(not(feature = "trace-context"))]
+    let future = A::handle(Context::new(app, req));
+
+    HandlerFuture {
+        inner: HandlerState::Handler {
+            future: AssertUnwindSafe(future).catch_unwind(),
+            method,
+            uri,
+        },
+    }
+}
+
+/// Future for the response to a request handled by the `Application`
+pub struct HandlerFuture<B> {
+    inner: HandlerState<B>,
+}
+
+impl<B> HandlerFuture<B> {
+    fn ready(rsp: Response<B>) -> Self {
+        Self {
+            inner: HandlerState::Ready(future::ready(rsp)),
+        }
+    }
+}
+
+impl<B: From<&'static str>> Future for HandlerFuture<B> {
+    type Output = Result<Response<B>, Infallible>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        Poll::Ready(Ok(match &mut self.inner {
+            HandlerState::Ready(future) => ready!(Pin::new(future).poll(cx)),
+            HandlerState::Handler {
+                future,
+                method,
+                uri,
+            } => match ready!(Pin::new(future).poll(cx)) {
+                Ok(rsp) => rsp,
+                Err(panic) => panic_response(method, uri, panic),
+            },
+        }))
     }
+}
+
+type BoxFuture<T> = Pin<Box<dyn Future<Output = T> + Send>>;
 
-    Ok(Response::builder()
+enum HandlerState<B> {
+    Ready(Ready<Response<B>>),
+    Handler {
+        future: CatchUnwind<AssertUnwindSafe<BoxFuture<Response<B>>>>,
+        method: Method,
+        uri: Uri,
+    },
+}
+
+/// Whether the request has no `Expect` header other than `100-continue`
+///
+/// hyper sends `100 Continue` when the request body is first polled, so the application
+/// decides whether to accept the body: requests rejected before extracting the body get their
+/// final response without the client sending the body first.
+fn expectation_supported(req: &Request<Incoming>) -> bool {
+    match req.headers().get(EXPECT) {
+        Some(expect) => expect.as_bytes().eq_ignore_ascii_case(b"100-continue"),
+        None => true,
+    }
+}
+
+fn expectation_failed<B: From<&'static str>>() -> Response<B> {
+    Response::builder()
+        .status(StatusCode::EXPECTATION_FAILED)
+        .body("Unsupported expectation".into())
+        .unwrap()
+}
+
+fn panic_response<B: From<&'static str>>(
+    method: &Method,
+    uri: &Uri,
+    panic: Box<dyn std::any::Any + std::marker::Send + 'static>,
+) -> Response<B> {
+    let msg = if let Some(s) = panic.downcast_ref::<String>() {
+        s.as_str()
+    } else if let Some(s) = panic.downcast_ref::<&'static str>() {
+        s
+    } else {
+        "<non-string panic payload>"
+    };
+
+    error!(%method, path = uri.path(), panic = msg, "caught panic from request handler");
+    Response::builder()
         .status(StatusCode::INTERNAL_SERVER_ERROR)
         .body("Caught panic".into())
-        .unwrap())
+        .unwrap()
+}
+
+/// A gRPC service served alongside the `Application`, see `Server::with_grpc()`
+#[cfg(feature = "grpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "grpc")))]
+#[derive(Clone)]
+pub struct Grpc<S>(S);
+
+#[cfg(feature = "grpc")]
+impl<A, S, B> Service<Request<Incoming>> for ConnectionService<A, Grpc<S>>
+where
+    A: Application + 'static,
+    A::RequestBody: From<Incoming>,
+    A::ResponseBody: From<&'static str> + Send + 'static,
+    S: tower_service::Service<Request<Incoming>, Response = Response<B>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<Box<dyn StdError + Send + Sync>> + Send,
+    B: Body<Data = <A::ResponseBody as Body>::Data>,
+{
+    type Response = Response<Either<A::ResponseBody, B>>;
+    type Error = Infallible;
+    type Future = GrpcFuture<A::ResponseBody, B>;
+
+    fn call(&self, mut req: Request<Incoming>) -> Self::Future {
+        if !expectation_supported(&req) {
+            return GrpcFuture::Application(HandlerFuture::ready(expectation_failed()));
+        }
+
+        req.extensions_mut().insert(ClientAddr(self.addr));
+        if !is_grpc(&req) {
+            let future = handle(self.app.clone(), req.map(|body| body.into()));
+            return GrpcFuture::Application(future);
+        }
+
+        let mut service = self.grpc.0.clone();
+        GrpcFuture::Grpc(Box::pin(async move {
+            let result = match poll_fn(|cx| service.poll_ready(cx)).await {
+                Ok(()) => service.call(req).await,
+                Err(error) => Err(error),
+            };
+
+            match result {
+                Ok(rsp) => rsp.map(Either::Right),
+                Err(error) => {
+                    let error = error.into();
+                    error!(%error, "gRPC service failed");
+                    // A trailers-only response, with the status in the headers
+                    Response::builder()
+                        .header(CONTENT_TYPE, "application/grpc")
+                        .header("grpc-status", "13") // INTERNAL
+                        .header("grpc-message", "gRPC service failed")
+                        .body(Either::Left("".into()))
+                        .unwrap()
+                }
+            }
+        }))
+    }
+}
+
+/// Future for the response to a request on a server with a gRPC service
+#[cfg(feature = "grpc")]
+#[cfg_attr(docsrs, doc(cfg(feature = "grpc")))]
+pub enum GrpcFuture<A, B> {
+    #[doc(hidden)]
+    Application(HandlerFuture<A>),
+    #[doc(hidden)]
+    Grpc(BoxFuture<Response<Either<A, B>>>),
+}
+
+#[cfg(feature = "grpc")]
+impl<A: From<&'static str>, B> Future for GrpcFuture<A, B> {
+    type Output = Result<Response<Either<A, B>>, Infallible>;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut std::task::Context<'_>) -> Poll<Self::Output> {
+        Poll::Ready(Ok(match &mut *self {
+            Self::Application(future) => match ready!(Pin::new(future).poll(cx)) {
+                Ok(rsp) => rsp.map(Either::Left),
+            },
+            Self::Grpc(future) => ready!(future.as_mut().poll(cx)),
+        }))
+    }
+}
+
+#[cfg(feature = "grpc")]
+fn is_grpc<B>(req: &Request<B>) -> bool {
+    match req.headers().get(CONTENT_TYPE) {
+        Some(value) => {
+            let value = value.as_bytes();
+            value.starts_with(b"application/grpc")
+                && matches!(value.get(16), None | Some(b'+') | Some(b';'))
+        }
+        None => false,
+    }
 }
 
 impl<'a, A: Application<RequestBody = Incoming>> FromContext<'a, A> for Incoming {
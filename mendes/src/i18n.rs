@@ -0,0 +1,312 @@
+#[cfg(feature = "forms")]
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::fmt::{self, Write};
+use std::path::Path;
+use std::sync::Arc;
+use std::{fs, io};
+
+use http::header::ACCEPT_LANGUAGE;
+use http::request::Parts;
+use thiserror::Error;
+
+use crate::application::{Application, FromContext, PathState};
+#[cfg(feature = "forms")]
+use crate::forms::{Field, Form, ItemContents};
+use crate::language;
+
+/// Give mendes-based APIs access to the message catalogs for the `Application`
+///
+/// This enables the `Locale` extractor, which selects the best catalog for the request
+/// based on its `Accept-Language` header.
+pub trait AppWithCatalogs: Application {
+    fn catalogs(&self) -> &Catalogs;
+}
+
+/// The message catalogs for all locales supported by an application
+pub struct Catalogs {
+    default: usize,
+    catalogs: Vec<(String, Catalog)>,
+}
+
+impl Catalogs {
+    /// Create a set of catalogs with the given catalog for the default locale
+    pub fn new(default: impl Into<String>, catalog: Catalog) -> Self {
+        Self {
+            default: 0,
+            catalogs: vec![(default.into(), catalog)],
+        }
+    }
+
+    /// Load catalogs from all `.ftl` files in the given directory
+    ///
+    /// The locale for each catalog is taken from the file name, so `nl-NL.ftl` provides
+    /// messages for the `nl-NL` locale. Fails if no catalog for the `default` locale is found.
+    pub fn load(dir: impl AsRef<Path>, default: &str) -> Result<Self, Error> {
+        let mut catalogs = Vec::new();
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("ftl") {
+                continue;
+            }
+
+            let locale = match path.file_stem().and_then(|s| s.to_str()) {
+                Some(locale) => locale.to_owned(),
+                None => continue,
+            };
+
+            let catalog = Catalog::parse(&fs::read_to_string(&path)?)?;
+            catalogs.push((locale, catalog));
+        }
+
+        catalogs.sort_by(|a, b| a.0.cmp(&b.0));
+        let default = catalogs
+            .iter()
+            .position(|(locale, _)| locale.eq_ignore_ascii_case(default))
+            .ok_or_else(|| Error::DefaultMissing(default.to_owned()))?;
+        Ok(Self { default, catalogs })
+    }
+
+    /// Add a catalog for the given locale
+    pub fn insert(&mut self, locale: impl Into<String>, catalog: Catalog) {
+        let locale = locale.into();
+        match self
+            .catalogs
+            .iter_mut()
+            .find(|(cur, _)| cur.eq_ignore_ascii_case(&locale))
+        {
+            Some((_, cur)) => *cur = catalog,
+            None => self.catalogs.push((locale, catalog)),
+        }
+    }
+
+    /// Select the best supported locale for the given `Accept-Language` header value
+    ///
+    /// Language ranges are tried in order of their quality value. A range matches a
+    /// supported locale if it is equal to it or to one of its prefixes (so `en-US` will
+    /// match a catalog for `en`, and `en` will match one for `en-GB`). Falls back to the
+    /// default locale if nothing matches.
+    pub fn negotiate(&self, accept: &str) -> Locale<'_> {
+        let locales = self.catalogs.iter().map(|(locale, _)| locale.as_str());
+        match language::best_match(language::ranges(accept), locales) {
+            Some(index) => Locale {
+                catalogs: self,
+                index,
+            },
+            None => self.default_locale(),
+        }
+    }
+
+    /// Get the `Locale` for the given locale tag, if a catalog is available for it
+    pub fn get(&self, locale: &str) -> Option<Locale<'_>> {
+        self.catalogs
+            .iter()
+            .position(|(cur, _)| cur.eq_ignore_ascii_case(locale))
+            .map(|index| Locale {
+                catalogs: self,
+                index,
+            })
+    }
+
+    /// Get the `Locale` for the default locale
+    pub fn default_locale(&self) -> Locale<'_> {
+        Locale {
+            catalogs: self,
+            index: self.default,
+        }
+    }
+}
+
+/// The locale selected for a request
+///
+/// Use this as a handler argument to get at the messages for the locale that best matches
+/// the request's `Accept-Language` header. Messages missing from the selected catalog are
+/// taken from the default catalog instead.
+#[derive(Clone, Copy)]
+pub struct Locale<'a> {
+    catalogs: &'a Catalogs,
+    index: usize,
+}
+
+impl<'a> Locale<'a> {
+    /// The tag for this locale (for example, `en-US`)
+    pub fn tag(&self) -> &'a str {
+        &self.catalogs.catalogs[self.index].0
+    }
+
+    /// Get the message with the given `id`
+    ///
+    /// Returns the `id` itself if the message is not found in any catalog.
+    pub fn get<'b>(&self, id: &'b str) -> &'b str
+    where
+        'a: 'b,
+    {
+        self.message(id).unwrap_or(id)
+    }
+
+    /// Get the message with the given `id`, substituting the given arguments
+    ///
+    /// Placeholders in the message look like `{ $name }`. Returns the `id` itself if
+    /// the message is not found in any catalog.
+    pub fn format(&self, id: &str, args: &[(&str, &dyn fmt::Display)]) -> String {
+        let msg = match self.message(id) {
+            Some(msg) => msg,
+            None => return id.to_owned(),
+        };
+
+        let mut out = String::with_capacity(msg.len());
+        let mut rest = msg;
+        while let Some(start) = rest.find('{') {
+            out.push_str(&rest[..start]);
+            let end = match rest[start..].find('}') {
+                Some(end) => start + end,
+                None => break,
+            };
+
+            let name = rest[start + 1..end].trim();
+            match name
+                .strip_prefix('$')
+                .and_then(|name| args.iter().find(|(key, _)| *key == name))
+            {
+                Some((_, value)) => write!(out, "{value}").unwrap(),
+                None => out.push_str(&rest[start..=end]),
+            }
+            rest = &rest[end + 1..];
+        }
+        out.push_str(rest);
+        out
+    }
+
+    /// Replace labels and submit values in the `Form` with localized messages
+    ///
+    /// Each text is used as the message id to look up, and is left alone if no message is found.
+    #[cfg(feature = "forms")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "forms")))]
+    pub fn localize_form(&self, mut form: Form) -> Form {
+        fn localize_items(locale: &Locale<'_>, items: &mut [crate::forms::Item]) {
+            for item in items {
+                if let Some(label) = &mut item.label {
+                    if let Some(msg) = locale.message(label) {
+                        *label = Cow::Owned(msg.to_owned());
+                    }
+                }
+
+                match &mut item.contents {
+                    ItemContents::Single(Field::Submit(submit)) => {
+                        if let Some(value) = &mut submit.value {
+                            if let Some(msg) = locale.message(value) {
+                                *value = Cow::Owned(msg.to_owned());
+                            }
+                        }
+                    }
+                    ItemContents::Single(_) => {}
+                    ItemContents::Multi(items) => localize_items(locale, items),
+                }
+            }
+        }
+
+        for set in &mut form.sets {
+            localize_items(self, &mut set.items);
+        }
+        form
+    }
+
+    fn message(&self, id: &str) -> Option<&'a str> {
+        let catalogs = &self.catalogs.catalogs;
+        catalogs[self.index]
+            .1
+            .get(id)
+            .or_else(|| catalogs[self.catalogs.default].1.get(id))
+    }
+}
+
+impl<'a, A: AppWithCatalogs> FromContext<'a, A> for Locale<'a> {
+    fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let catalogs = app.catalogs();
+        Ok(match req.headers.get(ACCEPT_LANGUAGE).map(|v| v.to_str()) {
+            Some(Ok(accept)) => catalogs.negotiate(accept),
+            _ => catalogs.default_locale(),
+        })
+    }
+}
+
+/// A set of messages for a single locale
+///
+/// Catalogs use a small subset of the Fluent syntax: each message is defined as `id = message`
+/// on its own line, indented lines continue the previous message and lines starting with `#`
+/// are comments. Messages can contain placeholders like `{ $name }`.
+#[derive(Debug, Default)]
+pub struct Catalog {
+    messages: HashMap<String, String>,
+}
+
+impl Catalog {
+    /// Parse a catalog from its source text
+    pub fn parse(source: &str) -> Result<Self, Error> {
+        let mut messages = HashMap::new();
+        let mut current: Option<(String, String)> = None;
+        for (i, line) in source.lines().enumerate() {
+            if line.trim().is_empty() || line.starts_with('#') {
+                continue;
+            }
+
+            if line.starts_with(char::is_whitespace) {
+                match &mut current {
+                    Some((_, value)) => {
+                        if !value.is_empty() {
+                            value.push('\n');
+                        }
+                        value.push_str(line.trim());
+                        continue;
+                    }
+                    None => return Err(Error::Syntax(i + 1)),
+                }
+            }
+
+            let (id, value) = line.split_once('=').ok_or(Error::Syntax(i + 1))?;
+            let id = id.trim();
+            if id.is_empty()
+                || !id
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+            {
+                return Err(Error::Syntax(i + 1));
+            }
+
+            if let Some((id, value)) = current.replace((id.to_owned(), value.trim().to_owned())) {
+                messages.insert(id, value);
+            }
+        }
+
+        if let Some((id, value)) = current {
+            messages.insert(id, value);
+        }
+
+        Ok(Self { messages })
+    }
+
+    /// Add a message to the catalog
+    pub fn insert(&mut self, id: impl Into<String>, message: impl Into<String>) {
+        self.messages.insert(id.into(), message.into());
+    }
+
+    /// Get the message with the given `id`
+    pub fn get(&self, id: &str) -> Option<&str> {
+        self.messages.get(id).map(|s| s.as_str())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no catalog found for default locale {0:?}")]
+    DefaultMissing(String),
+    #[error("unable to read catalog: {0}")]
+    Io(#[from] io::Error),
+    #[error("syntax error in catalog on line {0}")]
+    Syntax(usize),
+}
@@ -0,0 +1,284 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::header::HeaderName;
+use http::{HeaderMap, HeaderValue, Method, Response, StatusCode};
+
+use crate::application::{Application, Context};
+use crate::utils::collect;
+
+/// Replay responses for retried requests carrying an `Idempotency-Key` header
+///
+/// The first `POST` or `PATCH` request with a given key is handled as usual and its response
+/// is kept in the store for the configured TTL. Retries with the same key get the stored
+/// response (marked with an `Idempotent-Replayed: true` header) instead of performing the
+/// operation again. Requests using a key that's still being handled get a `409 Conflict`,
+/// while requests reusing a key for a different method or URI get a
+/// `422 Unprocessable Entity`. Server errors (5xx) are not stored, so the client can retry;
+/// neither are requests whose handler panicked or was cancelled, if the store supports
+/// `IdempotencyStore::abandon()`.
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::idempotency::{Idempotency, MemoryStore};
+/// # use mendes::{route, Application, Body, Context, Error};
+/// # struct App {
+/// #     idempotency: Idempotency<MemoryStore>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(cx: Context<Self>) -> Response<Body> {
+///     let app = cx.app.clone();
+///     app.idempotency
+///         .handle(cx, |mut cx| async move {
+///             route!(match cx.path() {
+///                 Some("payments") => payments::create,
+///             })
+///         })
+///         .await
+/// }
+/// # }
+/// # mod payments {
+/// #     use super::*;
+/// #     #[mendes::handler(POST)]
+/// #     pub async fn create(_: &App) -> Result<Response<Body>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// Keys are not scoped by the store: if clients can't be trusted to pick unique keys, use a
+/// store that includes the client's identity in the key.
+pub struct Idempotency<S> {
+    store: S,
+    ttl: Duration,
+}
+
+impl<S: IdempotencyStore> Idempotency<S> {
+    /// Keep responses in `store` for `ttl`
+    pub fn new(store: S, ttl: Duration) -> Self {
+        Self { store, ttl }
+    }
+
+    /// Handle the request in `cx` with `handler`, or replay the stored response for its key
+    pub async fn handle<A, F, Fut>(&self, cx: Context<A>, handler: F) -> Response<A::ResponseBody>
+    where
+        A: Application,
+        A::ResponseBody: http_body::Body<Data = Bytes> + From<Bytes>,
+        F: FnOnce(Context<A>) -> Fut,
+        Fut: Future<Output = Response<A::ResponseBody>>,
+    {
+        if !matches!(cx.req.method, Method::POST | Method::PATCH) {
+            return handler(cx).await;
+        }
+
+        let key = match cx.req.headers.get(IDEMPOTENCY_KEY) {
+            Some(key) => match key.to_str() {
+                Ok(key) if !key.is_empty() => key.to_owned(),
+                _ => return status_response(StatusCode::BAD_REQUEST),
+            },
+            None => return handler(cx).await,
+        };
+
+        let fingerprint = format!("{} {}", cx.req.method, cx.req.uri);
+        match self.store.begin(&key, &fingerprint, self.ttl).await {
+            Lookup::New => {}
+            Lookup::InProgress => return status_response(StatusCode::CONFLICT),
+            Lookup::Mismatch => return status_response(StatusCode::UNPROCESSABLE_ENTITY),
+            Lookup::Completed(stored) => {
+                let mut rsp = stored.into_response();
+                rsp.headers_mut()
+                    .insert(REPLAYED, HeaderValue::from_static("true"));
+                return rsp;
+            }
+        }
+
+        // Release the key if the handler panics or this future is dropped before completing
+        let mut reservation = Reservation {
+            store: &self.store,
+            key: &key,
+            active: true,
+        };
+
+        let rsp = handler(cx).await;
+        if rsp.status().is_server_error() {
+            reservation.active = false;
+            self.store.release(&key).await;
+            return rsp;
+        }
+
+        let (parts, body) = rsp.into_parts();
+        let body = match collect(body).await {
+            Ok(body) => body,
+            Err(_) => {
+                reservation.active = false;
+                self.store.release(&key).await;
+                return status_response(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        };
+
+        let stored = StoredResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        };
+        reservation.active = false;
+        self.store.complete(&key, stored.clone(), self.ttl).await;
+        stored.into_response()
+    }
+}
+
+/// Storage for idempotency keys and their responses
+///
+/// Implementations must make `begin()` atomic, such that only a single caller gets
+/// `Lookup::New` for a given key until it is released or expires.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync {
+    /// Look up `key`, reserving it for a request with the given `fingerprint` if it's unknown
+    ///
+    /// The reservation should expire after `ttl`, in case the request is never completed.
+    async fn begin(&self, key: &str, fingerprint: &str, ttl: Duration) -> Lookup;
+
+    /// Store the response for a key reserved with `begin()`
+    async fn complete(&self, key: &str, response: StoredResponse, ttl: Duration);
+
+    /// Remove a key reserved with `begin()` without storing a response
+    async fn release(&self, key: &str);
+
+    /// Remove a key reserved with `begin()` whose request was abandoned
+    ///
+    /// This is called without awaiting, when handling the request panicked or its future was
+    /// dropped. The default does nothing: stores that can't remove the key synchronously rely
+    /// on the reservation expiring after its `ttl`.
+    fn abandon(&self, key: &str) {
+        let _ = key;
+    }
+}
+
+/// Abandons the reserved key when dropped while still active
+struct Reservation<'a, S: IdempotencyStore> {
+    store: &'a S,
+    key: &'a str,
+    active: bool,
+}
+
+impl<S: IdempotencyStore> Drop for Reservation<'_, S> {
+    fn drop(&mut self) {
+        if self.active {
+            self.store.abandon(self.key);
+        }
+    }
+}
+
+/// The state of an idempotency key in an `IdempotencyStore`
+#[derive(Debug)]
+pub enum Lookup {
+    /// The key was unknown and is now reserved for the caller
+    New,
+    /// A request with the same key is still being handled
+    InProgress,
+    /// The key was used for a request with a different fingerprint
+    Mismatch,
+    /// The request with this key has completed with the given response
+    Completed(StoredResponse),
+}
+
+/// A response kept in an `IdempotencyStore`
+#[derive(Clone, Debug)]
+pub struct StoredResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl StoredResponse {
+    pub(crate) fn into_response<B: From<Bytes>>(self) -> Response<B> {
+        let mut rsp = Response::new(B::from(self.body));
+        *rsp.status_mut() = self.status;
+        *rsp.headers_mut() = self.headers;
+        rsp
+    }
+}
+
+/// An `IdempotencyStore` keeping keys in memory
+///
+/// Keys are lost when the process exits and aren't shared between instances of the
+/// application; use a store backed by a database in that case.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    entries: Mutex<HashMap<String, Entry>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryStore {
+    async fn begin(&self, key: &str, fingerprint: &str, ttl: Duration) -> Lookup {
+        let now = Instant::now();
+        let mut entries = self.entries.lock().unwrap();
+        entries.retain(|_, entry| entry.expires > now);
+
+        match entries.get(key) {
+            Some(entry) if entry.fingerprint != fingerprint => Lookup::Mismatch,
+            Some(Entry {
+                response: Some(rsp),
+                ..
+            }) => Lookup::Completed(rsp.clone()),
+            Some(_) => Lookup::InProgress,
+            None => {
+                let entry = Entry {
+                    fingerprint: fingerprint.to_owned(),
+                    expires: now + ttl,
+                    response: None,
+                };
+                entries.insert(key.to_owned(), entry);
+                Lookup::New
+            }
+        }
+    }
+
+    async fn complete(&self, key: &str, response: StoredResponse, ttl: Duration) {
+        if let Some(entry) = self.entries.lock().unwrap().get_mut(key) {
+            entry.expires = Instant::now() + ttl;
+            entry.response = Some(response);
+        }
+    }
+
+    async fn release(&self, key: &str) {
+        self.abandon(key);
+    }
+
+    fn abandon(&self, key: &str) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.remove(key);
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Entry {
+    fingerprint: String,
+    expires: Instant,
+    response: Option<StoredResponse>,
+}
+
+fn status_response<B: From<Bytes>>(status: StatusCode) -> Response<B> {
+    let mut rsp = Response::new(B::from(Bytes::new()));
+    *rsp.status_mut() = status;
+    rsp
+}
+
+const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+const REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
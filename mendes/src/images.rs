@@ -0,0 +1,560 @@
+use std::borrow::Cow;
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::multipart::File;
+
+/// Limits for validating uploaded images
+///
+/// ```no_run
+/// # #[cfg(all(feature = "application", feature = "body-util"))]
+/// # mod example {
+/// # use mendes::forms::{from_form_data, File};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::images::{Format, Limits};
+/// # use mendes::{handler, Application, Body, Error};
+/// # use serde::Deserialize;
+/// # struct App {}
+/// # impl App {
+/// #     async fn store_avatar(&self, format: Format, data: &[u8]) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[derive(Deserialize)]
+/// struct Avatar<'a> {
+///     #[serde(borrow)]
+///     image: File<'a>,
+/// }
+///
+/// #[handler(POST)]
+/// async fn avatar(app: &App, req: &Parts, body: Body) -> Result<Response<Body>, Error> {
+///     let bytes = App::body_bytes(req, body, 4 * 1024 * 1024).await?;
+///     let form = from_form_data::<Avatar>(&req.headers, &bytes)?;
+///     let limits = Limits {
+///         max_width: 2048,
+///         max_height: 2048,
+///         ..Limits::default()
+///     };
+///
+///     let image = limits
+///         .validate(&form.image)
+///         .map_err(|e| Error::unprocessable(e.to_string()))?;
+///     app.store_avatar(image.format, &image.strip_metadata()).await?;
+///     todo!()
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Limits {
+    /// The maximum size of the encoded image, in bytes
+    pub max_size: usize,
+    pub max_width: u32,
+    pub max_height: u32,
+    /// The maximum number of pixels, which bounds the memory needed to decode the image
+    pub max_pixels: u64,
+}
+
+impl Limits {
+    /// Check that `file` is a well-formed image within these limits
+    ///
+    /// If the part has a content type, it must match the detected image format.
+    pub fn validate<'a>(&self, file: &File<'a>) -> Result<Image<'a>, Error> {
+        self.check(file.data, file.ctype)
+    }
+
+    /// Check that `data` is a well-formed image within these limits
+    pub fn check<'a>(&self, data: &'a [u8], ctype: Option<&str>) -> Result<Image<'a>, Error> {
+        if data.len() > self.max_size {
+            return Err(Error::TooLarge);
+        }
+
+        let image = Image::parse(data)?;
+        if let Some(ctype) = ctype {
+            let ctype = ctype.split(';').next().unwrap_or_default().trim();
+            let matches = ctype.eq_ignore_ascii_case(image.format.media_type())
+                || (image.format == Format::Jpeg && ctype.eq_ignore_ascii_case("image/jpg"));
+            if !matches {
+                return Err(Error::ContentTypeMismatch);
+            }
+        }
+
+        if image.width > self.max_width
+            || image.height > self.max_height
+            || u64::from(image.width) * u64::from(image.height) > self.max_pixels
+        {
+            return Err(Error::Dimensions {
+                width: image.width,
+                height: image.height,
+            });
+        }
+
+        Ok(image)
+    }
+}
+
+impl Default for Limits {
+    /// 10 MiB, at most 8192 pixels in either dimension and 40 megapixels in total
+    fn default() -> Self {
+        Self {
+            max_size: 10 * 1024 * 1024,
+            max_width: 8192,
+            max_height: 8192,
+            max_pixels: 40_000_000,
+        }
+    }
+}
+
+/// A well-formed image in one of the supported formats
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Image<'a> {
+    pub format: Format,
+    pub width: u32,
+    pub height: u32,
+    data: &'a [u8],
+    /// Byte ranges in `data` containing metadata, in order
+    metadata: Vec<Range<usize>>,
+}
+
+impl<'a> Image<'a> {
+    /// Detect the format of `data` and check its structure
+    ///
+    /// This walks the chunks or segments of the image (verifying checksums where the format
+    /// has them) to find its dimensions and make sure it is complete, but does not decode the
+    /// pixel data itself.
+    pub fn parse(data: &'a [u8]) -> Result<Self, Error> {
+        let (format, ((width, height), metadata)) = if data.starts_with(PNG_SIGNATURE) {
+            (Format::Png, png(data)?)
+        } else if data.starts_with(&[0xff, 0xd8]) {
+            (Format::Jpeg, jpeg(data)?)
+        } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+            (Format::Gif, gif(data)?)
+        } else if data.len() >= 12 && &data[..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+            (Format::WebP, webp(data)?)
+        } else {
+            return Err(Error::Unsupported);
+        };
+
+        if width == 0 || height == 0 {
+            return Err(Error::Invalid("image has no pixels"));
+        }
+
+        Ok(Self {
+            format,
+            width,
+            height,
+            data,
+            metadata,
+        })
+    }
+
+    /// The encoded image
+    pub fn data(&self) -> &'a [u8] {
+        self.data
+    }
+
+    /// Whether the image contains EXIF data, XMP data or comments
+    pub fn has_metadata(&self) -> bool {
+        !self.metadata.is_empty()
+    }
+
+    /// The encoded image without EXIF data, XMP data and comments
+    ///
+    /// This removes location data and other details users may not want to publish. Color
+    /// profiles are kept. Images that rely on their EXIF orientation will no longer be rotated
+    /// by browsers after stripping.
+    pub fn strip_metadata(&self) -> Cow<'a, [u8]> {
+        if self.metadata.is_empty() {
+            return Cow::Borrowed(self.data);
+        }
+
+        let mut out = Vec::with_capacity(self.data.len());
+        let mut start = 0;
+        for range in &self.metadata {
+            out.extend_from_slice(&self.data[start..range.start]);
+            start = range.end;
+        }
+        out.extend_from_slice(&self.data[start..]);
+
+        if self.format == Format::WebP {
+            // Fix up the RIFF size and clear the EXIF and XMP flags in the extended header
+            let size = (out.len() - 8) as u32;
+            out[4..8].copy_from_slice(&size.to_le_bytes());
+            if &out[12..16] == b"VP8X" {
+                out[20] &= !(VP8X_EXIF | VP8X_XMP);
+            }
+        }
+
+        Cow::Owned(out)
+    }
+}
+
+/// Supported image formats
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Format {
+    Gif,
+    Jpeg,
+    Png,
+    WebP,
+}
+
+impl Format {
+    pub fn media_type(&self) -> &'static str {
+        match self {
+            Format::Gif => "image/gif",
+            Format::Jpeg => "image/jpeg",
+            Format::Png => "image/png",
+            Format::WebP => "image/webp",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Gif => "gif",
+            Format::Jpeg => "jpg",
+            Format::Png => "png",
+            Format::WebP => "webp",
+        }
+    }
+}
+
+type Parsed = ((u32, u32), Vec<Range<usize>>);
+
+fn png(data: &[u8]) -> Result<Parsed, Error> {
+    let mut pos = PNG_SIGNATURE.len();
+    let (mut size, mut metadata, mut has_data) = (None, Vec::new(), false);
+    loop {
+        let start = pos;
+        let len = be32(data, pos)? as usize;
+        let kind = bytes(data, pos + 4, 4)?;
+        let chunk = bytes(data, pos + 4, len + 4)?;
+        let crc = be32(data, pos + 8 + len)?;
+        if crc32fast::hash(chunk) != crc {
+            return Err(Error::Invalid("chunk checksum mismatch"));
+        }
+        pos += 12 + len;
+
+        match (kind, size) {
+            (b"IHDR", None) if len == 13 => {
+                size = Some((be32(data, start + 8)?, be32(data, start + 12)?))
+            }
+            (_, None) => return Err(Error::Invalid("missing header chunk")),
+            (b"IDAT", _) => has_data = true,
+            (b"eXIf" | b"tEXt" | b"zTXt" | b"iTXt" | b"tIME", _) => metadata.push(start..pos),
+            (b"IEND", Some(size)) => {
+                return match has_data && pos == data.len() {
+                    true => Ok((size, metadata)),
+                    false => Err(Error::Invalid("missing image data")),
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+fn jpeg(data: &[u8]) -> Result<Parsed, Error> {
+    let mut pos = 2;
+    let (mut size, mut metadata) = (None, Vec::new());
+    loop {
+        if byte(data, pos)? != 0xff {
+            return Err(Error::Invalid("expected segment marker"));
+        }
+        // Markers may be preceded by any number of fill bytes
+        while byte(data, pos + 1)? == 0xff {
+            pos += 1;
+        }
+
+        let start = pos;
+        let marker = byte(data, pos + 1)?;
+        pos += 2;
+        match marker {
+            0xd9 => {
+                return match size {
+                    Some(size) => Ok((size, metadata)),
+                    None => Err(Error::Invalid("missing frame header")),
+                }
+            }
+            0x01 | 0xd0..=0xd7 => continue,
+            _ => {}
+        }
+
+        let len = usize::from(be16(data, pos)?);
+        if len < 2 {
+            return Err(Error::Invalid("invalid segment length"));
+        }
+        let segment = bytes(data, pos + 2, len - 2)?;
+        pos += len;
+
+        match marker {
+            // Start of frame, except for DHT, JPG and DAC which share the range
+            0xc0..=0xcf if !matches!(marker, 0xc4 | 0xc8 | 0xcc) => {
+                if segment.len() < 5 {
+                    return Err(Error::Invalid("invalid frame header"));
+                }
+                let height = u32::from(be16(segment, 1)?);
+                let width = u32::from(be16(segment, 3)?);
+                size = Some((width, height));
+            }
+            // APP1 (EXIF and XMP), APP13 (IPTC) and comments
+            0xe1 | 0xed | 0xfe => metadata.push(start..pos),
+            // Start of scan: skip the entropy-coded data up to the next marker
+            0xda => loop {
+                match (byte(data, pos)?, byte(data, pos + 1)?) {
+                    (0xff, 0x00 | 0xd0..=0xd7) => pos += 2,
+                    (0xff, _) => break,
+                    _ => pos += 1,
+                }
+            },
+            _ => {}
+        }
+    }
+}
+
+fn gif(data: &[u8]) -> Result<Parsed, Error> {
+    let width = u32::from(le16(data, 6)?);
+    let height = u32::from(le16(data, 8)?);
+    let mut pos = 13 + color_table(byte(data, 10)?);
+    let mut metadata = Vec::new();
+    loop {
+        let start = pos;
+        match byte(data, pos)? {
+            // Extension
+            0x21 => {
+                let label = byte(data, pos + 1)?;
+                pos = sub_blocks(data, pos + 2)?;
+                if label == 0xfe {
+                    metadata.push(start..pos);
+                }
+            }
+            // Image descriptor
+            0x2c => {
+                let flags = byte(data, pos + 9)?;
+                pos += 10 + color_table(flags);
+                // Skip the LZW minimum code size
+                pos = sub_blocks(data, pos + 1)?;
+            }
+            0x3b => {
+                return match pos + 1 == data.len() {
+                    true => Ok(((width, height), metadata)),
+                    false => Err(Error::Invalid("data after trailer")),
+                }
+            }
+            _ => return Err(Error::Invalid("unknown block")),
+        }
+    }
+}
+
+/// The size of the color table indicated by GIF descriptor `flags`
+fn color_table(flags: u8) -> usize {
+    match flags & 0x80 {
+        0 => 0,
+        _ => 3 << ((flags & 0x07) + 1),
+    }
+}
+
+/// Skip a sequence of GIF sub-blocks starting at `pos`, returning the position after them
+fn sub_blocks(data: &[u8], mut pos: usize) -> Result<usize, Error> {
+    loop {
+        match usize::from(byte(data, pos)?) {
+            0 => return Ok(pos + 1),
+            len => pos += 1 + len,
+        }
+    }
+}
+
+fn webp(data: &[u8]) -> Result<Parsed, Error> {
+    if le32(data, 4)? as usize != data.len() - 8 {
+        return Err(Error::Invalid("file size mismatch"));
+    }
+
+    let (mut pos, mut size, mut metadata) = (12, None, Vec::new());
+    while pos < data.len() {
+        let start = pos;
+        let kind = bytes(data, pos, 4)?;
+        let len = le32(data, pos + 4)? as usize;
+        let chunk = bytes(data, pos + 8, len)?;
+        // Chunks are padded to an even size
+        pos += 8 + len + (len & 1);
+
+        match kind {
+            b"VP8X" if start == 12 && len >= 10 => {
+                let width = le24(chunk, 4)? + 1;
+                let height = le24(chunk, 7)? + 1;
+                size = Some((width, height));
+            }
+            b"VP8 " if size.is_none() => {
+                if bytes(chunk, 3, 3)? != [0x9d, 0x01, 0x2a] {
+                    return Err(Error::Invalid("invalid VP8 header"));
+                }
+                let width = u32::from(le16(chunk, 6)? & 0x3fff);
+                let height = u32::from(le16(chunk, 8)? & 0x3fff);
+                size = Some((width, height));
+            }
+            b"VP8L" if size.is_none() => {
+                if byte(chunk, 0)? != 0x2f {
+                    return Err(Error::Invalid("invalid VP8L header"));
+                }
+                let bits = le32(chunk, 1)?;
+                size = Some(((bits & 0x3fff) + 1, ((bits >> 14) & 0x3fff) + 1));
+            }
+            b"EXIF" | b"XMP " => metadata.push(start..pos.min(data.len())),
+            _ => {}
+        }
+    }
+
+    match size {
+        Some(size) => Ok((size, metadata)),
+        None => Err(Error::Invalid("missing image data")),
+    }
+}
+
+fn byte(data: &[u8], pos: usize) -> Result<u8, Error> {
+    data.get(pos).copied().ok_or(Error::Truncated)
+}
+
+fn bytes(data: &[u8], pos: usize, len: usize) -> Result<&[u8], Error> {
+    pos.checked_add(len)
+        .and_then(|end| data.get(pos..end))
+        .ok_or(Error::Truncated)
+}
+
+fn be16(data: &[u8], pos: usize) -> Result<u16, Error> {
+    Ok(u16::from_be_bytes(bytes(data, pos, 2)?.try_into().unwrap()))
+}
+
+fn be32(data: &[u8], pos: usize) -> Result<u32, Error> {
+    Ok(u32::from_be_bytes(bytes(data, pos, 4)?.try_into().unwrap()))
+}
+
+fn le16(data: &[u8], pos: usize) -> Result<u16, Error> {
+    Ok(u16::from_le_bytes(bytes(data, pos, 2)?.try_into().unwrap()))
+}
+
+fn le24(data: &[u8], pos: usize) -> Result<u32, Error> {
+    let bytes = bytes(data, pos, 3)?;
+    Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], 0]))
+}
+
+fn le32(data: &[u8], pos: usize) -> Result<u32, Error> {
+    Ok(u32::from_le_bytes(bytes(data, pos, 4)?.try_into().unwrap()))
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("image too large")]
+    TooLarge,
+    #[error("image dimensions {width}x{height} exceed limits")]
+    Dimensions { width: u32, height: u32 },
+    #[error("content type does not match image format")]
+    ContentTypeMismatch,
+    #[error("unsupported image format")]
+    Unsupported,
+    #[error("image data truncated")]
+    Truncated,
+    #[error("invalid image: {0}")]
+    Invalid(&'static str),
+}
+
+const PNG_SIGNATURE: &[u8] = b"\x89PNG\r\n\x1a\n";
+const VP8X_EXIF: u8 = 0x08;
+const VP8X_XMP: u8 = 0x04;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chunk(out: &mut Vec<u8>, kind: &[u8], data: &[u8]) {
+        out.extend((data.len() as u32).to_be_bytes());
+        let start = out.len();
+        out.extend(kind);
+        out.extend(data);
+        let crc = crc32fast::hash(&out[start..]);
+        out.extend(crc.to_be_bytes());
+    }
+
+    #[test]
+    fn png() {
+        let mut header = Vec::new();
+        header.extend(3u32.to_be_bytes());
+        header.extend(2u32.to_be_bytes());
+        header.extend([8, 2, 0, 0, 0]);
+
+        let mut data = PNG_SIGNATURE.to_vec();
+        chunk(&mut data, b"IHDR", &header);
+        chunk(&mut data, b"tEXt", b"Comment\0hello");
+        chunk(&mut data, b"IDAT", &[0; 8]);
+        chunk(&mut data, b"IEND", &[]);
+
+        let limits = Limits::default();
+        let image = limits.check(&data, Some("image/png")).unwrap();
+        assert_eq!(
+            (image.format, image.width, image.height),
+            (Format::Png, 3, 2)
+        );
+        assert!(image.has_metadata());
+
+        let stripped = image.strip_metadata();
+        assert_eq!(stripped.len(), data.len() - 25);
+        assert!(!Image::parse(&stripped).unwrap().has_metadata());
+
+        assert!(matches!(
+            limits.check(&data, Some("image/gif")),
+            Err(Error::ContentTypeMismatch)
+        ));
+        let small = Limits {
+            max_width: 2,
+            ..limits
+        };
+        assert!(matches!(
+            small.check(&data, None),
+            Err(Error::Dimensions {
+                width: 3,
+                height: 2
+            })
+        ));
+
+        let last = data.len() - 1;
+        data[last] ^= 1;
+        assert!(matches!(Image::parse(&data), Err(Error::Invalid(_))));
+        assert!(matches!(Image::parse(&data[..20]), Err(Error::Truncated)));
+    }
+
+    #[test]
+    fn jpeg() {
+        let mut data = vec![0xff, 0xd8];
+        data.extend([0xff, 0xe0, 0x00, 0x07, b'J', b'F', b'I', b'F', 0]);
+        data.extend([0xff, 0xe1, 0x00, 0x08, b'E', b'x', b'i', b'f', 0, 0]);
+        data.extend([
+            0xff, 0xc0, 0x00, 0x0b, 8, 0x01, 0x00, 0x02, 0x80, 1, 1, 0x11, 0,
+        ]);
+        data.extend([0xff, 0xda, 0x00, 0x08, 1, 1, 0, 0, 0x3f, 0]);
+        data.extend([0x12, 0xff, 0x00, 0x34, 0xff, 0xd0, 0x56]);
+        data.extend([0xff, 0xd9]);
+
+        let image = Image::parse(&data).unwrap();
+        assert_eq!(
+            (image.format, image.width, image.height),
+            (Format::Jpeg, 640, 256)
+        );
+
+        let stripped = image.strip_metadata();
+        assert_eq!(stripped.len(), data.len() - 10);
+        assert!(!Image::parse(&stripped).unwrap().has_metadata());
+        assert!(Limits::default().check(&data, Some("image/jpg")).is_ok());
+        assert!(matches!(
+            Image::parse(&data[..data.len() - 1]),
+            Err(Error::Truncated)
+        ));
+    }
+}
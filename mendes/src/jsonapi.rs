@@ -0,0 +1,485 @@
+use std::fmt;
+
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{Response, StatusCode};
+use serde::ser::Error as _;
+use serde::Serialize;
+use serde_json::{json, Map, Value};
+
+use crate::application::{Application, Error, IntoResponse};
+use crate::pagination::{Pagination, CURSOR};
+
+/// A type that can be represented as a JSON:API resource object
+///
+/// The serialized fields of the type become the resource's `attributes`, except for an `id`
+/// field, which JSON:API keeps at the top level of the resource object:
+///
+/// ```no_run
+/// # use mendes::jsonapi::{Relationship, Resource};
+/// # use serde::Serialize;
+/// # #[derive(Serialize)]
+/// # struct Person {
+/// #     id: u64,
+/// # }
+/// # impl Resource for Person {
+/// #     const TYPE: &'static str = "people";
+/// #     fn id(&self) -> String {
+/// #         self.id.to_string()
+/// #     }
+/// # }
+/// #[derive(Serialize)]
+/// struct Article {
+///     id: u64,
+///     title: String,
+///     #[serde(skip)]
+///     author_id: u64,
+/// }
+///
+/// impl Resource for Article {
+///     const TYPE: &'static str = "articles";
+///
+///     fn id(&self) -> String {
+///         self.id.to_string()
+///     }
+///
+///     fn relationships(&self) -> Vec<(&'static str, Relationship)> {
+///         vec![("author", Relationship::one(Person::TYPE, self.author_id))]
+///     }
+/// }
+/// ```
+pub trait Resource: Serialize {
+    /// The resource type, like `"articles"`
+    const TYPE: &'static str;
+
+    fn id(&self) -> String;
+
+    /// Relationships to other resources, by name
+    fn relationships(&self) -> Vec<(&'static str, Relationship)> {
+        Vec::new()
+    }
+
+    /// The URL for this resource, used as its `self` link
+    fn url(&self) -> Option<String> {
+        None
+    }
+}
+
+/// A relationship from a resource to other resources, by their type and id
+#[derive(Clone, Debug, PartialEq)]
+pub struct Relationship {
+    data: Value,
+}
+
+impl Relationship {
+    /// A to-one relationship
+    pub fn one(kind: &str, id: impl fmt::Display) -> Self {
+        Self {
+            data: identifier(kind, id.to_string()),
+        }
+    }
+
+    /// An empty to-one relationship
+    pub fn none() -> Self {
+        Self { data: Value::Null }
+    }
+
+    /// A to-many relationship
+    pub fn many<I: fmt::Display>(kind: &str, ids: impl IntoIterator<Item = I>) -> Self {
+        let ids = ids.into_iter().map(|id| identifier(kind, id.to_string()));
+        Self {
+            data: Value::Array(ids.collect()),
+        }
+    }
+}
+
+/// A JSON:API document
+///
+/// Build a document from the primary data, then add related resources, links and metadata:
+///
+/// ```no_run
+/// # use mendes::http::request::Parts;
+/// # use mendes::jsonapi::{Document, Resource};
+/// # use mendes::pagination::Pagination;
+/// # use mendes::{handler, Body, Error};
+/// # use serde::Serialize;
+/// # #[derive(Serialize)]
+/// # struct Article {
+/// #     id: u64,
+/// # }
+/// # impl Resource for Article {
+/// #     const TYPE: &'static str = "articles";
+/// #     fn id(&self) -> String {
+/// #         self.id.to_string()
+/// #     }
+/// # }
+/// # #[derive(Serialize)]
+/// # struct Person {
+/// #     id: u64,
+/// # }
+/// # impl Resource for Person {
+/// #     const TYPE: &'static str = "people";
+/// #     fn id(&self) -> String {
+/// #         self.id.to_string()
+/// #     }
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn articles(&self, offset: u64, limit: u32) -> Result<(Vec<Article>, u64), Error> {
+/// #         todo!()
+/// #     }
+/// #     async fn authors(&self, articles: &[Article]) -> Result<Vec<Person>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn list(app: &App, req: &Parts, pages: Pagination) -> Result<Document, Error> {
+///     let (articles, total) = app.db.articles(pages.offset(), pages.limit()).await?;
+///     let authors = app.db.authors(&articles).await?;
+///     Ok(Document::collection(&articles)?
+///         .include_all(&authors)?
+///         .paginate(&pages, req, total))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Documents are sent with the `application/vnd.api+json` content type.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Document {
+    status: StatusCode,
+    data: Option<Value>,
+    errors: Vec<Value>,
+    included: Vec<Value>,
+    links: Map<String, Value>,
+    meta: Map<String, Value>,
+}
+
+impl Document {
+    /// A document with a single resource as its primary data
+    pub fn resource<T: Resource>(resource: &T) -> Result<Self, serde_json::Error> {
+        Ok(Self::new(Some(resource_object(resource)?)))
+    }
+
+    /// A document with a list of resources as its primary data
+    pub fn collection<'a, T: Resource + 'a>(
+        resources: impl IntoIterator<Item = &'a T>,
+    ) -> Result<Self, serde_json::Error> {
+        let data = resources
+            .into_iter()
+            .map(resource_object)
+            .collect::<Result<_, _>>()?;
+        Ok(Self::new(Some(Value::Array(data))))
+    }
+
+    /// A document describing `error`, with its status code
+    pub fn error(error: &Error) -> Self {
+        let mut doc = Self::new(None);
+        doc.status = error.status();
+        doc.errors.push(json!({
+            "status": error.status().as_str(),
+            "title": error.message(),
+        }));
+        doc
+    }
+
+    /// Add a related resource to the `included` resources
+    ///
+    /// Resources that were already included (or are part of the primary data) are skipped.
+    pub fn include<T: Resource>(mut self, resource: &T) -> Result<Self, serde_json::Error> {
+        let id = resource.id();
+        let primary = match &self.data {
+            Some(Value::Array(data)) => data.iter().any(|obj| is(obj, T::TYPE, &id)),
+            Some(obj) => is(obj, T::TYPE, &id),
+            None => false,
+        };
+
+        if !primary && !self.included.iter().any(|obj| is(obj, T::TYPE, &id)) {
+            self.included.push(resource_object(resource)?);
+        }
+        Ok(self)
+    }
+
+    /// Add several related resources to the `included` resources
+    pub fn include_all<'a, T: Resource + 'a>(
+        mut self,
+        resources: impl IntoIterator<Item = &'a T>,
+    ) -> Result<Self, serde_json::Error> {
+        for resource in resources {
+            self = self.include(resource)?;
+        }
+        Ok(self)
+    }
+
+    /// Add a top-level link
+    pub fn link(mut self, rel: &str, href: impl Into<String>) -> Self {
+        self.links
+            .insert(rel.to_owned(), Value::String(href.into()));
+        self
+    }
+
+    /// Add a top-level metadata entry
+    pub fn meta(mut self, key: &str, value: impl Into<Value>) -> Self {
+        self.meta.insert(key.to_owned(), value.into());
+        self
+    }
+
+    /// Add pagination links for the current page, and the `total` number of items as metadata
+    pub fn paginate<const DEFAULT: u32, const MAX: u32>(
+        mut self,
+        pages: &Pagination<DEFAULT, MAX>,
+        req: &Parts,
+        total: u64,
+    ) -> Self {
+        for (rel, url) in pages.links(req, total) {
+            self.links.insert(rel.to_owned(), Value::String(url));
+        }
+        self.meta("total", total)
+    }
+
+    /// Add a `next` pagination link for the `next` cursor, which is `null` on the last page
+    pub fn paginate_cursor<const DEFAULT: u32, const MAX: u32>(
+        mut self,
+        pages: &Pagination<DEFAULT, MAX>,
+        req: &Parts,
+        next: Option<&str>,
+    ) -> Self {
+        let next = next.map(|next| Value::String(pages.url(req, CURSOR, next)));
+        self.links
+            .insert("next".to_owned(), next.unwrap_or(Value::Null));
+        self
+    }
+
+    /// Set the status code for the response, `200 OK` by default
+    pub fn status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// The document as a JSON value
+    pub fn to_value(&self) -> Value {
+        let mut doc = Map::new();
+        if let Some(data) = &self.data {
+            doc.insert("data".to_owned(), data.clone());
+        }
+        if !self.errors.is_empty() {
+            doc.insert("errors".to_owned(), Value::Array(self.errors.clone()));
+        }
+        if !self.included.is_empty() {
+            doc.insert("included".to_owned(), Value::Array(self.included.clone()));
+        }
+        if !self.links.is_empty() {
+            doc.insert("links".to_owned(), Value::Object(self.links.clone()));
+        }
+        if !self.meta.is_empty() {
+            doc.insert("meta".to_owned(), Value::Object(self.meta.clone()));
+        }
+        Value::Object(doc)
+    }
+
+    fn new(data: Option<Value>) -> Self {
+        Self {
+            status: StatusCode::OK,
+            data,
+            errors: Vec::new(),
+            included: Vec::new(),
+            links: Map::new(),
+            meta: Map::new(),
+        }
+    }
+}
+
+impl<A: Application> IntoResponse<A> for Document
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        Response::builder()
+            .status(self.status)
+            .header(CONTENT_TYPE, MEDIA_TYPE)
+            .body(self.to_value().to_string().into())
+            .unwrap()
+    }
+}
+
+fn resource_object<T: Resource>(resource: &T) -> Result<Value, serde_json::Error> {
+    let mut attributes = match serde_json::to_value(resource)? {
+        Value::Object(attributes) => attributes,
+        _ => {
+            return Err(serde_json::Error::custom(
+                "resource must serialize to a map",
+            ))
+        }
+    };
+    attributes.remove("id");
+
+    let mut obj = Map::new();
+    obj.insert("type".to_owned(), Value::from(T::TYPE));
+    obj.insert("id".to_owned(), Value::from(resource.id()));
+    if !attributes.is_empty() {
+        obj.insert("attributes".to_owned(), Value::Object(attributes));
+    }
+
+    let relationships = resource.relationships();
+    if !relationships.is_empty() {
+        let relationships = relationships
+            .into_iter()
+            .map(|(name, rel)| (name.to_owned(), json!({ "data": rel.data })))
+            .collect();
+        obj.insert("relationships".to_owned(), Value::Object(relationships));
+    }
+
+    if let Some(url) = resource.url() {
+        obj.insert("links".to_owned(), json!({ "self": url }));
+    }
+
+    Ok(Value::Object(obj))
+}
+
+fn identifier(kind: &str, id: String) -> Value {
+    json!({ "type": kind, "id": id })
+}
+
+/// Whether the resource object `obj` has the given type and id
+fn is(obj: &Value, kind: &str, id: &str) -> bool {
+    obj["type"] == kind && obj["id"] == id
+}
+
+/// The JSON:API media type
+pub const MEDIA_TYPE: &str = "application/vnd.api+json";
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+    use serde::Serialize;
+
+    use super::*;
+    use crate::application::ErrorKind;
+
+    #[derive(Serialize)]
+    struct Article {
+        id: u64,
+        title: &'static str,
+        #[serde(skip)]
+        author: u64,
+    }
+
+    impl Resource for Article {
+        const TYPE: &'static str = "articles";
+
+        fn id(&self) -> String {
+            self.id.to_string()
+        }
+
+        fn relationships(&self) -> Vec<(&'static str, Relationship)> {
+            vec![("author", Relationship::one(Person::TYPE, self.author))]
+        }
+
+        fn url(&self) -> Option<String> {
+            Some(format!("/articles/{}", self.id))
+        }
+    }
+
+    #[derive(Serialize)]
+    struct Person {
+        id: u64,
+        name: &'static str,
+    }
+
+    impl Resource for Person {
+        const TYPE: &'static str = "people";
+
+        fn id(&self) -> String {
+            self.id.to_string()
+        }
+    }
+
+    #[test]
+    fn document() {
+        let articles = [
+            Article {
+                id: 1,
+                title: "Hello",
+                author: 9,
+            },
+            Article {
+                id: 2,
+                title: "World",
+                author: 9,
+            },
+        ];
+        let author = Person { id: 9, name: "Dan" };
+
+        let (req, _) = Request::get("/articles?page=1&per_page=2")
+            .body(())
+            .unwrap()
+            .into_parts();
+        let pages = Pagination::<20, 100>::from_query(req.uri.query().unwrap()).unwrap();
+        let doc = Document::collection(&articles)
+            .unwrap()
+            .include(&author)
+            .unwrap()
+            .include(&author)
+            .unwrap()
+            .paginate(&pages, &req, 3);
+
+        assert_eq!(
+            doc.to_value(),
+            json!({
+                "data": [
+                    {
+                        "type": "articles",
+                        "id": "1",
+                        "attributes": {"title": "Hello"},
+                        "relationships": {"author": {"data": {"type": "people", "id": "9"}}},
+                        "links": {"self": "/articles/1"},
+                    },
+                    {
+                        "type": "articles",
+                        "id": "2",
+                        "attributes": {"title": "World"},
+                        "relationships": {"author": {"data": {"type": "people", "id": "9"}}},
+                        "links": {"self": "/articles/2"},
+                    },
+                ],
+                "included": [
+                    {"type": "people", "id": "9", "attributes": {"name": "Dan"}},
+                ],
+                "links": {
+                    "first": "/articles?page=1&per_page=2",
+                    "next": "/articles?page=2&per_page=2",
+                    "last": "/articles?page=2&per_page=2",
+                },
+                "meta": {"total": 3},
+            })
+        );
+    }
+
+    #[test]
+    fn error() {
+        let doc = Document::error(&Error::from(ErrorKind::PathNotFound));
+        assert_eq!(doc.status, StatusCode::NOT_FOUND);
+        assert_eq!(
+            doc.to_value(),
+            json!({"errors": [{"status": "404", "title": "no matching routes"}]})
+        );
+
+        let rel = Relationship::many("tags", [1, 2]);
+        assert_eq!(
+            rel.data,
+            json!([{"type": "tags", "id": "1"}, {"type": "tags", "id": "2"}])
+        );
+        assert_eq!(Relationship::none().data, Value::Null);
+    }
+}
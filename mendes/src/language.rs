@@ -0,0 +1,213 @@
+use std::cmp::Reverse;
+use std::sync::Arc;
+
+use http::header::ACCEPT_LANGUAGE;
+use http::request::Parts;
+
+use crate::application::{Application, FromContext, PathState};
+
+/// Give the `Language` extractor access to the languages the `Application` supports
+pub trait AppWithLanguages: Application {
+    /// The supported language tags, starting with the default
+    ///
+    /// This must contain at least one language.
+    fn languages(&self) -> &[&str];
+}
+
+/// The supported language that best matches the request's `Accept-Language` header
+///
+/// Falls back to the first of the application's `languages()` if none of them match. Responses
+/// that depend on the language should list `Accept-Language` in their `Vary` header.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Language<'a>(pub &'a str);
+
+impl<'a, A: AppWithLanguages> FromContext<'a, A> for Language<'a> {
+    fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let languages = app.languages();
+        let accept = AcceptLanguage::from_request(req);
+        match accept
+            .negotiate(languages)
+            .or_else(|| languages.first().copied())
+        {
+            Some(language) => Ok(Language(language)),
+            None => panic!("no supported languages"),
+        }
+    }
+}
+
+/// The language ranges from the request's `Accept-Language` header
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::language::AcceptLanguage;
+/// # use mendes::{handler, Body, Error};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn home(app: &App, accept: AcceptLanguage) -> Result<Response<Body>, Error> {
+///     let language = accept.negotiate(&["en", "nl", "pt-BR"]).unwrap_or("en");
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct AcceptLanguage {
+    ranges: Vec<String>,
+}
+
+impl AcceptLanguage {
+    /// Get the language ranges from the request headers
+    pub fn from_request(req: &Parts) -> Self {
+        match req.headers.get(ACCEPT_LANGUAGE).map(|v| v.to_str()) {
+            Some(Ok(accept)) => Self::parse(accept),
+            _ => Self::default(),
+        }
+    }
+
+    /// Parse an `Accept-Language` header value
+    pub fn parse(accept: &str) -> Self {
+        Self {
+            ranges: ranges(accept).map(str::to_owned).collect(),
+        }
+    }
+
+    /// The language ranges in order of preference, leaving out those with a quality of zero
+    pub fn ranges(&self) -> impl Iterator<Item = &str> {
+        self.ranges.iter().map(String::as_str)
+    }
+
+    /// Select the best match from the `supported` language tags
+    ///
+    /// Language ranges are tried in order of their quality value. A range matches a
+    /// supported tag if it is equal to it or to one of its prefixes (so `en-US` will
+    /// match `en`, and `en` will match `en-GB`). Returns `None` if nothing matches.
+    pub fn negotiate<'s>(&self, supported: &[&'s str]) -> Option<&'s str> {
+        best_match(self.ranges(), supported.iter().copied()).map(|index| supported[index])
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for AcceptLanguage {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(Self::from_request(req))
+    }
+}
+
+/// Find the index of the best match for `ranges` (in order of preference) in `supported`
+pub(crate) fn best_match<'r, 's>(
+    ranges: impl Iterator<Item = &'r str>,
+    supported: impl Iterator<Item = &'s str> + Clone,
+) -> Option<usize> {
+    for range in ranges {
+        if range == "*" {
+            break;
+        }
+
+        if let Some(index) = find(range, supported.clone()) {
+            return Some(index);
+        }
+    }
+
+    None
+}
+
+fn find<'s>(range: &str, supported: impl Iterator<Item = &'s str> + Clone) -> Option<usize> {
+    let mut range = range;
+    loop {
+        if let Some(index) = supported
+            .clone()
+            .position(|tag| tag.eq_ignore_ascii_case(range))
+        {
+            return Some(index);
+        }
+
+        match range.rfind('-') {
+            Some(idx) => range = &range[..idx],
+            None => break,
+        }
+    }
+
+    supported.clone().position(|tag| {
+        tag.len() > range.len()
+            && tag.as_bytes()[range.len()] == b'-'
+            && tag[..range.len()].eq_ignore_ascii_case(range)
+    })
+}
+
+/// Parse the language ranges from an `Accept-Language` header value
+///
+/// Yields the ranges in order of descending quality, skipping those with a quality of zero.
+pub(crate) fn ranges(accept: &str) -> impl Iterator<Item = &str> {
+    let mut ranges = accept
+        .split(',')
+        .filter_map(|s| {
+            let mut parts = s.splitn(2, ';');
+            let range = parts.next()?.trim();
+            if range.is_empty() {
+                return None;
+            }
+
+            let qual = match parts.next() {
+                Some(s) => {
+                    let (key, value) = s.trim().split_once('=')?;
+                    if key.trim() != "q" {
+                        return None;
+                    }
+                    value.trim().parse::<f32>().ok()?
+                }
+                None => 1.0,
+            };
+
+            match qual > 0.0 {
+                true => Some((range, (qual * 1000.0) as u16)),
+                false => None,
+            }
+        })
+        .collect::<Vec<_>>();
+
+    ranges.sort_by_key(|(_, qual)| Reverse(*qual));
+    ranges.into_iter().map(|(range, _)| range)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn negotiate() {
+        let accept = AcceptLanguage::parse("fr-CH, fr;q=0.9, en-US;q=0.8, de;q=0");
+        assert_eq!(
+            accept.ranges().collect::<Vec<_>>(),
+            ["fr-CH", "fr", "en-US"]
+        );
+
+        let supported = ["en", "nl", "fr-FR"];
+        assert_eq!(accept.negotiate(&supported), Some("fr-FR"));
+        assert_eq!(
+            AcceptLanguage::parse("en-GB;q=0.5, nl").negotiate(&supported),
+            Some("nl")
+        );
+        assert_eq!(
+            AcceptLanguage::parse("de, *;q=0.5, en;q=0.1").negotiate(&supported),
+            None
+        );
+        assert_eq!(AcceptLanguage::default().negotiate(&supported), None);
+    }
+}
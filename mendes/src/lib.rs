@@ -10,7 +10,9 @@ pub use http;
 /// Core of the Mendes web application toolkit
 pub mod application;
 #[cfg(feature = "application")]
-pub use application::{handler, route, scope, Application, Context, Error, FromContext};
+pub use application::{
+    handler, route, scope, Application, Context, Error, FromContext, FromContextAsync,
+};
 
 #[cfg(feature = "application")]
 #[cfg_attr(docsrs, doc(cfg(feature = "application")))]
@@ -18,11 +20,91 @@ pub mod body;
 #[cfg(feature = "application")]
 pub use body::Body;
 
+#[cfg(feature = "apikeys")]
+#[cfg_attr(docsrs, doc(cfg(feature = "apikeys")))]
+/// API keys with scopes, stored as hashes
+pub mod apikeys;
+
+#[cfg(feature = "assets")]
+#[cfg_attr(docsrs, doc(cfg(feature = "assets")))]
+/// Static assets with fingerprinted file names
+pub mod assets;
+
+#[cfg(feature = "attachment")]
+#[cfg_attr(docsrs, doc(cfg(feature = "attachment")))]
+/// File download responses
+pub mod attachment;
+
+#[cfg(feature = "auth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "auth")))]
+/// Password hashing and login sessions
+pub mod auth;
+
+#[cfg(feature = "authz")]
+#[cfg_attr(docsrs, doc(cfg(feature = "authz")))]
+/// Permission checks for handlers and scopes
+pub mod authz;
+
+#[cfg(feature = "cache")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cache")))]
+/// Shared key-value cache
+pub mod cache;
+
+#[cfg(feature = "http")]
+#[cfg_attr(docsrs, doc(cfg(feature = "http")))]
+/// Typed `Cache-Control` and `Vary` headers
+pub mod cache_control;
+
+#[cfg(feature = "cbor")]
+#[cfg_attr(docsrs, doc(cfg(feature = "cbor")))]
+/// CBOR request bodies and responses
+pub mod cbor;
+
+#[cfg(feature = "config")]
+#[cfg_attr(docsrs, doc(cfg(feature = "config")))]
+/// Layered configuration loading
+pub mod config;
+
 #[cfg(feature = "cookies")]
 #[cfg_attr(docsrs, doc(cfg(feature = "cookies")))]
 /// Cookie support
 pub mod cookies;
 
+#[cfg(feature = "csv")]
+#[cfg_attr(docsrs, doc(cfg(feature = "csv")))]
+/// Streaming CSV responses
+pub mod csv;
+
+#[cfg(feature = "debug")]
+#[cfg_attr(docsrs, doc(cfg(feature = "debug")))]
+/// Debug error pages for development
+pub mod debug;
+
+#[cfg(feature = "dev")]
+#[cfg_attr(docsrs, doc(cfg(feature = "dev")))]
+/// Live reloading and request recording during development
+pub mod dev;
+
+#[cfg(feature = "email")]
+#[cfg_attr(docsrs, doc(cfg(feature = "email")))]
+/// Email messages and delivery
+pub mod email;
+
+#[cfg(feature = "embed")]
+#[cfg_attr(docsrs, doc(cfg(feature = "embed")))]
+/// Static files embedded into the binary
+pub mod embedded;
+
+#[cfg(feature = "feeds")]
+#[cfg_attr(docsrs, doc(cfg(feature = "feeds")))]
+/// Atom and RSS feeds
+pub mod feeds;
+
+#[cfg(feature = "flags")]
+#[cfg_attr(docsrs, doc(cfg(feature = "flags")))]
+/// Feature flags with percentage rollouts
+pub mod flags;
+
 #[cfg(feature = "key")]
 #[cfg_attr(docsrs, doc(cfg(feature = "key")))]
 /// AEAD encryption/decryption support
@@ -33,6 +115,161 @@ pub mod key;
 /// Form generation and data validation
 pub mod forms;
 
+#[cfg(feature = "geoip")]
+#[cfg_attr(docsrs, doc(cfg(feature = "geoip")))]
+/// Client geolocation with MaxMind databases
+pub mod geoip;
+
+#[cfg(feature = "html")]
+#[cfg_attr(docsrs, doc(cfg(feature = "html")))]
+/// HTML escaping and sanitization
+pub mod html;
+
+#[cfg(feature = "htmx")]
+#[cfg_attr(docsrs, doc(cfg(feature = "htmx")))]
+/// Fragment rendering and response headers for htmx
+pub mod htmx;
+
+#[cfg(feature = "i18n")]
+#[cfg_attr(docsrs, doc(cfg(feature = "i18n")))]
+/// Localization support
+pub mod i18n;
+
+#[cfg(feature = "idempotency")]
+#[cfg_attr(docsrs, doc(cfg(feature = "idempotency")))]
+/// Replaying responses for retried requests
+pub mod idempotency;
+
+#[cfg(feature = "images")]
+#[cfg_attr(docsrs, doc(cfg(feature = "images")))]
+/// Validation and metadata stripping for uploaded images
+pub mod images;
+
+#[cfg(feature = "jsonapi")]
+#[cfg_attr(docsrs, doc(cfg(feature = "jsonapi")))]
+/// JSON:API response documents
+pub mod jsonapi;
+
+#[cfg(feature = "language")]
+#[cfg_attr(docsrs, doc(cfg(feature = "language")))]
+/// `Accept-Language` negotiation
+pub mod language;
+
+#[cfg(feature = "live")]
+#[cfg_attr(docsrs, doc(cfg(feature = "live")))]
+/// Experimental server-rendered live views over WebSockets
+pub mod live;
+
+#[cfg(feature = "longpoll")]
+#[cfg_attr(docsrs, doc(cfg(feature = "longpoll")))]
+/// Long polling
+pub mod longpoll;
+
+#[cfg(feature = "maintenance")]
+#[cfg_attr(docsrs, doc(cfg(feature = "maintenance")))]
+/// Maintenance mode
+pub mod maintenance;
+
+#[cfg(feature = "msgpack")]
+#[cfg_attr(docsrs, doc(cfg(feature = "msgpack")))]
+/// MessagePack request bodies and responses
+pub mod msgpack;
+
+#[cfg(feature = "oauth")]
+#[cfg_attr(docsrs, doc(cfg(feature = "oauth")))]
+/// Sign-in with OAuth 2.0 and OpenID Connect providers
+pub mod oauth;
+
+#[cfg(feature = "pagination")]
+#[cfg_attr(docsrs, doc(cfg(feature = "pagination")))]
+/// Pagination, sorting and filtering for list endpoints
+pub mod pagination;
+
+#[cfg(feature = "parse")]
+#[cfg_attr(docsrs, doc(cfg(feature = "parse")))]
+/// Request body and header parsers usable without a `Context`
+pub mod parse;
+
+#[cfg(feature = "patch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "patch")))]
+/// JSON Merge Patch and JSON Patch request bodies
+pub mod patch;
+
+#[cfg(feature = "precondition")]
+#[cfg_attr(docsrs, doc(cfg(feature = "precondition")))]
+/// Optimistic concurrency with `ETag` and `If-Match`
+pub mod precondition;
+
+#[cfg(feature = "quota")]
+#[cfg_attr(docsrs, doc(cfg(feature = "quota")))]
+/// Per-client request and byte quotas
+pub mod quota;
+
+#[cfg(feature = "report")]
+#[cfg_attr(docsrs, doc(cfg(feature = "report")))]
+/// Catch-all error handling
+pub mod report;
+
+#[cfg(feature = "scan")]
+#[cfg_attr(docsrs, doc(cfg(feature = "scan")))]
+/// Content scanning for uploaded files
+pub mod scan;
+
+#[cfg(feature = "services")]
+#[cfg_attr(docsrs, doc(cfg(feature = "services")))]
+/// Request-scoped dependency injection
+pub mod services;
+
+#[cfg(feature = "signed")]
+#[cfg_attr(docsrs, doc(cfg(feature = "signed")))]
+/// Expiring signed URLs
+pub mod signed;
+
+#[cfg(feature = "singleflight")]
+#[cfg_attr(docsrs, doc(cfg(feature = "singleflight")))]
+/// Coalescing of concurrent identical requests
+pub mod singleflight;
+
+#[cfg(feature = "sitemap")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sitemap")))]
+/// `sitemap.xml` and `robots.txt` generation
+pub mod sitemap;
+
+#[cfg(feature = "sse")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+/// Server-Sent Events
+pub mod sse;
+
+#[cfg(feature = "storage")]
+#[cfg_attr(docsrs, doc(cfg(feature = "storage")))]
+/// Object storage in local directories or S3-compatible services
+pub mod storage;
+
+#[cfg(feature = "timing")]
+#[cfg_attr(docsrs, doc(cfg(feature = "timing")))]
+/// Server-Timing instrumentation
+pub mod timing;
+
+#[cfg(feature = "trace-context")]
+#[cfg_attr(docsrs, doc(cfg(feature = "trace-context")))]
+/// W3C Trace Context propagation
+pub mod trace_context;
+
+#[cfg(feature = "transfer")]
+#[cfg_attr(docsrs, doc(cfg(feature = "transfer")))]
+/// Request and response size accounting
+pub mod transfer;
+
+#[cfg(feature = "turbo")]
+#[cfg_attr(docsrs, doc(cfg(feature = "turbo")))]
+/// Turbo Stream responses for partial page updates
+pub mod turbo;
+
+#[cfg(feature = "user-agent")]
+#[cfg_attr(docsrs, doc(cfg(feature = "user-agent")))]
+/// `User-Agent` header access and classification
+pub mod user_agent;
+
 /// Some helperrs
 pub mod utils;
 
@@ -41,9 +278,40 @@ pub mod utils;
 /// Optional features that require hyper
 pub mod hyper;
 
+#[cfg(feature = "webauthn")]
+#[cfg_attr(docsrs, doc(cfg(feature = "webauthn")))]
+/// Passkey registration and sign-in
+pub mod webauthn;
+
+#[cfg(feature = "webhooks")]
+#[cfg_attr(docsrs, doc(cfg(feature = "webhooks")))]
+/// Receiving and sending webhooks
+pub mod webhooks;
+
+#[cfg(feature = "websocket")]
+#[cfg_attr(docsrs, doc(cfg(feature = "websocket")))]
+/// WebSocket connections upgraded from HTTP/1.1 requests
+pub mod websocket;
+
+#[cfg(feature = "xml")]
+#[cfg_attr(docsrs, doc(cfg(feature = "xml")))]
+/// XML request bodies and responses
+pub mod xml;
+
+#[cfg(feature = "zip")]
+#[cfg_attr(docsrs, doc(cfg(feature = "zip")))]
+/// Streaming ZIP archive responses
+pub mod zip;
+
 #[cfg(feature = "uploads")]
 mod multipart;
 
+#[cfg(any(feature = "cbor", feature = "msgpack"))]
+mod binary;
+
+#[cfg(any(feature = "cbor", feature = "msgpack", feature = "xml"))]
+mod value;
+
 /// Some content type definitions
 pub mod types {
     pub const HTML: &str = "text/html";
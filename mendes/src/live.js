@@ -0,0 +1,94 @@
+(() => {
+  const morph = (from, to) => {
+    const old = Array.from(from.childNodes);
+    const next = Array.from(to.childNodes);
+    next.forEach((node, i) => {
+      const cur = old[i];
+      if (!cur) {
+        from.appendChild(node);
+      } else if (cur.nodeType !== node.nodeType || cur.nodeName !== node.nodeName) {
+        from.replaceChild(node, cur);
+      } else if (cur.nodeType === Node.ELEMENT_NODE) {
+        for (const attr of Array.from(cur.attributes)) {
+          if (!node.hasAttribute(attr.name)) cur.removeAttribute(attr.name);
+        }
+        for (const attr of Array.from(node.attributes)) {
+          if (cur.getAttribute(attr.name) !== attr.value) cur.setAttribute(attr.name, attr.value);
+        }
+        if ((cur.tagName === "INPUT" || cur.tagName === "TEXTAREA") && cur !== document.activeElement) {
+          cur.value = node.value;
+          cur.checked = node.checked;
+        }
+        morph(cur, node);
+      } else if (cur.nodeValue !== node.nodeValue) {
+        cur.nodeValue = node.nodeValue;
+      }
+    });
+    old.slice(next.length).forEach((node) => node.remove());
+  };
+
+  const connect = (root) => {
+    const url = new URL(root.dataset.live, location.href);
+    url.protocol = url.protocol === "https:" ? "wss:" : "ws:";
+    const socket = new WebSocket(url);
+    let html = "";
+    socket.onmessage = (msg) => {
+      const data = JSON.parse(msg.data);
+      if (data.html !== undefined) {
+        html = data.html;
+      } else {
+        const [start, end, text] = data.patch;
+        html = html.slice(0, start) + text + html.slice(end);
+      }
+      const template = document.createElement("template");
+      template.innerHTML = html;
+      morph(root, template.content);
+    };
+    socket.onclose = () => setTimeout(() => connect(root), 1000);
+    root.liveSend = (event, values) => {
+      if (socket.readyState === WebSocket.OPEN) {
+        socket.send(JSON.stringify(Object.assign({}, values, { event })));
+      }
+    };
+  };
+
+  const bind = (root) => {
+    const send = (event, values) => root.liveSend && root.liveSend(event, values);
+    root.addEventListener("click", (e) => {
+      const el = e.target.closest("[live-click]");
+      if (!el || !root.contains(el)) return;
+      e.preventDefault();
+      const values = {};
+      for (const attr of Array.from(el.attributes)) {
+        if (attr.name.startsWith("live-value-")) values[attr.name.slice(11)] = attr.value;
+      }
+      send(el.getAttribute("live-click"), values);
+    });
+    root.addEventListener("submit", (e) => {
+      const form = e.target.closest("[live-submit]");
+      if (!form || !root.contains(form)) return;
+      e.preventDefault();
+      send(form.getAttribute("live-submit"), Object.fromEntries(new FormData(form)));
+    });
+    root.addEventListener("input", (e) => {
+      const el = e.target.closest("[live-change]");
+      if (!el || !root.contains(el)) return;
+      send(el.getAttribute("live-change"), { [el.name || "value"]: el.value });
+    });
+  };
+
+  const start = () => {
+    for (const root of document.querySelectorAll("[data-live]")) {
+      if (root.liveBound) continue;
+      root.liveBound = true;
+      bind(root);
+      connect(root);
+    }
+  };
+
+  if (document.readyState === "loading") {
+    document.addEventListener("DOMContentLoaded", start);
+  } else {
+    start();
+  }
+})();
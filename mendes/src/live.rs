@@ -0,0 +1,221 @@
+use async_trait::async_trait;
+use serde::de::DeserializeOwned;
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::debug;
+
+use crate::html::{Markup, PreEscaped};
+use crate::websocket::{Error, Message, WebSocket};
+
+/// A page fragment whose state lives on the server
+///
+/// The fragment is rendered like any other page, inside a container from `mount()`. The
+/// client script from `script()` then connects the container to a WebSocket served by `run()`,
+/// sends events from bound elements to the handler and patches the fragment with every new
+/// rendering:
+///
+/// ```no_run
+/// # use async_trait::async_trait;
+/// # use mendes::html::{Markup, PreEscaped};
+/// # use mendes::http::Response;
+/// # use mendes::live::{self, LiveHandler};
+/// # use mendes::websocket::WebSocketUpgrade;
+/// # use mendes::{handler, Body, Error};
+/// # use serde::Deserialize;
+/// # fn layout(content: Markup, script: Markup) -> Markup {
+/// #     todo!()
+/// # }
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[derive(Default)]
+/// struct Counter(i64);
+///
+/// #[derive(Deserialize)]
+/// #[serde(tag = "event", rename_all = "lowercase")]
+/// enum CounterEvent {
+///     Add { by: String },
+///     Reset,
+/// }
+///
+/// #[async_trait]
+/// impl LiveHandler for Counter {
+///     type Event = CounterEvent;
+///
+///     fn render(&self) -> Markup {
+///         let mut html = Markup::new();
+///         html.push(PreEscaped("<p>")).push_display(self.0).push(PreEscaped("</p>"));
+///         html.push(PreEscaped(r#"<button live-click="add" live-value-by="1">+1</button>"#));
+///         html.push(PreEscaped(r#"<button live-click="reset">Reset</button>"#));
+///         html
+///     }
+///
+///     async fn handle_event(&mut self, event: CounterEvent) {
+///         match event {
+///             CounterEvent::Add { by } => self.0 += by.parse::<i64>().unwrap_or(0),
+///             CounterEvent::Reset => self.0 = 0,
+///         }
+///     }
+/// }
+///
+/// #[handler(GET)]
+/// async fn counter(app: &App) -> Result<Markup, Error> {
+///     Ok(layout(live::mount("/counter/live", &Counter::default()), live::script()))
+/// }
+///
+/// #[handler(GET)]
+/// async fn counter_live(app: &App, ws: WebSocketUpgrade) -> Result<Response<Body>, Error> {
+///     Ok(ws.on_upgrade(|socket| async move {
+///         let _ = live::run(socket, Counter::default()).await;
+///     }))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Elements inside the container bind events with these attributes:
+///
+/// * `live-click="name"` sends `name` when clicked, with the values of any `live-value-*`
+///   attributes on the element
+/// * `live-submit="name"` on a form sends `name` with the form's fields when submitted
+/// * `live-change="name"` on an input sends `name` with the input's `name` (or `value`) set to
+///   its value on every change
+///
+/// Events arrive as a JSON object with the event name in its `event` field and all values as
+/// strings, which suits an internally tagged enum like the one above.
+///
+/// This module is experimental: the handler only runs in response to client events, and
+/// state is lost when the connection drops, after which the client reconnects to a fresh
+/// handler.
+#[async_trait]
+pub trait LiveHandler: Send {
+    /// Events sent by the client
+    type Event: DeserializeOwned + Send;
+
+    /// Render the current state
+    fn render(&self) -> Markup;
+
+    /// Update the state for an event from the client
+    async fn handle_event(&mut self, event: Self::Event);
+}
+
+/// Run `handler` on `socket` until the client disconnects
+///
+/// Events that don't deserialize into `H::Event` are ignored.
+pub async fn run<H, S>(mut socket: WebSocket<S>, mut handler: H) -> Result<(), Error>
+where
+    H: LiveHandler,
+    S: AsyncRead + AsyncWrite + Unpin + Send,
+{
+    let mut current = handler.render().into_string();
+    socket.send(full(&current).into()).await?;
+
+    while let Some(message) = socket.recv().await? {
+        let text = match message {
+            Message::Text(text) => text,
+            Message::Binary(_) => continue,
+        };
+
+        match serde_json::from_str(&text) {
+            Ok(event) => handler.handle_event(event).await,
+            Err(error) => {
+                debug!(%error, "ignoring invalid live event");
+                continue;
+            }
+        }
+
+        let new = handler.render().into_string();
+        if let Some(patch) = patch(&current, &new) {
+            socket.send(patch.into()).await?;
+        }
+        current = new;
+    }
+
+    Ok(())
+}
+
+/// Render `handler` inside a container that connects to the live socket at `path`
+pub fn mount(path: &str, handler: &impl LiveHandler) -> Markup {
+    let mut html = Markup::new();
+    html.push(PreEscaped("<div data-live=\""))
+        .push(path)
+        .push(PreEscaped("\">"))
+        .push(handler.render())
+        .push(PreEscaped("</div>"));
+    html
+}
+
+/// The script tag connecting live containers to the server
+///
+/// The script can also be served from a file with the contents of `CLIENT`.
+pub fn script() -> Markup {
+    let mut html = Markup::new();
+    html.push(PreEscaped("<script>"))
+        .push(PreEscaped(CLIENT))
+        .push(PreEscaped("</script>"));
+    html
+}
+
+/// The client script connecting live containers to the server
+pub const CLIENT: &str = include_str!("live.js");
+
+/// A message replacing the whole fragment with `html`
+fn full(html: &str) -> String {
+    serde_json::json!({ "html": html }).to_string()
+}
+
+/// A message patching the `old` rendering into `new`, if they differ
+///
+/// The patch replaces the changed range between the common prefix and suffix. Offsets count
+/// UTF-16 code units, like JavaScript strings.
+fn patch(old: &str, new: &str) -> Option<String> {
+    if old == new {
+        return None;
+    }
+
+    let prefix = old
+        .chars()
+        .zip(new.chars())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+    let suffix = old[prefix..]
+        .chars()
+        .rev()
+        .zip(new[prefix..].chars().rev())
+        .take_while(|(a, b)| a == b)
+        .map(|(c, _)| c.len_utf8())
+        .sum::<usize>();
+
+    let start = old[..prefix].encode_utf16().count();
+    let end = start + old[prefix..old.len() - suffix].encode_utf16().count();
+    let text = &new[prefix..new.len() - suffix];
+    Some(serde_json::json!({ "patch": [start, end, text] }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn diff() {
+        assert_eq!(patch("<p>1</p>", "<p>1</p>"), None);
+        assert_eq!(
+            patch("<p>9</p>", "<p>10</p>").as_deref(),
+            Some(r#"{"patch":[3,4,"10"]}"#)
+        );
+        assert_eq!(
+            patch("<p>né 1</p>", "<p>né 2</p>").as_deref(),
+            Some(r#"{"patch":[6,7,"2"]}"#)
+        );
+        assert_eq!(
+            patch("<li>a</li><li>a</li>", "<li>a</li>").as_deref(),
+            Some(r#"{"patch":[10,20,""]}"#)
+        );
+    }
+}
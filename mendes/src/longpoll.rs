@@ -0,0 +1,178 @@
+use std::future::Future;
+use std::time::Duration;
+
+use http::request::Parts;
+use http::{Response, StatusCode};
+use tokio::sync::watch;
+use tokio::time::timeout;
+
+use crate::application::{Application, IntoResponse};
+
+/// The outcome of waiting for a long-poll request
+///
+/// As a response, `Timeout` is sent as `204 No Content`, after which the client should poll
+/// again. A `Ready` value is converted using its own `IntoResponse` implementation.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum LongPoll<T> {
+    Ready(T),
+    Timeout,
+}
+
+impl<T> LongPoll<T> {
+    /// Wait for `future` to complete, for at most `deadline`
+    ///
+    /// Use this to park a request on any kind of notification, like `Notify::notified()`.
+    pub async fn wait(future: impl Future<Output = T>, deadline: Duration) -> Self {
+        match timeout(deadline, future).await {
+            Ok(value) => LongPoll::Ready(value),
+            Err(_) => LongPoll::Timeout,
+        }
+    }
+
+    /// Convert the value if it's ready
+    pub fn map<U>(self, f: impl FnOnce(T) -> U) -> LongPoll<U> {
+        match self {
+            LongPoll::Ready(value) => LongPoll::Ready(f(value)),
+            LongPoll::Timeout => LongPoll::Timeout,
+        }
+    }
+}
+
+impl<A, T> IntoResponse<A> for LongPoll<T>
+where
+    A: Application,
+    A::ResponseBody: Default,
+    T: IntoResponse<A>,
+{
+    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        match self {
+            LongPoll::Ready(value) => value.into_response(app, req),
+            LongPoll::Timeout => {
+                let mut rsp = Response::new(A::ResponseBody::default());
+                *rsp.status_mut() = StatusCode::NO_CONTENT;
+                rsp
+            }
+        }
+    }
+}
+
+/// A value that long-poll clients can wait on for changes
+///
+/// Every published value gets a new, increasing version. Clients pass the version of the last
+/// value they've seen, so that they don't miss values published between two polls:
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use mendes::application::Query;
+/// # use mendes::http::Response;
+/// # use mendes::longpoll::{LongPoll, Topic};
+/// # use mendes::{handler, Body, Error};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # struct Since {
+/// #     since: u64,
+/// # }
+/// # fn json_response(version: u64, status: String) -> Response<Body> {
+/// #     todo!()
+/// # }
+/// # struct App {
+/// #     status: Topic<String>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn status(app: &App, Query(q): Query<Since>) -> Result<LongPoll<Response<Body>>, Error> {
+///     let next = app.status.wait(q.since, Duration::from_secs(30)).await;
+///     Ok(next.map(|(version, status)| json_response(version, status)))
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct Topic<T> {
+    sender: watch::Sender<(u64, T)>,
+}
+
+impl<T: Clone> Topic<T> {
+    /// Create a topic with the `initial` value, which has version 0
+    pub fn new(initial: T) -> Self {
+        Self {
+            sender: watch::channel((0, initial)).0,
+        }
+    }
+
+    /// Publish a new value, waking up all waiting clients
+    ///
+    /// Returns the version of the new value.
+    pub fn publish(&self, value: T) -> u64 {
+        let mut version = 0;
+        self.sender.send_modify(|current| {
+            version = current.0 + 1;
+            *current = (version, value);
+        });
+        version
+    }
+
+    /// The current value and its version
+    pub fn current(&self) -> (u64, T) {
+        self.sender.borrow().clone()
+    }
+
+    /// Wait for a value newer than version `since`, for at most `deadline`
+    ///
+    /// Returns immediately if a newer value has been published already.
+    pub async fn wait(&self, since: u64, deadline: Duration) -> LongPoll<(u64, T)> {
+        let mut receiver = self.sender.subscribe();
+        LongPoll::wait(
+            async move {
+                loop {
+                    {
+                        let current = receiver.borrow_and_update();
+                        if current.0 > since {
+                            return current.clone();
+                        }
+                    }
+
+                    // The sender is owned by `self`, which outlives this future
+                    receiver.changed().await.unwrap();
+                }
+            },
+            deadline,
+        )
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn topic() {
+        let topic = Arc::new(Topic::new("initial"));
+        assert_eq!(topic.current(), (0, "initial"));
+
+        let timeout = topic.wait(0, Duration::from_millis(10)).await;
+        assert_eq!(timeout, LongPoll::Timeout);
+
+        let waiting = tokio::spawn({
+            let topic = topic.clone();
+            async move { topic.wait(0, Duration::from_secs(30)).await }
+        });
+        tokio::task::yield_now().await;
+        assert_eq!(topic.publish("first"), 1);
+        assert_eq!(waiting.await.unwrap(), LongPoll::Ready((1, "first")));
+
+        topic.publish("second");
+        let missed = topic.wait(1, Duration::from_secs(30)).await;
+        assert_eq!(missed, LongPoll::Ready((2, "second")));
+    }
+}
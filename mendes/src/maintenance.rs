@@ -0,0 +1,191 @@
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+use bytes::Bytes;
+use http::header::{CACHE_CONTROL, CONTENT_TYPE, RETRY_AFTER};
+use http::request::Parts;
+use http::{HeaderValue, Response, StatusCode};
+
+/// A switch to take the application down for maintenance at runtime
+///
+/// While enabled, `check()` returns a `503 Service Unavailable` response for all requests
+/// except those for allowlisted paths (think health checks or an admin interface):
+///
+/// ```no_run
+/// # #[cfg(feature = "application")]
+/// # mod example {
+/// # use mendes::http::Response;
+/// # use mendes::maintenance::Maintenance;
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # struct App {
+/// #     maintenance: Maintenance,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     if let Some(rsp) = cx.app.maintenance.check(&cx.req) {
+///         return rsp;
+///     }
+///
+///     route!(match cx.path() {
+///         Some("health") => health,
+///     })
+/// }
+/// # }
+/// # #[handler(GET)]
+/// # async fn health(_: &App) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # fn migrate(app: &App) {
+///
+/// // Elsewhere, for example before running a migration
+/// app.maintenance.enable();
+/// # }
+/// # }
+/// # fn main() {}
+/// ```
+#[derive(Debug)]
+pub struct Maintenance {
+    enabled: AtomicBool,
+    allow: Vec<Cow<'static, str>>,
+    retry_after: Option<Duration>,
+    page: Option<Bytes>,
+}
+
+impl Maintenance {
+    /// Create a switch, which is initially disabled
+    pub fn new() -> Self {
+        Self {
+            enabled: AtomicBool::new(false),
+            allow: Vec::new(),
+            retry_after: None,
+            page: None,
+        }
+    }
+
+    /// Keep serving requests for `path` and paths below it while in maintenance mode
+    pub fn allow(mut self, path: impl Into<Cow<'static, str>>) -> Self {
+        let path = path.into();
+        self.allow.push(match path.strip_suffix('/') {
+            Some(stripped) => Cow::Owned(stripped.to_owned()),
+            None => path,
+        });
+        self
+    }
+
+    /// Send a `Retry-After` header telling clients when to try again
+    pub fn retry_after(mut self, retry_after: Duration) -> Self {
+        self.retry_after = Some(retry_after);
+        self
+    }
+
+    /// Respond with the given HTML page instead of a plain text message
+    pub fn page(mut self, html: impl Into<Bytes>) -> Self {
+        self.page = Some(html.into());
+        self
+    }
+
+    pub fn enable(&self) {
+        self.enabled.store(true, Ordering::Release);
+    }
+
+    pub fn disable(&self) {
+        self.enabled.store(false, Ordering::Release);
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Get the maintenance response for `req`, if it should not be handled
+    pub fn check<B: From<Bytes>>(&self, req: &Parts) -> Option<Response<B>> {
+        if !self.is_enabled() {
+            return None;
+        }
+
+        let path = req.uri.path();
+        let allowed = self
+            .allow
+            .iter()
+            .any(|prefix| match path.strip_prefix(&**prefix) {
+                Some(rest) => rest.is_empty() || rest.starts_with('/'),
+                None => false,
+            });
+        if allowed {
+            return None;
+        }
+
+        let (content_type, body) = match &self.page {
+            Some(page) => ("text/html; charset=utf-8", page.clone()),
+            None => ("text/plain; charset=utf-8", Bytes::from_static(MESSAGE)),
+        };
+
+        let mut rsp = Response::new(B::from(body));
+        *rsp.status_mut() = StatusCode::SERVICE_UNAVAILABLE;
+        let headers = rsp.headers_mut();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+        if let Some(retry_after) = self.retry_after {
+            headers.insert(RETRY_AFTER, HeaderValue::from(retry_after.as_secs()));
+        }
+
+        Some(rsp)
+    }
+}
+
+impl Default for Maintenance {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+const MESSAGE: &[u8] = b"service temporarily unavailable for maintenance";
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::*;
+
+    #[test]
+    fn check() {
+        let maintenance = Maintenance::new()
+            .allow("/health/")
+            .retry_after(Duration::from_secs(120));
+        assert!(maintenance.check::<Bytes>(&parts("/")).is_none());
+
+        maintenance.enable();
+        let rsp = maintenance.check::<Bytes>(&parts("/users")).unwrap();
+        assert_eq!(rsp.status(), StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(rsp.headers()[RETRY_AFTER], "120");
+        assert_eq!(rsp.headers()[CONTENT_TYPE], "text/plain; charset=utf-8");
+        assert_eq!(rsp.body(), MESSAGE);
+
+        assert!(maintenance.check::<Bytes>(&parts("/health")).is_none());
+        assert!(maintenance.check::<Bytes>(&parts("/health/db")).is_none());
+        assert!(maintenance.check::<Bytes>(&parts("/healthz")).is_some());
+
+        maintenance.disable();
+        assert!(maintenance.check::<Bytes>(&parts("/users")).is_none());
+
+        let maintenance = Maintenance::new().page("<h1>Back soon</h1>");
+        maintenance.enable();
+        let rsp = maintenance.check::<Bytes>(&parts("/")).unwrap();
+        assert_eq!(rsp.headers()[CONTENT_TYPE], "text/html; charset=utf-8");
+        assert!(!rsp.headers().contains_key(RETRY_AFTER));
+        assert_eq!(rsp.body(), "<h1>Back soon</h1>");
+    }
+
+    fn parts(path: &str) -> Parts {
+        Request::builder()
+            .uri(path)
+            .body(())
+            .unwrap()
+            .into_parts()
+            .0
+    }
+}
@@ -0,0 +1,779 @@
+use std::error::Error as StdError;
+use std::fmt::Display;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{Response, StatusCode};
+use http_body::Body as HttpBody;
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Serialize};
+use thiserror::Error;
+
+use crate::application::{self, check_content_type, Application, ErrorKind, FromContextAsync};
+use crate::application::{read_body, IntoResponse, PathState};
+use crate::binary::{self, Format};
+use crate::utils::negotiate;
+use crate::value::{Deserializer, Value};
+
+/// A MessagePack request body or response
+///
+/// As an extractor, `MsgPack<T>` accepts bodies of up to `MAX_LEN` bytes with an
+/// `application/msgpack` content type, or one of the older `application/x-msgpack` and
+/// `application/vnd.msgpack`. As a response, it is sent as whichever of those the request's
+/// `Accept` header prefers, or rejected with a `NotAcceptable` error if it accepts none:
+///
+/// ```no_run
+/// # use async_trait::async_trait;
+/// # use mendes::http::Response;
+/// # use mendes::msgpack::MsgPack;
+/// # use mendes::{handler, Application, Body, Context, Error};
+/// # use serde::{Deserialize, Serialize};
+/// # struct App {}
+/// # #[async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: Context<Self>) -> Response<Body> { todo!() }
+/// # }
+/// #[derive(Deserialize, Serialize)]
+/// struct Order {
+///     id: u64,
+///     items: Vec<String>,
+/// }
+///
+/// #[handler(POST)]
+/// async fn submit(_: &App, #[async_extract] order: MsgPack<Order>) -> Result<MsgPack<Order>, Error> {
+///     let MsgPack(mut order) = order;
+///     order.items.push("receipt".to_owned());
+///     Ok(MsgPack(order))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Structs are maps keyed by field name, like in JSON. Extension types are not supported.
+pub struct MsgPack<T>(pub T);
+
+#[async_trait]
+impl<'a, A, T> FromContextAsync<'a, A> for MsgPack<T>
+where
+    A: Application + Sync,
+    A::RequestBody: HttpBody + Send,
+    <A::RequestBody as HttpBody>::Data: Send,
+    <A::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
+    T: DeserializeOwned,
+{
+    async fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let body = match body.take() {
+            Some(body) => body,
+            None => panic!("attempted to retrieve body twice"),
+        };
+
+        check_content_type(req, MEDIA_TYPES).map_err(|e| state.reject::<A>(e, req))?;
+        let bytes = read_body(body, MAX_LEN)
+            .await
+            .map_err(|e| state.reject::<A>(e, req))?;
+        match from_slice(&bytes) {
+            Ok(value) => Ok(Self(value)),
+            Err(e) => Err(state.reject::<A>(e.into(), req)),
+        }
+    }
+}
+
+impl<A: Application, T: Serialize> IntoResponse<A> for MsgPack<T>
+where
+    A::ResponseBody: From<String> + From<Vec<u8>>,
+{
+    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        let media_type = match negotiate(req, MEDIA_TYPES) {
+            Some(media_type) => media_type,
+            None => {
+                return application::Error::from(ErrorKind::NotAcceptable).into_response(app, req)
+            }
+        };
+
+        match to_vec(&self.0) {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, media_type)
+                .body(body.into())
+                .unwrap(),
+            Err(e) => application::Error::internal(e).into_response(app, req),
+        }
+    }
+}
+
+/// Deserialize an instance of `T` from MessagePack
+pub fn from_slice<T: DeserializeOwned>(input: &[u8]) -> Result<T, Error> {
+    let mut parser = Parser { input, pos: 0 };
+    let value = parser.value(0)?;
+    if parser.pos < input.len() {
+        return Err(parser.error("trailing bytes"));
+    }
+    T::deserialize(Deserializer::new(value))
+}
+
+/// Serialize `value` as MessagePack
+///
+/// Integers and lengths use the smallest encoding that fits.
+pub fn to_vec<T: Serialize + ?Sized>(value: &T) -> Result<Vec<u8>, Error> {
+    binary::to_vec::<MsgPackFormat, _>(value)
+}
+
+struct Parser<'a> {
+    input: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn value(&mut self, depth: usize) -> Result<Value, Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("too deeply nested"));
+        }
+
+        let marker = self.take(1)?[0];
+        Ok(match marker {
+            0x00..=0x7f => Value::Int(marker.into()),
+            0x80..=0x8f => self.map(usize::from(marker & 0x0f), depth)?,
+            0x90..=0x9f => self.seq(usize::from(marker & 0x0f), depth)?,
+            0xa0..=0xbf => self.str(usize::from(marker & 0x1f))?,
+            0xc0 => Value::Null,
+            0xc2 => Value::Bool(false),
+            0xc3 => Value::Bool(true),
+            0xc4..=0xc6 => {
+                let len = self.len(1 << (marker - 0xc4))?;
+                Value::Bytes(self.take(len)?.to_vec())
+            }
+            0xca => Value::Float(f32::from_bits(self.uint(4)? as u32).into()),
+            0xcb => Value::Float(f64::from_bits(self.uint(8)?)),
+            0xcc..=0xcf => Value::Int(self.uint(1 << (marker - 0xcc))?.into()),
+            0xd0..=0xd3 => {
+                let size = 1 << (marker - 0xd0);
+                // Sign-extend from the size of the integer
+                let shift = 64 - 8 * size;
+                Value::Int(((self.uint(size)? << shift) as i64 >> shift).into())
+            }
+            0xd9..=0xdb => {
+                let len = self.len(1 << (marker - 0xd9))?;
+                self.str(len)?
+            }
+            0xdc | 0xdd => {
+                let len = self.len(2 << (marker - 0xdc))?;
+                self.seq(len, depth)?
+            }
+            0xde | 0xdf => {
+                let len = self.len(2 << (marker - 0xde))?;
+                self.map(len, depth)?
+            }
+            0xe0..=0xff => Value::Int((marker as i8).into()),
+            0xc7..=0xc9 | 0xd4..=0xd8 => return Err(self.error("unsupported extension type")),
+            0xc1 => return Err(self.error("invalid marker")),
+        })
+    }
+
+    fn seq(&mut self, len: usize, depth: usize) -> Result<Value, Error> {
+        // Every value takes at least a byte, which bounds the allocation
+        let mut items = Vec::with_capacity(len.min(self.input.len() - self.pos));
+        for _ in 0..len {
+            items.push(self.value(depth + 1)?);
+        }
+        Ok(Value::Seq(items))
+    }
+
+    fn map(&mut self, len: usize, depth: usize) -> Result<Value, Error> {
+        let mut entries = Vec::with_capacity(len.min(self.input.len() - self.pos));
+        for _ in 0..len {
+            entries.push((self.value(depth + 1)?, self.value(depth + 1)?));
+        }
+        Ok(Value::Map(entries))
+    }
+
+    fn str(&mut self, len: usize) -> Result<Value, Error> {
+        let start = self.pos;
+        match std::str::from_utf8(self.take(len)?) {
+            Ok(s) => Ok(Value::Str(s.to_owned())),
+            Err(e) => Err(Error::Syntax(start + e.valid_up_to(), "invalid UTF-8")),
+        }
+    }
+
+    fn len(&mut self, size: usize) -> Result<usize, Error> {
+        let len = self.uint(size)?;
+        usize::try_from(len).map_err(|_| self.error("length out of range"))
+    }
+
+    /// Read a big-endian unsigned integer of `size` bytes
+    fn uint(&mut self, size: usize) -> Result<u64, Error> {
+        let bytes = self.take(size)?;
+        Ok(bytes.iter().fold(0, |acc, &b| acc << 8 | u64::from(b)))
+    }
+
+    fn take(&mut self, len: usize) -> Result<&'a [u8], Error> {
+        match self.input.get(self.pos..).and_then(|rest| rest.get(..len)) {
+            Some(bytes) => {
+                self.pos += len;
+                Ok(bytes)
+            }
+            None => Err(self.error("unexpected end of input")),
+        }
+    }
+
+    fn error(&self, msg: &'static str) -> Error {
+        Error::Syntax(self.pos, msg)
+    }
+}
+
+struct MsgPackFormat;
+
+impl MsgPackFormat {
+    /// Write a length using the `fix` marker if it fits in `fix_bits`, or the smallest of the
+    /// `markers` for 8, 16 and 32-bit lengths (where 0 means there is no 8-bit encoding)
+    fn len(out: &mut Vec<u8>, len: usize, fix: u8, fix_bits: u32, markers: [u8; 3]) {
+        match len {
+            _ if len < 1 << fix_bits => out.push(fix | len as u8),
+            0..=0xff if markers[0] != 0 => out.extend([markers[0], len as u8]),
+            0..=0xffff => {
+                out.push(markers[1]);
+                out.extend((len as u16).to_be_bytes());
+            }
+            _ => {
+                out.push(markers[2]);
+                out.extend((len as u32).to_be_bytes());
+            }
+        }
+    }
+
+    fn check_len(len: usize) -> Result<(), Error> {
+        match u32::try_from(len) {
+            Ok(_) => Ok(()),
+            Err(_) => Err(ser::Error::custom("length out of range")),
+        }
+    }
+}
+
+impl Format for MsgPackFormat {
+    type Error = Error;
+
+    fn null(out: &mut Vec<u8>) {
+        out.push(0xc0);
+    }
+
+    fn bool(out: &mut Vec<u8>, v: bool) {
+        out.push(if v { 0xc3 } else { 0xc2 });
+    }
+
+    fn int(out: &mut Vec<u8>, v: i128) -> Result<(), Error> {
+        if let Ok(v) = u64::try_from(v) {
+            match v {
+                0..=0x7f => out.push(v as u8),
+                0x80..=0xff => out.extend([0xcc, v as u8]),
+                0x100..=0xffff => {
+                    out.push(0xcd);
+                    out.extend((v as u16).to_be_bytes());
+                }
+                0x1_0000..=0xffff_ffff => {
+                    out.push(0xce);
+                    out.extend((v as u32).to_be_bytes());
+                }
+                _ => {
+                    out.push(0xcf);
+                    out.extend(v.to_be_bytes());
+                }
+            }
+        } else if let Ok(v) = i64::try_from(v) {
+            match v {
+                -32..=-1 => out.push(v as u8),
+                -0x80..=-33 => out.extend([0xd0, v as u8]),
+                -0x8000..=-0x81 => {
+                    out.push(0xd1);
+                    out.extend((v as i16).to_be_bytes());
+                }
+                -0x8000_0000..=-0x8001 => {
+                    out.push(0xd2);
+                    out.extend((v as i32).to_be_bytes());
+                }
+                _ => {
+                    out.push(0xd3);
+                    out.extend(v.to_be_bytes());
+                }
+            }
+        } else {
+            return Err(ser::Error::custom("integer out of range"));
+        }
+        Ok(())
+    }
+
+    fn f32(out: &mut Vec<u8>, v: f32) {
+        out.push(0xca);
+        out.extend(v.to_be_bytes());
+    }
+
+    fn f64(out: &mut Vec<u8>, v: f64) {
+        out.push(0xcb);
+        out.extend(v.to_be_bytes());
+    }
+
+    fn str(out: &mut Vec<u8>, v: &str) -> Result<(), Error> {
+        Self::check_len(v.len())?;
+        Self::len(out, v.len(), 0xa0, 5, [0xd9, 0xda, 0xdb]);
+        out.extend_from_slice(v.as_bytes());
+        Ok(())
+    }
+
+    fn bytes(out: &mut Vec<u8>, v: &[u8]) -> Result<(), Error> {
+        Self::check_len(v.len())?;
+        Self::len(out, v.len(), 0, 0, [0xc4, 0xc5, 0xc6]);
+        out.extend_from_slice(v);
+        Ok(())
+    }
+
+    fn seq(out: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+        Self::check_len(len)?;
+        Self::len(out, len, 0x90, 4, [0, 0xdc, 0xdd]);
+        Ok(())
+    }
+
+    fn map(out: &mut Vec<u8>, len: usize) -> Result<(), Error> {
+        Self::check_len(len)?;
+        Self::len(out, len, 0x80, 4, [0, 0xde, 0xdf]);
+        Ok(())
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid MessagePack at byte {0}: {1}")]
+    Syntax(usize, &'static str),
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// The maximum size of MessagePack request bodies, in bytes
+pub const MAX_LEN: usize = 1024 * 1024;
+
+const MEDIA_TYPES: &[&str] = &[
+    "application/msgpack",
+    "application/x-msgpack",
+    "application/vnd.msgpack",
+];
+const MAX_DEPTH: usize = 64;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Order {
+        id: u64,
+        note: Option<String>,
+        items: Vec<(String, f64)>,
+        status: Status,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Status {
+        Open,
+        Shipped { carrier: String },
+    }
+
+    #[test]
+    fn round_trip() {
+        let order = Order {
+            id: 300,
+            note: None,
+            items: vec![("tea".to_owned(), 2.5)],
+            status: Status::Shipped {
+                carrier: "post".to_owned(),
+            },
+        };
+
+        let bytes = to_vec(&order).unwrap();
+        assert_eq!(
+            bytes,
+            [
+                &[0x84, 0xa2][..],
+                b"id",
+                &[0xcd, 0x01, 0x2c, 0xa4],
+                b"note",
+                &[0xc0, 0xa5],
+                b"items",
+                &[0x91, 0x92, 0xa3],
+                b"tea",
+                &[0xcb, 0x40, 0x04, 0, 0, 0, 0, 0, 0, 0xa6],
+                b"status",
+                &[0x81, 0xa7],
+                b"Shipped",
+                &[0x81, 0xa7],
+                b"carrier",
+                &[0xa4],
+                b"post",
+            ]
+            .concat()
+        );
+        assert_eq!(from_slice::<Order>(&bytes).unwrap(), order);
+    }
+
+    #[test]
+    fn integers() {
+        for (v, encoded) in [
+            (0, &[0x00][..]),
+            (-1, &[0xff]),
+            (-32, &[0xe0]),
+            (-33, &[0xd0, 0xdf]),
+            (200, &[0xcc, 0xc8]),
+            (-1000, &[0xd1, 0xfc, 0x18]),
+            (70000, &[0xce, 0x00, 0x01, 0x11, 0x70]),
+            (i64::MIN, &[0xd3, 0x80, 0, 0, 0, 0, 0, 0, 0]),
+        ] {
+            assert_eq!(to_vec(&v).unwrap(), encoded, "{v}");
+            assert_eq!(from_slice::<i64>(encoded).unwrap(), v);
+        }
+
+        assert_eq!(
+            from_slice::<u64>(&[0xcf; 9]).unwrap(),
+            0xcfcf_cfcf_cfcf_cfcf
+        );
+        assert!(from_slice::<u8>(&[0xcd, 0x01, 0x00]).is_err());
+        assert!(to_vec(&u128::MAX).is_err());
+    }
+
+    #[test]
+    fn parse() {
+        // A map with a str8 key, an array16, a float32 and an unknown field
+        let mut input = vec![0x82, 0xd9, 0x01, b'a', 0xdc, 0x00, 0x02, 0xca];
+        input.extend(1.5f32.to_be_bytes());
+        input.extend([0xc3, 0xa1, b'b', 0xc0]);
+        let map = from_slice::<BTreeMap<String, Option<(f32, bool)>>>(&input).unwrap();
+        assert_eq!(map["a"], Some((1.5, true)));
+        assert_eq!(map["b"], None);
+        assert_eq!(from_slice::<String>(&[0xc4, 2, b'h', b'i']).unwrap(), "hi");
+
+        for invalid in [
+            &[][..],
+            &[0xc1],
+            &[0xa2, b'a'],
+            &[0xa1, 0xff],
+            &[0xd4, 0x01, 0x00],
+            &[0xc0, 0xc0],
+            &[0xdd, 0xff, 0xff, 0xff, 0xff],
+        ] {
+            assert!(
+                matches!(from_slice::<()>(invalid), Err(Error::Syntax(..))),
+                "{invalid:?}"
+            );
+        }
+
+        let deep = [0x91; 100];
+        assert!(from_slice::<Vec<()>>(&deep).is_err());
+    }
+
+    #[test]
+    fn data_model() {
+        let value = DataModel::new();
+        let bytes = to_vec(&value).unwrap();
+        assert_eq!(from_slice::<DataModel>(&bytes).unwrap(), value);
+
+        for (value, encoded) in [
+            (Shape::Point, &[0xa5, b'P', b'o', b'i', b'n', b't'][..]),
+            (
+                Shape::Circle(-1),
+                &[0x81, 0xa6, b'C', b'i', b'r', b'c', b'l', b'e', 0xff],
+            ),
+            (
+                Shape::Line(1, 2),
+                &[0x81, 0xa4, b'L', b'i', b'n', b'e', 0x92, 0x01, 0x02],
+            ),
+            (
+                Shape::Rect { w: 3 },
+                &[0x81, 0xa4, b'R', b'e', b'c', b't', 0x81, 0xa1, b'w', 0x03],
+            ),
+        ] {
+            assert_eq!(to_vec(&value).unwrap(), encoded, "{value:?}");
+            assert_eq!(from_slice::<Shape>(encoded).unwrap(), value);
+        }
+        assert!(from_slice::<Shape>(&[0x81, 0xa3, b'S', b'u', b'n', 0xc0]).is_err());
+
+        assert_eq!(
+            to_vec(&Bytes(vec![1, 2])).unwrap(),
+            [0xc4, 0x02, 0x01, 0x02]
+        );
+        assert_eq!(to_vec(&'\u{e9}').unwrap(), [0xa2, 0xc3, 0xa9]);
+        assert_eq!(to_vec(&()).unwrap(), [0xc0]);
+        assert_eq!(to_vec(&1.5f32).unwrap(), [0xca, 0x3f, 0xc0, 0, 0]);
+
+        // Sequences of unknown length are buffered to write the length first
+        let unsized_seq = Unsized(vec![1, 2, 3]);
+        assert_eq!(to_vec(&unsized_seq).unwrap(), [0x93, 0x01, 0x02, 0x03]);
+
+        // Lengths at the boundaries between encodings
+        for (len, header) in [
+            (31, &[0xbf][..]),
+            (32, &[0xd9, 0x20]),
+            (256, &[0xda, 0x01, 0x00]),
+            (65536, &[0xdb, 0x00, 0x01, 0x00, 0x00]),
+        ] {
+            let bytes = to_vec(&"a".repeat(len)).unwrap();
+            assert_eq!(&bytes[..header.len()], header, "{len}");
+            assert_eq!(from_slice::<String>(&bytes).unwrap().len(), len);
+        }
+
+        for (len, header) in [(15, &[0x9f][..]), (16, &[0xdc, 0x00, 0x10])] {
+            let bytes = to_vec(&vec![0u8; len]).unwrap();
+            assert_eq!(&bytes[..header.len()], header, "{len}");
+            assert_eq!(from_slice::<Vec<u8>>(&bytes).unwrap().len(), len);
+        }
+    }
+
+    #[test]
+    fn lengths() {
+        for truncated in [
+            // Missing length bytes, for each size of length
+            &[0xd9][..],
+            &[0xda, 0x00],
+            &[0xdb, 0x00, 0x00, 0x00],
+            &[0xc4],
+            &[0xc5, 0x00],
+            &[0xc6, 0x00, 0x00, 0x00],
+            &[0xdc, 0x00],
+            &[0xdd, 0x00, 0x00, 0x00],
+            &[0xde, 0x00],
+            &[0xdf, 0x00, 0x00, 0x00],
+            // Fewer bytes or items than the length says
+            &[0xa3, b'a', b'b'],
+            &[0xc4, 0x02, 0x00],
+            &[0x93, 0x01, 0x02],
+            &[0x82, 0x01, 0x02, 0x03],
+            &[0xcd, 0x01],
+            &[0xd2, 0xff, 0xff],
+            &[0xcb, 0, 0, 0, 0],
+        ] {
+            assert!(
+                matches!(
+                    from_slice::<Ignored>(truncated),
+                    Err(Error::Syntax(_, "unexpected end of input"))
+                ),
+                "{truncated:?}"
+            );
+        }
+
+        // Lengths far beyond the input must fail without allocating for them
+        for oversized in [
+            &[0xc6, 0xff, 0xff, 0xff, 0xff, 0x00][..],
+            &[0xdb, 0xff, 0xff, 0xff, 0xff, b'a'],
+            &[0xdd, 0xff, 0xff, 0xff, 0xff, 0x00],
+            &[0xdf, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00],
+            &[0x91, 0xdd, 0xff, 0xff, 0xff, 0xff],
+        ] {
+            assert!(
+                matches!(
+                    from_slice::<Ignored>(oversized),
+                    Err(Error::Syntax(_, "unexpected end of input"))
+                ),
+                "{oversized:?}"
+            );
+        }
+
+        assert!(matches!(
+            from_slice::<String>(&[0xa3, b'a', 0xc3, 0x28]),
+            Err(Error::Syntax(2, "invalid UTF-8"))
+        ));
+        assert!(matches!(
+            from_slice::<u8>(&[0x01, 0x02]),
+            Err(Error::Syntax(1, "trailing bytes"))
+        ));
+        for extension in [&[0xc7, 0x01, 0x01, 0x00][..], &[0xd8], &[0xc9]] {
+            assert!(
+                matches!(
+                    from_slice::<Ignored>(extension),
+                    Err(Error::Syntax(1, "unsupported extension type"))
+                ),
+                "{extension:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn keys() {
+        let mut ints = BTreeMap::new();
+        ints.insert(-10i64, "a".to_owned());
+        ints.insert(500, "b".to_owned());
+        let bytes = to_vec(&ints).unwrap();
+        assert_eq!(
+            bytes,
+            [0x82, 0xf6, 0xa1, b'a', 0xcd, 0x01, 0xf4, 0xa1, b'b']
+        );
+        assert_eq!(from_slice::<BTreeMap<i64, String>>(&bytes).unwrap(), ints);
+
+        let mut composite = BTreeMap::new();
+        composite.insert((true, 'x'), 1u8);
+        composite.insert((false, 'y'), 2);
+        let bytes = to_vec(&composite).unwrap();
+        assert_eq!(
+            from_slice::<BTreeMap<(bool, char), u8>>(&bytes).unwrap(),
+            composite
+        );
+
+        let mut units = BTreeMap::new();
+        units.insert(Shape::Point, 1u8);
+        units.insert(Shape::Circle(2), 2);
+        let bytes = to_vec(&units).unwrap();
+        assert_eq!(from_slice::<BTreeMap<Shape, u8>>(&bytes).unwrap(), units);
+
+        // Keys of another type than the map's
+        assert!(from_slice::<BTreeMap<String, u8>>(&[0x81, 0x01, 0x02]).is_err());
+        assert!(from_slice::<BTreeMap<u8, u8>>(&[0x81, 0xa1, b'a', 0x02]).is_err());
+        assert!(from_slice::<BTreeMap<u8, u8>>(&[0x81, 0xc0, 0x02]).is_err());
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct DataModel {
+        bool: bool,
+        i8: i8,
+        i16: i16,
+        i32: i32,
+        i64: i64,
+        i128: i128,
+        u8: u8,
+        u16: u16,
+        u32: u32,
+        u64: u64,
+        u128: u128,
+        f32: f32,
+        f64: f64,
+        char: char,
+        string: String,
+        bytes: Bytes,
+        none: Option<u8>,
+        some: Option<String>,
+        unit: (),
+        unit_struct: UnitStruct,
+        newtype: Newtype,
+        tuple: (u8, String, bool),
+        tuple_struct: TupleStruct,
+        seq: Vec<Vec<u16>>,
+        map: BTreeMap<String, Option<f64>>,
+        shapes: Vec<Shape>,
+    }
+
+    impl DataModel {
+        fn new() -> Self {
+            let mut map = BTreeMap::new();
+            map.insert("e".to_owned(), Some(-1e300));
+            map.insert("none".to_owned(), None);
+
+            Self {
+                bool: false,
+                i8: i8::MIN,
+                i16: i16::MIN,
+                i32: i32::MIN,
+                i64: i64::MIN,
+                i128: i64::MIN.into(),
+                u8: u8::MAX,
+                u16: u16::MAX,
+                u32: u32::MAX,
+                u64: u64::MAX,
+                u128: u64::MAX.into(),
+                f32: -0.1,
+                f64: f64::MIN_POSITIVE,
+                char: '\u{1F600}',
+                string: "h\u{e9}llo\0".to_owned(),
+                bytes: Bytes((0..=255).collect()),
+                none: None,
+                some: Some(String::new()),
+                unit: (),
+                unit_struct: UnitStruct,
+                newtype: Newtype(-7),
+                tuple: (1, "two".to_owned(), true),
+                tuple_struct: TupleStruct(1.5, 'q'),
+                seq: vec![vec![], vec![1, 300, 70]],
+                map,
+                shapes: vec![
+                    Shape::Point,
+                    Shape::Circle(-100),
+                    Shape::Line(0, 1 << 40),
+                    Shape::Rect { w: 9 },
+                ],
+            }
+        }
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct UnitStruct;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Newtype(i32);
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct TupleStruct(f32, char);
+
+    #[derive(Debug, Deserialize, Eq, Ord, PartialEq, PartialOrd, Serialize)]
+    enum Shape {
+        Point,
+        Circle(i8),
+        Line(u8, u64),
+        Rect { w: u16 },
+    }
+
+    /// A byte string, which `Vec<u8>` would serialize as an array
+    #[derive(Debug, PartialEq)]
+    struct Bytes(Vec<u8>);
+
+    impl Serialize for Bytes {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Bytes {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            struct BytesVisitor;
+
+            impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+                type Value = Bytes;
+
+                fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                    f.write_str("a byte string")
+                }
+
+                fn visit_byte_buf<E>(self, v: Vec<u8>) -> Result<Bytes, E> {
+                    Ok(Bytes(v))
+                }
+            }
+
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+
+    /// A sequence that doesn't know its length up front
+    struct Unsized(Vec<u8>);
+
+    impl Serialize for Unsized {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            serializer.collect_seq(self.0.iter().filter(|_| true))
+        }
+    }
+
+    type Ignored = serde::de::IgnoredAny;
+}
@@ -12,25 +12,102 @@ pub fn from_form_data<'a, T: Deserialize<'a>>(
     headers: &HeaderMap,
     input: &'a [u8],
 ) -> std::result::Result<T, Error> {
-    let ctype = headers
-        .get("content-type")
-        .ok_or_else(|| Error::custom("content-type header not found"))?
-        .as_bytes();
-    let split =
-        memmem::find(ctype, b"; boundary=").ok_or_else(|| Error::custom("boundary not found"))?;
-
-    let mut boundary = Vec::with_capacity(2 + ctype.len() - split - 11);
-    boundary.extend(b"--");
-    boundary.extend(&ctype[split + 11..]);
+    from_content_type(content_type(headers)?, input)
+}
 
+/// Deserialize `input` with the boundary from the `Content-Type` header value `ctype`
+pub(crate) fn from_content_type<'a, T: Deserialize<'a>>(
+    ctype: &[u8],
+    input: &'a [u8],
+) -> Result<T> {
     let mut deserializer = Deserializer {
         input,
-        boundary,
+        boundary: boundary(ctype)?,
         state: None,
     };
     T::deserialize(&mut deserializer)
 }
 
+#[cfg(feature = "parse")]
+/// The number of parts in `input`, counting the delimiters for the boundary from `ctype`
+pub(crate) fn count_parts(ctype: &[u8], input: &[u8]) -> Result<usize> {
+    let boundary = boundary(ctype)?;
+    Ok(memmem::find_iter(input, &boundary)
+        .count()
+        .saturating_sub(1))
+}
+
+#[cfg(feature = "scan")]
+/// The file parts in `input`, with their field names
+pub(crate) fn files<'a>(
+    headers: &HeaderMap,
+    mut input: &'a [u8],
+) -> Result<Vec<(&'a str, File<'a>)>> {
+    let boundary = boundary(content_type(headers)?)?;
+    let split_len = boundary.len();
+    let mut files = Vec::new();
+    while input.starts_with(&boundary) {
+        match input.get(split_len..split_len + 2) {
+            Some(b"\r\n") => {}
+            Some(b"--") => break,
+            _ => return Err(Error::custom("invalid boundary delimiter")),
+        }
+
+        let (len, part) = Part::from_bytes(&input[split_len + 2..], &boundary)?;
+        if let Part::Blob {
+            name,
+            filename,
+            ctype,
+            data,
+        } = part
+        {
+            files.push((
+                name,
+                File {
+                    ctype,
+                    filename,
+                    data,
+                },
+            ));
+        }
+        input = &input[split_len + 2 + len..];
+    }
+
+    Ok(files)
+}
+
+fn content_type(headers: &HeaderMap) -> Result<&[u8]> {
+    match headers.get("content-type") {
+        Some(ctype) => Ok(ctype.as_bytes()),
+        None => Err(Error::custom("content-type header not found")),
+    }
+}
+
+/// The delimiter for parts: two dashes and the `boundary` parameter from `ctype`
+fn boundary(ctype: &[u8]) -> Result<Vec<u8>> {
+    let split =
+        memmem::find(ctype, b"boundary=").ok_or_else(|| Error::custom("boundary not found"))?;
+    let value = &ctype[split + 9..];
+    let value = match value.iter().position(|&b| b == b';') {
+        Some(end) => &value[..end],
+        None => value,
+    };
+    let value = match value {
+        [b'"', inner @ .., b'"'] => inner,
+        _ => value,
+    };
+
+    // RFC 2046 limits boundaries to 70 characters
+    if value.is_empty() || value.len() > 70 {
+        return Err(Error::custom("invalid boundary length"));
+    }
+
+    let mut boundary = Vec::with_capacity(2 + value.len());
+    boundary.extend(b"--");
+    boundary.extend(value);
+    Ok(boundary)
+}
+
 macro_rules! parse_value_type {
     ($($ty:ident => ($visit_method:ident, $deserializer_method:ident),)*) => {
         $(
@@ -44,7 +121,7 @@ macro_rules! parse_value_type {
                         $ty::from_str(s).map_err(|_| Error::custom("unable to convert str to $ty"))?,
                     )
                 } else {
-                    unreachable!()
+                    Err(Error::custom("expected a text field"))
                 }
             }
         )*
@@ -57,14 +134,14 @@ pub struct Deserializer<'de> {
     state: Option<(State, Part<'de>)>,
 }
 
-impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
+impl<'de> serde::de::Deserializer<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn deserialize_any<V>(self, _: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::custom("self-describing types are not supported"))
     }
 
     fn deserialize_identifier<V>(self, visitor: V) -> Result<V::Value>
@@ -81,22 +158,26 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
             }
             Some((State::Filename, part)) => match part {
                 Part::Blob { .. } => visitor.visit_borrowed_str("filename"),
-                Part::Text { .. } => unreachable!(),
+                Part::Text { .. } => Err(Error::custom("unexpected text field")),
             },
             Some((State::Type, _)) => visitor.visit_borrowed_str("type"),
             Some((State::Data, part)) => match part {
                 Part::Blob { .. } => visitor.visit_borrowed_str("data"),
                 Part::Text { .. } => self.deserialize_str(visitor),
             },
-            _ => unreachable!(),
+            _ => Err(Error::custom("unexpected field")),
         }
     }
 
-    fn deserialize_ignored_any<V>(self, _: V) -> Result<V::Value>
+    fn deserialize_ignored_any<V>(self, visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        // Skip the rest of the part, including the fields of a file
+        if let Some((_, part)) = self.state.take() {
+            self.state = Some((State::Ignored, part));
+        }
+        visitor.visit_unit()
     }
 
     parse_value_type! {
@@ -119,12 +200,12 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
     {
         if let Some((State::Data, Part::Text { data, .. })) = self.state {
             let s = str::from_utf8(data)
-                .map_err(|_| Error::custom("invalid input while UTF-8 decoding for i32"))?;
+                .map_err(|_| Error::custom("invalid input while UTF-8 decoding for char"))?;
             visitor.visit_char(
-                char::from_str(s).map_err(|_| Error::custom("unable to convert str to $ty"))?,
+                char::from_str(s).map_err(|_| Error::custom("unable to convert str to char"))?,
             )
         } else {
-            unreachable!()
+            Err(Error::custom("expected a text field"))
         }
     }
 
@@ -133,13 +214,19 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         V: Visitor<'de>,
     {
         match self.state.as_ref() {
-            Some((State::Name, _)) => unreachable!(),
-            Some((State::Filename, Part::Blob { filename, .. })) => {
-                visitor.visit_borrowed_str(filename.as_ref().unwrap())
-            }
-            Some((State::Type, Part::Blob { ctype, .. })) => {
-                visitor.visit_borrowed_str(ctype.as_ref().unwrap())
-            }
+            Some((
+                State::Filename,
+                Part::Blob {
+                    filename: Some(filename),
+                    ..
+                },
+            )) => visitor.visit_borrowed_str(filename),
+            Some((
+                State::Type,
+                Part::Blob {
+                    ctype: Some(ctype), ..
+                },
+            )) => visitor.visit_borrowed_str(ctype),
             Some((State::Data, part)) => {
                 let data = match part {
                     Part::Blob { data, .. } => data,
@@ -149,7 +236,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
                     .map_err(|_| Error::custom("error while decoding str from UTF-8"))?;
                 visitor.visit_borrowed_str(data)
             }
-            _ => unreachable!(),
+            _ => Err(Error::custom("expected a string")),
         }
     }
 
@@ -167,7 +254,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         let data = match self.state.as_ref() {
             Some((_, Part::Blob { data, .. })) => data,
             Some((_, Part::Text { data, .. })) => data,
-            None => unreachable!(),
+            None => return Err(Error::custom("expected a field")),
         };
         visitor.visit_borrowed_bytes(data)
     }
@@ -179,7 +266,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
         let data = match self.state.as_ref() {
             Some((_, Part::Blob { data, .. })) => data,
             Some((_, Part::Text { data, .. })) => data,
-            None => unreachable!(),
+            None => return Err(Error::custom("expected a field")),
         };
         visitor.visit_byte_buf(data.to_vec())
     }
@@ -206,7 +293,8 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
                     visitor.visit_none()
                 }
             }
-            _ => unreachable!(),
+            Some((State::Data, _)) => visitor.visit_some(self),
+            _ => Err(Error::custom("unexpected optional value")),
         }
     }
 
@@ -214,7 +302,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::custom("unit values are not supported"))
     }
 
     fn deserialize_unit_struct<V>(self, _name: &'static str, visitor: V) -> Result<V::Value>
@@ -235,7 +323,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
     where
         V: Visitor<'de>,
     {
-        unreachable!()
+        Err(Error::custom("sequences are not supported"))
     }
 
     fn deserialize_tuple<V>(self, _len: usize, visitor: V) -> Result<V::Value>
@@ -293,7 +381,7 @@ impl<'de, 'a> serde::de::Deserializer<'de> for &'a mut Deserializer<'de> {
 // Note that we have maps at two levels: the top level as well as the fields
 // inside a `File` object (`Part::Blob` variant). This is especially relevant
 // when deciding to return `Ok(None)` from `next_key_seed()`.
-impl<'de, 'a> MapAccess<'de> for &'a mut Deserializer<'de> {
+impl<'de> MapAccess<'de> for &mut Deserializer<'de> {
     type Error = Error;
 
     fn next_key_seed<K>(&mut self, seed: K) -> Result<Option<K::Value>>
@@ -302,8 +390,10 @@ impl<'de, 'a> MapAccess<'de> for &'a mut Deserializer<'de> {
     {
         let split_len = self.boundary.len();
         if self.state.is_none() && self.input.starts_with(&self.boundary) {
-            if &self.input[split_len..split_len + 4] == b"--\r\n" {
-                return Ok(None);
+            match self.input.get(split_len..split_len + 2) {
+                Some(b"--") => return Ok(None),
+                Some(b"\r\n") => {}
+                _ => return Err(Error::custom("invalid boundary delimiter")),
             }
 
             let (len, part) = Part::from_bytes(&self.input[split_len + 2..], &self.boundary)?;
@@ -325,13 +415,13 @@ impl<'de, 'a> MapAccess<'de> for &'a mut Deserializer<'de> {
                 },
                 State::Type => seed.deserialize(&mut **self).map(Some),
                 State::Data => seed.deserialize(&mut **self).map(Some),
-                State::End => {
+                State::End | State::Ignored => {
                     self.state = None;
                     Ok(None)
                 }
             }
         } else {
-            unreachable!()
+            Err(Error::custom("expected boundary"))
         }
     }
 
@@ -341,15 +431,16 @@ impl<'de, 'a> MapAccess<'de> for &'a mut Deserializer<'de> {
     {
         let res = seed.deserialize(&mut **self);
         self.state = match self.state.take() {
-            Some((State::Name, _)) => unreachable!(),
+            Some((State::Name | State::End, _)) => {
+                return Err(Error::custom("unexpected value"));
+            }
             Some((State::Filename, part)) => Some((State::Type, part)),
             Some((State::Type, part)) => Some((State::Data, part)),
             Some((State::Data, part)) => match part {
                 Part::Blob { .. } => Some((State::End, part)),
                 Part::Text { .. } => None,
             },
-            Some((State::End, _)) => unreachable!(),
-            None => None,
+            Some((State::Ignored, _)) | None => None,
         };
         res
     }
@@ -389,14 +480,14 @@ impl<'de, 'a> VariantAccess<'de> for Enum<'a, 'de> {
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::custom("tuple variants are not supported"))
     }
 
     fn struct_variant<V>(self, _fields: &'static [&'static str], _visitor: V) -> Result<V::Value>
     where
         V: Visitor<'de>,
     {
-        unimplemented!()
+        Err(Error::custom("struct variants are not supported"))
     }
 }
 
@@ -421,6 +512,7 @@ enum State {
     Type,
     Data,
     End,
+    Ignored,
 }
 
 impl<'a> Part<'a> {
@@ -428,10 +520,9 @@ impl<'a> Part<'a> {
         let mut header_buf = [httparse::EMPTY_HEADER; 4];
         let status = httparse::parse_headers(bytes, &mut header_buf)
             .map_err(|_| Error::custom("unable to parse part headers"))?;
-        let (header_len, headers) = if let httparse::Status::Complete((len, headers)) = status {
-            (len, headers)
-        } else {
-            unreachable!();
+        let (header_len, headers) = match status {
+            httparse::Status::Complete((len, headers)) => (len, headers),
+            httparse::Status::Partial => return Err(Error::custom("incomplete part headers")),
         };
 
         let (mut name, mut filename, mut ctype) = (None, None, None);
@@ -449,7 +540,13 @@ impl<'a> Part<'a> {
                         .find('=')
                         .ok_or_else(|| Error::custom("parameter value not found"))?;
                     let pname = &param[..sep].trim();
-                    let value = &param[sep + 2..param.len() - 1];
+                    let value = param[sep + 1..].trim();
+                    let value = match value.strip_prefix('"') {
+                        Some(quoted) => quoted
+                            .strip_suffix('"')
+                            .ok_or_else(|| Error::custom("unterminated parameter value"))?,
+                        None => value,
+                    };
                     if *pname == "name" {
                         name = Some(value);
                     } else if *pname == "filename" {
@@ -461,11 +558,15 @@ impl<'a> Part<'a> {
             }
         }
 
-        let (len, data) = if let Some(pos) = memmem::find(bytes, boundary) {
-            (pos, &bytes[header_len..pos - 2])
-        } else {
-            (bytes.len(), &bytes[header_len..])
+        // The data ends with a line break before the next delimiter
+        let pos = memmem::find(&bytes[header_len..], boundary)
+            .map(|pos| header_len + pos)
+            .ok_or_else(|| Error::custom("closing boundary not found"))?;
+        let data = match bytes[header_len..pos].strip_suffix(b"\r\n") {
+            Some(data) => data,
+            None => return Err(Error::custom("missing line break before boundary")),
         };
+        let len = pos;
 
         let name = name.ok_or_else(|| Error::custom("no name found"))?;
         let part = match &filename {
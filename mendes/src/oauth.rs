@@ -0,0 +1,570 @@
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::sync::Arc;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use data_encoding::BASE64URL_NOPAD;
+use http::header::{
+    InvalidHeaderValue, ACCEPT, AUTHORIZATION, CONTENT_TYPE, LOCATION, SET_COOKIE, USER_AGENT,
+};
+use http::request::Parts;
+use http::{HeaderValue, Request, Response, StatusCode};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use thiserror::Error;
+
+use crate::application::{ErrorKind, FromContext, PathState};
+use crate::cookies::{AppWithAeadKey, AppWithCookies, CookieData, CookieMeta, SameSite};
+use crate::utils::{constant_time_eq, is_local_path};
+
+/// Sign-in through OAuth 2.0 and OpenID Connect providers
+///
+/// Implements the authorization code flow with PKCE. `login()` redirects to the provider,
+/// which redirects back to the provider's `redirect_uri`, where `callback()` exchanges the
+/// code for tokens, loads the user's identity and stores it in an encrypted cookie. Handlers
+/// can then take a `User` argument:
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use mendes::cookies::{AppWithAeadKey, Key};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::oauth::{HttpClient, OAuth, User};
+/// # use mendes::{handler, Body, Error};
+/// # struct App {
+/// #     key: Key,
+/// #     oauth: OAuth<Arc<dyn HttpClient>>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithAeadKey for App {
+/// #     fn key(&self) -> &Key {
+/// #         &self.key
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn login(app: &App, provider: String) -> Result<Response<Body>, Error> {
+///     Ok(app.oauth.login(app, &provider, "/")?)
+/// }
+///
+/// #[handler(GET)]
+/// async fn callback(app: &App, req: &Parts) -> Result<Response<Body>, Error> {
+///     Ok(app.oauth.callback(app, req).await?)
+/// }
+///
+/// #[handler(GET)]
+/// async fn home(_: &App, user: Option<User>) -> Result<Response<Body>, Error> {
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The state, nonce and PKCE verifier for a pending sign-in are kept in a short-lived
+/// encrypted cookie, so no server-side storage is needed. Requests to the provider are made
+/// through the application's `HttpClient`.
+pub struct OAuth<C> {
+    client: C,
+    providers: Vec<Provider>,
+}
+
+impl<C: HttpClient> OAuth<C> {
+    pub fn new(client: C) -> Self {
+        Self {
+            client,
+            providers: Vec::new(),
+        }
+    }
+
+    /// Add a provider, replacing any earlier provider with the same name
+    pub fn provider(mut self, provider: Provider) -> Self {
+        self.providers.retain(|p| p.name != provider.name);
+        self.providers.push(provider);
+        self
+    }
+
+    /// Redirect to the named provider to start signing in
+    ///
+    /// After signing in, the user is sent back to `return_to`, which must be a local path.
+    pub fn login<A: AppWithAeadKey, B: From<Bytes>>(
+        &self,
+        app: &A,
+        provider: &str,
+        return_to: &str,
+    ) -> Result<Response<B>, Error> {
+        let provider = self.get(provider)?;
+        if !is_local_path(return_to) {
+            return Err(Error::InvalidReturnPath);
+        }
+
+        let flow = Flow {
+            provider: provider.name.to_string(),
+            state: random(),
+            nonce: random(),
+            verifier: random(),
+            return_to: return_to.to_owned(),
+        };
+
+        let challenge = BASE64URL_NOPAD.encode(digest(&SHA256, flow.verifier.as_bytes()).as_ref());
+        let scope = provider.scopes.join(" ");
+        let query = serde_urlencoded::to_string([
+            ("response_type", "code"),
+            ("client_id", &provider.client_id),
+            ("redirect_uri", &provider.redirect_uri),
+            ("scope", &scope),
+            ("state", &flow.state),
+            ("nonce", &flow.nonce),
+            ("code_challenge", &challenge),
+            ("code_challenge_method", "S256"),
+        ])
+        .unwrap();
+
+        let separator = match provider.authorize_url.contains('?') {
+            true => '&',
+            false => '?',
+        };
+
+        redirect(
+            &format!("{}{separator}{query}", provider.authorize_url),
+            [app.set_cookie_header(Some(flow))?],
+        )
+    }
+
+    /// Complete signing in when the provider redirects back to the application
+    ///
+    /// Validates the state against the pending sign-in, exchanges the code for tokens and
+    /// loads the user's identity, either from the ID token or from the provider's user info
+    /// endpoint. Redirects to the path passed to `login()`, setting a cookie for the `User`.
+    pub async fn callback<A: AppWithAeadKey, B: From<Bytes>>(
+        &self,
+        app: &A,
+        req: &Parts,
+    ) -> Result<Response<B>, Error> {
+        let query = serde_urlencoded::from_str::<CallbackQuery>(req.uri.query().unwrap_or(""))
+            .map_err(|_| Error::InvalidCallback)?;
+        if let Some(error) = query.error {
+            return Err(Error::Denied(error));
+        }
+
+        let flow = app.cookie::<Flow>(&req.headers).ok_or(Error::FlowMissing)?;
+        match query.state {
+            Some(state) if constant_time_eq(state.as_bytes(), flow.state.as_bytes()) => {}
+            _ => return Err(Error::StateMismatch),
+        }
+
+        let code = query.code.ok_or(Error::InvalidCallback)?;
+        let provider = self.get(&flow.provider)?;
+        let tokens = self.exchange(provider, &code, &flow.verifier).await?;
+        let user = match (&tokens.id_token, &provider.userinfo_url) {
+            (Some(id_token), _) => provider.verify_id_token(id_token, &flow.nonce)?,
+            (None, Some(url)) => {
+                let req = Request::get(url.as_ref())
+                    .header(ACCEPT, "application/json")
+                    .header(AUTHORIZATION, format!("Bearer {}", tokens.access_token))
+                    .header(USER_AGENT, "mendes")
+                    .body(Bytes::new())
+                    .unwrap();
+                provider.user(&self.request(req).await?)?
+            }
+            (None, None) => return Err(Error::InvalidResponse),
+        };
+
+        if !is_local_path(&flow.return_to) {
+            return Err(Error::InvalidReturnPath);
+        }
+
+        redirect(
+            &flow.return_to,
+            [
+                app.set_cookie_header::<Flow>(None)?,
+                app.set_cookie_header(Some(user))?,
+            ],
+        )
+    }
+
+    /// Sign out by clearing the `User` cookie and redirecting to `return_to`
+    ///
+    /// Like for `login()`, `return_to` must be a local path.
+    pub fn logout<A: AppWithAeadKey, B: From<Bytes>>(
+        &self,
+        app: &A,
+        return_to: &str,
+    ) -> Result<Response<B>, Error> {
+        if !is_local_path(return_to) {
+            return Err(Error::InvalidReturnPath);
+        }
+
+        redirect(return_to, [app.set_cookie_header::<User>(None)?])
+    }
+
+    async fn exchange(
+        &self,
+        provider: &Provider,
+        code: &str,
+        verifier: &str,
+    ) -> Result<TokenResponse, Error> {
+        let mut form = vec![
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", &provider.redirect_uri),
+            ("client_id", &provider.client_id),
+            ("code_verifier", verifier),
+        ];
+        if let Some(secret) = &provider.client_secret {
+            form.push(("client_secret", secret));
+        }
+
+        let req = Request::post(provider.token_url.as_ref())
+            .header(ACCEPT, "application/json")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .header(USER_AGENT, "mendes")
+            .body(Bytes::from(serde_urlencoded::to_string(form).unwrap()))
+            .unwrap();
+
+        let body = self.request(req).await?;
+        let rsp =
+            serde_json::from_slice::<TokenResponse>(&body).map_err(|_| Error::InvalidResponse)?;
+        match (&rsp.error, rsp.access_token.is_empty()) {
+            (Some(error), _) => Err(Error::Token(error.clone())),
+            (None, true) => Err(Error::InvalidResponse),
+            (None, false) => Ok(rsp),
+        }
+    }
+
+    async fn request(&self, req: Request<Bytes>) -> Result<Bytes, Error> {
+        let rsp = self.client.send(req).await.map_err(Error::Transport)?;
+        match rsp.status().is_success() {
+            true => Ok(rsp.into_body()),
+            false => Err(Error::Status(rsp.status())),
+        }
+    }
+
+    fn get(&self, name: &str) -> Result<&Provider, Error> {
+        self.providers
+            .iter()
+            .find(|p| p.name == name)
+            .ok_or(Error::UnknownProvider)
+    }
+}
+
+/// Sends requests to OAuth providers
+///
+/// Implement this on top of the HTTP client used by the application.
+#[async_trait]
+pub trait HttpClient: Send + Sync {
+    async fn send(
+        &self,
+        req: Request<Bytes>,
+    ) -> Result<Response<Bytes>, Box<dyn StdError + Send + Sync>>;
+}
+
+#[async_trait]
+impl<C: HttpClient + ?Sized> HttpClient for Arc<C> {
+    async fn send(
+        &self,
+        req: Request<Bytes>,
+    ) -> Result<Response<Bytes>, Box<dyn StdError + Send + Sync>> {
+        (**self).send(req).await
+    }
+}
+
+/// Configuration for an OAuth 2.0 or OpenID Connect provider
+#[derive(Clone, Debug)]
+pub struct Provider {
+    /// The name used to refer to the provider in `login()` and in `User::provider`
+    pub name: Cow<'static, str>,
+    pub authorize_url: Cow<'static, str>,
+    pub token_url: Cow<'static, str>,
+    /// Used to load the user's identity if the token response has no ID token
+    pub userinfo_url: Option<Cow<'static, str>>,
+    /// The expected issuer for ID tokens, if any
+    pub issuer: Option<Cow<'static, str>>,
+    pub client_id: String,
+    pub client_secret: Option<String>,
+    /// The callback URL registered with the provider
+    pub redirect_uri: String,
+    pub scopes: Vec<Cow<'static, str>>,
+}
+
+impl Provider {
+    /// Sign in with Google, through OpenID Connect
+    pub fn google(client_id: String, client_secret: String, redirect_uri: String) -> Self {
+        Self {
+            name: "google".into(),
+            authorize_url: "https://accounts.google.com/o/oauth2/v2/auth".into(),
+            token_url: "https://oauth2.googleapis.com/token".into(),
+            userinfo_url: None,
+            issuer: Some("https://accounts.google.com".into()),
+            client_id,
+            client_secret: Some(client_secret),
+            redirect_uri,
+            scopes: vec!["openid".into(), "email".into(), "profile".into()],
+        }
+    }
+
+    /// Sign in with GitHub
+    pub fn github(client_id: String, client_secret: String, redirect_uri: String) -> Self {
+        Self {
+            name: "github".into(),
+            authorize_url: "https://github.com/login/oauth/authorize".into(),
+            token_url: "https://github.com/login/oauth/access_token".into(),
+            userinfo_url: Some("https://api.github.com/user".into()),
+            issuer: None,
+            client_id,
+            client_secret: Some(client_secret),
+            redirect_uri,
+            scopes: vec!["read:user".into(), "user:email".into()],
+        }
+    }
+
+    /// Get the user's identity from the claims in an ID token
+    ///
+    /// The token was received directly from the provider's token endpoint over TLS, so its
+    /// signature is not checked (as allowed by OpenID Connect Core, section 3.1.3.7).
+    fn verify_id_token(&self, token: &str, nonce: &str) -> Result<User, Error> {
+        let payload = token.split('.').nth(1).ok_or(Error::InvalidIdToken)?;
+        let payload = BASE64URL_NOPAD
+            .decode(payload.trim_end_matches('=').as_bytes())
+            .map_err(|_| Error::InvalidIdToken)?;
+        let claims =
+            serde_json::from_slice::<Value>(&payload).map_err(|_| Error::InvalidIdToken)?;
+
+        let audience = match &claims["aud"] {
+            Value::String(aud) => aud == &self.client_id,
+            Value::Array(aud) => aud.iter().any(|aud| aud == self.client_id.as_str()),
+            _ => false,
+        };
+
+        let issuer = match &self.issuer {
+            Some(issuer) => claims["iss"] == issuer.as_ref(),
+            None => true,
+        };
+
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let fresh = claims["exp"].as_u64().is_some_and(|exp| exp > now);
+        if !audience || !issuer || !fresh {
+            return Err(Error::InvalidIdToken);
+        }
+
+        match claims["nonce"].as_str() {
+            Some(claim) if constant_time_eq(claim.as_bytes(), nonce.as_bytes()) => {}
+            _ => return Err(Error::NonceMismatch),
+        }
+
+        self.user_from(&claims).ok_or(Error::InvalidIdToken)
+    }
+
+    /// Get the user's identity from a user info response
+    fn user(&self, body: &[u8]) -> Result<User, Error> {
+        let info = serde_json::from_slice::<Value>(body).map_err(|_| Error::InvalidResponse)?;
+        self.user_from(&info).ok_or(Error::InvalidResponse)
+    }
+
+    fn user_from(&self, info: &Value) -> Option<User> {
+        let id = match info.get("sub").or_else(|| info.get("id"))? {
+            Value::String(id) => id.clone(),
+            Value::Number(id) => id.to_string(),
+            _ => return None,
+        };
+
+        let string = |key: &str| info.get(key)?.as_str().map(str::to_owned);
+        Some(User {
+            provider: self.name.to_string(),
+            id,
+            email: string("email"),
+            name: string("name").or_else(|| string("login")),
+        })
+    }
+}
+
+/// The signed-in user, as stored in an encrypted cookie by `OAuth::callback()`
+///
+/// Requests without a valid cookie are rejected with a `SignInRequired` error; use
+/// `Option<User>` for pages that are also available to anonymous users.
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct User {
+    /// The name of the provider the user signed in with
+    pub provider: String,
+    /// The user's identifier at the provider
+    pub id: String,
+    pub email: Option<String>,
+    pub name: Option<String>,
+}
+
+impl CookieData for User {
+    fn meta() -> CookieMeta<'static> {
+        CookieMeta {
+            http_only: true,
+            same_site: Some(SameSite::Lax),
+            ..CookieMeta::default()
+        }
+    }
+
+    const NAME: &'static str = "User";
+}
+
+impl<'a, A: AppWithAeadKey> FromContext<'a, A> for User {
+    fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        match app.cookie::<User>(&req.headers) {
+            Some(user) => Ok(user),
+            None => Err(state.reject::<A>(ErrorKind::SignInRequired.into(), req)),
+        }
+    }
+}
+
+/// A pending sign-in
+#[derive(Deserialize, Serialize)]
+struct Flow {
+    provider: String,
+    state: String,
+    nonce: String,
+    verifier: String,
+    return_to: String,
+}
+
+impl CookieData for Flow {
+    fn meta() -> CookieMeta<'static> {
+        CookieMeta {
+            http_only: true,
+            max_age: 10 * 60,
+            // The cookie must be sent when the provider redirects back to the application
+            same_site: Some(SameSite::Lax),
+            ..CookieMeta::default()
+        }
+    }
+
+    const NAME: &'static str = "OAuthFlow";
+}
+
+#[derive(Deserialize)]
+struct CallbackQuery {
+    code: Option<String>,
+    state: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    #[serde(default)]
+    access_token: String,
+    id_token: Option<String>,
+    error: Option<String>,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("unknown provider")]
+    UnknownProvider,
+    #[error("return path must be a local path")]
+    InvalidReturnPath,
+    #[error("invalid redirect location")]
+    InvalidLocation(#[from] InvalidHeaderValue),
+    #[error("invalid callback request")]
+    InvalidCallback,
+    #[error("sign-in denied: {0}")]
+    Denied(String),
+    #[error("no pending sign-in")]
+    FlowMissing,
+    #[error("state mismatch")]
+    StateMismatch,
+    #[error("nonce mismatch")]
+    NonceMismatch,
+    #[error("token request failed: {0}")]
+    Token(String),
+    #[error("request to provider failed")]
+    Transport(#[source] Box<dyn StdError + Send + Sync>),
+    #[error("provider responded with status {0}")]
+    Status(StatusCode),
+    #[error("invalid response from provider")]
+    InvalidResponse,
+    #[error("invalid ID token")]
+    InvalidIdToken,
+    #[error("cookie error: {0}")]
+    Cookie(#[from] crate::cookies::Error),
+}
+
+fn redirect<B: From<Bytes>>(
+    location: &str,
+    cookies: impl IntoIterator<Item = HeaderValue>,
+) -> Result<Response<B>, Error> {
+    let mut rsp = Response::new(B::from(Bytes::new()));
+    *rsp.status_mut() = StatusCode::FOUND;
+    let headers = rsp.headers_mut();
+    headers.insert(LOCATION, HeaderValue::try_from(location)?);
+    for cookie in cookies {
+        headers.append(SET_COOKIE, cookie);
+    }
+    Ok(rsp)
+}
+
+fn random() -> String {
+    let mut buf = [0; 32];
+    SystemRandom::new()
+        .fill(&mut buf)
+        .expect("failed to generate random value");
+    BASE64URL_NOPAD.encode(&buf)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn id_token() {
+        let provider = Provider::google("client".into(), "secret".into(), "/cb".into());
+        let claims = serde_json::json!({
+            "iss": "https://accounts.google.com",
+            "aud": "client",
+            "sub": "1234",
+            "email": "user@example.com",
+            "nonce": "abc",
+            "exp": u64::MAX / 2,
+        });
+        let payload = BASE64URL_NOPAD.encode(claims.to_string().as_bytes());
+        let token = format!("e30.{payload}.sig");
+
+        let user = provider.verify_id_token(&token, "abc").unwrap();
+        assert_eq!(user.id, "1234");
+        assert_eq!(user.email.as_deref(), Some("user@example.com"));
+        assert!(matches!(
+            provider.verify_id_token(&token, "abd"),
+            Err(Error::NonceMismatch)
+        ));
+
+        let other = Provider::google("other".into(), "secret".into(), "/cb".into());
+        assert!(matches!(
+            other.verify_id_token(&token, "abc"),
+            Err(Error::InvalidIdToken)
+        ));
+    }
+
+    #[test]
+    fn user_info() {
+        let provider = Provider::github("client".into(), "secret".into(), "/cb".into());
+        let user = provider
+            .user(br#"{"id": 42, "login": "octocat", "email": null}"#)
+            .unwrap();
+        assert_eq!(user.provider, "github");
+        assert_eq!(user.id, "42");
+        assert_eq!(user.name.as_deref(), Some("octocat"));
+        assert_eq!(user.email, None);
+    }
+}
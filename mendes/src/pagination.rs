@@ -0,0 +1,544 @@
+use std::fmt::Write;
+use std::sync::Arc;
+
+use http::header::{HeaderName, LINK};
+use http::request::Parts;
+use http::{HeaderValue, Response};
+
+use crate::application::{Application, ErrorKind, FromContext, PathState};
+
+/// Pagination parameters from the request query
+///
+/// Reads `page` (starting at 1) and `per_page` for offset pagination, or `cursor` for
+/// cursor pagination. `per_page` defaults to `DEFAULT` and is capped at `MAX`:
+///
+/// ```no_run
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::pagination::Pagination;
+/// # use mendes::{handler, Body, Error};
+/// # struct Post;
+/// # struct Db;
+/// # impl Db {
+/// #     async fn posts(&self, offset: u64, limit: u32) -> Result<(Vec<Post>, u64), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # fn json(posts: &[Post]) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn list(app: &App, req: &Parts, pages: Pagination) -> Result<Response<Body>, Error> {
+///     let (posts, total) = app.db.posts(pages.offset(), pages.limit()).await?;
+///     let mut rsp = json(&posts)?;
+///     pages.apply(req, &mut rsp, total);
+///     Ok(rsp)
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Invalid values are rejected with a `QueryDecode` error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Pagination<const DEFAULT: u32 = 20, const MAX: u32 = 100> {
+    pub page: u32,
+    pub per_page: u32,
+    pub cursor: Option<String>,
+}
+
+impl<const DEFAULT: u32, const MAX: u32> Pagination<DEFAULT, MAX> {
+    /// Parse pagination parameters from a query string
+    pub fn from_query(query: &str) -> Result<Self, ErrorKind> {
+        let mut pagination = Self {
+            page: 1,
+            per_page: DEFAULT.min(MAX),
+            cursor: None,
+        };
+
+        for (key, value) in query_pairs(query)? {
+            match key.as_str() {
+                PAGE => match value.parse() {
+                    Ok(page) if page > 0 => pagination.page = page,
+                    _ => return Err(ErrorKind::QueryDecode),
+                },
+                PER_PAGE => match value.parse::<u32>() {
+                    Ok(per_page) if per_page > 0 => pagination.per_page = per_page.min(MAX),
+                    _ => return Err(ErrorKind::QueryDecode),
+                },
+                CURSOR if !value.is_empty() => pagination.cursor = Some(value),
+                _ => {}
+            }
+        }
+
+        Ok(pagination)
+    }
+
+    /// The number of items to skip for offset pagination
+    pub fn offset(&self) -> u64 {
+        u64::from(self.page - 1) * u64::from(self.per_page)
+    }
+
+    /// The number of items to fetch
+    pub fn limit(&self) -> u32 {
+        self.per_page
+    }
+
+    /// Add `Link` headers for the first, previous, next and last pages, and an
+    /// `X-Total-Count` header with the `total` number of items
+    pub fn apply<B>(&self, req: &Parts, rsp: &mut Response<B>, total: u64) {
+        set_links(rsp, self.links(req, total));
+        rsp.headers_mut()
+            .insert(TOTAL_COUNT, HeaderValue::from(total));
+    }
+
+    /// The URLs for the first, previous, next and last pages, by link relation
+    pub(crate) fn links(&self, req: &Parts, total: u64) -> Vec<(&'static str, String)> {
+        let per_page = u64::from(self.per_page);
+        let last = ((total + per_page - 1) / per_page).max(1);
+        let page = u64::from(self.page);
+        let mut links = vec![("first", 1)];
+        if page > 1 {
+            links.push(("prev", (page - 1).min(last)));
+        }
+        if page < last {
+            links.push(("next", page + 1));
+        }
+        links.push(("last", last));
+
+        links
+            .into_iter()
+            .map(|(rel, page)| (rel, self.url(req, PAGE, &page.to_string())))
+            .collect()
+    }
+
+    /// Add a `Link` header for the `next` cursor, if there are more items
+    pub fn apply_cursor<B>(&self, req: &Parts, rsp: &mut Response<B>, next: Option<&str>) {
+        if let Some(next) = next {
+            set_links(rsp, [("next", self.url(req, CURSOR, next))]);
+        }
+    }
+
+    /// The request's path and query, with `key` set to `value` and `per_page` made explicit
+    pub(crate) fn url(&self, req: &Parts, key: &str, value: &str) -> String {
+        let query = req.uri.query().unwrap_or("");
+        let mut pairs = serde_urlencoded::from_str::<Vec<(String, String)>>(query)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(k, _)| !matches!(k.as_str(), PAGE | PER_PAGE | CURSOR))
+            .collect::<Vec<_>>();
+        pairs.push((key.to_owned(), value.to_owned()));
+        pairs.push((PER_PAGE.to_owned(), self.per_page.to_string()));
+
+        let query = serde_urlencoded::to_string(&pairs).unwrap_or_default();
+        format!("{}?{query}", req.uri.path())
+    }
+}
+
+impl<'a, A: Application, const DEFAULT: u32, const MAX: u32> FromContext<'a, A>
+    for Pagination<DEFAULT, MAX>
+{
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Self::from_query(req.uri.query().unwrap_or(""))
+            .map_err(|kind| state.reject::<A>(kind.into(), req))
+    }
+}
+
+/// A field that list endpoints allow sorting or filtering by
+///
+/// Usually implemented for an enum of the allowed fields:
+///
+/// ```no_run
+/// # use mendes::pagination::Field;
+/// #[derive(Clone, Copy, PartialEq)]
+/// enum PostField {
+///     CreatedAt,
+///     Status,
+/// }
+///
+/// impl Field for PostField {
+///     fn from_name(name: &str) -> Option<Self> {
+///         match name {
+///             "created_at" => Some(Self::CreatedAt),
+///             "status" => Some(Self::Status),
+///             _ => None,
+///         }
+///     }
+///
+///     fn column(&self) -> &'static str {
+///         match self {
+///             Self::CreatedAt => "created_at",
+///             Self::Status => "status",
+///         }
+///     }
+/// }
+/// ```
+pub trait Field: Sized {
+    /// Look up the field for a `name` from the query string
+    fn from_name(name: &str) -> Option<Self>;
+
+    /// The column for this field in SQL queries
+    ///
+    /// This is included in queries as is, so it must not come from the request.
+    fn column(&self) -> &'static str;
+}
+
+/// Sort order from the `sort` query parameter
+///
+/// Parses a comma-separated list of fields like `?sort=-created_at,title`, where a `-`
+/// prefix sorts in descending order. Unknown fields are rejected with a `QueryDecode` error.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Sort<T> {
+    pub fields: Vec<(T, Direction)>,
+}
+
+impl<T: Field> Sort<T> {
+    /// Parse the sort order from a query string
+    pub fn from_query(query: &str) -> Result<Self, ErrorKind> {
+        let mut fields = Vec::new();
+        for (key, value) in query_pairs(query)? {
+            if key != SORT {
+                continue;
+            }
+
+            for name in value.split(',').filter(|name| !name.is_empty()) {
+                let (name, direction) = match name.strip_prefix('-') {
+                    Some(name) => (name, Direction::Descending),
+                    None => (name, Direction::Ascending),
+                };
+                let field = T::from_name(name).ok_or(ErrorKind::QueryDecode)?;
+                fields.push((field, direction));
+            }
+        }
+
+        Ok(Self { fields })
+    }
+
+    /// An SQL `ORDER BY` clause for the sort order, or an empty string if there is none
+    pub fn order_by(&self) -> String {
+        let mut sql = String::new();
+        for (field, direction) in &self.fields {
+            sql.push_str(match sql.is_empty() {
+                true => "ORDER BY ",
+                false => ", ",
+            });
+            sql.push_str(field.column());
+            sql.push_str(match direction {
+                Direction::Ascending => " ASC",
+                Direction::Descending => " DESC",
+            });
+        }
+        sql
+    }
+}
+
+impl<T> Default for Sort<T> {
+    fn default() -> Self {
+        Self { fields: Vec::new() }
+    }
+}
+
+impl<'a, A: Application, T: Field> FromContext<'a, A> for Sort<T> {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Self::from_query(req.uri.query().unwrap_or(""))
+            .map_err(|kind| state.reject::<A>(kind.into(), req))
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Ascending,
+    Descending,
+}
+
+/// Equality filters from `filter[field]` query parameters
+///
+/// `?filter[status]=open&filter[author]=42` selects items matching all conditions. Unknown
+/// fields are rejected with a `QueryDecode` error, other query parameters are ignored.
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::pagination::{Field, Filter, Sort};
+/// # use mendes::{handler, Body, Error};
+/// # #[derive(Clone, Copy, PartialEq)]
+/// # enum PostField {
+/// #     CreatedAt,
+/// #     Status,
+/// # }
+/// # impl Field for PostField {
+/// #     fn from_name(name: &str) -> Option<Self> {
+/// #         todo!()
+/// #     }
+/// #     fn column(&self) -> &'static str {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct Post;
+/// # struct Db;
+/// # impl Db {
+/// #     async fn query(&self, sql: &str, params: &[&str]) -> Result<Vec<Post>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn list(
+///     app: &App,
+///     filter: Filter<PostField>,
+///     sort: Sort<PostField>,
+/// ) -> Result<Response<Body>, Error> {
+///     let (clause, params) = filter.where_clause(1);
+///     let sql = format!("SELECT * FROM posts {clause} {}", sort.order_by());
+///     let posts = app.db.query(&sql, &params).await?;
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Filter<T> {
+    pub conditions: Vec<(T, String)>,
+}
+
+impl<T: Field> Filter<T> {
+    /// Parse the filters from a query string
+    pub fn from_query(query: &str) -> Result<Self, ErrorKind> {
+        let mut conditions = Vec::new();
+        for (key, value) in query_pairs(query)? {
+            let name = match key
+                .strip_prefix(FILTER)
+                .and_then(|key| key.strip_prefix('['))
+                .and_then(|key| key.strip_suffix(']'))
+            {
+                Some(name) => name,
+                None => continue,
+            };
+
+            let field = T::from_name(name).ok_or(ErrorKind::QueryDecode)?;
+            conditions.push((field, value));
+        }
+
+        Ok(Self { conditions })
+    }
+
+    /// The value to filter `field` by, if any
+    pub fn get(&self, field: &T) -> Option<&str>
+    where
+        T: PartialEq,
+    {
+        self.conditions
+            .iter()
+            .find(|(f, _)| f == field)
+            .map(|(_, value)| value.as_str())
+    }
+
+    /// An SQL `WHERE` clause for the filters and the values to bind to its parameters
+    ///
+    /// Parameters are numbered from `first` in PostgreSQL style (`$1`, `$2`, ...), so that
+    /// the values never become part of the query itself. Returns an empty clause if there
+    /// are no filters.
+    pub fn where_clause(&self, first: usize) -> (String, Vec<&str>) {
+        let mut sql = String::new();
+        let mut params = Vec::with_capacity(self.conditions.len());
+        for (field, value) in &self.conditions {
+            sql.push_str(match sql.is_empty() {
+                true => "WHERE ",
+                false => " AND ",
+            });
+            write!(sql, "{} = ${}", field.column(), first + params.len()).unwrap();
+            params.push(value.as_str());
+        }
+        (sql, params)
+    }
+}
+
+impl<T> Default for Filter<T> {
+    fn default() -> Self {
+        Self {
+            conditions: Vec::new(),
+        }
+    }
+}
+
+impl<'a, A: Application, T: Field> FromContext<'a, A> for Filter<T> {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Self::from_query(req.uri.query().unwrap_or(""))
+            .map_err(|kind| state.reject::<A>(kind.into(), req))
+    }
+}
+
+fn query_pairs(query: &str) -> Result<Vec<(String, String)>, ErrorKind> {
+    serde_urlencoded::from_str(query).map_err(|_| ErrorKind::QueryDecode)
+}
+
+fn set_links<'a, B>(rsp: &mut Response<B>, links: impl IntoIterator<Item = (&'a str, String)>) {
+    let mut value = String::new();
+    for (rel, url) in links {
+        if !value.is_empty() {
+            value.push_str(", ");
+        }
+        write!(value, "<{url}>; rel=\"{rel}\"").unwrap();
+    }
+
+    // URLs built from a valid request URI and URL-encoded parameters are valid header values
+    if let Ok(value) = HeaderValue::try_from(value) {
+        rsp.headers_mut().append(LINK, value);
+    }
+}
+
+const PAGE: &str = "page";
+const PER_PAGE: &str = "per_page";
+pub(crate) const CURSOR: &str = "cursor";
+const SORT: &str = "sort";
+const FILTER: &str = "filter";
+const TOTAL_COUNT: HeaderName = HeaderName::from_static("x-total-count");
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::*;
+
+    #[test]
+    fn parse() {
+        let pages = Pagination::<20, 100>::from_query("").unwrap();
+        assert_eq!((pages.page, pages.per_page, pages.offset()), (1, 20, 0));
+
+        let pages = Pagination::<20, 50>::from_query("page=3&per_page=500&q=x").unwrap();
+        assert_eq!((pages.page, pages.per_page, pages.offset()), (3, 50, 100));
+
+        let pages = Pagination::<20, 100>::from_query("cursor=abc").unwrap();
+        assert_eq!(pages.cursor.as_deref(), Some("abc"));
+
+        for query in ["page=0", "page=x", "per_page=0", "per_page=-1"] {
+            assert_eq!(
+                Pagination::<20, 100>::from_query(query),
+                Err(ErrorKind::QueryDecode)
+            );
+        }
+    }
+
+    #[test]
+    fn links() {
+        let req = Request::get("/posts?q=rust+web&page=2&per_page=10")
+            .body(())
+            .unwrap();
+        let (req, _) = req.into_parts();
+        let pages = Pagination::<20, 100>::from_query(req.uri.query().unwrap()).unwrap();
+
+        let mut rsp = Response::new(());
+        pages.apply(&req, &mut rsp, 35);
+        assert_eq!(
+            rsp.headers()[LINK],
+            "</posts?q=rust+web&page=1&per_page=10>; rel=\"first\", \
+             </posts?q=rust+web&page=1&per_page=10>; rel=\"prev\", \
+             </posts?q=rust+web&page=3&per_page=10>; rel=\"next\", \
+             </posts?q=rust+web&page=4&per_page=10>; rel=\"last\""
+        );
+        assert_eq!(rsp.headers()[TOTAL_COUNT], "35");
+
+        let mut rsp = Response::new(());
+        pages.apply_cursor(&req, &mut rsp, Some("b/c"));
+        assert_eq!(
+            rsp.headers()[LINK],
+            "</posts?q=rust+web&cursor=b%2Fc&per_page=10>; rel=\"next\""
+        );
+    }
+
+    #[test]
+    fn sort_filter() {
+        let query = "sort=-created_at,status&filter%5Bstatus%5D=open&filter[author]=x%27y&page=2";
+        let sort = Sort::<PostField>::from_query(query).unwrap();
+        assert_eq!(
+            sort.fields,
+            [
+                (PostField::CreatedAt, Direction::Descending),
+                (PostField::Status, Direction::Ascending)
+            ]
+        );
+        assert_eq!(sort.order_by(), "ORDER BY created_at DESC, status ASC");
+        assert_eq!(Sort::<PostField>::default().order_by(), "");
+
+        let filter = Filter::<PostField>::from_query(query).unwrap();
+        assert_eq!(filter.get(&PostField::Status), Some("open"));
+        assert_eq!(
+            filter.where_clause(2),
+            (
+                "WHERE status = $2 AND author = $3".to_owned(),
+                vec!["open", "x'y"]
+            )
+        );
+
+        for query in ["sort=password", "sort=-"] {
+            assert_eq!(
+                Sort::<PostField>::from_query(query),
+                Err(ErrorKind::QueryDecode)
+            );
+        }
+        assert_eq!(
+            Filter::<PostField>::from_query("filter[password]=x"),
+            Err(ErrorKind::QueryDecode)
+        );
+    }
+
+    #[derive(Debug, PartialEq)]
+    enum PostField {
+        Author,
+        CreatedAt,
+        Status,
+    }
+
+    impl Field for PostField {
+        fn from_name(name: &str) -> Option<Self> {
+            Some(match name {
+                "author" => Self::Author,
+                "created_at" => Self::CreatedAt,
+                "status" => Self::Status,
+                _ => return None,
+            })
+        }
+
+        fn column(&self) -> &'static str {
+            match self {
+                Self::Author => "author",
+                Self::CreatedAt => "created_at",
+                Self::Status => "status",
+            }
+        }
+    }
+}
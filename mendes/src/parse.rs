@@ -0,0 +1,188 @@
+use serde::Deserialize;
+use thiserror::Error;
+
+#[cfg(feature = "uploads")]
+#[cfg_attr(docsrs, doc(cfg(feature = "uploads")))]
+pub use crate::multipart::Error as MultipartError;
+
+/// Deserialize an `application/x-www-form-urlencoded` body
+///
+/// This uses the same parser as body extraction, but works without a `Context` (for
+/// example, from a fuzz target).
+pub fn form<'de, T: Deserialize<'de>>(input: &'de [u8], limits: &Limits) -> Result<T, Error> {
+    limits.check_len(input.len())?;
+    limits.check_fields(input.split(|&b| b == b'&').count())?;
+    Ok(serde_urlencoded::from_bytes(input)?)
+}
+
+/// Deserialize a `multipart/form-data` body
+///
+/// The boundary is taken from `content_type`, the value of the request's `Content-Type` header.
+#[cfg(feature = "uploads")]
+#[cfg_attr(docsrs, doc(cfg(feature = "uploads")))]
+pub fn multipart<'de, T: Deserialize<'de>>(
+    content_type: &[u8],
+    input: &'de [u8],
+    limits: &Limits,
+) -> Result<T, Error> {
+    limits.check_len(input.len())?;
+    limits.check_fields(crate::multipart::count_parts(content_type, input)?)?;
+    Ok(crate::multipart::from_content_type(content_type, input)?)
+}
+
+/// Split the value of a `Cookie` header into name/value pairs
+///
+/// Pairs without a `=` are skipped. Values are returned as sent, without decoding.
+pub fn cookies<'a>(header: &'a str, limits: &Limits) -> Result<Vec<(&'a str, &'a str)>, Error> {
+    limits.check_len(header.len())?;
+    let mut pairs = Vec::new();
+    for pair in crate::utils::cookie_pairs(header) {
+        limits.check_fields(pairs.len() + 1)?;
+        pairs.push(pair);
+    }
+    Ok(pairs)
+}
+
+/// Limits on the input accepted by the parsers in this module
+///
+/// None of the parsers recurse, so the size of their input and the number of fields in it
+/// bound the work they do; there is no nesting depth to limit. Body extraction through
+/// `Context` doesn't apply these limits: its input is bounded by the body size limit.
+#[derive(Clone, Debug)]
+pub struct Limits {
+    /// The maximum length of the input in bytes
+    pub max_len: usize,
+    /// The maximum number of fields (form pairs, multipart parts or cookies)
+    pub max_fields: usize,
+}
+
+impl Limits {
+    fn check_len(&self, len: usize) -> Result<(), Error> {
+        match len > self.max_len {
+            true => Err(Error::TooLong(self.max_len)),
+            false => Ok(()),
+        }
+    }
+
+    fn check_fields(&self, fields: usize) -> Result<(), Error> {
+        match fields > self.max_fields {
+            true => Err(Error::TooManyFields(self.max_fields)),
+            false => Ok(()),
+        }
+    }
+}
+
+impl Default for Limits {
+    /// At most 1 MiB of input with at most 1000 fields
+    fn default() -> Self {
+        Self {
+            max_len: 1024 * 1024,
+            max_fields: 1000,
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("input longer than {0} bytes")]
+    TooLong(usize),
+    #[error("input has more than {0} fields")]
+    TooManyFields(usize),
+    #[error(transparent)]
+    Form(#[from] serde_urlencoded::de::Error),
+    #[cfg(feature = "uploads")]
+    #[error(transparent)]
+    Multipart(#[from] MultipartError),
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use super::*;
+
+    #[test]
+    fn limits() {
+        let limits = Limits {
+            max_len: 16,
+            max_fields: 2,
+        };
+
+        let map = form::<HashMap<&str, &str>>(b"a=1&b=2", &limits).unwrap();
+        assert_eq!(map["b"], "2");
+        assert!(matches!(
+            form::<HashMap<&str, &str>>(b"a=1&b=2&c=3", &limits),
+            Err(Error::TooManyFields(2))
+        ));
+        assert!(matches!(
+            form::<HashMap<&str, &str>>(b"a=0123456789abcdef", &limits),
+            Err(Error::TooLong(16))
+        ));
+
+        assert_eq!(
+            cookies("a=1; b; c=3", &limits).unwrap(),
+            vec![("a", "1"), ("c", "3")]
+        );
+        assert!(matches!(
+            cookies("a=1;b=2;c=3", &limits),
+            Err(Error::TooManyFields(2))
+        ));
+    }
+
+    #[cfg(feature = "uploads")]
+    #[test]
+    fn mutations() {
+        #[derive(Deserialize)]
+        #[allow(dead_code)]
+        struct Upload<'a> {
+            name: &'a str,
+            count: Option<u32>,
+            #[serde(borrow)]
+            file: crate::multipart::File<'a>,
+        }
+
+        const CTYPE: &[u8] = b"multipart/form-data; boundary=sep";
+        const BODY: &[u8] = b"--sep\r\n\
+            Content-Disposition: form-data; name=\"name\"\r\n\r\n\
+            value\r\n\
+            --sep\r\n\
+            Content-Disposition: form-data; name=\"count\"\r\n\r\n\
+            3\r\n\
+            --sep\r\n\
+            Content-Disposition: form-data; name=\"file\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            hello\r\n\
+            --sep--\r\n";
+
+        let limits = Limits::default();
+        let upload = multipart::<Upload<'_>>(CTYPE, BODY, &limits).unwrap();
+        assert_eq!(upload.name, "value");
+        assert_eq!(upload.file.data, b"hello");
+
+        // Truncate and corrupt the valid input in many ways, which must not panic
+        let mut state = 0x2545_f491_4f6c_dd1d_u64;
+        let mut next = move || {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            state as usize
+        };
+
+        for _ in 0..2000 {
+            let mut input = BODY.to_vec();
+            input.truncate(next() % (input.len() + 1));
+            for _ in 0..next() % 4 {
+                if input.is_empty() {
+                    break;
+                }
+                let pos = next() % input.len();
+                input[pos] = b"\r\n-\"=;:x\xff"[next() % 9];
+            }
+
+            let _ = multipart::<Upload<'_>>(CTYPE, &input, &limits);
+            let _ = multipart::<HashMap<&str, &str>>(CTYPE, &input, &limits);
+            let _ = form::<HashMap<String, String>>(&input, &limits);
+            let _ = cookies(&String::from_utf8_lossy(&input), &limits);
+        }
+    }
+}
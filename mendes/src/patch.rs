@@ -0,0 +1,496 @@
+use std::error::Error as StdError;
+use std::marker::PhantomData;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::request::Parts;
+use http_body::Body as HttpBody;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+use crate::application::{
+    check_content_type, read_body, Application, Error, ErrorKind, FromContextAsync, PathState,
+};
+
+/// A JSON Merge Patch (RFC 7386) request body
+///
+/// A merge patch is a partial document: fields in the patch replace those in the target,
+/// nested objects are merged recursively and `null` removes a field:
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::patch::MergePatch;
+/// # use mendes::{handler, Body, Error};
+/// # use serde::{Deserialize, Serialize};
+/// # #[derive(Deserialize, Serialize)]
+/// # struct Post {}
+/// # struct Db;
+/// # impl Db {
+/// #     async fn post(&self, id: u64) -> Result<Post, Error> {
+/// #         todo!()
+/// #     }
+/// #     async fn save(&self, post: &Post) -> Result<(), Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(PATCH)]
+/// async fn update(
+///     app: &App,
+///     id: u64,
+///     #[async_extract] patch: MergePatch<Post>,
+/// ) -> Result<Response<Body>, Error> {
+///     let mut post = app.db.post(id).await?;
+///     patch.apply(&mut post)?;
+///     app.db.save(&post).await?;
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The extractor reads the request body, so handler arguments must be annotated with
+/// `#[async_extract]`. It accepts bodies of up to `MAX_LEN` bytes with an
+/// `application/merge-patch+json` or `application/json` content type.
+pub struct MergePatch<T = Value> {
+    pub patch: Value,
+    target: PhantomData<fn() -> T>,
+}
+
+impl<T: Serialize + DeserializeOwned> MergePatch<T> {
+    pub fn new(patch: Value) -> Self {
+        Self {
+            patch,
+            target: PhantomData,
+        }
+    }
+
+    /// Apply the patch to `target`
+    ///
+    /// Fails with a `BodyDecodeJson` error if the patched document is not a valid `T`, in which
+    /// case `target` is left unchanged.
+    pub fn apply(&self, target: &mut T) -> Result<(), Error> {
+        let mut doc = to_value(target)?;
+        merge(&mut doc, &self.patch);
+        *target = from_value(doc)?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<'a, A, T> FromContextAsync<'a, A> for MergePatch<T>
+where
+    A: Application + Sync,
+    A::RequestBody: HttpBody + Send,
+    <A::RequestBody as HttpBody>::Data: Send,
+    <A::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
+{
+    async fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let body = match body.take() {
+            Some(body) => body,
+            None => panic!("attempted to retrieve body twice"),
+        };
+
+        let patch = read_json::<A, _>(req, state, body, &[MERGE_PATCH, JSON]).await?;
+        Ok(Self {
+            patch,
+            target: PhantomData,
+        })
+    }
+}
+
+/// Merge `patch` into `target` following RFC 7386
+pub fn merge(target: &mut Value, patch: &Value) {
+    let patch = match patch {
+        Value::Object(patch) => patch,
+        _ => {
+            *target = patch.clone();
+            return;
+        }
+    };
+
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+
+    // Was made an object above
+    let target = target.as_object_mut().unwrap();
+    for (key, value) in patch {
+        match value {
+            Value::Null => {
+                target.remove(key);
+            }
+            _ => merge(target.entry(key.clone()).or_insert(Value::Null), value),
+        }
+    }
+}
+
+/// A JSON Patch (RFC 6902) request body
+///
+/// A JSON Patch is a list of operations on parts of the target document, identified by JSON
+/// Pointers (RFC 6901). It's applied atomically: if any operation fails (for example because
+/// a path doesn't exist or a `test` operation doesn't match), the target is left unchanged and
+/// a `PatchFailed` error (`409 Conflict`) is returned.
+///
+/// The extractor accepts bodies of up to `MAX_LEN` bytes with an
+/// `application/json-patch+json` or `application/json` content type. Documents with invalid
+/// operations or pointers are rejected with a `BodyDecodeJson` error.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(transparent)]
+pub struct JsonPatch(pub Vec<Operation>);
+
+impl JsonPatch {
+    /// Apply the patch to `target`
+    ///
+    /// Fails with a `BodyDecodeJson` error if the patched document is not a valid `T`.
+    pub fn apply<T: Serialize + DeserializeOwned>(&self, target: &mut T) -> Result<(), Error> {
+        let mut doc = to_value(target)?;
+        self.apply_value(&mut doc)?;
+        *target = from_value(doc)?;
+        Ok(())
+    }
+
+    /// Apply the patch to a JSON document
+    pub fn apply_value(&self, doc: &mut Value) -> Result<(), ErrorKind> {
+        let mut patched = doc.clone();
+        for op in &self.0 {
+            op.apply(&mut patched).ok_or(ErrorKind::PatchFailed)?;
+        }
+        *doc = patched;
+        Ok(())
+    }
+
+    /// Check that all pointers in the patch are valid
+    pub fn validate(&self) -> Result<(), ErrorKind> {
+        for op in &self.0 {
+            let valid = match op {
+                Operation::Add { path, .. }
+                | Operation::Remove { path }
+                | Operation::Replace { path, .. }
+                | Operation::Test { path, .. } => parse_pointer(path).is_some(),
+                Operation::Move { from, path } | Operation::Copy { from, path } => {
+                    parse_pointer(from).is_some() && parse_pointer(path).is_some()
+                }
+            };
+
+            if !valid {
+                return Err(ErrorKind::BodyDecodeJson);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl<'a, A> FromContextAsync<'a, A> for JsonPatch
+where
+    A: Application + Sync,
+    A::RequestBody: HttpBody + Send,
+    <A::RequestBody as HttpBody>::Data: Send,
+    <A::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
+{
+    async fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let body = match body.take() {
+            Some(body) => body,
+            None => panic!("attempted to retrieve body twice"),
+        };
+
+        let patch = read_json::<A, _>(req, state, body, &[JSON_PATCH, JSON]).await?;
+        let patch = from_value::<JsonPatch>(patch).map_err(|e| state.reject::<A>(e, req))?;
+        match patch.validate() {
+            Ok(()) => Ok(patch),
+            Err(kind) => Err(state.reject::<A>(kind.into(), req)),
+        }
+    }
+}
+
+/// A single JSON Patch operation
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(tag = "op", rename_all = "lowercase")]
+pub enum Operation {
+    Add { path: String, value: Value },
+    Remove { path: String },
+    Replace { path: String, value: Value },
+    Move { from: String, path: String },
+    Copy { from: String, path: String },
+    Test { path: String, value: Value },
+}
+
+impl Operation {
+    fn apply(&self, doc: &mut Value) -> Option<()> {
+        match self {
+            Self::Add { path, value } => add(doc, &parse_pointer(path)?, value.clone()),
+            Self::Remove { path } => remove(doc, &parse_pointer(path)?).map(|_| ()),
+            Self::Replace { path, value } => {
+                *get_mut(doc, &parse_pointer(path)?)? = value.clone();
+                Some(())
+            }
+            Self::Move { from, path } => {
+                let (from, path) = (parse_pointer(from)?, parse_pointer(path)?);
+                if from == path {
+                    return get_mut(doc, &from).map(|_| ());
+                }
+                // A value can't be moved into one of its children
+                if path.starts_with(&from) {
+                    return None;
+                }
+                let value = remove(doc, &from)?;
+                add(doc, &path, value)
+            }
+            Self::Copy { from, path } => {
+                let value = get_mut(doc, &parse_pointer(from)?)?.clone();
+                add(doc, &parse_pointer(path)?, value)
+            }
+            Self::Test { path, value } => match get_mut(doc, &parse_pointer(path)?)? == value {
+                true => Some(()),
+                false => None,
+            },
+        }
+    }
+}
+
+fn add(doc: &mut Value, path: &[String], value: Value) -> Option<()> {
+    let (last, parent) = match path.split_last() {
+        Some(split) => split,
+        None => {
+            *doc = value;
+            return Some(());
+        }
+    };
+
+    match get_mut(doc, parent)? {
+        Value::Object(map) => {
+            map.insert(last.clone(), value);
+        }
+        Value::Array(array) => {
+            let index = match last.as_str() {
+                "-" => array.len(),
+                index => parse_index(index).filter(|&i| i <= array.len())?,
+            };
+            array.insert(index, value);
+        }
+        _ => return None,
+    }
+
+    Some(())
+}
+
+fn remove(doc: &mut Value, path: &[String]) -> Option<Value> {
+    let (last, parent) = path.split_last()?;
+    match get_mut(doc, parent)? {
+        Value::Object(map) => map.remove(last),
+        Value::Array(array) => {
+            let index = parse_index(last).filter(|&i| i < array.len())?;
+            Some(array.remove(index))
+        }
+        _ => None,
+    }
+}
+
+fn get_mut<'a>(doc: &'a mut Value, path: &[String]) -> Option<&'a mut Value> {
+    path.iter().try_fold(doc, |value, token| match value {
+        Value::Object(map) => map.get_mut(token),
+        Value::Array(array) => array.get_mut(parse_index(token)?),
+        _ => None,
+    })
+}
+
+/// Split a JSON Pointer into its unescaped reference tokens
+fn parse_pointer(pointer: &str) -> Option<Vec<String>> {
+    if pointer.is_empty() {
+        return Some(Vec::new());
+    }
+
+    pointer
+        .strip_prefix('/')?
+        .split('/')
+        .map(|token| {
+            let mut unescaped = String::with_capacity(token.len());
+            let mut chars = token.chars();
+            while let Some(c) = chars.next() {
+                unescaped.push(match c {
+                    '~' => match chars.next()? {
+                        '0' => '~',
+                        '1' => '/',
+                        _ => return None,
+                    },
+                    c => c,
+                });
+            }
+            Some(unescaped)
+        })
+        .collect()
+}
+
+/// Parse an array index, which must not have leading zeros
+fn parse_index(token: &str) -> Option<usize> {
+    match token.bytes().all(|b| b.is_ascii_digit()) && (token == "0" || !token.starts_with('0')) {
+        true => token.parse().ok(),
+        false => None,
+    }
+}
+
+async fn read_json<A, B>(
+    req: &Parts,
+    state: &mut PathState,
+    body: B,
+    types: &[&str],
+) -> Result<Value, A::Error>
+where
+    A: Application,
+    B: HttpBody,
+    B::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
+{
+    check_content_type(req, types).map_err(|e| state.reject::<A>(e, req))?;
+    let bytes = read_body(body, MAX_LEN)
+        .await
+        .map_err(|e| state.reject::<A>(e, req))?;
+    serde_json::from_slice(&bytes).map_err(|e| state.reject::<A>(e.into(), req))
+}
+
+fn to_value<T: Serialize>(value: &T) -> Result<Value, Error> {
+    serde_json::to_value(value).map_err(|e| Error::from(ErrorKind::Other).with_source(e))
+}
+
+fn from_value<T: DeserializeOwned>(value: Value) -> Result<T, Error> {
+    serde_json::from_value(value).map_err(Error::from)
+}
+
+/// The maximum size of patch request bodies, in bytes
+pub const MAX_LEN: usize = 1024 * 1024;
+
+const JSON: &str = "application/json";
+const MERGE_PATCH: &str = "application/merge-patch+json";
+const JSON_PATCH: &str = "application/json-patch+json";
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn merge_patch() {
+        // Examples from RFC 7386, appendix A
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+
+        for (mut target, patch, expected) in cases {
+            merge(&mut target, &patch);
+            assert_eq!(target, expected);
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Post {
+            title: String,
+            draft: bool,
+        }
+
+        let mut post = Post {
+            title: "Hello".into(),
+            draft: true,
+        };
+        MergePatch::new(json!({"draft": false}))
+            .apply(&mut post)
+            .unwrap();
+        assert!(!post.draft);
+
+        let err = MergePatch::new(json!({"title": null}))
+            .apply(&mut post)
+            .unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::BodyDecodeJson);
+        assert_eq!(post.title, "Hello");
+    }
+
+    #[test]
+    fn json_patch() {
+        let patch = serde_json::from_value::<JsonPatch>(json!([
+            {"op": "test", "path": "/a~1b/0", "value": 1},
+            {"op": "add", "path": "/list/-", "value": 4},
+            {"op": "add", "path": "/list/0", "value": 0},
+            {"op": "remove", "path": "/gone"},
+            {"op": "replace", "path": "/x~0y", "value": "new"},
+            {"op": "move", "from": "/a~1b", "path": "/moved"},
+            {"op": "copy", "from": "/list/1", "path": "/copied"},
+        ]))
+        .unwrap();
+        patch.validate().unwrap();
+
+        let mut doc = json!({"a/b": [1], "list": [1, 2, 3], "gone": true, "x~y": "old"});
+        patch.apply_value(&mut doc).unwrap();
+        assert_eq!(
+            doc,
+            json!({
+                "list": [0, 1, 2, 3, 4],
+                "x~y": "new",
+                "moved": [1],
+                "copied": 1,
+            })
+        );
+
+        let original = doc.clone();
+        let failing = [
+            json!([{"op": "test", "path": "/copied", "value": 2}]),
+            json!([{"op": "remove", "path": "/missing"}]),
+            json!([{"op": "add", "path": "/list/9", "value": 1}]),
+            json!([{"op": "replace", "path": "/list/01", "value": 1}]),
+            json!([{"op": "move", "from": "/list", "path": "/list/0"}]),
+            json!([
+                {"op": "add", "path": "/new", "value": 1},
+                {"op": "add", "path": "/missing/child", "value": 1},
+            ]),
+        ];
+        for patch in failing {
+            let patch = serde_json::from_value::<JsonPatch>(patch).unwrap();
+            assert_eq!(patch.apply_value(&mut doc), Err(ErrorKind::PatchFailed));
+            assert_eq!(doc, original);
+        }
+
+        let invalid = json!([{"op": "add", "path": "no-slash", "value": 1}]);
+        let invalid = serde_json::from_value::<JsonPatch>(invalid).unwrap();
+        assert_eq!(invalid.validate(), Err(ErrorKind::BodyDecodeJson));
+        assert!(serde_json::from_value::<JsonPatch>(json!([{"op": "add", "path": ""}])).is_err());
+    }
+}
@@ -0,0 +1,322 @@
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use http::header::{ETAG, IF_MATCH};
+use http::request::Parts;
+use http::{HeaderValue, Response};
+
+use crate::application::{Application, ErrorKind, FromContext, PathState};
+
+/// An entity tag identifying a version of a resource
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ETag {
+    tag: String,
+    weak: bool,
+}
+
+impl ETag {
+    /// A strong tag with the given opaque value
+    ///
+    /// Returns `None` if `tag` contains characters not allowed in entity tags, like `"`.
+    pub fn new(tag: impl Into<String>) -> Option<Self> {
+        let tag = tag.into();
+        match tag.bytes().all(is_etag_char) {
+            true => Some(Self { tag, weak: false }),
+            false => None,
+        }
+    }
+
+    /// A strong tag for a resource's version number or counter
+    ///
+    /// Used together with a version column that is incremented on every update, this makes
+    /// `IfMatch::version()` return the version the client last saw.
+    pub fn version(version: impl fmt::Display) -> Self {
+        // Numbers and other `Display` output without quotes or whitespace are valid tags
+        let tag = version.to_string();
+        debug_assert!(tag.bytes().all(is_etag_char));
+        Self { tag, weak: false }
+    }
+
+    /// Mark the tag as weak, for resources that are equivalent but not byte-for-byte equal
+    ///
+    /// Weak tags never satisfy `If-Match`.
+    pub fn weak(mut self) -> Self {
+        self.weak = true;
+        self
+    }
+
+    /// The opaque value of the tag, without quotes
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Set the `ETag` header on `rsp`
+    pub fn apply<B>(&self, rsp: &mut Response<B>) {
+        if let Ok(value) = HeaderValue::try_from(self.to_string()) {
+            rsp.headers_mut().insert(ETAG, value);
+        }
+    }
+
+    /// Compare two tags using the strong comparison function
+    fn strong_eq(&self, other: &Self) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+}
+
+impl FromStr for ETag {
+    type Err = ErrorKind;
+
+    /// Parse a quoted tag like `"abc"` or `W/"abc"`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (weak, s) = match s.strip_prefix("W/") {
+            Some(s) => (true, s),
+            None => (false, s),
+        };
+
+        match s.strip_prefix('"').and_then(|s| s.strip_suffix('"')) {
+            Some(tag) if tag.bytes().all(is_etag_char) => Ok(Self {
+                tag: tag.to_owned(),
+                weak,
+            }),
+            _ => Err(ErrorKind::PreconditionFailed),
+        }
+    }
+}
+
+impl fmt::Display for ETag {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            f.write_str("W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// The `If-Match` precondition from the request
+///
+/// Clients send the `ETag` they last saw with updates, so that an update based on an outdated
+/// version of the resource is rejected instead of silently overwriting someone else's change:
+///
+/// ```no_run
+/// # #[cfg(feature = "body-util")]
+/// # mod example {
+/// # use mendes::application::ErrorKind;
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::precondition::{ETag, IfMatch};
+/// # use mendes::{handler, Application, Body, Error};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # struct PostForm {}
+/// # struct Post {
+/// #     version: u64,
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn update_post(&self, id: u64, version: u64, form: &PostForm) -> Result<Option<Post>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # fn json(post: &Post) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(PUT)]
+/// async fn update(
+///     app: &App,
+///     req: &Parts,
+///     if_match: IfMatch,
+///     id: u64,
+///     body: Body,
+/// ) -> Result<Response<Body>, Error> {
+///     let form = App::from_body::<PostForm>(req, body, 16 * 1024).await?;
+///     let version = if_match.version::<u64>()?.ok_or(ErrorKind::PreconditionRequired)?;
+///     // UPDATE posts SET ..., version = version + 1 WHERE id = $1 AND version = $2
+///     let post = app.db.update_post(id, version, &form).await?;
+///     let post = post.ok_or(ErrorKind::PreconditionFailed)?;
+///
+///     let mut rsp = json(&post)?;
+///     ETag::version(post.version).apply(&mut rsp);
+///     Ok(rsp)
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// Checking the version in the same statement as the update makes the check atomic. For
+/// resources that are loaded before being updated, `check()` compares against the current
+/// tag instead. Failed checks result in a `412 Precondition Failed` response, and a missing
+/// header where one is required in `428 Precondition Required`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum IfMatch {
+    /// The request has no `If-Match` header
+    Absent,
+    /// `If-Match: *`, which matches any existing version of the resource
+    Any,
+    /// The tags listed in the header; tags that fail to parse are left out
+    Tags(Vec<ETag>),
+}
+
+impl IfMatch {
+    /// Get the precondition from the request headers
+    pub fn from_request(req: &Parts) -> Self {
+        let mut values = req.headers.get_all(IF_MATCH).iter().peekable();
+        if values.peek().is_none() {
+            return Self::Absent;
+        }
+
+        let mut tags = Vec::new();
+        for value in values {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            for tag in value.split(',').map(str::trim) {
+                if tag == "*" {
+                    return Self::Any;
+                }
+                if let Ok(tag) = tag.parse() {
+                    tags.push(tag);
+                }
+            }
+        }
+
+        Self::Tags(tags)
+    }
+
+    /// Check the precondition against the `current` tag of an existing resource
+    ///
+    /// Requests without an `If-Match` header pass; use `require()` to reject those.
+    pub fn check(&self, current: &ETag) -> Result<(), ErrorKind> {
+        match self {
+            Self::Absent | Self::Any => Ok(()),
+            Self::Tags(tags) if tags.iter().any(|tag| tag.strong_eq(current)) => Ok(()),
+            Self::Tags(_) => Err(ErrorKind::PreconditionFailed),
+        }
+    }
+
+    /// Like `check()`, but reject requests without an `If-Match` header
+    pub fn require(&self, current: &ETag) -> Result<(), ErrorKind> {
+        match self {
+            Self::Absent => Err(ErrorKind::PreconditionRequired),
+            _ => self.check(current),
+        }
+    }
+
+    /// The version from a tag created with `ETag::version()`
+    ///
+    /// Returns `None` for requests without a specific version (no header or `*`), and fails with
+    /// `PreconditionFailed` if the header doesn't contain exactly one strong tag of type `T`.
+    pub fn version<T: FromStr>(&self) -> Result<Option<T>, ErrorKind> {
+        match self {
+            Self::Absent | Self::Any => Ok(None),
+            Self::Tags(tags) => match tags.as_slice() {
+                [tag] if !tag.weak => match tag.tag.parse() {
+                    Ok(version) => Ok(Some(version)),
+                    Err(_) => Err(ErrorKind::PreconditionFailed),
+                },
+                _ => Err(ErrorKind::PreconditionFailed),
+            },
+        }
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for IfMatch {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(Self::from_request(req))
+    }
+}
+
+/// Characters allowed in an entity tag, between the quotes
+fn is_etag_char(b: u8) -> bool {
+    b == 0x21 || (0x23..=0x7e).contains(&b) || b >= 0x80
+}
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::*;
+
+    fn if_match(value: Option<&str>) -> IfMatch {
+        let mut req = Request::put("/posts/1");
+        if let Some(value) = value {
+            req = req.header(IF_MATCH, value);
+        }
+        IfMatch::from_request(&req.body(()).unwrap().into_parts().0)
+    }
+
+    #[test]
+    fn etag() {
+        let tag = ETag::version(42);
+        assert_eq!(tag.to_string(), "\"42\"");
+        assert_eq!("\"42\"".parse::<ETag>().unwrap(), tag);
+        assert_eq!("W/\"42\"".parse::<ETag>().unwrap(), tag.clone().weak());
+        assert!("42".parse::<ETag>().is_err());
+        assert_eq!(ETag::new("a\"b"), None);
+
+        let mut rsp = Response::new(());
+        tag.weak().apply(&mut rsp);
+        assert_eq!(rsp.headers()[ETAG], "W/\"42\"");
+    }
+
+    #[test]
+    fn check() {
+        let current = ETag::version(3);
+        assert_eq!(if_match(None), IfMatch::Absent);
+        assert_eq!(if_match(None).check(&current), Ok(()));
+        assert_eq!(
+            if_match(None).require(&current),
+            Err(ErrorKind::PreconditionRequired)
+        );
+        assert_eq!(if_match(Some("*")).require(&current), Ok(()));
+        assert_eq!(if_match(Some("\"2\", \"3\"")).require(&current), Ok(()));
+        assert_eq!(
+            if_match(Some("\"2\"")).check(&current),
+            Err(ErrorKind::PreconditionFailed)
+        );
+        assert_eq!(
+            if_match(Some("W/\"3\"")).check(&current),
+            Err(ErrorKind::PreconditionFailed)
+        );
+        assert_eq!(
+            if_match(Some("garbage")).check(&current),
+            Err(ErrorKind::PreconditionFailed)
+        );
+    }
+
+    #[test]
+    fn version() {
+        assert_eq!(if_match(None).version::<u64>(), Ok(None));
+        assert_eq!(if_match(Some("*")).version::<u64>(), Ok(None));
+        assert_eq!(if_match(Some("\"7\"")).version::<u64>(), Ok(Some(7)));
+        for value in ["\"x\"", "W/\"7\"", "\"7\", \"8\""] {
+            assert_eq!(
+                if_match(Some(value)).version::<u64>(),
+                Err(ErrorKind::PreconditionFailed)
+            );
+        }
+    }
+}
@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http::header::{HeaderName, CONTENT_TYPE, RETRY_AFTER};
+use http::{HeaderValue, Response, StatusCode};
+
+/// Budgets of requests (or bytes, or any other unit) per client per period
+///
+/// Usage is counted in a `QuotaStore`, keyed by whatever identifies the client, like an API
+/// key or a claim from its token. Check the quota before handling the request:
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::quota::{MemoryStore, Quotas};
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # fn unauthorized() -> Response<Body> {
+/// #     todo!()
+/// # }
+/// # struct App {
+/// #     quotas: Quotas<MemoryStore>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     let key = match cx.req.headers.get("x-api-key").and_then(|v| v.to_str().ok()) {
+///         Some(key) => key.to_owned(),
+///         None => return unauthorized(),
+///     };
+///
+///     let usage = cx.app.quotas.check(&key).await;
+///     if usage.exceeded() {
+///         return usage.response();
+///     }
+///
+///     let mut rsp = route!(match cx.path() {
+///         Some("orders") => orders,
+///     });
+///     usage.apply(&mut rsp);
+///     rsp
+/// }
+/// # }
+/// # #[handler(GET)]
+/// # async fn orders(_: &App) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// Byte budgets can be enforced by charging the bytes counted by a `Transfer` once the
+/// response is done, and checking with `usage()` before handling later requests.
+///
+/// Calendar periods follow UTC, so daily quotas reset at midnight UTC and monthly quotas at
+/// the start of the month. With `Period::Window`, quotas work as a rate limiter.
+///
+/// To enforce limits across several instances of the application, use a store shared by
+/// all of them, like a `CacheStore` backed by Redis (which updates counters atomically).
+pub struct Quotas<S> {
+    store: S,
+    limit: u64,
+    period: Period,
+}
+
+impl<S: QuotaStore> Quotas<S> {
+    /// Allow `limit` units per `period` per key
+    pub fn new(store: S, limit: u64, period: Period) -> Self {
+        Self {
+            store,
+            limit,
+            period,
+        }
+    }
+
+    /// Count a single request for `key`
+    ///
+    /// Requests are counted even if the quota has already been exceeded.
+    pub async fn check(&self, key: &str) -> Usage {
+        self.charge(key, 1).await
+    }
+
+    /// Count `amount` units for `key`
+    pub async fn charge(&self, key: &str, amount: u64) -> Usage {
+        let now = SystemTime::now();
+        let (window, reset) = self.period.window(now);
+        let length = match self.period {
+            Period::Window(length) => length,
+            _ => {
+                let used = self.store.add(key, window, amount, reset).await;
+                return Usage {
+                    limit: self.limit,
+                    used,
+                    reset,
+                };
+            }
+        };
+
+        // Keep counts for an extra window, since they're used to estimate the next one
+        let used = self.store.add(key, window, amount, reset + length).await;
+        let previous = self
+            .store
+            .add(key, window.saturating_sub(1), 0, reset)
+            .await;
+        let overlap = reset.duration_since(now).unwrap_or_default().as_millis();
+        let weighted = u128::from(previous) * overlap / length.as_millis().max(1);
+        Usage {
+            limit: self.limit,
+            used: used + weighted as u64,
+            reset,
+        }
+    }
+
+    /// The current usage for `key`, without counting anything
+    pub async fn usage(&self, key: &str) -> Usage {
+        self.charge(key, 0).await
+    }
+}
+
+/// The period after which a quota resets
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Period {
+    Day,
+    Month,
+    /// A rolling window of the given length, for rate limits like 100 requests per minute
+    ///
+    /// Usage is estimated from the counts for the current and the previous fixed window,
+    /// weighing the latter by how much of it overlaps the rolling window. This avoids the
+    /// bursts that fixed windows allow around their boundaries, while only needing atomic
+    /// counters from the store.
+    Window(Duration),
+}
+
+impl Period {
+    /// An identifier for the period containing `time`, and the time at which it ends
+    fn window(self, time: SystemTime) -> (u64, SystemTime) {
+        let days = time
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs()
+            / DAY;
+        match self {
+            Period::Day => (days, UNIX_EPOCH + Duration::from_secs((days + 1) * DAY)),
+            Period::Month => {
+                let (year, month) = year_month(days);
+                let month = year * 12 + month - 1;
+                let next = month + 1;
+                let end = days_from_civil(next / 12, next % 12 + 1);
+                (month, UNIX_EPOCH + Duration::from_secs(end * DAY))
+            }
+            Period::Window(length) => {
+                let length = length.as_millis().max(1);
+                let millis = time
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_millis();
+                let window = millis / length;
+                let end = ((window + 1) * length) as u64;
+                (window as u64, UNIX_EPOCH + Duration::from_millis(end))
+            }
+        }
+    }
+}
+
+/// The usage of a quota after counting a request
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Usage {
+    pub limit: u64,
+    pub used: u64,
+    /// The time at which the quota resets
+    pub reset: SystemTime,
+}
+
+impl Usage {
+    pub fn exceeded(&self) -> bool {
+        self.used > self.limit
+    }
+
+    pub fn remaining(&self) -> u64 {
+        self.limit.saturating_sub(self.used)
+    }
+
+    /// Add `X-RateLimit-Limit`, `X-RateLimit-Remaining` and `X-RateLimit-Reset` headers
+    ///
+    /// The reset header contains the number of seconds until the quota resets.
+    pub fn apply<B>(&self, rsp: &mut Response<B>) {
+        let headers = rsp.headers_mut();
+        headers.insert(LIMIT, HeaderValue::from(self.limit));
+        headers.insert(REMAINING, HeaderValue::from(self.remaining()));
+        headers.insert(RESET, HeaderValue::from(self.reset_after().as_secs()));
+    }
+
+    /// A `429 Too Many Requests` response with quota headers and a `Retry-After` header
+    pub fn response<B: From<Bytes>>(&self) -> Response<B> {
+        let mut rsp = Response::new(B::from(Bytes::from_static(b"quota exceeded")));
+        *rsp.status_mut() = StatusCode::TOO_MANY_REQUESTS;
+        self.apply(&mut rsp);
+        let headers = rsp.headers_mut();
+        let retry_after = self.reset_after().as_secs();
+        headers.insert(RETRY_AFTER, HeaderValue::from(retry_after));
+        headers.insert(
+            CONTENT_TYPE,
+            HeaderValue::from_static("text/plain; charset=utf-8"),
+        );
+        rsp
+    }
+
+    fn reset_after(&self) -> Duration {
+        // Round up, so clients don't retry just before the reset
+        let after = self
+            .reset
+            .duration_since(SystemTime::now())
+            .unwrap_or_default();
+        Duration::from_secs(after.as_secs() + u64::from(after.subsec_nanos() > 0))
+    }
+}
+
+/// Storage for quota usage, like a database table or a Redis instance
+///
+/// Use a persistent store shared by all instances of the application to enforce quotas
+/// across restarts and instances.
+#[async_trait]
+pub trait QuotaStore: Send + Sync {
+    /// Add `amount` to the usage for `key` in `window` and return the new total
+    ///
+    /// Usage for a window is no longer needed once it `expires`. Implementations must make
+    /// this atomic, so concurrent requests can't exceed the quota.
+    async fn add(&self, key: &str, window: u64, amount: u64, expires: SystemTime) -> u64;
+}
+
+/// A `QuotaStore` keeping usage in memory, for a single instance of the application
+///
+/// Usage is lost when the process exits.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    usage: Mutex<HashMap<(String, u64), (u64, SystemTime)>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl QuotaStore for MemoryStore {
+    async fn add(&self, key: &str, window: u64, amount: u64, expires: SystemTime) -> u64 {
+        let now = SystemTime::now();
+        let mut usage = self.usage.lock().unwrap();
+        usage.retain(|_, (_, expires)| *expires > now);
+        let (used, _) = usage
+            .entry((key.to_owned(), window))
+            .or_insert((0, expires));
+        *used += amount;
+        *used
+    }
+}
+
+/// The year and month (1-12) for the given number of days since the Unix epoch
+///
+/// This is the `civil_from_days()` algorithm from <http://howardhinnant.github.io/date_algorithms.html>.
+fn year_month(days: u64) -> (u64, u64) {
+    let z = days + 719468;
+    let era = z / 146097;
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month)
+}
+
+/// The number of days since the Unix epoch for the first day of the given month
+fn days_from_civil(year: u64, month: u64) -> u64 {
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+const DAY: u64 = 24 * 60 * 60;
+const LIMIT: HeaderName = HeaderName::from_static("x-ratelimit-limit");
+const REMAINING: HeaderName = HeaderName::from_static("x-ratelimit-remaining");
+const RESET: HeaderName = HeaderName::from_static("x-ratelimit-reset");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn windows() {
+        // 2024-02-29T12:00:00Z
+        let time = UNIX_EPOCH + Duration::from_secs(1_709_208_000);
+        let (day, reset) = Period::Day.window(time);
+        assert_eq!(day, 19782);
+        assert_eq!(reset, UNIX_EPOCH + Duration::from_secs(1_709_251_200));
+
+        let (month, reset) = Period::Month.window(time);
+        assert_eq!(month, 2024 * 12 + 1);
+        // 2024-03-01T00:00:00Z
+        assert_eq!(reset, UNIX_EPOCH + Duration::from_secs(1_709_251_200));
+
+        // 2023-12-15T00:00:00Z
+        let time = UNIX_EPOCH + Duration::from_secs(1_702_598_400);
+        let (_, reset) = Period::Month.window(time);
+        // 2024-01-01T00:00:00Z
+        assert_eq!(reset, UNIX_EPOCH + Duration::from_secs(1_704_067_200));
+
+        let (minute, reset) = Period::Window(Duration::from_secs(60)).window(time);
+        assert_eq!(minute, 1_702_598_400 / 60);
+        assert_eq!(reset, time + Duration::from_secs(60));
+    }
+
+    #[tokio::test]
+    async fn quota() {
+        let quotas = Quotas::new(MemoryStore::new(), 2, Period::Day);
+        assert_eq!(quotas.check("a").await.remaining(), 1);
+        assert!(!quotas.check("a").await.exceeded());
+        assert!(!quotas.check("b").await.exceeded());
+
+        let usage = quotas.check("a").await;
+        assert!(usage.exceeded());
+        let rsp = usage.response::<Bytes>();
+        assert_eq!(rsp.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(rsp.headers()[LIMIT], "2");
+        assert_eq!(rsp.headers()[REMAINING], "0");
+        assert!(rsp.headers().contains_key(RETRY_AFTER));
+        assert_eq!(quotas.usage("a").await.used, 3);
+    }
+
+    #[tokio::test]
+    async fn window() {
+        let length = Duration::from_secs(60);
+        let (window, _) = Period::Window(length).window(SystemTime::now());
+        let store = MemoryStore::new();
+        let expires = SystemTime::now() + length;
+        store.add("a", window - 1, 10, expires).await;
+
+        // Part of the previous window's usage still counts
+        let quotas = Quotas::new(store, 20, Period::Window(length));
+        let usage = quotas.check("a").await;
+        assert!(usage.used >= 1 && usage.used <= 11);
+        assert!(usage.reset <= SystemTime::now() + length);
+        assert_eq!(quotas.store.add("a", window, 0, expires).await, 1);
+    }
+}
@@ -0,0 +1,107 @@
+use std::error::Error as StdError;
+use std::fmt;
+
+use http::request::Parts;
+use http::{Response, StatusCode};
+
+use crate::application::{Application, Error, IntoResponse};
+
+/// Render `Report` errors that don't map to a client error
+pub trait AppWithReport: Application {
+    /// Build the response for an internal error
+    ///
+    /// By the time this is called, the error chain has been logged (if the `tracing`
+    /// feature is enabled), so the response doesn't need to contain any details.
+    fn internal_error(&self, report: &Report, req: &Parts) -> Response<Self::ResponseBody>;
+}
+
+/// A catch-all error type for applications that don't need bespoke error types
+///
+/// Any type implementing `std::error::Error` can be converted into a `Report` with `?`, so
+/// handlers can freely mix errors from different libraries. Use `Report` as the
+/// `Application::Error` type to render all errors from a single place: a `mendes::Error`
+/// produces its own response (for example, a 404 for an unknown path), while any other error
+/// has its chain logged and gets the response from `AppWithReport::internal_error()`.
+pub struct Report(Box<dyn StdError + Send + Sync + 'static>);
+
+impl Report {
+    /// Create a report from a plain message
+    pub fn msg(msg: impl fmt::Display) -> Self {
+        Self(msg.to_string().into())
+    }
+
+    /// Iterate over the error and its sources
+    pub fn chain(&self) -> impl Iterator<Item = &(dyn StdError + 'static)> {
+        let mut next = Some(&*self.0 as &(dyn StdError + 'static));
+        std::iter::from_fn(move || {
+            let cur = next?;
+            next = cur.source();
+            Some(cur)
+        })
+    }
+
+    /// Get a reference to the inner error if it is of type `E`
+    pub fn downcast_ref<E: StdError + 'static>(&self) -> Option<&E> {
+        self.0.downcast_ref()
+    }
+
+    /// Get the inner error
+    pub fn into_inner(self) -> Box<dyn StdError + Send + Sync + 'static> {
+        self.0
+    }
+}
+
+impl<E: StdError + Send + Sync + 'static> From<E> for Report {
+    fn from(error: E) -> Self {
+        Self(Box::new(error))
+    }
+}
+
+impl From<&Report> for StatusCode {
+    fn from(report: &Report) -> StatusCode {
+        match report.downcast_ref::<Error>() {
+            Some(error) => error.status(),
+            None => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl<A: AppWithReport> IntoResponse<A> for Report
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        match self.0.downcast::<Error>() {
+            Ok(error) => (*error).into_response(app, req),
+            Err(error) => {
+                let report = Report(error);
+                #[cfg(feature = "tracing")]
+                tracing::error!(
+                    method = %req.method,
+                    path = req.uri.path(),
+                    "error while handling request: {report:#}"
+                );
+                app.internal_error(&report, req)
+            }
+        }
+    }
+}
+
+/// Formats the error; the alternate format (`{:#}`) includes all of its sources
+impl fmt::Display for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0)?;
+        if f.alternate() {
+            for source in self.chain().skip(1) {
+                write!(f, ": {source}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Debug for Report {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{self:#}")
+    }
+}
@@ -0,0 +1,149 @@
+use std::io;
+
+use async_trait::async_trait;
+use http::HeaderMap;
+use thiserror::Error;
+
+pub use crate::multipart::File;
+use crate::multipart::{self, files};
+
+#[cfg(feature = "clamav")]
+#[cfg_attr(docsrs, doc(cfg(feature = "clamav")))]
+pub mod clamav;
+
+/// Scans uploaded files for malware or other unwanted content
+///
+/// Call `scan_form_data()` before deserializing a multipart form to check all of its files:
+///
+/// ```no_run
+/// # #[cfg(feature = "body-util")]
+/// # mod example {
+/// # use mendes::forms::from_form_data;
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::scan::{scan_form_data, File, UploadScanner, Verdict};
+/// # use mendes::{handler, Application, Body, Error};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # struct Upload {}
+/// # struct Scanner;
+/// # #[async_trait::async_trait]
+/// # impl UploadScanner for Scanner {
+/// #     async fn scan(&self, file: &File<'_>) -> Result<Verdict, std::io::Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     scanner: Scanner,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn upload(app: &App, req: &Parts, body: Body) -> Result<Response<Body>, Error> {
+///     let bytes = App::body_bytes(req, body, 16 * 1024 * 1024).await?;
+///     scan_form_data(&app.scanner, &req.headers, &bytes).await?;
+///     let form = from_form_data::<Upload>(&req.headers, &bytes)?;
+///     todo!()
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+#[async_trait]
+pub trait UploadScanner: Send + Sync {
+    /// Scan a single file
+    ///
+    /// Returns an error if the file could not be scanned, for example because the scanning
+    /// service is unavailable.
+    async fn scan(&self, file: &File<'_>) -> Result<Verdict, io::Error>;
+}
+
+/// The outcome of scanning a file
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Verdict {
+    Clean,
+    /// The file should be rejected, for the given reason (like the name of a virus signature)
+    Rejected(String),
+}
+
+/// Scan every file part in multipart form data `input`
+///
+/// Files are scanned one at a time, in order, stopping at the first file that is rejected or
+/// cannot be scanned.
+pub async fn scan_form_data<S: UploadScanner + ?Sized>(
+    scanner: &S,
+    headers: &HeaderMap,
+    input: &[u8],
+) -> Result<(), Error> {
+    for (field, file) in files(headers, input)? {
+        if let Verdict::Rejected(reason) = scanner.scan(&file).await? {
+            return Err(Error::Rejected {
+                field: field.to_owned(),
+                reason,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("file in field {field:?} rejected: {reason}")]
+    Rejected { field: String, reason: String },
+    #[error("unable to scan file: {0}")]
+    Failed(#[from] io::Error),
+    #[error("invalid form data: {0}")]
+    Multipart(#[from] multipart::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use http::header::CONTENT_TYPE;
+    use memchr::memmem;
+
+    use super::*;
+
+    struct Signature(&'static [u8]);
+
+    #[async_trait]
+    impl UploadScanner for Signature {
+        async fn scan(&self, file: &File<'_>) -> Result<Verdict, io::Error> {
+            Ok(match memmem::find(file.data, self.0) {
+                Some(_) => Verdict::Rejected("signature found".to_owned()),
+                None => Verdict::Clean,
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn scan() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CONTENT_TYPE,
+            "multipart/form-data; boundary=XYZ".parse().unwrap(),
+        );
+        let body = b"--XYZ\r\n\
+            Content-Disposition: form-data; name=\"title\"\r\n\r\n\
+            VIRUS\r\n\
+            --XYZ\r\n\
+            Content-Disposition: form-data; name=\"attachment\"; filename=\"a.txt\"\r\n\
+            Content-Type: text/plain\r\n\r\n\
+            some VIRUS here\r\n\
+            --XYZ--\r\n";
+
+        scan_form_data(&Signature(b"NOPE"), &headers, body)
+            .await
+            .unwrap();
+        assert!(matches!(
+            scan_form_data(&Signature(b"VIRUS"), &headers, body).await,
+            Err(Error::Rejected { field, .. }) if field == "attachment"
+        ));
+    }
+}
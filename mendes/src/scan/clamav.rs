@@ -0,0 +1,133 @@
+use std::io;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use tokio::io::{AsyncBufReadExt, AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt, BufReader};
+use tokio::net::TcpStream;
+use tokio::time::timeout;
+
+use super::{File, UploadScanner, Verdict};
+
+/// An `UploadScanner` backed by a ClamAV daemon (`clamd`) listening on TCP
+///
+/// Every file is sent over a new connection using the `INSTREAM` command. Files larger than
+/// the daemon's `StreamMaxLength` setting fail to scan rather than being accepted.
+pub struct ClamAv {
+    addr: String,
+    timeout: Duration,
+}
+
+impl ClamAv {
+    /// Create a scanner using the daemon at `addr`, like `"localhost:3310"`
+    pub fn new(addr: impl Into<String>) -> Self {
+        Self {
+            addr: addr.into(),
+            timeout: Duration::from_secs(30),
+        }
+    }
+
+    /// Give up on scanning a file after `timeout`, 30 seconds by default
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+}
+
+#[async_trait]
+impl UploadScanner for ClamAv {
+    async fn scan(&self, file: &File<'_>) -> Result<Verdict, io::Error> {
+        let scan = async {
+            let stream = TcpStream::connect(&self.addr).await?;
+            instream(stream, file.data).await
+        };
+
+        match timeout(self.timeout, scan).await {
+            Ok(result) => result,
+            Err(_) => Err(io::Error::new(io::ErrorKind::TimedOut, "scan timed out")),
+        }
+    }
+}
+
+/// Send `data` with the `INSTREAM` command and parse the reply
+async fn instream<S: AsyncRead + AsyncWrite + Unpin>(
+    mut stream: S,
+    data: &[u8],
+) -> Result<Verdict, io::Error> {
+    stream.write_all(b"zINSTREAM\0").await?;
+    for chunk in data.chunks(CHUNK_SIZE) {
+        stream
+            .write_all(&(chunk.len() as u32).to_be_bytes())
+            .await?;
+        stream.write_all(chunk).await?;
+    }
+    stream.write_all(&[0; 4]).await?;
+    stream.flush().await?;
+
+    let mut reply = Vec::new();
+    BufReader::new(stream)
+        .take(MAX_REPLY)
+        .read_until(0, &mut reply)
+        .await?;
+    if reply.pop() != Some(0) {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "incomplete reply from clamd",
+        ));
+    }
+
+    let reply = String::from_utf8_lossy(&reply);
+    let result = reply.strip_prefix("stream: ").unwrap_or(&reply);
+    if result == "OK" {
+        Ok(Verdict::Clean)
+    } else if let Some(signature) = result.strip_suffix(" FOUND") {
+        Ok(Verdict::Rejected(signature.to_owned()))
+    } else {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            format!("clamd error: {result}"),
+        ))
+    }
+}
+
+const CHUNK_SIZE: usize = 64 * 1024;
+const MAX_REPLY: u64 = 4096;
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::duplex;
+
+    use super::*;
+
+    async fn clamd(reply: &'static [u8]) -> (Result<Verdict, io::Error>, Vec<u8>) {
+        let (client, mut server) = duplex(1024);
+        let server = async move {
+            let mut request = Vec::new();
+            let mut buf = [0; 256];
+            // The command, then one chunk and the terminating empty chunk
+            while request.len() < 10 + 4 + 5 + 4 {
+                let n = server.read(&mut buf).await.unwrap();
+                request.extend_from_slice(&buf[..n]);
+            }
+            server.write_all(reply).await.unwrap();
+            request
+        };
+
+        tokio::join!(instream(client, b"hello"), server)
+    }
+
+    #[tokio::test]
+    async fn scan() {
+        let (verdict, request) = clamd(b"stream: OK\0").await;
+        assert_eq!(verdict.unwrap(), Verdict::Clean);
+        assert_eq!(request, b"zINSTREAM\0\0\0\0\x05hello\0\0\0\0");
+
+        let (verdict, _) = clamd(b"stream: Eicar-Signature FOUND\0").await;
+        assert_eq!(
+            verdict.unwrap(),
+            Verdict::Rejected("Eicar-Signature".to_owned())
+        );
+
+        let (verdict, _) = clamd(b"INSTREAM size limit exceeded. ERROR\0").await;
+        assert!(verdict.is_err());
+    }
+}
@@ -0,0 +1,186 @@
+use std::any::{Any, TypeId};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+use http::request::Parts;
+
+use crate::application::{Application, Context, Error, ErrorKind, FromContext, PathState};
+
+/// Constructors for request-scoped services, like repositories or API clients
+///
+/// Register a constructor for each service type when building the application, then call
+/// `enter()` for each request to make the services available to its handlers. Handlers take
+/// a `Provide<T>` argument to get a service; each service is constructed at most once per
+/// request and cloned for every handler (or other constructor) asking for it:
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::services::{resolve, Provide, Services};
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # #[derive(Clone)]
+/// # struct Pool;
+/// # #[derive(Clone)]
+/// # struct Users(Pool);
+/// # impl Users {
+/// #     fn new(pool: Pool) -> Self {
+/// #         Users(pool)
+/// #     }
+/// # }
+/// # struct App {
+/// #     pool: Pool,
+/// #     services: Arc<Services<App>>,
+/// # }
+/// # fn services() -> Services<App> {
+/// let services = Services::new()
+///     .register(|app: &Arc<App>, _: &Parts| Ok(app.pool.clone()))
+///     .register(|app, req| Ok(Users::new(resolve::<_, Pool>(app, req)?)));
+/// # services
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+///
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     cx.app.services.clone().enter(&mut cx);
+///     route!(match cx.path() {
+///         Some("users") => users,
+///     })
+/// }
+/// # }
+///
+/// #[handler(GET)]
+/// async fn users(_: &App, Provide(users): Provide<Users>) -> Result<Response<Body>, Error> {
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Constructors must not (indirectly) depend on the service they construct.
+pub struct Services<A: Application> {
+    constructors: HashMap<TypeId, Constructor<A>>,
+}
+
+impl<A: Application + 'static> Services<A> {
+    pub fn new() -> Self {
+        Self {
+            constructors: HashMap::new(),
+        }
+    }
+
+    /// Register the `constructor` for services of type `T`, replacing any earlier one
+    ///
+    /// Constructors can get other services by calling `resolve()`.
+    pub fn register<T, F>(mut self, constructor: F) -> Self
+    where
+        T: Clone + Send + Sync + 'static,
+        F: Fn(&Arc<A>, &Parts) -> Result<T, A::Error> + Send + Sync + 'static,
+    {
+        self.constructors.insert(
+            TypeId::of::<T>(),
+            Box::new(move |app, req| Ok(Box::new(constructor(app, req)?))),
+        );
+        self
+    }
+
+    /// Make these services available to the handlers for the request in `cx`
+    pub fn enter(self: Arc<Self>, cx: &mut Context<A>) {
+        cx.req.extensions.insert(Scope {
+            services: self,
+            cache: Arc::default(),
+        });
+    }
+}
+
+impl<A: Application + 'static> Default for Services<A> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Extracts a request-scoped service of type `T`
+///
+/// Extraction fails with an internal server error if `Services::enter()` wasn't called for
+/// the request or if no constructor was registered for `T`. Errors from the constructor are
+/// returned as is.
+#[derive(Clone, Copy, Debug)]
+pub struct Provide<T>(pub T);
+
+impl<'a, A, T> FromContext<'a, A> for Provide<T>
+where
+    A: Application + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        lookup(app, req, |e| state.reject::<A>(e, req)).map(Provide)
+    }
+}
+
+/// Get the service of type `T` for the request, constructing it if necessary
+pub fn resolve<A, T>(app: &Arc<A>, req: &Parts) -> Result<T, A::Error>
+where
+    A: Application + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    lookup(app, req, |e| A::rejection(e, req))
+}
+
+fn lookup<A, T>(
+    app: &Arc<A>,
+    req: &Parts,
+    reject: impl FnOnce(Error) -> A::Error,
+) -> Result<T, A::Error>
+where
+    A: Application + 'static,
+    T: Clone + Send + Sync + 'static,
+{
+    let scope = match req.extensions.get::<Scope<A>>() {
+        Some(scope) => scope,
+        None => return Err(reject(ErrorKind::ServiceMissing.into())),
+    };
+
+    let id = TypeId::of::<T>();
+    if let Some(service) = scope.cache.lock().unwrap().get(&id) {
+        return Ok(service.downcast_ref::<T>().unwrap().clone());
+    }
+
+    let constructor = match scope.services.constructors.get(&id) {
+        Some(constructor) => constructor,
+        None => return Err(reject(ErrorKind::ServiceMissing.into())),
+    };
+
+    // Don't hold the lock while constructing, which may resolve other services
+    let service = constructor(app, req)?;
+    let mut cache = scope.cache.lock().unwrap();
+    let service = cache.entry(id).or_insert(service);
+    Ok(service.downcast_ref::<T>().unwrap().clone())
+}
+
+/// The services for a single request, stored in the request extensions
+struct Scope<A: Application> {
+    services: Arc<Services<A>>,
+    cache: Arc<Mutex<HashMap<TypeId, Box<dyn Any + Send + Sync>>>>,
+}
+
+impl<A: Application> Clone for Scope<A> {
+    fn clone(&self) -> Self {
+        Self {
+            services: self.services.clone(),
+            cache: self.cache.clone(),
+        }
+    }
+}
+
+type Constructor<A> = Box<
+    dyn Fn(&Arc<A>, &Parts) -> Result<Box<dyn Any + Send + Sync>, <A as Application>::Error>
+        + Send
+        + Sync,
+>;
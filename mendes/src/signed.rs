@@ -0,0 +1,191 @@
+use std::sync::Arc;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use data_encoding::BASE64URL_NOPAD;
+use http::request::Parts;
+use http::uri::PathAndQuery;
+use ring::hmac;
+
+use crate::application::{Application, ErrorKind, FromContext, PathState};
+
+/// Give the `Signed` extractor access to the application's `UrlSigner`
+pub trait AppWithUrlSigner: Application {
+    fn url_signer(&self) -> &UrlSigner;
+}
+
+/// Mints and verifies expiring URLs signed with HMAC-SHA256
+///
+/// Signed URLs grant access to a single resource without a session, which is useful for
+/// private downloads and for links sent by email (confirmation, unsubscribe):
+///
+/// ```no_run
+/// # use std::time::Duration;
+/// # use mendes::http::Response;
+/// # use mendes::signed::{AppWithUrlSigner, Signed, UrlSigner};
+/// # use mendes::{handler, Body, Error};
+/// # use serde::Deserialize;
+/// # struct User {
+/// #     id: u64,
+/// # }
+/// # #[derive(Deserialize)]
+/// # struct UserQuery {
+/// #     user: u64,
+/// # }
+/// # async fn send_email(user: &User, url: &str) -> Result<(), Error> {
+/// #     todo!()
+/// # }
+/// # struct App {
+/// #     signer: UrlSigner,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithUrlSigner for App {
+/// #     fn url_signer(&self) -> &UrlSigner {
+/// #         &self.signer
+/// #     }
+/// # }
+/// # async fn notify(app: &App, user: &User) -> Result<(), Error> {
+/// let url = app.url_signer().sign(&format!("/unsubscribe?user={}", user.id), Duration::from_secs(7 * 86400));
+/// send_email(&user, &format!("https://example.com{url}")).await?;
+/// # Ok(())
+/// # }
+///
+/// #[handler(GET)]
+/// async fn unsubscribe(app: &App, _: Signed, #[query] query: UserQuery) -> Result<Response<Body>, Error> {
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The signature covers the path and the whole query, including the expiry time, so none
+/// of them can be changed without invalidating the signature.
+pub struct UrlSigner {
+    key: hmac::Key,
+}
+
+impl UrlSigner {
+    /// Create a signer from a secret, which should be at least 32 random bytes
+    pub fn new(secret: &[u8]) -> Self {
+        Self {
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+        }
+    }
+
+    /// Sign `path_and_query` (like `/downloads/report.pdf?user=3`), valid for `ttl`
+    pub fn sign(&self, path_and_query: &str, ttl: Duration) -> String {
+        self.sign_until(path_and_query, SystemTime::now() + ttl)
+    }
+
+    /// Sign `path_and_query`, valid until `expires`
+    pub fn sign_until(&self, path_and_query: &str, expires: SystemTime) -> String {
+        let expires = expires
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let separator = match path_and_query.contains('?') {
+            true => '&',
+            false => '?',
+        };
+
+        let unsigned = format!("{path_and_query}{separator}{EXPIRES}={expires}");
+        let signature = hmac::sign(&self.key, unsigned.as_bytes());
+        format!(
+            "{unsigned}&{SIGNATURE}={}",
+            BASE64URL_NOPAD.encode(signature.as_ref())
+        )
+    }
+
+    /// Check the signature and expiry time of a signed path and query
+    pub fn verify(&self, path_and_query: &PathAndQuery) -> Result<SystemTime, ErrorKind> {
+        let s = path_and_query.as_str();
+        let (unsigned, signature) = s
+            .rsplit_once(&format!("&{SIGNATURE}="))
+            .ok_or(ErrorKind::SignatureInvalid)?;
+        let signature = BASE64URL_NOPAD
+            .decode(signature.as_bytes())
+            .map_err(|_| ErrorKind::SignatureInvalid)?;
+        hmac::verify(&self.key, unsigned.as_bytes(), &signature)
+            .map_err(|_| ErrorKind::SignatureInvalid)?;
+
+        let expires = unsigned
+            .rsplit_once(&format!("{EXPIRES}="))
+            .and_then(|(_, expires)| expires.parse::<u64>().ok())
+            .ok_or(ErrorKind::SignatureInvalid)?;
+        let expires = UNIX_EPOCH + Duration::from_secs(expires);
+        match SystemTime::now() < expires {
+            true => Ok(expires),
+            false => Err(ErrorKind::SignatureExpired),
+        }
+    }
+}
+
+/// Proof that the request URL was signed by the application's `UrlSigner`
+///
+/// Requests with a missing or invalid signature are rejected with a `SignatureInvalid`
+/// error, requests for expired URLs with a `SignatureExpired` error.
+#[derive(Clone, Copy, Debug)]
+pub struct Signed {
+    /// The time at which the URL expires
+    pub expires: SystemTime,
+}
+
+impl<'a, A: AppWithUrlSigner> FromContext<'a, A> for Signed {
+    fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let result = match req.uri.path_and_query() {
+            Some(path_and_query) => app.url_signer().verify(path_and_query),
+            None => Err(ErrorKind::SignatureInvalid),
+        };
+
+        match result {
+            Ok(expires) => Ok(Signed { expires }),
+            Err(kind) => Err(state.reject::<A>(kind.into(), req)),
+        }
+    }
+}
+
+const EXPIRES: &str = "expires";
+const SIGNATURE: &str = "signature";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sign() {
+        let signer = UrlSigner::new(&[1; 32]);
+        let url = signer.sign("/downloads/report.pdf?user=3", Duration::from_secs(60));
+        assert!(url.starts_with("/downloads/report.pdf?user=3&expires="));
+        assert!(signer.verify(&url.parse().unwrap()).is_ok());
+
+        let tampered = url.replace("user=3", "user=4");
+        assert_eq!(
+            signer.verify(&tampered.parse().unwrap()),
+            Err(ErrorKind::SignatureInvalid)
+        );
+
+        let other = UrlSigner::new(&[2; 32]);
+        assert_eq!(
+            other.verify(&url.parse().unwrap()),
+            Err(ErrorKind::SignatureInvalid)
+        );
+
+        let expired = signer.sign_until("/confirm", UNIX_EPOCH + Duration::from_secs(60));
+        assert!(expired.starts_with("/confirm?expires=60&signature="));
+        assert_eq!(
+            signer.verify(&expired.parse().unwrap()),
+            Err(ErrorKind::SignatureExpired)
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::Mutex;
+
+use bytes::Bytes;
+use http::{Method, Response, StatusCode};
+use tokio::sync::watch;
+
+use crate::application::{Application, Context};
+use crate::idempotency::StoredResponse;
+use crate::utils::collect;
+
+/// Share a single handler execution between concurrent identical requests
+///
+/// While a `GET` or `HEAD` request for a key is being handled, other requests for the same
+/// key wait for it to complete and get a copy of its response, instead of all hitting the
+/// database at once when a popular cache entry expires:
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::singleflight::SingleFlight;
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # struct App {
+/// #     single_flight: SingleFlight,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(cx: Context<Self>) -> Response<Body> {
+///     let app = cx.app.clone();
+///     app.single_flight
+///         .handle(cx, |mut cx| async move {
+///             route!(match cx.path() {
+///                 Some("leaderboard") => leaderboard,
+///             })
+///         })
+///         .await
+/// }
+/// # }
+/// # #[handler(GET)]
+/// # async fn leaderboard(_: &App) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// `handle()` uses the method and URI as the key, so it must only wrap responses that are
+/// the same for all clients. Use `handle_key()` for responses that depend on other parts
+/// of the request, like the session. Waiting requests run the handler themselves if the
+/// request they wait for is cancelled or its response body fails.
+#[derive(Debug, Default)]
+pub struct SingleFlight {
+    inflight: Mutex<HashMap<String, watch::Receiver<Option<StoredResponse>>>>,
+}
+
+impl SingleFlight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Handle the request in `cx` with `handler`, keyed by its method and URI
+    pub async fn handle<A, F, Fut>(&self, cx: Context<A>, handler: F) -> Response<A::ResponseBody>
+    where
+        A: Application,
+        A::ResponseBody: http_body::Body<Data = Bytes> + From<Bytes>,
+        F: FnOnce(Context<A>) -> Fut,
+        Fut: Future<Output = Response<A::ResponseBody>>,
+    {
+        let key = format!("{} {}", cx.req.method, cx.req.uri);
+        self.handle_key(key, cx, handler).await
+    }
+
+    /// Handle the request in `cx` with `handler`, sharing the response with other `GET` or
+    /// `HEAD` requests for `key`
+    pub async fn handle_key<A, F, Fut>(
+        &self,
+        key: String,
+        cx: Context<A>,
+        handler: F,
+    ) -> Response<A::ResponseBody>
+    where
+        A: Application,
+        A::ResponseBody: http_body::Body<Data = Bytes> + From<Bytes>,
+        F: FnOnce(Context<A>) -> Fut,
+        Fut: Future<Output = Response<A::ResponseBody>>,
+    {
+        if !matches!(cx.req.method, Method::GET | Method::HEAD) {
+            return handler(cx).await;
+        }
+
+        let tx = {
+            let mut inflight = self.inflight.lock().unwrap();
+            match inflight.get(&key) {
+                Some(rx) => Err(rx.clone()),
+                None => {
+                    let (tx, rx) = watch::channel(None);
+                    inflight.insert(key.clone(), rx);
+                    Ok(tx)
+                }
+            }
+        };
+
+        let tx = match tx {
+            Ok(tx) => tx,
+            Err(mut rx) => loop {
+                let stored = rx.borrow_and_update().clone();
+                if let Some(stored) = stored {
+                    return stored.into_response();
+                }
+                if rx.changed().await.is_err() {
+                    // The leading request failed or was cancelled
+                    return handler(cx).await;
+                }
+            },
+        };
+
+        // Remove the key when done, even if this future is dropped before completing
+        let _guard = Guard {
+            inflight: &self.inflight,
+            key: &key,
+        };
+
+        let (parts, body) = handler(cx).await.into_parts();
+        let body = match collect(body).await {
+            Ok(body) => body,
+            Err(_) => {
+                let mut rsp = Response::new(A::ResponseBody::from(Bytes::new()));
+                *rsp.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+                return rsp;
+            }
+        };
+
+        let stored = StoredResponse {
+            status: parts.status,
+            headers: parts.headers,
+            body,
+        };
+        let _ = tx.send(Some(stored.clone()));
+        stored.into_response()
+    }
+}
+
+struct Guard<'a> {
+    inflight: &'a Mutex<HashMap<String, watch::Receiver<Option<StoredResponse>>>>,
+    key: &'a str,
+}
+
+impl Drop for Guard<'_> {
+    fn drop(&mut self) {
+        if let Ok(mut inflight) = self.inflight.lock() {
+            inflight.remove(self.key);
+        }
+    }
+}
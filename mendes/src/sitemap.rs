@@ -0,0 +1,372 @@
+use std::borrow::Cow;
+use std::fmt::{self, Write};
+
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{Response, StatusCode};
+
+use crate::application::{Application, IntoResponse};
+use crate::html::escape_into;
+
+/// A `sitemap.xml` response
+///
+/// Since the router is a plain `match` expression, mendes can't discover routes by itself.
+/// Instead, keep the list of static pages next to the router and combine it with URLs from
+/// dynamic sources (like database records) in the handler:
+///
+/// ```no_run
+/// # use mendes::sitemap::{Sitemap, SitemapUrl};
+/// # use mendes::{handler, Error};
+/// # struct Db;
+/// # struct Post {
+/// #     slug: String,
+/// #     updated: String,
+/// # }
+/// # impl Db {
+/// #     async fn posts(&self) -> Result<std::vec::IntoIter<Post>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// const PAGES: &[&str] = &["/", "/about", "/blog"];
+///
+/// #[handler(GET)]
+/// async fn sitemap(app: &App) -> Result<Sitemap, Error> {
+///     Ok(Sitemap::new("https://example.com")
+///         .routes(PAGES.iter().copied())
+///         .extend(app.db.posts().await?.map(|post| {
+///             SitemapUrl::new(format!("/blog/{}", post.slug)).lastmod(post.updated)
+///         })))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Note that a single sitemap may contain at most 50,000 URLs.
+#[derive(Clone, Debug)]
+pub struct Sitemap {
+    base: Cow<'static, str>,
+    urls: Vec<SitemapUrl>,
+}
+
+impl Sitemap {
+    /// Create an empty sitemap
+    ///
+    /// Relative URLs (starting with a `/`) are resolved against `base`, which should contain
+    /// the scheme and host of the site.
+    pub fn new(base: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            base: base.into(),
+            urls: Vec::new(),
+        }
+    }
+
+    /// Add a URL with only a location
+    pub fn route(mut self, path: impl Into<Cow<'static, str>>) -> Self {
+        self.urls.push(SitemapUrl::new(path));
+        self
+    }
+
+    /// Add a URL with only a location for each path
+    pub fn routes<P: Into<Cow<'static, str>>>(self, paths: impl IntoIterator<Item = P>) -> Self {
+        self.extend(paths.into_iter().map(SitemapUrl::new))
+    }
+
+    /// Add a URL
+    pub fn url(mut self, url: SitemapUrl) -> Self {
+        self.urls.push(url);
+        self
+    }
+
+    /// Add all URLs from the given provider
+    pub fn extend(mut self, urls: impl IntoIterator<Item = SitemapUrl>) -> Self {
+        self.urls.extend(urls);
+        self
+    }
+
+    /// Render the sitemap as XML
+    pub fn render(&self) -> String {
+        let mut out = String::with_capacity(128 + self.urls.len() * 64);
+        out.push_str(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+        out.push('\n');
+        out.push_str(r#"<urlset xmlns="http://www.sitemaps.org/schemas/sitemap/0.9">"#);
+        out.push('\n');
+
+        let base = self.base.trim_end_matches('/');
+        for url in &self.urls {
+            out.push_str("<url><loc>");
+            if url.loc.starts_with('/') {
+                escape_into(&mut out, base);
+            }
+            escape_into(&mut out, &url.loc);
+            out.push_str("</loc>");
+            if let Some(lastmod) = &url.lastmod {
+                out.push_str("<lastmod>");
+                escape_into(&mut out, lastmod);
+                out.push_str("</lastmod>");
+            }
+            if let Some(freq) = url.changefreq {
+                write!(out, "<changefreq>{freq}</changefreq>").unwrap();
+            }
+            if let Some(priority) = url.priority {
+                write!(out, "<priority>{priority:.1}</priority>").unwrap();
+            }
+            out.push_str("</url>\n");
+        }
+
+        out.push_str("</urlset>\n");
+        out
+    }
+}
+
+impl<A: Application> IntoResponse<A> for Sitemap
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/xml; charset=utf-8")
+            .body(self.render().into())
+            .unwrap()
+    }
+}
+
+/// A single entry in a `Sitemap`
+#[derive(Clone, Debug)]
+pub struct SitemapUrl {
+    loc: Cow<'static, str>,
+    lastmod: Option<Cow<'static, str>>,
+    changefreq: Option<ChangeFreq>,
+    priority: Option<f32>,
+}
+
+impl SitemapUrl {
+    /// Create an entry for the given location
+    ///
+    /// The location can either be an absolute URL or a path starting with `/`.
+    pub fn new(loc: impl Into<Cow<'static, str>>) -> Self {
+        Self {
+            loc: loc.into(),
+            lastmod: None,
+            changefreq: None,
+            priority: None,
+        }
+    }
+
+    /// Set the date of last modification, in W3C Datetime format (like `2024-05-01`)
+    pub fn lastmod(mut self, lastmod: impl Into<Cow<'static, str>>) -> Self {
+        self.lastmod = Some(lastmod.into());
+        self
+    }
+
+    /// Set how frequently the page is likely to change
+    pub fn changefreq(mut self, changefreq: ChangeFreq) -> Self {
+        self.changefreq = Some(changefreq);
+        self
+    }
+
+    /// Set the priority relative to other URLs on the site, between 0.0 and 1.0
+    pub fn priority(mut self, priority: f32) -> Self {
+        self.priority = Some(priority.clamp(0.0, 1.0));
+        self
+    }
+}
+
+/// How frequently a page is likely to change
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ChangeFreq {
+    Always,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+    Yearly,
+    Never,
+}
+
+impl fmt::Display for ChangeFreq {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ChangeFreq::Always => "always",
+            ChangeFreq::Hourly => "hourly",
+            ChangeFreq::Daily => "daily",
+            ChangeFreq::Weekly => "weekly",
+            ChangeFreq::Monthly => "monthly",
+            ChangeFreq::Yearly => "yearly",
+            ChangeFreq::Never => "never",
+        })
+    }
+}
+
+/// A `robots.txt` response
+///
+/// Rules are grouped by user agent, in the order they were added.
+///
+/// ```no_run
+/// # use mendes::sitemap::Robots;
+/// # use mendes::{handler, Error};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn robots(_: &App) -> Result<Robots, Error> {
+///     Ok(Robots::new()
+///         .user_agent("*")
+///         .disallow("/admin/")
+///         .sitemap("https://example.com/sitemap.xml"))
+/// }
+/// # fn main() {}
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct Robots {
+    groups: Vec<RobotsGroup>,
+    sitemaps: Vec<Cow<'static, str>>,
+}
+
+impl Robots {
+    /// Create an empty `robots.txt`, which allows crawling everything
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start a group of rules for the given user agent
+    pub fn user_agent(mut self, agent: impl Into<Cow<'static, str>>) -> Self {
+        self.groups.push(RobotsGroup {
+            agent: agent.into(),
+            rules: Vec::new(),
+        });
+        self
+    }
+
+    /// Allow crawling paths with the given prefix in the current group
+    pub fn allow(self, path: impl Into<Cow<'static, str>>) -> Self {
+        self.rule("Allow", path.into())
+    }
+
+    /// Disallow crawling paths with the given prefix in the current group
+    pub fn disallow(self, path: impl Into<Cow<'static, str>>) -> Self {
+        self.rule("Disallow", path.into())
+    }
+
+    /// Reference a sitemap by its absolute URL
+    pub fn sitemap(mut self, url: impl Into<Cow<'static, str>>) -> Self {
+        self.sitemaps.push(url.into());
+        self
+    }
+
+    fn rule(mut self, kind: &'static str, path: Cow<'static, str>) -> Self {
+        if self.groups.is_empty() {
+            self = self.user_agent("*");
+        }
+        self.groups.last_mut().unwrap().rules.push((kind, path));
+        self
+    }
+}
+
+impl fmt::Display for Robots {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (i, group) in self.groups.iter().enumerate() {
+            if i > 0 {
+                writeln!(f)?;
+            }
+            writeln!(f, "User-agent: {}", group.agent)?;
+            for (kind, path) in &group.rules {
+                writeln!(f, "{kind}: {path}")?;
+            }
+        }
+
+        if !self.groups.is_empty() && !self.sitemaps.is_empty() {
+            writeln!(f)?;
+        }
+        for url in &self.sitemaps {
+            writeln!(f, "Sitemap: {url}")?;
+        }
+        Ok(())
+    }
+}
+
+impl<A: Application> IntoResponse<A> for Robots
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+            .body(self.to_string().into())
+            .unwrap()
+    }
+}
+
+#[derive(Clone, Debug)]
+struct RobotsGroup {
+    agent: Cow<'static, str>,
+    rules: Vec<(&'static str, Cow<'static, str>)>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sitemap() {
+        let sitemap = Sitemap::new("https://example.com/")
+            .routes(["/", "/about"])
+            .url(
+                SitemapUrl::new("/search?q=a&b")
+                    .lastmod("2024-05-01")
+                    .changefreq(ChangeFreq::Daily)
+                    .priority(0.8),
+            )
+            .route("https://cdn.example.com/page");
+
+        assert_eq!(
+            sitemap.render(),
+            concat!(
+                "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+                "<urlset xmlns=\"http://www.sitemaps.org/schemas/sitemap/0.9\">\n",
+                "<url><loc>https://example.com/</loc></url>\n",
+                "<url><loc>https://example.com/about</loc></url>\n",
+                "<url><loc>https://example.com/search?q=a&amp;b</loc>",
+                "<lastmod>2024-05-01</lastmod><changefreq>daily</changefreq>",
+                "<priority>0.8</priority></url>\n",
+                "<url><loc>https://cdn.example.com/page</loc></url>\n",
+                "</urlset>\n",
+            )
+        );
+    }
+
+    #[test]
+    fn robots() {
+        let robots = Robots::new()
+            .disallow("/admin/")
+            .user_agent("BadBot")
+            .disallow("/")
+            .sitemap("https://example.com/sitemap.xml");
+
+        assert_eq!(
+            robots.to_string(),
+            "User-agent: *\nDisallow: /admin/\n\nUser-agent: BadBot\nDisallow: /\n\n\
+             Sitemap: https://example.com/sitemap.xml\n"
+        );
+        assert_eq!(Robots::new().to_string(), "");
+    }
+}
@@ -0,0 +1,403 @@
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::fmt::Write;
+use std::io;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use bytes::Bytes;
+use futures_util::stream::{self, Stream};
+use http::header::{CACHE_CONTROL, CONTENT_TYPE};
+use http::request::Parts;
+use http::{Response, StatusCode};
+use http_body::{Frame, SizeHint};
+use pin_project::pin_project;
+use thiserror::Error;
+use tokio::sync::broadcast;
+use tokio::time::{interval_at, Instant, Interval, MissedTickBehavior};
+
+use crate::application::{Application, IntoResponse};
+use crate::Body;
+
+/// A `text/event-stream` response streaming Server-Sent Events
+///
+/// If the stream yields an error, the response body is aborted; browsers will reconnect
+/// automatically, sending the id of the last event they received in a `Last-Event-ID` header.
+pub struct Sse<S> {
+    events: S,
+    keep_alive: Option<Duration>,
+}
+
+impl<S> Sse<S> {
+    /// Create a response from a stream of events
+    pub fn new(events: S) -> Self {
+        Self {
+            events,
+            keep_alive: None,
+        }
+    }
+
+    /// Send a comment if no event has been sent for `interval`
+    ///
+    /// This prevents proxies from closing connections that are idle for too long.
+    pub fn keep_alive(mut self, interval: Duration) -> Self {
+        self.keep_alive = Some(interval);
+        self
+    }
+}
+
+impl<S, E> Sse<S>
+where
+    S: Stream<Item = Result<Event, E>> + Send + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Convert into a `Response` with a streaming `Body`
+    pub fn into_response(self) -> Response<Body> {
+        let keep_alive = self.keep_alive.map(|period| {
+            let mut interval = interval_at(Instant::now() + period, period);
+            interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+            interval
+        });
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/event-stream")
+            .header(CACHE_CONTROL, "no-cache")
+            .body(Body::stream(SseBody {
+                events: self.events,
+                keep_alive,
+                done: false,
+            }))
+            .unwrap()
+    }
+}
+
+impl<A, S, E> IntoResponse<A> for Sse<S>
+where
+    A: Application<ResponseBody = Body>,
+    S: Stream<Item = Result<Event, E>> + Send + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<Body> {
+        Sse::into_response(self)
+    }
+}
+
+/// A single Server-Sent Event
+#[derive(Clone, Debug, Default)]
+pub struct Event {
+    data: String,
+    event: Option<Cow<'static, str>>,
+    id: Option<Cow<'static, str>>,
+    retry: Option<Duration>,
+}
+
+impl Event {
+    /// Create an event with the given data
+    ///
+    /// Multi-line data is sent as multiple `data` fields, which clients join back together.
+    pub fn new(data: impl Into<String>) -> Self {
+        Self {
+            data: data.into(),
+            ..Self::default()
+        }
+    }
+
+    /// Create an event with the JSON representation of `data`
+    #[cfg(feature = "json")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "json")))]
+    pub fn json<T: serde::Serialize>(data: &T) -> Result<Self, serde_json::Error> {
+        Ok(Self::new(serde_json::to_string(data)?))
+    }
+
+    /// Set the event type, which selects the event listener on the client
+    pub fn event(mut self, event: impl Into<Cow<'static, str>>) -> Self {
+        self.event = Some(event.into());
+        self
+    }
+
+    /// Set the event id, which clients send back when reconnecting
+    pub fn id(mut self, id: impl Into<Cow<'static, str>>) -> Self {
+        self.id = Some(id.into());
+        self
+    }
+
+    /// Set the time clients should wait before reconnecting
+    pub fn retry(mut self, retry: Duration) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    fn encode(&self) -> String {
+        let mut out = String::with_capacity(self.data.len() + 16);
+        if let Some(event) = &self.event {
+            field(&mut out, "event", event);
+        }
+        if let Some(id) = &self.id {
+            field(&mut out, "id", id);
+        }
+        if let Some(retry) = self.retry {
+            writeln!(out, "retry: {}", retry.as_millis()).unwrap();
+        }
+        for line in self.data.split('\n') {
+            field(&mut out, "data", line.strip_suffix('\r').unwrap_or(line));
+        }
+        out.push('\n');
+        out
+    }
+}
+
+/// Write a single field, dropping any characters that would end it prematurely
+fn field(out: &mut String, name: &str, value: &str) {
+    out.push_str(name);
+    out.push_str(": ");
+    out.extend(value.chars().filter(|c| !matches!(c, '\n' | '\r')));
+    out.push('\n');
+}
+
+#[pin_project]
+struct SseBody<S> {
+    #[pin]
+    events: S,
+    keep_alive: Option<Interval>,
+    done: bool,
+}
+
+impl<S, E> http_body::Body for SseBody<S>
+where
+    S: Stream<Item = Result<Event, E>>,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+
+        match this.events.poll_next(cx) {
+            Poll::Ready(Some(Ok(event))) => {
+                if let Some(keep_alive) = this.keep_alive {
+                    keep_alive.reset();
+                }
+                return Poll::Ready(Some(Ok(Frame::data(event.encode().into()))));
+            }
+            Poll::Ready(Some(Err(error))) => {
+                *this.done = true;
+                return Poll::Ready(Some(Err(io::Error::new(io::ErrorKind::Other, error))));
+            }
+            Poll::Ready(None) => {
+                *this.done = true;
+                return Poll::Ready(None);
+            }
+            Poll::Pending => {}
+        }
+
+        match this.keep_alive {
+            Some(keep_alive) => match keep_alive.poll_tick(cx) {
+                Poll::Ready(_) => Poll::Ready(Some(Ok(Frame::data(Bytes::from_static(b":\n\n"))))),
+                Poll::Pending => Poll::Pending,
+            },
+            None => Poll::Pending,
+        }
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+/// Fans out messages from application code to any number of subscribers
+///
+/// Each subscriber has a queue of `capacity` messages. Subscribers that fall behind by more
+/// than that (like clients on a slow connection) get a `Lagged` error, which ends their `Sse`
+/// response, rather than slowing down the others.
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use std::time::Duration;
+/// # use futures_util::stream::{Stream, StreamExt};
+/// # use mendes::sse::{Broadcaster, Event, Lagged, Sse};
+/// # use mendes::{handler, Error};
+/// # #[derive(Clone)]
+/// # struct Message {
+/// #     text: String,
+/// # }
+/// # struct App {
+/// #     events: Arc<Broadcaster<Message>>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn events(app: &App) -> Result<Sse<impl Stream<Item = Result<Event, Lagged>>>, Error> {
+///     let events = app.events.subscribe().map(|msg| msg.map(|msg| Event::new(msg.text)));
+///     Ok(Sse::new(events).keep_alive(Duration::from_secs(15)))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Streams for subscribers stay open until the `Broadcaster` is closed. To let graceful
+/// shutdown complete, call `close()` from the shutdown signal future:
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use mendes::sse::Broadcaster;
+/// # struct App {
+/// #     events: Arc<Broadcaster<String>>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # #[cfg(feature = "hyper")]
+/// # async fn run() -> std::io::Result<()> {
+/// # use mendes::hyper::Server;
+/// let events = Arc::new(Broadcaster::new(16));
+/// let app = App { events: events.clone() };
+/// Server::bind("127.0.0.1:8080".parse().unwrap(), app)
+///     .await?
+///     .with_graceful_shutdown(async move {
+///         tokio::signal::ctrl_c().await.ok();
+///         events.close();
+///     })
+///     .serve()
+///     .await
+/// # }
+/// ```
+pub struct Broadcaster<T> {
+    sender: Mutex<Option<broadcast::Sender<T>>>,
+    capacity: usize,
+}
+
+impl<T: Clone + Send + 'static> Broadcaster<T> {
+    /// Create a broadcaster keeping up to `capacity` messages per subscriber
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            sender: Mutex::new(Some(broadcast::channel(capacity).0)),
+            capacity,
+        }
+    }
+
+    /// Send `msg` to all current subscribers, returning the number of subscribers
+    ///
+    /// Messages sent while there are no subscribers (or after closing) are dropped.
+    pub fn send(&self, msg: T) -> usize {
+        match &*self.sender.lock().unwrap() {
+            Some(sender) => sender.send(msg).unwrap_or(0),
+            None => 0,
+        }
+    }
+
+    /// Subscribe to messages sent after this call
+    ///
+    /// The stream ends when the broadcaster is closed or dropped.
+    pub fn subscribe(&self) -> impl Stream<Item = Result<T, Lagged>> + Send + 'static {
+        let receiver = match &*self.sender.lock().unwrap() {
+            Some(sender) => sender.subscribe(),
+            // Create a receiver that ends immediately
+            None => broadcast::channel(1).1,
+        };
+
+        stream::unfold(Some(receiver), |receiver| async move {
+            let mut receiver = receiver?;
+            match receiver.recv().await {
+                Ok(msg) => Some((Ok(msg), Some(receiver))),
+                Err(broadcast::error::RecvError::Lagged(missed)) => {
+                    Some((Err(Lagged(missed)), None))
+                }
+                Err(broadcast::error::RecvError::Closed) => None,
+            }
+        })
+    }
+
+    /// The number of current subscribers
+    pub fn subscribers(&self) -> usize {
+        match &*self.sender.lock().unwrap() {
+            Some(sender) => sender.receiver_count(),
+            None => 0,
+        }
+    }
+
+    /// The number of messages kept for each subscriber
+    pub fn capacity(&self) -> usize {
+        self.capacity
+    }
+
+    /// End all subscriber streams once they have received pending messages
+    pub fn close(&self) {
+        self.sender.lock().unwrap().take();
+    }
+}
+
+/// A subscriber missed messages because it fell too far behind
+#[derive(Debug, Error)]
+#[error("subscriber lagged behind, missing {0} message(s)")]
+pub struct Lagged(pub u64);
+
+#[cfg(test)]
+mod tests {
+    use futures_util::StreamExt;
+
+    use super::*;
+
+    #[test]
+    fn encode() {
+        let event = Event::new("line 1\r\nline 2")
+            .event("update")
+            .id("4\n2")
+            .retry(Duration::from_secs(3));
+        assert_eq!(
+            event.encode(),
+            "event: update\nid: 42\nretry: 3000\ndata: line 1\ndata: line 2\n\n"
+        );
+        assert_eq!(Event::new("").encode(), "data: \n\n");
+    }
+
+    #[tokio::test]
+    async fn broadcast() {
+        let broadcaster = Broadcaster::new(2);
+        let mut fast = Box::pin(broadcaster.subscribe());
+        let mut slow = Box::pin(broadcaster.subscribe());
+        assert_eq!(broadcaster.subscribers(), 2);
+
+        for i in 0..2 {
+            broadcaster.send(i);
+        }
+        assert_eq!(fast.next().await.unwrap().unwrap(), 0);
+        assert_eq!(fast.next().await.unwrap().unwrap(), 1);
+
+        broadcaster.send(2);
+        assert_eq!(fast.next().await.unwrap().unwrap(), 2);
+        assert!(matches!(slow.next().await, Some(Err(Lagged(1)))));
+        assert!(slow.next().await.is_none());
+
+        broadcaster.send(3);
+        broadcaster.close();
+        assert_eq!(fast.next().await.unwrap().unwrap(), 3);
+        assert!(fast.next().await.is_none());
+        assert!(Box::pin(broadcaster.subscribe()).next().await.is_none());
+    }
+}
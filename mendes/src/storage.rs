@@ -0,0 +1,159 @@
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use http::{Method, StatusCode};
+use thiserror::Error;
+
+use crate::attachment::Attachment;
+use crate::Body;
+
+mod local;
+pub use local::Local;
+
+#[cfg(feature = "s3")]
+#[cfg_attr(docsrs, doc(cfg(feature = "s3")))]
+mod s3;
+#[cfg(feature = "s3")]
+#[cfg_attr(docsrs, doc(cfg(feature = "s3")))]
+pub use s3::{Credentials, S3};
+
+/// A store for files (objects) addressed by keys like `"avatars/42.png"`
+///
+/// Objects are streamed in and out as `Body` values, so large files are never held in memory:
+///
+/// ```no_run
+/// # use mendes::application::ErrorKind;
+/// # use mendes::attachment::Attachment;
+/// # use mendes::http::header::CONTENT_TYPE;
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::storage::ObjectStore;
+/// # use mendes::{handler, Body, Error};
+/// # fn header(req: &Parts, name: mendes::http::HeaderName) -> Option<&str> {
+/// #     todo!()
+/// # }
+/// # struct Uuid;
+/// # impl Uuid {
+/// #     fn new_v4() -> String {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     storage: Box<dyn ObjectStore>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn upload(app: &App, req: &Parts, body: Body) -> Result<Response<Body>, Error> {
+///     let key = format!("uploads/{}", Uuid::new_v4());
+///     app.storage.put(&key, body, header(req, CONTENT_TYPE)).await?;
+///     todo!()
+/// }
+///
+/// #[handler(GET)]
+/// async fn download(app: &App, #[rest] key: &str) -> Result<Attachment, Error> {
+///     match app.storage.get(key).await? {
+///         Some(object) => Ok(object.into_attachment("report.pdf")),
+///         None => Err(ErrorKind::PathNotFound.into()),
+///     }
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Keys consist of `/`-separated segments, which may not be empty, `.` or `..`.
+#[async_trait]
+pub trait ObjectStore: Send + Sync {
+    /// Get the object stored under `key`, or `None` if there is none
+    async fn get(&self, key: &str) -> Result<Option<Object>, Error>;
+
+    /// Store `body` under `key`, replacing any existing object
+    async fn put(&self, key: &str, body: Body, content_type: Option<&str>) -> Result<(), Error>;
+
+    /// Remove the object stored under `key`, if any
+    async fn delete(&self, key: &str) -> Result<(), Error>;
+
+    /// A URL granting access to `key` with `method` for the next `ttl`, without credentials
+    ///
+    /// This lets clients download or upload objects directly.
+    fn presign(&self, method: &Method, key: &str, ttl: Duration) -> Result<String, Error>;
+}
+
+#[async_trait]
+impl<S: ObjectStore + ?Sized> ObjectStore for Arc<S> {
+    async fn get(&self, key: &str) -> Result<Option<Object>, Error> {
+        (**self).get(key).await
+    }
+
+    async fn put(&self, key: &str, body: Body, content_type: Option<&str>) -> Result<(), Error> {
+        (**self).put(key, body, content_type).await
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        (**self).delete(key).await
+    }
+
+    fn presign(&self, method: &Method, key: &str, ttl: Duration) -> Result<String, Error> {
+        (**self).presign(method, key, ttl)
+    }
+}
+
+/// An object retrieved from an `ObjectStore`
+pub struct Object {
+    pub body: Body,
+    pub len: Option<u64>,
+    pub content_type: Option<String>,
+}
+
+impl Object {
+    /// A download response for this object, suggesting `filename`
+    pub fn into_attachment(
+        self,
+        filename: impl Into<std::borrow::Cow<'static, str>>,
+    ) -> Attachment {
+        let mut attachment = Attachment::new(filename, self.body);
+        if let Some(len) = self.len {
+            attachment = attachment.content_length(len);
+        }
+        if let Some(content_type) = self.content_type {
+            attachment = attachment.content_type(content_type);
+        }
+        attachment
+    }
+}
+
+/// Check that `key` is a valid object key
+fn check_key(key: &str) -> Result<(), Error> {
+    let valid = key
+        .split('/')
+        .all(|segment| !matches!(segment, "" | "." | "..") && !segment.contains('\\'));
+    match valid {
+        true => Ok(()),
+        false => Err(Error::InvalidKey),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid object key")]
+    InvalidKey,
+    #[error("operation not supported by this store")]
+    Unsupported,
+    #[error("object size must be known in advance")]
+    LengthRequired,
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[cfg(feature = "s3")]
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+    #[error("storage service returned {status}: {message}")]
+    Service { status: StatusCode, message: String },
+}
@@ -0,0 +1,158 @@
+use std::future::poll_fn;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::pin::pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use http::Method;
+use http_body::Body as _;
+use tokio::fs::{self, File};
+use tokio::io::AsyncWriteExt;
+
+use super::{check_key, Error, Object, ObjectStore};
+use crate::body::ReaderBody;
+#[cfg(feature = "signed")]
+use crate::signed::UrlSigner;
+use crate::Body;
+
+/// An `ObjectStore` keeping objects as files in a local directory
+///
+/// Objects are written to a temporary file first, so readers never see partial objects.
+/// Content types are not stored.
+///
+/// With the `signed` feature, `presign()` can mint signed URLs for a handler serving the
+/// objects; otherwise it returns `Error::Unsupported`.
+pub struct Local {
+    root: PathBuf,
+    #[cfg(feature = "signed")]
+    signer: Option<(UrlSigner, String)>,
+    temp: AtomicU64,
+}
+
+impl Local {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            #[cfg(feature = "signed")]
+            signer: None,
+            temp: AtomicU64::new(0),
+        }
+    }
+
+    /// Presign URLs for objects as `{prefix}/{key}` with `signer`
+    ///
+    /// Only `GET` URLs can be presigned; the application must serve them with a handler that
+    /// takes a `Signed` argument.
+    #[cfg(feature = "signed")]
+    #[cfg_attr(docsrs, doc(cfg(feature = "signed")))]
+    pub fn presign_with(mut self, signer: UrlSigner, prefix: impl Into<String>) -> Self {
+        self.signer = Some((signer, prefix.into()));
+        self
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, Error> {
+        check_key(key)?;
+        Ok(self.root.join(key))
+    }
+
+    async fn write(&self, path: &Path, body: Body) -> Result<(), Error> {
+        let mut file = File::create(path).await?;
+        let mut body = pin!(body);
+        while let Some(frame) = poll_fn(|cx| body.as_mut().poll_frame(cx)).await {
+            if let Ok(data) = frame?.into_data() {
+                file.write_all(&data).await?;
+            }
+        }
+        file.sync_all().await?;
+        Ok(())
+    }
+}
+
+#[async_trait]
+impl ObjectStore for Local {
+    async fn get(&self, key: &str) -> Result<Option<Object>, Error> {
+        let file = match File::open(self.path(key)?).await {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+
+        let len = file.metadata().await?.len();
+        Ok(Some(Object {
+            body: Body::stream(ReaderBody::new(file).len(len)),
+            len: Some(len),
+            content_type: None,
+        }))
+    }
+
+    async fn put(&self, key: &str, body: Body, _: Option<&str>) -> Result<(), Error> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).await?;
+        }
+
+        let temp = self.temp.fetch_add(1, Ordering::Relaxed);
+        let mut temp_name = path.file_name().unwrap_or_default().to_owned();
+        temp_name.push(format!(".{}-{temp}.tmp", std::process::id()));
+        let temp = path.with_file_name(temp_name);
+
+        let result = match self.write(&temp, body).await {
+            Ok(()) => fs::rename(&temp, &path).await.map_err(Error::from),
+            Err(error) => Err(error),
+        };
+        if result.is_err() {
+            let _ = fs::remove_file(&temp).await;
+        }
+        result
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), Error> {
+        match fs::remove_file(self.path(key)?).await {
+            Err(error) if error.kind() != io::ErrorKind::NotFound => Err(error.into()),
+            _ => Ok(()),
+        }
+    }
+
+    #[allow(unused_variables)] // Depends on features
+    fn presign(&self, method: &Method, key: &str, ttl: Duration) -> Result<String, Error> {
+        check_key(key)?;
+        #[cfg(feature = "signed")]
+        if let (Some((signer, prefix)), &Method::GET) = (&self.signer, method) {
+            return Ok(signer.sign(&format!("{prefix}/{key}"), ttl));
+        }
+
+        Err(Error::Unsupported)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils::collect;
+
+    #[tokio::test]
+    async fn roundtrip() {
+        let root = std::env::temp_dir().join(format!("mendes-storage-{}", std::process::id()));
+        let store = Local::new(&root);
+
+        store
+            .put("a/b.txt", Body::from("hello"), Some("text/plain"))
+            .await
+            .unwrap();
+        let object = store.get("a/b.txt").await.unwrap().unwrap();
+        assert_eq!(object.len, Some(5));
+        assert_eq!(collect(object.body).await.unwrap(), "hello");
+
+        store.delete("a/b.txt").await.unwrap();
+        store.delete("a/b.txt").await.unwrap();
+        assert!(store.get("a/b.txt").await.unwrap().is_none());
+        assert!(matches!(
+            store.get("a/../../etc/passwd").await,
+            Err(Error::InvalidKey)
+        ));
+
+        fs::remove_dir_all(root).await.unwrap();
+    }
+}
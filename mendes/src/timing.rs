@@ -0,0 +1,265 @@
+use std::borrow::Cow;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use http::header::HeaderName;
+use http::request::Parts;
+use http::{HeaderValue, Response};
+
+use crate::application::{Application, FromContext, PathState};
+
+/// Collects timing metrics for a request to report in a `Server-Timing` response header
+///
+/// Enable collection for a request by calling `Context::server_timing()`, which stores a
+/// `ServerTiming` in the request extensions and returns a handle to it. Handlers can then take
+/// a `ServerTiming` argument to record their own metrics. Once the response has been produced,
+/// call `apply()` to add the `Server-Timing` header to it:
+///
+/// ```no_run
+/// # use mendes::http::{Response, StatusCode};
+/// # use mendes::timing::ServerTiming;
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # async fn load_user() -> Result<String, Error> {
+/// #     todo!()
+/// # }
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     let timing = cx.server_timing();
+///     let mut rsp = route!(match cx.path() {
+///         Some("hello") => hello,
+///     });
+///     timing.apply(&mut rsp);
+///     rsp
+/// }
+/// # }
+///
+/// #[handler(GET)]
+/// async fn hello(_: &App, timing: ServerTiming) -> Result<Response<Body>, Error> {
+///     let user = {
+///         let _span = timing.start("db");
+///         load_user().await?
+///     };
+///     Ok(Response::builder()
+///         .status(StatusCode::OK)
+///         .body(format!("Hello, {user}").into())
+///         .unwrap())
+/// }
+/// # fn main() {}
+/// ```
+///
+/// If timing was not enabled for a request, the extracted `ServerTiming` silently drops all
+/// metrics recorded into it.
+#[derive(Clone)]
+pub struct ServerTiming {
+    inner: Arc<Mutex<Inner>>,
+}
+
+impl ServerTiming {
+    pub(crate) fn new() -> Self {
+        Self {
+            inner: Arc::new(Mutex::new(Inner {
+                started: Instant::now(),
+                metrics: Vec::new(),
+            })),
+        }
+    }
+
+    /// Record a metric with the given name and duration
+    pub fn record(&self, name: &'static str, duration: Duration) {
+        self.push(Metric {
+            name,
+            description: None,
+            duration,
+        });
+    }
+
+    /// Record a metric with the given name, description and duration
+    pub fn record_with_description(
+        &self,
+        name: &'static str,
+        description: impl Into<Cow<'static, str>>,
+        duration: Duration,
+    ) {
+        self.push(Metric {
+            name,
+            description: Some(description.into()),
+            duration,
+        });
+    }
+
+    /// Start timing a metric with the given name
+    ///
+    /// The metric is recorded when the returned guard is dropped.
+    pub fn start(&self, name: &'static str) -> TimingGuard<'_> {
+        TimingGuard {
+            timing: self,
+            name,
+            started: Instant::now(),
+        }
+    }
+
+    /// Add the `Server-Timing` header to the given response
+    ///
+    /// This includes all metrics recorded so far, followed by a `total` metric covering the
+    /// time since timing was enabled for the request.
+    pub fn apply<B>(&self, rsp: &mut Response<B>) {
+        if let Some(value) = self.header_value() {
+            rsp.headers_mut().append(SERVER_TIMING, value);
+        }
+    }
+
+    /// Render the recorded metrics as a `Server-Timing` header value
+    pub fn header_value(&self) -> Option<HeaderValue> {
+        let inner = self.inner.lock().unwrap();
+        let mut value = String::new();
+        for metric in &inner.metrics {
+            write!(value, "{}", metric.name).unwrap();
+            if let Some(description) = &metric.description {
+                let description = description.replace(['"', '\\'], "");
+                write!(value, ";desc=\"{description}\"").unwrap();
+            }
+            write!(value, ";dur={:.1}, ", as_millis(metric.duration)).unwrap();
+        }
+
+        write!(value, "total;dur={:.1}", as_millis(inner.started.elapsed())).unwrap();
+        HeaderValue::try_from(value).ok()
+    }
+
+    fn push(&self, metric: Metric) {
+        self.inner.lock().unwrap().metrics.push(metric);
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for ServerTiming {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(match req.extensions.get::<ServerTiming>() {
+            Some(timing) => timing.clone(),
+            None => ServerTiming::new(),
+        })
+    }
+}
+
+/// Records a metric into the `ServerTiming` when dropped
+pub struct TimingGuard<'a> {
+    timing: &'a ServerTiming,
+    name: &'static str,
+    started: Instant,
+}
+
+impl Drop for TimingGuard<'_> {
+    fn drop(&mut self) {
+        self.timing.record(self.name, self.started.elapsed());
+    }
+}
+
+struct Inner {
+    started: Instant,
+    metrics: Vec<Metric>,
+}
+
+struct Metric {
+    name: &'static str,
+    description: Option<Cow<'static, str>>,
+    duration: Duration,
+}
+
+fn as_millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
+
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+#[cfg(test)]
+mod tests {
+    use std::thread::sleep;
+
+    use async_trait::async_trait;
+    use http::Request;
+
+    use super::*;
+    use crate::{Body, Context, Error};
+
+    #[test]
+    fn guard() {
+        let timing = ServerTiming::new();
+        {
+            let _guard = timing.start("db");
+            assert!(header(&timing).starts_with("total;dur="));
+            sleep(Duration::from_millis(5));
+        }
+
+        let value = header(&timing);
+        let duration = value
+            .strip_prefix("db;dur=")
+            .and_then(|rest| rest.split(',').next())
+            .unwrap();
+        assert!(duration.parse::<f64>().unwrap() >= 5.0, "{value}");
+        assert!(value.contains(", total;dur="), "{value}");
+    }
+
+    #[test]
+    fn description() {
+        // Quotes and backslashes can't end the quoted `desc` early
+        let timing = ServerTiming::new();
+        timing.record_with_description("cache", r#"say "hi" \"#, Duration::from_millis(2));
+        let value = header(&timing);
+        assert!(
+            value.starts_with(r#"cache;desc="say hi ";dur=2.0, total;dur="#),
+            "{value}"
+        );
+    }
+
+    #[test]
+    fn extractor() {
+        // Without timing enabled, metrics go nowhere
+        let mut cx = Context::new(Arc::new(App), Request::new(()));
+        extract(&mut cx).record("db", Duration::from_millis(1));
+        assert!(header(&cx.server_timing()).starts_with("total;dur="));
+
+        let mut cx = Context::new(Arc::new(App), Request::new(()));
+        let timing = cx.server_timing();
+        extract(&mut cx).record("db", Duration::from_millis(1));
+        assert!(header(&timing).starts_with("db;dur=1.0, total;dur="));
+    }
+
+    fn extract(cx: &mut Context<App>) -> ServerTiming {
+        let Context {
+            app,
+            req,
+            body,
+            path,
+        } = cx;
+        match ServerTiming::from_context(app, req, path, body) {
+            Ok(timing) => timing,
+            Err(_) => unreachable!(),
+        }
+    }
+
+    fn header(timing: &ServerTiming) -> String {
+        timing.header_value().unwrap().to_str().unwrap().to_owned()
+    }
+
+    struct App;
+
+    #[async_trait]
+    impl Application for App {
+        type RequestBody = ();
+        type ResponseBody = Body;
+        type Error = Error;
+
+        async fn handle(_: Context<Self>) -> Response<Body> {
+            unreachable!()
+        }
+    }
+}
@@ -0,0 +1,247 @@
+use std::fmt;
+use std::sync::Arc;
+
+use http::header::HeaderName;
+use http::request::Parts;
+use http::{HeaderMap, HeaderValue, Method, Uri};
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::application::{Application, FromContext, PathState};
+
+/// W3C Trace Context for a request
+///
+/// Identifies the trace a request belongs to and the span for handling it. If the request
+/// came with a valid `traceparent` header, the request's span continues that trace; otherwise
+/// a new trace is started.
+///
+/// When serving an application via `mendes::hyper`, the server creates a `TraceContext` for
+/// each request and runs the request in the `tracing` span returned by `span()`. That span
+/// records the trace and span ids as fields, next to fields named after the OpenTelemetry
+/// semantic conventions for HTTP servers. This only propagates the trace context: mendes does
+/// not export spans, and a subscriber bridging `tracing` to OpenTelemetry (such as
+/// `tracing-opentelemetry`) assigns its own span ids rather than using these. The recorded ids
+/// serve to correlate log output with the trace.
+///
+/// Use this type as a handler argument to propagate the trace to outgoing requests by way of
+/// `inject()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TraceContext {
+    trace_id: [u8; 16],
+    span_id: [u8; 8],
+    parent_id: Option<[u8; 8]>,
+    flags: u8,
+    state: Option<HeaderValue>,
+}
+
+impl TraceContext {
+    /// Create a `TraceContext` for a request with the given headers
+    pub fn from_headers(headers: &HeaderMap) -> Self {
+        let parent = headers
+            .get(TRACEPARENT)
+            .and_then(|v| v.to_str().ok())
+            .and_then(parse_traceparent);
+
+        let mut span_id = [0; 8];
+        fill_random(&mut span_id);
+        match parent {
+            Some((trace_id, parent_id, flags)) => Self {
+                trace_id,
+                span_id,
+                parent_id: Some(parent_id),
+                flags,
+                state: headers.get(TRACESTATE).cloned(),
+            },
+            None => {
+                let mut trace_id = [0; 16];
+                fill_random(&mut trace_id);
+                Self {
+                    trace_id,
+                    span_id,
+                    parent_id: None,
+                    flags: SAMPLED,
+                    state: None,
+                }
+            }
+        }
+    }
+
+    /// Create a `tracing` span for the request
+    ///
+    /// The `http.response.status_code` field is left empty, to be recorded once the response
+    /// is available.
+    pub fn span(&self, method: &Method, uri: &Uri) -> Span {
+        tracing::info_span!(
+            "request",
+            otel.name = %format_args!("{} {}", method, uri.path()),
+            otel.kind = "server",
+            trace_id = %Hex(&self.trace_id),
+            span_id = %Hex(&self.span_id),
+            parent_span_id = self.parent_id.as_ref().map(|id| tracing::field::display(Hex(id))),
+            http.request.method = %method,
+            url.path = uri.path(),
+            url.query = uri.query(),
+            http.response.status_code = Empty,
+        )
+    }
+
+    /// Add the headers to propagate this trace to an outgoing request
+    ///
+    /// The outgoing request will be a child of the span for the current request.
+    pub fn inject(&self, headers: &mut HeaderMap) {
+        headers.insert(TRACEPARENT, self.traceparent());
+        if let Some(state) = &self.state {
+            headers.insert(TRACESTATE, state.clone());
+        }
+    }
+
+    /// The `traceparent` header value identifying this request's span
+    pub fn traceparent(&self) -> HeaderValue {
+        let value = format!(
+            "00-{}-{}-{:02x}",
+            Hex(&self.trace_id),
+            Hex(&self.span_id),
+            self.flags
+        );
+        HeaderValue::try_from(value).unwrap()
+    }
+
+    /// The trace id, as 32 lowercase hex digits
+    pub fn trace_id(&self) -> String {
+        Hex(&self.trace_id).to_string()
+    }
+
+    /// The id of the span for this request, as 16 lowercase hex digits
+    pub fn span_id(&self) -> String {
+        Hex(&self.span_id).to_string()
+    }
+
+    /// The id of the parent span from the incoming `traceparent` header, if any
+    pub fn parent_span_id(&self) -> Option<String> {
+        self.parent_id.as_ref().map(|id| Hex(id).to_string())
+    }
+
+    /// Whether the caller has sampled this trace
+    pub fn sampled(&self) -> bool {
+        self.flags & SAMPLED != 0
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for TraceContext {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(match req.extensions.get::<TraceContext>() {
+            Some(trace) => trace.clone(),
+            None => TraceContext::from_headers(&req.headers),
+        })
+    }
+}
+
+/// Parse a version 00 `traceparent` header value
+///
+/// Values with higher versions are parsed as far as version 00 defines them.
+fn parse_traceparent(value: &str) -> Option<([u8; 16], [u8; 8], u8)> {
+    let mut parts = value.trim().split('-');
+    let version = parts.next()?;
+    let (trace_id, parent_id, flags) = (parts.next()?, parts.next()?, parts.next()?);
+    if version.len() != 2 || version == "ff" || (version == "00" && parts.next().is_some()) {
+        return None;
+    }
+
+    let version = u8::from_str_radix(version, 16).ok()?;
+    let trace_id = parse_hex::<16>(trace_id)?;
+    let parent_id = parse_hex::<8>(parent_id)?;
+    let flags = match version {
+        0 => parse_hex::<1>(flags)?[0],
+        _ => parse_hex::<1>(flags.get(..2)?)?[0],
+    };
+
+    match trace_id == [0; 16] || parent_id == [0; 8] {
+        true => None,
+        false => Some((trace_id, parent_id, flags)),
+    }
+}
+
+fn parse_hex<const N: usize>(s: &str) -> Option<[u8; N]> {
+    if s.len() != N * 2 || !s.bytes().all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f')) {
+        return None;
+    }
+
+    let mut out = [0; N];
+    for (i, byte) in out.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).ok()?;
+    }
+    Some(out)
+}
+
+fn fill_random(buf: &mut [u8]) {
+    // Ids must not be all zeroes; the odds of that happening are negligible
+    getrandom::getrandom(buf).expect("failed to generate random trace id");
+}
+
+struct Hex<'a>(&'a [u8]);
+
+impl fmt::Display for Hex<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for byte in self.0 {
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+const SAMPLED: u8 = 0x01;
+const TRACEPARENT: HeaderName = HeaderName::from_static("traceparent");
+const TRACESTATE: HeaderName = HeaderName::from_static("tracestate");
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn continue_trace() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            TRACEPARENT,
+            HeaderValue::from_static("00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01"),
+        );
+        headers.insert(TRACESTATE, HeaderValue::from_static("congo=t61rcWkgMzE"));
+
+        let trace = TraceContext::from_headers(&headers);
+        assert_eq!(trace.trace_id(), "0af7651916cd43dd8448eb211c80319c");
+        assert_eq!(trace.parent_span_id().unwrap(), "b7ad6b7169203331");
+        assert_ne!(trace.span_id(), "b7ad6b7169203331");
+        assert!(trace.sampled());
+
+        let mut outgoing = HeaderMap::new();
+        trace.inject(&mut outgoing);
+        let expected = format!("00-0af7651916cd43dd8448eb211c80319c-{}-01", trace.span_id());
+        assert_eq!(outgoing[TRACEPARENT], expected.as_str());
+        assert_eq!(outgoing[TRACESTATE], "congo=t61rcWkgMzE");
+    }
+
+    #[test]
+    fn invalid_traceparent() {
+        for value in [
+            "00-00000000000000000000000000000000-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-0000000000000000-01",
+            "00-0AF7651916CD43DD8448EB211C80319C-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra",
+            "ff-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01",
+            "00-0af7651916cd43dd8448eb211c80319c-b7ad6b71692033-01",
+        ] {
+            assert_eq!(parse_traceparent(value), None, "{value}");
+        }
+
+        let future = "01-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01-extra";
+        assert!(parse_traceparent(future).is_some());
+
+        let trace = TraceContext::from_headers(&HeaderMap::new());
+        assert_eq!(trace.parent_span_id(), None);
+        assert_eq!(trace.trace_id().len(), 32);
+    }
+}
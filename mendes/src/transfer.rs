@@ -0,0 +1,317 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::task::{ready, Context, Poll};
+
+use bytes::Buf;
+use http::request::Parts;
+use http::Response;
+use http_body::{Frame, SizeHint};
+use pin_project::{pin_project, pinned_drop};
+
+use crate::application::{Application, FromContext, PathState};
+use crate::Body;
+
+/// Counts the bytes of a request's body and its response's body
+///
+/// Enable accounting for a request by calling `Context::transfer()`, which wraps the request
+/// body to count the bytes read from it. Pass the response through `wrap()` after any other
+/// processing (like compression), so that the bytes written are counted as they are sent:
+///
+/// ```no_run
+/// # #[cfg(any(feature = "brotli", feature = "deflate", feature = "gzip"))]
+/// # mod example {
+/// # use mendes::body::EncodeResponse;
+/// # use mendes::http::Response;
+/// # use mendes::{handler, route, Application, Body, Context, Error};
+/// # mod billing {
+/// #     pub fn record(read: u64, written: u64) {}
+/// # }
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// async fn handle(mut cx: Context<Self>) -> Response<Body> {
+///     let transfer = cx.transfer();
+///     let req = cx.req.clone();
+///     let rsp = route!(match cx.path() {
+///         Some("upload") => upload,
+///     });
+///
+///     transfer.on_finish(|transfer| billing::record(transfer.read(), transfer.written()));
+///     transfer.wrap(rsp.encoded(&req))
+/// }
+/// # }
+/// # #[handler(POST)]
+/// # async fn upload(_: &App) -> Result<Response<Body>, Error> {
+/// #     todo!()
+/// # }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// Handlers can take a `Transfer` argument to see how much of the request body has been read.
+/// If accounting wasn't enabled for a request, the extracted `Transfer` counts nothing.
+#[derive(Clone, Default)]
+pub struct Transfer {
+    inner: Arc<Inner>,
+}
+
+impl Transfer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The number of request body bytes read so far
+    pub fn read(&self) -> u64 {
+        self.inner.read.load(Ordering::Relaxed)
+    }
+
+    /// The number of response body bytes written so far
+    pub fn written(&self) -> u64 {
+        self.inner.written.load(Ordering::Relaxed)
+    }
+
+    /// Count the bytes read from `body`
+    pub fn count_read<B>(&self, body: B) -> Counted<B> {
+        Counted::new(body, self.inner.clone(), Direction::Read)
+    }
+
+    /// Count the bytes written from `body`
+    ///
+    /// Calls the `on_finish()` callback once the body is dropped.
+    pub fn count_written<B>(&self, body: B) -> Counted<B> {
+        Counted::new(body, self.inner.clone(), Direction::Written)
+    }
+
+    /// Count the bytes written for the body of `rsp`
+    ///
+    /// Streaming responses keep their size hint, so a `Content-Length` is still sent for them.
+    pub fn wrap(&self, rsp: Response<Body>) -> Response<Body> {
+        rsp.map(|body| Body::stream(self.count_written(body)))
+    }
+
+    /// Call `f` when the response body is done, whether it was sent completely or not
+    ///
+    /// This is useful for writing access logs or billing records. Replaces any earlier callback.
+    pub fn on_finish(&self, f: impl FnOnce(&Transfer) + Send + 'static) {
+        *self.inner.on_finish.lock().unwrap() = Some(Box::new(f));
+    }
+}
+
+impl fmt::Debug for Transfer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Transfer")
+            .field("read", &self.read())
+            .field("written", &self.written())
+            .finish()
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for Transfer {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        Ok(match req.extensions.get::<Transfer>() {
+            Some(transfer) => transfer.clone(),
+            None => Transfer::new(),
+        })
+    }
+}
+
+#[derive(Default)]
+struct Inner {
+    read: AtomicU64,
+    written: AtomicU64,
+    #[allow(clippy::type_complexity)]
+    on_finish: Mutex<Option<Box<dyn FnOnce(&Transfer) + Send>>>,
+}
+
+/// A body that counts the bytes passing through it into a `Transfer`
+#[pin_project(PinnedDrop)]
+pub struct Counted<B> {
+    #[pin]
+    inner: B,
+    counts: Arc<Inner>,
+    direction: Direction,
+}
+
+impl<B> Counted<B> {
+    fn new(inner: B, counts: Arc<Inner>, direction: Direction) -> Self {
+        Self {
+            inner,
+            counts,
+            direction,
+        }
+    }
+}
+
+impl<B: http_body::Body> http_body::Body for Counted<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        if let Some(data) = frame.as_ref().and_then(|f| f.as_ref().ok()?.data_ref()) {
+            let counter = match this.direction {
+                Direction::Read => &this.counts.read,
+                Direction::Written => &this.counts.written,
+            };
+            counter.fetch_add(data.remaining() as u64, Ordering::Relaxed);
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[pinned_drop]
+impl<B> PinnedDrop for Counted<B> {
+    fn drop(self: Pin<&mut Self>) {
+        if self.direction != Direction::Written {
+            return;
+        }
+
+        let on_finish = self.counts.on_finish.lock().unwrap().take();
+        if let Some(f) = on_finish {
+            f(&Transfer {
+                inner: self.counts.clone(),
+            });
+        }
+    }
+}
+
+/// A body that reports how much of it has been read
+///
+/// Calls its callback with the number of bytes received so far and the total size, if known
+/// from the body's size hint, after every chunk of data. Once the body is complete, the
+/// callback is called a last time with the total set to the number of bytes received.
+///
+/// Use `Context::upload_progress()` to track the request body, for example to persist upload
+/// progress for a polling endpoint. Because multipart forms are parsed after reading the
+/// complete body, this also reports progress for multipart uploads.
+#[pin_project]
+pub struct Progress<B> {
+    #[pin]
+    inner: B,
+    received: u64,
+    total: Option<u64>,
+    on_progress: Box<dyn FnMut(u64, Option<u64>) + Send>,
+}
+
+impl<B: http_body::Body> Progress<B> {
+    pub fn new(inner: B, on_progress: impl FnMut(u64, Option<u64>) + Send + 'static) -> Self {
+        Self {
+            total: inner.size_hint().exact(),
+            inner,
+            received: 0,
+            on_progress: Box::new(on_progress),
+        }
+    }
+}
+
+impl<B: http_body::Body> http_body::Body for Progress<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        let frame = ready!(this.inner.poll_frame(cx));
+        match &frame {
+            Some(Ok(frame)) => {
+                if let Some(data) = frame.data_ref() {
+                    *this.received += data.remaining() as u64;
+                    (this.on_progress)(*this.received, *this.total);
+                }
+            }
+            Some(Err(_)) => {}
+            None => {
+                *this.total = Some(*this.received);
+                (this.on_progress)(*this.received, *this.total);
+            }
+        }
+        Poll::Ready(frame)
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        self.inner.size_hint()
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Direction {
+    Read,
+    Written,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::AtomicBool;
+
+    use bytes::Bytes;
+    use http_body::Body as _;
+
+    use super::*;
+    use crate::utils::collect;
+
+    #[tokio::test]
+    async fn count() {
+        let transfer = Transfer::new();
+        let body = collect(transfer.count_read(Body::from("hello")))
+            .await
+            .unwrap();
+        assert_eq!(body, "hello");
+        assert_eq!(transfer.read(), 5);
+
+        let finished = Arc::new(AtomicBool::new(false));
+        transfer.on_finish({
+            let finished = finished.clone();
+            move |transfer| {
+                assert_eq!(transfer.written(), 11);
+                finished.store(true, Ordering::SeqCst);
+            }
+        });
+
+        let rsp = transfer.wrap(Response::new(Body::from(Bytes::from("hello world"))));
+        assert_eq!(rsp.body().size_hint().exact(), Some(11));
+        let body = collect(rsp.into_body()).await.unwrap();
+        assert_eq!(body, "hello world");
+        assert!(finished.load(Ordering::SeqCst));
+        assert_eq!((transfer.read(), transfer.written()), (5, 11));
+    }
+
+    #[tokio::test]
+    async fn progress() {
+        let updates = Arc::new(Mutex::new(Vec::new()));
+        let body = Progress::new(Body::from("hello"), {
+            let updates = updates.clone();
+            move |received, total| updates.lock().unwrap().push((received, total))
+        });
+
+        assert_eq!(collect(body).await.unwrap(), "hello");
+        assert_eq!(*updates.lock().unwrap(), [(5, Some(5)), (5, Some(5))]);
+    }
+}
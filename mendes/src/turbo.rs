@@ -0,0 +1,317 @@
+use std::fmt;
+
+use http::header::{ACCEPT, CONTENT_TYPE};
+use http::request::Parts;
+use http::{Response, StatusCode};
+
+use crate::application::{Application, IntoResponse};
+use crate::html::{Markup, PreEscaped, Render};
+#[cfg(feature = "sse")]
+use crate::sse::Event;
+
+/// A Turbo Stream action, updating part of a page rendered by the server
+///
+/// Content is escaped unless it is `PreEscaped` or `Markup`, so template output should be
+/// wrapped in `PreEscaped`:
+///
+/// ```no_run
+/// # #[cfg(feature = "body-util")]
+/// # mod example {
+/// # use mendes::html::PreEscaped;
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::turbo::{TurboStream, TurboStreams};
+/// # use mendes::{handler, Application, Body, Error};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # struct NewComment {}
+/// # struct Comment;
+/// # struct CommentTemplate<'a>(&'a Comment);
+/// # impl<'a> CommentTemplate<'a> {
+/// #     fn new(comment: &'a Comment) -> Self {
+/// #         Self(comment)
+/// #     }
+/// #     fn render(&self) -> Result<String, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn add_comment(&self, comment: NewComment) -> Result<Comment, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn comment(app: &App, req: &Parts, body: Body) -> Result<TurboStreams, Error> {
+///     let form = App::from_body::<NewComment>(req, body, 16 * 1024).await?;
+///     let comment = app.db.add_comment(form).await?;
+///     let html = CommentTemplate::new(&comment).render()?;
+///     Ok(TurboStreams::new()
+///         .push(TurboStream::append("comments", PreEscaped(html)))
+///         .push(TurboStream::remove("no-comments")))
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// With the `sse` feature, streams convert into an `Event`, to send updates to a
+/// `<turbo-stream-source>` element.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct TurboStream {
+    action: Action,
+    target: Target,
+    content: Option<Markup>,
+}
+
+impl TurboStream {
+    /// Append `content` to the children of the target
+    pub fn append(target: impl Into<Target>, content: impl Render) -> Self {
+        Self::new(Action::Append, target, Some(content))
+    }
+
+    /// Prepend `content` to the children of the target
+    pub fn prepend(target: impl Into<Target>, content: impl Render) -> Self {
+        Self::new(Action::Prepend, target, Some(content))
+    }
+
+    /// Replace the target with `content`
+    pub fn replace(target: impl Into<Target>, content: impl Render) -> Self {
+        Self::new(Action::Replace, target, Some(content))
+    }
+
+    /// Replace the children of the target with `content`
+    pub fn update(target: impl Into<Target>, content: impl Render) -> Self {
+        Self::new(Action::Update, target, Some(content))
+    }
+
+    /// Insert `content` before the target
+    pub fn before(target: impl Into<Target>, content: impl Render) -> Self {
+        Self::new(Action::Before, target, Some(content))
+    }
+
+    /// Insert `content` after the target
+    pub fn after(target: impl Into<Target>, content: impl Render) -> Self {
+        Self::new(Action::After, target, Some(content))
+    }
+
+    /// Remove the target
+    pub fn remove(target: impl Into<Target>) -> Self {
+        Self::new(Action::Remove, target, None::<&str>)
+    }
+
+    pub fn new(action: Action, target: impl Into<Target>, content: Option<impl Render>) -> Self {
+        Self {
+            action,
+            target: target.into(),
+            content: content.map(|content| {
+                let mut markup = Markup::new();
+                markup.push(content);
+                markup
+            }),
+        }
+    }
+
+    /// The stream element as HTML
+    pub fn to_markup(&self) -> Markup {
+        let (attr, target) = match &self.target {
+            Target::Id(id) => ("target", id),
+            Target::Selector(selector) => ("targets", selector),
+        };
+
+        let mut out = Markup::new();
+        out.push(PreEscaped("<turbo-stream action=\""))
+            .push(self.action.as_str())
+            .push(PreEscaped(format_args!("\" {attr}=\"")))
+            .push(target.as_str())
+            .push(PreEscaped("\">"));
+        if let Some(content) = &self.content {
+            out.push(PreEscaped("<template>"))
+                .push(content)
+                .push(PreEscaped("</template>"));
+        }
+        out.push(PreEscaped("</turbo-stream>"));
+        out
+    }
+}
+
+impl fmt::Display for TurboStream {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.to_markup().fmt(f)
+    }
+}
+
+impl Render for TurboStream {
+    fn render(self, out: &mut Markup) {
+        out.push(self.to_markup());
+    }
+}
+
+#[cfg(feature = "sse")]
+#[cfg_attr(docsrs, doc(cfg(feature = "sse")))]
+impl From<TurboStream> for Event {
+    fn from(stream: TurboStream) -> Self {
+        Event::new(stream.to_string())
+    }
+}
+
+/// The element(s) a `TurboStream` action applies to
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Target {
+    /// The element with this `id`
+    Id(String),
+    /// All elements matching this CSS selector
+    Selector(String),
+}
+
+impl Target {
+    pub fn selector(selector: impl Into<String>) -> Self {
+        Self::Selector(selector.into())
+    }
+}
+
+impl From<&str> for Target {
+    fn from(id: &str) -> Self {
+        Self::Id(id.to_owned())
+    }
+}
+
+impl From<String> for Target {
+    fn from(id: String) -> Self {
+        Self::Id(id)
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Action {
+    Append,
+    Prepend,
+    Replace,
+    Update,
+    Remove,
+    Before,
+    After,
+}
+
+impl Action {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Action::Append => "append",
+            Action::Prepend => "prepend",
+            Action::Replace => "replace",
+            Action::Update => "update",
+            Action::Remove => "remove",
+            Action::Before => "before",
+            Action::After => "after",
+        }
+    }
+}
+
+/// A response with a list of Turbo Stream actions
+///
+/// Turbo only applies stream responses to form submissions that announced support for them,
+/// which can be checked with `accepts_turbo_stream()`.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct TurboStreams(pub Vec<TurboStream>);
+
+impl TurboStreams {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(mut self, stream: TurboStream) -> Self {
+        self.0.push(stream);
+        self
+    }
+}
+
+impl From<TurboStream> for TurboStreams {
+    fn from(stream: TurboStream) -> Self {
+        Self(vec![stream])
+    }
+}
+
+impl<A: Application> IntoResponse<A> for TurboStreams
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<A::ResponseBody> {
+        let mut out = Markup::new();
+        for stream in self.0 {
+            out.push(stream);
+        }
+
+        Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "text/vnd.turbo-stream.html; charset=utf-8")
+            .body(out.into_string().into())
+            .unwrap()
+    }
+}
+
+impl<A: Application> IntoResponse<A> for TurboStream
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        TurboStreams::from(self).into_response(app, req)
+    }
+}
+
+/// Whether the request accepts Turbo Stream responses
+pub fn accepts_turbo_stream(req: &Parts) -> bool {
+    req.headers
+        .get_all(ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .any(|v| v.contains(MEDIA_TYPE))
+}
+
+/// The Turbo Stream media type
+pub const MEDIA_TYPE: &str = "text/vnd.turbo-stream.html";
+
+#[cfg(test)]
+mod tests {
+    use http::Request;
+
+    use super::*;
+
+    #[test]
+    fn render() {
+        let stream = TurboStream::append("comments", PreEscaped("<p>Nice & quick</p>"));
+        assert_eq!(
+            stream.to_string(),
+            "<turbo-stream action=\"append\" target=\"comments\">\
+             <template><p>Nice & quick</p></template></turbo-stream>"
+        );
+
+        let stream = TurboStream::update(Target::selector(".count[data-x=\"1\"]"), "<3");
+        assert_eq!(
+            stream.to_string(),
+            "<turbo-stream action=\"update\" targets=\".count[data-x=&quot;1&quot;]\">\
+             <template>&lt;3</template></turbo-stream>"
+        );
+        assert_eq!(
+            TurboStream::remove("flash").to_string(),
+            "<turbo-stream action=\"remove\" target=\"flash\"></turbo-stream>"
+        );
+
+        let (req, _) = Request::post("/comments")
+            .header(ACCEPT, "text/vnd.turbo-stream.html, text/html")
+            .body(())
+            .unwrap()
+            .into_parts();
+        assert!(accepts_turbo_stream(&req));
+    }
+}
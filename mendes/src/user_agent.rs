@@ -0,0 +1,234 @@
+use std::sync::Arc;
+
+use http::header::USER_AGENT;
+use http::request::Parts;
+
+use crate::application::{Application, FromContext, PathState};
+
+/// The request's `User-Agent` header
+///
+/// This can also classify the browser, operating system and whether the client is a bot:
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::user_agent::UserAgent;
+/// # use mendes::{handler, Body, Error};
+/// # struct Analytics;
+/// # impl Analytics {
+/// #     fn record(&self, browser: Option<&str>, os: Option<&str>) {}
+/// # }
+/// # struct App {
+/// #     analytics: Analytics,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn page(app: &App, ua: UserAgent<'_>) -> Result<Response<Body>, Error> {
+///     if !ua.is_bot() {
+///         app.analytics.record(ua.browser().map(|b| b.name), ua.os());
+///     }
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Classification uses a small set of well-known tokens rather than a full user agent
+/// database, so it is suited for statistics and heuristics, not for security decisions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct UserAgent<'a> {
+    raw: Option<&'a str>,
+}
+
+impl<'a> UserAgent<'a> {
+    pub fn new(raw: Option<&'a str>) -> Self {
+        Self { raw }
+    }
+
+    /// The header value, if the request has one that is valid UTF-8
+    pub fn raw(&self) -> Option<&'a str> {
+        self.raw
+    }
+
+    /// The browser and its version, if recognized
+    pub fn browser(&self) -> Option<Browser<'a>> {
+        let raw = self.raw?;
+        if raw.contains("Trident/") {
+            return Some(Browser {
+                name: "Internet Explorer",
+                version: version_after(raw, "rv:").or_else(|| version_after(raw, "MSIE ")),
+            });
+        }
+
+        let (name, token) = BROWSERS.iter().find(|(_, token)| raw.contains(token))?;
+        let version = match *name {
+            // Safari puts its version number in a separate token
+            "Safari" => version_after(raw, "Version/"),
+            _ => version_after(raw, token),
+        };
+        Some(Browser { name, version })
+    }
+
+    /// The name of the operating system, if recognized
+    pub fn os(&self) -> Option<&'static str> {
+        let raw = self.raw?;
+        OPERATING_SYSTEMS
+            .iter()
+            .find(|(_, token)| raw.contains(token))
+            .map(|(name, _)| *name)
+    }
+
+    /// Whether the client identifies as a crawler, bot or scripted HTTP client
+    pub fn is_bot(&self) -> bool {
+        let raw = match self.raw {
+            Some(raw) => raw.to_ascii_lowercase(),
+            None => return false,
+        };
+
+        BOTS.iter().any(|token| raw.contains(token))
+    }
+
+    /// Whether the client identifies as a mobile device
+    pub fn is_mobile(&self) -> bool {
+        self.raw
+            .is_some_and(|raw| raw.contains("Mobi") || raw.contains("iPhone"))
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for UserAgent<'a> {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        _: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let raw = req.headers.get(USER_AGENT).and_then(|v| v.to_str().ok());
+        Ok(Self::new(raw))
+    }
+}
+
+/// A browser recognized from the `User-Agent` header
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Browser<'a> {
+    pub name: &'static str,
+    pub version: Option<&'a str>,
+}
+
+/// The version directly following `token` in `raw`
+fn version_after<'a>(raw: &'a str, token: &str) -> Option<&'a str> {
+    let start = raw.find(token)? + token.len();
+    let version = raw[start..].split([' ', ';', ')']).next()?;
+    match version.is_empty() {
+        true => None,
+        false => Some(version),
+    }
+}
+
+/// Browser names and their product tokens
+///
+/// Browsers based on Chrome also mention Chrome and Safari, so those come last.
+const BROWSERS: &[(&str, &str)] = &[
+    ("Edge", "Edg/"),
+    ("Edge", "Edge/"),
+    ("Edge", "EdgiOS/"),
+    ("Opera", "OPR/"),
+    ("Samsung Internet", "SamsungBrowser/"),
+    ("Firefox", "Firefox/"),
+    ("Firefox", "FxiOS/"),
+    ("Chrome", "CriOS/"),
+    ("Chrome", "Chrome/"),
+    ("Safari", "Safari/"),
+];
+
+/// Operating system names and the tokens identifying them
+///
+/// iOS and Android user agents also mention macOS and Linux, so those come last.
+const OPERATING_SYSTEMS: &[(&str, &str)] = &[
+    ("Windows", "Windows"),
+    ("iOS", "iPhone"),
+    ("iOS", "iPad"),
+    ("iOS", "iPod"),
+    ("macOS", "Macintosh"),
+    ("Android", "Android"),
+    ("ChromeOS", "CrOS"),
+    ("Linux", "Linux"),
+];
+
+/// Lowercase tokens used by crawlers and scripted clients
+const BOTS: &[&str] = &[
+    "bot",
+    "crawl",
+    "spider",
+    "slurp",
+    "facebookexternalhit",
+    "headlesschrome",
+    "lighthouse",
+    "curl/",
+    "wget/",
+    "httpie/",
+    "python-requests",
+    "python-urllib",
+    "go-http-client",
+    "okhttp",
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn classify() {
+        let chrome = UserAgent::new(Some(
+            "Mozilla/5.0 (Windows NT 10.0; Win64; x64) AppleWebKit/537.36 \
+             (KHTML, like Gecko) Chrome/120.0.0.0 Safari/537.36",
+        ));
+        assert_eq!(
+            chrome.browser(),
+            Some(Browser {
+                name: "Chrome",
+                version: Some("120.0.0.0")
+            })
+        );
+        assert_eq!(chrome.os(), Some("Windows"));
+        assert!(!chrome.is_bot() && !chrome.is_mobile());
+
+        let safari = UserAgent::new(Some(
+            "Mozilla/5.0 (iPhone; CPU iPhone OS 17_1 like Mac OS X) AppleWebKit/605.1.15 \
+             (KHTML, like Gecko) Version/17.1 Mobile/15E148 Safari/604.1",
+        ));
+        assert_eq!(
+            safari.browser(),
+            Some(Browser {
+                name: "Safari",
+                version: Some("17.1")
+            })
+        );
+        assert_eq!(safari.os(), Some("iOS"));
+        assert!(safari.is_mobile());
+
+        let edge = UserAgent::new(Some(
+            "Mozilla/5.0 (X11; Linux x86_64) AppleWebKit/537.36 (KHTML, like Gecko) \
+             Chrome/120.0.0.0 Safari/537.36 Edg/120.0.2210.91",
+        ));
+        assert_eq!(edge.browser().unwrap().name, "Edge");
+        assert_eq!(edge.os(), Some("Linux"));
+
+        let bot = UserAgent::new(Some(
+            "Mozilla/5.0 (compatible; Googlebot/2.1; +http://www.google.com/bot.html)",
+        ));
+        assert!(bot.is_bot());
+        assert!(UserAgent::new(Some("curl/8.4.0")).is_bot());
+
+        let none = UserAgent::new(None);
+        assert_eq!(
+            (none.browser(), none.os(), none.is_bot()),
+            (None, None, false)
+        );
+    }
+}
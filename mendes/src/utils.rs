@@ -1,6 +1,6 @@
 #[cfg(feature = "static")]
 mod file_mod {
-    use crate::application::Error;
+    use crate::application::{Error, ErrorKind};
     use http::header::{CONTENT_LENGTH, CONTENT_TYPE};
     use http::StatusCode;
     use std::path::PathBuf;
@@ -10,10 +10,14 @@ mod file_mod {
     where
         B: From<Vec<u8>>,
     {
-        let mut metadata = fs::metadata(&path).await.map_err(|_| Error::FileNotFound)?;
+        let mut metadata = fs::metadata(&path)
+            .await
+            .map_err(|_| Error::from(ErrorKind::FileNotFound))?;
         if metadata.is_dir() {
             path = path.join("index.html");
-            metadata = fs::metadata(&path).await.map_err(|_| Error::FileNotFound)?;
+            metadata = fs::metadata(&path)
+                .await
+                .map_err(|_| Error::from(ErrorKind::FileNotFound))?;
         }
 
         let mut builder = http::Response::builder()
@@ -24,7 +28,9 @@ mod file_mod {
             builder = builder.header(CONTENT_TYPE, mime.to_string());
         }
 
-        let bytes = fs::read(path).await.map_err(|_| Error::FileNotFound)?;
+        let bytes = fs::read(path)
+            .await
+            .map_err(|_| Error::from(ErrorKind::FileNotFound))?;
         Ok(builder.body(B::from(bytes)).unwrap())
     }
 }
@@ -32,3 +38,175 @@ mod file_mod {
 #[cfg(feature = "static")]
 #[cfg_attr(docsrs, doc(cfg(feature = "static")))]
 pub use file_mod::file;
+
+/// Collect all data frames from `body` into a single buffer
+#[cfg(any(
+    feature = "dev",
+    feature = "idempotency",
+    all(test, any(feature = "reader", feature = "transfer"))
+))]
+pub(crate) async fn collect<B>(body: B) -> Result<bytes::Bytes, B::Error>
+where
+    B: http_body::Body<Data = bytes::Bytes>,
+{
+    let mut body = std::pin::pin!(body);
+    let mut buf = bytes::BytesMut::new();
+    while let Some(frame) = std::future::poll_fn(|cx| body.as_mut().poll_frame(cx)).await {
+        if let Ok(data) = frame?.into_data() {
+            buf.extend_from_slice(&data);
+        }
+    }
+    Ok(buf.freeze())
+}
+
+/// Split the value of a `Cookie` header into name/value pairs
+#[cfg(any(feature = "cookies", feature = "parse"))]
+pub(crate) fn cookie_pairs(value: &str) -> impl Iterator<Item = (&str, &str)> {
+    // A single cookie header can contain multiple cookies (delimited by ;)
+    value
+        .split(';')
+        .filter_map(|cookie| cookie.trim_start().split_once('='))
+}
+
+/// Build a `Content-Disposition` header value suggesting a download as `filename`
+#[cfg(any(feature = "csv", feature = "zip"))]
+pub(crate) fn attachment(filename: &str) -> http::HeaderValue {
+    content_disposition("attachment", filename)
+}
+
+/// Build a `Content-Disposition` header value of the given `disposition` type for `filename`
+///
+/// File names with non-ASCII characters are percent-encoded as described in RFC 5987.
+#[cfg(any(feature = "attachment", feature = "csv", feature = "zip"))]
+pub(crate) fn content_disposition(disposition: &str, filename: &str) -> http::HeaderValue {
+    use percent_encoding::{utf8_percent_encode, AsciiSet, NON_ALPHANUMERIC};
+
+    const UNRESERVED: &AsciiSet = &NON_ALPHANUMERIC
+        .remove(b'-')
+        .remove(b'.')
+        .remove(b'_')
+        .remove(b'~');
+
+    let value = match filename.bytes().all(|b| b.is_ascii_graphic() || b == b' ') {
+        true => format!(
+            "{disposition}; filename=\"{}\"",
+            filename.replace(['"', '\\'], "_")
+        ),
+        false => format!(
+            "{disposition}; filename*=UTF-8''{}",
+            utf8_percent_encode(filename, UNRESERVED)
+        ),
+    };
+    http::HeaderValue::try_from(value).unwrap()
+}
+
+/// Whether `path` is a path on the same origin, which is safe to redirect to
+///
+/// Browsers treat a backslash like a slash and skip tabs and line breaks, so `/\evil.com` and
+/// `/\t/evil.com` lead to another site just like `//evil.com`. Only printable ASCII without
+/// backslashes is accepted.
+#[cfg(any(feature = "auth", feature = "oauth"))]
+pub(crate) fn is_local_path(path: &str) -> bool {
+    path.starts_with('/')
+        && !path.starts_with("//")
+        && path.bytes().all(|b| matches!(b, b' '..=b'~') && b != b'\\')
+}
+
+/// Compare `a` and `b` in time that depends only on their lengths
+#[cfg(any(
+    feature = "apikeys",
+    feature = "auth",
+    feature = "oauth",
+    feature = "webhooks"
+))]
+pub(crate) fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The media type from `offered` that the request's `Accept` header prefers
+///
+/// Without an `Accept` header, the first offered type is used. Returns `None` if the request
+/// accepts none of them.
+#[cfg(any(feature = "cbor", feature = "msgpack", feature = "xml"))]
+pub(crate) fn negotiate(
+    req: &http::request::Parts,
+    offered: &[&'static str],
+) -> Option<&'static str> {
+    let ranges = req
+        .headers
+        .get_all(http::header::ACCEPT)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .filter_map(|range| {
+            let mut params = range.split(';');
+            let media_range = params.next()?.trim();
+            let quality = match params.find_map(|p| p.trim().strip_prefix("q=")) {
+                Some(q) => q.trim().parse::<f32>().ok()?,
+                None => 1.0,
+            };
+            Some((media_range, quality))
+        })
+        .filter(|(media_range, _)| !media_range.is_empty())
+        .collect::<Vec<_>>();
+
+    if ranges.is_empty() {
+        return offered.first().copied();
+    }
+
+    let mut best = None;
+    for &media_type in offered {
+        let ty = media_type.split('/').next().unwrap_or_default();
+        // The most specific matching range determines the quality
+        let quality = ranges
+            .iter()
+            .filter_map(|&(range, quality)| match range.split_once('/') {
+                _ if range.eq_ignore_ascii_case(media_type) => Some((2, quality)),
+                Some((prefix, "*")) if prefix.eq_ignore_ascii_case(ty) => Some((1, quality)),
+                Some(("*", "*")) => Some((0, quality)),
+                _ => None,
+            })
+            .max_by_key(|&(specificity, _)| specificity)
+            .map_or(0.0, |(_, quality)| quality);
+
+        if quality > 0.0 && best.map_or(true, |(_, best)| quality > best) {
+            best = Some((media_type, quality));
+        }
+    }
+    best.map(|(media_type, _)| media_type)
+}
+
+#[cfg(test)]
+mod tests {
+    #[cfg(any(feature = "auth", feature = "oauth"))]
+    #[test]
+    fn local_path() {
+        use super::is_local_path;
+
+        assert!(is_local_path("/"));
+        assert!(is_local_path("/account?tab=keys#top"));
+        for path in [
+            "",
+            "https://evil.com",
+            "//evil.com",
+            "/\\evil.com",
+            "/\t/evil.com",
+            "/é",
+        ] {
+            assert!(!is_local_path(path), "{path:?}");
+        }
+    }
+
+    #[cfg(any(feature = "csv", feature = "zip"))]
+    #[test]
+    fn attachment() {
+        assert_eq!(
+            super::attachment("report \"q1\".csv"),
+            "attachment; filename=\"report _q1_.csv\""
+        );
+        assert_eq!(
+            super::attachment("résumé.csv"),
+            "attachment; filename*=UTF-8''r%C3%A9sum%C3%A9.csv"
+        );
+    }
+}
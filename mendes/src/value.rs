@@ -0,0 +1,368 @@
+use std::marker::PhantomData;
+
+use serde::de::{self, DeserializeSeed, IntoDeserializer, Visitor};
+
+/// A parsed document, deserialized into the caller's type by `Deserializer`
+///
+/// This lets the hand-written body formats share a single `serde::Deserializer`. Parsing into
+/// a tree first costs an allocation per value, which is fine for request bodies.
+#[derive(Clone, Debug, PartialEq)]
+pub(crate) enum Value {
+    #[cfg_attr(not(any(feature = "cbor", feature = "msgpack")), allow(dead_code))]
+    Null,
+    #[cfg_attr(not(any(feature = "cbor", feature = "msgpack")), allow(dead_code))]
+    Bool(bool),
+    #[cfg_attr(not(any(feature = "cbor", feature = "msgpack")), allow(dead_code))]
+    Int(i128),
+    #[cfg_attr(not(any(feature = "cbor", feature = "msgpack")), allow(dead_code))]
+    Float(f64),
+    #[cfg_attr(not(any(feature = "cbor", feature = "msgpack")), allow(dead_code))]
+    Str(String),
+    #[cfg_attr(not(any(feature = "cbor", feature = "msgpack")), allow(dead_code))]
+    Bytes(Vec<u8>),
+    Seq(Vec<Value>),
+    #[cfg_attr(not(any(feature = "cbor", feature = "msgpack")), allow(dead_code))]
+    Map(Vec<(Value, Value)>),
+    /// Untyped text from XML, which deserializes into strings, numbers and booleans
+    #[cfg_attr(not(feature = "xml"), allow(dead_code))]
+    Text(String),
+    /// The attributes and child elements of an XML element, by name
+    #[cfg_attr(not(feature = "xml"), allow(dead_code))]
+    Element(Vec<(String, Value)>),
+}
+
+/// Deserializes a `Value`, producing errors of type `E`
+pub(crate) struct Deserializer<E> {
+    value: Value,
+    error: PhantomData<E>,
+}
+
+impl<E> Deserializer<E> {
+    pub(crate) fn new(value: Value) -> Self {
+        Self {
+            value,
+            error: PhantomData,
+        }
+    }
+}
+
+/// Parse numbers from untyped text; typed values are left to the visitor
+macro_rules! deserialize_parse {
+    ($($method:ident => $visit:ident,)*) => {
+        $(fn $method<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+            match self.value {
+                Value::Text(text) => match text.trim().parse() {
+                    Ok(v) => visitor.$visit(v),
+                    Err(_) => Err(E::invalid_value(de::Unexpected::Str(&text), &visitor)),
+                },
+                _ => self.deserialize_any(visitor),
+            }
+        })*
+    };
+}
+
+impl<'de, E: de::Error> de::Deserializer<'de> for Deserializer<E> {
+    type Error = E;
+
+    fn deserialize_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Null => visitor.visit_unit(),
+            Value::Bool(v) => visitor.visit_bool(v),
+            Value::Int(n) => match (i64::try_from(n), u64::try_from(n)) {
+                (Ok(n), _) => visitor.visit_i64(n),
+                (_, Ok(n)) => visitor.visit_u64(n),
+                _ => visitor.visit_i128(n),
+            },
+            Value::Float(v) => visitor.visit_f64(v),
+            Value::Str(s) | Value::Text(s) => visitor.visit_string(s),
+            Value::Bytes(bytes) => visitor.visit_byte_buf(bytes),
+            Value::Seq(items) => visitor.visit_seq(SeqAccess::new(items)),
+            Value::Map(entries) => visitor.visit_map(MapAccess::new(entries)),
+            Value::Element(entries) => visitor.visit_map(MapAccess::element(entries)),
+        }
+    }
+
+    fn deserialize_bool<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Text(text) => match text.trim() {
+                "true" | "1" => visitor.visit_bool(true),
+                "false" | "0" => visitor.visit_bool(false),
+                _ => Err(E::invalid_value(de::Unexpected::Str(&text), &visitor)),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    deserialize_parse! {
+        deserialize_i8 => visit_i8,
+        deserialize_i16 => visit_i16,
+        deserialize_i32 => visit_i32,
+        deserialize_i64 => visit_i64,
+        deserialize_i128 => visit_i128,
+        deserialize_u8 => visit_u8,
+        deserialize_u16 => visit_u16,
+        deserialize_u32 => visit_u32,
+        deserialize_u64 => visit_u64,
+        deserialize_u128 => visit_u128,
+        deserialize_f32 => visit_f32,
+        deserialize_f64 => visit_f64,
+    }
+
+    fn deserialize_char<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_str<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_string(visitor)
+    }
+
+    fn deserialize_string<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_bytes<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_byte_buf(visitor)
+    }
+
+    fn deserialize_byte_buf<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Text(text) => visitor.visit_byte_buf(text.into_bytes()),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_option<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Null => visitor.visit_none(),
+            // Missing XML elements are `None`, so every one that is present is `Some`
+            _ => visitor.visit_some(self),
+        }
+    }
+
+    fn deserialize_unit<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Null | Value::Text(_) => visitor.visit_unit(),
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_unit_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        self.deserialize_unit(visitor)
+    }
+
+    fn deserialize_newtype_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        visitor.visit_newtype_struct(self)
+    }
+
+    fn deserialize_seq<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Seq(items) => visitor.visit_seq(SeqAccess::new(items)),
+            // A single XML element where a list was expected
+            value @ (Value::Text(_) | Value::Element(_)) => {
+                visitor.visit_seq(SeqAccess::new(vec![value]))
+            }
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_tuple<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_tuple_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: usize,
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        self.deserialize_seq(visitor)
+    }
+
+    fn deserialize_map<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        match self.value {
+            Value::Element(entries) => visitor.visit_map(MapAccess::element(entries)),
+            // A text-only XML element, whose text is its `$text`
+            Value::Text(text) => match text.trim().is_empty() {
+                true => visitor.visit_map(MapAccess::new(Vec::new())),
+                false => visitor.visit_map(MapAccess::element(vec![(
+                    TEXT.to_owned(),
+                    Value::Text(text),
+                )])),
+            },
+            _ => self.deserialize_any(visitor),
+        }
+    }
+
+    fn deserialize_struct<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        self.deserialize_map(visitor)
+    }
+
+    fn deserialize_enum<V: Visitor<'de>>(
+        self,
+        _: &'static str,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        let (variant, value) = match self.value {
+            Value::Text(text) => {
+                return visitor.visit_enum(text.trim().to_owned().into_deserializer())
+            }
+            Value::Str(s) => return visitor.visit_enum(s.into_deserializer()),
+            Value::Element(mut entries) if entries.len() == 1 => {
+                let (variant, value) = entries.pop().unwrap();
+                (Value::Text(variant), value)
+            }
+            Value::Map(mut entries) if entries.len() == 1 => entries.pop().unwrap(),
+            _ => return Err(E::custom("expected an enum variant")),
+        };
+
+        visitor.visit_enum(EnumAccess {
+            variant,
+            value: Deserializer::new(value),
+        })
+    }
+
+    fn deserialize_identifier<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        self.deserialize_any(visitor)
+    }
+
+    fn deserialize_ignored_any<V: Visitor<'de>>(self, visitor: V) -> Result<V::Value, E> {
+        visitor.visit_unit()
+    }
+}
+
+struct SeqAccess<E> {
+    items: std::vec::IntoIter<Value>,
+    error: PhantomData<E>,
+}
+
+impl<E> SeqAccess<E> {
+    fn new(items: Vec<Value>) -> Self {
+        Self {
+            items: items.into_iter(),
+            error: PhantomData,
+        }
+    }
+}
+
+impl<'de, E: de::Error> de::SeqAccess<'de> for SeqAccess<E> {
+    type Error = E;
+
+    fn next_element_seed<T: DeserializeSeed<'de>>(
+        &mut self,
+        seed: T,
+    ) -> Result<Option<T::Value>, E> {
+        match self.items.next() {
+            Some(value) => seed.deserialize(Deserializer::new(value)).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.items.len())
+    }
+}
+
+struct MapAccess<E> {
+    entries: std::vec::IntoIter<(Value, Value)>,
+    value: Option<Value>,
+    error: PhantomData<E>,
+}
+
+impl<E> MapAccess<E> {
+    fn new(entries: Vec<(Value, Value)>) -> Self {
+        Self {
+            entries: entries.into_iter(),
+            value: None,
+            error: PhantomData,
+        }
+    }
+
+    fn element(entries: Vec<(String, Value)>) -> Self {
+        let entries = entries.into_iter();
+        Self::new(entries.map(|(k, v)| (Value::Text(k), v)).collect())
+    }
+}
+
+impl<'de, E: de::Error> de::MapAccess<'de> for MapAccess<E> {
+    type Error = E;
+
+    fn next_key_seed<K: DeserializeSeed<'de>>(&mut self, seed: K) -> Result<Option<K::Value>, E> {
+        let (key, value) = match self.entries.next() {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        self.value = Some(value);
+        seed.deserialize(Deserializer::new(key)).map(Some)
+    }
+
+    fn next_value_seed<V: DeserializeSeed<'de>>(&mut self, seed: V) -> Result<V::Value, E> {
+        match self.value.take() {
+            Some(value) => seed.deserialize(Deserializer::new(value)),
+            None => Err(E::custom("value requested before key")),
+        }
+    }
+
+    fn size_hint(&self) -> Option<usize> {
+        Some(self.entries.len())
+    }
+}
+
+struct EnumAccess<E> {
+    variant: Value,
+    value: Deserializer<E>,
+}
+
+impl<'de, E: de::Error> de::EnumAccess<'de> for EnumAccess<E> {
+    type Error = E;
+    type Variant = Deserializer<E>;
+
+    fn variant_seed<V: DeserializeSeed<'de>>(
+        self,
+        seed: V,
+    ) -> Result<(V::Value, Self::Variant), E> {
+        let variant = seed.deserialize(Deserializer::<E>::new(self.variant))?;
+        Ok((variant, self.value))
+    }
+}
+
+impl<'de, E: de::Error> de::VariantAccess<'de> for Deserializer<E> {
+    type Error = E;
+
+    fn unit_variant(self) -> Result<(), E> {
+        Ok(())
+    }
+
+    fn newtype_variant_seed<T: DeserializeSeed<'de>>(self, seed: T) -> Result<T::Value, E> {
+        seed.deserialize(self)
+    }
+
+    fn tuple_variant<V: Visitor<'de>>(self, _: usize, visitor: V) -> Result<V::Value, E> {
+        de::Deserializer::deserialize_seq(self, visitor)
+    }
+
+    fn struct_variant<V: Visitor<'de>>(
+        self,
+        _: &'static [&'static str],
+        visitor: V,
+    ) -> Result<V::Value, E> {
+        de::Deserializer::deserialize_map(self, visitor)
+    }
+}
+
+/// The name under which the text content of an element with attributes or children is kept
+pub(crate) const TEXT: &str = "$text";
@@ -0,0 +1,934 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use data_encoding::BASE64URL_NOPAD;
+use http::header::{CONTENT_TYPE, SET_COOKIE};
+use http::request::Parts;
+use http::{HeaderValue, Response};
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use ring::signature::{UnparsedPublicKey, VerificationAlgorithm, ECDSA_P256_SHA256_ASN1, ED25519};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
+use thiserror::Error;
+
+use crate::cookies::{AppWithAeadKey, AppWithCookies, CookieData, CookieMeta, SameSite};
+
+/// Passkey registration and authentication for a WebAuthn relying party
+///
+/// Both ceremonies take two requests. The first returns options as JSON, which the browser
+/// passes to `PublicKeyCredential.parseCreationOptionsFromJSON()` (or
+/// `parseRequestOptionsFromJSON()`) and then to `navigator.credentials.create()` (or
+/// `get()`). The second receives the credential as serialized by its `toJSON()` method:
+///
+/// ```no_run
+/// # #[cfg(feature = "body-util")]
+/// # mod example {
+/// # use std::sync::Arc;
+/// # use mendes::application::PathState;
+/// # use mendes::cookies::{AppWithAeadKey, Key};
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::webauthn::{MemoryStore, RelyingParty};
+/// # use mendes::{handler, Application, Body, Error, FromContext};
+/// # struct Session {
+/// #     user: u64,
+/// # }
+/// # impl<'a> FromContext<'a, App> for Session {
+/// #     fn from_context(
+/// #         _: &'a Arc<App>,
+/// #         _: &'a Parts,
+/// #         _: &mut PathState,
+/// #         _: &mut Option<Body>,
+/// #     ) -> Result<Self, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct User {
+/// #     id: u64,
+/// #     email: String,
+/// #     name: String,
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn user(&self, id: u64) -> Result<User, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// #     key: Key,
+/// #     passkeys: RelyingParty<MemoryStore>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # impl AppWithAeadKey for App {
+/// #     fn key(&self) -> &Key {
+/// #         &self.key
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn register_start(app: &App, session: Session) -> Result<Response<Body>, Error> {
+///     let user = app.db.user(session.user).await?;
+///     Ok(app.passkeys.start_registration(app, &user.id.to_be_bytes(), &user.email, &user.name).await?)
+/// }
+///
+/// #[handler(POST)]
+/// async fn register_finish(app: &App, req: &Parts, body: Body) -> Result<Response<Body>, Error> {
+///     let body = App::body_bytes(req, body, 16 * 1024).await?;
+///     app.passkeys.finish_registration(app, req, &body).await?;
+///     todo!()
+/// }
+/// # }
+/// # fn main() {}
+/// ```
+///
+/// The challenge for a ceremony in progress is kept in a short-lived encrypted cookie, and
+/// registered credentials are kept in the application's `CredentialStore`. Each challenge can
+/// only be used once: the relying party remembers completed challenges until they expire, so
+/// applications running several processes should route a ceremony's requests to the same
+/// process. Attestation statements are not verified, as is usual for passkeys; ES256 and
+/// Ed25519 keys are supported.
+pub struct RelyingParty<S> {
+    store: S,
+    id: Cow<'static, str>,
+    name: Cow<'static, str>,
+    origin: Cow<'static, str>,
+    /// Challenges of completed ceremonies, with the time at which they can be forgotten
+    used: Mutex<HashMap<String, Instant>>,
+}
+
+impl<S: CredentialStore> RelyingParty<S> {
+    /// Create a relying party
+    ///
+    /// The `id` is usually the application's domain (like `example.com`), `name` is shown to
+    /// users by their authenticator and `origin` is the origin of the pages running the
+    /// ceremonies (like `https://example.com`).
+    pub fn new(
+        store: S,
+        id: impl Into<Cow<'static, str>>,
+        name: impl Into<Cow<'static, str>>,
+        origin: impl Into<Cow<'static, str>>,
+    ) -> Self {
+        Self {
+            store,
+            id: id.into(),
+            name: name.into(),
+            origin: origin.into(),
+            used: Mutex::default(),
+        }
+    }
+
+    /// Start registering a passkey for the given user
+    ///
+    /// The `user_id` is an opaque identifier of at most 64 bytes, which should not contain
+    /// personal information. The user's existing credentials are excluded, so the same
+    /// authenticator isn't registered twice.
+    pub async fn start_registration<A: AppWithAeadKey, B: From<Bytes>>(
+        &self,
+        app: &A,
+        user_id: &[u8],
+        name: &str,
+        display_name: &str,
+    ) -> Result<Response<B>, Error> {
+        let ceremony = Ceremony::new(Kind::Register, Some(user_id.to_vec()));
+        let exclude = self.store.credentials(user_id).await;
+        let options = json!({
+            "rp": { "id": self.id, "name": self.name },
+            "user": {
+                "id": BASE64URL_NOPAD.encode(user_id),
+                "name": name,
+                "displayName": display_name,
+            },
+            "challenge": ceremony.challenge,
+            "pubKeyCredParams": [
+                { "type": "public-key", "alg": ES256 },
+                { "type": "public-key", "alg": EDDSA },
+            ],
+            "timeout": TIMEOUT * 1000,
+            "excludeCredentials": descriptors(&exclude),
+            "authenticatorSelection": {
+                "residentKey": "preferred",
+                "userVerification": "preferred",
+            },
+            "attestation": "none",
+        });
+
+        options_response(app.set_cookie_header(Some(ceremony))?, &options)
+    }
+
+    /// Verify a new credential from `navigator.credentials.create()` and store it
+    pub async fn finish_registration<A: AppWithAeadKey>(
+        &self,
+        app: &A,
+        req: &Parts,
+        body: &[u8],
+    ) -> Result<Credential, Error> {
+        let ceremony = app
+            .cookie::<Ceremony>(&req.headers)
+            .filter(|ceremony| ceremony.kind == Kind::Register)
+            .ok_or(Error::ChallengeMissing)?;
+        let user_id = ceremony.user_id.clone().ok_or(Error::ChallengeMissing)?;
+
+        let rsp = serde_json::from_slice::<PublicKeyCredential<Attestation>>(body)?;
+        self.client_data(&rsp.response.client_data_json, "webauthn.create", &ceremony)?;
+
+        let object = decode(&rsp.response.attestation_object)?;
+        let (object, _) = cbor::Value::parse(&object).ok_or(Error::Malformed)?;
+        let auth_data = match object.get_text("authData") {
+            Some(cbor::Value::Bytes(data)) => *data,
+            _ => return Err(Error::Malformed),
+        };
+
+        let data = self.authenticator_data(auth_data)?;
+        if data.flags & ATTESTED_CREDENTIAL == 0 || auth_data.len() < 55 {
+            return Err(Error::Malformed);
+        }
+
+        // Attested credential data: AAGUID, credential ID length and ID, then the key
+        let len = u16::from_be_bytes([auth_data[53], auth_data[54]]) as usize;
+        let id = auth_data.get(55..55 + len).ok_or(Error::Malformed)?;
+        let (key, _) = cbor::Value::parse(&auth_data[55 + len..]).ok_or(Error::Malformed)?;
+        let (algorithm, public_key) = cose_key(&key)?;
+
+        if self.store.get(id).await.is_some() {
+            return Err(Error::Duplicate);
+        }
+
+        self.complete(&ceremony)?;
+        let credential = Credential {
+            id: id.to_vec(),
+            user_id,
+            public_key,
+            algorithm,
+            sign_count: data.sign_count,
+        };
+        self.store.insert(credential.clone()).await;
+        Ok(credential)
+    }
+
+    /// Start signing in with a passkey
+    ///
+    /// If `user_id` is given, only that user's credentials are allowed. Otherwise, the
+    /// authenticator offers any passkey it has for this relying party.
+    pub async fn start_authentication<A: AppWithAeadKey, B: From<Bytes>>(
+        &self,
+        app: &A,
+        user_id: Option<&[u8]>,
+    ) -> Result<Response<B>, Error> {
+        let allow = match user_id {
+            Some(user_id) => self.store.credentials(user_id).await,
+            None => Vec::new(),
+        };
+
+        let ceremony = Ceremony::new(Kind::Authenticate, user_id.map(|id| id.to_vec()));
+        let options = json!({
+            "challenge": ceremony.challenge,
+            "rpId": self.id,
+            "timeout": TIMEOUT * 1000,
+            "allowCredentials": descriptors(&allow),
+            "userVerification": "preferred",
+        });
+
+        options_response(app.set_cookie_header(Some(ceremony))?, &options)
+    }
+
+    /// Verify an assertion from `navigator.credentials.get()`
+    ///
+    /// Returns the credential that was used, with its updated signature counter. Its
+    /// `user_id` identifies the user that signed in.
+    pub async fn finish_authentication<A: AppWithAeadKey>(
+        &self,
+        app: &A,
+        req: &Parts,
+        body: &[u8],
+    ) -> Result<Credential, Error> {
+        let ceremony = app
+            .cookie::<Ceremony>(&req.headers)
+            .filter(|ceremony| ceremony.kind == Kind::Authenticate)
+            .ok_or(Error::ChallengeMissing)?;
+
+        let rsp = serde_json::from_slice::<PublicKeyCredential<Assertion>>(body)?;
+        let client_data =
+            self.client_data(&rsp.response.client_data_json, "webauthn.get", &ceremony)?;
+
+        let auth_data = decode(&rsp.response.authenticator_data)?;
+        let data = self.authenticator_data(&auth_data)?;
+
+        let id = decode(&rsp.raw_id)?;
+        let mut credential = self.store.get(&id).await.ok_or(Error::UnknownCredential)?;
+        let user_handle = rsp
+            .response
+            .user_handle
+            .as_deref()
+            .map(decode)
+            .transpose()?;
+        let expected = ceremony.user_id.as_ref().or(user_handle.as_ref());
+        if expected.is_some_and(|user_id| *user_id != credential.user_id) {
+            return Err(Error::UnknownCredential);
+        }
+
+        let mut message = auth_data.clone();
+        message.extend_from_slice(digest(&SHA256, &client_data).as_ref());
+        let signature = decode(&rsp.response.signature)?;
+        let alg: &dyn VerificationAlgorithm = match credential.algorithm {
+            ES256 => &ECDSA_P256_SHA256_ASN1,
+            EDDSA => &ED25519,
+            _ => return Err(Error::UnsupportedAlgorithm),
+        };
+        UnparsedPublicKey::new(alg, &credential.public_key)
+            .verify(&message, &signature)
+            .map_err(|_| Error::InvalidSignature)?;
+
+        // A counter that doesn't increase suggests that the authenticator was cloned
+        if (data.sign_count != 0 || credential.sign_count != 0)
+            && data.sign_count <= credential.sign_count
+        {
+            return Err(Error::CounterRegression);
+        }
+
+        // Synced passkeys always report a zero counter, so the challenge must not be reusable
+        self.complete(&ceremony)?;
+        credential.sign_count = data.sign_count;
+        self.store
+            .update_sign_count(&credential.id, data.sign_count)
+            .await;
+        Ok(credential)
+    }
+
+    /// Mark the challenge of `ceremony` as used, failing if it was used before
+    fn complete(&self, ceremony: &Ceremony) -> Result<(), Error> {
+        let now = Instant::now();
+        let mut used = self.used.lock().unwrap();
+        used.retain(|_, expires| *expires > now);
+        // The cookie holding the challenge expires after `TIMEOUT`, so it can be forgotten then
+        let expires = now + Duration::from_secs(TIMEOUT);
+        match used.insert(ceremony.challenge.clone(), expires) {
+            Some(_) => Err(Error::ChallengeUsed),
+            None => Ok(()),
+        }
+    }
+
+    /// Check the client data, returning its raw bytes for signature verification
+    fn client_data(
+        &self,
+        encoded: &str,
+        kind: &str,
+        ceremony: &Ceremony,
+    ) -> Result<Vec<u8>, Error> {
+        let raw = decode(encoded)?;
+        let data = serde_json::from_slice::<ClientData>(&raw)?;
+        if data.kind != kind || data.challenge != ceremony.challenge {
+            return Err(Error::ChallengeMismatch);
+        }
+
+        match data.origin == self.origin {
+            true => Ok(raw),
+            false => Err(Error::OriginMismatch),
+        }
+    }
+
+    fn authenticator_data(&self, data: &[u8]) -> Result<AuthenticatorData, Error> {
+        if data.len() < 37 {
+            return Err(Error::Malformed);
+        }
+
+        if data[..32] != *digest(&SHA256, self.id.as_bytes()).as_ref() {
+            return Err(Error::RelyingPartyMismatch);
+        }
+
+        let flags = data[32];
+        if flags & USER_PRESENT == 0 {
+            return Err(Error::UserNotPresent);
+        }
+
+        Ok(AuthenticatorData {
+            flags,
+            sign_count: u32::from_be_bytes([data[33], data[34], data[35], data[36]]),
+        })
+    }
+}
+
+/// Storage for registered credentials, like a database table
+#[async_trait]
+pub trait CredentialStore: Send + Sync {
+    /// All credentials registered for the given user
+    async fn credentials(&self, user_id: &[u8]) -> Vec<Credential>;
+
+    async fn get(&self, id: &[u8]) -> Option<Credential>;
+
+    async fn insert(&self, credential: Credential);
+
+    async fn update_sign_count(&self, id: &[u8], sign_count: u32);
+}
+
+/// A `CredentialStore` keeping credentials in memory
+///
+/// Credentials are lost when the process exits, so this is mostly useful for testing.
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    credentials: Mutex<Vec<Credential>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl CredentialStore for MemoryStore {
+    async fn credentials(&self, user_id: &[u8]) -> Vec<Credential> {
+        let credentials = self.credentials.lock().unwrap();
+        credentials
+            .iter()
+            .filter(|c| c.user_id == user_id)
+            .cloned()
+            .collect()
+    }
+
+    async fn get(&self, id: &[u8]) -> Option<Credential> {
+        let credentials = self.credentials.lock().unwrap();
+        credentials.iter().find(|c| c.id == id).cloned()
+    }
+
+    async fn insert(&self, credential: Credential) {
+        self.credentials.lock().unwrap().push(credential);
+    }
+
+    async fn update_sign_count(&self, id: &[u8], sign_count: u32) {
+        let mut credentials = self.credentials.lock().unwrap();
+        if let Some(credential) = credentials.iter_mut().find(|c| c.id == id) {
+            credential.sign_count = sign_count;
+        }
+    }
+}
+
+/// A registered passkey
+#[derive(Clone, Debug, Deserialize, PartialEq, Eq, Serialize)]
+pub struct Credential {
+    /// The credential ID chosen by the authenticator
+    pub id: Vec<u8>,
+    pub user_id: Vec<u8>,
+    /// The public key, as an uncompressed point (ES256) or raw key (Ed25519)
+    pub public_key: Vec<u8>,
+    /// The COSE algorithm identifier
+    pub algorithm: i64,
+    pub sign_count: u32,
+}
+
+/// Extract the algorithm and public key in the format used by ring from a COSE key
+fn cose_key(key: &cbor::Value<'_>) -> Result<(i64, Vec<u8>), Error> {
+    let bytes = |label| match key.get_int(label) {
+        Some(cbor::Value::Bytes(bytes)) => Ok(*bytes),
+        _ => Err(Error::Malformed),
+    };
+    let int = |label| key.get_int(label).and_then(|v| v.int());
+
+    match (int(KTY), int(ALG), int(CRV)) {
+        (Some(2), Some(ES256), Some(1)) => {
+            let (x, y) = (bytes(X)?, bytes(Y)?);
+            if x.len() != 32 || y.len() != 32 {
+                return Err(Error::Malformed);
+            }
+
+            let mut point = Vec::with_capacity(65);
+            point.push(4);
+            point.extend_from_slice(x);
+            point.extend_from_slice(y);
+            Ok((ES256, point))
+        }
+        (Some(1), Some(EDDSA), Some(6)) => Ok((EDDSA, bytes(X)?.to_vec())),
+        _ => Err(Error::UnsupportedAlgorithm),
+    }
+}
+
+fn descriptors(credentials: &[Credential]) -> Vec<serde_json::Value> {
+    credentials
+        .iter()
+        .map(|c| json!({ "type": "public-key", "id": BASE64URL_NOPAD.encode(&c.id) }))
+        .collect()
+}
+
+fn options_response<B: From<Bytes>>(
+    cookie: HeaderValue,
+    options: &serde_json::Value,
+) -> Result<Response<B>, Error> {
+    Ok(Response::builder()
+        .header(CONTENT_TYPE, "application/json")
+        .header(SET_COOKIE, cookie)
+        .body(B::from(Bytes::from(options.to_string())))
+        .unwrap())
+}
+
+fn decode(s: &str) -> Result<Vec<u8>, Error> {
+    BASE64URL_NOPAD
+        .decode(s.trim_end_matches('=').as_bytes())
+        .map_err(|_| Error::Malformed)
+}
+
+/// The challenge for a ceremony in progress
+#[derive(Deserialize, Serialize)]
+struct Ceremony {
+    kind: Kind,
+    challenge: String,
+    user_id: Option<Vec<u8>>,
+}
+
+impl Ceremony {
+    fn new(kind: Kind, user_id: Option<Vec<u8>>) -> Self {
+        let mut challenge = [0; 32];
+        SystemRandom::new()
+            .fill(&mut challenge)
+            .expect("failed to generate random challenge");
+        Self {
+            kind,
+            challenge: BASE64URL_NOPAD.encode(&challenge),
+            user_id,
+        }
+    }
+}
+
+impl CookieData for Ceremony {
+    fn meta() -> CookieMeta<'static> {
+        CookieMeta {
+            http_only: true,
+            max_age: TIMEOUT as u32,
+            same_site: Some(SameSite::Strict),
+            ..CookieMeta::default()
+        }
+    }
+
+    const NAME: &'static str = "WebAuthn";
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Eq, Serialize)]
+enum Kind {
+    Register,
+    Authenticate,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PublicKeyCredential<R> {
+    raw_id: String,
+    response: R,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Attestation {
+    #[serde(rename = "clientDataJSON")]
+    client_data_json: String,
+    attestation_object: String,
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct Assertion {
+    #[serde(rename = "clientDataJSON")]
+    client_data_json: String,
+    authenticator_data: String,
+    signature: String,
+    user_handle: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct ClientData {
+    #[serde(rename = "type")]
+    kind: String,
+    challenge: String,
+    origin: String,
+}
+
+struct AuthenticatorData {
+    flags: u8,
+    sign_count: u32,
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("no ceremony in progress")]
+    ChallengeMissing,
+    #[error("challenge mismatch")]
+    ChallengeMismatch,
+    #[error("challenge already used")]
+    ChallengeUsed,
+    #[error("origin mismatch")]
+    OriginMismatch,
+    #[error("relying party ID mismatch")]
+    RelyingPartyMismatch,
+    #[error("user not present")]
+    UserNotPresent,
+    #[error("unsupported public key algorithm")]
+    UnsupportedAlgorithm,
+    #[error("credential already registered")]
+    Duplicate,
+    #[error("unknown credential")]
+    UnknownCredential,
+    #[error("invalid signature")]
+    InvalidSignature,
+    #[error("signature counter did not increase")]
+    CounterRegression,
+    #[error("malformed credential")]
+    Malformed,
+    #[error("unable to decode credential: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("cookie error: {0}")]
+    Cookie(#[from] crate::cookies::Error),
+}
+
+/// A minimal CBOR decoder, for the subset used by WebAuthn
+mod cbor {
+    pub(super) enum Value<'a> {
+        Uint(u64),
+        /// A negative integer, encoded as `-1 - n`
+        Neg(u64),
+        Bytes(&'a [u8]),
+        Text(&'a str),
+        Map(Vec<(Value<'a>, Value<'a>)>),
+        /// An array or simple value, which are skipped over but not needed
+        Other,
+    }
+
+    impl<'a> Value<'a> {
+        /// Parse a value from the start of `buf`, returning it and the remaining bytes
+        pub(super) fn parse(buf: &'a [u8]) -> Option<(Self, &'a [u8])> {
+            Self::parse_depth(buf, 0)
+        }
+
+        fn parse_depth(buf: &'a [u8], depth: usize) -> Option<(Self, &'a [u8])> {
+            if depth > MAX_DEPTH {
+                return None;
+            }
+
+            let (&first, mut rest) = buf.split_first()?;
+            let arg = match first & 0x1f {
+                n @ 0..=23 => u64::from(n),
+                n @ 24..=27 => {
+                    let len = 1 << (n - 24);
+                    let (bytes, tail) = (rest.get(..len)?, &rest[len..]);
+                    rest = tail;
+                    bytes.iter().fold(0, |acc, &b| (acc << 8) | u64::from(b))
+                }
+                _ => return None,
+            };
+
+            let value = match first >> 5 {
+                0 => Value::Uint(arg),
+                1 => Value::Neg(arg),
+                2 | 3 => {
+                    let len = usize::try_from(arg).ok()?;
+                    let (bytes, tail) = (rest.get(..len)?, &rest[len..]);
+                    rest = tail;
+                    match first >> 5 {
+                        2 => Value::Bytes(bytes),
+                        _ => Value::Text(std::str::from_utf8(bytes).ok()?),
+                    }
+                }
+                4 => {
+                    for _ in 0..arg {
+                        rest = Self::parse_depth(rest, depth + 1)?.1;
+                    }
+                    Value::Other
+                }
+                5 => {
+                    let mut entries = Vec::new();
+                    for _ in 0..arg {
+                        let (key, tail) = Self::parse_depth(rest, depth + 1)?;
+                        let (value, tail) = Self::parse_depth(tail, depth + 1)?;
+                        entries.push((key, value));
+                        rest = tail;
+                    }
+                    Value::Map(entries)
+                }
+                7 => Value::Other,
+                _ => return None,
+            };
+
+            Some((value, rest))
+        }
+
+        pub(super) fn int(&self) -> Option<i64> {
+            match *self {
+                Value::Uint(n) => i64::try_from(n).ok(),
+                Value::Neg(n) => i64::try_from(n).ok().map(|n| -1 - n),
+                _ => None,
+            }
+        }
+
+        pub(super) fn get_int(&self, label: i64) -> Option<&Value<'a>> {
+            self.get(|key| key.int() == Some(label))
+        }
+
+        pub(super) fn get_text(&self, label: &str) -> Option<&Value<'a>> {
+            self.get(|key| matches!(key, Value::Text(text) if *text == label))
+        }
+
+        fn get(&self, f: impl Fn(&Value<'a>) -> bool) -> Option<&Value<'a>> {
+            match self {
+                Value::Map(entries) => entries.iter().find(|(k, _)| f(k)).map(|(_, v)| v),
+                _ => None,
+            }
+        }
+    }
+
+    const MAX_DEPTH: usize = 16;
+}
+
+const ES256: i64 = -7;
+const EDDSA: i64 = -8;
+const KTY: i64 = 1;
+const ALG: i64 = 3;
+const CRV: i64 = -1;
+const X: i64 = -2;
+const Y: i64 = -3;
+const USER_PRESENT: u8 = 0x01;
+const ATTESTED_CREDENTIAL: u8 = 0x40;
+/// How long a ceremony can take, in seconds
+const TIMEOUT: u64 = 5 * 60;
+
+#[cfg(test)]
+mod tests {
+    use http::header::COOKIE;
+    use http::Request;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    use super::*;
+    use crate::application::{Application, Context, ErrorKind, IntoResponse};
+    use crate::cookies::Key;
+
+    #[tokio::test]
+    async fn ceremonies() {
+        let app = App {
+            key: Key::new(&[5; 32]),
+        };
+        let rp = RelyingParty::new(
+            MemoryStore::new(),
+            "example.com",
+            "Example",
+            "https://example.com",
+        );
+
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+
+        // Registration
+        let rsp = rp
+            .start_registration::<_, Bytes>(&app, b"user-1", "user@example.com", "User")
+            .await
+            .unwrap();
+        let (req, challenge) = next_request(&rsp);
+        let data = client_data("webauthn.create", &challenge, "https://example.com");
+
+        let mut cose = vec![0xa4, 0x01, 0x01, 0x03, 0x27, 0x20, 0x06, 0x21, 0x58, 0x20];
+        cose.extend_from_slice(pair.public_key().as_ref());
+        let mut auth_data = rp_id_hash();
+        auth_data.extend_from_slice(&[0x41, 0, 0, 0, 0]);
+        auth_data.extend_from_slice(&[0; 16]);
+        auth_data.extend_from_slice(&[0, 16]);
+        auth_data.extend_from_slice(&[9; 16]);
+        auth_data.extend_from_slice(&cose);
+
+        let mut object = vec![0xa3, 0x63];
+        object.extend_from_slice(b"fmt");
+        object.push(0x64);
+        object.extend_from_slice(b"none");
+        object.push(0x67);
+        object.extend_from_slice(b"attStmt");
+        object.extend_from_slice(&[0xa0, 0x68]);
+        object.extend_from_slice(b"authData");
+        object.extend_from_slice(&[0x58, auth_data.len() as u8]);
+        object.extend_from_slice(&auth_data);
+
+        let body = json!({
+            "id": BASE64URL_NOPAD.encode(&[9; 16]),
+            "rawId": BASE64URL_NOPAD.encode(&[9; 16]),
+            "type": "public-key",
+            "response": {
+                "clientDataJSON": BASE64URL_NOPAD.encode(&data),
+                "attestationObject": BASE64URL_NOPAD.encode(&object),
+            },
+        });
+        let body = body.to_string();
+        let credential = rp
+            .finish_registration(&app, &req, body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(credential.user_id, b"user-1");
+        assert_eq!(credential.algorithm, EDDSA);
+        assert!(matches!(
+            rp.finish_registration(&app, &req, body.as_bytes()).await,
+            Err(Error::Duplicate)
+        ));
+
+        // Authentication
+        let assertion = |challenge: &str, origin: &str, sign_count: u32| {
+            let client_data = client_data("webauthn.get", challenge, origin);
+            let mut auth_data = rp_id_hash();
+            auth_data.push(0x01);
+            auth_data.extend_from_slice(&sign_count.to_be_bytes());
+            let mut message = auth_data.clone();
+            message.extend_from_slice(digest(&SHA256, &client_data).as_ref());
+            json!({
+                "id": BASE64URL_NOPAD.encode(&[9; 16]),
+                "rawId": BASE64URL_NOPAD.encode(&[9; 16]),
+                "type": "public-key",
+                "response": {
+                    "clientDataJSON": BASE64URL_NOPAD.encode(&client_data),
+                    "authenticatorData": BASE64URL_NOPAD.encode(&auth_data),
+                    "signature": BASE64URL_NOPAD.encode(pair.sign(&message).as_ref()),
+                    "userHandle": BASE64URL_NOPAD.encode(b"user-1"),
+                },
+            })
+            .to_string()
+        };
+
+        let rsp = rp
+            .start_authentication::<_, Bytes>(&app, None)
+            .await
+            .unwrap();
+        let (req, challenge) = next_request(&rsp);
+        assert!(matches!(
+            rp.finish_authentication(
+                &app,
+                &req,
+                assertion(&challenge, "https://evil.com", 1).as_bytes()
+            )
+            .await,
+            Err(Error::OriginMismatch)
+        ));
+
+        let body = assertion(&challenge, "https://example.com", 1);
+        let credential = rp
+            .finish_authentication(&app, &req, body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(credential.sign_count, 1);
+
+        let rsp = rp
+            .start_authentication::<_, Bytes>(&app, None)
+            .await
+            .unwrap();
+        let (req, challenge) = next_request(&rsp);
+        assert!(matches!(
+            rp.finish_authentication(
+                &app,
+                &req,
+                assertion(&challenge, "https://example.com", 1).as_bytes()
+            )
+            .await,
+            Err(Error::CounterRegression)
+        ));
+    }
+
+    #[tokio::test]
+    async fn replay() {
+        let app = App {
+            key: Key::new(&[5; 32]),
+        };
+        let rp = RelyingParty::new(
+            MemoryStore::new(),
+            "example.com",
+            "Example",
+            "https://example.com",
+        );
+
+        // A synced passkey, which never increments its counter
+        let rng = SystemRandom::new();
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&rng).unwrap();
+        let pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        rp.store
+            .insert(Credential {
+                id: vec![9; 16],
+                user_id: b"user-1".to_vec(),
+                public_key: pair.public_key().as_ref().to_vec(),
+                algorithm: EDDSA,
+                sign_count: 0,
+            })
+            .await;
+
+        let rsp = rp
+            .start_authentication::<_, Bytes>(&app, None)
+            .await
+            .unwrap();
+        let (req, challenge) = next_request(&rsp);
+        let client_data = client_data("webauthn.get", &challenge, "https://example.com");
+        let mut auth_data = rp_id_hash();
+        auth_data.extend_from_slice(&[0x01, 0, 0, 0, 0]);
+        let mut message = auth_data.clone();
+        message.extend_from_slice(digest(&SHA256, &client_data).as_ref());
+        let body = json!({
+            "rawId": BASE64URL_NOPAD.encode(&[9; 16]),
+            "response": {
+                "clientDataJSON": BASE64URL_NOPAD.encode(&client_data),
+                "authenticatorData": BASE64URL_NOPAD.encode(&auth_data),
+                "signature": BASE64URL_NOPAD.encode(pair.sign(&message).as_ref()),
+            },
+        })
+        .to_string();
+
+        let credential = rp
+            .finish_authentication(&app, &req, body.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(credential.sign_count, 0);
+        assert!(matches!(
+            rp.finish_authentication(&app, &req, body.as_bytes()).await,
+            Err(Error::ChallengeUsed)
+        ));
+    }
+
+    fn next_request(rsp: &Response<Bytes>) -> (Parts, String) {
+        let cookie = rsp.headers()[SET_COOKIE].to_str().unwrap();
+        let cookie = cookie.split(';').next().unwrap();
+        let (mut req, _) = Request::new(()).into_parts();
+        req.headers.insert(COOKIE, cookie.parse().unwrap());
+
+        let options = serde_json::from_slice::<serde_json::Value>(rsp.body()).unwrap();
+        (req, options["challenge"].as_str().unwrap().to_owned())
+    }
+
+    fn client_data(kind: &str, challenge: &str, origin: &str) -> Vec<u8> {
+        json!({ "type": kind, "challenge": challenge, "origin": origin })
+            .to_string()
+            .into_bytes()
+    }
+
+    fn rp_id_hash() -> Vec<u8> {
+        digest(&SHA256, b"example.com").as_ref().to_vec()
+    }
+
+    struct App {
+        key: Key,
+    }
+
+    impl AppWithAeadKey for App {
+        fn key(&self) -> &Key {
+            &self.key
+        }
+    }
+
+    #[async_trait]
+    impl Application for App {
+        type RequestBody = ();
+        type ResponseBody = crate::Body;
+        type Error = crate::Error;
+
+        async fn handle(cx: Context<Self>) -> Response<crate::Body> {
+            crate::Error::from(ErrorKind::PathNotFound).into_response(&*cx.app, &cx.req)
+        }
+    }
+}
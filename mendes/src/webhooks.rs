@@ -0,0 +1,415 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use data_encoding::{BASE64, HEXLOWER_PERMISSIVE};
+use http::request::Parts;
+use http::HeaderMap;
+use http_body::Body as HttpBody;
+use ring::hmac;
+
+use crate::application::{read_body, Application, ErrorKind, FromContextAsync, PathState};
+use crate::utils::constant_time_eq;
+
+#[cfg(feature = "webhook-dispatch")]
+#[cfg_attr(docsrs, doc(cfg(feature = "webhook-dispatch")))]
+pub mod dispatch;
+
+/// Selects the `Verifier` for webhooks from a particular sender
+///
+/// Sources are usually unit structs, so that an application can receive webhooks from
+/// several senders:
+///
+/// ```no_run
+/// # use mendes::webhooks::{Verifier, WebhookSource};
+/// # struct App {
+/// #     stripe_webhooks: Verifier,
+/// # }
+/// struct Stripe;
+///
+/// impl WebhookSource<App> for Stripe {
+///     fn verifier(app: &App) -> &Verifier {
+///         &app.stripe_webhooks
+///     }
+/// }
+/// ```
+pub trait WebhookSource<A> {
+    fn verifier(app: &A) -> &Verifier;
+}
+
+/// A webhook request with a verified signature
+///
+/// The extractor consumes the request body to check the signature, and makes the raw bytes
+/// available for decoding. It is asynchronous, so the handler argument must be annotated
+/// with `#[async_extract]`:
+///
+/// ```no_run
+/// # use mendes::http::request::Parts;
+/// # use mendes::http::Response;
+/// # use mendes::webhooks::{Verifier, Webhook, WebhookSource};
+/// # use mendes::{handler, Application, Body, Error};
+/// # use serde::Deserialize;
+/// # #[derive(Deserialize)]
+/// # struct Event {}
+/// # struct App {
+/// #     stripe_webhooks: Verifier,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct Stripe;
+/// # impl WebhookSource<App> for Stripe {
+/// #     fn verifier(app: &App) -> &Verifier {
+/// #         &app.stripe_webhooks
+/// #     }
+/// # }
+/// #[handler(POST)]
+/// async fn stripe(
+///     app: &App,
+///     req: &Parts,
+///     #[async_extract] hook: Webhook<Stripe>,
+/// ) -> Result<Response<Body>, Error> {
+///     let event = App::from_body_bytes::<Event>(req, &hook.body)?;
+///     todo!()
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Requests with a missing or invalid signature are rejected with a `WebhookInvalid` error,
+/// requests with a timestamp outside the tolerance with a `WebhookExpired` error and
+/// requests that were seen before with a `WebhookReplayed` error.
+pub struct Webhook<S> {
+    /// The raw request body, as signed by the sender
+    pub body: Bytes,
+    /// The delivery id, if the scheme has one
+    pub id: Option<String>,
+    /// The time at which the sender signed the request, if the scheme includes it
+    pub timestamp: Option<SystemTime>,
+    source: PhantomData<fn() -> S>,
+}
+
+#[async_trait]
+impl<'a, A, S> FromContextAsync<'a, A> for Webhook<S>
+where
+    A: Application + Sync,
+    A::RequestBody: HttpBody + Send,
+    <A::RequestBody as HttpBody>::Data: Send,
+    <A::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
+    S: WebhookSource<A>,
+{
+    async fn from_context(
+        app: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let body = match body.take() {
+            Some(body) => body,
+            None => panic!("attempted to retrieve body twice"),
+        };
+
+        let verifier = S::verifier(app);
+        let body = read_body(body, verifier.max_len)
+            .await
+            .map_err(|e| state.reject::<A>(e, req))?;
+        match verifier.verify(&req.headers, &body) {
+            Ok(verified) => Ok(Webhook {
+                body,
+                id: verified.id,
+                timestamp: verified.timestamp,
+                source: PhantomData,
+            }),
+            Err(kind) => Err(state.reject::<A>(kind.into(), req)),
+        }
+    }
+}
+
+/// Verifies webhook signatures (HMAC-SHA256) for one of the common schemes
+///
+/// For schemes that sign a timestamp, requests are only accepted within the tolerance
+/// (5 minutes by default) of the current time. Accepted signatures are remembered until
+/// their timestamp falls outside the tolerance, so that replays are rejected. This state
+/// is kept in memory, so applications running on several hosts should also deduplicate
+/// deliveries by id (or by the event id in the payload).
+pub struct Verifier {
+    scheme: Scheme,
+    key: hmac::Key,
+    tolerance: Duration,
+    max_len: usize,
+    seen: Mutex<HashMap<Vec<u8>, SystemTime>>,
+}
+
+impl Verifier {
+    /// Verify Stripe's `Stripe-Signature` header, with the endpoint secret (`whsec_...`)
+    pub fn stripe(secret: &str) -> Self {
+        Self::new(Scheme::Stripe, secret.as_bytes())
+    }
+
+    /// Verify GitHub's `X-Hub-Signature-256` header, with the webhook secret
+    ///
+    /// GitHub doesn't sign a timestamp, so replays can only be detected within the
+    /// tolerance period.
+    pub fn github(secret: &str) -> Self {
+        Self::new(Scheme::GitHub, secret.as_bytes())
+    }
+
+    /// Verify the `webhook-id`, `webhook-timestamp` and `webhook-signature` headers of the
+    /// Standard Webhooks specification, with a base64-encoded (`whsec_...`) secret
+    ///
+    /// Returns `None` if the secret isn't valid base64.
+    pub fn standard(secret: &str) -> Option<Self> {
+        let secret = secret.strip_prefix("whsec_").unwrap_or(secret);
+        let secret = BASE64.decode(secret.as_bytes()).ok()?;
+        Some(Self::new(Scheme::Standard, &secret))
+    }
+
+    fn new(scheme: Scheme, secret: &[u8]) -> Self {
+        Self {
+            scheme,
+            key: hmac::Key::new(hmac::HMAC_SHA256, secret),
+            tolerance: Duration::from_secs(5 * 60),
+            max_len: 1024 * 1024,
+            seen: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Accept timestamps up to `tolerance` before or after the current time
+    pub fn tolerance(mut self, tolerance: Duration) -> Self {
+        self.tolerance = tolerance;
+        self
+    }
+
+    /// Reject bodies larger than `max_len` bytes (defaults to 1 MiB)
+    pub fn max_len(mut self, max_len: usize) -> Self {
+        self.max_len = max_len;
+        self
+    }
+
+    /// Check the signature of a request with the given `headers` and `body`
+    pub fn verify(&self, headers: &HeaderMap, body: &[u8]) -> Result<Verified, ErrorKind> {
+        self.verify_at(headers, body, SystemTime::now())
+    }
+
+    /// Check the signature of a request against the given time
+    pub fn verify_at(
+        &self,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: SystemTime,
+    ) -> Result<Verified, ErrorKind> {
+        let header = |name| {
+            headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .ok_or(ErrorKind::WebhookInvalid)
+        };
+
+        let (signed, signatures, id, timestamp) = match self.scheme {
+            Scheme::Stripe => {
+                let mut timestamp = None;
+                let mut signatures = Vec::new();
+                for item in header("stripe-signature")?.split(',') {
+                    match item.trim().split_once('=') {
+                        Some(("t", value)) => timestamp = Some(value),
+                        Some(("v1", value)) => signatures.push(hex(value)),
+                        _ => {}
+                    }
+                }
+
+                let timestamp = timestamp.ok_or(ErrorKind::WebhookInvalid)?;
+                let mut signed = format!("{timestamp}.").into_bytes();
+                signed.extend_from_slice(body);
+                (signed, signatures, None, Some(timestamp))
+            }
+            Scheme::GitHub => {
+                let signature = header("x-hub-signature-256")?
+                    .strip_prefix("sha256=")
+                    .ok_or(ErrorKind::WebhookInvalid)?;
+                let id = header("x-github-delivery").ok();
+                (body.to_vec(), vec![hex(signature)], id, None)
+            }
+            Scheme::Standard => {
+                let id = header("webhook-id")?;
+                let timestamp = header("webhook-timestamp")?;
+                let signatures = header("webhook-signature")?
+                    .split(' ')
+                    .filter_map(|item| item.strip_prefix("v1,"))
+                    .map(|value| BASE64.decode(value.as_bytes()).ok())
+                    .collect();
+
+                let mut signed = format!("{id}.{timestamp}.").into_bytes();
+                signed.extend_from_slice(body);
+                (signed, signatures, Some(id), Some(timestamp))
+            }
+        };
+
+        let tag = hmac::sign(&self.key, &signed);
+        let mut valid = false;
+        // Check all signatures (senders include several while rotating secrets)
+        for signature in signatures.iter().flatten() {
+            valid |= constant_time_eq(signature, tag.as_ref());
+        }
+        if !valid {
+            return Err(ErrorKind::WebhookInvalid);
+        }
+
+        // Only interpret the timestamp once the signature shows it comes from the sender
+        let verified = Verified {
+            id: id.map(str::to_owned),
+            timestamp: timestamp.map(parse_timestamp).transpose()?,
+        };
+        if let Some(timestamp) = verified.timestamp {
+            let delta = match now.duration_since(timestamp) {
+                Ok(delta) => delta,
+                Err(err) => err.duration(),
+            };
+            if delta > self.tolerance {
+                return Err(ErrorKind::WebhookExpired);
+            }
+        }
+
+        let mut seen = self.seen.lock().unwrap();
+        seen.retain(|_, expires| *expires > now);
+        // Timestamps past the tolerance are rejected anyway, so signatures can be forgotten then
+        let expires = verified.timestamp.unwrap_or(now) + self.tolerance;
+        match seen.insert(tag.as_ref().to_vec(), expires) {
+            Some(_) => Err(ErrorKind::WebhookReplayed),
+            None => Ok(verified),
+        }
+    }
+}
+
+/// The details of a verified webhook request
+#[derive(Clone, Debug)]
+pub struct Verified {
+    /// The delivery id, if the scheme has one
+    pub id: Option<String>,
+    /// The time at which the sender signed the request, if the scheme includes it
+    pub timestamp: Option<SystemTime>,
+}
+
+enum Scheme {
+    Stripe,
+    GitHub,
+    Standard,
+}
+
+fn hex(value: &str) -> Option<Vec<u8>> {
+    HEXLOWER_PERMISSIVE.decode(value.as_bytes()).ok()
+}
+
+fn parse_timestamp(value: &str) -> Result<SystemTime, ErrorKind> {
+    value
+        .parse::<u64>()
+        .ok()
+        .and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+        .ok_or(ErrorKind::WebhookInvalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use data_encoding::HEXLOWER;
+
+    use super::*;
+
+    #[test]
+    fn stripe() {
+        let verifier = Verifier::stripe("whsec_test");
+        let body = br#"{"id":"evt_1"}"#;
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let signature = sign(b"whsec_test", b"1700000000.", body);
+
+        let headers = headers(&[(
+            "stripe-signature",
+            &format!("t=1700000000,v1=00ff,v1={signature}"),
+        )]);
+        let verified = verifier.verify_at(&headers, body, now).unwrap();
+        assert_eq!(verified.timestamp, Some(now));
+        assert_eq!(
+            verifier.verify_at(&headers, body, now).unwrap_err(),
+            ErrorKind::WebhookReplayed
+        );
+
+        let later = now + Duration::from_secs(301);
+        let verifier = Verifier::stripe("whsec_test");
+        assert_eq!(
+            verifier.verify_at(&headers, body, later).unwrap_err(),
+            ErrorKind::WebhookExpired
+        );
+        assert_eq!(
+            verifier.verify_at(&headers, b"{}", now).unwrap_err(),
+            ErrorKind::WebhookInvalid
+        );
+
+        let timestamp = u64::MAX.to_string();
+        let signature = sign(b"whsec_test", format!("{timestamp}.").as_bytes(), body);
+        let overflow =
+            self::headers(&[("stripe-signature", &format!("t={timestamp},v1={signature}"))]);
+        assert_eq!(
+            verifier.verify_at(&overflow, body, now).unwrap_err(),
+            ErrorKind::WebhookInvalid
+        );
+    }
+
+    #[test]
+    fn github() {
+        let verifier = Verifier::github("secret");
+        let body = b"{}";
+        let signature = sign(b"secret", b"", body);
+        let headers = headers(&[
+            ("x-hub-signature-256", &format!("sha256={signature}")),
+            ("x-github-delivery", "72d3162e"),
+        ]);
+        let verified = verifier.verify(&headers, body).unwrap();
+        assert_eq!(verified.id.as_deref(), Some("72d3162e"));
+        assert_eq!(verified.timestamp, None);
+    }
+
+    #[test]
+    fn standard() {
+        let secret = [7; 24];
+        let verifier = Verifier::standard(&format!("whsec_{}", BASE64.encode(&secret))).unwrap();
+        let body = b"{}";
+        let now = SystemTime::now();
+        let timestamp = now.duration_since(UNIX_EPOCH).unwrap().as_secs();
+        let prefix = format!("msg_1.{timestamp}.");
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &secret);
+        let mut signed = prefix.into_bytes();
+        signed.extend_from_slice(body);
+        let signature = BASE64.encode(hmac::sign(&key, &signed).as_ref());
+
+        let headers = headers(&[
+            ("webhook-id", "msg_1"),
+            ("webhook-timestamp", &timestamp.to_string()),
+            ("webhook-signature", &format!("v1,Zm9v v1,{signature}")),
+        ]);
+        let verified = verifier.verify(&headers, body).unwrap();
+        assert_eq!(verified.id.as_deref(), Some("msg_1"));
+        assert!(Verifier::standard("whsec_!").is_none());
+    }
+
+    fn sign(secret: &[u8], prefix: &[u8], body: &[u8]) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, secret);
+        let mut signed = prefix.to_vec();
+        signed.extend_from_slice(body);
+        HEXLOWER.encode(hmac::sign(&key, &signed).as_ref())
+    }
+
+    fn headers(pairs: &[(&'static str, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.insert(*name, value.parse().unwrap());
+        }
+        headers
+    }
+}
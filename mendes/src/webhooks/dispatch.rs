@@ -0,0 +1,395 @@
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use data_encoding::{BASE64, HEXLOWER};
+use http::header::CONTENT_TYPE;
+use http::{HeaderMap, HeaderValue, StatusCode};
+use ring::hmac;
+
+/// Delivers webhooks to the application's users, retrying with exponential backoff
+///
+/// Events are signed following the Standard Webhooks specification, so receivers can use
+/// existing libraries (or `Verifier::standard()`) to check them:
+///
+/// ```no_run
+/// # use std::error::Error;
+/// # use std::time::Duration;
+/// # use mendes::webhooks::dispatch::{Dispatcher, Endpoint, MemoryStore, WebhookClient};
+/// # struct App<C> {
+/// #     webhooks: Dispatcher<MemoryStore, C>,
+/// # }
+/// # struct Db;
+/// # impl Db {
+/// #     async fn save_endpoint(&self, user: u64, url: &str, secret: &str) -> Result<(), Box<dyn Error>> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # struct User {
+/// #     id: u64,
+/// # }
+/// # #[cfg(feature = "json")]
+/// # async fn example<C: WebhookClient>(app: &App<C>, db: &Db, user: User) -> Result<(), Box<dyn Error>> {
+/// # let event = serde_json::json!({});
+/// let endpoint = Endpoint::generate("https://customer.example.com/hooks");
+/// db.save_endpoint(user.id, &endpoint.url, &endpoint.secret_string()).await?;
+/// // ...
+/// let delivery = app.webhooks.send(&endpoint, serde_json::to_vec(&event)?).await;
+///
+/// // In a background task
+/// app.webhooks.run(Duration::from_secs(5)).await;
+/// # Ok(())
+/// # }
+/// ```
+///
+/// Deliveries are persisted through the `DeliveryStore` before the first attempt, so they
+/// survive restarts if the store does. A delivery succeeds when the endpoint responds with
+/// a 2xx status; after `max_attempts` failed attempts it is marked as failed.
+pub struct Dispatcher<S, C> {
+    store: S,
+    client: C,
+    initial_backoff: Duration,
+    max_attempts: u32,
+}
+
+impl<S: DeliveryStore, C: WebhookClient> Dispatcher<S, C> {
+    pub fn new(store: S, client: C) -> Self {
+        Self {
+            store,
+            client,
+            initial_backoff: Duration::from_secs(30),
+            max_attempts: 8,
+        }
+    }
+
+    /// Wait `initial` before the first retry, doubling the wait for every later retry, and
+    /// give up after `max_attempts` attempts
+    ///
+    /// Defaults to 30 seconds and 8 attempts, for a last retry about an hour after the event.
+    pub fn backoff(mut self, initial: Duration, max_attempts: u32) -> Self {
+        self.initial_backoff = initial;
+        self.max_attempts = max_attempts;
+        self
+    }
+
+    /// Queue `payload` (usually JSON) for delivery to `endpoint`
+    pub async fn send(&self, endpoint: &Endpoint, payload: impl Into<Bytes>) -> Delivery {
+        let delivery = Delivery {
+            id: format!("msg_{}", random_hex()),
+            endpoint: endpoint.clone(),
+            payload: payload.into(),
+            status: Status::Pending,
+            attempts: 0,
+            next_attempt: Some(SystemTime::now()),
+            last_error: None,
+        };
+        self.store.insert(&delivery).await;
+        delivery
+    }
+
+    /// The current state of the delivery with the given `id`
+    pub async fn status(&self, id: &str) -> Option<Delivery> {
+        self.store.get(id).await
+    }
+
+    /// Attempt all deliveries that are due at `now`
+    ///
+    /// Returns the number of deliveries attempted.
+    pub async fn deliver_due(&self, now: SystemTime) -> usize {
+        let due = self.store.due(now).await;
+        let count = due.len();
+        for mut delivery in due {
+            self.attempt(&mut delivery, now).await;
+            self.store.update(&delivery).await;
+        }
+        count
+    }
+
+    /// Deliver due events every `interval`, forever
+    pub async fn run(&self, interval: Duration) {
+        loop {
+            self.deliver_due(SystemTime::now()).await;
+            tokio::time::sleep(interval).await;
+        }
+    }
+
+    async fn attempt(&self, delivery: &mut Delivery, now: SystemTime) {
+        let timestamp = now.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let mut headers = HeaderMap::new();
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let signature = delivery
+            .endpoint
+            .sign(&delivery.id, timestamp, &delivery.payload);
+        for (name, value) in [
+            ("webhook-id", delivery.id.clone()),
+            ("webhook-timestamp", timestamp.to_string()),
+            ("webhook-signature", format!("v1,{signature}")),
+        ] {
+            // Ids and signatures are ASCII, so these can't fail
+            headers.insert(name, HeaderValue::try_from(value).unwrap());
+        }
+
+        let result = self
+            .client
+            .post(&delivery.endpoint.url, headers, delivery.payload.clone())
+            .await;
+        delivery.attempts += 1;
+        let error = match result {
+            Ok(status) if status.is_success() => {
+                delivery.status = Status::Delivered;
+                delivery.next_attempt = None;
+                delivery.last_error = None;
+                return;
+            }
+            Ok(status) => format!("endpoint responded with {status}"),
+            Err(error) => error.to_string(),
+        };
+
+        delivery.last_error = Some(error);
+        match delivery.attempts >= self.max_attempts {
+            true => {
+                delivery.status = Status::Failed;
+                delivery.next_attempt = None;
+            }
+            false => {
+                let backoff = self.initial_backoff * 2u32.saturating_pow(delivery.attempts - 1);
+                delivery.next_attempt = Some(now + backoff);
+            }
+        }
+    }
+}
+
+/// A URL that receives webhooks, with the secret used to sign them
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Endpoint {
+    pub url: String,
+    pub secret: Vec<u8>,
+}
+
+impl Endpoint {
+    pub fn new(url: impl Into<String>, secret: Vec<u8>) -> Self {
+        Self {
+            url: url.into(),
+            secret,
+        }
+    }
+
+    /// Create an endpoint with a new random secret
+    pub fn generate(url: impl Into<String>) -> Self {
+        let mut secret = vec![0; SECRET_LEN];
+        getrandom::getrandom(&mut secret).expect("failed to generate random webhook secret");
+        Self::new(url, secret)
+    }
+
+    /// Create an endpoint from a secret returned by `secret_string()`
+    pub fn from_secret_string(url: impl Into<String>, secret: &str) -> Option<Self> {
+        let secret = secret.strip_prefix("whsec_").unwrap_or(secret);
+        let secret = BASE64.decode(secret.as_bytes()).ok()?;
+        Some(Self::new(url, secret))
+    }
+
+    /// The secret in the `whsec_...` form, to be shown to the receiver
+    pub fn secret_string(&self) -> String {
+        format!("whsec_{}", BASE64.encode(&self.secret))
+    }
+
+    fn sign(&self, id: &str, timestamp: u64, payload: &[u8]) -> String {
+        let key = hmac::Key::new(hmac::HMAC_SHA256, &self.secret);
+        let mut ctx = hmac::Context::with_key(&key);
+        ctx.update(format!("{id}.{timestamp}.").as_bytes());
+        ctx.update(payload);
+        BASE64.encode(ctx.sign().as_ref())
+    }
+}
+
+/// A webhook event and the state of its delivery
+#[derive(Clone, Debug)]
+pub struct Delivery {
+    pub id: String,
+    pub endpoint: Endpoint,
+    pub payload: Bytes,
+    pub status: Status,
+    /// The number of attempts made so far
+    pub attempts: u32,
+    /// When the next attempt is due, for pending deliveries
+    pub next_attempt: Option<SystemTime>,
+    /// The error from the last failed attempt
+    pub last_error: Option<String>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Status {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+/// Sends webhook requests
+///
+/// Implement this with your HTTP client of choice. Implementations should apply a timeout,
+/// and should refuse to connect to private addresses since endpoint URLs are supplied by
+/// users.
+#[async_trait]
+pub trait WebhookClient: Send + Sync {
+    async fn post(
+        &self,
+        url: &str,
+        headers: HeaderMap,
+        body: Bytes,
+    ) -> Result<StatusCode, Box<dyn StdError + Send + Sync>>;
+}
+
+/// Storage for webhook deliveries
+#[async_trait]
+pub trait DeliveryStore: Send + Sync {
+    async fn insert(&self, delivery: &Delivery);
+    async fn get(&self, id: &str) -> Option<Delivery>;
+    /// Pending deliveries with a `next_attempt` at or before `now`
+    async fn due(&self, now: SystemTime) -> Vec<Delivery>;
+    async fn update(&self, delivery: &Delivery);
+}
+
+/// A `DeliveryStore` that keeps deliveries in memory, for tests and development
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    deliveries: Mutex<HashMap<String, Delivery>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl DeliveryStore for MemoryStore {
+    async fn insert(&self, delivery: &Delivery) {
+        let mut deliveries = self.deliveries.lock().unwrap();
+        deliveries.insert(delivery.id.clone(), delivery.clone());
+    }
+
+    async fn get(&self, id: &str) -> Option<Delivery> {
+        self.deliveries.lock().unwrap().get(id).cloned()
+    }
+
+    async fn due(&self, now: SystemTime) -> Vec<Delivery> {
+        let deliveries = self.deliveries.lock().unwrap();
+        deliveries
+            .values()
+            .filter(|delivery| delivery.status == Status::Pending)
+            .filter(|delivery| delivery.next_attempt.is_some_and(|next| next <= now))
+            .cloned()
+            .collect()
+    }
+
+    async fn update(&self, delivery: &Delivery) {
+        self.insert(delivery).await;
+    }
+}
+
+fn random_hex() -> String {
+    let mut buf = [0; 16];
+    getrandom::getrandom(&mut buf).expect("failed to generate random webhook id");
+    HEXLOWER.encode(&buf)
+}
+
+const SECRET_LEN: usize = 24;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::webhooks::Verifier;
+
+    #[tokio::test]
+    async fn retries() {
+        let dispatcher = Dispatcher::new(MemoryStore::new(), MockClient::default())
+            .backoff(Duration::from_secs(10), 3);
+        let endpoint = Endpoint::generate("https://example.com/hooks");
+        let delivery = dispatcher.send(&endpoint, &b"{}"[..]).await;
+        assert_eq!(delivery.status, Status::Pending);
+
+        let now = SystemTime::now();
+        assert_eq!(dispatcher.deliver_due(now).await, 1);
+        let delivery = dispatcher.status(&delivery.id).await.unwrap();
+        assert_eq!(delivery.attempts, 1);
+        assert_eq!(delivery.next_attempt, Some(now + Duration::from_secs(10)));
+        assert_eq!(
+            delivery.last_error.as_deref(),
+            Some("endpoint responded with 500 Internal Server Error")
+        );
+
+        // Not due yet
+        assert_eq!(dispatcher.deliver_due(now).await, 0);
+        let now = now + Duration::from_secs(10);
+        assert_eq!(dispatcher.deliver_due(now).await, 1);
+        let delivery = dispatcher.status(&delivery.id).await.unwrap();
+        assert_eq!(delivery.status, Status::Delivered);
+        assert_eq!(delivery.attempts, 2);
+
+        // The receiver can verify the signature with the endpoint secret
+        let (headers, body) = dispatcher.client.requests.lock().unwrap().pop().unwrap();
+        let verifier = Verifier::standard(&endpoint.secret_string()).unwrap();
+        let verified = verifier.verify_at(&headers, &body, now).unwrap();
+        assert_eq!(verified.id, Some(delivery.id));
+    }
+
+    #[tokio::test]
+    async fn give_up() {
+        let dispatcher =
+            Dispatcher::new(MemoryStore::new(), FailingClient).backoff(Duration::from_secs(1), 2);
+        let endpoint = Endpoint::generate("https://example.com/hooks");
+        let delivery = dispatcher.send(&endpoint, &b"{}"[..]).await;
+        let now = SystemTime::now();
+        dispatcher.deliver_due(now).await;
+        dispatcher.deliver_due(now + Duration::from_secs(1)).await;
+
+        let delivery = dispatcher.status(&delivery.id).await.unwrap();
+        assert_eq!(delivery.status, Status::Failed);
+        assert_eq!(delivery.next_attempt, None);
+        assert_eq!(delivery.last_error.as_deref(), Some("connection refused"));
+        assert_eq!(
+            Endpoint::from_secret_string(&endpoint.url, &endpoint.secret_string()),
+            Some(endpoint)
+        );
+    }
+
+    /// Fails the first request, then accepts all others
+    #[derive(Default)]
+    struct MockClient {
+        requests: Mutex<Vec<(HeaderMap, Bytes)>>,
+    }
+
+    #[async_trait]
+    impl WebhookClient for MockClient {
+        async fn post(
+            &self,
+            _: &str,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> Result<StatusCode, Box<dyn StdError + Send + Sync>> {
+            let mut requests = self.requests.lock().unwrap();
+            requests.push((headers, body));
+            Ok(match requests.len() {
+                1 => StatusCode::INTERNAL_SERVER_ERROR,
+                _ => StatusCode::NO_CONTENT,
+            })
+        }
+    }
+
+    struct FailingClient;
+
+    #[async_trait]
+    impl WebhookClient for FailingClient {
+        async fn post(
+            &self,
+            _: &str,
+            _: HeaderMap,
+            _: Bytes,
+        ) -> Result<StatusCode, Box<dyn StdError + Send + Sync>> {
+            Err("connection refused".into())
+        }
+    }
+}
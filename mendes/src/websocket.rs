@@ -0,0 +1,519 @@
+use std::future::Future;
+use std::io;
+use std::sync::Arc;
+
+use data_encoding::BASE64;
+use http::header::{HeaderName, UPGRADE};
+use http::header::{CONNECTION, SEC_WEBSOCKET_ACCEPT, SEC_WEBSOCKET_KEY, SEC_WEBSOCKET_VERSION};
+use http::request::Parts;
+use http::{Method, Response, StatusCode};
+use hyper::upgrade::{OnUpgrade, Upgraded};
+use hyper_util::rt::TokioIo;
+use thiserror::Error;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tracing::debug;
+
+use crate::application::{Application, ErrorKind, FromContext, PathState};
+
+mod hub;
+pub use hub::{Connection, Hub, Member};
+
+/// A request to upgrade the connection to a WebSocket
+///
+/// Respond with the result of `on_upgrade()`, which runs the given closure once the client has
+/// switched protocols:
+///
+/// ```no_run
+/// # use std::sync::Arc;
+/// # use mendes::http::Response;
+/// # use mendes::websocket::{Message, WebSocketUpgrade};
+/// # use mendes::{handler, Body, Error};
+/// # struct Chat;
+/// # impl Chat {
+/// #     async fn post(&self, text: String) {}
+/// # }
+/// # struct App {
+/// #     chat: Chat,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = Body;
+/// #     type ResponseBody = Body;
+/// #     type Error = Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> Response<Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn chat(app: &Arc<App>, ws: WebSocketUpgrade) -> Result<Response<Body>, Error> {
+///     let app = app.clone();
+///     Ok(ws.on_upgrade(move |mut socket| async move {
+///         while let Ok(Some(Message::Text(text))) = socket.recv().await {
+///             app.chat.post(text).await;
+///         }
+///     }))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Only WebSockets over HTTP/1.1 are supported.
+pub struct WebSocketUpgrade {
+    accept: String,
+    on_upgrade: OnUpgrade,
+    max_message_size: usize,
+}
+
+impl WebSocketUpgrade {
+    /// Get the upgrade from `req`, if it is a valid WebSocket handshake
+    pub fn from_request(req: &Parts) -> Option<Self> {
+        let has_token = |name: HeaderName, token: &str| {
+            req.headers
+                .get_all(name)
+                .iter()
+                .filter_map(|v| v.to_str().ok())
+                .flat_map(|v| v.split(','))
+                .any(|v| v.trim().eq_ignore_ascii_case(token))
+        };
+
+        if req.method != Method::GET
+            || !has_token(CONNECTION, "upgrade")
+            || !has_token(UPGRADE, "websocket")
+            || req.headers.get(SEC_WEBSOCKET_VERSION)? != "13"
+        {
+            return None;
+        }
+
+        let key = req.headers.get(SEC_WEBSOCKET_KEY)?.to_str().ok()?;
+        Some(Self {
+            accept: accept_key(key),
+            on_upgrade: req.extensions.get::<OnUpgrade>()?.clone(),
+            max_message_size: MAX_MESSAGE_SIZE,
+        })
+    }
+
+    /// Set the maximum size of received messages (defaults to 64 KiB)
+    pub fn max_message_size(mut self, size: usize) -> Self {
+        self.max_message_size = size;
+        self
+    }
+
+    /// Accept the upgrade, spawning a task that runs `f` on the WebSocket
+    ///
+    /// The task is not tracked by the server, so graceful shutdown does not wait for it.
+    pub fn on_upgrade<B, F, Fut>(self, f: F) -> Response<B>
+    where
+        B: Default,
+        F: FnOnce(WebSocket) -> Fut + Send + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let Self {
+            accept,
+            on_upgrade,
+            max_message_size,
+        } = self;
+
+        tokio::spawn(async move {
+            match on_upgrade.await {
+                Ok(upgraded) => {
+                    let mut socket = WebSocket::new(TokioIo::new(upgraded));
+                    socket.reader.max_message_size = max_message_size;
+                    f(socket).await
+                }
+                Err(error) => debug!(%error, "WebSocket upgrade failed"),
+            }
+        });
+
+        Response::builder()
+            .status(StatusCode::SWITCHING_PROTOCOLS)
+            .header(CONNECTION, "upgrade")
+            .header(UPGRADE, "websocket")
+            .header(SEC_WEBSOCKET_ACCEPT, accept)
+            .body(B::default())
+            .unwrap()
+    }
+}
+
+impl<'a, A: Application> FromContext<'a, A> for WebSocketUpgrade {
+    fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        _: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        match Self::from_request(req) {
+            Some(upgrade) => Ok(upgrade),
+            None => Err(state.reject::<A>(ErrorKind::WebSocketUpgrade.into(), req)),
+        }
+    }
+}
+
+/// The server side of a WebSocket connection
+///
+/// Pings are answered and fragmented messages reassembled while receiving. Neither `recv()`
+/// nor `send()` is cancellation safe.
+pub struct WebSocket<S = TokioIo<Upgraded>> {
+    stream: S,
+    reader: Reader,
+    closed: bool,
+}
+
+impl<S: AsyncRead + AsyncWrite + Unpin> WebSocket<S> {
+    /// Wrap a `stream` on which the server has completed the handshake
+    pub fn new(stream: S) -> Self {
+        Self {
+            stream,
+            reader: Reader::new(MAX_MESSAGE_SIZE),
+            closed: false,
+        }
+    }
+
+    /// Receive the next message, or `None` once the connection has been closed
+    ///
+    /// Protocol violations close the connection with the matching status code.
+    pub async fn recv(&mut self) -> Result<Option<Message>, Error> {
+        match self.read_message().await {
+            Ok(Some(message)) => Ok(Some(message)),
+            Ok(None) => {
+                self.closed = true;
+                Ok(None)
+            }
+            Err(error) => {
+                if let Some(code) = error.close_code() {
+                    let _ = self.close_with(code).await;
+                }
+                self.closed = true;
+                Err(error)
+            }
+        }
+    }
+
+    /// Send a message
+    pub async fn send(&mut self, message: Message) -> Result<(), Error> {
+        write_message(&mut self.stream, &message).await
+    }
+
+    /// Close the connection normally
+    pub async fn close(mut self) -> Result<(), Error> {
+        self.close_with(1000).await
+    }
+
+    async fn close_with(&mut self, code: u16) -> Result<(), Error> {
+        if self.closed {
+            return Ok(());
+        }
+
+        self.closed = true;
+        write_close(&mut self.stream, code).await
+    }
+
+    async fn read_message(&mut self) -> Result<Option<Message>, Error> {
+        loop {
+            match self.reader.read(&mut self.stream).await? {
+                Some(Incoming::Message(message)) => return Ok(Some(message)),
+                Some(Incoming::Ping(payload)) => {
+                    write_frame(&mut self.stream, OP_PONG, &payload).await?
+                }
+                Some(Incoming::Close(code)) => {
+                    self.close_with(code).await?;
+                    return Ok(None);
+                }
+                None => return Ok(None),
+            }
+        }
+    }
+}
+
+/// Reassembles messages from the frames read from a stream
+struct Reader {
+    partial: Option<(u8, Vec<u8>)>,
+    max_message_size: usize,
+}
+
+impl Reader {
+    fn new(max_message_size: usize) -> Self {
+        Self {
+            partial: None,
+            max_message_size,
+        }
+    }
+
+    /// Read the next message or control frame that needs an answer
+    ///
+    /// Returns `None` if the stream ended without a close frame.
+    async fn read<R: AsyncRead + Unpin>(
+        &mut self,
+        stream: &mut R,
+    ) -> Result<Option<Incoming>, Error> {
+        loop {
+            let frame = match self.read_frame(stream).await? {
+                Some(frame) => frame,
+                None => return Ok(None),
+            };
+
+            let (opcode, payload) = match (frame.opcode, &mut self.partial) {
+                (OP_PING, _) => return Ok(Some(Incoming::Ping(frame.payload))),
+                (OP_PONG, _) => continue,
+                (OP_CLOSE, _) => {
+                    let code = match frame.payload.get(..2) {
+                        Some(code) => u16::from_be_bytes([code[0], code[1]]),
+                        None => 1000,
+                    };
+                    return Ok(Some(Incoming::Close(code)));
+                }
+                (OP_CONTINUATION, Some((_, data))) => {
+                    if data.len() + frame.payload.len() > self.max_message_size {
+                        return Err(Error::TooLarge(self.max_message_size));
+                    }
+                    data.extend_from_slice(&frame.payload);
+                    match frame.fin {
+                        true => self.partial.take().unwrap(),
+                        false => continue,
+                    }
+                }
+                (OP_TEXT | OP_BINARY, None) => match frame.fin {
+                    true => (frame.opcode, frame.payload),
+                    false => {
+                        self.partial = Some((frame.opcode, frame.payload));
+                        continue;
+                    }
+                },
+                (OP_CONTINUATION, None) => {
+                    return Err(Error::Protocol("continuation without a message"))
+                }
+                (OP_TEXT | OP_BINARY, Some(_)) => {
+                    return Err(Error::Protocol("new message within fragmented message"))
+                }
+                _ => return Err(Error::Protocol("unknown opcode")),
+            };
+
+            return match opcode {
+                OP_TEXT => match String::from_utf8(payload) {
+                    Ok(text) => Ok(Some(Incoming::Message(Message::Text(text)))),
+                    Err(_) => Err(Error::InvalidText),
+                },
+                _ => Ok(Some(Incoming::Message(Message::Binary(payload)))),
+            };
+        }
+    }
+
+    async fn read_frame<R: AsyncRead + Unpin>(
+        &self,
+        stream: &mut R,
+    ) -> Result<Option<Frame>, Error> {
+        let mut head = [0; 2];
+        match stream.read_exact(&mut head).await {
+            Ok(_) => {}
+            Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        if head[0] & 0x70 != 0 {
+            return Err(Error::Protocol("reserved bits set"));
+        } else if head[1] & 0x80 == 0 {
+            return Err(Error::Protocol("client frame not masked"));
+        }
+
+        let (fin, opcode) = (head[0] & 0x80 != 0, head[0] & 0x0f);
+        let len = match head[1] & 0x7f {
+            126 => stream.read_u16().await? as u64,
+            127 => stream.read_u64().await?,
+            len => len as u64,
+        };
+
+        if opcode & 0x08 != 0 && (!fin || len > 125) {
+            return Err(Error::Protocol("invalid control frame"));
+        } else if len > self.max_message_size as u64 {
+            return Err(Error::TooLarge(self.max_message_size));
+        }
+
+        let mut mask = [0; 4];
+        stream.read_exact(&mut mask).await?;
+        let mut payload = vec![0; len as usize];
+        stream.read_exact(&mut payload).await?;
+        for (i, byte) in payload.iter_mut().enumerate() {
+            *byte ^= mask[i % 4];
+        }
+
+        Ok(Some(Frame {
+            fin,
+            opcode,
+            payload,
+        }))
+    }
+}
+
+/// What `Reader::read()` found on the stream
+enum Incoming {
+    Message(Message),
+    Ping(Vec<u8>),
+    Close(u16),
+}
+
+async fn write_message<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    message: &Message,
+) -> Result<(), Error> {
+    match message {
+        Message::Text(text) => write_frame(stream, OP_TEXT, text.as_bytes()).await,
+        Message::Binary(data) => write_frame(stream, OP_BINARY, data).await,
+    }
+}
+
+/// Send a close frame with `code`, then shut down the stream
+async fn write_close<W: AsyncWrite + Unpin>(stream: &mut W, code: u16) -> Result<(), Error> {
+    write_frame(stream, OP_CLOSE, &code.to_be_bytes()).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+async fn write_frame<W: AsyncWrite + Unpin>(
+    stream: &mut W,
+    opcode: u8,
+    payload: &[u8],
+) -> Result<(), Error> {
+    let mut frame = Vec::with_capacity(payload.len() + 10);
+    frame.push(0x80 | opcode);
+    match payload.len() {
+        len if len < 126 => frame.push(len as u8),
+        len if len <= u16::MAX as usize => {
+            frame.push(126);
+            frame.extend_from_slice(&(len as u16).to_be_bytes());
+        }
+        len => {
+            frame.push(127);
+            frame.extend_from_slice(&(len as u64).to_be_bytes());
+        }
+    }
+
+    frame.extend_from_slice(payload);
+    stream.write_all(&frame).await?;
+    stream.flush().await?;
+    Ok(())
+}
+
+struct Frame {
+    fin: bool,
+    opcode: u8,
+    payload: Vec<u8>,
+}
+
+/// A WebSocket data message
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Message {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl From<String> for Message {
+    fn from(text: String) -> Self {
+        Self::Text(text)
+    }
+}
+
+impl From<&str> for Message {
+    fn from(text: &str) -> Self {
+        Self::Text(text.to_owned())
+    }
+}
+
+impl From<Vec<u8>> for Message {
+    fn from(data: Vec<u8>) -> Self {
+        Self::Binary(data)
+    }
+}
+
+/// The `Sec-WebSocket-Accept` value for the client's `key`
+fn accept_key(key: &str) -> String {
+    let mut ctx = ring::digest::Context::new(&ring::digest::SHA1_FOR_LEGACY_USE_ONLY);
+    ctx.update(key.as_bytes());
+    ctx.update(b"258EAFA5-E914-47DA-95CA-C5AB0DC85B11");
+    BASE64.encode(ctx.finish().as_ref())
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("WebSocket I/O error: {0}")]
+    Io(#[from] io::Error),
+    #[error("WebSocket protocol error: {0}")]
+    Protocol(&'static str),
+    #[error("WebSocket text message is not valid UTF-8")]
+    InvalidText,
+    #[error("WebSocket message larger than {0} bytes")]
+    TooLarge(usize),
+}
+
+impl Error {
+    /// The status code to close the connection with after this error
+    fn close_code(&self) -> Option<u16> {
+        match self {
+            Self::Io(_) => None,
+            Self::Protocol(_) => Some(1002),
+            Self::InvalidText => Some(1007),
+            Self::TooLarge(_) => Some(1009),
+        }
+    }
+}
+
+const MAX_MESSAGE_SIZE: usize = 64 * 1024;
+
+const OP_CONTINUATION: u8 = 0x0;
+const OP_TEXT: u8 = 0x1;
+const OP_BINARY: u8 = 0x2;
+const OP_CLOSE: u8 = 0x8;
+const OP_PING: u8 = 0x9;
+const OP_PONG: u8 = 0xa;
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::duplex;
+
+    use super::*;
+
+    #[test]
+    fn accept() {
+        // From RFC 6455, section 1.3
+        assert_eq!(
+            accept_key("dGhlIHNhbXBsZSBub25jZQ=="),
+            "s3pPLMBiTxaQ9kYGzzhZRbK+xOo="
+        );
+    }
+
+    #[tokio::test]
+    async fn frames() {
+        let (client, server) = duplex(1024);
+        let mut socket = WebSocket::new(server);
+
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let masked = |first: u8, payload: &[u8]| {
+            let mut frame = vec![first, 0x80 | payload.len() as u8];
+            frame.extend_from_slice(&mask);
+            frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+            frame
+        };
+
+        let mut client = client;
+        let mut frames = masked(0x01, b"Hel");
+        frames.extend(masked(0x89, b"ping"));
+        frames.extend(masked(0x80, b"lo"));
+        frames.extend(masked(0x88, &1000u16.to_be_bytes()));
+        client.write_all(&frames).await.unwrap();
+
+        assert_eq!(socket.recv().await.unwrap(), Some("Hello".into()));
+        socket.send("Hi".into()).await.unwrap();
+        assert_eq!(socket.recv().await.unwrap(), None);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(
+            received,
+            [
+                &[0x8a, 4][..],
+                b"ping",
+                &[0x81, 2],
+                b"Hi",
+                &[0x88, 2, 0x03, 0xe8]
+            ]
+            .concat()
+        );
+    }
+}
@@ -0,0 +1,445 @@
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::hash::Hash;
+use std::sync::{Arc, Mutex};
+
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio::sync::mpsc;
+
+use super::{
+    write_close, write_frame, write_message, Error, Incoming, Message, WebSocket, OP_PONG,
+};
+
+/// Groups WebSocket connections into rooms, so messages can be broadcast to everyone in a room
+///
+/// Every connection gets its own queue of `capacity` messages, which a separate writer drains
+/// into the socket. Members that fall behind by more than that (like clients on a slow
+/// connection) are disconnected with status 1008, rather than slowing down the others.
+///
+/// ```no_run
+/// # use mendes::http::Response;
+/// # use mendes::websocket::{Hub, WebSocketUpgrade};
+/// # use mendes::{handler, Body, Error};
+/// # struct App {
+/// #     chat: Hub<String>,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn chat(app: &App, ws: WebSocketUpgrade, room: String) -> Result<Response<Body>, Error> {
+///     let hub = app.chat.clone();
+///     Ok(ws.on_upgrade(move |socket| async move {
+///         let conn = hub.connect();
+///         conn.join(room.clone());
+///         let _ = conn
+///             .serve(socket, |msg| {
+///                 hub.broadcast(&room, msg);
+///                 async {}
+///             })
+///             .await;
+///     }))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Cloning a `Hub` is cheap, and all clones share the same members and rooms.
+pub struct Hub<R> {
+    inner: Arc<Inner<R>>,
+}
+
+impl<R: Clone + Eq + Hash> Hub<R> {
+    /// Create a hub queueing up to `capacity` messages per connection
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                state: Mutex::new(State {
+                    next_id: 0,
+                    members: HashMap::new(),
+                    rooms: HashMap::new(),
+                }),
+                capacity,
+            }),
+        }
+    }
+
+    /// Register a new connection, which is a member of no rooms yet
+    pub fn connect(&self) -> Connection<R> {
+        let (sender, queue) = mpsc::channel(self.inner.capacity);
+        let mut state = self.inner.state.lock().unwrap();
+        let id = state.next_id;
+        state.next_id += 1;
+        state.members.insert(
+            id,
+            MemberState {
+                sender,
+                rooms: HashSet::new(),
+            },
+        );
+
+        Connection {
+            member: Member {
+                hub: self.clone(),
+                id,
+            },
+            queue,
+        }
+    }
+
+    /// Queue `message` for every member of `room`, returning the number of members
+    ///
+    /// Members whose queue is full are disconnected and not counted.
+    pub fn broadcast(&self, room: &R, message: impl Into<Message>) -> usize {
+        let message = message.into();
+        let mut state = self.inner.state.lock().unwrap();
+        let ids = match state.rooms.get(room) {
+            Some(ids) => ids.iter().copied().collect::<Vec<_>>(),
+            None => return 0,
+        };
+
+        let mut sent = 0;
+        for id in ids {
+            match state.send(id, message.clone()) {
+                true => sent += 1,
+                false => state.remove(id),
+            }
+        }
+        sent
+    }
+
+    /// The number of members in `room`
+    pub fn members(&self, room: &R) -> usize {
+        let state = self.inner.state.lock().unwrap();
+        state.rooms.get(room).map_or(0, |ids| ids.len())
+    }
+
+    /// The number of connections to the hub
+    pub fn connections(&self) -> usize {
+        self.inner.state.lock().unwrap().members.len()
+    }
+}
+
+impl<R> Clone for Hub<R> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+/// A connection to a `Hub`, before it is served
+///
+/// Dereferences to its `Member`, so rooms can be joined before calling `serve()`.
+pub struct Connection<R: Clone + Eq + Hash> {
+    member: Member<R>,
+    queue: mpsc::Receiver<Message>,
+}
+
+impl<R: Clone + Eq + Hash> Connection<R> {
+    /// A handle to this connection's membership, for use from `serve()`'s callback
+    pub fn member(&self) -> Member<R> {
+        self.member.clone()
+    }
+
+    /// Run `socket` until the connection closes, calling `on_message` for each message
+    ///
+    /// Queued messages are written while `on_message` runs, so a slow callback only holds up
+    /// this connection's incoming messages. The connection leaves the hub when this returns.
+    pub async fn serve<S, F, Fut>(
+        mut self,
+        socket: WebSocket<S>,
+        mut on_message: F,
+    ) -> Result<(), Error>
+    where
+        S: AsyncRead + AsyncWrite + Unpin,
+        F: FnMut(Message) -> Fut,
+        Fut: Future<Output = ()>,
+    {
+        let WebSocket {
+            stream,
+            mut reader,
+            closed,
+        } = socket;
+        if closed {
+            return Ok(());
+        }
+
+        let (mut input, mut output) = tokio::io::split(stream);
+        // Answers to the client's control frames, sent ahead of queued messages
+        let (control, mut controls) = mpsc::channel(1);
+
+        let read = async move {
+            loop {
+                let reply = match reader.read(&mut input).await {
+                    Ok(Some(Incoming::Message(message))) => {
+                        on_message(message).await;
+                        continue;
+                    }
+                    Ok(Some(Incoming::Ping(payload))) => {
+                        let _ = control.send(Control::Pong(payload)).await;
+                        continue;
+                    }
+                    Ok(Some(Incoming::Close(code))) => Ok(Some(code)),
+                    Ok(None) => Ok(None),
+                    Err(error) => Err(error),
+                };
+
+                let code = match &reply {
+                    Ok(code) => *code,
+                    Err(error) => error.close_code(),
+                };
+                if let Some(code) = code {
+                    let _ = control.send(Control::Close(code)).await;
+                }
+                return reply.map(|_| ());
+            }
+        };
+
+        let queue = &mut self.queue;
+        let write = async move {
+            loop {
+                tokio::select! {
+                    biased;
+                    control = controls.recv() => match control {
+                        Some(Control::Pong(payload)) => {
+                            write_frame(&mut output, OP_PONG, &payload).await?
+                        }
+                        Some(Control::Close(code)) => return write_close(&mut output, code).await,
+                        None => return Ok(()),
+                    },
+                    message = queue.recv() => match message {
+                        Some(message) => write_message(&mut output, &message).await?,
+                        // Removed from the hub for falling behind
+                        None => return write_close(&mut output, 1008).await,
+                    },
+                }
+            }
+        };
+
+        tokio::pin!(read, write);
+        tokio::select! {
+            biased;
+            result = &mut read => {
+                let written = write.await;
+                result.and(written)
+            }
+            // The reader can't finish once the connection is closed for writing
+            result = &mut write => result,
+        }
+    }
+}
+
+impl<R: Clone + Eq + Hash> std::ops::Deref for Connection<R> {
+    type Target = Member<R>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.member
+    }
+}
+
+impl<R: Clone + Eq + Hash> Drop for Connection<R> {
+    fn drop(&mut self) {
+        self.member
+            .hub
+            .inner
+            .state
+            .lock()
+            .unwrap()
+            .remove(self.member.id);
+    }
+}
+
+/// A connection's membership in a `Hub`
+///
+/// Cheap to clone. Once the connection has closed, joining rooms and sending does nothing.
+pub struct Member<R> {
+    hub: Hub<R>,
+    id: u64,
+}
+
+impl<R: Clone + Eq + Hash> Member<R> {
+    /// An identifier for this connection, unique within its hub
+    pub fn id(&self) -> u64 {
+        self.id
+    }
+
+    /// Join `room`, receiving messages broadcast to it
+    pub fn join(&self, room: R) {
+        let mut state = self.hub.inner.state.lock().unwrap();
+        let State { members, rooms, .. } = &mut *state;
+        if let Some(member) = members.get_mut(&self.id) {
+            rooms.entry(room.clone()).or_default().insert(self.id);
+            member.rooms.insert(room);
+        }
+    }
+
+    /// Leave `room`
+    pub fn leave(&self, room: &R) {
+        let mut state = self.hub.inner.state.lock().unwrap();
+        if let Some(member) = state.members.get_mut(&self.id) {
+            member.rooms.remove(room);
+            state.leave(self.id, room);
+        }
+    }
+
+    /// Queue `message` for this connection only
+    ///
+    /// Returns false if the connection has closed, or if its queue was full, in which case it
+    /// is disconnected.
+    pub fn send(&self, message: impl Into<Message>) -> bool {
+        let mut state = self.hub.inner.state.lock().unwrap();
+        match state.send(self.id, message.into()) {
+            true => true,
+            false => {
+                state.remove(self.id);
+                false
+            }
+        }
+    }
+}
+
+impl<R: Clone + Eq + Hash> Clone for Member<R> {
+    fn clone(&self) -> Self {
+        Self {
+            hub: self.hub.clone(),
+            id: self.id,
+        }
+    }
+}
+
+struct Inner<R> {
+    state: Mutex<State<R>>,
+    capacity: usize,
+}
+
+struct State<R> {
+    next_id: u64,
+    members: HashMap<u64, MemberState<R>>,
+    rooms: HashMap<R, HashSet<u64>>,
+}
+
+impl<R: Clone + Eq + Hash> State<R> {
+    /// Queue `message` for member `id`, returning false if it is gone or its queue is full
+    fn send(&self, id: u64, message: Message) -> bool {
+        match self.members.get(&id) {
+            Some(member) => member.sender.try_send(message).is_ok(),
+            None => false,
+        }
+    }
+
+    /// Remove member `id` from the hub, which ends its queue
+    fn remove(&mut self, id: u64) {
+        if let Some(member) = self.members.remove(&id) {
+            for room in &member.rooms {
+                self.leave(id, room);
+            }
+        }
+    }
+
+    fn leave(&mut self, id: u64, room: &R) {
+        if let Some(ids) = self.rooms.get_mut(room) {
+            ids.remove(&id);
+            if ids.is_empty() {
+                self.rooms.remove(room);
+            }
+        }
+    }
+}
+
+struct MemberState<R> {
+    sender: mpsc::Sender<Message>,
+    rooms: HashSet<R>,
+}
+
+enum Control {
+    Pong(Vec<u8>),
+    Close(u16),
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{duplex, AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    fn masked(first: u8, payload: &[u8]) -> Vec<u8> {
+        let mask = [0x37, 0xfa, 0x21, 0x3d];
+        let mut frame = vec![first, 0x80 | payload.len() as u8];
+        frame.extend_from_slice(&mask);
+        frame.extend(payload.iter().enumerate().map(|(i, b)| b ^ mask[i % 4]));
+        frame
+    }
+
+    #[test]
+    fn rooms() {
+        let hub = Hub::new(2);
+        let (a, b) = (hub.connect(), hub.connect());
+        a.join("lobby");
+        b.join("lobby");
+        b.join("games");
+        assert_eq!(hub.members(&"lobby"), 2);
+
+        assert_eq!(hub.broadcast(&"lobby", "hi"), 2);
+        assert_eq!(hub.broadcast(&"games", "gg"), 1);
+        assert_eq!(hub.broadcast(&"empty", "?"), 0);
+
+        // `b` has a full queue now, so it is disconnected
+        assert_eq!(hub.broadcast(&"games", "again"), 0);
+        assert_eq!(hub.members(&"lobby"), 1);
+        assert_eq!(hub.members(&"games"), 0);
+        assert!(!b.send("gone"));
+        b.join("lobby");
+        assert_eq!(hub.members(&"lobby"), 1);
+
+        a.leave(&"lobby");
+        assert_eq!(hub.members(&"lobby"), 0);
+        assert_eq!(hub.connections(), 1);
+        drop((a, b));
+        assert_eq!(hub.connections(), 0);
+    }
+
+    #[tokio::test]
+    async fn serve() {
+        let (mut client, server) = duplex(1024);
+        let hub = Hub::new(4);
+        let conn = hub.connect();
+        conn.join("lobby");
+        hub.broadcast(&"lobby", "welcome");
+
+        let mut frames = masked(0x81, b"Hi");
+        frames.extend(masked(0x89, b"ping"));
+        frames.extend(masked(0x88, &1000u16.to_be_bytes()));
+        client.write_all(&frames).await.unwrap();
+
+        let echo = hub.clone();
+        conn.serve(WebSocket::new(server), |msg| {
+            echo.broadcast(&"lobby", msg);
+            async {}
+        })
+        .await
+        .unwrap();
+        assert_eq!(hub.connections(), 0);
+
+        let mut received = Vec::new();
+        client.read_to_end(&mut received).await.unwrap();
+        assert_eq!(
+            received,
+            [
+                &[0x8a, 4][..],
+                b"ping",
+                &[0x81, 7],
+                b"welcome",
+                &[0x81, 2],
+                b"Hi",
+                &[0x88, 2, 0x03, 0xe8]
+            ]
+            .concat()
+        );
+    }
+}
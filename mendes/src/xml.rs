@@ -0,0 +1,1477 @@
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::error::Error as StdError;
+use std::fmt::{Display, Write};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http::header::CONTENT_TYPE;
+use http::request::Parts;
+use http::{Response, StatusCode};
+use http_body::Body as HttpBody;
+use serde::de::DeserializeOwned;
+use serde::ser::{self, Impossible, Serialize};
+use thiserror::Error;
+
+use crate::application::{self, check_content_type, Application, ErrorKind, FromContextAsync};
+use crate::application::{read_body, IntoResponse, PathState};
+use crate::utils::negotiate;
+use crate::value::{Deserializer, Value, TEXT};
+
+/// An XML request body or response
+///
+/// As an extractor, `Xml<T>` accepts bodies of up to `MAX_LEN` bytes with an
+/// `application/xml` or `text/xml` content type. As a response, it is sent with whichever of
+/// those the request's `Accept` header prefers, or rejected with a `NotAcceptable` error if
+/// it accepts neither:
+///
+/// ```no_run
+/// # use mendes::xml::Xml;
+/// # use mendes::{handler, Error};
+/// # use serde::{Deserialize, Serialize};
+/// # struct App {}
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[derive(Deserialize, Serialize)]
+/// struct Order {
+///     #[serde(rename = "@id")]
+///     id: u64,
+///     #[serde(rename = "item")]
+///     items: Vec<String>,
+/// }
+///
+/// #[handler(POST)]
+/// async fn submit(_: &App, #[async_extract] order: Xml<Order>) -> Result<Xml<Order>, Error> {
+///     let Xml(mut order) = order;
+///     order.items.push("receipt".to_owned());
+///     Ok(Xml(order))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// The document above reads and writes `<Order id="1"><item>tea</item></Order>`: fields are
+/// child elements named after the field, fields whose names start with `@` are attributes and
+/// a `$text` field holds an element's text. Lists are repeated elements with the same name.
+/// The root element is named after the type.
+pub struct Xml<T>(pub T);
+
+#[async_trait]
+impl<'a, A, T> FromContextAsync<'a, A> for Xml<T>
+where
+    A: Application + Sync,
+    A::RequestBody: HttpBody + Send,
+    <A::RequestBody as HttpBody>::Data: Send,
+    <A::RequestBody as HttpBody>::Error: Into<Box<dyn StdError + Sync + Send + 'static>>,
+    T: DeserializeOwned,
+{
+    async fn from_context(
+        _: &'a Arc<A>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<A::RequestBody>,
+    ) -> Result<Self, A::Error> {
+        let body = match body.take() {
+            Some(body) => body,
+            None => panic!("attempted to retrieve body twice"),
+        };
+
+        check_content_type(req, MEDIA_TYPES).map_err(|e| state.reject::<A>(e, req))?;
+        let bytes = read_body(body, MAX_LEN)
+            .await
+            .map_err(|e| state.reject::<A>(e, req))?;
+        match from_slice(&bytes) {
+            Ok(value) => Ok(Self(value)),
+            Err(e) => Err(state.reject::<A>(e.into(), req)),
+        }
+    }
+}
+
+impl<A: Application, T: Serialize> IntoResponse<A> for Xml<T>
+where
+    A::ResponseBody: From<String>,
+{
+    fn into_response(self, app: &A, req: &Parts) -> Response<A::ResponseBody> {
+        let media_type = match negotiate(req, MEDIA_TYPES) {
+            Some(media_type) => media_type,
+            None => {
+                return application::Error::from(ErrorKind::NotAcceptable).into_response(app, req)
+            }
+        };
+
+        match to_string(&self.0) {
+            Ok(body) => Response::builder()
+                .status(StatusCode::OK)
+                .header(CONTENT_TYPE, format!("{media_type}; charset=utf-8"))
+                .body(body.into())
+                .unwrap(),
+            Err(e) => application::Error::internal(e).into_response(app, req),
+        }
+    }
+}
+
+/// Deserialize an instance of `T` from an XML document
+///
+/// Only UTF-8 documents are supported. Namespace prefixes are removed from element and
+/// attribute names, and document type declarations are rejected.
+pub fn from_slice<T: DeserializeOwned>(input: &[u8]) -> Result<T, Error> {
+    let input =
+        std::str::from_utf8(input).map_err(|e| Error::Syntax(e.valid_up_to(), "invalid UTF-8"))?;
+    from_str(input)
+}
+
+/// Deserialize an instance of `T` from an XML document
+pub fn from_str<T: DeserializeOwned>(input: &str) -> Result<T, Error> {
+    let value = Parser { input, pos: 0 }.document()?;
+    T::deserialize(Deserializer::new(value))
+}
+
+/// Serialize `value` as an XML document
+///
+/// `value` must serialize as a struct, which becomes the root element.
+pub fn to_string<T: Serialize + ?Sized>(value: &T) -> Result<String, Error> {
+    let mut out = String::from(r#"<?xml version="1.0" encoding="UTF-8"?>"#);
+    value.serialize(ElementSerializer {
+        out: &mut out,
+        name: None,
+    })?;
+    Ok(out)
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn document(&mut self) -> Result<Value, Error> {
+        self.misc()?;
+        if !self.rest().starts_with('<') {
+            return Err(self.error("expected root element"));
+        }
+
+        let (_, value) = self.element(0)?;
+        self.misc()?;
+        match self.pos == self.input.len() {
+            true => Ok(value),
+            false => Err(self.error("content after root element")),
+        }
+    }
+
+    /// Skip whitespace, comments and processing instructions outside the root element
+    fn misc(&mut self) -> Result<(), Error> {
+        loop {
+            self.pos += self.rest().len() - self.rest().trim_start().len();
+            if self.rest().starts_with("<?") {
+                self.skip_past("?>")?;
+            } else if self.rest().starts_with("<!--") {
+                self.skip_past("-->")?;
+            } else if self.rest().starts_with("<!") {
+                return Err(self.error("document type declarations are not supported"));
+            } else {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Parse the element at the current position, returning its local name and value
+    fn element(&mut self, depth: usize) -> Result<(&'a str, Value), Error> {
+        if depth > MAX_DEPTH {
+            return Err(self.error("elements nested too deeply"));
+        }
+
+        self.pos += 1;
+        let qname = self.name()?;
+        let mut entries = Vec::new();
+        let mut text = String::new();
+        let mut index = HashMap::new();
+
+        let empty = loop {
+            self.skip_whitespace();
+            if self.eat("/>") {
+                break true;
+            } else if self.eat(">") {
+                break false;
+            }
+
+            let name = self.name()?;
+            self.skip_whitespace();
+            if !self.eat("=") {
+                return Err(self.error("expected '=' after attribute name"));
+            }
+            self.skip_whitespace();
+
+            let quote = match self.rest().chars().next() {
+                Some(quote @ ('"' | '\'')) => quote,
+                _ => return Err(self.error("expected quoted attribute value")),
+            };
+            self.pos += 1;
+            let len = match self.rest().find(quote) {
+                Some(len) => len,
+                None => return Err(self.error("unterminated attribute value")),
+            };
+            let raw = &self.rest()[..len];
+            if raw.contains('<') {
+                return Err(self.error("'<' in attribute value"));
+            }
+            let value = self.unescape(raw)?;
+            self.pos += len + 1;
+
+            if name != "xmlns" && !name.starts_with("xmlns:") {
+                let key = format!("@{}", local(name));
+                push(
+                    &mut entries,
+                    &mut index,
+                    key,
+                    Value::Text(value.into_owned()),
+                );
+            }
+        };
+
+        if !empty {
+            loop {
+                let len = match self.rest().find('<') {
+                    Some(len) => len,
+                    None => return Err(self.error("unclosed element")),
+                };
+                text.push_str(&self.unescape(&self.rest()[..len])?);
+                self.pos += len;
+
+                if self.eat("</") {
+                    if self.name()? != qname {
+                        return Err(self.error("mismatched closing tag"));
+                    }
+                    self.skip_whitespace();
+                    if !self.eat(">") {
+                        return Err(self.error("expected '>'"));
+                    }
+                    break;
+                } else if self.rest().starts_with("<!--") {
+                    self.skip_past("-->")?;
+                } else if self.eat("<![CDATA[") {
+                    let len = match self.rest().find("]]>") {
+                        Some(len) => len,
+                        None => return Err(self.error("unterminated CDATA section")),
+                    };
+                    text.push_str(&self.rest()[..len]);
+                    self.pos += len + 3;
+                } else if self.rest().starts_with("<?") {
+                    self.skip_past("?>")?;
+                } else if self.rest().starts_with("<!") {
+                    return Err(self.error("document type declarations are not supported"));
+                } else {
+                    let (name, value) = self.element(depth + 1)?;
+                    push(&mut entries, &mut index, name.to_owned(), value);
+                }
+            }
+        }
+
+        if entries.is_empty() {
+            return Ok((local(qname), Value::Text(text)));
+        }
+
+        if !text.trim().is_empty() {
+            push(&mut entries, &mut index, TEXT.to_owned(), Value::Text(text));
+        }
+        Ok((local(qname), Value::Element(entries)))
+    }
+
+    fn name(&mut self) -> Result<&'a str, Error> {
+        let rest = self.rest();
+        let len = rest
+            .find(|c: char| c.is_whitespace() || matches!(c, '/' | '>' | '=' | '<'))
+            .unwrap_or(rest.len());
+        match len {
+            0 => Err(self.error("expected a name")),
+            _ => {
+                self.pos += len;
+                Ok(&rest[..len])
+            }
+        }
+    }
+
+    /// Resolve the character and entity references in `raw`
+    fn unescape(&self, raw: &'a str) -> Result<Cow<'a, str>, Error> {
+        if !raw.contains('&') {
+            return Ok(Cow::Borrowed(raw));
+        }
+
+        let mut out = String::with_capacity(raw.len());
+        let mut rest = raw;
+        while let Some(start) = rest.find('&') {
+            out.push_str(&rest[..start]);
+            let end = match rest[start..].find(';') {
+                Some(end) => start + end,
+                None => return Err(self.error("unterminated reference")),
+            };
+
+            let c = match &rest[start + 1..end] {
+                "lt" => Some('<'),
+                "gt" => Some('>'),
+                "amp" => Some('&'),
+                "quot" => Some('"'),
+                "apos" => Some('\''),
+                reference => match reference.strip_prefix('#') {
+                    Some(hex) if hex.starts_with('x') => u32::from_str_radix(&hex[1..], 16).ok(),
+                    Some(decimal) => decimal.parse().ok(),
+                    None => None,
+                }
+                .and_then(char::from_u32),
+            };
+
+            match c {
+                Some(c) => out.push(c),
+                None => return Err(self.error("unknown reference")),
+            }
+            rest = &rest[end + 1..];
+        }
+
+        out.push_str(rest);
+        Ok(Cow::Owned(out))
+    }
+
+    fn skip_past(&mut self, end: &str) -> Result<(), Error> {
+        match self.rest().find(end) {
+            Some(len) => {
+                self.pos += len + end.len();
+                Ok(())
+            }
+            None => Err(self.error("unexpected end of document")),
+        }
+    }
+
+    fn skip_whitespace(&mut self) {
+        self.pos += self.rest().len() - self.rest().trim_start().len();
+    }
+
+    fn eat(&mut self, s: &str) -> bool {
+        match self.rest().starts_with(s) {
+            true => {
+                self.pos += s.len();
+                true
+            }
+            false => false,
+        }
+    }
+
+    fn rest(&self) -> &'a str {
+        &self.input[self.pos..]
+    }
+
+    fn error(&self, msg: &'static str) -> Error {
+        Error::Syntax(self.pos, msg)
+    }
+}
+
+/// Add an entry, turning repeated names into a sequence at the position of the first one
+fn push(
+    entries: &mut Vec<(String, Value)>,
+    index: &mut HashMap<String, usize>,
+    name: String,
+    value: Value,
+) {
+    let i = match index.get(&name) {
+        Some(&i) => i,
+        None => {
+            index.insert(name.clone(), entries.len());
+            entries.push((name, value));
+            return;
+        }
+    };
+
+    match &mut entries[i].1 {
+        Value::Seq(items) => items.push(value),
+        existing => {
+            let first = std::mem::replace(existing, Value::Seq(Vec::new()));
+            *existing = Value::Seq(vec![first, value]);
+        }
+    }
+}
+
+/// The name without its namespace prefix
+fn local(name: &str) -> &str {
+    match name.split_once(':') {
+        Some((_, local)) => local,
+        None => name,
+    }
+}
+
+/// Serializes a value as an element named `name`, or after its type at the root
+struct ElementSerializer<'a> {
+    out: &'a mut String,
+    name: Option<Cow<'static, str>>,
+}
+
+impl<'a> ElementSerializer<'a> {
+    fn name(&self) -> Result<&str, Error> {
+        match &self.name {
+            Some(name) => Ok(name),
+            None => Err(Error::Custom("root value must be a struct".to_owned())),
+        }
+    }
+
+    fn text(self, value: impl Display) -> Result<(), Error> {
+        let name = self.name()?.to_owned();
+        check_name(&name)?;
+        write!(self.out, "<{name}>").unwrap();
+        escape(self.out, &value.to_string(), false)?;
+        write!(self.out, "</{name}>").unwrap();
+        Ok(())
+    }
+
+    fn open(
+        self,
+        name: Cow<'static, str>,
+        outer: Option<Cow<'static, str>>,
+    ) -> Result<StructSerializer<'a>, Error> {
+        check_name(&name)?;
+        if let Some(outer) = &outer {
+            check_name(outer)?;
+            write!(self.out, "<{outer}>").unwrap();
+        }
+
+        write!(self.out, "<{name}").unwrap();
+        Ok(StructSerializer {
+            out: self.out,
+            name,
+            outer,
+            open: true,
+            key: None,
+        })
+    }
+}
+
+macro_rules! serialize_display {
+    ($($method:ident($ty:ty),)*) => {
+        $(fn $method(self, v: $ty) -> Result<(), Error> {
+            self.text(v)
+        })*
+    };
+}
+
+impl<'a> ser::Serializer for ElementSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+    type SerializeSeq = SeqSerializer<'a>;
+    type SerializeTuple = SeqSerializer<'a>;
+    type SerializeTupleStruct = SeqSerializer<'a>;
+    type SerializeTupleVariant = Impossible<(), Error>;
+    type SerializeMap = StructSerializer<'a>;
+    type SerializeStruct = StructSerializer<'a>;
+    type SerializeStructVariant = StructSerializer<'a>;
+
+    serialize_display! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<(), Error> {
+        Err(Error::Custom("byte strings are not supported".to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<(), Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<(), Error> {
+        let name = self.name()?.to_owned();
+        check_name(&name)?;
+        write!(self.out, "<{name}/>").unwrap();
+        Ok(())
+    }
+
+    fn serialize_unit_struct(mut self, name: &'static str) -> Result<(), Error> {
+        self.name.get_or_insert(Cow::Borrowed(name));
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<(), Error> {
+        self.text(variant)
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        mut self,
+        name: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.name.get_or_insert(Cow::Borrowed(name));
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        let name = self.name()?.to_owned();
+        check_name(&name)?;
+        write!(self.out, "<{name}>").unwrap();
+        value.serialize(ElementSerializer {
+            out: self.out,
+            name: Some(Cow::Borrowed(variant)),
+        })?;
+        write!(self.out, "</{name}>").unwrap();
+        Ok(())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<SeqSerializer<'a>, Error> {
+        let name = self.name()?.to_owned();
+        Ok(SeqSerializer {
+            out: self.out,
+            name: Cow::Owned(name),
+        })
+    }
+
+    fn serialize_tuple(self, len: usize) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        len: usize,
+    ) -> Result<SeqSerializer<'a>, Error> {
+        self.serialize_seq(Some(len))
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(Error::Custom("tuple variants are not supported".to_owned()))
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<StructSerializer<'a>, Error> {
+        let name = Cow::Owned(self.name()?.to_owned());
+        self.open(name, None)
+    }
+
+    fn serialize_struct(
+        mut self,
+        name: &'static str,
+        _: usize,
+    ) -> Result<StructSerializer<'a>, Error> {
+        let name = self.name.take().unwrap_or(Cow::Borrowed(name));
+        self.open(name, None)
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+        _: usize,
+    ) -> Result<StructSerializer<'a>, Error> {
+        let outer = Cow::Owned(self.name()?.to_owned());
+        self.open(Cow::Borrowed(variant), Some(outer))
+    }
+}
+
+/// Serializes the items of a sequence as elements with the same name
+struct SeqSerializer<'a> {
+    out: &'a mut String,
+    name: Cow<'static, str>,
+}
+
+impl<'a> SeqSerializer<'a> {
+    fn item<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        value.serialize(ElementSerializer {
+            out: self.out,
+            name: Some(self.name.clone()),
+        })
+    }
+}
+
+impl<'a> ser::SerializeSeq for SeqSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTuple for SeqSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_element<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeTupleStruct for SeqSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        self.item(value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Serializes the fields of a struct (or the entries of a map) into an element
+struct StructSerializer<'a> {
+    out: &'a mut String,
+    name: Cow<'static, str>,
+    /// An element to close after this one, for struct variants
+    outer: Option<Cow<'static, str>>,
+    /// Whether the start tag is still open for attributes
+    open: bool,
+    /// The key of the map entry being serialized
+    key: Option<String>,
+}
+
+impl<'a> StructSerializer<'a> {
+    fn field<T: Serialize + ?Sized>(
+        &mut self,
+        key: Cow<'static, str>,
+        value: &T,
+    ) -> Result<(), Error> {
+        if let Some(attr) = key.strip_prefix('@') {
+            if !self.open {
+                return Err(Error::Custom(format!(
+                    "attribute {attr} must come before elements"
+                )));
+            }
+
+            check_name(attr)?;
+            if let Some(text) = value.serialize(TextSerializer)? {
+                write!(self.out, " {attr}=\"").unwrap();
+                escape(self.out, &text, true)?;
+                self.out.push('"');
+            }
+            return Ok(());
+        }
+
+        if self.open {
+            self.out.push('>');
+            self.open = false;
+        }
+
+        match key == TEXT {
+            true => match value.serialize(TextSerializer)? {
+                Some(text) => escape(self.out, &text, false),
+                None => Ok(()),
+            },
+            false => value.serialize(ElementSerializer {
+                out: self.out,
+                name: Some(key),
+            }),
+        }
+    }
+
+    fn finish(self) -> Result<(), Error> {
+        match self.open {
+            true => self.out.push_str("/>"),
+            false => write!(self.out, "</{}>", self.name).unwrap(),
+        }
+
+        if let Some(outer) = self.outer {
+            write!(self.out, "</{outer}>").unwrap();
+        }
+        Ok(())
+    }
+}
+
+impl<'a> ser::SerializeStruct for StructSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(Cow::Borrowed(key), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeStructVariant for StructSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_field<T: Serialize + ?Sized>(
+        &mut self,
+        key: &'static str,
+        value: &T,
+    ) -> Result<(), Error> {
+        self.field(Cow::Borrowed(key), value)
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+impl<'a> ser::SerializeMap for StructSerializer<'a> {
+    type Ok = ();
+    type Error = Error;
+
+    fn serialize_key<T: Serialize + ?Sized>(&mut self, key: &T) -> Result<(), Error> {
+        match key.serialize(TextSerializer)? {
+            Some(key) => {
+                self.key = Some(key);
+                Ok(())
+            }
+            None => Err(Error::Custom("map keys must not be empty".to_owned())),
+        }
+    }
+
+    fn serialize_value<T: Serialize + ?Sized>(&mut self, value: &T) -> Result<(), Error> {
+        match self.key.take() {
+            Some(key) => self.field(Cow::Owned(key), value),
+            None => Err(Error::Custom("map value without a key".to_owned())),
+        }
+    }
+
+    fn end(self) -> Result<(), Error> {
+        self.finish()
+    }
+}
+
+/// Serializes a value as text, for attributes, text content and map keys
+///
+/// Returns `None` for `None`, which omits the attribute or text.
+struct TextSerializer;
+
+macro_rules! serialize_text {
+    ($($method:ident($ty:ty),)*) => {
+        $(fn $method(self, v: $ty) -> Result<Option<String>, Error> {
+            Ok(Some(v.to_string()))
+        })*
+    };
+}
+
+impl ser::Serializer for TextSerializer {
+    type Ok = Option<String>;
+    type Error = Error;
+    type SerializeSeq = Impossible<Option<String>, Error>;
+    type SerializeTuple = Impossible<Option<String>, Error>;
+    type SerializeTupleStruct = Impossible<Option<String>, Error>;
+    type SerializeTupleVariant = Impossible<Option<String>, Error>;
+    type SerializeMap = Impossible<Option<String>, Error>;
+    type SerializeStruct = Impossible<Option<String>, Error>;
+    type SerializeStructVariant = Impossible<Option<String>, Error>;
+
+    serialize_text! {
+        serialize_bool(bool),
+        serialize_i8(i8),
+        serialize_i16(i16),
+        serialize_i32(i32),
+        serialize_i64(i64),
+        serialize_i128(i128),
+        serialize_u8(u8),
+        serialize_u16(u16),
+        serialize_u32(u32),
+        serialize_u64(u64),
+        serialize_u128(u128),
+        serialize_f32(f32),
+        serialize_f64(f64),
+        serialize_char(char),
+        serialize_str(&str),
+    }
+
+    fn serialize_bytes(self, _: &[u8]) -> Result<Option<String>, Error> {
+        Err(Error::Custom("byte strings are not supported".to_owned()))
+    }
+
+    fn serialize_none(self) -> Result<Option<String>, Error> {
+        Ok(None)
+    }
+
+    fn serialize_some<T: Serialize + ?Sized>(self, value: &T) -> Result<Option<String>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_unit(self) -> Result<Option<String>, Error> {
+        Ok(Some(String::new()))
+    }
+
+    fn serialize_unit_struct(self, _: &'static str) -> Result<Option<String>, Error> {
+        self.serialize_unit()
+    }
+
+    fn serialize_unit_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        variant: &'static str,
+    ) -> Result<Option<String>, Error> {
+        Ok(Some(variant.to_owned()))
+    }
+
+    fn serialize_newtype_struct<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        value: &T,
+    ) -> Result<Option<String>, Error> {
+        value.serialize(self)
+    }
+
+    fn serialize_newtype_variant<T: Serialize + ?Sized>(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: &T,
+    ) -> Result<Option<String>, Error> {
+        Err(not_text())
+    }
+
+    fn serialize_seq(self, _: Option<usize>) -> Result<Self::SerializeSeq, Error> {
+        Err(not_text())
+    }
+
+    fn serialize_tuple(self, _: usize) -> Result<Self::SerializeTuple, Error> {
+        Err(not_text())
+    }
+
+    fn serialize_tuple_struct(
+        self,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleStruct, Error> {
+        Err(not_text())
+    }
+
+    fn serialize_tuple_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeTupleVariant, Error> {
+        Err(not_text())
+    }
+
+    fn serialize_map(self, _: Option<usize>) -> Result<Self::SerializeMap, Error> {
+        Err(not_text())
+    }
+
+    fn serialize_struct(self, _: &'static str, _: usize) -> Result<Self::SerializeStruct, Error> {
+        Err(not_text())
+    }
+
+    fn serialize_struct_variant(
+        self,
+        _: &'static str,
+        _: u32,
+        _: &'static str,
+        _: usize,
+    ) -> Result<Self::SerializeStructVariant, Error> {
+        Err(not_text())
+    }
+}
+
+fn not_text() -> Error {
+    Error::Custom("attributes, text and map keys must be simple values".to_owned())
+}
+
+/// Write `text` to `out`, escaping markup (and quotes, in attribute values)
+fn escape(out: &mut String, text: &str, attribute: bool) -> Result<(), Error> {
+    for c in text.chars() {
+        match c {
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '&' => out.push_str("&amp;"),
+            '"' if attribute => out.push_str("&quot;"),
+            // Line breaks in attribute values would be normalized to spaces
+            '\n' if attribute => out.push_str("&#10;"),
+            '\t' | '\n' | '\r' => out.push(c),
+            c if c < ' ' => return Err(Error::Custom(format!("{c:?} is not allowed in XML"))),
+            c => out.push(c),
+        }
+    }
+    Ok(())
+}
+
+fn check_name(name: &str) -> Result<(), Error> {
+    let mut chars = name.chars();
+    let valid = chars.next().is_some_and(|c| c.is_alphabetic() || c == '_')
+        && chars.all(|c| c.is_alphanumeric() || matches!(c, '_' | '-' | '.' | ':'));
+    match valid {
+        true => Ok(()),
+        false => Err(Error::Custom(format!("{name:?} is not a valid XML name"))),
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum Error {
+    #[error("invalid XML at byte {0}: {1}")]
+    Syntax(usize, &'static str),
+    #[error("{0}")]
+    Custom(String),
+}
+
+impl serde::de::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+impl ser::Error for Error {
+    fn custom<T: Display>(msg: T) -> Self {
+        Self::Custom(msg.to_string())
+    }
+}
+
+/// The maximum size of XML request bodies, in bytes
+pub const MAX_LEN: usize = 1024 * 1024;
+
+const MEDIA_TYPES: &[&str] = &["application/xml", "text/xml"];
+const MAX_DEPTH: usize = 64;
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use serde::{Deserialize, Serialize};
+
+    use super::*;
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Order {
+        #[serde(rename = "@id")]
+        id: u64,
+        #[serde(rename = "@note", skip_serializing_if = "Option::is_none")]
+        note: Option<String>,
+        customer: Customer,
+        #[serde(rename = "item")]
+        items: Vec<Item>,
+        status: Status,
+        paid: bool,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Customer {
+        name: String,
+        email: Option<String>,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    struct Item {
+        #[serde(rename = "@currency")]
+        currency: String,
+        #[serde(rename = "$text")]
+        price: f64,
+    }
+
+    #[derive(Debug, Deserialize, PartialEq, Serialize)]
+    enum Status {
+        Open,
+        Shipped,
+    }
+
+    #[test]
+    fn round_trip() {
+        let order = Order {
+            id: 7,
+            note: None,
+            customer: Customer {
+                name: "Tom & Jerry <tj@example.com>".to_owned(),
+                email: None,
+            },
+            items: vec![
+                Item {
+                    currency: "EUR".to_owned(),
+                    price: 2.5,
+                },
+                Item {
+                    currency: "US\"D".to_owned(),
+                    price: 10.0,
+                },
+            ],
+            status: Status::Shipped,
+            paid: true,
+        };
+
+        let xml = to_string(&order).unwrap();
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Order id=\"7\">\
+             <customer><name>Tom &amp; Jerry &lt;tj@example.com&gt;</name></customer>\
+             <item currency=\"EUR\">2.5</item><item currency=\"US&quot;D\">10</item>\
+             <status>Shipped</status><paid>true</paid></Order>"
+        );
+        assert_eq!(from_str::<Order>(&xml).unwrap(), order);
+    }
+
+    #[test]
+    fn parse() {
+        let xml = r#"<?xml version="1.0"?>
+            <!-- an order -->
+            <o:Order xmlns:o="urn:orders" o:id='8' note="rush">
+                <customer>
+                    <name><![CDATA[<Jane>]]> &#x44;oe</name>
+                    <email>jane@example.com</email>
+                </customer>
+                <item currency="EUR"> 1.25 </item>
+                <status>Open</status>
+                <paid>0</paid>
+            </o:Order>"#;
+
+        let order = from_str::<Order>(xml).unwrap();
+        assert_eq!(order.id, 8);
+        assert_eq!(order.note.as_deref(), Some("rush"));
+        assert_eq!(order.customer.name, "<Jane> Doe");
+        assert_eq!(order.customer.email.as_deref(), Some("jane@example.com"));
+        assert_eq!(order.items.len(), 1);
+        assert_eq!(order.items[0].price, 1.25);
+        assert_eq!(order.status, Status::Open);
+        assert!(!order.paid);
+
+        let map = from_str::<BTreeMap<String, String>>("<m><a>1</a><b/></m>").unwrap();
+        assert_eq!(map["a"], "1");
+        assert_eq!(map["b"], "");
+
+        for invalid in [
+            "",
+            "<a>",
+            "<a></b>",
+            "<a/><b/>",
+            "<a x=1/>",
+            "<a>&bogus;</a>",
+            "<!DOCTYPE a [<!ENTITY x \"y\">]><a>&x;</a>",
+        ] {
+            assert!(
+                matches!(
+                    from_str::<BTreeMap<String, String>>(invalid),
+                    Err(Error::Syntax(..))
+                ),
+                "{invalid:?}"
+            );
+        }
+
+        let deep = format!("{}{}", "<a>".repeat(100), "</a>".repeat(100));
+        assert!(from_str::<BTreeMap<String, String>>(&deep).is_err());
+    }
+
+    #[test]
+    fn enums() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Event {
+            kind: Kind,
+            #[serde(rename = "shape")]
+            shapes: Vec<Shape>,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        enum Kind {
+            Created,
+            Deleted,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        enum Shape {
+            Point,
+            Circle(f64),
+            Rect { width: u32, height: u32 },
+        }
+
+        let event = Event {
+            kind: Kind::Deleted,
+            shapes: vec![
+                Shape::Circle(1.5),
+                Shape::Rect {
+                    width: 3,
+                    height: 4,
+                },
+                Shape::Circle(2.0),
+            ],
+        };
+
+        let xml = to_string(&event).unwrap();
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Event><kind>Deleted</kind>\
+             <shape><Circle>1.5</Circle></shape>\
+             <shape><Rect><width>3</width><height>4</height></Rect></shape>\
+             <shape><Circle>2</Circle></shape></Event>"
+        );
+        assert_eq!(from_str::<Event>(&xml).unwrap(), event);
+
+        let xml = "<Event><kind>Created</kind><shape>Point</shape></Event>";
+        let event = from_str::<Event>(xml).unwrap();
+        assert_eq!(event.kind, Kind::Created);
+        assert_eq!(event.shapes, vec![Shape::Point]);
+
+        assert!(from_str::<Event>("<Event><kind>Updated</kind></Event>").is_err());
+
+        #[derive(Serialize)]
+        struct Tuple {
+            value: Pair,
+        }
+
+        #[derive(Serialize)]
+        enum Pair {
+            Pair(u8, u8),
+        }
+
+        let tuple = Tuple {
+            value: Pair::Pair(1, 2),
+        };
+        assert!(matches!(to_string(&tuple), Err(Error::Custom(_))));
+    }
+
+    #[test]
+    fn sequences() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct List {
+            #[serde(rename = "n")]
+            numbers: Vec<u32>,
+            #[serde(rename = "row", default)]
+            rows: Vec<Row>,
+            #[serde(rename = "p")]
+            pair: (String, i8),
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Row {
+            #[serde(rename = "c")]
+            cells: Vec<String>,
+        }
+
+        let list = List {
+            numbers: vec![3, 1, 2],
+            rows: vec![
+                Row {
+                    cells: vec!["a".to_owned(), "b".to_owned()],
+                },
+                Row {
+                    cells: vec!["c".to_owned()],
+                },
+            ],
+            pair: ("x".to_owned(), -1),
+        };
+
+        let xml = to_string(&list).unwrap();
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><List><n>3</n><n>1</n><n>2</n>\
+             <row><c>a</c><c>b</c></row><row><c>c</c></row><p>x</p><p>-1</p></List>"
+        );
+        assert_eq!(from_str::<List>(&xml).unwrap(), list);
+
+        // A single element where a list is expected, and repeated elements that are not adjacent
+        let xml = "<List><n>5</n><p>y</p><row><c>d</c></row><p>2</p></List>";
+        let list = from_str::<List>(xml).unwrap();
+        assert_eq!(list.numbers, vec![5]);
+        assert_eq!(list.rows[0].cells, vec!["d"]);
+        assert_eq!(list.pair, ("y".to_owned(), 2));
+
+        let xml = "<List><n>5</n><n>six</n><p>y</p><p>2</p></List>";
+        assert!(from_str::<List>(xml).is_err());
+    }
+
+    #[test]
+    fn options() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Profile {
+            #[serde(rename = "@lang", skip_serializing_if = "Option::is_none")]
+            lang: Option<String>,
+            nickname: Option<String>,
+            age: Option<u8>,
+            #[serde(rename = "$text", skip_serializing_if = "Option::is_none")]
+            text: Option<String>,
+        }
+
+        let full = Profile {
+            lang: Some("en".to_owned()),
+            nickname: Some("Bo".to_owned()),
+            age: Some(30),
+            text: Some("bio".to_owned()),
+        };
+        let xml = to_string(&full).unwrap();
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <Profile lang=\"en\"><nickname>Bo</nickname><age>30</age>bio</Profile>"
+        );
+        assert_eq!(from_str::<Profile>(&xml).unwrap(), full);
+
+        let empty = Profile {
+            lang: None,
+            nickname: None,
+            age: None,
+            text: None,
+        };
+        let xml = to_string(&empty).unwrap();
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?><Profile></Profile>"
+        );
+        assert_eq!(from_str::<Profile>(&xml).unwrap(), empty);
+
+        // An empty element is present, so it is `Some`
+        let profile = from_str::<Profile>("<Profile><nickname/></Profile>").unwrap();
+        assert_eq!(profile.nickname.as_deref(), Some(""));
+        assert_eq!(profile.age, None);
+    }
+
+    #[test]
+    fn attributes() {
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct Link {
+            #[serde(rename = "@href")]
+            href: String,
+            #[serde(rename = "@rel")]
+            rel: Rel,
+            #[serde(rename = "@hidden")]
+            hidden: bool,
+            #[serde(rename = "$text")]
+            title: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        #[serde(rename_all = "lowercase")]
+        enum Rel {
+            Next,
+            Prev,
+        }
+
+        let link = Link {
+            href: "/a?b=1&c=\"2\"\n".to_owned(),
+            rel: Rel::Next,
+            hidden: false,
+            title: "Next page".to_owned(),
+        };
+        let xml = to_string(&link).unwrap();
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <Link href=\"/a?b=1&amp;c=&quot;2&quot;&#10;\" rel=\"next\" hidden=\"false\">\
+             Next page</Link>"
+        );
+        assert_eq!(from_str::<Link>(&xml).unwrap(), link);
+
+        let xml = "<Link hidden = 'true' rel=\"prev\" x:href='a\"b' xmlns:x=\"urn:x\">Back</Link>";
+        let link = from_str::<Link>(xml).unwrap();
+        assert_eq!(link.href, "a\"b");
+        assert_eq!(link.rel, Rel::Prev);
+        assert!(link.hidden);
+        assert_eq!(link.title, "Back");
+
+        #[derive(Serialize)]
+        struct Late {
+            child: u8,
+            #[serde(rename = "@id")]
+            id: u8,
+        }
+
+        let late = Late { child: 1, id: 2 };
+        assert!(matches!(to_string(&late), Err(Error::Custom(_))));
+
+        #[derive(Serialize)]
+        struct Nested {
+            #[serde(rename = "@list")]
+            list: Vec<u8>,
+        }
+
+        let nested = Nested { list: vec![1] };
+        assert!(matches!(to_string(&nested), Err(Error::Custom(_))));
+    }
+
+    #[test]
+    fn escaping() {
+        let map = |xml| from_str::<BTreeMap<String, String>>(xml).unwrap();
+
+        let parsed = map("<m><a>&lt;&gt;&amp;&quot;&apos;</a><b>&#60;&#x3E;&#x1F600;</b></m>");
+        assert_eq!(parsed["a"], "<>&\"'");
+        assert_eq!(parsed["b"], "<>\u{1F600}");
+
+        let parsed = map("<m><a><![CDATA[<b>&amp;</b> ]] ]>]]>!</a><b>x<!-- <c/> -->y</b></m>");
+        assert_eq!(parsed["a"], "<b>&amp;</b> ]] ]>!");
+        assert_eq!(parsed["b"], "xy");
+
+        let mut text = BTreeMap::new();
+        text.insert("a", "]]> &amp; <\t\r\n\u{e9}");
+        let xml = to_string(&Wrapper(text)).unwrap();
+        assert_eq!(
+            xml,
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\
+             <Wrapper><a>]]&gt; &amp;amp; &lt;\t\r\n\u{e9}</a></Wrapper>"
+        );
+        assert_eq!(map(&xml)["a"], "]]> &amp; <\t\r\n\u{e9}");
+
+        let mut control = BTreeMap::new();
+        control.insert("a", "\u{1}");
+        assert!(matches!(
+            to_string(&Wrapper(control)),
+            Err(Error::Custom(_))
+        ));
+
+        for invalid in [
+            "<m><a>&amp</a></m>",
+            "<m><a>&#xZZ;</a></m>",
+            "<m><a>&#xD800;</a></m>",
+            "<m><a>&#1114112;</a></m>",
+            "<m><a>&nbsp;</a></m>",
+            "<m><a x=\"&foo;\"/></m>",
+        ] {
+            assert!(
+                matches!(
+                    from_str::<BTreeMap<String, String>>(invalid),
+                    Err(Error::Syntax(..))
+                ),
+                "{invalid:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn errors() {
+        let syntax = |xml: &str| match from_str::<BTreeMap<String, String>>(xml) {
+            Err(Error::Syntax(pos, msg)) => (pos, msg),
+            result => panic!("{xml:?}: {result:?}"),
+        };
+
+        assert_eq!(syntax("  text"), (2, "expected root element"));
+        assert_eq!(syntax("<a"), (2, "expected a name"));
+        assert_eq!(syntax("<a>"), (3, "unclosed element"));
+        assert_eq!(syntax("<a><b></a>"), (9, "mismatched closing tag"));
+        assert_eq!(syntax("<a></a x>"), (7, "expected '>'"));
+        assert_eq!(syntax("<a/> <b/>"), (5, "content after root element"));
+        assert_eq!(syntax("<a/>text"), (4, "content after root element"));
+        assert_eq!(syntax("<a x/>"), (4, "expected '=' after attribute name"));
+        assert_eq!(syntax("<a x=1/>"), (5, "expected quoted attribute value"));
+        assert_eq!(syntax("<a x='1/>"), (6, "unterminated attribute value"));
+        assert_eq!(syntax("<a x='<'/>"), (6, "'<' in attribute value"));
+        assert_eq!(syntax("<a></>"), (5, "expected a name"));
+        assert_eq!(
+            syntax("<a><![CDATA[x</a>"),
+            (12, "unterminated CDATA section")
+        );
+        assert_eq!(syntax("<a><!-- x</a>"), (3, "unexpected end of document"));
+        assert_eq!(
+            syntax("<?xml version='1.0'"),
+            (0, "unexpected end of document")
+        );
+        assert_eq!(
+            syntax("<a><!ELEMENT a ANY></a>"),
+            (3, "document type declarations are not supported")
+        );
+
+        assert!(matches!(
+            from_slice::<BTreeMap<String, String>>(b"<a>\xff</a>"),
+            Err(Error::Syntax(3, "invalid UTF-8"))
+        ));
+        assert!(from_slice::<BTreeMap<String, String>>("<a>\u{e9}</a>".as_bytes()).is_ok());
+
+        // Well-formed documents that don't match the type
+        assert!(matches!(
+            from_str::<Order>("<Order id=\"x\"/>"),
+            Err(Error::Custom(_))
+        ));
+        assert!(from_str::<Order>("<Order id=\"1\"/>").is_err());
+
+        // Values that can't be written as a document
+        assert!(matches!(to_string(&1u8), Err(Error::Custom(_))));
+        assert!(matches!(to_string(&vec![1u8]), Err(Error::Custom(_))));
+        let mut invalid = BTreeMap::new();
+        invalid.insert("1a", "x");
+        assert!(matches!(
+            to_string(&Wrapper(invalid)),
+            Err(Error::Custom(_))
+        ));
+
+        #[derive(Serialize)]
+        struct Bytes<'a> {
+            #[serde(with = "as_bytes")]
+            data: &'a [u8],
+        }
+
+        mod as_bytes {
+            pub(super) fn serialize<S: serde::Serializer>(
+                data: &[u8],
+                serializer: S,
+            ) -> Result<S::Ok, S::Error> {
+                serializer.serialize_bytes(data)
+            }
+        }
+
+        assert!(matches!(
+            to_string(&Bytes { data: b"x" }),
+            Err(Error::Custom(_))
+        ));
+    }
+
+    #[derive(Serialize)]
+    struct Wrapper<T>(T);
+
+    #[test]
+    fn negotiate() {
+        let req = |accept: Option<&str>| {
+            let mut req = http::Request::builder();
+            if let Some(accept) = accept {
+                req = req.header(http::header::ACCEPT, accept);
+            }
+            req.body(()).unwrap().into_parts().0
+        };
+
+        let negotiate = |accept| super::negotiate(&req(accept), MEDIA_TYPES);
+        assert_eq!(negotiate(None), Some("application/xml"));
+        assert_eq!(negotiate(Some("*/*")), Some("application/xml"));
+        assert_eq!(
+            negotiate(Some("text/*, application/json")),
+            Some("text/xml")
+        );
+        assert_eq!(
+            negotiate(Some("application/xml;q=0.5, text/xml")),
+            Some("text/xml")
+        );
+        assert_eq!(negotiate(Some("application/json")), None);
+        assert_eq!(
+            negotiate(Some("*/*, application/xml;q=0")),
+            Some("text/xml")
+        );
+    }
+}
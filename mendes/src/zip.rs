@@ -0,0 +1,418 @@
+use std::borrow::Cow;
+use std::error::Error as StdError;
+use std::io;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+
+use bytes::{BufMut, Bytes, BytesMut};
+use futures_util::stream::{Stream, StreamExt};
+use http::header::{CONTENT_DISPOSITION, CONTENT_TYPE};
+use http::request::Parts;
+use http::{Response, StatusCode};
+use http_body::{Frame, SizeHint};
+use pin_project::pin_project;
+
+use crate::application::{Application, IntoResponse};
+use crate::utils::attachment;
+use crate::Body;
+
+/// An `application/zip` response assembled on the fly from a stream of entries
+///
+/// Entries are written to the archive as they arrive and their contents are streamed without
+/// buffering, so large archives can be served without temporary files. Entries are stored
+/// without compression, which is fine for file types that are already compressed (like images
+/// or PDFs).
+///
+/// ```no_run
+/// # use bytes::Bytes;
+/// # use futures_util::stream::{self, Stream};
+/// # use mendes::zip::{Zip, ZipEntry};
+/// # use mendes::{handler, Error};
+/// # struct Db;
+/// # struct Attachment {
+/// #     name: String,
+/// #     key: String,
+/// # }
+/// # impl Db {
+/// #     async fn attachments(&self, id: u64) -> Result<Vec<Attachment>, Error> {
+/// #         todo!()
+/// #     }
+/// # }
+/// # #[derive(Clone)]
+/// # struct Storage;
+/// # impl Storage {
+/// #     fn stream(&self, key: &str) -> impl Stream<Item = Result<Bytes, Error>> + Send + 'static {
+/// #         stream::empty()
+/// #     }
+/// # }
+/// # struct App {
+/// #     db: Db,
+/// #     storage: Storage,
+/// # }
+/// # #[async_trait::async_trait]
+/// # impl mendes::Application for App {
+/// #     type RequestBody = mendes::Body;
+/// #     type ResponseBody = mendes::Body;
+/// #     type Error = mendes::Error;
+/// #     async fn handle(cx: mendes::Context<Self>) -> mendes::http::Response<mendes::Body> {
+/// #         todo!()
+/// #     }
+/// # }
+/// #[handler(GET)]
+/// async fn download_all(
+///     app: &App,
+///     id: u64,
+/// ) -> Result<Zip<impl Stream<Item = Result<ZipEntry, Error>> + Send>, Error> {
+///     let storage = app.storage.clone();
+///     let entries = app.db.attachments(id).await?.into_iter().map(move |attachment| {
+///         Ok(ZipEntry::new(attachment.name, storage.stream(&attachment.key)))
+///     });
+///     Ok(Zip::new(stream::iter(entries)).filename("attachments.zip"))
+/// }
+/// # fn main() {}
+/// ```
+///
+/// Archives are limited to 65,535 entries and 4 GiB in total; the response body is aborted
+/// with an error if either limit is exceeded.
+pub struct Zip<S> {
+    entries: S,
+    filename: Option<Cow<'static, str>>,
+}
+
+impl<S> Zip<S> {
+    /// Create a ZIP response from a stream of entries
+    ///
+    /// If the stream (or the contents of an entry) yields an error, the response body is
+    /// aborted.
+    pub fn new(entries: S) -> Self {
+        Self {
+            entries,
+            filename: None,
+        }
+    }
+
+    /// Set the file name to suggest to the client for downloading the response
+    ///
+    /// This sets a `Content-Disposition: attachment` header on the response.
+    pub fn filename(mut self, filename: impl Into<Cow<'static, str>>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+}
+
+impl<S, E> Zip<S>
+where
+    S: Stream<Item = Result<ZipEntry, E>> + Send + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    /// Convert into a `Response` with a streaming `Body`
+    pub fn into_response(self) -> Response<Body> {
+        let mut builder = Response::builder()
+            .status(StatusCode::OK)
+            .header(CONTENT_TYPE, "application/zip");
+        if let Some(filename) = &self.filename {
+            builder = builder.header(CONTENT_DISPOSITION, attachment(filename));
+        }
+
+        builder
+            .body(Body::stream(ZipBody {
+                entries: self.entries,
+                current: None,
+                offset: 0,
+                directory: BytesMut::new(),
+                count: 0,
+                done: false,
+            }))
+            .unwrap()
+    }
+}
+
+impl<A, S, E> IntoResponse<A> for Zip<S>
+where
+    A: Application<ResponseBody = Body>,
+    S: Stream<Item = Result<ZipEntry, E>> + Send + 'static,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    fn into_response(self, _: &A, _: &Parts) -> Response<Body> {
+        Zip::into_response(self)
+    }
+}
+
+/// A single file in a `Zip` archive
+pub struct ZipEntry {
+    name: Cow<'static, str>,
+    data: Pin<Box<dyn Stream<Item = io::Result<Bytes>> + Send>>,
+}
+
+impl ZipEntry {
+    /// Create an entry with the given path in the archive and a stream of its contents
+    ///
+    /// Use `/` to separate directories in the `name`.
+    pub fn new<D, E>(name: impl Into<Cow<'static, str>>, data: D) -> Self
+    where
+        D: Stream<Item = Result<Bytes, E>> + Send + 'static,
+        E: Into<Box<dyn StdError + Send + Sync>>,
+    {
+        Self {
+            name: name.into(),
+            data: Box::pin(data.map(|r| r.map_err(|e| io::Error::new(io::ErrorKind::Other, e)))),
+        }
+    }
+
+    /// Create an entry with the given path in the archive from in-memory contents
+    pub fn from_bytes(name: impl Into<Cow<'static, str>>, data: impl Into<Bytes>) -> Self {
+        let data = futures_util::stream::iter([Ok::<_, io::Error>(data.into())]);
+        Self::new(name, data)
+    }
+}
+
+struct CurrentEntry {
+    entry: ZipEntry,
+    hasher: crc32fast::Hasher,
+    size: u64,
+    offset: u64,
+}
+
+#[pin_project]
+struct ZipBody<S> {
+    #[pin]
+    entries: S,
+    current: Option<CurrentEntry>,
+    offset: u64,
+    directory: BytesMut,
+    count: u16,
+    done: bool,
+}
+
+impl<S, E> http_body::Body for ZipBody<S>
+where
+    S: Stream<Item = Result<ZipEntry, E>>,
+    E: Into<Box<dyn StdError + Send + Sync>>,
+{
+    type Data = Bytes;
+    type Error = io::Error;
+
+    fn poll_frame(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+        let this = self.project();
+        if *this.done {
+            return Poll::Ready(None);
+        }
+
+        let result = match this.current {
+            Some(current) => match current.entry.data.as_mut().poll_next(cx) {
+                Poll::Ready(Some(Ok(data))) => {
+                    current.hasher.update(&data);
+                    current.size += data.len() as u64;
+                    *this.offset += data.len() as u64;
+                    Ok(data)
+                }
+                Poll::Ready(Some(Err(error))) => Err(error),
+                Poll::Ready(None) => {
+                    let current = this.current.take().unwrap();
+                    finish_entry(current, this.offset, this.directory)
+                }
+                Poll::Pending => return Poll::Pending,
+            },
+            None => match this.entries.poll_next(cx) {
+                Poll::Ready(Some(Ok(entry))) => {
+                    start_entry(entry, this.offset, this.count).map(|(current, header)| {
+                        *this.current = Some(current);
+                        header
+                    })
+                }
+                Poll::Ready(Some(Err(error))) => Err(io::Error::new(io::ErrorKind::Other, error)),
+                Poll::Ready(None) => {
+                    *this.done = true;
+                    finish_archive(*this.offset, this.directory, *this.count)
+                }
+                Poll::Pending => return Poll::Pending,
+            },
+        };
+
+        if result.is_err() {
+            *this.done = true;
+        }
+        Poll::Ready(Some(result.map(Frame::data)))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+
+    fn size_hint(&self) -> SizeHint {
+        SizeHint::default()
+    }
+}
+
+/// Write the local file header for `entry`
+fn start_entry(
+    entry: ZipEntry,
+    offset: &mut u64,
+    count: &mut u16,
+) -> io::Result<(CurrentEntry, Bytes)> {
+    *count = count
+        .checked_add(1)
+        .ok_or_else(|| too_large("too many entries"))?;
+    let name = entry.name.as_bytes();
+    if name.len() > u16::MAX as usize {
+        return Err(too_large("entry name too long"));
+    }
+
+    let mut buf = BytesMut::with_capacity(30 + name.len());
+    buf.put_u32_le(0x04034b50);
+    buf.put_u16_le(VERSION);
+    buf.put_u16_le(FLAGS);
+    buf.put_u16_le(0); // stored
+    buf.put_u16_le(DOS_TIME);
+    buf.put_u16_le(DOS_DATE);
+    buf.put_u32_le(0); // CRC-32 and sizes are in the data descriptor
+    buf.put_u32_le(0);
+    buf.put_u32_le(0);
+    buf.put_u16_le(name.len() as u16);
+    buf.put_u16_le(0);
+    buf.put_slice(name);
+
+    let current = CurrentEntry {
+        entry,
+        hasher: crc32fast::Hasher::new(),
+        size: 0,
+        offset: *offset,
+    };
+    *offset += buf.len() as u64;
+    Ok((current, buf.freeze()))
+}
+
+/// Write the data descriptor for `current` and add it to the central directory
+fn finish_entry(
+    current: CurrentEntry,
+    offset: &mut u64,
+    directory: &mut BytesMut,
+) -> io::Result<Bytes> {
+    if current.size > u32::MAX as u64 || current.offset > u32::MAX as u64 {
+        return Err(too_large("archive too large"));
+    }
+
+    let crc = current.hasher.finalize();
+    let size = current.size as u32;
+    let mut buf = BytesMut::with_capacity(16);
+    buf.put_u32_le(0x08074b50);
+    buf.put_u32_le(crc);
+    buf.put_u32_le(size);
+    buf.put_u32_le(size);
+    *offset += buf.len() as u64;
+
+    let name = current.entry.name.as_bytes();
+    directory.put_u32_le(0x02014b50);
+    directory.put_u16_le(VERSION);
+    directory.put_u16_le(VERSION);
+    directory.put_u16_le(FLAGS);
+    directory.put_u16_le(0);
+    directory.put_u16_le(DOS_TIME);
+    directory.put_u16_le(DOS_DATE);
+    directory.put_u32_le(crc);
+    directory.put_u32_le(size);
+    directory.put_u32_le(size);
+    directory.put_u16_le(name.len() as u16);
+    directory.put_u16_le(0); // extra field length
+    directory.put_u16_le(0); // comment length
+    directory.put_u16_le(0); // disk number
+    directory.put_u16_le(0); // internal attributes
+    directory.put_u32_le(0); // external attributes
+    directory.put_u32_le(current.offset as u32);
+    directory.put_slice(name);
+
+    Ok(buf.freeze())
+}
+
+/// Write the central directory and its end record
+fn finish_archive(offset: u64, directory: &mut BytesMut, count: u16) -> io::Result<Bytes> {
+    let size = directory.len() as u64;
+    if offset > u32::MAX as u64 || size > u32::MAX as u64 {
+        return Err(too_large("archive too large"));
+    }
+
+    let mut buf = std::mem::take(directory);
+    buf.put_u32_le(0x06054b50);
+    buf.put_u16_le(0);
+    buf.put_u16_le(0);
+    buf.put_u16_le(count);
+    buf.put_u16_le(count);
+    buf.put_u32_le(size as u32);
+    buf.put_u32_le(offset as u32);
+    buf.put_u16_le(0);
+    Ok(buf.freeze())
+}
+
+fn too_large(msg: &'static str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("unable to write ZIP: {msg}"),
+    )
+}
+
+/// Version 2.0, the minimum for archives using data descriptors
+const VERSION: u16 = 20;
+/// Sizes are written after the data (bit 3), names are UTF-8 (bit 11)
+const FLAGS: u16 = 0x0808;
+/// 1980-01-01 00:00, since entries don't have modification times
+const DOS_TIME: u16 = 0;
+const DOS_DATE: u16 = 0x21;
+
+#[cfg(test)]
+mod tests {
+    use futures_util::future::poll_fn;
+    use http_body::Body as _;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn archive() {
+        let entries = futures_util::stream::iter([
+            Ok::<_, io::Error>(ZipEntry::from_bytes("a.txt", "hello")),
+            Ok(ZipEntry::new(
+                "dir/b.txt",
+                futures_util::stream::iter([
+                    Ok::<_, io::Error>(Bytes::from("wor")),
+                    Ok(Bytes::from("ld")),
+                ]),
+            )),
+        ]);
+
+        let rsp = Zip::new(entries).filename("files.zip").into_response();
+        assert_eq!(rsp.headers()[CONTENT_TYPE], "application/zip");
+        assert_eq!(
+            rsp.headers()[CONTENT_DISPOSITION],
+            "attachment; filename=\"files.zip\""
+        );
+
+        let mut body = rsp.into_body();
+        let mut zip = Vec::new();
+        while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+            zip.extend_from_slice(frame.unwrap().data_ref().unwrap());
+        }
+
+        let u16_at = |i: usize| u16::from_le_bytes([zip[i], zip[i + 1]]);
+        let u32_at = |i: usize| u32::from_le_bytes(zip[i..i + 4].try_into().unwrap());
+
+        // End of central directory record
+        let end = zip.len() - 22;
+        assert_eq!(u32_at(end), 0x06054b50);
+        assert_eq!(u16_at(end + 10), 2);
+        let (dir_size, dir_offset) = (u32_at(end + 12) as usize, u32_at(end + 16) as usize);
+        assert_eq!(dir_offset + dir_size, end);
+
+        // Second central directory entry points at its local header
+        let second = dir_offset + 46 + "a.txt".len();
+        assert_eq!(u32_at(second), 0x02014b50);
+        assert_eq!(u32_at(second + 16), crc32fast::hash(b"world"));
+        assert_eq!(u32_at(second + 20), 5);
+        assert_eq!(&zip[second + 46..second + 55], b"dir/b.txt");
+        let local = u32_at(second + 42) as usize;
+        assert_eq!(u32_at(local), 0x04034b50);
+        assert_eq!(&zip[local + 30..local + 39], b"dir/b.txt");
+        assert_eq!(&zip[local + 39..local + 44], b"world");
+    }
+}
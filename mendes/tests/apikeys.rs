@@ -0,0 +1,115 @@
+#![cfg(all(feature = "apikeys", feature = "body-util"))]
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use mendes::apikeys::{ApiKey, ApiKeys, AppWithApiKeys, MemoryStore};
+use mendes::http::{Request, Response, StatusCode};
+use mendes::{handler, route, Application, Body, Context, Error};
+
+#[tokio::test]
+async fn test_api_key() {
+    let app = App::new();
+    let (key, stored) = app
+        .keys
+        .mint("reports", vec!["reports:read".to_owned()])
+        .await;
+    assert!(key.starts_with("test_"));
+
+    let rsp = handle(&app, Some(("x-api-key", key.clone()))).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(body(rsp).await, "reports for reports");
+
+    let rsp = handle(&app, Some(("authorization", format!("Bearer {key}")))).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+
+    assert!(app.keys.revoke(&stored.id).await);
+    let rsp = handle(&app, Some(("x-api-key", key))).await;
+    assert_eq!(rsp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body(rsp).await, "invalid API key");
+}
+
+#[tokio::test]
+async fn test_invalid() {
+    let app = App::new();
+    let (key, _) = app.keys.mint("other", vec![]).await;
+
+    let rsp = handle(&app, None).await;
+    assert_eq!(rsp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body(rsp).await, "no API key in request");
+
+    // Wrong secret for an existing key
+    let last = match key.ends_with('0') {
+        true => '1',
+        false => '0',
+    };
+    let forged = format!("{}{last}", &key[..key.len() - 1]);
+    let rsp = handle(&app, Some(("x-api-key", forged))).await;
+    assert_eq!(rsp.status(), StatusCode::UNAUTHORIZED);
+
+    let rsp = handle(&app, Some(("authorization", format!("Basic {key}")))).await;
+    assert_eq!(rsp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body(rsp).await, "no API key in request");
+
+    // Valid key without the required scope
+    let rsp = handle(&app, Some(("x-api-key", key))).await;
+    assert_eq!(rsp.status(), StatusCode::FORBIDDEN);
+}
+
+async fn handle(app: &Arc<App>, header: Option<(&str, String)>) -> Response<Body> {
+    let mut req = Request::builder().uri("https://example.com/reports");
+    if let Some((name, value)) = header {
+        req = req.header(name, value);
+    }
+
+    App::handle(Context::new(app.clone(), req.body(()).unwrap())).await
+}
+
+async fn body(rsp: Response<Body>) -> String {
+    let body = rsp.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+struct App {
+    keys: ApiKeys<MemoryStore>,
+}
+
+impl App {
+    fn new() -> Arc<Self> {
+        Arc::new(App {
+            keys: ApiKeys::new(MemoryStore::new(), "test"),
+        })
+    }
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("reports") => reports,
+        })
+    }
+}
+
+impl AppWithApiKeys for App {
+    type Store = MemoryStore;
+
+    fn api_keys(&self) -> &ApiKeys<MemoryStore> {
+        &self.keys
+    }
+}
+
+#[handler(GET)]
+async fn reports(_: &App, #[async_extract] key: ApiKey) -> Result<Response<Body>, Error> {
+    key.require("reports:read")?;
+    Ok(Response::new(Body::from(Bytes::from(format!(
+        "reports for {}",
+        key.0.name
+    )))))
+}
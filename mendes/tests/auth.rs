@@ -0,0 +1,158 @@
+#![cfg(feature = "auth")]
+
+use std::convert::TryInto;
+use std::sync::Arc;
+use std::time::SystemTime;
+
+use async_trait::async_trait;
+use mendes::auth::totp::Totp;
+use mendes::auth::{self, password::Hasher};
+use mendes::cookies::{cookie, AppWithAeadKey, AppWithCookies, Key};
+use mendes::http::header::{COOKIE, LOCATION, SET_COOKIE};
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::{handler, route, Application, Body, Context, Error};
+use serde::{Deserialize, Serialize};
+
+#[tokio::test]
+async fn two_factor_login() {
+    let app = Arc::new(App {
+        key: Key::new(&[3; 32]),
+        hash: Hasher::new(1_000.try_into().unwrap()).hash("hunter2"),
+        totp: Totp::generate(),
+    });
+
+    let rsp = handle(&app, "/login?password=hunter3", None).await;
+    assert_eq!(rsp.status(), StatusCode::UNAUTHORIZED);
+
+    let rsp = handle(&app, "/login?password=hunter2", None).await;
+    assert_eq!(rsp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(rsp.headers()[LOCATION], "/verify");
+    let pending = cookies(&rsp).remove(0);
+    assert!(pending.starts_with("PendingLogin="));
+
+    let rsp = handle(&app, "/verify?code=000000", Some(&pending)).await;
+    assert_eq!(rsp.status(), StatusCode::UNAUTHORIZED);
+
+    let code = app.totp.code_at(SystemTime::now());
+    let rsp = handle(&app, &format!("/verify?code={code}"), Some(&pending)).await;
+    assert_eq!(rsp.status(), StatusCode::SEE_OTHER);
+    assert_eq!(rsp.headers()[LOCATION], "/");
+    let cookies = cookies(&rsp);
+    assert_eq!(cookies[0], "PendingLogin=None");
+    assert!(cookies[1].starts_with("Session="));
+
+    let mut headers = mendes::http::HeaderMap::new();
+    headers.insert(COOKIE, cookies[1].as_str().try_into().unwrap());
+    assert_eq!(app.cookie::<Session>(&headers).unwrap().user, 37);
+}
+
+#[test]
+fn return_path() {
+    let app = App {
+        key: Key::new(&[3; 32]),
+        hash: String::new(),
+        totp: Totp::generate(),
+    };
+
+    let rsp = auth::login::<_, _, Body>(&app, Session { user: 37 }, "/home").unwrap();
+    assert_eq!(rsp.headers()[LOCATION], "/home");
+    for path in ["https://evil.com", "//evil.com", "/\\evil.com", "/home\r\n"] {
+        assert!(matches!(
+            auth::login::<_, _, Body>(&app, Session { user: 37 }, path),
+            Err(auth::Error::InvalidReturnPath)
+        ));
+    }
+}
+
+async fn handle(app: &Arc<App>, path: &str, cookie: Option<&str>) -> Response<Body> {
+    let mut req = Request::builder()
+        .uri(format!("https://example.com{path}"))
+        .body(())
+        .unwrap();
+    if let Some(cookie) = cookie {
+        req.headers_mut().insert(COOKIE, cookie.try_into().unwrap());
+    }
+    App::handle(Context::new(app.clone(), req)).await
+}
+
+fn cookies(rsp: &Response<Body>) -> Vec<String> {
+    rsp.headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|value| {
+            value
+                .to_str()
+                .unwrap()
+                .split(';')
+                .next()
+                .unwrap()
+                .to_owned()
+        })
+        .collect()
+}
+
+struct App {
+    key: Key,
+    hash: String,
+    totp: Totp,
+}
+
+impl AppWithAeadKey for App {
+    fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("login") => login,
+            Some("verify") => verify,
+        })
+    }
+}
+
+#[handler(GET)]
+async fn login(app: &App, #[query] form: LoginForm) -> Result<Response<Body>, Error> {
+    if !Hasher::new(1_000.try_into().unwrap())
+        .verify(&form.password, &app.hash)
+        .is_valid()
+    {
+        return Err(Error::unauthorized("invalid credentials"));
+    }
+
+    Ok(auth::challenge(app, Session { user: 37 }, "/verify")?)
+}
+
+#[handler(GET)]
+async fn verify(app: &App, req: &Parts, #[query] form: CodeForm) -> Result<Response<Body>, Error> {
+    let session = auth::pending::<_, Session>(app, &req.headers)
+        .ok_or_else(|| Error::unauthorized("login expired"))?;
+    if app.totp.verify(&form.code, None).is_none() {
+        return Err(Error::unauthorized("invalid code"));
+    }
+
+    Ok(auth::complete(app, session, "/")?)
+}
+
+#[derive(Deserialize)]
+struct LoginForm {
+    password: String,
+}
+
+#[derive(Deserialize)]
+struct CodeForm {
+    code: String,
+}
+
+#[cookie]
+#[derive(Deserialize, Serialize)]
+struct Session {
+    user: i32,
+}
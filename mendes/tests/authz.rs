@@ -0,0 +1,106 @@
+#![cfg(feature = "authz")]
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mendes::authz::{self, Permission, Policy, Requires, Roles};
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::{handler, route, scope, Application, Context, Error};
+
+#[tokio::test]
+async fn test_requires() {
+    let rsp = handle("/posts/edit", "editor").await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "edited");
+
+    let rsp = handle("/posts/edit", "viewer").await;
+    assert_eq!(rsp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(rsp.into_body(), "permission denied");
+}
+
+#[tokio::test]
+async fn test_guard() {
+    let rsp = handle("/admin/users", "admin").await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "users");
+
+    let rsp = handle("/admin/users", "editor").await;
+    assert_eq!(rsp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(rsp.into_body(), "permission denied");
+}
+
+async fn handle(path: &str, role: &str) -> Response<String> {
+    let app = Arc::new(App {
+        roles: Roles::new()
+            .role("editor", [EditPosts::NAME])
+            .role("admin", [EditPosts::NAME, ManageUsers::NAME]),
+    });
+
+    let req = Request::builder()
+        .uri(format!("https://example.com{path}"))
+        .header("x-role", role)
+        .body(())
+        .unwrap();
+    App::handle(Context::new(app, req)).await
+}
+
+struct App {
+    roles: Roles,
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = String;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("posts") => match cx.path() {
+                Some("edit") => edit,
+            },
+            Some("admin") => admin,
+        })
+    }
+}
+
+impl Policy for App {
+    fn permits(&self, req: &Parts, permission: &str) -> bool {
+        let role = req.headers.get("x-role").and_then(|v| v.to_str().ok());
+        self.roles.permits(role, permission)
+    }
+}
+
+#[handler(GET)]
+async fn edit(_: &App, _: Requires<EditPosts>) -> Result<Response<String>, Error> {
+    Ok(Response::new("edited".to_owned()))
+}
+
+#[scope]
+async fn admin(cx: &mut Context<App>) -> Response<String> {
+    if let Err(rsp) = authz::guard::<ManageUsers, _>(cx) {
+        return rsp;
+    }
+
+    route!(match cx.path() {
+        Some("users") => users,
+    })
+}
+
+#[handler(GET)]
+async fn users(_: &App) -> Result<Response<String>, Error> {
+    Ok(Response::new("users".to_owned()))
+}
+
+struct EditPosts;
+
+impl Permission for EditPosts {
+    const NAME: &'static str = "posts:edit";
+}
+
+struct ManageUsers;
+
+impl Permission for ManageUsers {
+    const NAME: &'static str = "users:manage";
+}
@@ -2,12 +2,16 @@
 
 use std::borrow::Cow;
 use std::sync::Arc;
+#[cfg(feature = "timing")]
+use std::time::Duration;
 
 use async_trait::async_trait;
-use mendes::application::{IntoResponse, PathState};
+use mendes::application::{Extension, IntoResponse, PathState, Rest};
 use mendes::http::request::Parts;
 use mendes::http::{Method, Request, Response, StatusCode};
-use mendes::{handler, route, scope, Application, Context, FromContext};
+#[cfg(feature = "timing")]
+use mendes::timing::ServerTiming;
+use mendes::{handler, route, scope, Application, Context, FromContext, FromContextAsync};
 
 #[tokio::test]
 async fn test_query() {
@@ -100,6 +104,171 @@ async fn test_custom_error_handler() {
     assert_eq!(rsp.status(), StatusCode::IM_A_TEAPOT);
 }
 
+#[tokio::test]
+async fn test_optional() {
+    let rsp = handle(path_request("/optional")).await;
+    assert_eq!(rsp.into_body(), "num: None, query: None");
+
+    let rsp = handle(path_request("/optional/12?foo=3&bar=baz")).await;
+    assert_eq!(
+        rsp.into_body(),
+        "num: Some(12), query: Some(Query { foo: 3, bar: \"baz\" })"
+    );
+}
+
+#[tokio::test]
+async fn test_optional_segment() {
+    let rsp = handle(path_request("/a/12/b")).await;
+    assert_eq!(rsp.into_body(), "num: Some(12), name: b");
+
+    // An unparseable component is not skipped, which would pass it on as `name`
+    let rsp = handle(path_request("/a/notanumber/b")).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(rsp.into_body(), "unable to parse path component");
+}
+
+#[tokio::test]
+async fn test_result() {
+    let rsp = handle(path_request("/result/12")).await;
+    assert_eq!(rsp.into_body(), "num = 12");
+
+    let rsp = handle(path_request("/result/Foo")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "error: unable to parse path component");
+}
+
+#[tokio::test]
+async fn test_extension() {
+    let rsp = handle(path_request("/extension")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "tenant: acme");
+
+    let rsp = handle(path_request("/missing-extension")).await;
+    assert_eq!(rsp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(rsp.into_body(), "request extension missing");
+}
+
+#[tokio::test]
+async fn test_derive_from_context() {
+    let rsp = handle(path_request("/env/7/3/Foo")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "tenant acme: 7 3 Foo (/env/7/3/Foo)");
+
+    let rsp = handle(path_request("/env/7/3")).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_async_extract() {
+    let rsp = handle(path_request("/session/bar")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "session for bar");
+
+    // Asynchronous extractors consume path components in declaration order
+    let rsp = handle(path_request("/session/bar/2")).await;
+    assert_eq!(rsp.into_body(), "session for bar (page 2)");
+
+    let rsp = handle(path_request("/session")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "no session");
+}
+
+#[tokio::test]
+async fn test_rest_extractor() {
+    let rsp = handle(path_request("/files/raw/some%20dir/file.txt")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "some%20dir/file.txt");
+
+    let rsp = handle(path_request("/files/decoded/some%20dir/file.txt")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "some dir/file.txt");
+}
+
+#[tokio::test]
+async fn test_method_override() {
+    let mut req = path_request("/resource?_method=delete");
+    *req.method_mut() = Method::POST;
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "DELETE");
+
+    let mut req = path_request("/resource");
+    *req.method_mut() = Method::POST;
+    req.headers_mut()
+        .insert("x-http-method-override", "PUT".parse().unwrap());
+    let rsp = handle(req).await;
+    assert_eq!(rsp.into_body(), "PUT");
+
+    // Only POST requests can be overridden, and only to a limited set of methods
+    let rsp = handle(path_request("/resource?_method=DELETE")).await;
+    assert_eq!(rsp.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    let mut req = path_request("/resource?_method=CONNECT");
+    *req.method_mut() = Method::POST;
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::METHOD_NOT_ALLOWED);
+}
+
+#[tokio::test]
+async fn test_accepts() {
+    let mut req = path_request("/upload");
+    *req.method_mut() = Method::POST;
+    req.headers_mut().insert(
+        "content-type",
+        "application/json; charset=utf-8".parse().unwrap(),
+    );
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+
+    let mut req = path_request("/upload");
+    *req.method_mut() = Method::POST;
+    req.headers_mut()
+        .insert("content-type", "image/png".parse().unwrap());
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+
+    let mut req = path_request("/upload");
+    *req.method_mut() = Method::POST;
+    req.headers_mut()
+        .insert("content-type", "text/plain".parse().unwrap());
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(
+        rsp.into_body(),
+        "content type on request body unknown: text/plain"
+    );
+
+    let mut req = path_request("/upload");
+    *req.method_mut() = Method::POST;
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+}
+
+#[tokio::test]
+async fn test_response_meta() {
+    let mut req = path_request("/created");
+    *req.method_mut() = Method::POST;
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::CREATED);
+    assert_eq!(rsp.headers()["cache-control"], "no-store");
+    assert_eq!(rsp.headers()["x-frame-options"], "DENY");
+    assert_eq!(rsp.into_body(), "created");
+
+    let rsp = handle(path_request("/created")).await;
+    assert_eq!(rsp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert!(!rsp.headers().contains_key("cache-control"));
+}
+
+#[cfg(feature = "timing")]
+#[tokio::test]
+async fn test_server_timing() {
+    let rsp = handle(path_request("/timed")).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    let value = rsp.headers()["server-timing"].to_str().unwrap();
+    assert!(value.starts_with("db;dur=5.0, cache;desc=\"Cache read\";dur=0.2, render;dur="));
+    assert!(value.contains(", total;dur="));
+}
+
 #[tokio::test]
 async fn basic() {
     let rsp = handle(path_request("/hello")).await;
@@ -126,7 +295,12 @@ impl Application for App {
     type Error = Error;
 
     async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
-        route!(match cx.path() {
+        #[cfg(feature = "timing")]
+        let timing = cx.server_timing();
+        cx.req.extensions.insert(Tenant("acme"));
+        cx.override_method();
+        #[allow(unused_mut)] // Depends on features
+        let mut rsp = route!(match cx.path() {
             Some("hello") => hello,
             Some("named") => named,
             Some("inc") => inc,
@@ -142,7 +316,26 @@ impl Application for App {
             Some("custom_hello") => custom_error,
 
             Some("query") => with_query,
-        })
+            #[cfg(feature = "timing")]
+            Some("timed") => timed,
+            Some("optional") => optional,
+            Some("a") => optional_segment,
+            Some("result") => result,
+            Some("extension") => extension,
+            Some("env") => with_env,
+            Some("session") => with_session,
+            Some("resource") => resource,
+            Some("upload") => upload,
+            Some("created") => created,
+            Some("files") => match cx.path() {
+                Some("raw") => raw_files,
+                Some("decoded") => decoded_files,
+            },
+            Some("missing-extension") => missing_extension,
+        });
+        #[cfg(feature = "timing")]
+        timing.apply(&mut rsp);
+        rsp
     }
 }
 
@@ -210,6 +403,179 @@ async fn hello(_: &App) -> Result<Response<String>, Error> {
         .unwrap())
 }
 
+#[handler(GET)]
+async fn optional(
+    _: &App,
+    num: Option<usize>,
+    #[query] query: Option<Query<'_>>,
+) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!("num: {num:?}, query: {query:?}"))
+        .unwrap())
+}
+
+#[handler(GET)]
+async fn optional_segment(
+    _: &App,
+    num: Option<usize>,
+    name: String,
+) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!("num: {num:?}, name: {name}"))
+        .unwrap())
+}
+
+#[handler(GET)]
+async fn result(_: &App, num: Result<usize, Error>) -> Result<Response<String>, Error> {
+    let body = match num {
+        Ok(num) => format!("num = {num}"),
+        Err(Error::Mendes(e)) => format!("error: {e}"),
+        Err(e) => return Err(e),
+    };
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(body)
+        .unwrap())
+}
+
+#[derive(Clone)]
+struct Tenant(&'static str);
+
+#[handler(GET)]
+async fn extension(
+    _: &App,
+    Extension(tenant): Extension<Tenant>,
+) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!("tenant: {}", tenant.0))
+        .unwrap())
+}
+
+#[derive(FromContext)]
+struct Env<'a> {
+    req: &'a Parts,
+    tenant: Extension<Tenant>,
+    num: usize,
+}
+
+#[derive(FromContext)]
+struct Path(u8, String);
+
+#[handler(GET)]
+async fn with_env(_: &App, env: Env<'_>, Path(id, name): Path) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!(
+            "tenant {}: {} {id} {name} ({})",
+            env.tenant.0 .0,
+            env.num,
+            env.req.uri.path()
+        ))
+        .unwrap())
+}
+
+struct Session(String);
+
+#[async_trait]
+impl<'a> FromContextAsync<'a, App> for Session {
+    async fn from_context(
+        app: &'a Arc<App>,
+        req: &'a Parts,
+        state: &mut PathState,
+        body: &mut Option<()>,
+    ) -> Result<Self, Error> {
+        let user = <String as FromContext<'a, App>>::from_context(app, req, state, body)?;
+        tokio::task::yield_now().await;
+        Ok(Session(user))
+    }
+}
+
+#[handler(GET)]
+async fn with_session(
+    _: &App,
+    #[async_extract] session: Option<Session>,
+    page: Option<String>,
+) -> Result<Response<String>, Error> {
+    let mut body = match session {
+        Some(session) => format!("session for {}", session.0),
+        None => "no session".to_owned(),
+    };
+    if let Some(page) = page {
+        body.push_str(&format!(" (page {page})"));
+    }
+
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(body)
+        .unwrap())
+}
+
+#[handler(POST, accepts = "application/json", accepts = "image/*")]
+async fn upload(_: &App) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body("uploaded".into())
+        .unwrap())
+}
+
+#[handler(
+    POST,
+    status = 201,
+    header("Cache-Control", "no-store"),
+    header("X-Frame-Options", "DENY")
+)]
+async fn created(_: &App) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .header("cache-control", "max-age=60")
+        .body("created".into())
+        .unwrap())
+}
+
+#[handler(PUT, DELETE)]
+async fn resource(_: &App, req: &Parts) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(req.method.to_string())
+        .unwrap())
+}
+
+#[handler(GET)]
+async fn raw_files(_: &App, Rest(path): Rest<&str>) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(path.to_owned())
+        .unwrap())
+}
+
+#[handler(GET)]
+async fn decoded_files(_: &App, Rest(path): Rest<Cow<'_, str>>) -> Result<Response<String>, Error> {
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(path.into_owned())
+        .unwrap())
+}
+
+#[handler(GET)]
+async fn missing_extension(_: &App, _: Extension<u32>) -> Result<Response<String>, Error> {
+    unreachable!()
+}
+
+#[cfg(feature = "timing")]
+#[handler(GET)]
+async fn timed(_: &App, timing: ServerTiming) -> Result<Response<String>, Error> {
+    timing.record("db", Duration::from_millis(5));
+    timing.record_with_description("cache", "Cache \"read\"", Duration::from_micros(250));
+    drop(timing.start("render"));
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body("timed".into())
+        .unwrap())
+}
+
 #[handler(GET)]
 async fn custom_error(_: &App, _x: ContextExtraction) -> Result<Response<String>, HandlerError> {
     Err(HandlerError::Test)
@@ -1,11 +1,14 @@
 #![cfg(all(feature = "application", feature = "hyper"))]
 
+use std::future::poll_fn;
+use std::pin::pin;
 use std::sync::Arc;
 
 use async_trait::async_trait;
 use mendes::application::IntoResponse;
 use mendes::http::request::Parts;
-use mendes::http::{Method, Request, Response, StatusCode};
+use mendes::http::{HeaderMap, HeaderValue, Method, Request, Response, StatusCode};
+use mendes::hyper::body::Body as _;
 use mendes::{handler, route, Application, Body, Context};
 
 #[cfg(feature = "json")]
@@ -16,6 +19,84 @@ async fn test_json_decode() {
     assert_eq!(rsp.into_body(), "6");
 }
 
+#[cfg(feature = "json")]
+#[tokio::test]
+async fn test_rejection() {
+    let rsp = handle(path_request("/max", "[1, 2,\n 3,]")).await;
+    assert_eq!(rsp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(rsp.into_body(), "invalid JSON at 2:4");
+
+    let rsp = handle(path_request("/max", "[1, 2, 3]")).await;
+    assert_eq!(rsp.into_body(), "3");
+
+    let mut req = path_request("/max", "[1, 2, 3]");
+    *req.method_mut() = Method::PUT;
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::METHOD_NOT_ALLOWED);
+    assert_eq!(rsp.into_body(), "rejected");
+
+    let mut req = path_request("/max", "[1, 2, 3]");
+    req.headers_mut()
+        .insert("Content-Type", HeaderValue::from_static("text/plain"));
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    assert_eq!(rsp.into_body(), "rejected");
+}
+
+#[cfg(all(feature = "body-util", feature = "json"))]
+#[tokio::test]
+async fn test_body_bytes_rejection() {
+    let mut req = path_request("/item", &"x".repeat(2048));
+    *req.method_mut() = Method::DELETE;
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    assert_eq!(rsp.into_body(), "rejected");
+}
+
+#[cfg(feature = "body-util")]
+#[tokio::test]
+async fn test_method_override_form() {
+    let req = Request::builder()
+        .method(Method::POST)
+        .uri("https://example.com/item")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body("name=foo&_method=DELETE".into())
+        .unwrap();
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "DELETE name=foo&_method=DELETE");
+}
+
+#[cfg(feature = "body-util")]
+#[tokio::test]
+async fn test_many_form_fields() {
+    // Body extraction is only limited by the body size
+    let form = (0..1500).map(|i| format!("f{i}=x")).collect::<Vec<_>>();
+    let req = Request::builder()
+        .method(Method::PUT)
+        .uri("https://example.com/fields")
+        .header("Content-Type", "application/x-www-form-urlencoded")
+        .body(form.join("&").into())
+        .unwrap();
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.into_body(), "1500");
+}
+
+#[tokio::test]
+async fn test_trailers() {
+    let mut trailers = HeaderMap::new();
+    trailers.insert("x-checksum", HeaderValue::from_static("abc"));
+    let mut body = pin!(Body::from("data").with_trailers(trailers));
+
+    let frame = poll_fn(|cx| body.as_mut().poll_frame(cx)).await;
+    assert_eq!(frame.unwrap().unwrap().into_data().unwrap(), "data");
+    let frame = poll_fn(|cx| body.as_mut().poll_frame(cx)).await;
+    let trailers = frame.unwrap().unwrap().into_trailers().unwrap();
+    assert_eq!(trailers.get("x-checksum").unwrap(), "abc");
+    assert!(poll_fn(|cx| body.as_mut().poll_frame(cx)).await.is_none());
+}
+
 fn path_request(path: &str, body: &str) -> Request<Body> {
     Request::builder()
         .method(Method::POST)
@@ -38,11 +119,45 @@ impl Application for App {
     type Error = Error;
 
     async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        #[cfg(feature = "body-util")]
+        if let Err(e) = cx.override_method_from_form(1024).await {
+            return Error::Mendes(e).into_response(&cx.app, &cx.req);
+        }
+
         route!(match cx.path() {
             #[cfg(feature = "json")]
             Some("sum") => sum,
+            #[cfg(feature = "json")]
+            Some("max") => max,
+            #[cfg(feature = "body-util")]
+            Some("item") => item,
+            #[cfg(feature = "body-util")]
+            Some("fields") => fields,
         })
     }
+
+    #[cfg(feature = "json")]
+    fn rejection(error: mendes::Error, _: &Parts) -> Error {
+        use mendes::application::ErrorKind;
+        use std::error::Error as _;
+
+        let json = match error.kind() {
+            ErrorKind::BodyDecodeJson => error
+                .source()
+                .and_then(|e| e.downcast_ref::<serde_json::Error>()),
+            ErrorKind::MethodNotAllowed | ErrorKind::BodyUnknownType => {
+                return Error::Rejected(error.status())
+            }
+            #[cfg(feature = "body-util")]
+            ErrorKind::BodyTooLarge => return Error::Rejected(error.status()),
+            _ => None,
+        };
+
+        match json {
+            Some(e) => Error::Json(e.line(), e.column()),
+            None => Error::Mendes(error),
+        }
+    }
 }
 
 #[cfg(feature = "json")]
@@ -54,9 +169,42 @@ async fn sum(_: &App, req: &Parts, body: Body) -> Result<Response<String>, Error
         .unwrap())
 }
 
+#[cfg(feature = "json")]
+#[handler(POST, accepts = "application/json")]
+async fn max(_: &App, req: &Parts, body: Body) -> Result<Response<String>, Error> {
+    let numbers = App::from_body::<Vec<u32>>(req, body, 16).await?;
+    Ok(Response::builder()
+        .body(numbers.iter().max().unwrap().to_string())
+        .unwrap())
+}
+
+#[cfg(feature = "body-util")]
+#[handler(DELETE)]
+async fn item(_: &App, req: &Parts, body: Body) -> Result<Response<String>, Error> {
+    let bytes = App::body_bytes(req, body, 1024).await?;
+    Ok(Response::builder()
+        .body(format!(
+            "{} {}",
+            req.method,
+            String::from_utf8_lossy(&bytes)
+        ))
+        .unwrap())
+}
+
+#[cfg(feature = "body-util")]
+#[handler(PUT)]
+async fn fields(_: &App, req: &Parts, body: Body) -> Result<Response<String>, Error> {
+    let fields = App::from_body::<Vec<(String, String)>>(req, body, 16 * 1024).await?;
+    Ok(Response::builder().body(fields.len().to_string()).unwrap())
+}
+
 #[derive(Debug)]
 enum Error {
     Mendes(mendes::Error),
+    #[cfg(feature = "json")]
+    Json(usize, usize),
+    #[cfg(feature = "json")]
+    Rejected(StatusCode),
 }
 
 impl From<mendes::Error> for Error {
@@ -67,17 +215,26 @@ impl From<mendes::Error> for Error {
 
 impl From<&Error> for StatusCode {
     fn from(e: &Error) -> StatusCode {
-        let Error::Mendes(e) = e;
-        StatusCode::from(e)
+        match e {
+            Error::Mendes(e) => StatusCode::from(e),
+            #[cfg(feature = "json")]
+            Error::Json(..) => StatusCode::BAD_REQUEST,
+            #[cfg(feature = "json")]
+            Error::Rejected(status) => *status,
+        }
     }
 }
 
 impl IntoResponse<App> for Error {
     fn into_response(self, _: &App, _: &Parts) -> Response<String> {
-        let Error::Mendes(err) = self;
-        Response::builder()
-            .status(StatusCode::from(&err))
-            .body(err.to_string())
-            .unwrap()
+        let builder = Response::builder().status(StatusCode::from(&self));
+        match self {
+            Error::Mendes(err) => builder.body(err.to_string()),
+            #[cfg(feature = "json")]
+            Error::Json(line, column) => builder.body(format!("invalid JSON at {line}:{column}")),
+            #[cfg(feature = "json")]
+            Error::Rejected(_) => builder.body("rejected".to_owned()),
+        }
+        .unwrap()
     }
 }
@@ -0,0 +1,50 @@
+#![cfg(feature = "embed")]
+
+use bytes::Bytes;
+use mendes::application::ErrorKind;
+use mendes::embedded::{embed_dir, Embedded};
+use mendes::http::header::{CONTENT_TYPE, ETAG, IF_NONE_MATCH};
+use mendes::http::request::Parts;
+use mendes::http::{Request, StatusCode};
+
+static STATIC: Embedded = embed_dir!("tests/static");
+
+#[test]
+fn test_serve() {
+    let paths = STATIC.iter().map(|file| file.path).collect::<Vec<_>>();
+    assert_eq!(paths, ["css/app.css", "index.html"]);
+
+    let rsp = STATIC.serve::<Bytes>(&parts(None), "/css/app.css").unwrap();
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.headers()[CONTENT_TYPE], "text/css");
+    assert_eq!(rsp.body(), "body { color: red; }\n");
+    let etag = rsp.headers()[ETAG].to_str().unwrap().to_owned();
+    assert!(etag.starts_with('"') && etag.ends_with('"'));
+
+    let rsp = STATIC.serve::<Bytes>(&parts(None), "/").unwrap();
+    assert_eq!(rsp.body(), "<h1>Hello</h1>\n");
+
+    let req = parts(Some(&format!("\"other\", W/{etag}")));
+    let rsp = STATIC.serve::<Bytes>(&req, "css/app.css").unwrap();
+    assert_eq!(rsp.status(), StatusCode::NOT_MODIFIED);
+    assert_eq!(rsp.headers()[ETAG], etag.as_str());
+    assert!(rsp.body().is_empty());
+
+    let rsp = STATIC
+        .serve::<Bytes>(&parts(Some("\"other\"")), "index.html")
+        .unwrap();
+    assert_eq!(rsp.status(), StatusCode::OK);
+
+    let error = STATIC
+        .serve::<Bytes>(&parts(None), "missing.js")
+        .unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::FileNotFound);
+}
+
+fn parts(if_none_match: Option<&str>) -> Parts {
+    let mut builder = Request::builder().uri("/");
+    if let Some(value) = if_none_match {
+        builder = builder.header(IF_NONE_MATCH, value);
+    }
+    builder.body(()).unwrap().into_parts().0
+}
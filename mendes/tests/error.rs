@@ -0,0 +1,228 @@
+#![cfg(feature = "application")]
+
+use std::error::Error as _;
+use std::io;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use mendes::application::{ErrorKind, IntoResponse};
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::{handler, route, Application, Context, Error};
+use serde::Deserialize;
+
+#[tokio::test]
+async fn test_builder() {
+    let rsp = handle(path_request("/denied")).await;
+    assert_eq!(rsp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(rsp.into_body(), "no access to this page");
+}
+
+#[tokio::test]
+async fn test_internal() {
+    let rsp = handle(path_request("/broken")).await;
+    assert_eq!(rsp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(rsp.into_body(), "internal server error");
+}
+
+#[tokio::test]
+async fn test_builtin() {
+    let rsp = handle(path_request("/foo")).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(rsp.into_body(), "no matching routes");
+
+    let rsp = handle(path_request("/denied/more")).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn test_source_not_exposed() {
+    let rsp = handle(path_request("/count?n=many")).await;
+    assert_eq!(rsp.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(rsp.into_body(), "unable to decode request URI query");
+}
+
+#[tokio::test]
+async fn test_app_error() {
+    let rsp = CustomApp::handle(Context::new(Arc::new(CustomApp), path_request("/denied"))).await;
+    assert_eq!(rsp.status(), StatusCode::FORBIDDEN);
+    assert_eq!(rsp.into_body(), "custom: no access to this page");
+
+    let rsp = CustomApp::handle(Context::new(Arc::new(CustomApp), path_request("/foo"))).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(rsp.into_body(), "custom: no matching routes");
+}
+
+#[tokio::test]
+async fn test_optional_rejection() {
+    let rsp = CustomApp::handle(Context::new(Arc::new(CustomApp), path_request("/optional"))).await;
+    assert_eq!(rsp.into_body(), "None");
+    // The error was discarded, so the hook didn't see it
+    assert!(!REJECTED.lock().unwrap().iter().any(|p| p == "/optional"));
+
+    // A path component that doesn't parse is still rejected, through the hook
+    let req = path_request("/optional/many");
+    let rsp = CustomApp::handle(Context::new(Arc::new(CustomApp), req)).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(rsp.into_body(), "custom: unable to parse path component");
+    assert!(REJECTED
+        .lock()
+        .unwrap()
+        .iter()
+        .any(|p| p == "/optional/many"));
+}
+
+#[cfg(feature = "debug")]
+#[tokio::test]
+async fn test_debug() {
+    let app = Arc::new(App { debug: true });
+    let rsp = App::handle(Context::new(app.clone(), path_request("/broken"))).await;
+    assert_eq!(rsp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(rsp.headers()["content-type"], "text/html; charset=utf-8");
+    let body = rsp.into_body();
+    assert!(body.contains("<li>database unavailable</li>"), "{body}");
+    assert!(body.contains("<code>error::broken</code>"), "{body}");
+
+    // Client errors are not affected
+    let rsp = App::handle(Context::new(app, path_request("/denied"))).await;
+    assert_eq!(rsp.into_body(), "no access to this page");
+}
+
+#[test]
+fn test_context() {
+    let error = Error::internal(io::Error::new(io::ErrorKind::Other, "disk on fire"))
+        .with_extension(Retry(3));
+    assert_eq!(error.kind(), ErrorKind::Other);
+    assert_eq!(error.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(error.source().unwrap().to_string(), "disk on fire");
+    assert_eq!(error.extensions().get::<Retry>(), Some(&Retry(3)));
+
+    let error = Error::from(ErrorKind::PathParse);
+    assert_eq!(StatusCode::from(&error), StatusCode::NOT_FOUND);
+    assert_eq!(error.to_string(), "unable to parse path component");
+}
+
+#[derive(Clone, Debug, PartialEq)]
+struct Retry(u8);
+
+fn path_request(path: &str) -> Request<()> {
+    Request::builder()
+        .uri(format!("https://example.com{path}"))
+        .body(())
+        .unwrap()
+}
+
+async fn handle(req: Request<()>) -> Response<String> {
+    App::handle(Context::new(Arc::new(App { debug: false }), req)).await
+}
+
+struct App {
+    debug: bool,
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = String;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("denied") => match cx.path() {
+                None => denied,
+            },
+            Some("broken") => broken,
+            Some("count") => count,
+        })
+    }
+
+    fn debug(&self) -> bool {
+        self.debug
+    }
+}
+
+#[handler(GET)]
+async fn denied(_: &App) -> Result<Response<String>, Error> {
+    Err(Error::forbidden("no access to this page"))
+}
+
+#[handler(GET)]
+async fn broken(_: &App) -> Result<Response<String>, Error> {
+    Err(Error::internal(io::Error::new(
+        io::ErrorKind::Other,
+        "database unavailable",
+    )))
+}
+
+#[handler(GET)]
+async fn count(_: &App, #[query] query: Count) -> Result<Response<String>, Error> {
+    Ok(Response::new(query.n.to_string()))
+}
+
+#[derive(Deserialize)]
+struct Count {
+    n: usize,
+}
+
+struct CustomApp;
+
+#[async_trait]
+impl Application for CustomApp {
+    type RequestBody = ();
+    type ResponseBody = String;
+    type Error = CustomError;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("denied") => custom_denied,
+            Some("optional") => custom_optional,
+        })
+    }
+
+    fn rejection(error: Error, req: &Parts) -> CustomError {
+        REJECTED.lock().unwrap().push(req.uri.path().to_owned());
+        CustomError(error)
+    }
+}
+
+/// Paths of the requests that errors passed through `CustomApp::rejection()` for
+static REJECTED: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+#[handler(GET)]
+async fn custom_denied(_: &CustomApp) -> Result<Response<String>, Error> {
+    Err(Error::forbidden("no access to this page"))
+}
+
+#[handler(GET)]
+async fn custom_optional(_: &CustomApp, num: Option<usize>) -> Result<Response<String>, Error> {
+    Ok(Response::new(format!("{num:?}")))
+}
+
+struct CustomError(Error);
+
+impl From<Error> for CustomError {
+    fn from(e: Error) -> Self {
+        Self(e)
+    }
+}
+
+impl From<CustomError> for Error {
+    fn from(e: CustomError) -> Self {
+        e.0
+    }
+}
+
+impl From<&CustomError> for StatusCode {
+    fn from(e: &CustomError) -> StatusCode {
+        e.0.status()
+    }
+}
+
+impl IntoResponse<CustomApp> for CustomError {
+    fn into_response(self, _: &CustomApp, _: &Parts) -> Response<String> {
+        Response::builder()
+            .status(self.0.status())
+            .body(format!("custom: {}", self.0.message()))
+            .unwrap()
+    }
+}
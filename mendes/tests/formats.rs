@@ -0,0 +1,160 @@
+#![cfg(all(feature = "cbor", feature = "msgpack", feature = "xml"))]
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use http_body_util::BodyExt;
+use mendes::cbor::{self, Cbor};
+use mendes::http::header::{ACCEPT, CONTENT_TYPE};
+use mendes::http::{Method, Request, Response, StatusCode};
+use mendes::msgpack::{self, MsgPack};
+use mendes::xml::Xml;
+use mendes::{handler, route, Application, Body, Context, Error};
+use serde::{Deserialize, Serialize};
+
+#[tokio::test]
+async fn xml() {
+    let order = r#"<Order id="3"><item>tea</item><item>scones</item></Order>"#;
+    let rsp = handle(request("application/xml", None, order)).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(
+        rsp.headers()[CONTENT_TYPE],
+        "application/xml; charset=utf-8"
+    );
+    assert_eq!(
+        body(rsp).await,
+        r#"<?xml version="1.0" encoding="UTF-8"?><Order id="4"><item>tea</item><item>scones</item></Order>"#
+    );
+
+    let rsp = handle(request("text/xml", Some("text/xml"), order)).await;
+    assert_eq!(rsp.headers()[CONTENT_TYPE], "text/xml; charset=utf-8");
+
+    let rsp = handle(request("application/xml", Some("application/json"), order)).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_ACCEPTABLE);
+
+    let rsp = handle(request("application/json", None, order)).await;
+    assert_eq!(rsp.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+
+    let rsp = handle(request("application/xml", None, "<Order id=\"x\"/>")).await;
+    assert_eq!(rsp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn msgpack() {
+    let order = msgpack::to_vec(&Order::default()).unwrap();
+    let req = binary_request("/msgpack", "application/x-msgpack", None, order);
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.headers()[CONTENT_TYPE], "application/msgpack");
+    let order = msgpack::from_slice::<Order>(&bytes(rsp).await).unwrap();
+    assert_eq!(order.id, 1);
+
+    let req = binary_request("/msgpack", "application/cbor", None, vec![0x80]);
+    assert_eq!(
+        handle(req).await.status(),
+        StatusCode::UNSUPPORTED_MEDIA_TYPE
+    );
+
+    let req = binary_request("/msgpack", "application/msgpack", None, vec![0xc1]);
+    assert_eq!(handle(req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn cbor() {
+    let order = cbor::to_vec(&Order::default()).unwrap();
+    let req = binary_request("/cbor", "application/cbor", Some("application/*"), order);
+    let rsp = handle(req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.headers()[CONTENT_TYPE], "application/cbor");
+    let order = cbor::from_slice::<Order>(&bytes(rsp).await).unwrap();
+    assert_eq!(order.id, 1);
+
+    let order = cbor::to_vec(&Order::default()).unwrap();
+    let req = binary_request("/cbor", "application/cbor", Some("text/*"), order);
+    assert_eq!(handle(req).await.status(), StatusCode::NOT_ACCEPTABLE);
+
+    let req = binary_request("/cbor", "application/cbor", None, vec![0xa1]);
+    assert_eq!(handle(req).await.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+async fn body(rsp: Response<Body>) -> String {
+    String::from_utf8(bytes(rsp).await).unwrap()
+}
+
+async fn bytes(rsp: Response<Body>) -> Vec<u8> {
+    let bytes = rsp.into_body().collect().await.unwrap().to_bytes();
+    bytes.to_vec()
+}
+
+fn request(content_type: &str, accept: Option<&str>, body: &str) -> Request<Body> {
+    binary_request("/order", content_type, accept, body.as_bytes().to_vec())
+}
+
+fn binary_request(
+    path: &str,
+    content_type: &str,
+    accept: Option<&str>,
+    body: Vec<u8>,
+) -> Request<Body> {
+    let mut req = Request::builder()
+        .method(Method::POST)
+        .uri(format!("https://example.com{path}"))
+        .header(CONTENT_TYPE, content_type);
+    if let Some(accept) = accept {
+        req = req.header(ACCEPT, accept);
+    }
+    req.body(body.into()).unwrap()
+}
+
+async fn handle(req: Request<Body>) -> Response<Body> {
+    App::handle(Context::new(Arc::new(App {}), req)).await
+}
+
+struct App {}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = Body;
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("order") => order,
+            Some("msgpack") => order_msgpack,
+            Some("cbor") => order_cbor,
+        })
+    }
+}
+
+#[handler(POST)]
+async fn order(_: &App, #[async_extract] order: Xml<Order>) -> Result<Xml<Order>, Error> {
+    let Xml(mut order) = order;
+    order.id += 1;
+    Ok(Xml(order))
+}
+
+#[handler(POST)]
+async fn order_msgpack(
+    _: &App,
+    #[async_extract] order: MsgPack<Order>,
+) -> Result<MsgPack<Order>, Error> {
+    let MsgPack(mut order) = order;
+    order.id += 1;
+    Ok(MsgPack(order))
+}
+
+#[handler(POST)]
+async fn order_cbor(_: &App, #[async_extract] order: Cbor<Order>) -> Result<Cbor<Order>, Error> {
+    let Cbor(mut order) = order;
+    order.id += 1;
+    Ok(Cbor(order))
+}
+
+#[derive(Default, Deserialize, Serialize)]
+struct Order {
+    #[serde(rename = "@id")]
+    id: u64,
+    #[serde(rename = "item")]
+    items: Vec<String>,
+}
@@ -3,6 +3,7 @@
 use std::borrow::Cow;
 
 use mendes::forms::{form, ToField, ToForm};
+use mendes::html::Markup;
 use serde::{Deserialize, Serialize};
 
 #[test]
@@ -13,6 +14,19 @@ fn test_generate() {
     assert!(!html.contains("skipped"));
 }
 
+#[test]
+fn test_escaping() {
+    let form = SomeForm::to_form();
+    let form = form.set("name", r#""><script>alert(1)</script>"#).unwrap();
+    let html = form.to_string();
+    assert!(!html.contains("<script>"));
+    assert!(html.contains(r#"value="&quot;&gt;&lt;script&gt;alert(1)&lt;/script&gt;""#));
+
+    let mut markup = Markup::new();
+    markup.push("<h1>").push(&form);
+    assert_eq!(markup.as_str(), format!("&lt;h1&gt;{html}"));
+}
+
 #[test]
 fn test_roundtrip() {
     let obj = SomeForm {
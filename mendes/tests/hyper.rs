@@ -1,17 +1,21 @@
 #![cfg(feature = "hyper")]
 
 use std::fmt::{self, Display};
-use std::io;
+use std::future::poll_fn;
+use std::io::{self, BufRead, BufReader, Read, Write};
 use std::net::SocketAddr;
+use std::pin::Pin;
 use std::time::Duration;
 
 use async_trait::async_trait;
 use bytes::Bytes;
-use mendes::application::IntoResponse;
+use mendes::application::{ErrorKind, IntoResponse};
 use mendes::http::request::Parts;
 use mendes::http::{Response, StatusCode};
-use mendes::hyper::body::Incoming;
+use mendes::hyper::body::{Body as _, Incoming};
 use mendes::hyper::{ClientAddr, Server};
+#[cfg(feature = "websocket")]
+use mendes::websocket::WebSocketUpgrade;
 use mendes::{handler, route, Application, Body, Context};
 use tokio::task::JoinHandle;
 use tokio::time::sleep;
@@ -54,6 +58,180 @@ async fn test_client_addr() {
     runner.stop();
 }
 
+#[tokio::test]
+async fn test_panic() {
+    let addr = "127.0.0.1:12347".parse::<SocketAddr>().unwrap();
+    let runner = ServerRunner::run(addr).await;
+
+    let client = reqwest::Client::new();
+    let rsp = client
+        .get(format!("http://{addr}/panic"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rsp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(rsp.text().await.unwrap(), "Caught panic");
+
+    // The connection survives the panic
+    let rsp = client
+        .get(format!("http://{addr}/client-addr"))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rsp.status(), StatusCode::OK);
+
+    runner.stop();
+}
+
+#[tokio::test]
+async fn test_expect_continue() {
+    let addr = "127.0.0.1:12348".parse::<SocketAddr>().unwrap();
+    let runner = ServerRunner::run(addr).await;
+
+    let request = |content_length: usize, expect: &'static str| {
+        tokio::task::spawn_blocking(move || {
+            let mut stream = std::net::TcpStream::connect(addr).unwrap();
+            write!(
+                stream,
+                "POST /upload HTTP/1.1\r\nHost: {addr}\r\nContent-Length: {content_length}\r\n\
+                 Expect: {expect}\r\nConnection: close\r\n\r\n"
+            )
+            .unwrap();
+
+            let mut reader = BufReader::new(stream.try_clone().unwrap());
+            let mut status = String::new();
+            reader.read_line(&mut status).unwrap();
+            if status.starts_with("HTTP/1.1 100") {
+                // Skip the empty line, then send the body
+                reader.read_line(&mut String::new()).unwrap();
+                stream.write_all(&vec![b'a'; content_length]).unwrap();
+                let mut rest = String::new();
+                reader.read_to_string(&mut rest).unwrap();
+                status.push_str(&rest);
+            }
+            status
+        })
+    };
+
+    let rsp = request(1024 * 1024, "100-continue").await.unwrap();
+    assert!(rsp.starts_with("HTTP/1.1 413"), "{rsp}");
+
+    let rsp = request(8, "100-continue").await.unwrap();
+    assert!(rsp.starts_with("HTTP/1.1 100"), "{rsp}");
+    assert!(rsp.contains("HTTP/1.1 200"), "{rsp}");
+    assert!(rsp.ends_with("received 8 bytes"), "{rsp}");
+
+    let rsp = request(8, "something-else").await.unwrap();
+    assert!(rsp.starts_with("HTTP/1.1 417"), "{rsp}");
+
+    runner.stop();
+}
+
+#[cfg(feature = "websocket")]
+#[tokio::test]
+async fn test_websocket() {
+    let addr = "127.0.0.1:12349".parse::<SocketAddr>().unwrap();
+    let runner = ServerRunner::run(addr).await;
+
+    let rsp = tokio::task::spawn_blocking(move || {
+        let mut stream = std::net::TcpStream::connect(addr).unwrap();
+        write!(
+            stream,
+            "GET /echo HTTP/1.1\r\nHost: {addr}\r\nConnection: Upgrade\r\nUpgrade: websocket\r\n\
+             Sec-WebSocket-Version: 13\r\nSec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n"
+        )
+        .unwrap();
+
+        let mut reader = BufReader::new(stream.try_clone().unwrap());
+        let mut head = String::new();
+        while !head.ends_with("\r\n\r\n") {
+            reader.read_line(&mut head).unwrap();
+        }
+
+        // A masked text frame with a zero mask
+        stream
+            .write_all(&[0x81, 0x82, 0, 0, 0, 0, b'h', b'i'])
+            .unwrap();
+        let mut frame = [0; 4];
+        reader.read_exact(&mut frame).unwrap();
+        (head, frame)
+    })
+    .await
+    .unwrap();
+
+    let (head, frame) = rsp;
+    assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+    assert!(head
+        .to_ascii_lowercase()
+        .contains("sec-websocket-accept: s3pplmbitxaq9kygzzhzrbk+xoo="));
+    assert_eq!(frame, [0x81, 2, b'h', b'i']);
+
+    runner.stop();
+}
+
+#[cfg(feature = "grpc")]
+#[tokio::test]
+async fn test_grpc_dispatch() {
+    let addr = "127.0.0.1:12346".parse::<SocketAddr>().unwrap();
+    let server = Server::bind(addr, App::default()).await.unwrap();
+    let handle = tokio::spawn(server.with_grpc(Grpc).serve());
+    sleep(Duration::from_millis(10)).await;
+
+    let client = reqwest::Client::new();
+    let rsp = client
+        .post(format!("http://{addr}/client-addr"))
+        .header("content-type", "application/grpc+proto")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.text().await.unwrap(), "grpc");
+
+    let rsp = client
+        .post(format!("http://{addr}/fail"))
+        .header("content-type", "application/grpc")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.headers()["content-type"], "application/grpc");
+    assert_eq!(rsp.headers()["grpc-status"], "13");
+    assert_eq!(rsp.text().await.unwrap(), "");
+
+    let rsp = reqwest::get(format!("http://{addr}/client-addr"))
+        .await
+        .unwrap();
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(rsp.text().await.unwrap(), "client_addr: 127.0.0.1");
+
+    handle.abort();
+}
+
+#[cfg(feature = "grpc")]
+#[derive(Clone)]
+struct Grpc;
+
+#[cfg(feature = "grpc")]
+impl tower_service::Service<mendes::http::Request<Incoming>> for Grpc {
+    type Response = Response<http_body_util::Full<Bytes>>;
+    type Error = io::Error;
+    type Future = std::future::Ready<Result<Self::Response, Self::Error>>;
+
+    fn poll_ready(
+        &mut self,
+        _: &mut std::task::Context<'_>,
+    ) -> std::task::Poll<Result<(), Self::Error>> {
+        std::task::Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, req: mendes::http::Request<Incoming>) -> Self::Future {
+        std::future::ready(match req.uri().path() {
+            "/fail" => Err(io::Error::new(io::ErrorKind::Other, "failed")),
+            _ => Ok(Response::new("grpc".into())),
+        })
+    }
+}
+
 #[derive(Default)]
 struct App {}
 
@@ -66,6 +244,10 @@ impl Application for App {
     async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
         route!(match cx.path() {
             Some("client-addr") => client_addr,
+            Some("panic") => panic,
+            Some("upload") => upload,
+            #[cfg(feature = "websocket")]
+            Some("echo") => echo,
         })
     }
 }
@@ -81,6 +263,41 @@ async fn client_addr(_: &App, client_addr: ClientAddr) -> Result<Response<Body>,
         .unwrap())
 }
 
+#[handler(GET)]
+async fn panic(_: &App) -> Result<Response<Body>, Error> {
+    panic!("handler failed")
+}
+
+#[handler(POST)]
+async fn upload(_: &App, mut body: Incoming) -> Result<Response<Body>, Error> {
+    if body.size_hint().upper().unwrap_or(u64::MAX) > 16 {
+        return Err(mendes::Error::from(ErrorKind::BodyTooLarge).into());
+    }
+
+    let mut len = 0;
+    while let Some(frame) = poll_fn(|cx| Pin::new(&mut body).poll_frame(cx)).await {
+        if let Ok(data) = frame.unwrap().into_data() {
+            len += data.len();
+        }
+    }
+
+    Ok(Response::new(Body::from(Bytes::from(format!(
+        "received {len} bytes"
+    )))))
+}
+
+#[cfg(feature = "websocket")]
+#[handler(GET)]
+async fn echo(_: &App, ws: WebSocketUpgrade) -> Result<Response<Body>, Error> {
+    Ok(ws.on_upgrade(|mut socket| async move {
+        while let Ok(Some(message)) = socket.recv().await {
+            if socket.send(message).await.is_err() {
+                break;
+            }
+        }
+    }))
+}
+
 #[derive(Debug)]
 enum Error {
     Mendes(mendes::Error),
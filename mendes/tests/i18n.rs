@@ -0,0 +1,114 @@
+#![cfg(feature = "i18n")]
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mendes::application::IntoResponse;
+use mendes::http::header::ACCEPT_LANGUAGE;
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::i18n::{AppWithCatalogs, Catalog, Catalogs, Locale};
+use mendes::{handler, route, Application, Context};
+
+#[tokio::test]
+async fn negotiate() {
+    let rsp = handle(request(Some("nl-NL,nl;q=0.9,en;q=0.5"))).await;
+    assert_eq!(rsp.into_body(), "Hallo, Piet! (nl)");
+
+    let rsp = handle(request(Some("fr-CH, fr;q=0.9, en-US;q=0.8"))).await;
+    assert_eq!(rsp.into_body(), "Hello, Piet! (en)");
+
+    let rsp = handle(request(Some("de, nl;q=0"))).await;
+    assert_eq!(rsp.into_body(), "Hello, Piet! (en)");
+
+    let rsp = handle(request(None)).await;
+    assert_eq!(rsp.into_body(), "Hello, Piet! (en)");
+}
+
+#[test]
+fn catalog() {
+    let catalog = Catalog::parse(
+        "# Comment\nhello = Hello, { $name }!\nmultiline =\n    First line\n    second line\n",
+    )
+    .unwrap();
+    assert_eq!(catalog.get("hello"), Some("Hello, { $name }!"));
+    assert_eq!(catalog.get("multiline"), Some("First line\nsecond line"));
+    assert!(Catalog::parse("no equals sign").is_err());
+}
+
+fn request(accept: Option<&str>) -> Request<()> {
+    let mut builder = Request::builder().uri("https://example.com/hello");
+    if let Some(accept) = accept {
+        builder = builder.header(ACCEPT_LANGUAGE, accept);
+    }
+    builder.body(()).unwrap()
+}
+
+async fn handle(req: Request<()>) -> Response<String> {
+    let mut catalogs = Catalogs::new(
+        "en",
+        Catalog::parse("hello = Hello, { $name }!\nsuffix = en").unwrap(),
+    );
+    catalogs.insert("nl", Catalog::parse("hello = Hallo, { $name }!").unwrap());
+    App::handle(Context::new(Arc::new(App { catalogs }), req)).await
+}
+
+struct App {
+    catalogs: Catalogs,
+}
+
+impl AppWithCatalogs for App {
+    fn catalogs(&self) -> &Catalogs {
+        &self.catalogs
+    }
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = String;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("hello") => hello,
+        })
+    }
+}
+
+#[handler(GET)]
+async fn hello(_: &App, locale: Locale<'_>) -> Result<Response<String>, Error> {
+    let greeting = locale.format("hello", &[("name", &"Piet")]);
+    Ok(Response::builder()
+        .status(StatusCode::OK)
+        .body(format!("{greeting} ({})", locale.tag()))
+        .unwrap())
+}
+
+#[derive(Debug)]
+enum Error {
+    Mendes(mendes::Error),
+}
+
+impl From<mendes::Error> for Error {
+    fn from(e: mendes::Error) -> Self {
+        Error::Mendes(e)
+    }
+}
+
+impl From<&Error> for StatusCode {
+    fn from(e: &Error) -> StatusCode {
+        let Error::Mendes(e) = e;
+        StatusCode::from(e)
+    }
+}
+
+impl IntoResponse<App> for Error {
+    fn into_response(self, _: &App, _: &Parts) -> Response<String> {
+        let Error::Mendes(err) = self;
+        Response::builder()
+            .status(StatusCode::from(&err))
+            .body(err.to_string())
+            .unwrap()
+    }
+}
@@ -0,0 +1,219 @@
+#![cfg(all(feature = "idempotency", feature = "body-util"))]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use mendes::http::{Method, Request, Response, StatusCode};
+use mendes::idempotency::{Idempotency, MemoryStore};
+use mendes::{handler, route, Application, Body, Context, Error};
+use tokio::task::JoinHandle;
+
+#[tokio::test]
+async fn test_replay() {
+    let app = app(Duration::from_secs(60));
+
+    let rsp = handle(&app, request(Method::POST, "/payments", Some("abc"))).await;
+    assert_eq!(rsp.status(), StatusCode::CREATED);
+    assert!(!rsp.headers().contains_key("idempotent-replayed"));
+    assert_eq!(body(rsp).await, "payment 1");
+
+    let rsp = handle(&app, request(Method::POST, "/payments", Some("abc"))).await;
+    assert_eq!(rsp.status(), StatusCode::CREATED);
+    assert_eq!(rsp.headers()["idempotent-replayed"], "true");
+    assert_eq!(body(rsp).await, "payment 1");
+    assert_eq!(app.created.load(Ordering::SeqCst), 1);
+
+    let rsp = handle(&app, request(Method::POST, "/payments", Some("def"))).await;
+    assert_eq!(body(rsp).await, "payment 2");
+
+    let rsp = handle(&app, request(Method::POST, "/payments", None)).await;
+    assert_eq!(body(rsp).await, "payment 3");
+
+    let rsp = handle(&app, request(Method::POST, "/refunds", Some("abc"))).await;
+    assert_eq!(rsp.status(), StatusCode::UNPROCESSABLE_ENTITY);
+}
+
+#[tokio::test]
+async fn test_server_error() {
+    let app = app(Duration::from_secs(60));
+
+    for _ in 0..2 {
+        let rsp = handle(&app, request(Method::POST, "/broken", Some("abc"))).await;
+        assert_eq!(rsp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert!(!rsp.headers().contains_key("idempotent-replayed"));
+    }
+}
+
+#[tokio::test]
+async fn test_concurrent() {
+    let app = app(Duration::from_secs(60));
+    let requests = (0..4)
+        .map(|_| {
+            let app = app.clone();
+            tokio::spawn(
+                async move { handle(&app, request(Method::POST, "/slow", Some("abc"))).await },
+            )
+        })
+        .collect::<Vec<_>>();
+
+    // Only one request runs the handler, the others conflict with it
+    started(&app, 1).await;
+    app.open.store(true, Ordering::SeqCst);
+    let mut statuses = Vec::new();
+    for request in requests {
+        statuses.push(request.await.unwrap().status());
+    }
+    statuses.sort();
+    assert_eq!(
+        statuses,
+        [
+            StatusCode::CREATED,
+            StatusCode::CONFLICT,
+            StatusCode::CONFLICT,
+            StatusCode::CONFLICT
+        ]
+    );
+
+    let rsp = handle(&app, request(Method::POST, "/slow", Some("abc"))).await;
+    assert_eq!(rsp.headers()["idempotent-replayed"], "true");
+    assert_eq!(body(rsp).await, "slow 1");
+    assert_eq!(app.created.load(Ordering::SeqCst), 1);
+}
+
+#[tokio::test]
+async fn test_abandoned() {
+    let app = app(Duration::from_secs(60));
+
+    // A cancelled request releases its key
+    let leader = spawn(&app, "abc");
+    started(&app, 1).await;
+    let rsp = handle(&app, request(Method::POST, "/slow", Some("abc"))).await;
+    assert_eq!(rsp.status(), StatusCode::CONFLICT);
+    leader.abort();
+    assert!(leader.await.is_err_and(|e| e.is_cancelled()));
+
+    app.open.store(true, Ordering::SeqCst);
+    let rsp = handle(&app, request(Method::POST, "/slow", Some("abc"))).await;
+    assert_eq!(rsp.status(), StatusCode::CREATED);
+    assert_eq!(body(rsp).await, "slow 2");
+
+    // So does a request whose handler panics
+    let rsp = spawn(&app, "def").await;
+    assert!(rsp.is_err_and(|e| e.is_panic()));
+    let rsp = handle(&app, request(Method::POST, "/slow", Some("def"))).await;
+    assert_eq!(rsp.status(), StatusCode::CREATED);
+    assert_eq!(body(rsp).await, "slow 4");
+}
+
+#[tokio::test]
+async fn test_expiry() {
+    let app = app(Duration::from_millis(20));
+    app.open.store(true, Ordering::SeqCst);
+    let rsp = handle(&app, request(Method::POST, "/payments", Some("abc"))).await;
+    assert_eq!(body(rsp).await, "payment 1");
+
+    let rsp = handle(&app, request(Method::POST, "/payments", Some("abc"))).await;
+    assert_eq!(body(rsp).await, "payment 1");
+
+    std::thread::sleep(Duration::from_millis(30));
+    let rsp = handle(&app, request(Method::POST, "/payments", Some("abc"))).await;
+    assert!(!rsp.headers().contains_key("idempotent-replayed"));
+    assert_eq!(body(rsp).await, "payment 2");
+}
+
+fn app(ttl: Duration) -> Arc<App> {
+    Arc::new(App {
+        idempotency: Idempotency::new(MemoryStore::new(), ttl),
+        created: AtomicUsize::new(0),
+        open: AtomicBool::new(false),
+    })
+}
+
+fn spawn(app: &Arc<App>, key: &'static str) -> JoinHandle<Response<Body>> {
+    let app = app.clone();
+    tokio::spawn(async move { handle(&app, request(Method::POST, "/slow", Some(key))).await })
+}
+
+/// Wait until the handler was called `n` times
+async fn started(app: &App, n: usize) {
+    while app.created.load(Ordering::SeqCst) < n {
+        tokio::task::yield_now().await;
+    }
+}
+
+fn request(method: Method, path: &str, key: Option<&str>) -> Request<()> {
+    let mut builder = Request::builder()
+        .method(method)
+        .uri(format!("https://example.com{path}"));
+    if let Some(key) = key {
+        builder = builder.header("idempotency-key", key);
+    }
+    builder.body(()).unwrap()
+}
+
+async fn handle(app: &Arc<App>, req: Request<()>) -> Response<Body> {
+    App::handle(Context::new(app.clone(), req)).await
+}
+
+async fn body(rsp: Response<Body>) -> String {
+    let body = rsp.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+struct App {
+    idempotency: Idempotency<MemoryStore>,
+    created: AtomicUsize,
+    /// Whether `slow` requests may complete
+    open: AtomicBool,
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(cx: Context<Self>) -> Response<Self::ResponseBody> {
+        let app = cx.app.clone();
+        app.idempotency
+            .handle(cx, |mut cx| async move {
+                route!(match cx.path() {
+                    Some("payments") => payments,
+                    Some("refunds") => payments,
+                    Some("broken") => broken,
+                    Some("slow") => slow,
+                })
+            })
+            .await
+    }
+}
+
+#[handler(POST, status = 201)]
+async fn payments(app: &App) -> Result<Response<Body>, Error> {
+    let id = app.created.fetch_add(1, Ordering::SeqCst) + 1;
+    Ok(Response::new(Body::from(Bytes::from(format!(
+        "payment {id}"
+    )))))
+}
+
+#[handler(POST)]
+async fn broken(_: &App) -> Result<Response<Body>, Error> {
+    Err(Error::internal("database unavailable"))
+}
+
+#[handler(POST, status = 201)]
+async fn slow(app: &App) -> Result<Response<Body>, Error> {
+    let id = app.created.fetch_add(1, Ordering::SeqCst) + 1;
+    while !app.open.load(Ordering::SeqCst) {
+        tokio::task::yield_now().await;
+    }
+    if id == 3 {
+        panic!("slow request failed");
+    }
+
+    Ok(Response::new(Body::from(Bytes::from(format!("slow {id}")))))
+}
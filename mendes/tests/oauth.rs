@@ -0,0 +1,212 @@
+#![cfg(all(feature = "oauth", feature = "body-util"))]
+
+use std::convert::TryInto;
+use std::error::Error as StdError;
+use std::sync::{Arc, Mutex};
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use mendes::cookies::{AppWithAeadKey, Key};
+use mendes::http::header::{COOKIE, LOCATION, SET_COOKIE};
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::oauth::{Error as OAuthError, HttpClient, OAuth, Provider, User};
+use mendes::{handler, route, Application, Body, Context, Error};
+
+#[tokio::test]
+async fn test_sign_in() {
+    let app = App::new();
+
+    let rsp = handle(&app, "/login/github", None).await;
+    assert_eq!(rsp.status(), StatusCode::FOUND);
+    let location = rsp.headers()[LOCATION].to_str().unwrap();
+    assert!(location.starts_with("https://github.com/login/oauth/authorize?"));
+    assert!(location.contains("code_challenge_method=S256"));
+    let state = location
+        .split(['?', '&'])
+        .find_map(|pair| pair.strip_prefix("state="))
+        .unwrap()
+        .to_owned();
+    let flow = cookie(&rsp, "OAuthFlow");
+
+    // A callback with the wrong state is rejected
+    let rsp = handle(&app, "/callback?code=abc&state=wrong", Some(&flow)).await;
+    assert_eq!(rsp.status(), StatusCode::BAD_REQUEST);
+
+    let path = format!("/callback?code=abc&state={state}");
+    let rsp = handle(&app, &path, Some(&flow)).await;
+    assert_eq!(rsp.status(), StatusCode::FOUND);
+    assert_eq!(rsp.headers()[LOCATION], "/me");
+    let user = cookie(&rsp, "User");
+
+    let sent = app.client.sent.lock().unwrap().clone();
+    assert_eq!(sent[0].0, "https://github.com/login/oauth/access_token");
+    assert!(sent[0].1.contains("code_verifier="));
+    assert_eq!(sent[1].0, "https://api.github.com/user");
+
+    let rsp = handle(&app, "/me", Some(&user)).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(body(rsp).await, "octocat (42)");
+
+    let rsp = handle(&app, "/me", None).await;
+    assert_eq!(rsp.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(body(rsp).await, "sign-in required");
+}
+
+#[test]
+fn test_return_path() {
+    let app = App::new();
+    let rsp = app.oauth.logout::<_, Body>(&*app, "/").unwrap();
+    assert_eq!(rsp.headers()[LOCATION], "/");
+
+    for path in [
+        "https://evil.com",
+        "//evil.com",
+        "/\\evil.com",
+        "/\t/evil.com",
+    ] {
+        assert!(matches!(
+            app.oauth.login::<_, Body>(&*app, "github", path),
+            Err(OAuthError::InvalidReturnPath)
+        ));
+        assert!(matches!(
+            app.oauth.logout::<_, Body>(&*app, path),
+            Err(OAuthError::InvalidReturnPath)
+        ));
+    }
+}
+
+#[test]
+fn test_errors() {
+    // Details from the callback request are not passed on to the client
+    let error = Error::from(OAuthError::Denied("<script>".to_owned()));
+    assert_eq!(error.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(error.message(), "sign-in failed");
+
+    let error = Error::from(OAuthError::Status(StatusCode::SERVICE_UNAVAILABLE));
+    assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(error.message(), "sign-in provider unavailable");
+
+    let error = Error::from(OAuthError::Transport("connection reset".into()));
+    assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+    assert_eq!(
+        error.source().unwrap().to_string(),
+        "request to provider failed"
+    );
+}
+
+async fn handle(app: &Arc<App>, path: &str, cookie: Option<&str>) -> Response<Body> {
+    let mut req = Request::builder()
+        .uri(format!("https://example.com{path}"))
+        .body(())
+        .unwrap();
+    if let Some(cookie) = cookie {
+        req.headers_mut().insert(COOKIE, cookie.try_into().unwrap());
+    }
+    App::handle(Context::new(app.clone(), req)).await
+}
+
+fn cookie(rsp: &Response<Body>, name: &str) -> String {
+    rsp.headers()
+        .get_all(SET_COOKIE)
+        .iter()
+        .map(|value| value.to_str().unwrap().split(';').next().unwrap())
+        .find(|value| value.starts_with(&format!("{name}=")) && !value.ends_with("=None"))
+        .unwrap()
+        .to_owned()
+}
+
+async fn body(rsp: Response<Body>) -> String {
+    let body = rsp.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+struct App {
+    key: Key,
+    oauth: OAuth<Arc<MockClient>>,
+    client: Arc<MockClient>,
+}
+
+impl App {
+    fn new() -> Arc<Self> {
+        let client = Arc::new(MockClient::default());
+        let github = Provider::github(
+            "client".to_owned(),
+            "secret".to_owned(),
+            "https://example.com/callback".to_owned(),
+        );
+
+        Arc::new(App {
+            key: Key::new(&[7; 32]),
+            oauth: OAuth::new(client.clone()).provider(github),
+            client,
+        })
+    }
+}
+
+impl AppWithAeadKey for App {
+    fn key(&self) -> &Key {
+        &self.key
+    }
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("login") => login,
+            Some("callback") => callback,
+            Some("me") => me,
+        })
+    }
+}
+
+#[handler(GET)]
+async fn login(app: &App, provider: String) -> Result<Response<Body>, Error> {
+    Ok(app.oauth.login(app, &provider, "/me")?)
+}
+
+#[handler(GET)]
+async fn callback(app: &App, req: &Parts) -> Result<Response<Body>, Error> {
+    Ok(app.oauth.callback(app, req).await?)
+}
+
+#[handler(GET)]
+async fn me(_: &App, user: User) -> Result<Response<Body>, Error> {
+    let name = user.name.unwrap_or_default();
+    Ok(Response::new(Body::from(Bytes::from(format!(
+        "{name} ({})",
+        user.id
+    )))))
+}
+
+#[derive(Default)]
+struct MockClient {
+    sent: Mutex<Vec<(String, String)>>,
+}
+
+#[async_trait]
+impl HttpClient for MockClient {
+    async fn send(
+        &self,
+        req: Request<Bytes>,
+    ) -> Result<Response<Bytes>, Box<dyn StdError + Send + Sync>> {
+        let uri = req.uri().to_string();
+        let body = String::from_utf8(req.body().to_vec())?;
+        self.sent.lock().unwrap().push((uri.clone(), body));
+
+        let body = match uri.as_str() {
+            "https://github.com/login/oauth/access_token" => {
+                r#"{"access_token": "token", "token_type": "bearer"}"#
+            }
+            "https://api.github.com/user" => r#"{"id": 42, "login": "octocat"}"#,
+            _ => return Ok(Response::builder().status(404).body(Bytes::new())?),
+        };
+        Ok(Response::new(Bytes::from(body)))
+    }
+}
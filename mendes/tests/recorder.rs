@@ -0,0 +1,102 @@
+#![cfg(all(feature = "dev", feature = "body-util"))]
+
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use mendes::dev::Recorder;
+use mendes::html::Markup;
+use mendes::http::header::{AUTHORIZATION, CONTENT_TYPE};
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::{handler, route, Application, Body, Context, Error};
+
+#[tokio::test]
+async fn test_record() {
+    let app = Arc::new(App {
+        recorder: Recorder::new(2),
+    });
+
+    let req = Request::post("https://example.com/login?next=/home&password=oops")
+        .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+        .header(AUTHORIZATION, "Bearer secret")
+        .body(Body::from("user=jane&password=hunter2"))
+        .unwrap();
+    let rsp = handle(&app, req).await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(body(rsp).await, "welcome jane");
+
+    let exchanges = app.recorder.exchanges();
+    let exchange = &exchanges[0];
+    assert_eq!(exchange.uri, "/login?next=/home&password=[redacted]");
+    assert_eq!(exchange.request_headers[AUTHORIZATION], "[redacted]");
+    let request = exchange.request_body();
+    assert!(request.complete);
+    assert_eq!(request.data, "user=jane&password=[redacted]");
+    assert_eq!(exchange.response_body().data, "welcome jane");
+
+    for _ in 0..2 {
+        let req = Request::get("https://example.com/missing")
+            .body(Body::empty())
+            .unwrap();
+        handle(&app, req).await;
+    }
+    let exchanges = app.recorder.exchanges();
+    assert_eq!(exchanges.len(), 2);
+    assert_eq!(exchanges[0].status, StatusCode::NOT_FOUND);
+
+    let req = Request::get("https://example.com/__requests")
+        .body(Body::empty())
+        .unwrap();
+    let page = body(handle(&app, req).await).await;
+    assert!(page.contains("#3 GET /missing"), "{page}");
+    assert_eq!(app.recorder.exchanges().len(), 2);
+}
+
+async fn handle(app: &Arc<App>, req: Request<Body>) -> Response<Body> {
+    App::handle(Context::new(app.clone(), req)).await
+}
+
+async fn body(rsp: Response<Body>) -> String {
+    let body = rsp.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+struct App {
+    recorder: Recorder,
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = Body;
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(cx: Context<Self>) -> Response<Self::ResponseBody> {
+        let app = cx.app.clone();
+        app.recorder
+            .handle(cx, |mut cx| async move {
+                route!(match cx.path() {
+                    Some("login") => login,
+                    Some("__requests") => requests,
+                })
+            })
+            .await
+    }
+}
+
+#[handler(POST)]
+async fn login(_: &App, req: &Parts, body: Body) -> Result<Response<Body>, Error> {
+    let form = App::body_bytes(req, body, 1024).await?;
+    let user = form.split(|&b| b == b'&').next().unwrap_or_default();
+    let name = String::from_utf8_lossy(&user[5..]);
+    Ok(Response::new(Body::from(Bytes::from(format!(
+        "welcome {name}"
+    )))))
+}
+
+#[handler(GET)]
+async fn requests(app: &App) -> Result<Markup, Error> {
+    Ok(app.recorder.inspect())
+}
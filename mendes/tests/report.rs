@@ -0,0 +1,98 @@
+#![cfg(feature = "report")]
+
+use std::io;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::report::{AppWithReport, Report};
+use mendes::{handler, route, Application, Context};
+
+#[tokio::test]
+async fn test_internal_error() {
+    let rsp = handle(path_request("/broken")).await;
+    assert_eq!(rsp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(rsp.into_body(), "something went wrong");
+}
+
+#[tokio::test]
+async fn test_mendes_error() {
+    let rsp = handle(path_request("/foo")).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(rsp.into_body(), "no matching routes");
+
+    let rsp = handle(path_request("/missing")).await;
+    assert_eq!(rsp.status(), StatusCode::NOT_FOUND);
+    assert_eq!(rsp.into_body(), "no such thing");
+}
+
+#[test]
+fn test_chain() {
+    let report = Report::from(Outer(io::Error::new(io::ErrorKind::Other, "disk on fire")));
+    assert_eq!(report.to_string(), "unable to load");
+    assert_eq!(format!("{report:#}"), "unable to load: disk on fire");
+    assert_eq!(report.chain().count(), 2);
+}
+
+#[derive(Debug)]
+struct Outer(io::Error);
+
+impl std::fmt::Display for Outer {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str("unable to load")
+    }
+}
+
+impl std::error::Error for Outer {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.0)
+    }
+}
+
+fn path_request(path: &str) -> Request<()> {
+    Request::builder()
+        .uri(format!("https://example.com{path}"))
+        .body(())
+        .unwrap()
+}
+
+async fn handle(req: Request<()>) -> Response<String> {
+    App::handle(Context::new(Arc::new(App {}), req)).await
+}
+
+struct App {}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = String;
+    type Error = Report;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        route!(match cx.path() {
+            Some("broken") => broken,
+            Some("missing") => missing,
+        })
+    }
+}
+
+impl AppWithReport for App {
+    fn internal_error(&self, _: &Report, _: &Parts) -> Response<String> {
+        Response::builder()
+            .status(StatusCode::INTERNAL_SERVER_ERROR)
+            .body("something went wrong".into())
+            .unwrap()
+    }
+}
+
+#[handler(GET)]
+async fn broken(_: &App) -> Result<Response<String>, Report> {
+    let _ = std::fs::read("/definitely/not/here")?;
+    unreachable!()
+}
+
+#[handler(GET)]
+async fn missing(_: &App) -> Result<Response<String>, Report> {
+    Err(mendes::Error::not_found("no such thing").into())
+}
@@ -0,0 +1,113 @@
+#![cfg(all(feature = "services", feature = "body-util"))]
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use mendes::application::ErrorKind;
+use mendes::http::request::Parts;
+use mendes::http::{Request, Response, StatusCode};
+use mendes::services::{resolve, Provide, Services};
+use mendes::{handler, route, Application, Body, Context, Error};
+
+#[tokio::test]
+async fn test_provide() {
+    let app = App::new();
+    let rsp = handle(&app, "/users").await;
+    assert_eq!(rsp.status(), StatusCode::OK);
+    assert_eq!(body(rsp).await, "users from pool 1");
+    assert_eq!(app.pools.load(Ordering::SeqCst), 1);
+
+    let rsp = handle(&app, "/users").await;
+    assert_eq!(body(rsp).await, "users from pool 2");
+    assert_eq!(app.pools.load(Ordering::SeqCst), 2);
+}
+
+#[tokio::test]
+async fn test_missing() {
+    let app = App::new();
+    let rsp = handle(&app, "/missing").await;
+    assert_eq!(rsp.status(), StatusCode::INTERNAL_SERVER_ERROR);
+    assert_eq!(body(rsp).await, "request-scoped service missing");
+}
+
+async fn handle(app: &Arc<App>, path: &str) -> Response<Body> {
+    let req = Request::builder()
+        .uri(format!("https://example.com{path}"))
+        .body(())
+        .unwrap();
+    App::handle(Context::new(app.clone(), req)).await
+}
+
+async fn body(rsp: Response<Body>) -> String {
+    let body = rsp.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+struct App {
+    services: Arc<Services<App>>,
+    pools: AtomicUsize,
+}
+
+impl App {
+    fn new() -> Arc<Self> {
+        let services = Services::new()
+            .register(|app: &Arc<App>, _: &Parts| {
+                Ok(Pool(app.pools.fetch_add(1, Ordering::SeqCst) + 1))
+            })
+            .register(|app: &Arc<App>, req: &Parts| {
+                Ok(Users {
+                    pool: resolve(app, req)?,
+                })
+            });
+
+        Arc::new(App {
+            services: Arc::new(services),
+            pools: AtomicUsize::new(0),
+        })
+    }
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(mut cx: Context<Self>) -> Response<Self::ResponseBody> {
+        cx.app.services.clone().enter(&mut cx);
+        route!(match cx.path() {
+            Some("users") => users,
+            Some("missing") => missing,
+        })
+    }
+}
+
+#[handler(GET)]
+async fn users(
+    _: &App,
+    Provide(users): Provide<Users>,
+    Provide(pool): Provide<Pool>,
+) -> Result<Response<Body>, Error> {
+    // The pool was constructed once, for `Users`, and then reused from the request's cache
+    assert_eq!(users.pool.0, pool.0);
+    Ok(Response::new(Body::from(Bytes::from(format!(
+        "users from pool {}",
+        users.pool.0
+    )))))
+}
+
+#[handler(GET)]
+async fn missing(_: &App, Provide(_): Provide<String>) -> Result<Response<Body>, Error> {
+    Err(Error::from(ErrorKind::Other))
+}
+
+#[derive(Clone)]
+struct Pool(usize);
+
+#[derive(Clone)]
+struct Users {
+    pool: Pool,
+}
@@ -0,0 +1,180 @@
+#![cfg(all(feature = "singleflight", feature = "body-util"))]
+
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use bytes::Bytes;
+use http_body_util::BodyExt;
+use mendes::http::{Method, Request, Response};
+use mendes::singleflight::SingleFlight;
+use mendes::{handler, route, Application, Body, Context, Error};
+use tokio::task::{yield_now, JoinHandle};
+
+#[tokio::test]
+async fn test_coalesce() {
+    let app = app(0);
+    let (a, b) = tokio::join!(
+        handle(&app, Method::GET, "/report"),
+        handle(&app, Method::GET, "/report"),
+    );
+    assert_eq!(body(a).await, "report 1");
+    assert_eq!(body(b).await, "report 1");
+    assert_eq!(app.computed.load(Ordering::SeqCst), 1);
+
+    // Once the first request is done, the next one runs the handler again
+    let rsp = handle(&app, Method::GET, "/report").await;
+    assert_eq!(body(rsp).await, "report 2");
+
+    let (a, b) = tokio::join!(
+        handle(&app, Method::POST, "/report"),
+        handle(&app, Method::POST, "/report"),
+    );
+    assert_ne!(body(a).await, body(b).await);
+}
+
+#[tokio::test]
+async fn test_shared() {
+    let app = app(0);
+    let requests = (0..5).map(|_| spawn(&app, "/slow")).collect::<Vec<_>>();
+    started(&app, 1).await;
+    app.open.store(true, Ordering::SeqCst);
+    for request in requests {
+        assert_eq!(body(request.await.unwrap()).await, "slow 1");
+    }
+    assert_eq!(app.computed.load(Ordering::SeqCst), 1);
+
+    // Different keys don't share a response
+    let (a, b) = tokio::join!(
+        handle(&app, Method::GET, "/slow?a"),
+        handle(&app, Method::GET, "/slow?b"),
+    );
+    assert_ne!(body(a).await, body(b).await);
+}
+
+#[tokio::test]
+async fn test_cancelled() {
+    let app = app(0);
+    let leader = spawn(&app, "/slow");
+    started(&app, 1).await;
+    let follower = spawn(&app, "/slow");
+    for _ in 0..10 {
+        yield_now().await;
+    }
+
+    // The waiting request runs the handler itself
+    leader.abort();
+    assert!(leader.await.is_err_and(|e| e.is_cancelled()));
+    app.open.store(true, Ordering::SeqCst);
+    assert_eq!(body(follower.await.unwrap()).await, "slow 2");
+
+    // The key was removed, so the next request runs the handler again
+    let rsp = handle(&app, Method::GET, "/slow").await;
+    assert_eq!(body(rsp).await, "slow 3");
+}
+
+#[tokio::test]
+async fn test_panicked() {
+    let app = app(1);
+    let leader = spawn(&app, "/slow");
+    started(&app, 1).await;
+    let follower = spawn(&app, "/slow");
+    for _ in 0..10 {
+        yield_now().await;
+    }
+
+    app.open.store(true, Ordering::SeqCst);
+    assert!(leader.await.is_err_and(|e| e.is_panic()));
+    assert_eq!(body(follower.await.unwrap()).await, "slow 2");
+
+    let rsp = handle(&app, Method::GET, "/slow").await;
+    assert_eq!(body(rsp).await, "slow 3");
+}
+
+/// Create an `App` whose `panic_at`th `slow` request panics
+fn app(panic_at: usize) -> Arc<App> {
+    Arc::new(App {
+        single_flight: SingleFlight::new(),
+        computed: AtomicUsize::new(0),
+        open: AtomicBool::new(false),
+        panic_at,
+    })
+}
+
+fn spawn(app: &Arc<App>, path: &'static str) -> JoinHandle<Response<Body>> {
+    let app = app.clone();
+    tokio::spawn(async move { handle(&app, Method::GET, path).await })
+}
+
+/// Wait until a handler was called `n` times
+async fn started(app: &App, n: usize) {
+    while app.computed.load(Ordering::SeqCst) < n {
+        yield_now().await;
+    }
+}
+
+async fn handle(app: &Arc<App>, method: Method, path: &str) -> Response<Body> {
+    let req = Request::builder()
+        .method(method)
+        .uri(format!("https://example.com{path}"))
+        .body(())
+        .unwrap();
+    App::handle(Context::new(app.clone(), req)).await
+}
+
+async fn body(rsp: Response<Body>) -> String {
+    let body = rsp.into_body().collect().await.unwrap().to_bytes();
+    String::from_utf8(body.to_vec()).unwrap()
+}
+
+struct App {
+    single_flight: SingleFlight,
+    computed: AtomicUsize,
+    /// Whether `slow` requests may complete
+    open: AtomicBool,
+    panic_at: usize,
+}
+
+#[async_trait]
+impl Application for App {
+    type RequestBody = ();
+    type ResponseBody = Body;
+    type Error = Error;
+
+    async fn handle(cx: Context<Self>) -> Response<Self::ResponseBody> {
+        let app = cx.app.clone();
+        app.single_flight
+            .handle(cx, |mut cx| async move {
+                route!(match cx.path() {
+                    Some("report") => report,
+                    Some("slow") => slow,
+                })
+            })
+            .await
+    }
+}
+
+#[handler(GET, POST)]
+async fn report(app: &App) -> Result<Response<Body>, Error> {
+    let id = app.computed.fetch_add(1, Ordering::SeqCst) + 1;
+    // Give concurrent requests a chance to arrive while the report is computed
+    for _ in 0..10 {
+        tokio::task::yield_now().await;
+    }
+    Ok(Response::new(Body::from(Bytes::from(format!(
+        "report {id}"
+    )))))
+}
+
+#[handler(GET)]
+async fn slow(app: &App) -> Result<Response<Body>, Error> {
+    let id = app.computed.fetch_add(1, Ordering::SeqCst) + 1;
+    while !app.open.load(Ordering::SeqCst) {
+        yield_now().await;
+    }
+    if id == app.panic_at {
+        panic!("slow request failed");
+    }
+
+    Ok(Response::new(Body::from(Bytes::from(format!("slow {id}")))))
+}